@@ -0,0 +1,50 @@
+// Resolving 1M paths, half relative and half absolute: absolute_path, which reads the working
+// directory for each relative path, against a PathResolver reading it once per command, as
+// extract does, and the implementation they replaced, which also sent every path through a
+// lossy string. Run with `cargo bench`.
+use serval::utils::{PathResolver, absolute_path};
+use std::hint::black_box;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const NUM_PATHS: usize = 1_000_000;
+
+// The replaced implementation, the prefix trimmed on every platform so all do the same work
+fn reference_absolute_path(path: PathBuf) -> io::Result<PathBuf> {
+    let path_buf = if path.is_absolute() {
+        path
+    } else {
+        let path = path.strip_prefix(".").unwrap_or(&path);
+        std::env::current_dir()?.join(path)
+    };
+    Ok(Path::new(path_buf.to_string_lossy().trim_start_matches(r"\\?\")).to_path_buf())
+}
+
+fn time(paths: &[PathBuf], absolute: impl Fn(PathBuf) -> io::Result<PathBuf>) -> Duration {
+    let start = Instant::now();
+    for path in paths {
+        black_box(absolute(black_box(path.clone())).unwrap());
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let paths: Vec<PathBuf> = (0..NUM_PATHS)
+        .map(|i| {
+            let path = format!("Collection/DEP{:02}/IMG_{i:07}.JPG", i % 40);
+            if i % 2 == 0 {
+                PathBuf::from(path)
+            } else {
+                PathBuf::from(format!("/data/project/{path}"))
+            }
+        })
+        .collect();
+    let reference = time(&paths, reference_absolute_path);
+    let current = time(&paths, absolute_path);
+    let resolver = PathResolver::new().unwrap();
+    let resolved = time(&paths, |path| Ok(resolver.absolute(path)));
+    println!("reference absolute_path: {reference:?} for {NUM_PATHS} paths");
+    println!("absolute_path:           {current:?} for {NUM_PATHS} paths");
+    println!("PathResolver::absolute:  {resolved:?} for {NUM_PATHS} paths");
+}
@@ -1,7 +1,10 @@
 use anyhow::Result;
 use std::env;
 
-use serval::{tags::get_classifications, utils::ResourceType};
+use serval::{
+    tags::{ObserveSettings, get_classifications},
+    utils::{OnConflict, ResourceType},
+};
 
 fn main() -> Result<()> {
     let source_dir = env::current_dir()?;
@@ -9,8 +12,11 @@ fn main() -> Result<()> {
         source_dir.clone(),
         source_dir,
         ResourceType::Xmp,
-        false,
-        true,
+        ObserveSettings {
+            volunteer_mode: true,
+            on_conflict: OnConflict::Overwrite, // Volunteers re-run the check in place
+            ..Default::default()
+        },
     );
     Ok(())
 }
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use std::env;
 
-use serval::utils::copy_xmp;
+use serval::utils::{copy_xmp, exclude_output_dir};
 
 fn main() -> Result<()> {
     let source_dir = env::current_dir()?;
@@ -9,6 +9,7 @@ fn main() -> Result<()> {
     let mut output_dir = source_dir.clone();
     output_dir.push("xmp");
 
-    copy_xmp(source_dir, output_dir)?;
+    let excluded_dir = exclude_output_dir(&source_dir, &output_dir)?;
+    copy_xmp(source_dir, output_dir, excluded_dir.as_deref())?;
     Ok(())
 }
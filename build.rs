@@ -0,0 +1,61 @@
+// Build details reported by `serval info` and recorded with outputs, see src/info.rs
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+// Dependencies whose version matters for bug reports
+const KEY_DEPENDENCIES: &[&str] = &["polars", "xmp_toolkit", "rusqlite", "image", "zip"];
+
+// Version of each key dependency in Cargo.lock, "unknown" when there is no lock file
+fn locked_versions(lock: &str) -> Vec<String> {
+    KEY_DEPENDENCIES
+        .iter()
+        .map(|name| {
+            let version = lock
+                .split("[[package]]")
+                .find(|package| package.contains(&format!("\nname = \"{name}\"\n")))
+                .and_then(|package| {
+                    package
+                        .lines()
+                        .find_map(|line| line.strip_prefix("version = \""))
+                        .and_then(|version| version.strip_suffix('"'))
+                })
+                .unwrap_or("unknown");
+            format!("{name}={version}")
+        })
+        .collect()
+}
+
+fn main() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let lock_path = Path::new(&manifest_dir).join("Cargo.lock");
+    println!("cargo:rerun-if-changed={}", lock_path.display());
+    let lock = fs::read_to_string(&lock_path).unwrap_or_default();
+    println!(
+        "cargo:rustc-env=SERVAL_DEPENDENCIES={}",
+        locked_versions(&lock).join(";")
+    );
+
+    let mut features: Vec<String> = env::vars()
+        .filter_map(|(key, _)| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|feature| feature.to_lowercase().replace('_', "-"))
+        })
+        .collect();
+    features.sort();
+    println!("cargo:rustc-env=SERVAL_FEATURES={}", features.join(","));
+    println!(
+        "cargo:rustc-env=SERVAL_PROFILE={}",
+        env::var("PROFILE").unwrap_or_default()
+    );
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|version| version.trim().trim_start_matches("rustc ").to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=SERVAL_RUSTC={rustc_version}");
+}
@@ -0,0 +1,946 @@
+use crate::schema::{
+    DATETIME_COLUMN, DEPLOYMENT_ID_COLUMN, LATITUDE_COLUMN, LEGACY_DATETIME_COLUMN,
+    LONGITUDE_COLUMN, OutputKind, PATH_COLUMN, write_output_schema,
+};
+use crate::tags::{Prompt, prompt_deployment_path_index};
+use crate::utils::{
+    ColumnMap, TagType, check_csv_columns, csv_projection_columns, csv_writer,
+    deployment_from_path, reject_duplicate_csv_columns,
+};
+use chrono::{Local, NaiveDate, NaiveDateTime};
+use polars::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DEPLOYMENT_START_COLUMN: &str = "deploymentStart";
+const DEPLOYMENT_END_COLUMN: &str = "deploymentEnd";
+
+// ISO dates or datetimes (camtrap-dp writes 2023-05-01T08:00:00+08:00), only the date counts
+fn parse_day(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value.get(..10)?, "%Y-%m-%d").ok()
+}
+
+// Calendar days of each record as (deployment, day, target), for rows with a parsed time
+fn record_days(
+    df: &DataFrame,
+    target: TagType,
+) -> anyhow::Result<Vec<(String, NaiveDate, Option<String>)>> {
+    let df = df
+        .clone()
+        .lazy()
+        .select([
+            col("deployment").cast(DataType::String),
+            col("time").dt().strftime("%Y-%m-%d").alias("day"),
+            col(target.col_name()).cast(DataType::String),
+        ])
+        .collect()?;
+    Ok(df
+        .column("deployment")?
+        .str()?
+        .iter()
+        .zip(df.column("day")?.str()?.iter())
+        .zip(df.column(target.col_name())?.str()?.iter())
+        .filter_map(|((deployment, day), value)| {
+            Some((
+                deployment?.to_string(),
+                parse_day(day?)?,
+                value.map(str::to_string),
+            ))
+        })
+        .collect())
+}
+
+// Active days per deployment, from deploymentStart/deploymentEnd of the deploy table
+// when available, otherwise from the first and last record
+fn deployment_ranges(
+    df_records: &DataFrame,
+    deploy_table: Option<&Path>,
+    target: TagType,
+) -> anyhow::Result<BTreeMap<String, (NaiveDate, NaiveDate)>> {
+    let mut ranges: BTreeMap<String, (NaiveDate, NaiveDate)> = BTreeMap::new();
+    for (deployment, day, _) in record_days(df_records, target)? {
+        let range = ranges.entry(deployment).or_insert((day, day));
+        range.0 = range.0.min(day);
+        range.1 = range.1.max(day);
+    }
+    let num_from_records = ranges.len();
+
+    if let Some(deploy_table) = deploy_table {
+        let deploy_df = CsvReadOptions::default()
+            .with_infer_schema_length(Some(0))
+            .with_columns(csv_projection_columns(&[
+                DEPLOYMENT_ID_COLUMN,
+                DEPLOYMENT_START_COLUMN,
+                DEPLOYMENT_END_COLUMN,
+            ]))
+            .try_into_reader_with_file_path(Some(deploy_table.to_path_buf()))
+            .and_then(|reader| reader.finish())
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Failed to read {DEPLOYMENT_ID_COLUMN}, {DEPLOYMENT_START_COLUMN} and {DEPLOYMENT_END_COLUMN} from {}: {e}",
+                    deploy_table.display()
+                )
+            })?;
+        reject_duplicate_csv_columns(&deploy_df)?;
+        let mut from_table: BTreeSet<String> = BTreeSet::new();
+        for ((deployment, start), end) in deploy_df
+            .column(DEPLOYMENT_ID_COLUMN)?
+            .str()?
+            .iter()
+            .zip(deploy_df.column(DEPLOYMENT_START_COLUMN)?.str()?.iter())
+            .zip(deploy_df.column(DEPLOYMENT_END_COLUMN)?.str()?.iter())
+        {
+            let (Some(deployment), Some(start), Some(end)) = (
+                deployment,
+                start.and_then(parse_day),
+                end.and_then(parse_day),
+            ) else {
+                continue;
+            };
+            if end < start {
+                return Err(anyhow::anyhow!(
+                    "{deployment}: {DEPLOYMENT_END_COLUMN} is before {DEPLOYMENT_START_COLUMN}"
+                ));
+            }
+            ranges.insert(deployment.to_string(), (start, end));
+            from_table.insert(deployment.to_string());
+        }
+        println!(
+            "Effort: {} deployment range(s) from {}",
+            from_table.len(),
+            deploy_table.display()
+        );
+        let fallback: Vec<&str> = ranges
+            .keys()
+            .filter(|deployment| !from_table.contains(*deployment))
+            .map(String::as_str)
+            .collect();
+        if !fallback.is_empty() {
+            println!(
+                "Warning: {} deployment(s) without a range in the deploy table, using first/last record: {}",
+                fallback.len(),
+                fallback.join(", ")
+            );
+        }
+    } else {
+        println!("Effort: {num_from_records} deployment range(s) from first/last record");
+    }
+    Ok(ranges)
+}
+
+fn write_csv(output_dir: &Path, filename: &str, df: &mut DataFrame) -> anyhow::Result<()> {
+    let mut file = fs::File::create(output_dir.join(filename))?;
+    csv_writer(&mut file).finish(df)?;
+    println!("Saved to {}", output_dir.join(filename).to_string_lossy());
+    Ok(())
+}
+
+// Species accumulation: cumulative camera-trap-days vs cumulative unique targets among the
+// independent records, one row per calendar day or every `step` trap-days
+#[allow(clippy::too_many_arguments)]
+pub fn write_species_accumulation(
+    df_records: &DataFrame,
+    df_independent: &DataFrame,
+    target: TagType,
+    deploy_table: Option<PathBuf>,
+    step: u32,
+    per_deployment: bool,
+    output_dir: &Path,
+    output_suffix: &str,
+) -> anyhow::Result<()> {
+    let ranges = deployment_ranges(df_records, deploy_table.as_deref(), target)?;
+    let (Some(first_day), Some(last_day)) = (
+        ranges.values().map(|range| range.0).min(),
+        ranges.values().map(|range| range.1).max(),
+    ) else {
+        return Err(anyhow::anyhow!("No dated records for species accumulation"));
+    };
+    let detections = record_days(df_independent, target)?;
+    let num_outside = detections
+        .iter()
+        .filter(|(deployment, day, _)| {
+            ranges
+                .get(deployment)
+                .is_none_or(|(start, end)| day < start || day > end)
+        })
+        .count();
+    if num_outside > 0 {
+        println!(
+            "Warning: {num_outside} independent record(s) outside their deployment range, check deploymentStart/deploymentEnd"
+        );
+    }
+
+    // First detection day of each target over all deployments
+    let mut first_detection: BTreeMap<&str, NaiveDate> = BTreeMap::new();
+    for (_, day, value) in &detections {
+        if let Some(value) = value {
+            let first = first_detection.entry(value).or_insert(*day);
+            *first = (*first).min(*day);
+        }
+    }
+    let mut new_per_day: BTreeMap<NaiveDate, u32> = BTreeMap::new();
+    for day in first_detection.values() {
+        *new_per_day.entry(*day).or_default() += 1;
+    }
+
+    let mut dates: Vec<String> = Vec::new();
+    let mut trap_days: Vec<u32> = Vec::new();
+    let mut cumulative_trap_days: Vec<u32> = Vec::new();
+    let mut cumulative_richness: Vec<u32> = Vec::new();
+    let (mut effort, mut richness, mut next_row_at, mut last_row_effort) = (0, 0, step, 0);
+    for day in first_day.iter_days().take_while(|day| *day <= last_day) {
+        let active = ranges
+            .values()
+            .filter(|(start, end)| *start <= day && day <= *end)
+            .count() as u32;
+        effort += active;
+        richness += new_per_day.get(&day).copied().unwrap_or_default();
+        if step > 1 && effort < next_row_at && day != last_day {
+            continue;
+        }
+        while next_row_at <= effort {
+            next_row_at += step;
+        }
+        dates.push(day.format("%Y-%m-%d").to_string());
+        // Trap-days since the previous row
+        trap_days.push(effort - last_row_effort);
+        last_row_effort = effort;
+        cumulative_trap_days.push(effort);
+        cumulative_richness.push(richness);
+    }
+    println!(
+        "Species accumulation: {richness} {} over {effort} trap-days ({} deployments)",
+        target.col_name(),
+        ranges.len()
+    );
+    let mut df_accumulation = df!(
+        "date" => dates,
+        "trap_days" => trap_days,
+        "cumulative_trap_days" => cumulative_trap_days,
+        "cumulative_richness" => cumulative_richness,
+    )?;
+    write_csv(
+        output_dir,
+        &format!("accumulation{output_suffix}"),
+        &mut df_accumulation,
+    )?;
+
+    if per_deployment {
+        let mut deployments: Vec<&str> = Vec::new();
+        let mut dates: Vec<String> = Vec::new();
+        let mut cumulative_trap_days: Vec<u32> = Vec::new();
+        let mut cumulative_richness: Vec<u32> = Vec::new();
+        for (deployment, (start, end)) in &ranges {
+            let mut detected_by_day: BTreeMap<NaiveDate, BTreeSet<&str>> = BTreeMap::new();
+            for (_, day, value) in detections.iter().filter(|row| &row.0 == deployment) {
+                if let Some(value) = value {
+                    detected_by_day.entry(*day).or_default().insert(value);
+                }
+            }
+            let mut seen: BTreeSet<&str> = BTreeSet::new();
+            for (index, day) in start.iter_days().take_while(|day| day <= end).enumerate() {
+                if let Some(detected) = detected_by_day.get(&day) {
+                    seen.extend(detected);
+                }
+                let effort = index as u32 + 1;
+                if !effort.is_multiple_of(step) && day != *end {
+                    continue;
+                }
+                deployments.push(deployment);
+                dates.push(day.format("%Y-%m-%d").to_string());
+                cumulative_trap_days.push(effort);
+                cumulative_richness.push(seen.len() as u32);
+            }
+        }
+        let mut df_by_deployment = df!(
+            "deployment" => deployments,
+            "date" => dates,
+            "cumulative_trap_days" => cumulative_trap_days,
+            "cumulative_richness" => cumulative_richness,
+        )?;
+        write_csv(
+            output_dir,
+            &format!("accumulation_by_deployment{output_suffix}"),
+            &mut df_by_deployment,
+        )?;
+    }
+    Ok(())
+}
+
+// Time-of-day values of two species for activity overlap (e.g. the R overlap package),
+// with a binned overlap coefficient (sum of hourly minima of the two densities) alongside
+pub fn write_activity_overlap(
+    df_independent: &DataFrame,
+    target: TagType,
+    species_pair: &str,
+    output_dir: &Path,
+    output_suffix: &str,
+) -> anyhow::Result<()> {
+    let pair: Vec<&str> = species_pair.split(',').map(str::trim).collect();
+    let [species_a, species_b] = pair[..] else {
+        return Err(anyhow::anyhow!(
+            "--overlap expects two comma separated names, got '{species_pair}'"
+        ));
+    };
+
+    let df = df_independent
+        .clone()
+        .lazy()
+        .select([
+            col(target.col_name()).cast(DataType::String),
+            (col("time").dt().hour().cast(DataType::Float64)
+                + col("time").dt().minute().cast(DataType::Float64) / lit(60.0)
+                + col("time").dt().second().cast(DataType::Float64) / lit(3600.0))
+            .alias("clock_time_decimal_hours"),
+        ])
+        .collect()?;
+    let mut times: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+    for (value, hours) in df
+        .column(target.col_name())?
+        .str()?
+        .iter()
+        .zip(df.column("clock_time_decimal_hours")?.f64()?.iter())
+    {
+        if let (Some(value), Some(hours)) = (value, hours) {
+            times.entry(value).or_default().push(hours);
+        }
+    }
+    for name in [species_a, species_b] {
+        if !times.contains_key(name) {
+            return Err(anyhow::anyhow!(
+                "'{name}' has no independent records, available: {}",
+                times.keys().copied().collect::<Vec<_>>().join(", ")
+            ));
+        }
+    }
+
+    let hourly_density = |values: &[f64]| {
+        let mut bins = [0.0; 24];
+        for hours in values {
+            bins[(*hours as usize).min(23)] += 1.0;
+        }
+        bins.map(|count| count / values.len() as f64)
+    };
+    let density_a = hourly_density(&times[species_a]);
+    let density_b = hourly_density(&times[species_b]);
+    let overlap: f64 = density_a.iter().zip(density_b).map(|(a, b)| a.min(b)).sum();
+    println!(
+        "Activity overlap (binned, hourly) of {species_a} ({}) and {species_b} ({}): {overlap:.3}",
+        times[species_a].len(),
+        times[species_b].len()
+    );
+
+    let (names, hours): (Vec<&str>, Vec<f64>) = [species_a, species_b]
+        .iter()
+        .flat_map(|name| times[name].iter().map(move |hours| (*name, *hours)))
+        .unzip();
+    let mut df_overlap = df!(
+        target.col_name() => names,
+        "clock_time_decimal_hours" => hours,
+    )?
+    .lazy()
+    .with_column(lit(overlap).alias("overlap_binned"))
+    .collect()?;
+    write_csv(
+        output_dir,
+        &format!("overlap{output_suffix}"),
+        &mut df_overlap,
+    )
+}
+
+const AGE_COLUMN: &str = "age";
+
+// Individual, sex and age of one tagged row
+type Demographic = (String, String, String);
+
+// Single value of a set, "mixed" when several were tagged and "unknown" when none
+fn resolve_value(values: Option<&BTreeSet<String>>, mixed: &str) -> String {
+    match values {
+        Some(values) if values.len() == 1 => values.iter().next().cloned().unwrap_or_default(),
+        Some(values) if values.len() > 1 => mixed.to_string(),
+        _ => "unknown".to_string(),
+    }
+}
+
+fn string_values(df: &DataFrame, name: &str) -> anyhow::Result<Vec<String>> {
+    Ok(df
+        .column(name)?
+        .cast(&DataType::String)?
+        .str()?
+        .iter()
+        .map(|value| value.unwrap_or_default().trim().to_string())
+        .collect())
+}
+
+// Distinct individuals by sex and age class per deployment and overall, over the independent
+// records that carry an individual. Sex and age come from the tags of those records.
+pub fn write_demographics(
+    df_records: &DataFrame,
+    df_independent: &DataFrame,
+    target: TagType,
+    output_dir: &Path,
+    output_suffix: &str,
+) -> anyhow::Result<()> {
+    let individual_col = TagType::Individual.col_name();
+    let sex_col = TagType::Sex.col_name();
+    for name in [individual_col, sex_col] {
+        if df_records.column(name).is_err() {
+            return Err(anyhow::anyhow!(
+                "--demographics needs the {name} column in the input CSV"
+            ));
+        }
+    }
+    let has_age = df_records.column(AGE_COLUMN).is_ok();
+    if !has_age {
+        println!("Note: no {AGE_COLUMN} column, age classes are reported as unknown");
+    }
+
+    // (path, target) -> (individual, sex, age) of the tagged rows
+    let mut tagged: BTreeMap<(String, String), Vec<Demographic>> = BTreeMap::new();
+    let paths = string_values(df_records, PATH_COLUMN)?;
+    let targets = string_values(df_records, target.col_name())?;
+    let individuals = string_values(df_records, individual_col)?;
+    let sexes = string_values(df_records, sex_col)?;
+    let ages = if has_age {
+        string_values(df_records, AGE_COLUMN)?
+    } else {
+        vec![String::new(); df_records.height()]
+    };
+    for ((((path, target_value), individual), sex), age) in paths
+        .into_iter()
+        .zip(targets)
+        .zip(individuals)
+        .zip(sexes)
+        .zip(ages)
+    {
+        if !individual.is_empty() {
+            tagged
+                .entry((path, target_value))
+                .or_default()
+                .push((individual, sex, age));
+        }
+    }
+
+    let mut individual_sexes: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut individual_ages: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut deployment_ages: BTreeMap<(String, String), BTreeSet<String>> = BTreeMap::new();
+    for ((path, deployment), target_value) in string_values(df_independent, PATH_COLUMN)?
+        .into_iter()
+        .zip(string_values(df_independent, "deployment")?)
+        .zip(string_values(df_independent, target.col_name())?)
+    {
+        let Some(rows) = tagged.get(&(path, target_value)) else {
+            continue;
+        };
+        for (individual, sex, age) in rows {
+            let sexes = individual_sexes.entry(individual.clone()).or_default();
+            let ages = individual_ages.entry(individual.clone()).or_default();
+            let deployment_ages = deployment_ages
+                .entry((deployment.clone(), individual.clone()))
+                .or_default();
+            if !sex.is_empty() {
+                sexes.insert(sex.clone());
+            }
+            if !age.is_empty() {
+                ages.insert(age.clone());
+                deployment_ages.insert(age.clone());
+            }
+        }
+    }
+    if individual_sexes.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No independent records with an individual, nothing to summarize"
+        ));
+    }
+
+    // Conflicting sex tags are a data-quality issue, reported rather than guessed
+    let conflicts: Vec<(&String, &BTreeSet<String>)> = individual_sexes
+        .iter()
+        .filter(|(_, sexes)| sexes.len() > 1)
+        .collect();
+    if !conflicts.is_empty() {
+        println!(
+            "Warning: {} individual(s) with conflicting sex tags, counted as conflicting:",
+            conflicts.len()
+        );
+        for (individual, sexes) in &conflicts {
+            println!(
+                "  {individual}: {}",
+                sexes.iter().cloned().collect::<Vec<_>>().join(", ")
+            );
+        }
+    }
+    let sex_of: BTreeMap<&String, String> = individual_sexes
+        .iter()
+        .map(|(individual, sexes)| (individual, resolve_value(Some(sexes), "conflicting")))
+        .collect();
+
+    let mut by_deployment: BTreeMap<(&str, &str, String), u32> = BTreeMap::new();
+    for ((deployment, individual), ages) in &deployment_ages {
+        *by_deployment
+            .entry((
+                deployment,
+                &sex_of[individual],
+                resolve_value(Some(ages), "mixed"),
+            ))
+            .or_default() += 1;
+    }
+    let mut overall: BTreeMap<(&str, String), u32> = BTreeMap::new();
+    for (individual, sex) in &sex_of {
+        *overall
+            .entry((
+                sex,
+                resolve_value(individual_ages.get(*individual), "mixed"),
+            ))
+            .or_default() += 1;
+    }
+
+    let mut df_by_deployment = df!(
+        "deployment" => by_deployment.keys().map(|key| key.0).collect::<Vec<_>>(),
+        "sex" => by_deployment.keys().map(|key| key.1).collect::<Vec<_>>(),
+        "age" => by_deployment.keys().map(|key| key.2.as_str()).collect::<Vec<_>>(),
+        "individuals" => by_deployment.values().copied().collect::<Vec<_>>(),
+    )?;
+    let mut df_overall = df!(
+        "sex" => overall.keys().map(|key| key.0).collect::<Vec<_>>(),
+        "age" => overall.keys().map(|key| key.1.as_str()).collect::<Vec<_>>(),
+        "individuals" => overall.values().copied().collect::<Vec<_>>(),
+    )?;
+    println!("{df_overall}");
+    let by_deployment_filename = format!("demographics_by_deployment{output_suffix}");
+    write_csv(output_dir, &by_deployment_filename, &mut df_by_deployment)?;
+    write_output_schema(
+        &output_dir.join(by_deployment_filename),
+        OutputKind::DemographicsByDeployment,
+        &df_by_deployment,
+    )?;
+    let overall_filename = format!("demographics{output_suffix}");
+    write_csv(output_dir, &overall_filename, &mut df_overall)?;
+    write_output_schema(
+        &output_dir.join(overall_filename),
+        OutputKind::Demographics,
+        &df_overall,
+    )?;
+    Ok(())
+}
+
+// Records after (or before) the last (first) long gap are only trimmed when they are a
+// short burst, e.g. retrieval shots weeks after the battery died
+const TERMINAL_BURST_SECONDS: i64 = 24 * 3600;
+
+// Activity window of one deployment from its sorted record times
+struct DeploymentActivity {
+    start: NaiveDateTime,
+    end: NaiveDateTime,
+    records: usize,
+    trimmed: usize,
+    ignored: usize,
+    trimming: Vec<String>,
+}
+
+fn gap_days(from: NaiveDateTime, to: NaiveDateTime) -> String {
+    format!("{:.1}d", (to - from).num_seconds() as f64 / 86400.0)
+}
+
+fn infer_activity(
+    times: &[NaiveDateTime],
+    ignored: usize,
+    gap_threshold: Option<chrono::Duration>,
+) -> DeploymentActivity {
+    let (mut first, mut last) = (0, times.len() - 1);
+    let mut trimming = Vec::new();
+    if let Some(threshold) = gap_threshold {
+        let burst = chrono::Duration::seconds(TERMINAL_BURST_SECONDS);
+        let long_gaps: Vec<usize> = times
+            .windows(2)
+            .enumerate()
+            .filter(|(_, pair)| pair[1] - pair[0] > threshold)
+            .map(|(index, _)| index)
+            .collect();
+        if let Some(&gap) = long_gaps.last()
+            && times[last] - times[gap + 1] < burst
+        {
+            trimming.push(format!(
+                "end trimmed: {} record(s) after a {} gap",
+                last - gap,
+                gap_days(times[gap], times[gap + 1])
+            ));
+            last = gap;
+        }
+        if let Some(&gap) = long_gaps.first()
+            && gap < last
+            && times[gap] - times[first] < burst
+        {
+            trimming.push(format!(
+                "start trimmed: {} record(s) before a {} gap",
+                gap + 1,
+                gap_days(times[gap], times[gap + 1])
+            ));
+            first = gap + 1;
+        }
+    }
+    DeploymentActivity {
+        start: times[first],
+        end: times[last],
+        records: times.len(),
+        trimmed: times.len() - (last - first + 1),
+        ignored,
+        trimming,
+    }
+}
+
+// Infer deploymentStart/deploymentEnd of each deployment from its first and last record,
+// optionally trimming terminal bursts separated by a gap longer than `gap_threshold`.
+// The output can be passed wherever a deploy table is accepted.
+pub fn infer_deployment_activity(
+    csv_path: PathBuf,
+    output_dir: PathBuf,
+    gap_threshold: Option<std::time::Duration>,
+    column_map: ColumnMap,
+) -> anyhow::Result<()> {
+    check_csv_columns(&csv_path, &[PATH_COLUMN, DATETIME_COLUMN], &column_map)?;
+    let mut df = CsvReadOptions::default()
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(csv_path))?
+        .finish()?;
+    reject_duplicate_csv_columns(&df)?;
+    column_map.apply(&mut df)?;
+    // Adapts to old tags.csv
+    if df.get_column_index(DATETIME_COLUMN).is_none() {
+        df.rename(LEGACY_DATETIME_COLUMN, DATETIME_COLUMN.into())?;
+    }
+
+    let paths = df.column(PATH_COLUMN)?.str()?;
+    let deployments: Vec<Option<String>> = match df.column("deployment") {
+        Ok(column) => column
+            .str()?
+            .iter()
+            .map(|value| value.filter(|value| !value.is_empty()).map(str::to_string))
+            .collect(),
+        Err(_) => {
+            let path_sample = paths
+                .iter()
+                .flatten()
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("No path values in the CSV"))?
+                .to_string();
+            let deploy_path_index = prompt_deployment_path_index(&mut Prompt::new()?, path_sample)?;
+            paths
+                .iter()
+                .map(|path| {
+                    path.and_then(|path| {
+                        deployment_from_path(Path::new(path), deploy_path_index).ok()
+                    })
+                })
+                .collect()
+        }
+    };
+
+    // Unparseable and future datetimes (clock not set, wrong year) don't count as activity
+    let now = Local::now().naive_local();
+    let mut times: BTreeMap<String, Vec<NaiveDateTime>> = BTreeMap::new();
+    let mut ignored: BTreeMap<String, usize> = BTreeMap::new();
+    for (deployment, datetime) in deployments
+        .into_iter()
+        .zip(df.column(DATETIME_COLUMN)?.str()?.iter())
+    {
+        let Some(deployment) = deployment else {
+            continue;
+        };
+        let datetime = datetime.map(str::trim).and_then(|value| {
+            NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+                .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+                .ok()
+        });
+        match datetime.filter(|datetime| *datetime <= now) {
+            Some(datetime) => times.entry(deployment).or_default().push(datetime),
+            None => *ignored.entry(deployment).or_insert(0) += 1,
+        }
+    }
+    for deployment in ignored.keys() {
+        if !times.contains_key(deployment) {
+            println!("Warning: {deployment} has no valid record datetime, skipped");
+        }
+    }
+    if times.is_empty() {
+        return Err(anyhow::anyhow!("No records with a valid datetime"));
+    }
+
+    let gap_threshold = gap_threshold.map(chrono::Duration::from_std).transpose()?;
+    let activities: Vec<(String, DeploymentActivity)> = times
+        .into_iter()
+        .map(|(deployment, mut times)| {
+            times.sort_unstable();
+            let activity = infer_activity(
+                &times,
+                ignored.get(&deployment).copied().unwrap_or(0),
+                gap_threshold,
+            );
+            (deployment, activity)
+        })
+        .collect();
+
+    let mut df_activity = df!(
+        DEPLOYMENT_ID_COLUMN => activities.iter().map(|(deployment, _)| deployment.as_str()).collect::<Vec<_>>(),
+        DEPLOYMENT_START_COLUMN => activities.iter().map(|(_, activity)| activity.start.format("%Y-%m-%dT%H:%M:%S").to_string()).collect::<Vec<_>>(),
+        DEPLOYMENT_END_COLUMN => activities.iter().map(|(_, activity)| activity.end.format("%Y-%m-%dT%H:%M:%S").to_string()).collect::<Vec<_>>(),
+        "active_days" => activities.iter().map(|(_, activity)| ((activity.end.date() - activity.start.date()).num_days() + 1) as u32).collect::<Vec<_>>(),
+        "records" => activities.iter().map(|(_, activity)| activity.records as u32).collect::<Vec<_>>(),
+        "trimmed_records" => activities.iter().map(|(_, activity)| activity.trimmed as u32).collect::<Vec<_>>(),
+        "ignored_records" => activities.iter().map(|(_, activity)| activity.ignored as u32).collect::<Vec<_>>(),
+        "trimming" => activities.iter().map(|(_, activity)| activity.trimming.join("; ")).collect::<Vec<_>>(),
+    )?;
+    let num_trimmed = activities
+        .iter()
+        .filter(|(_, activity)| !activity.trimming.is_empty())
+        .count();
+    let num_ignored: usize = activities
+        .iter()
+        .map(|(_, activity)| activity.ignored)
+        .sum();
+    println!(
+        "Effort: {} deployment range(s) inferred from records, {num_trimmed} trimmed",
+        activities.len()
+    );
+    if num_ignored > 0 {
+        println!(
+            "Note: {num_ignored} record(s) with a missing, invalid or future datetime ignored"
+        );
+    }
+    fs::create_dir_all(&output_dir)?;
+    write_csv(&output_dir, "deployment_activity.csv", &mut df_activity)
+}
+
+// Valid WGS84 point of a latitude/longitude pair of strings
+fn parse_coordinate(latitude: &str, longitude: &str) -> Option<Result<(f64, f64), ()>> {
+    let (latitude, longitude) = (
+        latitude.parse::<f64>().ok()?,
+        longitude.parse::<f64>().ok()?,
+    );
+    if (-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude) {
+        Some(Ok((latitude, longitude)))
+    } else {
+        Some(Err(()))
+    }
+}
+
+// Coordinates by key column, invalid pairs (outside lat [-90,90] / lon [-180,180]) counted
+fn coordinates_by(
+    df: &DataFrame,
+    key: &str,
+    source: &str,
+) -> anyhow::Result<BTreeMap<String, (f64, f64)>> {
+    let mut coordinates = BTreeMap::new();
+    if df.column(LATITUDE_COLUMN).is_err() || df.column(LONGITUDE_COLUMN).is_err() {
+        return Ok(coordinates);
+    }
+    let mut num_invalid = 0;
+    for ((key, latitude), longitude) in string_values(df, key)?
+        .into_iter()
+        .zip(string_values(df, LATITUDE_COLUMN)?)
+        .zip(string_values(df, LONGITUDE_COLUMN)?)
+    {
+        match parse_coordinate(&latitude, &longitude) {
+            Some(Ok(coordinate)) => {
+                coordinates.insert(key, coordinate);
+            }
+            Some(Err(())) => num_invalid += 1,
+            None => {}
+        }
+    }
+    if num_invalid > 0 {
+        println!(
+            "Warning: {num_invalid} {source} row(s) with coordinates outside lat [-90,90] / lon [-180,180] ignored"
+        );
+    }
+    Ok(coordinates)
+}
+
+fn point_feature(
+    (latitude, longitude): (f64, f64),
+    properties: serde_json::Map<String, serde_json::Value>,
+) -> serde_json::Value {
+    // GeoJSON positions are [longitude, latitude]
+    serde_json::json!({
+        "type": "Feature",
+        "geometry": { "type": "Point", "coordinates": [longitude, latitude] },
+        "properties": properties,
+    })
+}
+
+fn write_feature_collection(
+    output_dir: &Path,
+    filename: &str,
+    features: Vec<serde_json::Value>,
+) -> anyhow::Result<()> {
+    let collection = serde_json::json!({ "type": "FeatureCollection", "features": features });
+    fs::write(
+        output_dir.join(filename),
+        serde_json::to_string_pretty(&collection)?,
+    )?;
+    println!("Saved to {}", output_dir.join(filename).to_string_lossy());
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+// GeoJSON points of the deployments, with independent counts per target and the record date
+// range, located by the deploy table (latitude/longitude) or else the mean of their record
+// coordinates. A second file has one point per independent record when records carry GPS.
+pub fn write_geojson(
+    df_tags: &DataFrame,
+    df_records: &DataFrame,
+    df_independent: &DataFrame,
+    id_col_name: &str,
+    target: TagType,
+    deploy_table: Option<&Path>,
+    output_dir: &Path,
+    output_suffix: &str,
+) -> anyhow::Result<()> {
+    let suffix = output_suffix.replace(".csv", ".geojson");
+    let record_coordinates = if df_tags.column(PATH_COLUMN).is_ok() {
+        coordinates_by(df_tags, PATH_COLUMN, "record")?
+    } else {
+        BTreeMap::new()
+    };
+    let table_coordinates = match deploy_table {
+        Some(deploy_table) => {
+            let deploy_df = CsvReadOptions::default()
+                .with_infer_schema_length(Some(0))
+                .try_into_reader_with_file_path(Some(deploy_table.to_path_buf()))?
+                .finish()?;
+            reject_duplicate_csv_columns(&deploy_df)?;
+            if deploy_df.column(DEPLOYMENT_ID_COLUMN).is_ok() {
+                coordinates_by(&deploy_df, DEPLOYMENT_ID_COLUMN, "deploy table")?
+            } else {
+                BTreeMap::new()
+            }
+        }
+        None => BTreeMap::new(),
+    };
+
+    // Date range over all records, independent counts per target
+    let df_times = df_records
+        .clone()
+        .lazy()
+        .select([
+            col("deployment").cast(DataType::String),
+            col("time").dt().strftime("%Y-%m-%d %H:%M:%S").alias("time"),
+        ])
+        .collect()?;
+    let mut ranges: BTreeMap<String, (String, String)> = BTreeMap::new();
+    for (deployment, time) in string_values(&df_times, "deployment")?
+        .into_iter()
+        .zip(string_values(&df_times, "time")?)
+    {
+        if deployment.is_empty() || time.is_empty() {
+            continue;
+        }
+        let range = ranges
+            .entry(deployment)
+            .or_insert_with(|| (time.clone(), time.clone()));
+        if time < range.0 {
+            range.0 = time.clone();
+        }
+        if time > range.1 {
+            range.1 = time;
+        }
+    }
+    let independent_paths = string_values(df_independent, id_col_name)?;
+    let independent_deployments = string_values(df_independent, "deployment")?;
+    let independent_targets = string_values(df_independent, target.col_name())?;
+    let mut counts: BTreeMap<&str, BTreeMap<&str, u32>> = BTreeMap::new();
+    let mut record_sums: BTreeMap<&str, (f64, f64, u32)> = BTreeMap::new();
+    for ((path, deployment), value) in independent_paths
+        .iter()
+        .zip(&independent_deployments)
+        .zip(&independent_targets)
+    {
+        *counts
+            .entry(deployment)
+            .or_default()
+            .entry(value)
+            .or_insert(0) += 1;
+        if let Some((latitude, longitude)) = record_coordinates.get(path) {
+            let sum = record_sums.entry(deployment).or_insert((0.0, 0.0, 0));
+            sum.0 += latitude;
+            sum.1 += longitude;
+            sum.2 += 1;
+        }
+    }
+
+    let mut features = Vec::new();
+    let mut missing = Vec::new();
+    for (deployment, (start, end)) in &ranges {
+        let (coordinate, source) = match table_coordinates.get(deployment) {
+            Some(coordinate) => (*coordinate, "deploy table"),
+            None => match record_sums.get(deployment.as_str()) {
+                Some((latitude, longitude, n)) => {
+                    let mean = |sum: f64| (sum / *n as f64 * 1e6).round() / 1e6;
+                    ((mean(*latitude), mean(*longitude)), "records")
+                }
+                None => {
+                    missing.push(deployment.as_str());
+                    continue;
+                }
+            },
+        };
+        let deployment_counts = counts.get(deployment.as_str());
+        let mut properties = serde_json::Map::new();
+        properties.insert("deployment".into(), deployment.as_str().into());
+        properties.insert("start".into(), start.as_str().into());
+        properties.insert("end".into(), end.as_str().into());
+        properties.insert(
+            "independent_records".into(),
+            deployment_counts
+                .map_or(0, |counts| counts.values().sum::<u32>())
+                .into(),
+        );
+        properties.insert(
+            format!("{}_counts", target.col_name()),
+            serde_json::Value::Object(
+                deployment_counts
+                    .into_iter()
+                    .flatten()
+                    .map(|(value, count)| (value.to_string(), (*count).into()))
+                    .collect(),
+            ),
+        );
+        properties.insert("coordinate_source".into(), source.into());
+        features.push(point_feature(coordinate, properties));
+    }
+    if !missing.is_empty() {
+        println!(
+            "Warning: {} deployment(s) without coordinates omitted from the GeoJSON: {}",
+            missing.len(),
+            missing.join(", ")
+        );
+    }
+    write_feature_collection(output_dir, &format!("deployments{suffix}"), features)?;
+
+    if record_coordinates.is_empty() {
+        return Ok(());
+    }
+    let df_independent_times = df_independent
+        .clone()
+        .lazy()
+        .select([col("time").dt().strftime("%Y-%m-%d %H:%M:%S")])
+        .collect()?;
+    let times = string_values(&df_independent_times, "time")?;
+    let record_features: Vec<serde_json::Value> = independent_paths
+        .iter()
+        .zip(&independent_deployments)
+        .zip(&independent_targets)
+        .zip(&times)
+        .filter_map(|(((path, deployment), value), time)| {
+            let coordinate = record_coordinates.get(path)?;
+            let mut properties = serde_json::Map::new();
+            properties.insert(id_col_name.into(), path.as_str().into());
+            properties.insert("deployment".into(), deployment.as_str().into());
+            properties.insert("time".into(), time.as_str().into());
+            properties.insert(target.col_name().into(), value.as_str().into());
+            Some(point_feature(*coordinate, properties))
+        })
+        .collect();
+    write_feature_collection(output_dir, &format!("records{suffix}"), record_features)
+}
@@ -0,0 +1,190 @@
+use crate::schema::{
+    DEPLOYMENT_COLUMN, FILE_KEY_COLUMN, FILENAME_COLUMN, MEDIA_PATH_COLUMN, PATH_COLUMN,
+};
+use crate::utils::csv_writer;
+use polars::prelude::*;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+const PATH_TOKEN_PREFIX: &str = "anon_";
+const PATH_TOKEN_HEX_LEN: usize = 16;
+const DEPLOYMENT_PSEUDONYM_PREFIX: &str = "site_";
+const KEYFILE_PREFIX: &str = "anonymize_key";
+// Columns replaced with tokens and the kind of their keyfile rows. Filenames carry deployment
+// names once aligned and file_key hashes the path unsalted, so they go too
+const TOKENIZED_COLUMNS: [(&str, &str); 4] = [
+    (PATH_COLUMN, "path"),
+    (MEDIA_PATH_COLUMN, "path"),
+    (FILENAME_COLUMN, "filename"),
+    (FILE_KEY_COLUMN, "file_key"),
+];
+
+// anon_ followed by the hex of a salted hash, as written by --anonymize-paths
+pub(crate) fn is_path_token(path: &str) -> bool {
+    path.strip_prefix(PATH_TOKEN_PREFIX).is_some_and(|hash| {
+        hash.len() == PATH_TOKEN_HEX_LEN && hash.bytes().all(|byte| byte.is_ascii_hexdigit())
+    })
+}
+
+/// Keyfile name for an output suffix, e.g. anonymize_key_project_xmp_20240301.csv
+pub fn keyfile_name(output_suffix: &str) -> String {
+    format!("{KEYFILE_PREFIX}{output_suffix}")
+}
+
+/// Paths, filenames and deployments of outputs shared outside the project, replaced with
+/// salted hashes (anon_3f9c...) and pseudonyms (site_007).
+///
+/// The salt is drawn once per run: within a run the same path always gets the same token,
+/// across runs tokens can't be matched. The keyfile maps every token back.
+pub struct Anonymizer {
+    salt: [u8; 32],
+    // token -> kind and original
+    tokens: BTreeMap<String, (&'static str, String)>,
+    // deployment -> pseudonym
+    deployments: HashMap<String, String>,
+}
+
+impl Anonymizer {
+    /// Fails only when the OS random number generator can't be read
+    pub fn new() -> anyhow::Result<Self> {
+        let mut salt = [0u8; 32];
+        getrandom::fill(&mut salt)
+            .map_err(|e| anyhow::anyhow!("Failed to draw a salt for --anonymize-paths: {e}"))?;
+        Ok(Self {
+            salt,
+            tokens: BTreeMap::new(),
+            deployments: HashMap::new(),
+        })
+    }
+
+    fn salted_hash(&self, value: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt);
+        hasher.update(value.as_bytes());
+        let hash = format!("{:x}", hasher.finalize());
+        hash[..PATH_TOKEN_HEX_LEN].to_string()
+    }
+
+    // Same value, same token in every column, the keyfile keeps the kind it was first seen as
+    fn token(&mut self, kind: &'static str, value: &str) -> String {
+        let token = format!("{PATH_TOKEN_PREFIX}{}", self.salted_hash(value));
+        self.tokens
+            .entry(token.clone())
+            .or_insert_with(|| (kind, value.to_string()));
+        token
+    }
+
+    // Numbered in salted hash order, so pseudonyms don't follow the names
+    fn assign_pseudonyms<'a>(&mut self, deployments: impl Iterator<Item = &'a str>) {
+        let mut new: Vec<(String, &str)> = deployments
+            .filter(|deployment| !self.deployments.contains_key(*deployment))
+            .map(|deployment| (self.salted_hash(deployment), deployment))
+            .collect();
+        new.sort();
+        new.dedup();
+        for (_, deployment) in new {
+            let pseudonym = format!(
+                "{DEPLOYMENT_PSEUDONYM_PREFIX}{:03}",
+                self.deployments.len() + 1
+            );
+            self.deployments.insert(deployment.to_string(), pseudonym);
+        }
+    }
+
+    /// Copy of `df` with its path, media_path, filename and file_key columns hashed and its
+    /// deployment column pseudonymized, whichever it has; empty values stay empty
+    pub fn anonymize(&mut self, df: &DataFrame) -> anyhow::Result<DataFrame> {
+        let mut df = df.clone();
+        for (name, kind) in TOKENIZED_COLUMNS {
+            let Ok(column) = df.column(name) else {
+                continue;
+            };
+            let tokens: Vec<Option<String>> = column
+                .str()?
+                .iter()
+                .map(|path| {
+                    path.map(|path| {
+                        if path.is_empty() {
+                            String::new()
+                        } else {
+                            self.token(kind, path)
+                        }
+                    })
+                })
+                .collect();
+            df.with_column(Column::new(name.into(), tokens))?;
+        }
+        if let Ok(column) = df.column(DEPLOYMENT_COLUMN) {
+            let deployments = column.cast(&DataType::String)?;
+            let deployments = deployments.str()?;
+            self.assign_pseudonyms(
+                deployments
+                    .iter()
+                    .flatten()
+                    .filter(|deployment| !deployment.is_empty()),
+            );
+            let pseudonyms: Vec<Option<String>> = deployments
+                .iter()
+                .map(|deployment| {
+                    deployment.map(|deployment| {
+                        self.deployments
+                            .get(deployment)
+                            .cloned()
+                            .unwrap_or_default()
+                    })
+                })
+                .collect();
+            df.with_column(Column::new(DEPLOYMENT_COLUMN.into(), pseudonyms))?;
+        }
+        Ok(df)
+    }
+
+    /// kind (deployment, path, filename or file_key), token and original of every value
+    /// replaced so far
+    pub fn write_keyfile(&self, path: &Path) -> anyhow::Result<()> {
+        let mut deployments: Vec<(&String, &String)> = self
+            .deployments
+            .iter()
+            .map(|(deployment, pseudonym)| (pseudonym, deployment))
+            .collect();
+        deployments.sort();
+        let rows: Vec<(&str, &String, &String)> = deployments
+            .into_iter()
+            .map(|(pseudonym, deployment)| ("deployment", pseudonym, deployment))
+            .chain(
+                self.tokens
+                    .iter()
+                    .map(|(token, (kind, original))| (*kind, token, original)),
+            )
+            .collect();
+        let mut df = df!(
+            "kind" => rows.iter().map(|row| row.0).collect::<Vec<_>>(),
+            "token" => rows.iter().map(|row| row.1.as_str()).collect::<Vec<_>>(),
+            "original" => rows.iter().map(|row| row.2.as_str()).collect::<Vec<_>>(),
+        )?;
+        let mut file = fs::File::create(path)?;
+        csv_writer(&mut file).finish(&mut df)?;
+        println!(
+            "Saved the anonymization key to {}, keep it private: it maps the tokens back to paths, filenames and deployments",
+            path.display()
+        );
+        Ok(())
+    }
+}
+
+/// Fails on a CSV written with --anonymize-paths, whose paths name no files
+pub fn reject_anonymized(df: &DataFrame, csv_path: &Path) -> anyhow::Result<()> {
+    let Ok(column) = df.column(PATH_COLUMN) else {
+        return Ok(());
+    };
+    if column.str()?.iter().flatten().any(is_path_token) {
+        return Err(anyhow::anyhow!(
+            "{} has anonymized paths (--anonymize-paths), there are no files to extract. \
+             Restore the path column from the path rows of its {KEYFILE_PREFIX} CSV first",
+            csv_path.display()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use crate::schema::{XMP_EXTENSIONS, resource_extension};
+use crate::utils::{decode_xmp_bytes, is_ignored_relative};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+// Separator between an archive and the entry inside it, e.g. deploy.zip!/dq001/IMG_0001.JPG.xmp
+pub const ARCHIVE_ENTRY_SEPARATOR: &str = "!/";
+
+/// XMP sidecar read from inside an archive
+pub struct ArchiveSidecar {
+    pub path: PathBuf,
+    /// Decoded sidecar, or why the entry couldn't be read, for the errors CSV
+    pub content: Result<String, String>,
+    pub modified: String,
+}
+
+pub fn is_zip_archive(path: &Path) -> bool {
+    path.is_file() && resource_extension(path).is_some_and(|ext| ext == "zip")
+}
+
+pub fn is_archive_entry_path(path: &str) -> bool {
+    path.contains(ARCHIVE_ENTRY_SEPARATOR)
+}
+
+// Same rules as path_enumerate, plus macOS resource forks
+fn is_ignored_entry(name: &str) -> bool {
+    name.split('/').any(|part| part == "__MACOSX") || is_ignored_relative(name)
+}
+
+// Read all XMP sidecars of a ZIP archive without unpacking it. An entry that can't be read
+// is listed with its error rather than failing the whole archive.
+pub fn read_zip_sidecars(archive_path: &Path) -> anyhow::Result<Vec<ArchiveSidecar>> {
+    let mut archive = ZipArchive::new(File::open(archive_path)?)?;
+    let mut sidecars = Vec::new();
+    for index in 0..archive.len() {
+        // Named by the central directory, which holds even when the entry itself is broken
+        let Some(name) = archive.name_for_index(index).map(str::to_string) else {
+            continue;
+        };
+        let is_sidecar = resource_extension(Path::new(&name))
+            .is_some_and(|ext| XMP_EXTENSIONS.contains(&ext.as_str()));
+        if name.ends_with('/') || !is_sidecar || is_ignored_entry(&name) {
+            continue;
+        }
+        let path = PathBuf::from(format!(
+            "{}{ARCHIVE_ENTRY_SEPARATOR}{name}",
+            archive_path.display()
+        ));
+        match read_zip_entry(&mut archive, index) {
+            Ok(Some((content, modified))) => sidecars.push(ArchiveSidecar {
+                path,
+                content: Ok(content),
+                modified,
+            }),
+            Ok(None) => {}
+            Err(e) => sidecars.push(ArchiveSidecar {
+                path,
+                content: Err(e.to_string()),
+                modified: String::new(),
+            }),
+        }
+    }
+    Ok(sidecars)
+}
+
+// Decoded content and modified time of an entry, None when its name escapes the archive
+fn read_zip_entry(
+    archive: &mut ZipArchive<File>,
+    index: usize,
+) -> anyhow::Result<Option<(String, String)>> {
+    let mut entry = archive
+        .by_index(index)
+        .map_err(|e| anyhow::anyhow!("Failed to read: {e}"))?;
+    // enclosed_name rejects absolute and parent-escaping entry names
+    if entry.enclosed_name().is_none() {
+        return Ok(None);
+    }
+    let modified = entry
+        .last_modified()
+        .map(|datetime| {
+            format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                datetime.year(),
+                datetime.month(),
+                datetime.day(),
+                datetime.hour(),
+                datetime.minute(),
+                datetime.second()
+            )
+        })
+        .unwrap_or_default();
+    let mut bytes = Vec::new();
+    entry
+        .read_to_end(&mut bytes)
+        .map_err(|e| anyhow::anyhow!("Failed to read: {e}"))?;
+    let content = decode_xmp_bytes(&bytes).map_err(|e| anyhow::anyhow!("Failed to decode: {e}"))?;
+    Ok(Some((content, modified)))
+}
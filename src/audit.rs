@@ -0,0 +1,214 @@
+use crate::config::{CONFIG_FILE, ServalConfig};
+use crate::utils::{absolute_path, normalize_path_str};
+use chrono::Local;
+use serde_json::{Map, Value};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const AUDIT_FILE: &str = "serval_audit.jsonl";
+
+// Log the sidecar changes of this run are appended to, see start_audit
+struct AuditLog {
+    command: String,
+    file: File,
+}
+
+static AUDIT_LOG: Mutex<Option<AuditLog>> = Mutex::new(None);
+
+/// One field of a sidecar changed by a command
+pub struct AuditChange {
+    pub field: String,
+    pub old_value: String,
+    pub new_value: String,
+}
+
+impl AuditChange {
+    pub fn new(field: &str, old_value: impl Into<String>, new_value: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            old_value: old_value.into(),
+            new_value: new_value.into(),
+        }
+    }
+}
+
+/// Log file of the project: audit.path in serval.toml (relative to it), else serval_audit.jsonl
+/// next to serval.toml, in the working directory when there is none
+pub fn audit_path(config: &ServalConfig) -> anyhow::Result<PathBuf> {
+    let base_dir = match config.path().and_then(Path::parent) {
+        Some(dir) => dir.to_path_buf(),
+        None => env::current_dir()?,
+    };
+    Ok(match config.string("audit.path")? {
+        Some(path) => base_dir.join(path),
+        None => base_dir.join(AUDIT_FILE),
+    })
+}
+
+/// Append every sidecar change of this run to the log at `path`, created when missing
+pub fn start_audit(path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to open audit log {}: {e}", path.display()))?;
+    *AUDIT_LOG.lock().unwrap() = Some(AuditLog {
+        command: env::args().collect::<Vec<_>>().join(" "),
+        file,
+    });
+    Ok(())
+}
+
+/// Log the changes made to `file_path`, nothing when the run is not audited.
+///
+/// Each change is one line written in a single append and synced, so a crash leaves at most
+/// a truncated last line, which `audit show` and `audit verify` skip.
+pub fn record_changes(
+    file_path: &Path,
+    changes: &[AuditChange],
+    backup_path: Option<&Path>,
+) -> anyhow::Result<()> {
+    let mut audit_log = AUDIT_LOG.lock().unwrap();
+    let Some(audit_log) = audit_log.as_mut() else {
+        return Ok(());
+    };
+    let timestamp = Local::now().format("%Y-%m-%dT%H:%M:%S%:z").to_string();
+    let path = normalize_path_str(&absolute_path(file_path.to_path_buf())?.to_string_lossy());
+    let backup = backup_path
+        .map(|backup| absolute_path(backup.to_path_buf()))
+        .transpose()?
+        .map(|backup| normalize_path_str(&backup.to_string_lossy()));
+    let mut lines = String::new();
+    for change in changes {
+        let mut entry = Map::new();
+        entry.insert("timestamp".to_string(), timestamp.clone().into());
+        entry.insert("command".to_string(), audit_log.command.clone().into());
+        entry.insert("path".to_string(), path.clone().into());
+        entry.insert("field".to_string(), change.field.clone().into());
+        entry.insert("old_value".to_string(), change.old_value.clone().into());
+        entry.insert("new_value".to_string(), change.new_value.clone().into());
+        entry.insert(
+            "backup".to_string(),
+            backup.clone().map_or(Value::Null, Value::from),
+        );
+        lines.push_str(&Value::Object(entry).to_string());
+        lines.push('\n');
+    }
+    audit_log.file.write_all(lines.as_bytes())?;
+    audit_log.file.flush()?;
+    audit_log.file.sync_data()?;
+    Ok(())
+}
+
+// Entries of the log in the order they were written, unreadable lines are skipped
+fn read_entries(path: &Path) -> anyhow::Result<Vec<Map<String, Value>>> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read audit log {}: {e}", path.display()))?;
+    let mut entries = Vec::new();
+    let mut num_unreadable = 0;
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str::<Value>(line) {
+            Ok(Value::Object(entry)) => entries.push(entry),
+            _ => num_unreadable += 1,
+        }
+    }
+    if num_unreadable > 0 {
+        println!(
+            "Warning: {num_unreadable} unreadable line(s) in {} skipped",
+            path.display()
+        );
+    }
+    Ok(entries)
+}
+
+fn entry_field<'a>(entry: &'a Map<String, Value>, key: &str) -> &'a str {
+    entry.get(key).and_then(Value::as_str).unwrap_or_default()
+}
+
+/// Print the logged changes of one file, oldest first
+pub fn audit_show(log_path: &Path, file_path: &Path) -> anyhow::Result<()> {
+    let entries = read_entries(log_path)?;
+    let path = normalize_path_str(&absolute_path(file_path.to_path_buf())?.to_string_lossy());
+    // Renames are logged on the new path, they show up under the old one too
+    let history: Vec<&Map<String, Value>> = entries
+        .iter()
+        .filter(|entry| {
+            entry_field(entry, "path") == path
+                || (entry_field(entry, "field") == "path"
+                    && entry_field(entry, "old_value") == path)
+        })
+        .collect();
+    if history.is_empty() {
+        println!("No changes to {path} in {}", log_path.display());
+        return Ok(());
+    }
+    println!("{} change(s) to {path}:", history.len());
+    for entry in history {
+        println!(
+            "{}  {}: '{}' -> '{}'  ({})",
+            entry_field(entry, "timestamp"),
+            entry_field(entry, "field"),
+            entry_field(entry, "old_value"),
+            entry_field(entry, "new_value"),
+            entry_field(entry, "command"),
+        );
+        let backup = entry_field(entry, "backup");
+        if !backup.is_empty() {
+            println!("    backup: {backup}");
+        }
+    }
+    Ok(())
+}
+
+/// Check that the backups referenced by the log still exist, an error listing the missing ones
+pub fn audit_verify(log_path: &Path) -> anyhow::Result<()> {
+    let entries = read_entries(log_path)?;
+    let mut backups: Vec<&str> = entries
+        .iter()
+        .map(|entry| entry_field(entry, "backup"))
+        .filter(|backup| !backup.is_empty())
+        .collect();
+    backups.sort_unstable();
+    backups.dedup();
+    let missing: Vec<&str> = backups
+        .iter()
+        .copied()
+        .filter(|backup| !Path::new(backup).is_file())
+        .collect();
+    println!(
+        "{} entries, {} backup(s) referenced in {}",
+        entries.len(),
+        backups.len(),
+        log_path.display()
+    );
+    if !missing.is_empty() {
+        return Err(anyhow::anyhow!(
+            "{} backup(s) missing:\n  {}",
+            missing.len(),
+            missing.join("\n  ")
+        ));
+    }
+    println!("All backups present");
+    Ok(())
+}
+
+/// Log of `--log`, else the one of the project (see audit_path)
+pub fn resolve_audit_log(log: Option<PathBuf>) -> anyhow::Result<PathBuf> {
+    let path = match log {
+        Some(log) => log,
+        None => audit_path(&ServalConfig::load()?)?,
+    };
+    if !path.is_file() {
+        return Err(anyhow::anyhow!(
+            "No audit log at {}, set audit.path in {CONFIG_FILE} or pass --log",
+            path.display()
+        ));
+    }
+    Ok(path)
+}
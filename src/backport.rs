@@ -0,0 +1,327 @@
+use crate::reconcile::{media_key, read_csv, sidecar_target};
+use crate::schema::{DEPLOYMENT_COLUMN, PATH_COLUMN, XMP_UPDATE_COLUMN};
+use crate::tags::DELETE_TAG_VALUE;
+use crate::utils::{ColumnMap, CsvDialect, TagType, csv_header, csv_writer};
+use chrono::NaiveDateTime;
+use polars::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TIME_COLUMN: &str = "time";
+
+// Spreadsheets tend to rewrite datetimes when a reviewed file is saved
+const TIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y/%m/%d %H:%M:%S",
+    "%Y-%m-%d %H:%M:%S%.f",
+];
+
+// (deployment, media key, time) of a temporal-independence row
+type RecordKey = (String, String, String);
+
+// Rows of a temporal-independence CSV by record, several when a file has several tags
+struct IndependenceRecords {
+    rows: BTreeMap<RecordKey, Vec<String>>,
+    paths: HashMap<RecordKey, String>,
+}
+
+fn normalize_time(time: &str) -> String {
+    let time = time.trim();
+    TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(time, format).ok())
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| time.to_string())
+}
+
+impl IndependenceRecords {
+    fn read(csv_path: &Path, tag_column: &str) -> anyhow::Result<Self> {
+        let df = read_csv(
+            csv_path,
+            &[DEPLOYMENT_COLUMN, PATH_COLUMN, TIME_COLUMN, tag_column],
+            &ColumnMap::default(),
+        )?;
+        let mut records = Self {
+            rows: BTreeMap::new(),
+            paths: HashMap::new(),
+        };
+        let column = |name: &str| -> anyhow::Result<Vec<String>> {
+            Ok(df
+                .column(name)?
+                .str()?
+                .iter()
+                .map(|value| value.unwrap_or_default().trim().to_string())
+                .collect())
+        };
+        for (deployment, path, time, value) in itertools::izip!(
+            column(DEPLOYMENT_COLUMN)?,
+            column(PATH_COLUMN)?,
+            column(TIME_COLUMN)?,
+            column(tag_column)?
+        ) {
+            if path.is_empty() {
+                continue;
+            }
+            let key = (deployment, media_key(&path), normalize_time(&time));
+            records.paths.entry(key.clone()).or_insert(path);
+            records.rows.entry(key).or_default().push(value);
+        }
+        Ok(records)
+    }
+}
+
+// Tag column of a temporal-independence CSV, species or individual depending on the run
+fn target_column(csv_path: &Path) -> anyhow::Result<&'static str> {
+    let header = csv_header(csv_path)?;
+    [TagType::Species, TagType::Individual]
+        .into_iter()
+        .map(|tag_type| tag_type.col_name())
+        .find(|name| header.iter().any(|column| column == name))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "{} has no species or individual column, is it a temporal-independence CSV?",
+                csv_path.display()
+            )
+        })
+}
+
+// Values of `values` left once each of `other` has been taken out, repeats counted
+fn multiset_difference<'a>(values: &'a [String], other: &[String]) -> Vec<&'a String> {
+    let mut other: Vec<&String> = other.iter().collect();
+    values
+        .iter()
+        .filter(
+            |value| match other.iter().position(|taken| taken == value) {
+                Some(position) => {
+                    other.swap_remove(position);
+                    false
+                }
+                None => true,
+            },
+        )
+        .collect()
+}
+
+fn write_csv(df: &mut DataFrame, path: &Path) -> anyhow::Result<()> {
+    let mut file = fs::File::create(path)?;
+    csv_writer(&mut file).finish(df)?;
+    Ok(())
+}
+
+/// Carry species (or individual) fixes made in a reviewed temporal-independence CSV back to
+/// the sidecars and tags.csv.
+///
+/// Rows are matched to the original output by (deployment, path, time). Changed values
+/// become an `xmp update` CSV and an updated copy of tags.csv. Rows deleted, re-timed or
+/// added in review, and records whose change can't be tied to one tag, are listed in
+/// backport_unmatched.csv and left alone.
+pub fn backport_corrections(
+    reviewed_csv: PathBuf,
+    original_csv: PathBuf,
+    tags_csv: PathBuf,
+    output_dir: PathBuf,
+    column_map: ColumnMap,
+) -> anyhow::Result<()> {
+    let tag_column = target_column(&original_csv)?;
+    let original = IndependenceRecords::read(&original_csv, tag_column)?;
+    let reviewed = IndependenceRecords::read(&reviewed_csv, tag_column)?;
+
+    // (deployment, path, time, value, reason) of rows not backported
+    let mut unmatched: Vec<(String, String, String, String, String)> = Vec::new();
+    let mut flag =
+        |key: &RecordKey, paths: &HashMap<RecordKey, String>, values: &[String], reason: &str| {
+            let (deployment, _, time) = key;
+            unmatched.push((
+                deployment.clone(),
+                paths[key].clone(),
+                time.clone(),
+                values.join(CsvDialect::current().multi_value_separator()),
+                reason.to_string(),
+            ));
+        };
+    let original_times: HashMap<(&str, &str), &str> = original
+        .rows
+        .keys()
+        .map(|(deployment, path, time)| ((deployment.as_str(), path.as_str()), time.as_str()))
+        .collect();
+
+    // media key -> (old, new) value changes
+    let mut changes: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+    for (key, original_values) in &original.rows {
+        let Some(reviewed_values) = reviewed.rows.get(key) else {
+            // Re-timed records are reported with the reviewed rows below
+            if !reviewed
+                .rows
+                .keys()
+                .any(|other| other.0 == key.0 && other.1 == key.1)
+            {
+                flag(key, &original.paths, original_values, "deleted in review");
+            }
+            continue;
+        };
+        let removed = multiset_difference(original_values, reviewed_values);
+        let added = multiset_difference(reviewed_values, original_values);
+        if removed.is_empty() && added.is_empty() {
+            continue;
+        }
+        match (removed.as_slice(), added.as_slice()) {
+            ([old], [new]) if original_values.len() == reviewed_values.len() => {
+                let new = if new.is_empty() {
+                    DELETE_TAG_VALUE.to_string()
+                } else {
+                    new.to_string()
+                };
+                changes
+                    .entry(key.1.clone())
+                    .or_default()
+                    .push((old.to_string(), new));
+            }
+            _ if original_values.len() != reviewed_values.len() => flag(
+                key,
+                &reviewed.paths,
+                reviewed_values,
+                "ambiguous: rows of this record added or removed in review",
+            ),
+            _ => flag(
+                key,
+                &reviewed.paths,
+                reviewed_values,
+                &format!(
+                    "ambiguous: {} changed among {} rows of this record",
+                    removed.len(),
+                    original_values.len()
+                ),
+            ),
+        }
+    }
+    for (key, reviewed_values) in &reviewed.rows {
+        if original.rows.contains_key(key) {
+            continue;
+        }
+        let reason = match original_times.get(&(key.0.as_str(), key.1.as_str())) {
+            Some(time) => format!("re-timed in review from {time}"),
+            None => "not in the original".to_string(),
+        };
+        flag(key, &reviewed.paths, reviewed_values, &reason);
+    }
+
+    // Changes checked against the current tags, which may have moved on since the run
+    let mut df_tags = read_csv(&tags_csv, &[PATH_COLUMN, tag_column], &column_map)?;
+    let tag_paths: Vec<String> = df_tags
+        .column(PATH_COLUMN)?
+        .str()?
+        .iter()
+        .map(|path| path.unwrap_or_default().to_string())
+        .collect();
+    let mut tag_values: Vec<String> = df_tags
+        .column(tag_column)?
+        .str()?
+        .iter()
+        .map(|value| value.unwrap_or_default().to_string())
+        .collect();
+    let mut rows_by_file: HashMap<String, Vec<usize>> = HashMap::new();
+    for (row, path) in tag_paths.iter().enumerate() {
+        if !path.is_empty() {
+            rows_by_file.entry(media_key(path)).or_default().push(row);
+        }
+    }
+
+    let mut update_paths: Vec<String> = Vec::new();
+    let mut update_old: Vec<String> = Vec::new();
+    let mut update_new: Vec<String> = Vec::new();
+    let mut dropped_rows: BTreeSet<usize> = BTreeSet::new();
+    for (file, file_changes) in &changes {
+        let rows = rows_by_file.get(file).cloned().unwrap_or_default();
+        for (old, new) in file_changes {
+            let Some(&row) = rows.iter().find(|&&row| &tag_values[row] == old) else {
+                let key = original
+                    .rows
+                    .keys()
+                    .find(|key| &key.1 == file)
+                    .expect("changes come from original records");
+                flag(
+                    key,
+                    &original.paths,
+                    std::slice::from_ref(old),
+                    &format!("{tag_column} {old} no longer in the tags CSV for this file"),
+                );
+                continue;
+            };
+            // Merging into a value the file already has only removes the old one
+            let new = if rows.iter().any(|&other| &tag_values[other] == new) {
+                DELETE_TAG_VALUE.to_string()
+            } else {
+                new.clone()
+            };
+            if new == DELETE_TAG_VALUE {
+                if rows.len() > 1 {
+                    dropped_rows.insert(row);
+                } else {
+                    tag_values[row] = String::new();
+                }
+            } else {
+                tag_values[row] = new.clone();
+            }
+            update_paths.push(
+                sidecar_target(&tag_paths[row])
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+            update_old.push(old.clone());
+            update_new.push(new);
+        }
+    }
+
+    fs::create_dir_all(&output_dir)?;
+    let mut df_updates = df!(
+        PATH_COLUMN => &update_paths,
+        tag_column => update_old,
+        XMP_UPDATE_COLUMN => update_new,
+    )?;
+    let updates_path = output_dir.join("backport_updates.csv");
+    write_csv(&mut df_updates, &updates_path)?;
+    println!(
+        "{} correction(s) of {tag_column} backported, apply with serval xmp update -t {tag_column} {}",
+        update_paths.len(),
+        updates_path.display()
+    );
+
+    // tags.csv with the corrections, named columns written back as they were read
+    df_tags.with_column(Column::new(tag_column.into(), tag_values))?;
+    let kept = BooleanChunked::from_iter_values(
+        "kept".into(),
+        (0..df_tags.height()).map(|row| !dropped_rows.contains(&row)),
+    );
+    let mut df_tags = df_tags.filter(&kept)?;
+    column_map.restore(&mut df_tags)?;
+    let tags_path = output_dir.join(format!(
+        "{}_backported.csv",
+        tags_csv
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "tags".to_string())
+    ));
+    write_csv(&mut df_tags, &tags_path)?;
+    println!("Saved updated tags to {}", tags_path.display());
+
+    if !unmatched.is_empty() {
+        unmatched.sort();
+        let mut df_unmatched = df!(
+            DEPLOYMENT_COLUMN => unmatched.iter().map(|row| row.0.as_str()).collect::<Vec<_>>(),
+            PATH_COLUMN => unmatched.iter().map(|row| row.1.as_str()).collect::<Vec<_>>(),
+            TIME_COLUMN => unmatched.iter().map(|row| row.2.as_str()).collect::<Vec<_>>(),
+            tag_column => unmatched.iter().map(|row| row.3.as_str()).collect::<Vec<_>>(),
+            "reason" => unmatched.iter().map(|row| row.4.as_str()).collect::<Vec<_>>(),
+        )?;
+        let unmatched_path = output_dir.join("backport_unmatched.csv");
+        write_csv(&mut df_unmatched, &unmatched_path)?;
+        println!(
+            "Warning: {} row(s) not backported, review {}",
+            unmatched.len(),
+            unmatched_path.display()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use crate::schema::PATH_COLUMN;
+use crate::utils::{
+    CsvDialect, TagType, csv_projection_columns, csv_writer, media_path_for,
+    reject_duplicate_csv_columns,
+};
+use chrono::Local;
+use polars::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Tags of one file in a tagging pass, keyed by the normalized key
+struct PassEntry {
+    original_key: String,
+    tags: BTreeSet<String>,
+}
+
+// Directory components shared by every path of a pass, e.g. the volunteer's copy root
+fn common_dir_prefix(paths: &[String]) -> Vec<String> {
+    let mut prefix: Option<Vec<&str>> = None;
+    for path in paths {
+        let mut dirs: Vec<&str> = path.split('/').collect();
+        dirs.pop();
+        prefix = Some(match prefix {
+            None => dirs,
+            Some(prefix) => prefix
+                .into_iter()
+                .zip(dirs)
+                .take_while(|(a, b)| a == b)
+                .map(|(a, _)| a)
+                .collect(),
+        });
+    }
+    prefix
+        .unwrap_or_default()
+        .into_iter()
+        .map(str::to_string)
+        .collect()
+}
+
+// Paths are compared relative to the pass root, with sidecar and media rows sharing a key
+fn normalize_path_keys(paths: &[String]) -> Vec<String> {
+    let paths: Vec<String> = paths
+        .iter()
+        .map(|path| {
+            let path = path.replace('\\', "/");
+            media_path_for(Path::new(&path))
+                .map(|media| media.to_string_lossy().into_owned())
+                .unwrap_or(path)
+        })
+        .collect();
+    let prefix_len = common_dir_prefix(&paths).len();
+    paths
+        .iter()
+        .map(|path| {
+            path.split('/')
+                .skip(prefix_len)
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .collect()
+}
+
+fn read_pass(
+    csv_path: &Path,
+    key: &str,
+    tag_type: TagType,
+) -> anyhow::Result<BTreeMap<String, PassEntry>> {
+    let df = CsvReadOptions::default()
+        .with_infer_schema_length(Some(0))
+        .with_columns(csv_projection_columns(&[key, tag_type.col_name()]))
+        .try_into_reader_with_file_path(Some(csv_path.to_path_buf()))
+        .and_then(|reader| reader.finish())
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to read {} with columns {key}, {}: {e}",
+                csv_path.display(),
+                tag_type.col_name()
+            )
+        })?;
+    reject_duplicate_csv_columns(&df)?;
+    let original_keys: Vec<String> = df
+        .column(key)?
+        .str()?
+        .iter()
+        .map(|value| value.unwrap_or_default().trim().to_string())
+        .collect();
+    let keys = if key == PATH_COLUMN {
+        normalize_path_keys(&original_keys)
+    } else {
+        original_keys.clone()
+    };
+
+    let mut entries: BTreeMap<String, PassEntry> = BTreeMap::new();
+    for ((key, original_key), value) in keys
+        .into_iter()
+        .zip(original_keys)
+        .zip(df.column(tag_type.col_name())?.str()?.iter())
+    {
+        if key.is_empty() {
+            continue;
+        }
+        let entry = entries.entry(key).or_insert_with(|| PassEntry {
+            original_key,
+            tags: BTreeSet::new(),
+        });
+        // A file may be spread over several rows or joined with '|', either way it is one set
+        entry.tags.extend(
+            value
+                .unwrap_or_default()
+                .split('|')
+                .map(str::trim)
+                .filter(|tag| !tag.is_empty())
+                .map(str::to_string),
+        );
+    }
+    Ok(entries)
+}
+
+fn join_tags(tags: Option<&BTreeSet<String>>) -> String {
+    tags.map(|tags| {
+        tags.iter()
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(CsvDialect::current().multi_value_separator())
+    })
+    .unwrap_or_default()
+}
+
+// Compare two tagging passes of the same resources (double-observer QA)
+pub fn compare_tags(
+    csv_a: PathBuf,
+    csv_b: PathBuf,
+    key: String,
+    tag_type: TagType,
+    output_dir: PathBuf,
+) -> anyhow::Result<()> {
+    let pass_a = read_pass(&csv_a, &key, tag_type)?;
+    let pass_b = read_pass(&csv_b, &key, tag_type)?;
+    let tag_col = tag_type.col_name();
+
+    let keys: BTreeSet<&String> = pass_a.keys().chain(pass_b.keys()).collect();
+    let mut num_shared = 0;
+    let mut num_agreed = 0;
+    // tag -> (files tagged with it in both passes, files tagged with it in either pass)
+    let mut tag_agreement: BTreeMap<&str, (u32, u32)> = BTreeMap::new();
+    let mut conflicts: Vec<(String, String, String, String, String, &str)> = Vec::new();
+    for key in keys {
+        let entry_a = pass_a.get(key);
+        let entry_b = pass_b.get(key);
+        let tags_a = entry_a.map(|entry| &entry.tags);
+        let tags_b = entry_b.map(|entry| &entry.tags);
+        let status = match (tags_a, tags_b) {
+            (Some(tags_a), Some(tags_b)) => {
+                num_shared += 1;
+                for tag in tags_a.union(tags_b) {
+                    let counts = tag_agreement.entry(tag).or_default();
+                    counts.1 += 1;
+                    if tags_a.contains(tag) && tags_b.contains(tag) {
+                        counts.0 += 1;
+                    }
+                }
+                if tags_a == tags_b {
+                    num_agreed += 1;
+                    continue;
+                } else if tags_a.is_empty() {
+                    "blank_in_a"
+                } else if tags_b.is_empty() {
+                    "blank_in_b"
+                } else {
+                    "conflict"
+                }
+            }
+            (Some(_), None) => "missing_in_b",
+            (None, Some(_)) => "missing_in_a",
+            (None, None) => unreachable!(),
+        };
+        conflicts.push((
+            key.clone(),
+            entry_a
+                .map(|entry| entry.original_key.clone())
+                .unwrap_or_default(),
+            entry_b
+                .map(|entry| entry.original_key.clone())
+                .unwrap_or_default(),
+            join_tags(tags_a),
+            join_tags(tags_b),
+            status,
+        ));
+    }
+
+    if num_shared == 0 {
+        return Err(anyhow::anyhow!(
+            "No {key} values shared between {} and {}, check that both passes cover the same files",
+            csv_a.display(),
+            csv_b.display()
+        ));
+    }
+    println!(
+        "{num_shared} file(s) in both passes, {num_agreed} agreed ({:.1}%), {} to review",
+        num_agreed as f64 / num_shared as f64 * 100.0,
+        conflicts.len()
+    );
+
+    let mut df_agreement = df!(
+        tag_col => tag_agreement.keys().copied().collect::<Vec<_>>(),
+        "agreed" => tag_agreement.values().map(|counts| counts.0).collect::<Vec<_>>(),
+        "tagged" => tag_agreement.values().map(|counts| counts.1).collect::<Vec<_>>(),
+        "agreement_rate" => tag_agreement
+            .values()
+            .map(|counts| counts.0 as f64 / counts.1 as f64)
+            .collect::<Vec<_>>(),
+    )?
+    .sort(["agreement_rate"], SortMultipleOptions::default())?;
+    println!("{df_agreement}");
+
+    let mut df_conflicts = df!(
+        key.as_str() => conflicts.iter().map(|row| row.0.as_str()).collect::<Vec<_>>(),
+        format!("{key}_a").as_str() => conflicts.iter().map(|row| row.1.as_str()).collect::<Vec<_>>(),
+        format!("{key}_b").as_str() => conflicts.iter().map(|row| row.2.as_str()).collect::<Vec<_>>(),
+        format!("{tag_col}_a").as_str() => conflicts.iter().map(|row| row.3.as_str()).collect::<Vec<_>>(),
+        format!("{tag_col}_b").as_str() => conflicts.iter().map(|row| row.4.as_str()).collect::<Vec<_>>(),
+        "status" => conflicts.iter().map(|row| row.5).collect::<Vec<_>>(),
+        // Left empty for the reviewer's decision
+        "resolution" => vec![""; conflicts.len()],
+    )?;
+
+    fs::create_dir_all(&output_dir)?;
+    let timestamp = Local::now().format("%Y%m%d%H%M%S");
+    let agreement_path = output_dir.join(format!("{tag_col}_agreement_{timestamp}.csv"));
+    let mut file = fs::File::create(&agreement_path)?;
+    csv_writer(&mut file).finish(&mut df_agreement)?;
+    println!("Saved to {}", agreement_path.display());
+    let conflicts_path = output_dir.join(format!("{tag_col}_conflicts_{timestamp}.csv"));
+    let mut file = fs::File::create(&conflicts_path)?;
+    csv_writer(&mut file).finish(&mut df_conflicts)?;
+    println!("Saved to {}", conflicts_path.display());
+    Ok(())
+}
@@ -0,0 +1,449 @@
+use crate::info::environment_toml;
+use crate::schema::{CUSTOM_COLUMN, RATING_COLUMN, SPECIES_COLUMN};
+use chrono::Local;
+use polars::prelude::*;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use toml::{Table, Value};
+
+pub const CONFIG_FILE: &str = "serval.toml";
+
+#[derive(Clone, Copy, PartialEq)]
+enum SettingKind {
+    List,
+    Integer,
+    Text,
+}
+
+// Settings accepted by `serval config set`, lists are given comma-separated
+const CONFIG_KEYS: &[(&str, SettingKind, &str)] = &[
+    (
+        "capture.exclude",
+        SettingKind::List,
+        "Tags excluded from capture in addition to the built-in ones, e.g. \"Test,Setup\"",
+    ),
+    (
+        "species.indeterminate",
+        SettingKind::List,
+        "Species labels for animals that could not be identified, replacing the defaults \"Unidentified,Unknown\"",
+    ),
+    (
+        "tags.extra",
+        SettingKind::List,
+        "Tag categories observe reads besides the built-in ones, e.g. \"Behaviour,Age\" for Behaviour|Grazing",
+    ),
+    (
+        "audit.path",
+        SettingKind::Text,
+        "Log of the sidecar changes, relative to serval.toml, e.g. \"logs/serval_audit.jsonl\" (default: serval_audit.jsonl next to it)",
+    ),
+    (
+        "review.max_rating",
+        SettingKind::Integer,
+        "Records rated at or below this need review, e.g. 1",
+    ),
+    (
+        "review.custom",
+        SettingKind::List,
+        "Custom tag values marking records that need review, e.g. \"Review\"",
+    ),
+    (
+        "review.species",
+        SettingKind::List,
+        "Species marking records that need review, e.g. \"Unidentified\"",
+    ),
+];
+
+fn check_key(key: &str) -> anyhow::Result<SettingKind> {
+    if let Some((_, kind, _)) = CONFIG_KEYS.iter().find(|(known, _, _)| *known == key) {
+        return Ok(*kind);
+    }
+    Err(anyhow::anyhow!(
+        "Unknown setting {key}, expected one of: {}",
+        CONFIG_KEYS
+            .iter()
+            .map(|(known, _, _)| *known)
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))
+}
+
+/// Project settings from serval.toml in the working directory or the closest parent
+#[derive(Default)]
+pub struct ServalConfig {
+    path: Option<PathBuf>,
+    table: Table,
+}
+
+impl ServalConfig {
+    pub fn load() -> anyhow::Result<Self> {
+        let current_dir = env::current_dir()?;
+        let Some(path) = current_dir
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE))
+            .find(|path| path.is_file())
+        else {
+            return Ok(Self::default());
+        };
+        let table = fs::read_to_string(&path)?
+            .parse::<Table>()
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {e}", path.display()))?;
+        Ok(Self {
+            path: Some(path),
+            table,
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    // Value of a dotted key, e.g. capture.exclude
+    fn get(&self, key: &str) -> Option<&Value> {
+        let (section, name) = key.split_once('.')?;
+        self.table.get(section)?.get(name)
+    }
+
+    fn display_path(&self) -> std::path::Display<'_> {
+        self.path
+            .as_deref()
+            .unwrap_or(Path::new(CONFIG_FILE))
+            .display()
+    }
+
+    /// Integer setting, None when not set
+    pub fn integer(&self, key: &str) -> anyhow::Result<Option<i64>> {
+        self.get(key)
+            .map(|value| {
+                value.as_integer().ok_or_else(|| {
+                    anyhow::anyhow!("{key} in {} should be an integer", self.display_path())
+                })
+            })
+            .transpose()
+    }
+
+    /// Text setting, None when not set
+    pub fn string(&self, key: &str) -> anyhow::Result<Option<String>> {
+        self.get(key)
+            .map(|value| {
+                value.as_str().map(str::to_string).ok_or_else(|| {
+                    anyhow::anyhow!("{key} in {} should be a string", self.display_path())
+                })
+            })
+            .transpose()
+    }
+
+    /// List setting, empty when not set
+    pub fn string_list(&self, key: &str) -> anyhow::Result<Vec<String>> {
+        let Some(value) = self.get(key) else {
+            return Ok(Vec::new());
+        };
+        value
+            .as_array()
+            .and_then(|values| {
+                values
+                    .iter()
+                    .map(|value| value.as_str().map(str::to_string))
+                    .collect::<Option<Vec<_>>>()
+            })
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{key} in {} should be a list of strings",
+                    self.display_path()
+                )
+            })
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> anyhow::Result<()> {
+        let value = match check_key(key)? {
+            SettingKind::List => Value::Array(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|value| !value.is_empty())
+                    .map(|value| Value::String(value.to_string()))
+                    .collect(),
+            ),
+            SettingKind::Integer => Value::Integer(
+                value
+                    .trim()
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("{key} should be an integer, got {value}"))?,
+            ),
+            SettingKind::Text => Value::String(value.trim().to_string()),
+        };
+        let (section, name) = key.split_once('.').unwrap();
+        let section = self
+            .table
+            .entry(section)
+            .or_insert_with(|| Value::Table(Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| anyhow::anyhow!("{section} in {CONFIG_FILE} is not a table"))?;
+        section.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// Each setting with its value, or the description of what it would do when not set
+    pub fn effective(&self) -> Vec<String> {
+        CONFIG_KEYS
+            .iter()
+            .map(|(key, _, description)| match self.get(key) {
+                Some(value) => format!("{key} = {value}"),
+                None => format!("# {key} (not set): {description}"),
+            })
+            .collect()
+    }
+
+    // Written back where it was found, a new serval.toml in the working directory otherwise
+    fn save(&self) -> anyhow::Result<PathBuf> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => env::current_dir()?.join(CONFIG_FILE),
+        };
+        fs::write(&path, toml::to_string(&self.table)?)?;
+        Ok(path)
+    }
+}
+
+pub fn config_set(key: String, value: String) -> anyhow::Result<()> {
+    let mut config = ServalConfig::load()?;
+    config.set(&key, &value)?;
+    let path = config.save()?;
+    println!(
+        "{key} = {} saved to {}",
+        config.get(&key).unwrap(),
+        path.display()
+    );
+    Ok(())
+}
+
+pub fn config_show() -> anyhow::Result<()> {
+    let config = ServalConfig::load()?;
+    match config.path() {
+        Some(path) => println!("# {}", path.display()),
+        None => println!("# No {CONFIG_FILE} found, using defaults"),
+    }
+    for line in config.effective() {
+        println!("{line}");
+    }
+    Ok(())
+}
+
+/// Species labels of animals that could not be identified, unless species.indeterminate is set
+pub const DEFAULT_INDETERMINATE_LABELS: &[&str] = &["Unidentified", "Unknown"];
+
+static INDETERMINATE_LABELS: OnceLock<IndeterminateLabels> = OnceLock::new();
+
+/// Species labels that are not a species: counted apart in species_stats, excluded by capture
+/// and skipped by extract's ALL_VALUES
+#[derive(Clone, Debug)]
+pub struct IndeterminateLabels {
+    labels: Vec<String>,
+}
+
+impl Default for IndeterminateLabels {
+    fn default() -> Self {
+        Self {
+            labels: DEFAULT_INDETERMINATE_LABELS
+                .iter()
+                .map(|label| label.to_string())
+                .collect(),
+        }
+    }
+}
+
+// Lowercase letters and digits only, so "Un-identified " and "unidentified" compare equal
+fn spelling_key(label: &str) -> String {
+    label
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl IndeterminateLabels {
+    /// Labels of this invocation, set once by configure_indeterminate_labels
+    pub fn current() -> Self {
+        INDETERMINATE_LABELS.get().cloned().unwrap_or_default()
+    }
+
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    pub fn contains(&self, species: &str) -> bool {
+        self.labels.iter().any(|label| label == species)
+    }
+
+    /// Another spelling of a label (case, punctuation, or a label with more words, e.g.
+    /// "unknown bird"), which is counted as a species until added to the set
+    pub fn is_other_spelling(&self, species: &str) -> bool {
+        let key = spelling_key(species);
+        !self.contains(species)
+            && self.labels.iter().any(|label| {
+                let label = spelling_key(label);
+                !label.is_empty() && key.contains(&label)
+            })
+    }
+
+    /// True for rows whose `column` is one of the labels
+    pub fn expr(&self, column: &str) -> Expr {
+        col(column)
+            .cast(DataType::String)
+            .is_in(
+                lit(Series::new("indeterminate".into(), self.labels.clone())).implode(false),
+                false,
+            )
+            .fill_null(lit(false))
+    }
+}
+
+/// Indeterminate labels from --indeterminate, else species.indeterminate in serval.toml
+pub fn configure_indeterminate_labels(labels: Option<Vec<String>>) -> anyhow::Result<()> {
+    let labels = match labels {
+        Some(labels) => labels,
+        None => ServalConfig::load()?.string_list("species.indeterminate")?,
+    };
+    let labels: Vec<String> = labels
+        .into_iter()
+        .map(|label| label.trim().to_string())
+        .filter(|label| !label.is_empty())
+        .collect();
+    if !labels.is_empty() {
+        let _ = INDETERMINATE_LABELS.set(IndeterminateLabels { labels });
+    }
+    Ok(())
+}
+
+/// Records that need review (uncertain identifications), from the review.* settings
+#[derive(Default)]
+pub struct ReviewFilter {
+    max_rating: Option<i64>,
+    custom: Vec<String>,
+    species: Vec<String>,
+}
+
+impl ReviewFilter {
+    pub fn from_config(config: &ServalConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            max_rating: config.integer("review.max_rating")?,
+            custom: config.string_list("review.custom")?,
+            species: config.string_list("review.species")?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max_rating.is_none() && self.custom.is_empty() && self.species.is_empty()
+    }
+
+    pub fn describe(&self) -> String {
+        let mut conditions = Vec::new();
+        if let Some(max_rating) = self.max_rating {
+            conditions.push(format!("rating <= {max_rating}"));
+        }
+        if !self.custom.is_empty() {
+            conditions.push(format!("custom in [{}]", self.custom.join(", ")));
+        }
+        if !self.species.is_empty() {
+            conditions.push(format!("species in [{}]", self.species.join(", ")));
+        }
+        conditions.join(" or ")
+    }
+
+    /// True for flagged rows, conditions on columns missing from the CSV never match
+    pub fn flag_expr(&self, df: &DataFrame) -> Expr {
+        let has_column = |name: &str| df.get_column_index(name).is_some();
+        let mut expr = lit(false);
+        if let Some(max_rating) = self.max_rating
+            && has_column(RATING_COLUMN)
+        {
+            expr = expr.or(col(RATING_COLUMN)
+                .cast(DataType::Int64)
+                .lt_eq(lit(max_rating))
+                .fill_null(lit(false)));
+        }
+        for (column, values) in [
+            (CUSTOM_COLUMN, &self.custom),
+            (SPECIES_COLUMN, &self.species),
+        ] {
+            if !has_column(column) {
+                continue;
+            }
+            for value in values {
+                expr = expr.or(col(column)
+                    .cast(DataType::String)
+                    .eq(lit(value.as_str()))
+                    .fill_null(lit(false)));
+            }
+        }
+        expr
+    }
+
+    pub fn set_params(&self, params: &mut RunParams) {
+        if let Some(max_rating) = self.max_rating {
+            params.set("review_max_rating", max_rating);
+        }
+        params.set("review_custom", self.custom.clone());
+        params.set("review_species", self.species.clone());
+    }
+}
+
+/// Parameters of one run, written next to its outputs so published results can cite them
+pub struct RunParams {
+    command: String,
+    params: Table,
+}
+
+impl RunParams {
+    pub fn new(command: &str) -> Self {
+        Self {
+            command: command.to_string(),
+            params: Table::new(),
+        }
+    }
+
+    pub fn set<V: Into<Value>>(&mut self, key: &str, value: V) {
+        self.params.insert(key.to_string(), value.into());
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.params.get(key)
+    }
+
+    /// Params file written by an earlier run
+    pub fn read(path: &Path) -> anyhow::Result<Self> {
+        let table: Table = fs::read_to_string(path)?
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {e}", path.display()))?;
+        let field = |key: &str| table.get(key).and_then(|value| value.as_str());
+        Ok(Self {
+            command: field("command").unwrap_or_default().to_string(),
+            params: table
+                .get("params")
+                .and_then(|params| params.as_table())
+                .cloned()
+                .unwrap_or_default(),
+        })
+    }
+
+    pub fn write(&self, output_dir: &Path, filename: &str) -> anyhow::Result<()> {
+        let mut table = Table::new();
+        table.insert(
+            "serval_version".to_string(),
+            env!("CARGO_PKG_VERSION").into(),
+        );
+        table.insert("command".to_string(), self.command.as_str().into());
+        table.insert(
+            "created".to_string(),
+            Local::now().format("%Y-%m-%dT%H:%M:%S").to_string().into(),
+        );
+        table.insert("params".to_string(), Value::Table(self.params.clone()));
+        table.insert("environment".to_string(), Value::Table(environment_toml()));
+        let path = output_dir.join(filename);
+        fs::write(&path, toml::to_string(&table)?)?;
+        println!("Saved to {}", path.to_string_lossy());
+        Ok(())
+    }
+}
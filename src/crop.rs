@@ -0,0 +1,269 @@
+use crate::progress::ServalProgress;
+use crate::schema::{IMAGE_EXTENSIONS, PATH_COLUMN, VIDEO_EXTENSIONS, media_extension};
+use crate::utils::{
+    ColumnMap, check_csv_columns, csv_writer, media_path_for, reject_duplicate_csv_columns,
+};
+use image::ImageFormat;
+use polars::prelude::*;
+use rayon::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const BOX_COLUMNS: [&str; 4] = ["x", "y", "w", "h"];
+
+// One detection row, box normalized to the image size (0-1, top-left origin)
+struct Detection {
+    source_row: u32,
+    index: u32,
+    bbox: [f64; 4],
+}
+
+// Manifest row of one detection
+struct CropRecord {
+    source_row: u32,
+    path: String,
+    index: u32,
+    crop_path: String,
+    status: String,
+}
+
+// Pixel rectangle of a normalized box grown by `padding` percent of its size on each side,
+// clamped to the image. None when nothing is left after clamping.
+fn pixel_rect(bbox: [f64; 4], padding: f64, width: u32, height: u32) -> Option<[u32; 4]> {
+    let [x, y, w, h] = bbox;
+    let pad_x = w * padding / 100.0;
+    let pad_y = h * padding / 100.0;
+    let clamp =
+        |value: f64, size: u32| (value * size as f64).round().clamp(0.0, size as f64) as u32;
+    let left = clamp(x - pad_x, width);
+    let top = clamp(y - pad_y, height);
+    let right = clamp(x + w + pad_x, width);
+    let bottom = clamp(y + h + pad_y, height);
+    (right > left && bottom > top).then(|| [left, top, right - left, bottom - top])
+}
+
+fn crop_image(
+    image_path: &Path,
+    detections: &[Detection],
+    output_dir: &Path,
+    padding: f64,
+) -> anyhow::Result<Vec<(u32, anyhow::Result<PathBuf>)>> {
+    let image = image::open(image_path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", image_path.display()))?;
+    fs::create_dir_all(output_dir)?;
+    let stem = image_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(detections
+        .iter()
+        .map(|detection| {
+            let result = pixel_rect(detection.bbox, padding, image.width(), image.height())
+                .ok_or_else(|| anyhow::anyhow!("box is outside the image"))
+                .and_then(|[left, top, width, height]| {
+                    let crop_path = output_dir.join(format!("{stem}_box{}.jpg", detection.index));
+                    // JPEG has no alpha channel, PNG sources are flattened to RGB
+                    image
+                        .crop_imm(left, top, width, height)
+                        .to_rgb8()
+                        .save_with_format(&crop_path, ImageFormat::Jpeg)?;
+                    Ok(crop_path)
+                });
+            (detection.source_row, result)
+        })
+        .collect())
+}
+
+fn parse_box(values: [Option<&str>; 4], row: usize) -> anyhow::Result<[f64; 4]> {
+    let mut bbox = [0.0; 4];
+    for ((value, name), coordinate) in values.iter().zip(BOX_COLUMNS).zip(bbox.iter_mut()) {
+        *coordinate = value
+            .map(str::trim)
+            .and_then(|value| value.parse::<f64>().ok())
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid {name} in row {}: {}",
+                    row + 1,
+                    value.unwrap_or_default()
+                )
+            })?;
+    }
+    Ok(bbox)
+}
+
+// Directory shared by every image, crops keep the structure below it
+fn common_root(paths: &[&Path]) -> PathBuf {
+    paths
+        .iter()
+        .filter_map(|path| path.parent().map(Path::to_path_buf))
+        .reduce(|common, parent| {
+            common
+                .ancestors()
+                .find(|ancestor| parent.starts_with(ancestor))
+                .map(Path::to_path_buf)
+                .unwrap_or_default()
+        })
+        .unwrap_or_default()
+}
+
+// Crop the normalized bounding boxes (path,x,y,w,h) of a detections CSV into JPEG files
+pub fn crop_detections(csv_path: PathBuf, output_dir: PathBuf, padding: f64) -> anyhow::Result<()> {
+    let required: Vec<&str> = [PATH_COLUMN].into_iter().chain(BOX_COLUMNS).collect();
+    check_csv_columns(&csv_path, &required, &ColumnMap::default())?;
+    let df = CsvReadOptions::default()
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(csv_path))?
+        .finish()?;
+    reject_duplicate_csv_columns(&df)?;
+
+    let paths = df.column(PATH_COLUMN)?.str()?;
+    let boxes = BOX_COLUMNS
+        .iter()
+        .map(|name| df.column(name)?.str().cloned())
+        .collect::<PolarsResult<Vec<_>>>()?;
+
+    // Boxes grouped per image, numbered in CSV order
+    let mut detections: BTreeMap<String, Vec<Detection>> = BTreeMap::new();
+    let mut records: Vec<CropRecord> = Vec::new();
+    let mut num_video = 0;
+    for (row, path) in paths.iter().enumerate() {
+        let Some(path) = path.filter(|path| !path.is_empty()) else {
+            continue;
+        };
+        let media = media_path_for(Path::new(path))
+            .map(|media| media.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.to_string());
+        let extension = media_extension(Path::new(&media)).unwrap_or_default();
+        let status = if VIDEO_EXTENSIONS.contains(&extension.as_str()) {
+            num_video += 1;
+            Some("skipped: cropping video frames is not supported yet")
+        } else if !IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+            Some("skipped: not an image")
+        } else {
+            None
+        };
+        if let Some(status) = status {
+            records.push(CropRecord {
+                source_row: row as u32 + 1,
+                path: media,
+                index: 0,
+                crop_path: String::new(),
+                status: status.to_string(),
+            });
+            continue;
+        }
+        let bbox = parse_box(
+            [
+                boxes[0].get(row),
+                boxes[1].get(row),
+                boxes[2].get(row),
+                boxes[3].get(row),
+            ],
+            row,
+        )?;
+        let image_detections = detections.entry(media).or_default();
+        image_detections.push(Detection {
+            source_row: row as u32 + 1,
+            index: image_detections.len() as u32 + 1,
+            bbox,
+        });
+    }
+    if num_video > 0 {
+        println!(
+            "Warning: {num_video} row(s) reference videos, cropping video frames is not supported yet, skipped"
+        );
+    }
+    if detections.is_empty() {
+        return Err(anyhow::anyhow!("No image detections to crop"));
+    }
+
+    let image_paths: Vec<&Path> = detections.keys().map(Path::new).collect();
+    let root = common_root(&image_paths);
+    let num_boxes: usize = detections.values().map(Vec::len).sum();
+    println!(
+        "Cropping {num_boxes} box(es) from {} image(s)",
+        detections.len()
+    );
+    let pb = ServalProgress::new(num_boxes as u64, "cropping");
+    let cropped: Vec<CropRecord> = detections
+        .par_iter()
+        .flat_map_iter(|(path, image_detections)| {
+            let image_path = Path::new(path);
+            let relative_dir = image_path
+                .parent()
+                .and_then(|parent| parent.strip_prefix(&root).ok())
+                .unwrap_or(Path::new(""));
+            let results = crop_image(
+                image_path,
+                image_detections,
+                &output_dir.join(relative_dir),
+                padding,
+            );
+            pb.inc(image_detections.len() as u64);
+            let results: Vec<(u32, anyhow::Result<PathBuf>)> = match results {
+                Ok(results) => results,
+                Err(e) => {
+                    pb.warn("Unreadable images", format!("Warning: {e}"));
+                    image_detections
+                        .iter()
+                        .map(|detection| (detection.source_row, Err(anyhow::anyhow!("{e}"))))
+                        .collect()
+                }
+            };
+            results
+                .into_iter()
+                .zip(image_detections)
+                .map(|((source_row, result), detection)| match result {
+                    Ok(crop_path) => CropRecord {
+                        source_row,
+                        path: path.clone(),
+                        index: detection.index,
+                        crop_path: crop_path.to_string_lossy().into_owned(),
+                        status: "ok".to_string(),
+                    },
+                    Err(e) => {
+                        pb.notice(
+                            "Failed crops",
+                            format!("{path} box {}: {e}", detection.index),
+                        );
+                        CropRecord {
+                            source_row,
+                            path: path.clone(),
+                            index: detection.index,
+                            crop_path: String::new(),
+                            status: format!("failed: {e}"),
+                        }
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    pb.finish();
+    let num_ok = cropped
+        .iter()
+        .filter(|record| record.status == "ok")
+        .count();
+    records.extend(cropped);
+    records.sort_by_key(|record| record.source_row);
+
+    let mut df_manifest = df!(
+        "source_row" => records.iter().map(|record| record.source_row).collect::<Vec<_>>(),
+        PATH_COLUMN => records.iter().map(|record| record.path.as_str()).collect::<Vec<_>>(),
+        "box" => records.iter().map(|record| record.index).collect::<Vec<_>>(),
+        "crop_path" => records.iter().map(|record| record.crop_path.as_str()).collect::<Vec<_>>(),
+        "status" => records.iter().map(|record| record.status.as_str()).collect::<Vec<_>>(),
+    )?;
+    fs::create_dir_all(&output_dir)?;
+    let manifest_path = output_dir.join("crop_manifest.csv");
+    let mut file = fs::File::create(&manifest_path)?;
+    csv_writer(&mut file).finish(&mut df_manifest)?;
+    println!(
+        "{num_ok} crop(s) written to {}, {} row(s) not cropped",
+        output_dir.display(),
+        records.len() - num_ok
+    );
+    println!("Saved manifest to {}", manifest_path.display());
+    Ok(())
+}
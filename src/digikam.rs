@@ -0,0 +1,196 @@
+use crate::reconcile::{correction_rows, media_key, read_csv, read_tagged_files, sidecar_target};
+use crate::schema::{PATH_COLUMN, XMP_UPDATE_COLUMN};
+use crate::utils::{ColumnMap, TagType, XmpUpdateType, csv_writer};
+use polars::prelude::*;
+use rusqlite::{Connection, OpenFlags};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const SQLITE_HEADER: &[u8] = b"SQLite format 3\0";
+
+// Value of a digiKam tag path under the root of `tag_type`, e.g. Species/Serval -> Serval
+fn tag_value(tag_path: &str, tag_type: TagType) -> Option<String> {
+    let tag_path = tag_path.trim();
+    tag_path
+        .strip_prefix(tag_type.digikam_tag_prefix())
+        .or_else(|| tag_path.strip_prefix(tag_type.adobe_tag_prefix()))
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+fn is_sqlite(path: &Path) -> anyhow::Result<bool> {
+    let mut header = [0u8; 16];
+    let mut file = fs::File::open(path)?;
+    Ok(file.read_exact(&mut header).is_ok() && header == SQLITE_HEADER)
+}
+
+// Tag paths of the visible images in digikam4.db, album paths joined to `root`
+fn read_digikam_db(db_path: &Path, root: &Path) -> anyhow::Result<BTreeMap<PathBuf, Vec<String>>> {
+    let connection = Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut tags: HashMap<i64, (i64, String)> = HashMap::new();
+    let mut statement = connection.prepare("SELECT id, pid, name FROM Tags")?;
+    let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+    for row in rows {
+        let (id, pid, name): (i64, Option<i64>, String) = row?;
+        tags.insert(id, (pid.unwrap_or_default(), name));
+    }
+    let tag_path = |mut id: i64| {
+        let mut names = Vec::new();
+        // Bounded walk up the parents, in case of a cycle in a damaged database
+        while let Some((pid, name)) = tags.get(&id) {
+            names.push(name.as_str());
+            if names.len() > tags.len() {
+                break;
+            }
+            id = *pid;
+        }
+        names.reverse();
+        names.join("/")
+    };
+
+    let mut images: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    let mut statement = connection.prepare(
+        "SELECT Albums.relativePath, Images.name, ImageTags.tagid
+         FROM Images
+         JOIN Albums ON Images.album = Albums.id
+         LEFT JOIN ImageTags ON ImageTags.imageid = Images.id
+         WHERE Images.status = 1",
+    )?;
+    let rows = statement.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+    for row in rows {
+        let (album, name, tag_id): (String, String, Option<i64>) = row?;
+        let path = album
+            .split('/')
+            .filter(|part| !part.is_empty())
+            .fold(root.to_path_buf(), |path, part| path.join(part))
+            .join(name);
+        let image_tags = images.entry(path).or_default();
+        if let Some(tag_id) = tag_id {
+            image_tags.push(tag_path(tag_id));
+        }
+    }
+    Ok(images)
+}
+
+// Tag paths of a CSV exported from digiKam, `;` or `,` separated, relative paths joined to `root`
+fn read_digikam_csv(
+    csv_path: &Path,
+    root: &Path,
+    path_column: &str,
+    tags_column: &str,
+) -> anyhow::Result<BTreeMap<PathBuf, Vec<String>>> {
+    let df = read_csv(csv_path, &[path_column, tags_column], &ColumnMap::default())?;
+    let mut images: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    for (path, image_tags) in df
+        .column(path_column)?
+        .str()?
+        .iter()
+        .zip(df.column(tags_column)?.str()?.iter())
+    {
+        let Some(path) = path.filter(|path| !path.is_empty()) else {
+            continue;
+        };
+        images.entry(root.join(path)).or_default().extend(
+            image_tags
+                .unwrap_or_default()
+                .split([';', ','])
+                .filter(|tag| !tag.trim().is_empty())
+                .map(str::to_string),
+        );
+    }
+    Ok(images)
+}
+
+// Compare the tags of a digiKam collection (digikam4.db or a CSV export) with Serval's tags.csv
+// and write the `xmp update` CSV bringing the sidecars in line, tags removed in digiKam as
+// DELETE_TAG rows. digiKam images not found in the tags.csv are listed, not dropped.
+#[allow(clippy::too_many_arguments)]
+pub fn import_digikam(
+    source: PathBuf,
+    root: PathBuf,
+    tags_csv: PathBuf,
+    output_dir: PathBuf,
+    update_type: XmpUpdateType,
+    source_path_column: String,
+    source_tags_column: String,
+    column_map: ColumnMap,
+) -> anyhow::Result<()> {
+    let Some(tag_type) = update_type.tag_type() else {
+        return Err(anyhow::anyhow!(
+            "digiKam import compares tags, {update_type:?} is not a tag type"
+        ));
+    };
+    let tag_column = update_type.col_name();
+    let images = if is_sqlite(&source)? {
+        read_digikam_db(&source, &root)?
+    } else {
+        read_digikam_csv(&source, &root, &source_path_column, &source_tags_column)?
+    };
+    let tagged_files = read_tagged_files(&tags_csv, tag_column, &column_map)?;
+
+    let mut target_paths: Vec<String> = Vec::new();
+    let mut old_values: Vec<String> = Vec::new();
+    let mut new_values: Vec<String> = Vec::new();
+    let mut unmatched: Vec<String> = Vec::new();
+    let (mut num_matched, mut num_changed) = (0, 0);
+    for (path, image_tags) in &images {
+        let Some(tagged_file) = tagged_files.get(&media_key(&path.to_string_lossy())) else {
+            unmatched.push(path.to_string_lossy().into_owned());
+            continue;
+        };
+        num_matched += 1;
+        let values: BTreeSet<String> = image_tags
+            .iter()
+            .filter_map(|tag_path| tag_value(tag_path, tag_type))
+            .collect();
+        let rows = correction_rows(&tagged_file.values, &values);
+        if rows.is_empty() {
+            continue;
+        }
+        num_changed += 1;
+        let sidecar = sidecar_target(&tagged_file.path);
+        for (old_value, new_value) in rows {
+            target_paths.push(sidecar.to_string_lossy().into_owned());
+            old_values.push(old_value);
+            new_values.push(new_value);
+        }
+    }
+
+    fs::create_dir_all(&output_dir)?;
+    let mut df_updates = df!(
+        PATH_COLUMN => target_paths,
+        tag_column => old_values,
+        XMP_UPDATE_COLUMN => new_values,
+    )?;
+    let updates_path = output_dir.join("digikam_updates.csv");
+    let mut file = fs::File::create(&updates_path)?;
+    csv_writer(&mut file).finish(&mut df_updates)?;
+    println!(
+        "{num_matched} of {} digiKam image(s) found in the tags CSV, {num_changed} with changed {tag_column}",
+        images.len()
+    );
+    println!(
+        "Saved to {}, apply with serval xmp update -t {tag_column}",
+        updates_path.display()
+    );
+
+    if !unmatched.is_empty() {
+        let reasons = vec!["not in the tags CSV"; unmatched.len()];
+        let mut df_unmatched = df!(
+            PATH_COLUMN => &unmatched,
+            "reason" => reasons,
+        )?;
+        let unmatched_path = output_dir.join("digikam_unmatched.csv");
+        let mut file = fs::File::create(&unmatched_path)?;
+        csv_writer(&mut file).finish(&mut df_unmatched)?;
+        println!(
+            "Warning: {} digiKam image(s) not matched, check --root and review {}",
+            unmatched.len(),
+            unmatched_path.display()
+        );
+    }
+    Ok(())
+}
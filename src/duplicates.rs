@@ -0,0 +1,185 @@
+use crate::progress::ServalProgress;
+use crate::utils::{csv_writer, hash_file, with_io_permit};
+use chrono::NaiveDateTime;
+use itertools::izip;
+use polars::prelude::*;
+use rayon::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_DUPLICATE_THRESHOLD: f64 = 50.0;
+
+/// What makes two files copies of one another when looking for duplicate deployments
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum DuplicateKey {
+    /// File name, size and datetime
+    Name,
+    /// SHA-256 of the file content, reads every file
+    Hash,
+}
+
+/// Directories holding copies of the same deployment, e.g. backup_2023/dep_x and archive/dep_x
+#[derive(Clone, Copy, Debug)]
+pub struct DuplicateCheck {
+    pub key: DuplicateKey,
+    /// Share of the smaller directory found in the other one, in percent
+    pub threshold: f64,
+}
+
+// Files of two directories with the same key, and the datetimes they span
+#[derive(Default)]
+struct Overlap {
+    shared: usize,
+    start: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+}
+
+impl DuplicateCheck {
+    // None for files that can't be read
+    fn file_keys(
+        &self,
+        files: &[PathBuf],
+        datetimes: &[Option<NaiveDateTime>],
+    ) -> Vec<Option<String>> {
+        match self.key {
+            DuplicateKey::Name => files
+                .iter()
+                .zip(datetimes)
+                .map(|(path, datetime)| {
+                    let name = path.file_name()?.to_string_lossy();
+                    let size = fs::metadata(path).ok()?.len();
+                    let datetime = datetime.map(|datetime| datetime.to_string());
+                    Some(format!("{name}\t{size}\t{}", datetime.unwrap_or_default()))
+                })
+                .collect(),
+            DuplicateKey::Hash => {
+                let pb = ServalProgress::new(files.len() as u64, "hashing files");
+                let keys = files
+                    .par_iter()
+                    .map(|path| {
+                        let hash = with_io_permit(|| hash_file(path)).ok();
+                        pb.inc(1);
+                        hash
+                    })
+                    .collect();
+                pb.finish();
+                keys
+            }
+        }
+    }
+
+    /// Pairs of directories sharing at least `threshold` percent of the files of either one,
+    /// grouped into clusters of copies, written to `output_path`. Returns the number of pairs.
+    pub fn report(
+        &self,
+        files: &[PathBuf],
+        datetimes: &[Option<NaiveDateTime>],
+        output_path: &Path,
+    ) -> anyhow::Result<usize> {
+        let keys = self.file_keys(files, datetimes);
+        let num_unreadable = keys.iter().filter(|key| key.is_none()).count();
+        if num_unreadable > 0 {
+            println!(
+                "Warning: {num_unreadable} file(s) could not be read and are left out of the duplicate check"
+            );
+        }
+
+        // directory -> number of files, key -> (directory, datetime) of its files
+        let mut num_files: BTreeMap<&Path, usize> = BTreeMap::new();
+        let mut copies: HashMap<&str, Vec<(&Path, Option<NaiveDateTime>)>> = HashMap::new();
+        for (path, key, datetime) in izip!(files, &keys, datetimes) {
+            let directory = path.parent().unwrap_or(Path::new(""));
+            *num_files.entry(directory).or_default() += 1;
+            if let Some(key) = key {
+                copies.entry(key).or_default().push((directory, *datetime));
+            }
+        }
+        let mut overlaps: BTreeMap<(&Path, &Path), Overlap> = BTreeMap::new();
+        for files in copies.values() {
+            let directories: BTreeSet<&Path> = files.iter().map(|file| file.0).collect();
+            if directories.len() < 2 {
+                continue;
+            }
+            let datetime = files.iter().find_map(|file| file.1);
+            let directories: Vec<&Path> = directories.into_iter().collect();
+            for (i, a) in directories.iter().enumerate() {
+                for b in &directories[i + 1..] {
+                    let overlap = overlaps.entry((a, b)).or_default();
+                    overlap.shared += 1;
+                    if let Some(datetime) = datetime {
+                        overlap.start =
+                            Some(overlap.start.map_or(datetime, |start| start.min(datetime)));
+                        overlap.end = Some(overlap.end.map_or(datetime, |end| end.max(datetime)));
+                    }
+                }
+            }
+        }
+        let percent =
+            |shared: usize, total: usize| (shared as f64 / total as f64 * 10000.0).round() / 100.0;
+        overlaps.retain(|(a, b), overlap| {
+            percent(overlap.shared, num_files[a]).max(percent(overlap.shared, num_files[b]))
+                >= self.threshold
+        });
+
+        // Directories linked by a pair are one cluster, numbered in directory order
+        let mut cluster_of: HashMap<&Path, usize> = HashMap::new();
+        let mut num_clusters = 0;
+        let linked: BTreeSet<&Path> = overlaps.keys().flat_map(|(a, b)| [*a, *b]).collect();
+        for directory in linked {
+            if cluster_of.contains_key(directory) {
+                continue;
+            }
+            num_clusters += 1;
+            let mut pending = vec![directory];
+            while let Some(current) = pending.pop() {
+                if cluster_of.insert(current, num_clusters).is_some() {
+                    continue;
+                }
+                for (a, b) in overlaps.keys() {
+                    if *a == current {
+                        pending.push(b);
+                    } else if *b == current {
+                        pending.push(a);
+                    }
+                }
+            }
+        }
+        let mut pairs: Vec<(&(&Path, &Path), &Overlap)> = overlaps.iter().collect();
+        pairs.sort_by_key(|(directories, _)| (cluster_of[directories.0], *directories));
+
+        let format_datetime = |datetime: Option<NaiveDateTime>| {
+            datetime.map(|datetime| datetime.format("%Y-%m-%d %H:%M:%S").to_string())
+        };
+        let directory = |path: &Path| path.to_string_lossy().into_owned();
+        let mut df = df!(
+            "cluster" => pairs.iter().map(|(directories, _)| cluster_of[directories.0] as u32).collect::<Vec<_>>(),
+            "directory_a" => pairs.iter().map(|((a, _), _)| directory(a)).collect::<Vec<_>>(),
+            "directory_b" => pairs.iter().map(|((_, b), _)| directory(b)).collect::<Vec<_>>(),
+            "files_a" => pairs.iter().map(|((a, _), _)| num_files[a] as u32).collect::<Vec<_>>(),
+            "files_b" => pairs.iter().map(|((_, b), _)| num_files[b] as u32).collect::<Vec<_>>(),
+            "shared_files" => pairs.iter().map(|(_, overlap)| overlap.shared as u32).collect::<Vec<_>>(),
+            "percent_of_a" => pairs.iter().map(|((a, _), overlap)| percent(overlap.shared, num_files[a])).collect::<Vec<_>>(),
+            "percent_of_b" => pairs.iter().map(|((_, b), overlap)| percent(overlap.shared, num_files[b])).collect::<Vec<_>>(),
+            "overlap_start" => pairs.iter().map(|(_, overlap)| format_datetime(overlap.start)).collect::<Vec<_>>(),
+            "overlap_end" => pairs.iter().map(|(_, overlap)| format_datetime(overlap.end)).collect::<Vec<_>>(),
+        )?;
+        let mut file = fs::File::create(output_path)?;
+        csv_writer(&mut file).finish(&mut df)?;
+        if pairs.is_empty() {
+            println!(
+                "No directories share {}% or more of their files, saved to {}",
+                self.threshold,
+                output_path.display()
+            );
+        } else {
+            println!(
+                "Warning: {} pair(s) of directories in {num_clusters} cluster(s) share {}% or more of their files, likely copies of one deployment counted twice; see {}",
+                pairs.len(),
+                self.threshold,
+                output_path.display()
+            );
+        }
+        Ok(pairs.len())
+    }
+}
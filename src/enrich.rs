@@ -0,0 +1,138 @@
+use crate::utils::{csv_writer, reject_duplicate_csv_columns};
+use polars::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Join key of a path-like value, separators unified and case folded where the filesystem ignores it
+fn normalize_key(value: &str) -> String {
+    let key = value.trim().replace('\\', "/");
+    if cfg!(windows) {
+        key.to_lowercase()
+    } else {
+        key
+    }
+}
+
+fn read_csv(csv_path: &Path, on: &str) -> anyhow::Result<(DataFrame, Vec<Option<String>>)> {
+    let df = CsvReadOptions::default()
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(csv_path.to_path_buf()))?
+        .finish()?;
+    reject_duplicate_csv_columns(&df)?;
+    let keys = df
+        .column(on)
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Join column {on} not found in {}, found: {}",
+                csv_path.display(),
+                df.get_column_names()
+                    .iter()
+                    .map(|name| name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })?
+        .str()?
+        .iter()
+        .map(|value| {
+            value
+                .filter(|value| !value.trim().is_empty())
+                .map(normalize_key)
+        })
+        .collect();
+    Ok((df, keys))
+}
+
+// Left-join enrichment CSVs (OCR, GPS, camera info, hashes...) onto a base tags.csv by `on`
+pub fn enrich_tags(
+    base_csv: PathBuf,
+    enrichments: Vec<PathBuf>,
+    on: String,
+    output: PathBuf,
+) -> anyhow::Result<()> {
+    let (mut df, base_keys) = read_csv(&base_csv, &on)?;
+    let num_rows = df.height();
+
+    for enrichment in &enrichments {
+        let (df_enrich, keys) = read_csv(enrichment, &on)?;
+        let mut rows: HashMap<&str, IdxSize> = HashMap::new();
+        let mut duplicates: Vec<&str> = Vec::new();
+        for (row, key) in keys.iter().enumerate() {
+            let Some(key) = key else {
+                continue;
+            };
+            if rows.insert(key, row as IdxSize).is_some() {
+                duplicates.push(key);
+            }
+        }
+        // A repeated key would multiply the base rows it matches
+        if !duplicates.is_empty() {
+            duplicates.sort_unstable();
+            duplicates.dedup();
+            return Err(anyhow::anyhow!(
+                "{} has {} repeated {on} value(s), joining would duplicate rows: {}{}",
+                enrichment.display(),
+                duplicates.len(),
+                duplicates
+                    .iter()
+                    .take(5)
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                if duplicates.len() > 5 { ", ..." } else { "" }
+            ));
+        }
+
+        let indices: Vec<Option<IdxSize>> = base_keys
+            .iter()
+            .map(|key| key.as_deref().and_then(|key| rows.get(key).copied()))
+            .collect();
+        let num_matched = indices.iter().flatten().count();
+        let df_matched = df_enrich.drop(&on)?.take(&IdxCa::from_iter_options(
+            "rows".into(),
+            indices.into_iter(),
+        ))?;
+
+        // Colliding columns take the enrichment file name as suffix
+        let suffix = enrichment
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut columns = Vec::new();
+        for column in df_matched.columns() {
+            let mut column = column.clone();
+            if df.get_column_index(column.name()).is_some() {
+                let renamed = format!("{}_{suffix}", column.name());
+                if df.get_column_index(&renamed).is_some() {
+                    return Err(anyhow::anyhow!(
+                        "Column {} of {} collides with {renamed}, rename it before enriching",
+                        column.name(),
+                        enrichment.display()
+                    ));
+                }
+                println!("Note: {} renamed to {renamed}", column.name());
+                column.rename(renamed.into());
+            }
+            columns.push(column);
+        }
+        df.hstack_mut(&columns)?;
+        println!(
+            "{}: matched {num_matched}/{num_rows} rows ({:.1}%)",
+            enrichment.display(),
+            if num_rows == 0 {
+                0.0
+            } else {
+                num_matched as f64 / num_rows as f64 * 100.0
+            }
+        );
+    }
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = fs::File::create(&output)?;
+    csv_writer(&mut file).finish(&mut df)?;
+    println!("Saved to {}", output.display());
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use crate::config::RunParams;
+use crate::reconcile::read_csv;
+use crate::schema::{DATETIME_COLUMN, DEPLOYMENT_COLUMN, PATH_COLUMN};
+use crate::utils::{ColumnMap, deployment_from_path};
+use chrono::NaiveDateTime;
+use polars::prelude::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const INDEPENDENCE_PREFIX: &str = "temporal-independence";
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// Capture parameters an independence CSV was made with, as recorded in its params file
+struct EventWindow {
+    min_delta_time: chrono::Duration,
+    compare_to_last_record: bool,
+    target: String,
+    // None when capture read the deployment column written by observe
+    deploy_path_index: Option<i32>,
+}
+
+impl EventWindow {
+    // params_<suffix>.toml written next to temporal-independence_<suffix>.csv
+    fn for_independence_csv(independence_csv: &Path) -> anyhow::Result<Self> {
+        let params_path = independence_csv
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_prefix(INDEPENDENCE_PREFIX))
+            .map(|suffix| {
+                independence_csv
+                    .with_file_name(format!("params{}", suffix.replace(".csv", ".toml")))
+            })
+            .filter(|path| path.is_file())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "No capture params file next to {}, --events-from reads the window from the \
+                     params_*.toml that serval capture writes with temporal-independence_*.csv",
+                    independence_csv.display()
+                )
+            })?;
+        let params = RunParams::read(&params_path)?;
+        let missing = |key: &str| anyhow::anyhow!("{} has no valid {key}", params_path.display());
+        let min_delta_time = params
+            .get("min_delta_time_minutes")
+            .and_then(|value| value.as_integer())
+            .and_then(chrono::Duration::try_minutes)
+            .ok_or_else(|| missing("min_delta_time_minutes"))?;
+        let compare_to_last_record = match params
+            .get("delta_time_compared_to")
+            .and_then(|value| value.as_str())
+        {
+            Some("LastRecord") => true,
+            Some("LastIndependentRecord") => false,
+            _ => return Err(missing("delta_time_compared_to")),
+        };
+        let target = params
+            .get("target")
+            .and_then(|value| value.as_str())
+            .ok_or_else(|| missing("target"))?
+            .to_string();
+        let deploy_path_index = params
+            .get("deployment_path_index")
+            .and_then(|value| value.as_integer())
+            .and_then(|value| i32::try_from(value).ok());
+        Ok(Self {
+            min_delta_time,
+            compare_to_last_record,
+            target,
+            deploy_path_index,
+        })
+    }
+}
+
+fn parse_time(time: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(time.trim(), TIME_FORMAT).ok()
+}
+
+/// Rows of `df_tags` in the events of the independent records listed in `independence_csv`.
+///
+/// An event is its independent record and the records of the same target at the same
+/// deployment that capture counted with it: those less than the window after it (LIR), or
+/// each less than the window after the one before (LR).
+pub fn event_records(
+    df_tags: &DataFrame,
+    independence_csv: &Path,
+) -> anyhow::Result<BooleanChunked> {
+    let window = EventWindow::for_independence_csv(independence_csv)?;
+    let target = window.target.as_str();
+    let df_independent = read_csv(
+        independence_csv,
+        &["deployment", "time", target],
+        &ColumnMap::default(),
+    )?;
+    if df_tags.column(target).is_err() {
+        return Err(anyhow::anyhow!(
+            "The tags CSV has no {target} column to match {} against",
+            independence_csv.display()
+        ));
+    }
+
+    // (deployment, target value) -> times of its independent records
+    let mut starts: HashMap<(String, String), Vec<NaiveDateTime>> = HashMap::new();
+    for (deployment, value, time) in itertools::izip!(
+        df_independent.column("deployment")?.str()?.iter(),
+        df_independent.column(target)?.str()?.iter(),
+        df_independent.column("time")?.str()?.iter(),
+    ) {
+        let (Some(deployment), Some(value), Some(time)) =
+            (deployment, value, time.and_then(parse_time))
+        else {
+            continue;
+        };
+        starts
+            .entry((deployment.to_string(), value.to_string()))
+            .or_default()
+            .push(time);
+    }
+
+    // The deployment column written by observe, as capture prefers it over the path;
+    // camtrap-dp runs have observations, not file paths to find deployments in
+    let deployment_column = df_tags
+        .column(DEPLOYMENT_COLUMN)
+        .ok()
+        .map(|column| column.cast(&DataType::String))
+        .transpose()?;
+    let deployments = deployment_column
+        .as_ref()
+        .map(|column| column.str())
+        .transpose()?;
+    if deployments.is_none() && window.deploy_path_index.is_none() {
+        return Err(anyhow::anyhow!(
+            "The tags CSV has no {DEPLOYMENT_COLUMN} column and the capture params no \
+             deployment_path_index to find deployments in the paths"
+        ));
+    }
+
+    // Dated rows of the tags CSV per (deployment, target value), in time order
+    let mut groups: HashMap<(String, String), Vec<(NaiveDateTime, usize)>> = HashMap::new();
+    for (row, (path, value, time)) in itertools::izip!(
+        df_tags.column(PATH_COLUMN)?.str()?.iter(),
+        df_tags.column(target)?.str()?.iter(),
+        df_tags.column(DATETIME_COLUMN)?.str()?.iter(),
+    )
+    .enumerate()
+    {
+        let (Some(path), Some(value), Some(time)) = (path, value, time.and_then(parse_time)) else {
+            continue;
+        };
+        let deployment = match (deployments, window.deploy_path_index) {
+            (Some(deployments), _) => match deployments.get(row) {
+                Some(deployment) if !deployment.is_empty() => deployment.to_string(),
+                _ => continue,
+            },
+            (None, Some(deploy_path_index)) => {
+                deployment_from_path(&PathBuf::from(path), deploy_path_index)?
+            }
+            (None, None) => unreachable!("checked above"),
+        };
+        let key = (deployment, value.to_string());
+        if starts.contains_key(&key) {
+            groups.entry(key).or_default().push((time, row));
+        }
+    }
+
+    let mut selected = vec![false; df_tags.height()];
+    let mut num_found = 0;
+    for (key, group) in &mut groups {
+        group.sort();
+        let starts = &starts[key];
+        let mut event_start: Option<NaiveDateTime> = None;
+        let mut previous: Option<NaiveDateTime> = None;
+        for &(time, row) in group.iter() {
+            if starts.contains(&time) {
+                if event_start != Some(time) {
+                    num_found += 1;
+                }
+                event_start = Some(time);
+            } else if let Some(start) = event_start {
+                let reference = if window.compare_to_last_record {
+                    previous.unwrap_or(start)
+                } else {
+                    start
+                };
+                if time - reference >= window.min_delta_time {
+                    event_start = None;
+                }
+            }
+            selected[row] = event_start.is_some();
+            previous = Some(time);
+        }
+    }
+    let num_starts: usize = starts.values().map(Vec::len).sum();
+    if num_found < num_starts {
+        println!(
+            "Warning: {} of {num_starts} independent record(s) not found in the tags CSV",
+            num_starts - num_found
+        );
+    }
+    Ok(BooleanChunked::from_slice("event".into(), &selected))
+}
@@ -0,0 +1,341 @@
+use crate::schema::{
+    DEPLOYMENT_ID_COLUMN, FILE_KEY_COLUMN, FILENAME_COLUMN, INDIVIDUAL_COLUMN, MEDIA_PATH_COLUMN,
+    PATH_COLUMN, SPECIES_COLUMN,
+};
+use crate::utils::reject_duplicate_csv_columns;
+use chrono::{DateTime, Local, NaiveDateTime};
+use polars::prelude::*;
+use rusqlite::{Connection, params, types::Value};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Output CSVs picked up by the export, by file name prefix. Longer prefixes first so that
+// e.g. species_stats_by_tagger is not taken for species_stats. Analysis outputs keep their
+// parameters (target, min delta time...) in the table name, as several can sit side by side.
+const EXPORT_OUTPUTS: &[(&str, bool)] = &[
+    ("tags", false),
+    ("species_stats_by_tagger", false),
+    ("species_stats", false),
+    ("temporal-independence", true),
+    ("events", true),
+    ("count_by_deployment", false),
+    ("count_all", false),
+    ("demographics_by_deployment", true),
+    ("demographics", true),
+    ("accumulation_by_deployment", true),
+    ("accumulation", true),
+    ("overlap", true),
+];
+
+// Identifiers stay text even when they look numeric (deployment 2023, individual 007)
+const TEXT_COLUMNS: &[&str] = &[
+    PATH_COLUMN,
+    FILENAME_COLUMN,
+    MEDIA_PATH_COLUMN,
+    FILE_KEY_COLUMN,
+    "deployment",
+    DEPLOYMENT_ID_COLUMN,
+    SPECIES_COLUMN,
+    INDIVIDUAL_COLUMN,
+];
+
+const INDEXED_COLUMNS: &[&str] = &[PATH_COLUMN, "deployment", SPECIES_COLUMN];
+
+const PROVENANCE_TABLE: &str = "serval_provenance";
+
+const DATETIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Clone, Copy, PartialEq)]
+enum SqlType {
+    Integer,
+    Real,
+    Boolean,
+    DateTime,
+    Text,
+}
+
+impl SqlType {
+    fn declared(self) -> &'static str {
+        match self {
+            SqlType::Integer => "INTEGER",
+            SqlType::Real => "REAL",
+            SqlType::Boolean => "BOOLEAN",
+            SqlType::DateTime => "DATETIME",
+            SqlType::Text => "TEXT",
+        }
+    }
+
+    fn value(self, value: Option<&str>) -> Value {
+        let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) else {
+            return Value::Null;
+        };
+        let text = || Value::Text(value.to_string());
+        match self {
+            SqlType::Integer => value.parse().map_or_else(|_| text(), Value::Integer),
+            SqlType::Real => value.parse().map_or_else(|_| text(), Value::Real),
+            SqlType::Boolean => Value::Integer((value == "true") as i64),
+            SqlType::DateTime => parse_datetime(value).map_or_else(text, |datetime| {
+                Value::Text(datetime.format(DATETIME_FORMAT).to_string())
+            }),
+            SqlType::Text => text(),
+        }
+    }
+}
+
+fn parse_datetime(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, DATETIME_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        .ok()
+}
+
+// Narrowest type every non-empty value fits, text when the column is empty
+fn infer_type(values: &StringChunked) -> SqlType {
+    let mut candidates = [
+        SqlType::Integer,
+        SqlType::Real,
+        SqlType::Boolean,
+        SqlType::DateTime,
+    ]
+    .to_vec();
+    let mut any_value = false;
+    for value in values.iter().flatten().map(str::trim) {
+        if value.is_empty() {
+            continue;
+        }
+        any_value = true;
+        candidates.retain(|candidate| match candidate {
+            // A leading zero is an identifier, not a number
+            SqlType::Integer => {
+                value.parse::<i64>().is_ok()
+                    && !(value.trim_start_matches('-').len() > 1
+                        && value.trim_start_matches('-').starts_with('0'))
+            }
+            SqlType::Real => value.parse::<f64>().is_ok_and(f64::is_finite),
+            SqlType::Boolean => value == "true" || value == "false",
+            SqlType::DateTime => parse_datetime(value).is_some(),
+            SqlType::Text => true,
+        });
+        if candidates.is_empty() {
+            break;
+        }
+    }
+    match candidates.first() {
+        Some(&candidate) if any_value => candidate,
+        _ => SqlType::Text,
+    }
+}
+
+fn quote(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+// Table name and parameter suffix of a known output CSV
+fn table_for(csv_path: &Path) -> Option<(String, String)> {
+    if !csv_path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"))
+    {
+        return None;
+    }
+    let stem = csv_path.file_stem()?.to_str()?;
+    EXPORT_OUTPUTS.iter().find_map(|&(prefix, keep_suffix)| {
+        let suffix = if stem == prefix {
+            ""
+        } else {
+            stem.strip_prefix(prefix)?.strip_prefix('_')?
+        };
+        let table = if keep_suffix && !suffix.is_empty() {
+            format!("{prefix}_{suffix}")
+        } else {
+            prefix.to_string()
+        };
+        let table = table
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>()
+            .to_lowercase();
+        Some((table, suffix.to_string()))
+    })
+}
+
+struct ExportSource {
+    path: PathBuf,
+    parameters: String,
+    modified: Option<DateTime<Local>>,
+}
+
+// Known output CSVs of the given files and directories (not recursive), by table name.
+// Timestamped outputs of the same kind replace each other, the latest wins.
+fn collect_sources(inputs: &[PathBuf]) -> anyhow::Result<BTreeMap<String, ExportSource>> {
+    let mut csv_paths = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let mut entries = fs::read_dir(input)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            entries.sort();
+            csv_paths.extend(entries.into_iter().filter(|path| table_for(path).is_some()));
+        } else if input.is_file() {
+            if table_for(input).is_none() {
+                println!(
+                    "Warning: {} is not a known Serval output, skipped",
+                    input.display()
+                );
+                continue;
+            }
+            csv_paths.push(input.clone());
+        } else {
+            return Err(anyhow::anyhow!("{} not found", input.display()));
+        }
+    }
+
+    let mut sources: BTreeMap<String, ExportSource> = BTreeMap::new();
+    for path in csv_paths {
+        let (table, parameters) = table_for(&path).unwrap();
+        let modified = fs::metadata(&path)?.modified().ok().map(DateTime::from);
+        let source = ExportSource {
+            path,
+            parameters,
+            modified,
+        };
+        match sources.get(&table) {
+            Some(existing) if existing.modified >= source.modified => {
+                println!(
+                    "Note: {} skipped, {} is newer",
+                    source.path.display(),
+                    existing.path.display()
+                );
+            }
+            Some(existing) => {
+                println!(
+                    "Note: {} skipped, {} is newer",
+                    existing.path.display(),
+                    source.path.display()
+                );
+                sources.insert(table, source);
+            }
+            None => {
+                sources.insert(table, source);
+            }
+        }
+    }
+    Ok(sources)
+}
+
+// Replace `table` with the content of a CSV, returns the number of rows
+fn write_table(connection: &Connection, table: &str, csv_path: &Path) -> anyhow::Result<usize> {
+    let df = CsvReadOptions::default()
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(csv_path.to_path_buf()))?
+        .finish()?;
+    reject_duplicate_csv_columns(&df)?;
+
+    let columns = df
+        .columns()
+        .iter()
+        .map(|column| {
+            let values = column.str()?;
+            let sql_type = if TEXT_COLUMNS.contains(&column.name().as_str()) {
+                SqlType::Text
+            } else {
+                infer_type(values)
+            };
+            Ok((column.name().to_string(), sql_type, values))
+        })
+        .collect::<PolarsResult<Vec<_>>>()?;
+
+    connection.execute_batch(&format!("DROP TABLE IF EXISTS {}", quote(table)))?;
+    connection.execute_batch(&format!(
+        "CREATE TABLE {} ({})",
+        quote(table),
+        columns
+            .iter()
+            .map(|(name, sql_type, _)| format!("{} {}", quote(name), sql_type.declared()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    ))?;
+    let mut insert = connection.prepare(&format!(
+        "INSERT INTO {} VALUES ({})",
+        quote(table),
+        vec!["?"; columns.len()].join(", ")
+    ))?;
+    for row in 0..df.height() {
+        insert.execute(rusqlite::params_from_iter(
+            columns
+                .iter()
+                .map(|(_, sql_type, values)| sql_type.value(values.get(row))),
+        ))?;
+    }
+    for (name, _, _) in columns
+        .iter()
+        .filter(|(name, _, _)| INDEXED_COLUMNS.contains(&name.as_str()))
+    {
+        connection.execute_batch(&format!(
+            "CREATE INDEX {} ON {} ({})",
+            quote(&format!("{table}_{name}")),
+            quote(table),
+            quote(name)
+        ))?;
+    }
+    Ok(df.height())
+}
+
+// Write the Serval outputs found in `inputs` (output directories or CSVs) as tables of one
+// SQLite database. Tables already in the database are replaced in a single transaction.
+pub fn export_sqlite(inputs: Vec<PathBuf>, database: PathBuf) -> anyhow::Result<()> {
+    let sources = collect_sources(&inputs)?;
+    if sources.is_empty() {
+        return Err(anyhow::anyhow!(
+            "No Serval outputs (tags, species_stats, temporal-independence...) found to export"
+        ));
+    }
+    if let Some(parent) = database.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut connection = Connection::open(&database)?;
+    let transaction = connection.transaction()?;
+    transaction.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            table_name TEXT PRIMARY KEY,
+            source TEXT,
+            parameters TEXT,
+            rows INTEGER,
+            source_modified DATETIME,
+            exported_at DATETIME,
+            serval_version TEXT
+        )",
+        quote(PROVENANCE_TABLE)
+    ))?;
+    let exported_at = Local::now().format(DATETIME_FORMAT).to_string();
+    for (table, source) in &sources {
+        let num_rows = write_table(&transaction, table, &source.path)
+            .map_err(|e| anyhow::anyhow!("Failed to export {}: {e}", source.path.display()))?;
+        transaction.execute(
+            &format!(
+                "INSERT OR REPLACE INTO {} VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                quote(PROVENANCE_TABLE)
+            ),
+            params![
+                table,
+                source.path.to_string_lossy(),
+                source.parameters,
+                num_rows as i64,
+                source
+                    .modified
+                    .map(|modified| modified.format(DATETIME_FORMAT).to_string()),
+                exported_at,
+                env!("CARGO_PKG_VERSION"),
+            ],
+        )?;
+        println!("{table}: {num_rows} rows from {}", source.path.display());
+    }
+    transaction.commit()?;
+    println!(
+        "Exported {} table(s) to {}",
+        sources.len(),
+        database.display()
+    );
+    Ok(())
+}
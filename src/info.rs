@@ -0,0 +1,103 @@
+use crate::config::{CONFIG_FILE, IndeterminateLabels, ServalConfig};
+use crate::tags::DEFAULT_EXCLUDE_TAGS;
+
+/// Serval version, build and platform, as `(key, value)` pairs in display order.
+///
+/// Printed by `serval info` and recorded in the params and schema files of outputs,
+/// so every result can be traced back to the environment that produced it.
+pub fn environment() -> Vec<(String, String)> {
+    let features = env!("SERVAL_FEATURES");
+    let mut environment: Vec<(String, String)> = [
+        ("serval", env!("CARGO_PKG_VERSION")),
+        ("profile", env!("SERVAL_PROFILE")),
+        (
+            "features",
+            if features.is_empty() {
+                "none"
+            } else {
+                features
+            },
+        ),
+        ("rustc", env!("SERVAL_RUSTC")),
+        ("os", std::env::consts::OS),
+        ("arch", std::env::consts::ARCH),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect();
+    environment.extend(
+        env!("SERVAL_DEPENDENCIES")
+            .split(';')
+            .filter_map(|dependency| dependency.split_once('='))
+            .map(|(name, version)| (name.to_string(), version.to_string())),
+    );
+    environment
+}
+
+/// Environment as a TOML table, for params files
+pub fn environment_toml() -> toml::Table {
+    environment()
+        .into_iter()
+        .map(|(key, value)| (key, value.into()))
+        .collect()
+}
+
+/// Environment as a JSON object, for schema files
+pub fn environment_json() -> serde_json::Value {
+    serde_json::Value::Object(
+        environment()
+            .into_iter()
+            .map(|(key, value)| (key, value.into()))
+            .collect(),
+    )
+}
+
+// Environment, default output directories and the configuration in effect here
+fn info_text(default_outputs: &[(String, String)]) -> anyhow::Result<String> {
+    let mut lines: Vec<String> = environment()
+        .into_iter()
+        .map(|(key, value)| format!("{key}: {value}"))
+        .collect();
+
+    lines.push(String::new());
+    lines.push("Default output directories:".to_string());
+    lines.extend(
+        default_outputs
+            .iter()
+            .map(|(command, output)| format!("  {command}: {output}")),
+    );
+
+    lines.push(String::new());
+    let config = ServalConfig::load()?;
+    match config.path() {
+        Some(path) => lines.push(format!("Configuration ({}):", path.display())),
+        None => lines.push(format!("Configuration (no {CONFIG_FILE} found, defaults):")),
+    }
+    lines.extend(
+        config
+            .effective()
+            .into_iter()
+            .map(|line| format!("  {line}")),
+    );
+    lines.push(format!(
+        "  capture built-in excludes = {DEFAULT_EXCLUDE_TAGS:?}"
+    ));
+    lines.push(format!(
+        "  indeterminate labels = {:?}",
+        IndeterminateLabels::current().labels()
+    ));
+    Ok(lines.join("\n"))
+}
+
+/// Print what bug reports need, `bug_report` wraps it in a Markdown block ready to paste
+pub fn print_info(default_outputs: &[(String, String)], bug_report: bool) -> anyhow::Result<()> {
+    let text = info_text(default_outputs)?;
+    if bug_report {
+        println!("### Environment\n");
+        println!("Command that failed: `serval ...` (paste the full command with its flags)\n");
+        println!("```text\n{text}\n```");
+    } else {
+        println!("{text}");
+    }
+    Ok(())
+}
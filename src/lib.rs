@@ -1,3 +1,25 @@
+pub mod analysis;
+pub mod anonymize;
+pub mod archive;
+pub mod audit;
+pub mod backport;
+pub mod compare;
+pub mod config;
+pub mod crop;
+pub mod digikam;
+pub mod duplicates;
+pub mod enrich;
+pub mod events;
+pub mod export;
+pub mod info;
+pub mod lock;
+pub mod progress;
+pub mod propagate;
+pub mod qa;
+pub mod reconcile;
 pub mod schema;
+pub mod snapshot;
 pub mod tags;
 pub mod utils;
+pub mod verify;
+pub mod viewer;
@@ -0,0 +1,268 @@
+use chrono::{DateTime, Local};
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once};
+
+pub const LOCK_FILE: &str = ".serval.lock";
+
+// A lock older than this is taken over when its process can't be checked
+pub const STALE_LOCK_AGE: chrono::Duration = chrono::Duration::hours(24);
+
+// Exit code of a run stopped with Ctrl-C, as for the default SIGINT handling
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+// Locks held by this process, released by their writers once they stop on Ctrl-C
+static HELD_LOCKS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+static CTRLC_HANDLER: Once = Once::new();
+// Set by the first Ctrl-C while locks are held, checked by the writers between files
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// A run stopped by Ctrl-C between two files
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Whether Ctrl-C asked the writers to stop
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// [`Interrupted`] once Ctrl-C was pressed, for writers to stop at a file boundary
+pub fn check_interrupted() -> anyhow::Result<()> {
+    if is_interrupted() {
+        return Err(Interrupted.into());
+    }
+    Ok(())
+}
+
+/// Who holds a lock, as recorded in the lock file
+#[derive(Debug)]
+pub struct LockOwner {
+    pub pid: u32,
+    pub host: String,
+    pub command: String,
+    pub started: DateTime<Local>,
+}
+
+impl LockOwner {
+    fn current() -> Self {
+        LockOwner {
+            pid: std::process::id(),
+            host: host_name(),
+            command: std::env::args().collect::<Vec<_>>().join(" "),
+            started: Local::now(),
+        }
+    }
+
+    fn to_toml(&self) -> String {
+        let mut table = toml::Table::new();
+        table.insert("pid".to_string(), i64::from(self.pid).into());
+        table.insert("host".to_string(), self.host.clone().into());
+        table.insert("command".to_string(), self.command.clone().into());
+        table.insert("started".to_string(), self.started.to_rfc3339().into());
+        toml::to_string(&table).unwrap_or_default()
+    }
+
+    fn from_toml(text: &str) -> Option<Self> {
+        let table: toml::Table = text.parse().ok()?;
+        Some(LockOwner {
+            pid: u32::try_from(table.get("pid")?.as_integer()?).ok()?,
+            host: table.get("host")?.as_str()?.to_string(),
+            command: table
+                .get("command")
+                .and_then(|command| command.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            started: DateTime::parse_from_rfc3339(table.get("started")?.as_str()?)
+                .ok()?
+                .with_timezone(&Local),
+        })
+    }
+
+    /// Left behind by a run that is gone: its process no longer runs on this host, or the lock
+    /// is too old when the process can't be checked (another host, no process check)
+    pub fn is_stale(&self) -> bool {
+        let running = (self.host == host_name())
+            .then(|| process_is_running(self.pid))
+            .flatten();
+        match running {
+            Some(running) => !running,
+            None => Local::now() - self.started > STALE_LOCK_AGE,
+        }
+    }
+}
+
+fn host_name() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+// Whether a process of this host runs, None when it can't be checked
+#[cfg(unix)]
+fn process_is_running(pid: u32) -> Option<bool> {
+    use nix::errno::Errno;
+    use nix::sys::signal::kill;
+    use nix::unistd::Pid;
+
+    let Ok(pid) = i32::try_from(pid) else {
+        return Some(false);
+    };
+    // Signal 0 only checks the process exists, EPERM means it runs as another user
+    Some(matches!(
+        kill(Pid::from_raw(pid), None),
+        Ok(()) | Err(Errno::EPERM)
+    ))
+}
+
+#[cfg(not(unix))]
+fn process_is_running(_pid: u32) -> Option<bool> {
+    None
+}
+
+/// Remove the locks held and exit as interrupted, for Ctrl-C caught while nothing is written
+/// (e.g. at a prompt)
+pub fn exit_interrupted() -> ! {
+    if let Ok(locks) = HELD_LOCKS.lock() {
+        for path in locks.iter() {
+            let _ = fs::remove_file(path);
+        }
+    }
+    std::process::exit(INTERRUPTED_EXIT_CODE);
+}
+
+// The first Ctrl-C lets the writers stop between files and release their locks. A second one
+// exits at once, leaving the locks of files possibly half written, taken over as stale later.
+fn on_ctrlc() {
+    let holds_locks = HELD_LOCKS.lock().is_ok_and(|locks| !locks.is_empty());
+    if !holds_locks {
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        eprintln!("Stopped, the output lock is left in place");
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    eprintln!("Interrupted, stopping after the current file (Ctrl-C again to stop at once)");
+}
+
+fn install_ctrlc_handler() {
+    CTRLC_HANDLER.call_once(|| {
+        let result = ctrlc::set_handler(on_ctrlc);
+        if let Err(e) = result {
+            eprintln!("Lock files won't be removed on Ctrl-C: {e}");
+        }
+    });
+}
+
+/// Lock file in an output directory, so two runs don't write the same outputs at once.
+///
+/// Removed when dropped, also when the run stops on Ctrl-C.
+#[derive(Debug)]
+pub struct OutputLock {
+    path: PathBuf,
+}
+
+impl OutputLock {
+    /// Create `<dir>/.serval.lock`, refusing when another run holds a fresh lock there.
+    /// Stale locks, and with `force` any lock, are taken over with a warning.
+    pub fn acquire(dir: &Path, force: bool) -> anyhow::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(LOCK_FILE);
+        let owner = LockOwner::current();
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    file.write_all(owner.to_toml().as_bytes())?;
+                    break;
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    let holder = match fs::read_to_string(&path) {
+                        Ok(text) => LockOwner::from_toml(&text),
+                        // Released meanwhile
+                        Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                        Err(_) => None,
+                    };
+                    match holder {
+                        Some(holder) if !force && !holder.is_stale() => {
+                            return Err(anyhow::anyhow!(
+                                "{} is in use by another serval run (pid {} on {}, started {}): {}\n\
+                                 Wait for it to finish, choose another --output, or pass \
+                                 --force-lock if that run is gone",
+                                dir.display(),
+                                holder.pid,
+                                holder.host,
+                                holder.started.format("%Y-%m-%d %H:%M:%S"),
+                                holder.command
+                            ));
+                        }
+                        Some(holder) => eprintln!(
+                            "Taking over the lock of {} (pid {} on {}, started {})",
+                            dir.display(),
+                            holder.pid,
+                            holder.host,
+                            holder.started.format("%Y-%m-%d %H:%M:%S")
+                        ),
+                        // Unreadable or half-written, most likely from a crash while locking
+                        None if force || lock_file_is_old(&path) => {
+                            eprintln!("Taking over the unreadable lock {}", path.display())
+                        }
+                        None => {
+                            return Err(anyhow::anyhow!(
+                                "{} is locked by {} that can't be read, pass --force-lock \
+                                 if no other serval run uses it",
+                                dir.display(),
+                                path.display()
+                            ));
+                        }
+                    }
+                    match fs::remove_file(&path) {
+                        Ok(()) => {}
+                        // Another run took it over first, try again against its lock
+                        Err(e) if e.kind() == ErrorKind::NotFound => {}
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        HELD_LOCKS
+            .lock()
+            .map_err(|_| anyhow::anyhow!("Lock registry poisoned"))?
+            .push(path.clone());
+        install_ctrlc_handler();
+        Ok(OutputLock { path })
+    }
+}
+
+fn lock_file_is_old(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map(|modified| DateTime::<Local>::from(modified) < Local::now() - STALE_LOCK_AGE)
+        .unwrap_or(false)
+}
+
+impl Drop for OutputLock {
+    fn drop(&mut self) {
+        if let Ok(mut locks) = HELD_LOCKS.lock() {
+            locks.retain(|path| path != &self.path);
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
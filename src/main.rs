@@ -1,21 +1,94 @@
+mod analysis;
+mod anonymize;
+mod archive;
+mod audit;
+mod backport;
+mod compare;
+mod config;
+mod crop;
+mod digikam;
+mod duplicates;
+mod enrich;
+mod events;
+mod export;
+mod info;
+mod lock;
+mod progress;
+mod propagate;
+mod qa;
+mod reconcile;
 mod schema;
+mod snapshot;
 mod tags;
 mod utils;
+mod verify;
+mod viewer;
 
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use analysis::infer_deployment_activity;
+use audit::{audit_path, audit_show, audit_verify, resolve_audit_log, start_audit};
+use backport::backport_corrections;
+use chrono::FixedOffset;
+use clap::{CommandFactory, Parser, Subcommand};
+use compare::compare_tags;
+use config::{ReviewFilter, ServalConfig, config_set, config_show};
+use crop::crop_detections;
+use digikam::import_digikam;
+use duplicates::{DEFAULT_DUPLICATE_THRESHOLD, DuplicateCheck, DuplicateKey};
+use enrich::enrich_tags;
+use export::export_sqlite;
+use lock::OutputLock;
+use propagate::propagate_tags;
+use reconcile::reconcile_tags;
+use schema::{
+    CAMTRAP_DP_COLUMNS, DATETIME_COLUMN, DEPLOYMENT_ID_COLUMN, OutputKind, PATH_COLUMN,
+    output_schema,
+};
+use snapshot::{snapshot, verify_snapshot};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tags::{
-    extract_resources, get_classifications, get_temporal_independence, init_xmp, update_datetime,
-    update_tags, write_taglist,
+    CAPTURE_REPLAY_FILE, CaptureComparison, CaptureOptions, EXTRACT_REPLAY_FILE, ExtractOptions,
+    KeepRelativeTo, ObserveResume, ObserveSettings, RequireSidecar, capture_exclude_tags,
+    extract_resources, get_classifications, get_temporal_independence, init_xmp, prompt_tag_value,
+    update_datetime, update_tags, write_taglist,
 };
 use utils::{
-    ExtractFilterType, ResourceType, SubdirType, TagType, XmpUpdateType, absolute_path, copy_xmp,
-    deployments_align, deployments_rename, remove_xmp_files, resources_flatten, sync_xmp_directory,
-    sync_xmp_from_csv, tags_csv_translate,
+    ALIGN_FAILED_EXIT_CODE, BalanceBy, ColumnMap, CsvDialect, DatetimeTimezone, DeploymentLookup,
+    ExtractFilterType, GroupBy, IndependenceMode, MultivalueSeparator, OnConflict, Preflight,
+    ResourceType, SidecarConvention, SubdirType, TagType, TagsFormat, UtcOffsets, XmpUpdateType,
+    absolute_path, check_tags_staleness, copy_xmp, deployments_align, deployments_rename,
+    embed_xmp_directory, exclude_output_dir, expand_name_list, parse_column_map_arg,
+    parse_duration_arg, parse_multivalue_separator_arg, parse_percent_arg, parse_size_arg,
+    parse_timezone_arg, parse_utc_offset_arg, parse_window_minutes, remove_xmp_files,
+    resources_flatten, scan_resources, sync_xmp_directory, sync_xmp_from_csv, tags_csv_checklist,
+    tags_csv_translate, xmp_rename_convention,
 };
+use verify::extract_verify_sample;
 
 fn main() -> anyhow::Result<()> {
+    match run() {
+        // The writers stopped between files and released their locks on the way out
+        Err(err) if err.is::<lock::Interrupted>() => {
+            eprintln!("{err}");
+            std::process::exit(lock::INTERRUPTED_EXIT_CODE);
+        }
+        result => result,
+    }
+}
+
+fn run() -> anyhow::Result<()> {
     let args = Cli::parse();
+    progress::configure_output(
+        args.no_progress,
+        args.verbose,
+        args.notice_interval,
+        args.notice_batch,
+        args.log_file.as_deref(),
+    )?;
+    utils::configure_parallelism(args.threads, args.io_concurrency)?;
+    utils::configure_csv_dialect(args.csv_dialect);
+    utils::configure_ignores(&args.ignore, args.no_default_ignores)?;
+    config::configure_indeterminate_labels(args.indeterminate)?;
 
     match args.command {
         Commands::Align {
@@ -26,29 +99,64 @@ fn main() -> anyhow::Result<()> {
             dryrun,
             move_mode,
             keep_first_subdir,
+            structure,
+            only,
+            skip,
+            jobs,
+            fail_fast,
         } => {
+            let path = absolute_path(path)?;
+            let mut preflight = Preflight::default();
+            preflight.input_dir(&path);
+            if let Some(deploy_table) = &deploy_table {
+                preflight.csv_columns(deploy_table, &[DEPLOYMENT_ID_COLUMN], &ColumnMap::default());
+            }
+            if !dryrun {
+                preflight.output_dir(&output);
+            }
+            preflight.finish()?;
+            let excluded_dir = exclude_output_dir(&path, &output)?;
+            let lock = (!dryrun)
+                .then(|| OutputLock::acquire(&output, args.force_lock))
+                .transpose()?;
             if let Some(deploy_table) = deploy_table {
                 println!("Aligning deployments in {}", path.display());
-                deployments_align(
-                    absolute_path(path)?,
+                let report = deployments_align(
+                    path,
                     output,
                     deploy_table,
                     type_resource,
                     dryrun,
                     move_mode,
                     keep_first_subdir,
+                    structure,
+                    expand_name_list(only)?,
+                    expand_name_list(skip)?,
+                    jobs,
+                    fail_fast,
+                    excluded_dir.as_deref(),
                 )?;
+                if !report.is_complete() {
+                    drop(lock);
+                    std::process::exit(ALIGN_FAILED_EXIT_CODE);
+                }
             } else {
                 println!("Flatten resources in {}", path.display());
-                resources_flatten(
-                    absolute_path(path)?,
+                let summary = resources_flatten(
+                    path,
                     output,
                     type_resource,
                     dryrun,
                     move_mode,
                     false,
                     keep_first_subdir,
+                    fail_fast,
+                    excluded_dir.as_deref(),
                 )?;
+                if !summary.failed.is_empty() {
+                    drop(lock);
+                    std::process::exit(ALIGN_FAILED_EXIT_CODE);
+                }
             }
         }
         Commands::Observe {
@@ -58,27 +166,112 @@ fn main() -> anyhow::Result<()> {
             video,
             image,
             debug,
+            file_timeout,
+            unique_name,
+            pair_media,
+            include_trash,
+            exif_fallback,
+            gps,
+            camera_info,
+            extra_tags,
+            event_gap,
+            derive_dateparts,
+            strict_datetime,
+            timezone,
+            on_conflict,
+            force,
+            scan_only,
+            utc_offset,
+            deploy_table,
+            deploy_level,
+            anonymize_paths,
+            detect_duplicate_deployments,
+            duplicate_threshold,
+            duplicate_key,
+            resume,
+            check_mtime,
+            review,
+            no_format,
+            format,
         } => {
-            let resource_type = if xmp {
-                utils::ResourceType::Xmp
-            } else if video {
-                if image {
+            let media_dir = absolute_path(media_dir)?;
+            let mut preflight = Preflight::default();
+            preflight.input_dir(&media_dir);
+            if !scan_only {
+                preflight.output_dir(&output);
+            }
+            if let Some(deploy_table) = &deploy_table {
+                preflight.csv_columns(deploy_table, &[DEPLOYMENT_ID_COLUMN], &ColumnMap::default());
+            }
+            preflight.finish()?;
+            if scan_only {
+                scan_resources(media_dir)?;
+            } else {
+                let _lock = OutputLock::acquire(&output, args.force_lock)?;
+                let resource_type = if xmp {
+                    utils::ResourceType::Xmp
+                } else if video {
+                    if image {
+                        utils::ResourceType::Media
+                    } else {
+                        utils::ResourceType::Video
+                    }
+                } else if image {
+                    utils::ResourceType::Image
+                } else {
                     utils::ResourceType::Media
+                };
+                let extra_tags = if extra_tags.is_empty() {
+                    ServalConfig::load()?.string_list("tags.extra")?
                 } else {
-                    utils::ResourceType::Video
-                }
-            } else if image {
-                utils::ResourceType::Image
-            } else {
-                utils::ResourceType::Media
-            };
-            get_classifications(
-                absolute_path(media_dir)?,
-                output,
-                resource_type,
-                debug,
-                false,
-            )?;
+                    extra_tags
+                };
+                get_classifications(
+                    media_dir,
+                    output,
+                    resource_type,
+                    ObserveSettings {
+                        debug_mode: debug,
+                        file_timeout,
+                        unique_name,
+                        pair_media,
+                        include_trash,
+                        exif_fallback,
+                        gps,
+                        camera_info,
+                        extra_tags,
+                        event_gap,
+                        derive_dateparts,
+                        strict_datetime,
+                        timezone,
+                        utc_offsets: (utc_offset.is_some() || deploy_table.is_some())
+                            .then(|| UtcOffsets::new(utc_offset, deploy_table.as_deref()))
+                            .transpose()?,
+                        deployment_lookup: match (deploy_level, &deploy_table) {
+                            (Some(level), _) => Some(DeploymentLookup::Level(level)),
+                            (None, Some(deploy_table)) => {
+                                Some(DeploymentLookup::from_deploy_table(deploy_table)?)
+                            }
+                            (None, None) => None,
+                        },
+                        anonymize_paths,
+                        duplicate_check: detect_duplicate_deployments.then_some(DuplicateCheck {
+                            key: duplicate_key,
+                            threshold: duplicate_threshold,
+                        }),
+                        resume: resume.map(|tags| ObserveResume { tags, check_mtime }),
+                        review_view: review,
+                        no_format,
+                        tags_format: format,
+                        on_conflict: if force {
+                            OnConflict::Overwrite
+                        } else {
+                            on_conflict
+                        },
+                        ..Default::default()
+                    },
+                )?;
+            }
         }
         Commands::Rename {
             project_dir,
@@ -103,67 +296,321 @@ fn main() -> anyhow::Result<()> {
             event,
             no_exclude,
             camtrap_dp,
+            check_stale,
+            fail_if_stale,
+            accumulation,
+            accumulation_step,
+            per_deployment,
+            deploy_table,
+            overlap,
+            demographics,
+            geojson,
+            no_format,
+            derive_dateparts,
+            carry_columns,
+            gap_histogram,
+            compare,
+            compare_modes,
+            anonymize_paths,
+            exclude,
+            no_default_excludes,
+            include_review,
+            replay,
+            on_conflict,
+            path_column,
+            column_map,
+            multivalue_separator,
         } => {
+            let column_map = column_map
+                .unwrap_or_default()
+                .with_path_column(path_column)?;
+            let mut preflight = Preflight::default();
+            if camtrap_dp {
+                preflight.csv_columns(&csv_path, CAMTRAP_DP_COLUMNS, &ColumnMap::default());
+            } else {
+                preflight.csv_columns(&csv_path, &[PATH_COLUMN, DATETIME_COLUMN], &column_map);
+            }
+            if let Some(deploy_table) = &deploy_table {
+                preflight.input_file(deploy_table);
+            }
+            preflight.output_dir(&output);
+            preflight.finish()?;
+            let _lock = OutputLock::acquire(&output, args.force_lock)?;
+            // camtrap-dp observations carry no file paths to check
+            if !camtrap_dp {
+                check_tags_staleness(&csv_path, check_stale, fail_if_stale, &column_map)?;
+            }
+            let config = ServalConfig::load()?;
+            let exclude_tags =
+                capture_exclude_tags(&config, exclude, no_default_excludes, no_exclude)?;
+            let replay = replay_path(replay, &output, CAPTURE_REPLAY_FILE);
             get_temporal_independence(
                 absolute_path(csv_path)?,
                 output,
-                event,
-                no_exclude,
-                camtrap_dp,
+                CaptureOptions {
+                    event,
+                    no_exclude,
+                    camtrap_dp,
+                    accumulation: accumulation.then_some(accumulation_step),
+                    per_deployment,
+                    deploy_table,
+                    overlap,
+                    demographics,
+                    geojson,
+                    no_format,
+                    derive_dateparts,
+                    carry_columns,
+                    gap_histogram,
+                    compare: (!compare.is_empty()).then_some(CaptureComparison {
+                        windows: compare,
+                        modes: compare_modes,
+                    }),
+                    anonymize_paths,
+                    exclude_tags,
+                    review: ReviewFilter::from_config(&config)?,
+                    include_review,
+                    on_conflict,
+                    column_map,
+                    multivalue_separator,
+                    replay,
+                    ..Default::default()
+                },
             )?;
         }
+        Commands::Effort {
+            csv_path,
+            gap_threshold,
+            path_column,
+            column_map,
+            output,
+        } => {
+            let column_map = column_map
+                .unwrap_or_default()
+                .with_path_column(path_column)?;
+            infer_deployment_activity(absolute_path(csv_path)?, output, gap_threshold, column_map)?;
+        }
         Commands::Extract {
             csv_path,
             value,
             filter_type,
+            custom_column,
             rename,
             skip_existing,
             output,
             use_subdir,
             subdir_type,
+            group_by,
+            check_stale,
+            fail_if_stale,
+            path_column,
+            column_map,
+            multivalue_separator,
+            per_species_limit,
+            balance_by,
+            seed,
+            require_mtime,
+            require_sidecar,
+            strict,
+            review_only,
+            whole_event,
+            events_from,
+            max_size,
+            keep_relative_to,
+            include_indeterminate,
+            taglist,
+            verify_sample,
+            replay,
         } => {
+            let column_map = column_map
+                .unwrap_or_default()
+                .with_path_column(path_column)?;
+            let mut preflight = Preflight::default();
+            preflight.csv_columns(&csv_path, &[PATH_COLUMN], &column_map);
+            if let Some(taglist) = &taglist {
+                preflight.input_file(taglist);
+            }
+            if let Some(verify_sample) = &verify_sample {
+                preflight.input_file(verify_sample);
+            }
+            if let Some(events_from) = &events_from {
+                preflight.input_file(events_from);
+            }
+            preflight.output_dir(&output);
+            preflight.finish()?;
+            let _lock = OutputLock::acquire(&output, args.force_lock)?;
+            check_tags_staleness(&csv_path, check_stale, fail_if_stale, &column_map)?;
+            if let Some(verify_sample) = verify_sample {
+                return extract_verify_sample(csv_path, verify_sample, output, column_map);
+            }
+            let replay = replay_path(replay, &output, EXTRACT_REPLAY_FILE);
+            let review = if review_only {
+                Some(ReviewFilter::from_config(&ServalConfig::load()?)?)
+            } else {
+                None
+            };
+            // The review and event filters replace the filter type and value, their name the output's
+            let filter_type = filter_type.unwrap_or(ExtractFilterType::Custom);
+            let value = match value {
+                Some(value) => value,
+                None if review_only => "review".to_string(),
+                None if events_from.is_some() => "events".to_string(),
+                None => {
+                    let tag_type = match filter_type {
+                        ExtractFilterType::Species => TagType::Species,
+                        ExtractFilterType::Individual => TagType::Individual,
+                        _ => {
+                            return Err(anyhow::anyhow!(
+                                "--value is required for the {filter_type:?} filter"
+                            ));
+                        }
+                    };
+                    prompt_tag_value(
+                        &csv_path,
+                        tag_type,
+                        taglist,
+                        &column_map,
+                        multivalue_separator.as_ref(),
+                    )?
+                }
+            };
             extract_resources(
                 value,
                 filter_type,
-                rename,
-                skip_existing,
                 csv_path,
                 output,
-                use_subdir,
-                subdir_type,
+                ExtractOptions {
+                    custom_column,
+                    rename,
+                    skip_existing,
+                    use_subdir,
+                    subdir_value: subdir_type,
+                    group_by,
+                    column_map,
+                    multivalue_separator,
+                    per_species_limit,
+                    balance_by,
+                    seed,
+                    require_mtime,
+                    require_sidecar: require_sidecar.then_some(if strict {
+                        RequireSidecar::Strict
+                    } else {
+                        RequireSidecar::Report
+                    }),
+                    review,
+                    whole_event,
+                    events_from,
+                    max_size,
+                    keep_relative_to,
+                    include_indeterminate,
+                    replay,
+                    ..Default::default()
+                },
             )?;
         }
+        Commands::Audit(audit_cmd) => match audit_cmd {
+            AuditCommands::Show { path, log } => audit_show(&resolve_audit_log(log)?, &path)?,
+            AuditCommands::Verify { log } => audit_verify(&resolve_audit_log(log)?)?,
+        },
+        Commands::Config(config_cmd) => match config_cmd {
+            ConfigCommands::Set { key, value } => config_set(key, value)?,
+            ConfigCommands::Show => config_show()?,
+        },
         Commands::Xmp(xmp_cmd) => match xmp_cmd {
             XmpCommands::Copy {
                 source_dir,
                 output_dir,
             } => {
-                copy_xmp(absolute_path(source_dir)?, output_dir)?;
+                let source_dir = absolute_path(source_dir)?;
+                let excluded_dir = exclude_output_dir(&source_dir, &output_dir)?;
+                copy_xmp(source_dir, output_dir, excluded_dir.as_deref())?;
             }
-            XmpCommands::Init { source_dir, info } => {
-                init_xmp(absolute_path(source_dir)?, info)?;
+            XmpCommands::Init {
+                source_dir,
+                info,
+                file_timeout,
+                template,
+                deploy_level,
+            } => {
+                init_xmp(
+                    absolute_path(source_dir)?,
+                    info,
+                    file_timeout,
+                    template,
+                    deploy_level,
+                )?;
             }
             XmpCommands::Update {
                 csv_path,
                 tag_type,
                 datetime,
+                tagger,
+                path_column,
+                verify_roundtrip,
             } => {
+                let column_map = ColumnMap::from_path_column(path_column);
+                start_audit_log(args.no_audit)?;
                 if datetime {
-                    update_datetime(absolute_path(csv_path)?)?;
+                    update_datetime(
+                        absolute_path(csv_path)?,
+                        tagger,
+                        verify_roundtrip,
+                        column_map,
+                    )?;
                 } else {
                     let tag_type =
                         tag_type.ok_or_else(|| anyhow::anyhow!("Tag type is required"))?;
-                    update_tags(absolute_path(csv_path)?, tag_type)?;
+                    update_tags(
+                        absolute_path(csv_path)?,
+                        tag_type,
+                        tagger,
+                        verify_roundtrip,
+                        column_map,
+                    )?;
                 }
             }
             XmpCommands::Remove { source_dir } => {
+                start_audit_log(args.no_audit)?;
                 remove_xmp_files(absolute_path(source_dir)?)?;
             }
-            XmpCommands::Sync { dir, csv } => {
+            XmpCommands::Embed {
+                dir,
+                only_images,
+                verify,
+                remove_sidecars_after,
+            } => {
+                if remove_sidecars_after {
+                    start_audit_log(args.no_audit)?;
+                }
+                embed_xmp_directory(
+                    absolute_path(dir)?,
+                    only_images,
+                    verify,
+                    remove_sidecars_after,
+                )?;
+            }
+            XmpCommands::RenameConvention { dir, to, dryrun } => {
+                if !dryrun {
+                    start_audit_log(args.no_audit)?;
+                }
+                xmp_rename_convention(absolute_path(dir)?, to, dryrun)?;
+            }
+            XmpCommands::Sync {
+                dir,
+                csv,
+                check,
+                output,
+                path_column,
+            } => {
+                // --check and --output require each other
+                let check = output.filter(|_| check);
                 if let Some(dir) = dir {
-                    sync_xmp_directory(absolute_path(dir)?)?;
+                    sync_xmp_directory(absolute_path(dir)?, check.as_deref())?;
                 } else if let Some(csv) = csv {
-                    sync_xmp_from_csv(absolute_path(csv)?)?;
+                    sync_xmp_from_csv(
+                        absolute_path(csv)?,
+                        check.as_deref(),
+                        ColumnMap::from_path_column(path_column),
+                    )?;
                 } else {
                     return Err(anyhow::anyhow!(
                         "Either --csv or directory path must be specified"
@@ -177,6 +624,9 @@ fn main() -> anyhow::Result<()> {
             output,
             from,
             to,
+            add_columns,
+            on_conflict,
+            column_map,
         } => {
             println!("Translate tags in {}", csv_path.display());
             tags_csv_translate(
@@ -184,19 +634,274 @@ fn main() -> anyhow::Result<()> {
                 absolute_path(taglist_path)?,
                 output,
                 &from,
-                &to,
+                to.as_deref(),
+                &add_columns,
+                on_conflict,
+                column_map.unwrap_or_default(),
             )?;
         }
+        Commands::Checklist {
+            tags,
+            list,
+            match_column,
+            columns,
+            output,
+            column_map,
+        } => {
+            tags_csv_checklist(
+                absolute_path(tags)?,
+                absolute_path(list)?,
+                output,
+                &match_column,
+                columns,
+                column_map.unwrap_or_default(),
+            )?;
+        }
+        Commands::Compare {
+            a,
+            b,
+            key,
+            tag_type,
+            output,
+        } => {
+            compare_tags(absolute_path(a)?, absolute_path(b)?, key, tag_type, output)?;
+        }
+        Commands::Crop {
+            csv,
+            output,
+            padding,
+        } => {
+            crop_detections(absolute_path(csv)?, output, padding)?;
+        }
+        Commands::Enrich {
+            base,
+            with,
+            on,
+            output,
+        } => {
+            enrich_tags(base, with, on, output)?;
+        }
+        Commands::Propagate {
+            tags,
+            window,
+            apply_xmp,
+            path_column,
+            column_map,
+            output,
+        } => {
+            let column_map = column_map
+                .unwrap_or_default()
+                .with_path_column(path_column)?;
+            if apply_xmp {
+                start_audit_log(args.no_audit)?;
+            }
+            propagate_tags(absolute_path(tags)?, window, output, apply_xmp, column_map)?;
+        }
+        Commands::Reconcile {
+            original,
+            reviewed,
+            manifest,
+            tag_type,
+            path_column,
+            column_map,
+            output,
+        } => {
+            let column_map = column_map
+                .unwrap_or_default()
+                .with_path_column(path_column)?;
+            reconcile_tags(
+                absolute_path(original)?,
+                absolute_path(reviewed)?,
+                absolute_path(manifest)?,
+                output,
+                tag_type,
+                column_map,
+            )?;
+        }
+        Commands::Backport {
+            reviewed,
+            original,
+            tags,
+            path_column,
+            column_map,
+            output,
+        } => {
+            let column_map = column_map
+                .unwrap_or_default()
+                .with_path_column(path_column)?;
+            backport_corrections(
+                absolute_path(reviewed)?,
+                absolute_path(original)?,
+                absolute_path(tags)?,
+                output,
+                column_map,
+            )?;
+        }
+        Commands::Digikam {
+            source,
+            root,
+            tags,
+            tag_type,
+            source_path_column,
+            source_tags_column,
+            path_column,
+            column_map,
+            output,
+        } => {
+            let column_map = column_map
+                .unwrap_or_default()
+                .with_path_column(path_column)?;
+            import_digikam(
+                absolute_path(source)?,
+                absolute_path(root)?,
+                absolute_path(tags)?,
+                output,
+                tag_type,
+                source_path_column,
+                source_tags_column,
+                column_map,
+            )?;
+        }
+        Commands::Export { inputs, sqlite } => {
+            export_sqlite(inputs, sqlite)?;
+        }
+        Commands::Snapshot { dir, output } => {
+            snapshot(absolute_path(dir)?, output)?;
+        }
+        Commands::VerifySnapshot { dir, manifest } => {
+            verify_snapshot(absolute_path(dir)?, manifest)?;
+        }
+        Commands::Schema { output } => {
+            println!("{}", serde_json::to_string_pretty(&output_schema(output))?);
+        }
+        Commands::Info { bug_report } => {
+            info::print_info(&default_outputs(), bug_report)?;
+        }
+        Commands::Qa {
+            project_dir,
+            counts,
+            column,
+            tolerance,
+            output,
+        } => {
+            let project_dir = absolute_path(project_dir)?;
+            let mut preflight = Preflight::default();
+            preflight.input_dir(&project_dir);
+            if let Some(deploy_table) = &counts {
+                preflight.csv_columns(
+                    deploy_table,
+                    &[DEPLOYMENT_ID_COLUMN, column.as_str()],
+                    &ColumnMap::default(),
+                );
+            }
+            preflight.output_dir(&output);
+            preflight.finish()?;
+            let report = qa::run_qa(
+                &project_dir,
+                counts
+                    .as_deref()
+                    .map(|deploy_table| (deploy_table, column.as_str())),
+                tolerance,
+                &output,
+            )?;
+            if report.num_failures() > 0 {
+                std::process::exit(qa::QA_FAILED_EXIT_CODE);
+            }
+        }
     }
     Ok(())
 }
 
+// Default --output of each command, e.g. observe: ./serval_output/serval_observe
+fn default_outputs() -> Vec<(String, String)> {
+    fn collect(command: &clap::Command, prefix: &str, outputs: &mut Vec<(String, String)>) {
+        for subcommand in command.get_subcommands() {
+            let name = format!("{prefix}{}", subcommand.get_name());
+            if let Some(default) = subcommand
+                .get_arguments()
+                .find(|arg| arg.get_id() == "output")
+                .and_then(|arg| arg.get_default_values().first())
+            {
+                outputs.push((name.clone(), default.to_string_lossy().into_owned()));
+            }
+            collect(subcommand, &format!("{name} "), outputs);
+        }
+    }
+    let mut outputs = Vec::new();
+    collect(&Cli::command(), "", &mut outputs);
+    outputs
+}
+
+// Sidecar changes of the run are appended to the project's audit log unless --no-audit
+fn start_audit_log(no_audit: bool) -> anyhow::Result<()> {
+    if no_audit {
+        return Ok(());
+    }
+    let path = audit_path(&ServalConfig::load()?)?;
+    start_audit(&path)?;
+    println!("Logging sidecar changes to {}", path.display());
+    Ok(())
+}
+
+// --replay without a file reads the answers saved in the output directory
+fn replay_path(replay: Option<Option<PathBuf>>, output: &Path, file_name: &str) -> Option<PathBuf> {
+    replay.map(|replay| replay.unwrap_or_else(|| output.join(file_name)))
+}
+
 #[derive(Parser, Debug)]
 #[command(name = "Serval")]
 #[command(author, version, about)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Hide progress bars (e.g. when redirecting output to a log file)
+    #[arg(long, global = true)]
+    no_progress: bool,
+    /// Print every per-file notice instead of a counted summary
+    #[arg(long, global = true)]
+    verbose: bool,
+    /// Append every per-file notice and warning to FILE, whatever the verbosity
+    #[arg(long, global = true, value_name = "FILE")]
+    log_file: Option<PathBuf>,
+    /// Print the notice counts so far every SECS seconds during a run (0: only at the end)
+    #[arg(long, global = true, value_name = "SECS", default_value_t = 30)]
+    notice_interval: u64,
+    /// Print the notice counts so far every N notices during a run (0: only at the end)
+    #[arg(long, global = true, value_name = "N", default_value_t = 10_000)]
+    notice_batch: u64,
+    /// Number of worker threads for parallel stages (default: all cores)
+    #[arg(long, global = true, value_name = "N", env = "SERVAL_THREADS")]
+    threads: Option<usize>,
+    /// Maximum number of files opened/copied concurrently in parallel stages
+    #[arg(long, global = true, value_name = "N")]
+    io_concurrency: Option<usize>,
+    /// Take over the output directory's lock file, when the run holding it is known to be gone
+    #[arg(long, global = true)]
+    force_lock: bool,
+    /// Layout of the output CSVs, excel-eu for spreadsheets in European locales (serval itself
+    /// reads back the default dialect)
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIALECT",
+        value_enum,
+        default_value_t
+    )]
+    csv_dialect: CsvDialect,
+    /// Skip files and directories whose name or path relative to the scanned directory matches
+    /// GLOB, on top of the patterns of a .servalignore there (repeatable)
+    #[arg(long, global = true, value_name = "GLOB")]
+    ignore: Vec<String>,
+    /// Also scan hidden entries and 精选 directories, skipped by default
+    #[arg(long, global = true)]
+    no_default_ignores: bool,
+    /// Species labels of animals that could not be identified, comma-separated (default:
+    /// species.indeterminate in serval.toml, else "Unidentified,Unknown")
+    #[arg(long, global = true, value_name = "LABELS", value_delimiter = ',')]
+    indeterminate: Option<Vec<String>>,
+    /// Do not log sidecar changes to the project's audit log (serval_audit.jsonl)
+    #[arg(long, global = true)]
+    no_audit: bool,
 }
 
 #[derive(Debug, Subcommand)]
@@ -224,6 +929,28 @@ enum Commands {
         /// Keep the first subdirectory as an output folder (flatten mode)
         #[arg(long)]
         keep_first_subdir: bool,
+        /// Output structure per deployment (align mode), placeholders are deploy table columns
+        /// plus {collection}, e.g. "{year}/{collection}/{deploymentID}"
+        #[arg(long, value_name = "TEMPLATE", requires = "deploy_table")]
+        structure: Option<String>,
+        /// Only align these deploymentIDs (comma separated, or @file with one per line)
+        #[arg(long, value_name = "DEPLOYMENTS", requires = "deploy_table")]
+        only: Vec<String>,
+        /// Skip these deploymentIDs (comma separated, or @file with one per line)
+        #[arg(long, value_name = "DEPLOYMENTS", requires = "deploy_table")]
+        skip: Vec<String>,
+        /// Number of deployments aligned concurrently, keep low for spinning disks
+        #[arg(
+            short,
+            long,
+            value_name = "N",
+            default_value_t = 3,
+            requires = "deploy_table"
+        )]
+        jobs: usize,
+        /// Stop at the first file that can't be copied or moved, instead of reporting it at the end
+        #[arg(long)]
+        fail_fast: bool,
     },
     /// Retrieve tags from media metadata
     #[command(arg_required_else_help = true)]
@@ -249,6 +976,111 @@ enum Commands {
         /// Debug mode
         #[arg(short, long)]
         debug: bool,
+        /// Skip files whose metadata read takes longer than this (e.g. 30s), no timeout by default
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration_arg)]
+        file_timeout: Option<Duration>,
+        /// Add a file_key column (path hash + filename) that stays unique across deployments
+        #[arg(long)]
+        unique_name: bool,
+        /// Add media_path, sidecar_exists and media_exists columns pairing each file with its media/sidecar
+        #[arg(long)]
+        pair_media: bool,
+        /// Also read files digiKam moved to its trash (.dtrash), marked in a trashed column
+        #[arg(long)]
+        include_trash: bool,
+        /// Read the datetime of images without one in XMP from their EXIF DateTimeOriginal,
+        /// the source of each datetime goes to a datetime_source column
+        #[arg(long, conflicts_with = "xmp")]
+        exif_fallback: bool,
+        /// Read the GPS coordinates of images without them in XMP from their EXIF GPS block
+        #[arg(long)]
+        gps: bool,
+        /// Add camera_make, camera_model and camera_serial columns, to tell which camera took each file
+        #[arg(long)]
+        camera_info: bool,
+        /// Also read the tags under this category, e.g. Behaviour for Behaviour|Grazing, into a
+        /// lowercase column (repeatable, tags.extra in serval.toml when not given)
+        #[arg(long = "extra-tag", value_name = "CATEGORY")]
+        extra_tags: Vec<String>,
+        /// Group bursts into an event_id column, a new event starting when the gap to the
+        /// previous file of the deployment (else directory) exceeds this many seconds, the same
+        /// sequences as propagate --window
+        #[arg(long, value_name = "SECONDS")]
+        event_gap: Option<u64>,
+        /// Add date, year, month and hour columns from the datetime, for pivot tables
+        #[arg(long)]
+        derive_dateparts: bool,
+        /// Abort on a datetime that can't be parsed, instead of leaving it empty and listing
+        /// the file in the errors CSV
+        #[arg(long)]
+        strict_datetime: bool,
+        /// UTC offsets of the XMP datetimes: `strip` them (camera time as shown), `keep` them
+        /// (UTC instants, written with +00:00) or convert to the local time of an IANA zone
+        /// like Asia/Shanghai
+        #[arg(long, value_name = "ZONE", default_value = "strip", value_parser = parse_timezone_arg)]
+        timezone: DatetimeTimezone,
+        /// When tags/species_stats CSVs of the same name exist from an earlier run
+        #[arg(long, value_enum, default_value_t = OnConflict::Version)]
+        on_conflict: OnConflict,
+        /// Overwrite outputs of an earlier run, same as --on-conflict overwrite
+        #[arg(long, conflicts_with = "on_conflict")]
+        force: bool,
+        /// Only count images, videos and sidecars per subdirectory (and what is ignored), no file is read
+        #[arg(long)]
+        scan_only: bool,
+        /// Add a datetime_utc column, camera clocks being this far from UTC (e.g. +08:00, +05:30)
+        #[arg(long, value_name = "OFFSET", value_parser = parse_utc_offset_arg, allow_hyphen_values = true)]
+        utc_offset: Option<FixedOffset>,
+        /// Deploy table with per-deployment utcOffset values for datetime_utc, --utc-offset covers the rest;
+        /// its deploymentIDs also fill a deployment column from the directories of each path
+        #[arg(long, value_name = "CSV")]
+        deploy_table: Option<PathBuf>,
+        /// Add a deployment column, the directory at this level of the path (numbered as in the capture prompt)
+        #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(i32).range(1..))]
+        deploy_level: Option<i32>,
+        /// Replace paths, filenames and file keys with salted hashes and deployments with
+        /// pseudonyms (site_001) in tags and data_quality, the mapping back goes to anonymize_key_*.csv
+        #[arg(long)]
+        anonymize_paths: bool,
+        /// Report directories holding copies of the same files (e.g. backup_2023/dep_x and archive/dep_x)
+        /// in duplicate_deployments_*.csv, no file is touched
+        #[arg(long)]
+        detect_duplicate_deployments: bool,
+        /// Share of the files of a directory found in another one to report the pair, e.g. 50%
+        #[arg(
+            long,
+            value_name = "PERCENT",
+            value_parser = parse_percent_arg,
+            default_value_t = DEFAULT_DUPLICATE_THRESHOLD,
+            requires = "detect_duplicate_deployments"
+        )]
+        duplicate_threshold: f64,
+        /// How files are matched: name (file name, size and datetime) or hash (SHA-256, reads every file)
+        #[arg(
+            long,
+            value_enum,
+            default_value_t = DuplicateKey::Name,
+            requires = "detect_duplicate_deployments"
+        )]
+        duplicate_key: DuplicateKey,
+        /// Take the rows of files already in this earlier tags CSV (or Parquet) over and only
+        /// read new files, files gone from disk are dropped and files listed in the errors CSV
+        /// of that run are read again
+        #[arg(long, value_name = "TAGS")]
+        resume: Option<PathBuf>,
+        /// With --resume, also read again the files modified since they were read, by their
+        /// time_modified (written with --debug), else since that tags CSV was written
+        #[arg(long, requires = "resume")]
+        check_mtime: bool,
+        /// Browse species counts, deployments, untagged files and errors in the terminal afterwards
+        #[arg(long)]
+        review: bool,
+        /// Write species_stats as plain value counts, without sorting, (untagged)/TOTAL rows or percent
+        #[arg(long)]
+        no_format: bool,
+        /// File format of the tags table, parquet is read by capture in place of the CSV
+        #[arg(long, value_enum, default_value_t)]
+        format: TagsFormat,
     },
     /// Rename a deployment directory from deployment_name to deployment_id
     #[command(arg_required_else_help = true)]
@@ -277,13 +1109,103 @@ enum Commands {
         /// Create event ID
         #[arg(long)]
         event: bool,
-        /// Do not exclude default tags (Blank, Useless data, Unidentified, Unknown, Blur) from temporal independence analysis
+        /// Do not exclude default tags (Blank, Useless data, Blur and the indeterminate labels) from temporal independence analysis
         #[arg(long)]
         no_exclude: bool,
         /// Use observation table from camtrap-dp data package
         #[arg(long)]
         camtrap_dp: bool,
-        // TODO custom exclude tags
+        /// Check for files modified after tags.csv was generated (always on with time_modified)
+        #[arg(long)]
+        check_stale: bool,
+        /// Abort when more than this share of files is stale (e.g. 5%)
+        #[arg(long, value_name = "PERCENT", value_parser = parse_percent_arg)]
+        fail_if_stale: Option<f64>,
+        /// Write species accumulation data (cumulative trap-days vs richness) to accumulation.csv
+        #[arg(long)]
+        accumulation: bool,
+        /// Accumulation row interval in trap-days (default: one row per day)
+        #[arg(long, value_name = "TRAP_DAYS", default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), requires = "accumulation")]
+        accumulation_step: u32,
+        /// Also write the accumulation per deployment
+        #[arg(long, requires = "accumulation")]
+        per_deployment: bool,
+        /// Deployment table with deploymentStart/deploymentEnd for the effort (else first/last record),
+        /// e.g. deployment_activity.csv from `serval effort`, and latitude/longitude for --geojson
+        #[arg(short, long, value_name = "FILE")]
+        deploy_table: Option<PathBuf>,
+        /// Write time-of-day values of two species for activity overlap, e.g. "Leopard cat,Red fox"
+        #[arg(long, value_name = "A,B")]
+        overlap: Option<String>,
+        /// Write distinct individuals by sex and age per deployment and overall (demographics.csv)
+        #[arg(long)]
+        demographics: bool,
+        /// Write deployments (and records with GPS) as GeoJSON points with independent counts
+        #[arg(long)]
+        geojson: bool,
+        /// Write count_all as plain counts, without sorting, (untagged)/TOTAL rows or percent
+        #[arg(long)]
+        no_format: bool,
+        /// Add date, year, month and hour columns from the time of each record to the
+        /// temporal-independence and events CSVs
+        #[arg(long)]
+        derive_dateparts: bool,
+        /// Copy these columns of the input (e.g. translate --add-columns output) into count_all
+        /// and count_by_deployment, next to the species
+        #[arg(
+            long,
+            value_name = "COLUMNS",
+            value_delimiter = ',',
+            conflicts_with = "camtrap_dp"
+        )]
+        carry_columns: Vec<String>,
+        /// Write the gaps between consecutive records per species and deployment, binned from
+        /// 1 to 360+ minutes, with the share of records each window would keep
+        #[arg(long)]
+        gap_histogram: bool,
+        /// Run each of these windows (e.g. 30m,60m) over the same records and compare the
+        /// counts in capture_comparison_<target>.csv; count_all and count_by_deployment are of the first
+        #[arg(long, value_name = "WINDOWS", value_delimiter = ',', value_parser = parse_window_minutes)]
+        compare: Vec<i32>,
+        /// Comparisons to run with --compare (LIR, LR), default: the one asked
+        #[arg(
+            long,
+            value_name = "MODES",
+            value_delimiter = ',',
+            value_enum,
+            ignore_case = true,
+            requires = "compare"
+        )]
+        compare_modes: Vec<IndependenceMode>,
+        /// Replace paths, filenames and file keys with salted hashes and deployments with
+        /// pseudonyms (site_001) in the outputs, the mapping back goes to anonymize_key.csv
+        #[arg(long, conflicts_with_all = ["deploy_table", "geojson", "demographics"])]
+        anonymize_paths: bool,
+        /// Also exclude these tags (prefix match), comma-separated, added to serval.toml capture.exclude
+        #[arg(long, value_name = "TAGS", value_delimiter = ',')]
+        exclude: Vec<String>,
+        /// Drop the built-in exclude tags (Blank, the indeterminate labels, ...)
+        #[arg(long)]
+        no_default_excludes: bool,
+        /// Keep records needing review (review.* in serval.toml) in the analysis
+        #[arg(long)]
+        include_review: bool,
+        /// Reuse the prompt answers saved by an earlier run, from FILE or the output directory
+        #[arg(long, value_name = "FILE")]
+        replay: Option<Option<PathBuf>>,
+        /// When outputs with the same parameters exist from an earlier run
+        #[arg(long, value_enum, default_value_t = OnConflict::Version)]
+        on_conflict: OnConflict,
+        /// Read file paths from this column instead of `path`
+        #[arg(long, value_name = "COLUMN")]
+        path_column: Option<String>,
+        /// Map Serval columns onto CSV columns, e.g. "path=RelativePath,datetime=DateTime", or @file
+        #[arg(long, value_name = "MAP", value_parser = parse_column_map_arg, env = "SERVAL_COLUMN_MAP")]
+        column_map: Option<ColumnMap>,
+        /// Split species/individual cells listing several values, e.g. "fox; badger" from Timelapse,
+        /// on this separator (`auto` picks the most common of |, ; and , in the cells)
+        #[arg(long, value_name = "SEP", value_parser = parse_multivalue_separator_arg, conflicts_with = "camtrap_dp")]
+        multivalue_separator: Option<MultivalueSeparator>,
         /// Output directory
         #[arg(
             short,
@@ -293,6 +1215,30 @@ enum Commands {
         )]
         output: PathBuf,
     },
+    /// Deployment activity periods (deploymentStart/deploymentEnd) for effort calculations,
+    /// inferred from the first and last record of each deployment
+    #[command(arg_required_else_help = true)]
+    Effort {
+        /// Path for tags.csv
+        csv_path: PathBuf,
+        /// Trim terminal bursts of records separated by a longer gap (e.g. 14d, battery death)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration_arg)]
+        gap_threshold: Option<Duration>,
+        /// Read file paths from this column instead of `path`
+        #[arg(long, value_name = "COLUMN")]
+        path_column: Option<String>,
+        /// Map Serval columns onto CSV columns, e.g. "path=RelativePath,datetime=DateTime", or @file
+        #[arg(long, value_name = "MAP", value_parser = parse_column_map_arg, env = "SERVAL_COLUMN_MAP")]
+        column_map: Option<ColumnMap>,
+        /// Output directory
+        #[arg(
+            short,
+            long,
+            value_name = "OUTPUT_DIR",
+            default_value = "./serval_output/serval_effort"
+        )]
+        output: PathBuf,
+    },
     /// Extract and copy resources by filtering target values (based on tags.csv)
     #[command(arg_required_else_help = true)]
     #[command(
@@ -313,21 +1259,56 @@ enum Commands {
     -f advanced -v \"(species:Serval and rating:4-5) or (species:Snow leopard and rating:5)\"\n\n\
     # Field Aliases\n\
     species: sp, s  |  individual: ind, i  |  rating: rate, r\n\
-    path: p  |  event: e  |  custom: c\n\n\
+    path: p  |  event: e  |  custom: c\n\
+    pick_label: pick, pl  |  color_label: color, colour, cl\n\n\
     # Operators\n\
     Exact match:     species:Fox\n\
     Range:           rating:3-5\n\
-    Comparisons:     rating:>=4, rating:>4, rating:<5, rating:<=5"
+    Comparisons:     rating:>=4, rating:>4, rating:<5, rating:<=5\n\n\
+    # digiKam Labels\n\
+    -f pick-label -v accepted  |  -f advanced -v \"pick:accepted and color:red\"\n\
+    Pick: none, rejected, pending, accepted  |  Color: none, red, orange, yellow, green,\n\
+    blue, magenta, gray, black, white (digiKam indices also work)"
     )]
     Extract {
         /// Path for tags.csv
         csv_path: PathBuf,
         /// Specify the filter type
-        #[arg(short, long, value_name = "FILTER", required = true, value_enum)]
-        filter_type: ExtractFilterType,
-        /// The target value (or substring for the path filter), use "ALL_VALUES" for all non-empty values
-        #[arg(short, long, value_name = "VALUE", required = true)]
-        value: String,
+        #[arg(
+            short,
+            long,
+            value_name = "FILTER",
+            required_unless_present_any = ["review_only", "events_from"],
+            value_enum
+        )]
+        filter_type: Option<ExtractFilterType>,
+        /// The target value (or substring for the path filter), use "ALL_VALUES" for all non-empty values.
+        /// Asked with Tab completion when omitted for the species and individual filters
+        #[arg(short, long, value_name = "VALUE")]
+        value: Option<String>,
+        /// Column read by the custom filter instead of `custom`, e.g. behaviour from observe
+        /// --extra-tag Behaviour, a value matching any of the tags joined in a cell
+        #[arg(long, value_name = "COLUMN", conflicts_with_all = ["review_only", "events_from"])]
+        custom_column: Option<String>,
+        /// Select the records needing review, as set by review.* in serval.toml
+        #[arg(long, conflicts_with_all = ["filter_type", "value"])]
+        review_only: bool,
+        /// Also take every record sharing an event_id with a matched one (events CSV of capture --event)
+        #[arg(long)]
+        whole_event: bool,
+        /// Select the whole events of the independent records in this temporal-independence CSV,
+        /// with the window and comparison of the capture run that wrote it
+        #[arg(long, value_name = "CSV", conflicts_with_all = ["filter_type", "value", "review_only", "whole_event"])]
+        events_from: Option<PathBuf>,
+        /// Taglist CSV completing the asked value, instead of the values in the tags CSV
+        #[arg(long, value_name = "CSV", conflicts_with = "value")]
+        taglist: Option<PathBuf>,
+        /// Draw a stratified sample for verification instead, strata and sizes from a TOML spec
+        #[arg(long, value_name = "SPEC", conflicts_with_all = ["value", "filter_type", "per_species_limit", "review_only", "rename", "use_subdir", "group_by"])]
+        verify_sample: Option<PathBuf>,
+        /// Reuse the keep level answered in an earlier run, from FILE or the output directory
+        #[arg(long, value_name = "FILE")]
+        replay: Option<Option<PathBuf>>,
         /// Enable rename rename mode (including tags in filenames)
         #[arg(long)]
         rename: bool,
@@ -340,6 +1321,56 @@ enum Commands {
         /// Specify the type used when creating subdirectories
         #[arg(long, default_value_t = SubdirType::Species, value_enum)]
         subdir_type: SubdirType,
+        /// Put each copy under a directory named after this column (_unknown when missing),
+        /// independently of --use-subdir and --rename
+        #[arg(long, value_name = "COLUMN", value_enum)]
+        group_by: Option<GroupBy>,
+        /// Check for files modified after tags.csv was generated (always on with time_modified)
+        #[arg(long)]
+        check_stale: bool,
+        /// Abort when more than this share of files is stale (e.g. 5%)
+        #[arg(long, value_name = "PERCENT", value_parser = parse_percent_arg)]
+        fail_if_stale: Option<f64>,
+        /// Read file paths from this column instead of `path`
+        #[arg(long, value_name = "COLUMN")]
+        path_column: Option<String>,
+        /// Map Serval columns onto CSV columns, e.g. "path=RelativePath,datetime=DateTime", or @file
+        #[arg(long, value_name = "MAP", value_parser = parse_column_map_arg, env = "SERVAL_COLUMN_MAP")]
+        column_map: Option<ColumnMap>,
+        /// Split species/individual cells listing several values, e.g. "fox; badger" from Timelapse,
+        /// on this separator (`auto` picks the most common of |, ; and , in the cells)
+        #[arg(long, value_name = "SEP", value_parser = parse_multivalue_separator_arg)]
+        multivalue_separator: Option<MultivalueSeparator>,
+        /// Copy at most N records per species (smaller groups are taken in full)
+        #[arg(long, value_name = "N")]
+        per_species_limit: Option<usize>,
+        /// Spread each species quota evenly over this grouping
+        #[arg(long, value_enum, requires = "per_species_limit")]
+        balance_by: Option<BalanceBy>,
+        /// Seed for quota sampling, the same seed gives the same selection
+        #[arg(long, default_value_t = 0, requires = "per_species_limit")]
+        seed: u64,
+        /// Fail when the copies' modification time can't be preserved (SMB/NFS, FAT cards)
+        #[arg(long)]
+        require_mtime: bool,
+        /// Report videos without an XMP sidecar (their tags only live there) in
+        /// missing_sidecars.csv, and check each copied video has its sidecar in the output
+        #[arg(long)]
+        require_sidecar: bool,
+        /// With --require-sidecar, copy nothing while any video misses its sidecar
+        #[arg(long, requires = "require_sidecar")]
+        strict: bool,
+        /// Abort before copying when the matched media add up to more than SIZE (e.g. 200G)
+        #[arg(long, value_name = "SIZE", value_parser = parse_size_arg)]
+        max_size: Option<u64>,
+        /// Keep the directories below DIR instead of asking for the level, `auto` for the
+        /// common directory of all paths in the CSV
+        #[arg(long, value_name = "DIR")]
+        keep_relative_to: Option<KeepRelativeTo>,
+        /// With the species filter and ALL_VALUES, also extract the indeterminate labels
+        /// (Unidentified, Unknown, see --indeterminate)
+        #[arg(long)]
+        include_indeterminate: bool,
         /// Set the output directory
         #[arg(
             short,
@@ -352,6 +1383,12 @@ enum Commands {
     /// XMP file operations
     #[command(subcommand)]
     Xmp(XmpCommands),
+    /// Project settings in serval.toml
+    #[command(subcommand)]
+    Config(ConfigCommands),
+    /// History of the sidecar changes made by serval, from the audit log
+    #[command(subcommand)]
+    Audit(AuditCommands),
     /// Translate species column in csv according to taglist
     Translate {
         /// Path for tags.csv
@@ -371,8 +1408,322 @@ enum Commands {
         #[arg(long, value_name = "FROM", required = true)]
         from: String,
         /// Column name (in taglist) to translate to
-        #[arg(long, value_name = "TO", required = true)]
-        to: String,
+        #[arg(long, value_name = "TO", required_unless_present = "add_columns")]
+        to: Option<String>,
+        /// Keep the species and append these taglist columns, e.g. "scientific,common_zh"
+        #[arg(
+            long,
+            value_name = "COLUMNS",
+            value_delimiter = ',',
+            conflicts_with = "to"
+        )]
+        add_columns: Vec<String>,
+        /// When the translated CSV exists from an earlier run
+        #[arg(long, value_enum, default_value_t = OnConflict::Version)]
+        on_conflict: OnConflict,
+        /// Map Serval columns onto CSV columns, e.g. "path=RelativePath,datetime=DateTime", or @file
+        #[arg(long, value_name = "MAP", value_parser = parse_column_map_arg, env = "SERVAL_COLUMN_MAP")]
+        column_map: Option<ColumnMap>,
+    },
+    /// Map species onto a regional checklist and report tagged species absent from it
+    #[command(arg_required_else_help = true)]
+    Checklist {
+        /// Path for tags.csv
+        #[arg(long, value_name = "CSV", required = true)]
+        tags: PathBuf,
+        /// Checklist CSV, one row per species
+        #[arg(long, value_name = "CSV", required = true)]
+        list: PathBuf,
+        /// Checklist column matched against species (case-insensitive)
+        #[arg(long = "match", value_name = "COLUMN", required = true)]
+        match_column: String,
+        /// Checklist columns appended to the tags, comma-delimited
+        #[arg(
+            long,
+            value_name = "COLUMNS",
+            value_delimiter = ',',
+            default_value = "scientific_name,status"
+        )]
+        columns: Vec<String>,
+        /// Output directory
+        #[arg(
+            short,
+            long,
+            value_name = "OUTPUT_DIR",
+            default_value = "./serval_output/serval_checklist"
+        )]
+        output: PathBuf,
+        /// Map Serval columns onto CSV columns, e.g. "path=RelativePath,datetime=DateTime", or @file
+        #[arg(long, value_name = "MAP", value_parser = parse_column_map_arg, env = "SERVAL_COLUMN_MAP")]
+        column_map: Option<ColumnMap>,
+    },
+    /// Compare two tagging passes of the same resources and list the disagreements
+    #[command(arg_required_else_help = true)]
+    Compare {
+        /// tags.csv of the first pass
+        #[arg(long, value_name = "CSV", required = true)]
+        a: PathBuf,
+        /// tags.csv of the second pass
+        #[arg(long, value_name = "CSV", required = true)]
+        b: PathBuf,
+        /// Column used to align rows, path prefixes (pass roots) are normalized away
+        #[arg(long, value_name = "COLUMN", default_value = "path")]
+        key: String,
+        /// Tag type to compare
+        #[arg(short, long, value_name = "TYPE", default_value_t = TagType::Species, value_enum)]
+        tag_type: TagType,
+        /// Output directory
+        #[arg(
+            short,
+            long,
+            value_name = "OUTPUT_DIR",
+            default_value = "./serval_output/serval_compare"
+        )]
+        output: PathBuf,
+    },
+    /// Crop the bounding boxes of a detections CSV (path,x,y,w,h normalized to 0-1) into JPEGs
+    #[command(arg_required_else_help = true)]
+    Crop {
+        /// Detections CSV
+        #[arg(long, value_name = "CSV", required = true)]
+        csv: PathBuf,
+        /// Output directory, crops keep the directory structure of the source images
+        #[arg(
+            short,
+            long,
+            value_name = "OUTPUT_DIR",
+            default_value = "./serval_output/serval_crop"
+        )]
+        output: PathBuf,
+        /// Grow each box by this percent of its size on every side
+        #[arg(long, value_name = "PERCENT", default_value_t = 0.0, value_parser = parse_percent_arg)]
+        padding: f64,
+    },
+    /// Left-join enrichment CSVs (OCR, GPS, camera info, hashes...) onto a tags.csv
+    #[command(arg_required_else_help = true)]
+    Enrich {
+        /// Base tags.csv, its rows and order are kept
+        #[arg(long, value_name = "CSV", required = true)]
+        base: PathBuf,
+        /// Enrichment CSV, repeat for several
+        #[arg(long, value_name = "CSV", required = true)]
+        with: Vec<PathBuf>,
+        /// Join column, present in every CSV
+        #[arg(long, value_name = "COLUMN", default_value = "path")]
+        on: String,
+        /// Output CSV
+        #[arg(short, long, value_name = "CSV", required = true)]
+        output: PathBuf,
+    },
+    /// Copy species/count/sex from tagged frames to untagged frames of the same burst
+    #[command(arg_required_else_help = true)]
+    Propagate {
+        /// Path for tags.csv
+        #[arg(long, value_name = "CSV", required = true)]
+        tags: PathBuf,
+        /// Frames of a deployment at most this far apart form one sequence (e.g. 5s)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration_arg, required = true)]
+        window: Duration,
+        /// Also insert the propagated tags into the XMP sidecars
+        #[arg(long)]
+        apply_xmp: bool,
+        /// Read file paths from this column instead of `path`
+        #[arg(long, value_name = "COLUMN")]
+        path_column: Option<String>,
+        /// Map Serval columns onto CSV columns, e.g. "path=RelativePath,datetime=DateTime", or @file
+        #[arg(long, value_name = "MAP", value_parser = parse_column_map_arg, env = "SERVAL_COLUMN_MAP")]
+        column_map: Option<ColumnMap>,
+        /// Output directory
+        #[arg(
+            short,
+            long,
+            value_name = "OUTPUT_DIR",
+            default_value = "./serval_output/serval_propagate"
+        )]
+        output: PathBuf,
+    },
+    /// Map re-tagged extraction copies back to the originals as an xmp update CSV
+    #[command(arg_required_else_help = true)]
+    Reconcile {
+        /// tags.csv the extraction was made from
+        #[arg(long, value_name = "CSV", required = true)]
+        original: PathBuf,
+        /// tags.csv observed on the re-tagged copies
+        #[arg(long, value_name = "CSV", required = true)]
+        reviewed: PathBuf,
+        /// manifest.csv written by the extraction
+        #[arg(long, value_name = "CSV", required = true)]
+        manifest: PathBuf,
+        /// Tag to reconcile
+        #[arg(short, long, value_name = "TYPE", value_enum, default_value_t = XmpUpdateType::Species)]
+        tag_type: XmpUpdateType,
+        /// Read file paths from this column instead of `path`
+        #[arg(long, value_name = "COLUMN")]
+        path_column: Option<String>,
+        /// Map Serval columns onto CSV columns, e.g. "path=RelativePath,species=Species", or @file
+        #[arg(long, value_name = "MAP", value_parser = parse_column_map_arg, env = "SERVAL_COLUMN_MAP")]
+        column_map: Option<ColumnMap>,
+        /// Output directory
+        #[arg(
+            short,
+            long,
+            value_name = "OUTPUT_DIR",
+            default_value = "./serval_output/serval_reconcile"
+        )]
+        output: PathBuf,
+    },
+    /// Carry species fixes made in a reviewed temporal-independence CSV back to the sidecars
+    /// (as an xmp update CSV) and tags.csv, rows that can't be matched are listed, not guessed
+    #[command(arg_required_else_help = true)]
+    Backport {
+        /// temporal-independence CSV edited during review
+        #[arg(long, value_name = "CSV", required = true)]
+        reviewed: PathBuf,
+        /// temporal-independence CSV as written by capture
+        #[arg(long, value_name = "CSV", required = true)]
+        original: PathBuf,
+        /// tags.csv the capture was run on
+        #[arg(long, value_name = "CSV", required = true)]
+        tags: PathBuf,
+        /// Read file paths of the tags CSV from this column instead of `path`
+        #[arg(long, value_name = "COLUMN")]
+        path_column: Option<String>,
+        /// Map Serval columns onto tags CSV columns, e.g. "path=RelativePath,species=Species", or @file
+        #[arg(long, value_name = "MAP", value_parser = parse_column_map_arg, env = "SERVAL_COLUMN_MAP")]
+        column_map: Option<ColumnMap>,
+        /// Output directory
+        #[arg(
+            short,
+            long,
+            value_name = "OUTPUT_DIR",
+            default_value = "./serval_output/serval_backport"
+        )]
+        output: PathBuf,
+    },
+    /// Turn digiKam tag edits (digikam4.db or its CSV export) into an xmp update CSV
+    #[command(arg_required_else_help = true)]
+    Digikam {
+        /// digikam4.db, or a CSV exported from digiKam with file paths and tag paths
+        #[arg(value_name = "DB_OR_CSV", required = true)]
+        source: PathBuf,
+        /// Project root the digiKam album paths are relative to
+        #[arg(long, value_name = "DIR", required = true)]
+        root: PathBuf,
+        /// tags.csv observed on the project
+        #[arg(long, value_name = "CSV", required = true)]
+        tags: PathBuf,
+        /// Tag to import, read from digiKam tags under its root (e.g. Species/Serval)
+        #[arg(short, long, value_name = "TYPE", value_enum, default_value_t = XmpUpdateType::Species)]
+        tag_type: XmpUpdateType,
+        /// Path column of the digiKam CSV
+        #[arg(long, value_name = "COLUMN", default_value = "path")]
+        source_path_column: String,
+        /// Tag paths column of the digiKam CSV, `;` or `,` separated
+        #[arg(long, value_name = "COLUMN", default_value = "tags")]
+        source_tags_column: String,
+        /// Read file paths of the tags.csv from this column instead of `path`
+        #[arg(long, value_name = "COLUMN")]
+        path_column: Option<String>,
+        /// Map Serval columns onto tags.csv columns, e.g. "path=RelativePath,species=Species", or @file
+        #[arg(long, value_name = "MAP", value_parser = parse_column_map_arg, env = "SERVAL_COLUMN_MAP")]
+        column_map: Option<ColumnMap>,
+        /// Output directory
+        #[arg(
+            short,
+            long,
+            value_name = "OUTPUT_DIR",
+            default_value = "./serval_output/serval_digikam"
+        )]
+        output: PathBuf,
+    },
+    /// Export tags, species stats and analysis outputs as tables of one SQLite database
+    #[command(arg_required_else_help = true)]
+    Export {
+        /// Output directories or CSVs of serval capture/observe/analysis
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// SQLite database, existing tables of the same outputs are replaced
+        #[arg(long, value_name = "DB", required = true)]
+        sqlite: PathBuf,
+    },
+    /// Record size, mtime and SHA-256 of every media and XMP file (resumable)
+    #[command(arg_required_else_help = true)]
+    Snapshot {
+        dir: PathBuf,
+        /// Output manifest, CSV or Parquet by extension
+        #[arg(short, long, value_name = "MANIFEST", required = true)]
+        output: PathBuf,
+    },
+    /// Check a directory against a snapshot manifest, exits non-zero on differences
+    #[command(arg_required_else_help = true)]
+    VerifySnapshot {
+        dir: PathBuf,
+        /// Manifest (CSV or Parquet) written by `serval snapshot`
+        #[arg(long, value_name = "MANIFEST", required = true)]
+        manifest: PathBuf,
+    },
+    /// Print the columns of an output as JSON, as written next to it in <output>.schema.json
+    #[command(arg_required_else_help = true)]
+    Schema {
+        #[arg(value_enum)]
+        output: OutputKind,
+    },
+    /// Check a project before analysis, exits non-zero when a check fails.
+    /// --counts compares the media of each <collection>/<deploymentID> directory with the
+    /// count expected in the deploy table, e.g. the card's file count recorded at pickup
+    #[command(arg_required_else_help = true)]
+    Qa {
+        project_dir: PathBuf,
+        /// Deploy table with the expected media count of each deployment
+        #[arg(long, value_name = "DEPLOY_TABLE")]
+        counts: Option<PathBuf>,
+        /// Column of the deploy table holding the expected media count
+        #[arg(long, default_value = qa::DEFAULT_EXPECTED_COUNT_COLUMN, requires = "counts")]
+        column: String,
+        /// How many files a deployment may be short of the expected count, e.g. 5 or 2%
+        #[arg(long, value_parser = qa::parse_count_tolerance, default_value = "0")]
+        tolerance: qa::CountTolerance,
+        /// Output directory of qa_report.csv
+        #[arg(
+            short,
+            long,
+            value_name = "OUTPUT_DIR",
+            default_value = "./serval_output/serval_qa"
+        )]
+        output: PathBuf,
+    },
+    /// Print the version, build, platform, default output directories and configuration in effect
+    Info {
+        /// Wrap it in a Markdown block to paste into a bug report
+        #[arg(long)]
+        bug_report: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommands {
+    /// Set a setting in serval.toml (created in the working directory if none is found)
+    Set { key: String, value: String },
+    /// Show the settings in effect
+    Show,
+}
+
+#[derive(Debug, Subcommand)]
+enum AuditCommands {
+    /// Print the logged changes of one sidecar, oldest first
+    Show {
+        /// Sidecar whose history to print
+        #[arg(long, value_name = "FILE")]
+        path: PathBuf,
+        /// Audit log to read instead of the project's (audit.path in serval.toml)
+        #[arg(long, value_name = "FILE")]
+        log: Option<PathBuf>,
+    },
+    /// Check that every backup referenced by the log still exists
+    Verify {
+        /// Audit log to read instead of the project's (audit.path in serval.toml)
+        #[arg(long, value_name = "FILE")]
+        log: Option<PathBuf>,
     },
 }
 
@@ -389,21 +1740,56 @@ enum XmpCommands {
         /// Enable info mode and write an XMP init datetime CSV
         #[arg(short, long)]
         info: bool,
+        /// Skip media files whose metadata read takes longer than this (e.g. 30s)
+        #[arg(long, value_name = "DURATION", value_parser = parse_duration_arg)]
+        file_timeout: Option<Duration>,
+        /// XMP file whose properties are added to each new sidecar that lacks them,
+        /// with `{datetime}`, `{deployment}` and `{filename}` filled in per media file
+        #[arg(long, value_name = "XMP")]
+        template: Option<PathBuf>,
+        /// Deployment of `{deployment}` and the info CSV, the directory at this level of the path
+        /// (numbered as in the capture prompt), asked for when not given
+        #[arg(long, value_name = "LEVEL", value_parser = clap::value_parser!(i32).range(1..))]
+        deploy_level: Option<i32>,
     },
     /// Update XMP files from CSV.
-    /// Tag mode uses: `xmp_update`, plus `species`, `individual`, or `rating` according to `--tag-type`.
+    /// Tag mode uses: `xmp_update`, plus `species`, `individual`, `rating`, `pick_label` or `color_label` according to `--tag-type`.
+    /// An `xmp_update` of DELETE_TAG removes the species/individual tag named in its column.
+    /// An optional `rating_update` column (integer 0-5) sets xmp:Rating in the same write.
     /// Datetime mode (`--datetime`) uses: `xmp_update_datetime` (format: yyyy-MM-dd HH:mm:ss).
+    /// Sidecars that can't be decoded are skipped and listed in errors_<CSV name> next to the CSV.
     Update {
         csv_path: PathBuf,
-        /// Tag type for tag mode (`species`, `individual`, or `rating`).
+        /// Tag type for tag mode (`species`, `individual`, `rating`, `pick-label` or `color-label`).
         #[arg(short, long, value_name = "TYPE", required_unless_present = "datetime")]
         tag_type: Option<XmpUpdateType>,
         /// Use datetime mode (reads `xmp_update_datetime` instead of xmp_update).
         #[arg(long)]
         datetime: bool,
+        /// Record who made the change as `serval:tagger` in every modified sidecar
+        #[arg(long, value_name = "NAME", env = "SERVAL_TAGGER")]
+        tagger: Option<String>,
+        /// Read file paths from this column instead of `path`
+        #[arg(long, value_name = "COLUMN")]
+        path_column: Option<String>,
+        /// Re-read every rewritten sidecar and restore the backup if any untouched property changed
+        #[arg(long)]
+        verify_roundtrip: bool,
     },
     /// Remove all XMP files recursively from a directory
     Remove { source_dir: PathBuf },
+    /// Rename XMP sidecars between the appended (IMG_0001.JPG.xmp) and replaced (IMG_0001.xmp) conventions
+    RenameConvention {
+        /// Root directory to search for XMP files
+        #[arg(long, value_name = "DIR")]
+        dir: PathBuf,
+        /// Target naming convention
+        #[arg(long, value_enum)]
+        to: SidecarConvention,
+        /// Dry run
+        #[arg(long)]
+        dryrun: bool,
+    },
     /// Sync XMP metadata to corresponding media files
     Sync {
         /// Directory containing XMP files to sync
@@ -412,5 +1798,37 @@ enum XmpCommands {
         /// CSV file with paths to XMP files to sync
         #[arg(long, value_name = "CSV_PATH")]
         csv: Option<PathBuf>,
+        /// Read-only: compare sidecars with the embedded XMP and write a diff CSV
+        #[arg(long, requires = "output")]
+        check: bool,
+        /// Directory for the diff CSV of --check
+        #[arg(short, long, value_name = "DIR", requires = "check")]
+        output: Option<PathBuf>,
+        /// Read file paths from this column instead of `path` (with --csv)
+        #[arg(long, value_name = "COLUMN", requires = "csv")]
+        path_column: Option<String>,
+    },
+    /// Embed sidecar metadata into the media files, e.g. before archival, and write a report CSV
+    Embed {
+        /// Root directory to search for XMP files
+        #[arg(long, value_name = "DIR")]
+        dir: PathBuf,
+        /// Only embed into images, videos are skipped as rewriting them is risky
+        /// (--only-images=false to embed into videos too)
+        #[arg(
+            long,
+            default_value_t = true,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "true",
+            action = clap::ArgAction::Set
+        )]
+        only_images: bool,
+        /// Re-read every embedded file and compare hierarchicalSubject and DateTimeOriginal with the sidecar
+        #[arg(long)]
+        verify: bool,
+        /// Delete the sidecars whose embed passed verification
+        #[arg(long, requires = "verify")]
+        remove_sidecars_after: bool,
     },
 }
@@ -0,0 +1,299 @@
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+static PROGRESS_ENABLED: AtomicBool = AtomicBool::new(true);
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+static NOTICE_INTERVAL_SECS: AtomicU64 = AtomicU64::new(30);
+static NOTICE_BATCH: AtomicU64 = AtomicU64::new(10_000);
+static LOG_FILE: Mutex<Option<File>> = Mutex::new(None);
+
+// Global switches, set once from the CLI before any command runs. Counted notices are
+// flushed every `notice_interval` seconds or `notice_batch` notices, 0 disables either.
+pub fn configure_output(
+    no_progress: bool,
+    verbose: bool,
+    notice_interval: u64,
+    notice_batch: u64,
+    log_file: Option<&Path>,
+) -> anyhow::Result<()> {
+    PROGRESS_ENABLED.store(!no_progress, Ordering::Relaxed);
+    VERBOSE.store(verbose, Ordering::Relaxed);
+    NOTICE_INTERVAL_SECS.store(notice_interval, Ordering::Relaxed);
+    NOTICE_BATCH.store(notice_batch, Ordering::Relaxed);
+    if let Some(log_file) = log_file {
+        let file = File::options()
+            .create(true)
+            .append(true)
+            .open(log_file)
+            .map_err(|e| anyhow::anyhow!("Failed to open log file {}: {e}", log_file.display()))?;
+        *LOG_FILE.lock().unwrap() = Some(file);
+    }
+    Ok(())
+}
+
+pub fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+// Append a line to the --log-file, if any
+fn log_line(msg: &str) {
+    if let Some(file) = LOG_FILE.lock().unwrap().as_mut() {
+        let _ = writeln!(file, "{msg}");
+    }
+}
+
+// Serval bar style
+pub fn serval_pb_style() -> ProgressStyle {
+    ProgressStyle::default_bar()
+        .template(
+            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}",
+        )
+        .unwrap()
+        .progress_chars("=> ")
+}
+
+// Serval bar style for copies, position in bytes so large videos don't look stalled
+pub fn serval_bytes_pb_style() -> ProgressStyle {
+    ProgressStyle::default_bar()
+        .template(
+            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {bytes}/{total_bytes} ({eta}) {msg}",
+        )
+        .unwrap()
+        .progress_chars("=> ")
+}
+
+// Files done out of total, shown in the message of a bytes-based bar
+struct FileCount {
+    done: AtomicU64,
+    total: u64,
+    stage: String,
+}
+
+// Per-file notices counted by category, with the warnings kept for the final summary
+struct Notices {
+    counts: BTreeMap<String, usize>,
+    warnings: Vec<String>,
+    pending: u64,
+    last_flush: Instant,
+}
+
+impl Notices {
+    fn new() -> Self {
+        Self {
+            counts: BTreeMap::new(),
+            warnings: Vec::new(),
+            pending: 0,
+            last_flush: Instant::now(),
+        }
+    }
+
+    // One line of the counts so far when the interval or the batch size is reached
+    fn flush_due(&mut self) -> Option<String> {
+        self.pending += 1;
+        let batch = NOTICE_BATCH.load(Ordering::Relaxed);
+        let interval = NOTICE_INTERVAL_SECS.load(Ordering::Relaxed);
+        let due = (batch > 0 && self.pending >= batch)
+            || (interval > 0 && self.last_flush.elapsed() >= Duration::from_secs(interval));
+        if !due {
+            return None;
+        }
+        self.pending = 0;
+        self.last_flush = Instant::now();
+        let counts: Vec<String> = self
+            .counts
+            .iter()
+            .map(|(category, count)| format!("{category}: {count}"))
+            .collect();
+        Some(format!("So far: {}", counts.join(", ")))
+    }
+}
+
+/// Progress bar shared by all commands.
+///
+/// Per-file notices and warnings are counted by category and only printed in verbose mode,
+/// the counts so far are printed every few seconds or notices, and a summary of the counts
+/// with every warning is printed when the bar finishes. The --log-file gets every message.
+pub struct ServalProgress {
+    pb: ProgressBar,
+    notices: Mutex<Notices>,
+    files: Option<FileCount>,
+}
+
+impl ServalProgress {
+    pub fn new(len: u64, stage: &str) -> Self {
+        Self::with_multi(None, len, stage)
+    }
+
+    /// Bar over `total_bytes`, advanced per file with `inc_file`
+    pub fn new_bytes(total_bytes: u64, num_files: u64, stage: &str) -> Self {
+        Self::bytes_with_multi(None, total_bytes, num_files, stage)
+    }
+
+    // One line of a ServalMultiProgress when given, a standalone bar otherwise
+    fn with_multi(multi: Option<&MultiProgress>, len: u64, stage: &str) -> Self {
+        let pb = Self::bar(multi, len, serval_pb_style());
+        pb.set_message(stage.to_string());
+        Self {
+            pb,
+            notices: Mutex::new(Notices::new()),
+            files: None,
+        }
+    }
+
+    fn bytes_with_multi(
+        multi: Option<&MultiProgress>,
+        total_bytes: u64,
+        num_files: u64,
+        stage: &str,
+    ) -> Self {
+        let pb = Self::bar(multi, total_bytes, serval_bytes_pb_style());
+        pb.set_message(format!("0/{num_files} files {stage}"));
+        Self {
+            pb,
+            notices: Mutex::new(Notices::new()),
+            files: Some(FileCount {
+                done: AtomicU64::new(0),
+                total: num_files,
+                stage: stage.to_string(),
+            }),
+        }
+    }
+
+    fn bar(multi: Option<&MultiProgress>, len: u64, style: ProgressStyle) -> ProgressBar {
+        if PROGRESS_ENABLED.load(Ordering::Relaxed) {
+            let pb = match multi {
+                Some(multi) => multi.add(ProgressBar::new(len)),
+                None => ProgressBar::new(len),
+            };
+            pb.set_style(style);
+            pb.enable_steady_tick(std::time::Duration::from_secs(1));
+            pb
+        } else {
+            ProgressBar::hidden()
+        }
+    }
+
+    /// Count one file of `bytes` on a bar created with `new_bytes`
+    pub fn inc_file(&self, bytes: u64) {
+        self.pb.inc(bytes);
+        if let Some(files) = &self.files {
+            let done = files.done.fetch_add(1, Ordering::Relaxed) + 1;
+            self.pb
+                .set_message(format!("{done}/{} files {}", files.total, files.stage));
+        }
+    }
+
+    pub fn inc(&self, delta: u64) {
+        self.pb.inc(delta);
+    }
+
+    pub fn set_position(&self, pos: u64) {
+        self.pb.set_position(pos);
+    }
+
+    // Print a message above the bar, on stderr when the bar is hidden
+    fn print(&self, msg: &str) {
+        if self.pb.is_hidden() {
+            eprintln!("{msg}");
+        } else {
+            self.pb.println(msg);
+        }
+    }
+
+    /// Count a per-file notice under `category`, printing it only in verbose mode
+    pub fn notice<I: AsRef<str>>(&self, category: &str, msg: I) {
+        self.count(category, msg.as_ref(), false);
+    }
+
+    /// Count a per-file warning under `category`, kept for the summary when the bar finishes
+    pub fn warn<I: AsRef<str>>(&self, category: &str, msg: I) {
+        self.count(category, msg.as_ref(), true);
+    }
+
+    fn count(&self, category: &str, msg: &str, warning: bool) {
+        log_line(msg);
+        if is_verbose() {
+            self.print(msg);
+        }
+        let mut notices = self.notices.lock().unwrap();
+        *notices.counts.entry(category.to_string()).or_insert(0) += 1;
+        if warning {
+            notices.warnings.push(msg.to_string());
+        }
+        if let Some(line) = notices.flush_due()
+            && !is_verbose()
+        {
+            self.print(&line);
+        }
+    }
+
+    pub fn finish(&self) {
+        self.pb.finish();
+        self.print_notice_summary();
+    }
+
+    pub fn finish_with_message(&self, msg: &str) {
+        self.pb.finish_with_message(msg.to_string());
+        self.print_notice_summary();
+    }
+
+    fn print_notice_summary(&self) {
+        let notices = self.notices.lock().unwrap();
+        for (category, count) in &notices.counts {
+            println!("{category}: {count}");
+            log_line(&format!("{category}: {count}"));
+        }
+        if !notices.warnings.is_empty() {
+            println!("{} warning(s):", notices.warnings.len());
+            for warning in &notices.warnings {
+                println!("  {warning}");
+            }
+        }
+    }
+}
+
+/// Several progress lines drawn together, e.g. one per concurrent deployment
+pub struct ServalMultiProgress {
+    multi: MultiProgress,
+}
+
+impl ServalMultiProgress {
+    pub fn new() -> Self {
+        let multi = if PROGRESS_ENABLED.load(Ordering::Relaxed) {
+            MultiProgress::new()
+        } else {
+            MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+        };
+        Self { multi }
+    }
+
+    pub fn add(&self, len: u64, stage: &str) -> ServalProgress {
+        ServalProgress::with_multi(Some(&self.multi), len, stage)
+    }
+
+    pub fn add_bytes(&self, total_bytes: u64, num_files: u64, stage: &str) -> ServalProgress {
+        ServalProgress::bytes_with_multi(Some(&self.multi), total_bytes, num_files, stage)
+    }
+
+    /// Print a message above all lines
+    pub fn println<I: AsRef<str>>(&self, msg: I) {
+        log_line(msg.as_ref());
+        if self.multi.is_hidden() {
+            println!("{}", msg.as_ref());
+        } else {
+            let _ = self.multi.println(msg);
+        }
+    }
+}
+
+impl Default for ServalMultiProgress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
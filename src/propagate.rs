@@ -0,0 +1,353 @@
+use crate::progress::ServalProgress;
+use crate::schema::{
+    COUNT_COLUMN, DATETIME_COLUMN, LEGACY_DATETIME_COLUMN, PATH_COLUMN, SEX_COLUMN, SPECIES_COLUMN,
+};
+use crate::tags::{Prompt, prompt_deployment_path_index, update_xmp};
+use crate::utils::{
+    ColumnMap, XmpUpdateType, check_csv_columns, csv_writer, deployment_from_path,
+    existing_sidecar_for, media_path_for, reject_duplicate_csv_columns,
+};
+use chrono::NaiveDateTime;
+use polars::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Tag copied across a sequence as (species, count, sex), empty when not set
+type Tag = (String, String, String);
+
+type TagField = fn(&Tag) -> &String;
+
+const TAG_FIELDS: [(XmpUpdateType, TagField); 3] = [
+    (XmpUpdateType::Species, |tag| &tag.0),
+    (XmpUpdateType::Count, |tag| &tag.1),
+    (XmpUpdateType::Sex, |tag| &tag.2),
+];
+
+// One file of tags.csv, which may span several rows (one per tag)
+struct SequenceFile {
+    path: String,
+    rows: Vec<usize>,
+    deployment: Option<String>,
+    datetime: Option<NaiveDateTime>,
+    tags: BTreeSet<Tag>,
+}
+
+fn format_tag((species, count, sex): &Tag) -> String {
+    let details: Vec<&str> = [count.as_str(), sex.as_str()]
+        .into_iter()
+        .filter(|value| !value.is_empty())
+        .collect();
+    if details.is_empty() {
+        species.clone()
+    } else {
+        format!("{species} ({})", details.join(", "))
+    }
+}
+
+fn optional_column(df: &DataFrame, name: &str) -> anyhow::Result<Option<StringChunked>> {
+    match df.column(name) {
+        Ok(column) => Ok(Some(column.str()?.clone())),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Files of each deployment chained into sequences while consecutive datetimes are within
+/// `window`, as indices into `files` (deployment, datetime). Sequences come by deployment, then
+/// time, ties in file order; files without a deployment or datetime are in none.
+///
+/// Shared by propagate and observe --event-gap, so that sequences and event_id agree.
+pub(crate) fn sequences<'a>(
+    files: impl IntoIterator<Item = (Option<&'a str>, Option<NaiveDateTime>)>,
+    window: chrono::Duration,
+) -> Vec<Vec<usize>> {
+    let mut by_deployment: BTreeMap<&str, Vec<(NaiveDateTime, usize)>> = BTreeMap::new();
+    for (index, file) in files.into_iter().enumerate() {
+        if let (Some(deployment), Some(datetime)) = file {
+            by_deployment
+                .entry(deployment)
+                .or_default()
+                .push((datetime, index));
+        }
+    }
+    let mut sequences = Vec::new();
+    for mut times in by_deployment.into_values() {
+        times.sort_unstable();
+        let mut current: Vec<usize> = Vec::new();
+        let mut last: Option<NaiveDateTime> = None;
+        for (datetime, index) in times {
+            if last.is_some_and(|last| datetime - last > window) {
+                sequences.push(std::mem::take(&mut current));
+            }
+            current.push(index);
+            last = Some(datetime);
+        }
+        if !current.is_empty() {
+            sequences.push(current);
+        }
+    }
+    sequences
+}
+
+// Insert the propagated tags into the sidecar of each file
+fn apply_to_xmp(
+    files: &[SequenceFile],
+    propagated: &BTreeMap<usize, &BTreeSet<Tag>>,
+) -> anyhow::Result<()> {
+    let pb = ServalProgress::new(propagated.len() as u64, "writing XMP");
+    let mut num_written = 0;
+    for (&index, tags) in propagated {
+        pb.inc(1);
+        let path = Path::new(&files[index].path);
+        let sidecar = if media_path_for(path).is_some() {
+            Some(path.to_path_buf())
+        } else {
+            existing_sidecar_for(path)
+        };
+        let Some(sidecar) = sidecar.filter(|sidecar| sidecar.is_file()) else {
+            pb.notice(
+                "Missing sidecars",
+                format!("No XMP sidecar for {}", path.display()),
+            );
+            continue;
+        };
+        for (update_type, field) in TAG_FIELDS {
+            let values: BTreeSet<&String> = tags
+                .iter()
+                .map(field)
+                .filter(|value| !value.is_empty())
+                .collect();
+            for value in values {
+                update_xmp(
+                    sidecar.clone(),
+                    String::new(),
+                    value.clone(),
+                    update_type,
+                    None,
+                    None,
+                    false,
+                    &pb,
+                )?;
+            }
+        }
+        num_written += 1;
+    }
+    pb.finish();
+    println!("Tags written to {num_written} sidecar(s)");
+    Ok(())
+}
+
+// Copy species/count/sex from tagged frames to the untagged frames of the same sequence
+// (same deployment, consecutive datetimes within `window`). Sequences whose tagged frames
+// disagree are left untouched and listed for review.
+pub fn propagate_tags(
+    csv_path: PathBuf,
+    window: std::time::Duration,
+    output_dir: PathBuf,
+    apply_xmp: bool,
+    column_map: ColumnMap,
+) -> anyhow::Result<()> {
+    check_csv_columns(
+        &csv_path,
+        &[PATH_COLUMN, DATETIME_COLUMN, SPECIES_COLUMN],
+        &column_map,
+    )?;
+    let mut df = CsvReadOptions::default()
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(csv_path))?
+        .finish()?;
+    reject_duplicate_csv_columns(&df)?;
+    column_map.apply(&mut df)?;
+    // Adapts to old tags.csv
+    if df.get_column_index(DATETIME_COLUMN).is_none() {
+        df.rename(LEGACY_DATETIME_COLUMN, DATETIME_COLUMN.into())?;
+    }
+
+    let paths = df.column(PATH_COLUMN)?.str()?.clone();
+    let datetimes = df.column(DATETIME_COLUMN)?.str()?.clone();
+    let species = df.column(SPECIES_COLUMN)?.str()?.clone();
+    let counts = optional_column(&df, COUNT_COLUMN)?;
+    let sexes = optional_column(&df, SEX_COLUMN)?;
+    let deployments: Option<StringChunked> = optional_column(&df, "deployment")?;
+    let deploy_path_index = match deployments {
+        Some(_) => None,
+        None => {
+            let path_sample = paths
+                .iter()
+                .flatten()
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("No path values in the CSV"))?
+                .to_string();
+            Some(prompt_deployment_path_index(
+                &mut Prompt::new()?,
+                path_sample,
+            )?)
+        }
+    };
+    let value = |column: &Option<StringChunked>, row: usize| {
+        column
+            .as_ref()
+            .and_then(|column| column.get(row))
+            .map(str::trim)
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    let mut files: Vec<SequenceFile> = Vec::new();
+    let mut file_of_path: HashMap<&str, usize> = HashMap::new();
+    let mut file_of_row: Vec<Option<usize>> = vec![None; df.height()];
+    for (row, path) in paths.iter().enumerate() {
+        let Some(path) = path.filter(|path| !path.is_empty()) else {
+            continue;
+        };
+        let index = *file_of_path.entry(path).or_insert_with(|| {
+            let deployment = match (&deployments, deploy_path_index) {
+                (Some(deployments), _) => deployments
+                    .get(row)
+                    .filter(|deployment| !deployment.is_empty())
+                    .map(str::to_string),
+                (None, Some(index)) => deployment_from_path(Path::new(path), index).ok(),
+                (None, None) => None,
+            };
+            let datetime = datetimes.get(row).map(str::trim).and_then(|value| {
+                NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+                    .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+                    .ok()
+            });
+            files.push(SequenceFile {
+                path: path.to_string(),
+                rows: Vec::new(),
+                deployment,
+                datetime,
+                tags: BTreeSet::new(),
+            });
+            files.len() - 1
+        });
+        let file = &mut files[index];
+        file.rows.push(row);
+        file_of_row[row] = Some(index);
+        let species = species.get(row).map(str::trim).unwrap_or_default();
+        if !species.is_empty() {
+            file.tags
+                .insert((species.to_string(), value(&counts, row), value(&sexes, row)));
+        }
+    }
+    let num_undated = files.iter().filter(|file| file.datetime.is_none()).count();
+    if num_undated > 0 {
+        println!("Warning: {num_undated} file(s) without a valid datetime, not grouped");
+    }
+
+    let sequences = sequences(
+        files
+            .iter()
+            .map(|file| (file.deployment.as_deref(), file.datetime)),
+        chrono::Duration::from_std(window)?,
+    );
+    let mut propagated: BTreeMap<usize, &BTreeSet<Tag>> = BTreeMap::new();
+    let mut conflicts: Vec<(usize, usize)> = Vec::new();
+    let mut num_propagated_sequences = 0;
+    for (sequence_index, sequence) in sequences.iter().enumerate() {
+        let (tagged, untagged): (Vec<usize>, Vec<usize>) = sequence
+            .iter()
+            .partition(|&&index| !files[index].tags.is_empty());
+        if tagged.is_empty() || untagged.is_empty() {
+            continue;
+        }
+        let tag_sets: BTreeSet<&BTreeSet<Tag>> =
+            tagged.iter().map(|&index| &files[index].tags).collect();
+        if tag_sets.len() > 1 {
+            conflicts.extend(sequence.iter().map(|&index| (sequence_index + 1, index)));
+            continue;
+        }
+        let tags = tag_sets.into_iter().next().unwrap();
+        propagated.extend(untagged.into_iter().map(|index| (index, tags)));
+        num_propagated_sequences += 1;
+    }
+
+    // Each propagated file gets one row per tag, copied from its first row
+    let mut take_rows: Vec<IdxSize> = Vec::new();
+    let mut overrides: Vec<Option<&Tag>> = Vec::new();
+    for (row, file) in file_of_row.iter().enumerate() {
+        match file.and_then(|index| propagated.get(&index).map(|tags| (index, tags))) {
+            Some((index, tags)) => {
+                if files[index].rows[0] == row {
+                    for tag in tags.iter() {
+                        take_rows.push(row as IdxSize);
+                        overrides.push(Some(tag));
+                    }
+                }
+            }
+            None => {
+                take_rows.push(row as IdxSize);
+                overrides.push(None);
+            }
+        }
+    }
+    let mut df_output = df.take(&IdxCa::from_vec("rows".into(), take_rows))?;
+    for (update_type, field) in TAG_FIELDS {
+        let name = update_type.col_name();
+        let Some(original) = optional_column(&df_output, name)? else {
+            continue;
+        };
+        let values: StringChunked = original
+            .iter()
+            .zip(&overrides)
+            .map(|(value, tag)| match tag {
+                Some(tag) => Some(field(tag).as_str()).filter(|value| !value.is_empty()),
+                None => value,
+            })
+            .collect();
+        df_output.with_column(values.with_name(name.into()).into_column())?;
+    }
+    df_output.with_column(Column::new(
+        "propagated".into(),
+        overrides.iter().map(Option::is_some).collect::<Vec<_>>(),
+    ))?;
+
+    fs::create_dir_all(&output_dir)?;
+    let output_path = output_dir.join("tags_propagated.csv");
+    let mut file = fs::File::create(&output_path)?;
+    csv_writer(&mut file).finish(&mut df_output)?;
+    println!(
+        "{} sequence(s) within {}s, tags propagated to {} file(s) in {num_propagated_sequences} sequence(s)",
+        sequences.len(),
+        window.as_secs_f64(),
+        propagated.len()
+    );
+    println!("Saved to {}", output_path.display());
+
+    if !conflicts.is_empty() {
+        let num_conflicts = conflicts
+            .iter()
+            .map(|(sequence, _)| sequence)
+            .collect::<BTreeSet<_>>()
+            .len();
+        let conflict_files: Vec<&SequenceFile> =
+            conflicts.iter().map(|&(_, index)| &files[index]).collect();
+        let mut df_conflicts = df!(
+            "sequence" => conflicts.iter().map(|&(sequence, _)| sequence as u32).collect::<Vec<_>>(),
+            "deployment" => conflict_files.iter().map(|file| file.deployment.as_deref()).collect::<Vec<_>>(),
+            PATH_COLUMN => conflict_files.iter().map(|file| file.path.as_str()).collect::<Vec<_>>(),
+            DATETIME_COLUMN => conflict_files
+                .iter()
+                .map(|file| file.datetime.map(|datetime| datetime.format("%Y-%m-%d %H:%M:%S").to_string()))
+                .collect::<Vec<_>>(),
+            "tags" => conflict_files
+                .iter()
+                .map(|file| file.tags.iter().map(format_tag).collect::<Vec<_>>().join("; "))
+                .collect::<Vec<_>>(),
+        )?;
+        let conflicts_path = output_dir.join("propagation_conflicts.csv");
+        let mut file = fs::File::create(&conflicts_path)?;
+        csv_writer(&mut file).finish(&mut df_conflicts)?;
+        println!(
+            "Warning: {num_conflicts} sequence(s) with conflicting tags not propagated, review {}",
+            conflicts_path.display()
+        );
+    }
+
+    if apply_xmp && !propagated.is_empty() {
+        apply_to_xmp(&files, &propagated)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use crate::schema::DEPLOYMENT_ID_COLUMN;
+use crate::utils::{
+    ResourceType, csv_writer, path_enumerate, reject_duplicate_csv_columns, resolve_deploy_dir,
+};
+use polars::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Exit code when a QA check found problems, the report being complete
+pub const QA_FAILED_EXIT_CODE: i32 = 3;
+
+pub const DEFAULT_EXPECTED_COUNT_COLUMN: &str = "expectedMediaCount";
+
+/// How far below the expected media count a deployment may be, in files or percent
+#[derive(Clone, Copy, Debug)]
+pub enum CountTolerance {
+    Files(u64),
+    Percent(f64),
+}
+
+impl CountTolerance {
+    fn allowed_shortfall(self, expected: u64) -> u64 {
+        match self {
+            CountTolerance::Files(files) => files,
+            CountTolerance::Percent(percent) => (expected as f64 * percent / 100.0).floor() as u64,
+        }
+    }
+}
+
+// Parse tolerances like "5" (files) or "2%" (of the expected count)
+pub fn parse_count_tolerance(value: &str) -> anyhow::Result<CountTolerance> {
+    let value = value.trim();
+    match value.strip_suffix('%') {
+        Some(percent) => {
+            let percent: f64 = percent
+                .trim()
+                .parse()
+                .ok()
+                .filter(|percent| (0.0..=100.0).contains(percent))
+                .ok_or_else(|| anyhow::anyhow!("Invalid tolerance: {value}, expected 0-100%"))?;
+            Ok(CountTolerance::Percent(percent))
+        }
+        None => value
+            .parse()
+            .map(CountTolerance::Files)
+            .map_err(|_| anyhow::anyhow!("Invalid tolerance: {value}, e.g. 5 or 2%")),
+    }
+}
+
+/// Outcome of a deployment in a QA check
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum QaStatus {
+    Ok,
+    Short,
+    Missing,
+    NotChecked,
+}
+
+impl QaStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QaStatus::Ok => "ok",
+            QaStatus::Short => "short",
+            QaStatus::Missing => "missing",
+            QaStatus::NotChecked => "not checked",
+        }
+    }
+
+    pub fn is_failure(self) -> bool {
+        matches!(self, QaStatus::Short | QaStatus::Missing)
+    }
+}
+
+/// Media found in a deployment directory against the count expected from the deploy table
+pub struct CountCheck {
+    pub deployment: String,
+    pub expected: Option<u64>,
+    pub actual: Option<u64>,
+    pub status: QaStatus,
+}
+
+/// Findings of `serval qa`, one row per deployment and check
+#[derive(Default)]
+pub struct QaReport {
+    pub counts: Vec<CountCheck>,
+}
+
+impl QaReport {
+    pub fn num_failures(&self) -> usize {
+        self.counts
+            .iter()
+            .filter(|check| check.status.is_failure())
+            .count()
+    }
+
+    fn write(&self, output_dir: &Path) -> anyhow::Result<PathBuf> {
+        let rows = &self.counts;
+        let mut df = DataFrame::new(
+            rows.len(),
+            vec![
+                Column::new("check".into(), vec!["counts"; rows.len()]),
+                Column::new(
+                    DEPLOYMENT_ID_COLUMN.into(),
+                    rows.iter()
+                        .map(|check| check.deployment.as_str())
+                        .collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "expected".into(),
+                    rows.iter().map(|check| check.expected).collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "actual".into(),
+                    rows.iter().map(|check| check.actual).collect::<Vec<_>>(),
+                ),
+                Column::new(
+                    "status".into(),
+                    rows.iter()
+                        .map(|check| check.status.as_str())
+                        .collect::<Vec<_>>(),
+                ),
+            ],
+        )?;
+        fs::create_dir_all(output_dir)?;
+        let path = output_dir.join("qa_report.csv");
+        let mut file = fs::File::create(&path)?;
+        csv_writer(&mut file).finish(&mut df)?;
+        Ok(path)
+    }
+}
+
+/// Compare the media of each deployment directory (<project>/<collection>/<deploymentID>, as
+/// for align) with the expected count in `column` of the deploy table. Deployments short by
+/// more than the tolerance or without a directory fail, those without a count aren't checked.
+pub fn check_media_counts(
+    project_dir: &Path,
+    deploy_table: &Path,
+    column: &str,
+    tolerance: CountTolerance,
+) -> anyhow::Result<Vec<CountCheck>> {
+    let deploy_df = CsvReadOptions::default()
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(deploy_table.to_path_buf()))?
+        .finish()?;
+    reject_duplicate_csv_columns(&deploy_df)?;
+    let deploy_ids = deploy_df.column(DEPLOYMENT_ID_COLUMN)?.str()?;
+    let expected_counts = deploy_df
+        .column(column)
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Column {column} not found in {}, choose it with --column",
+                deploy_table.display()
+            )
+        })?
+        .str()?;
+
+    let mut checks = Vec::new();
+    let mut invalid_rows = Vec::new();
+    for (row, (deploy_id, expected)) in deploy_ids.iter().zip(expected_counts.iter()).enumerate() {
+        let Some(deploy_id) = deploy_id else {
+            continue;
+        };
+        let expected = match expected.map(str::trim).filter(|value| !value.is_empty()) {
+            None => None,
+            Some(value) => match value.parse::<u64>() {
+                Ok(expected) => Some(expected),
+                Err(_) => {
+                    // Line numbers count the header
+                    invalid_rows.push(format!("line {}: '{value}'", row + 2));
+                    continue;
+                }
+            },
+        };
+        let deploy_dir = deploy_id
+            .rsplit_once('_')
+            .and_then(|(_, collection)| resolve_deploy_dir(project_dir, collection, deploy_id).0);
+        let actual = deploy_dir.map(|dir| path_enumerate(dir, ResourceType::Media).len() as u64);
+        let status = match (expected, actual) {
+            (None, _) => QaStatus::NotChecked,
+            (Some(_), None) => QaStatus::Missing,
+            (Some(expected), Some(actual)) => {
+                if actual + tolerance.allowed_shortfall(expected) < expected {
+                    QaStatus::Short
+                } else {
+                    QaStatus::Ok
+                }
+            }
+        };
+        checks.push(CountCheck {
+            deployment: deploy_id.to_string(),
+            expected,
+            actual,
+            status,
+        });
+    }
+    if !invalid_rows.is_empty() {
+        return Err(anyhow::anyhow!(
+            "{column} should be a whole number of files in {}: {}",
+            deploy_table.display(),
+            invalid_rows.join(", ")
+        ));
+    }
+    Ok(checks)
+}
+
+/// Run the QA checks asked for, print what failed and write qa_report.csv to `output_dir`
+pub fn run_qa(
+    project_dir: &Path,
+    counts: Option<(&Path, &str)>,
+    tolerance: CountTolerance,
+    output_dir: &Path,
+) -> anyhow::Result<QaReport> {
+    let mut report = QaReport::default();
+    let Some((deploy_table, column)) = counts else {
+        return Err(anyhow::anyhow!("Nothing to check, e.g. pass --counts"));
+    };
+    report.counts = check_media_counts(project_dir, deploy_table, column, tolerance)?;
+
+    let num_with = |status: QaStatus| {
+        report
+            .counts
+            .iter()
+            .filter(|check| check.status == status)
+            .count()
+    };
+    println!(
+        "Media counts: {} ok, {} short, {} missing, {} not checked",
+        num_with(QaStatus::Ok),
+        num_with(QaStatus::Short),
+        num_with(QaStatus::Missing),
+        num_with(QaStatus::NotChecked)
+    );
+    for check in &report.counts {
+        match check.status {
+            QaStatus::Short => println!(
+                "Short: {} has {} media file(s), {} expected",
+                check.deployment,
+                check.actual.unwrap_or_default(),
+                check.expected.unwrap_or_default()
+            ),
+            QaStatus::Missing => println!(
+                "Missing: {} has no directory under {}",
+                check.deployment,
+                project_dir.display()
+            ),
+            QaStatus::NotChecked => {
+                println!("Not checked: {} has no expected count", check.deployment)
+            }
+            QaStatus::Ok => {}
+        }
+    }
+    let path = report.write(output_dir)?;
+    println!("Saved to {}", path.display());
+    Ok(report)
+}
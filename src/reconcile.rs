@@ -0,0 +1,306 @@
+use crate::schema::{PATH_COLUMN, XMP_UPDATE_COLUMN};
+use crate::tags::DELETE_TAG_VALUE;
+use crate::utils::{
+    ColumnMap, XmpUpdateType, check_csv_columns, csv_writer, existing_sidecar_for, media_path_for,
+    normalize_path_str, reject_duplicate_csv_columns, sidecar_path_for,
+};
+use polars::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const OUTPUT_PATH_COLUMN: &str = "output_path";
+const REVIEWED_PATH_COLUMN: &str = "reviewed_path";
+
+// One file of a tags.csv (several rows when it has several tags)
+pub(crate) struct TaggedFile {
+    pub(crate) path: String,
+    pub(crate) values: BTreeSet<String>,
+}
+
+// Media path of a tags.csv row with `/` separators, sidecar rows standing for their media
+pub(crate) fn media_key(path: &str) -> String {
+    let media = media_path_for(Path::new(path)).unwrap_or_else(|| PathBuf::from(path));
+    normalize_path_str(&media.to_string_lossy())
+}
+
+pub(crate) fn read_csv(
+    csv_path: &Path,
+    columns: &[&str],
+    column_map: &ColumnMap,
+) -> anyhow::Result<DataFrame> {
+    check_csv_columns(csv_path, columns, column_map)?;
+    let mut df = CsvReadOptions::default()
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(csv_path.to_path_buf()))?
+        .finish()?;
+    reject_duplicate_csv_columns(&df)?;
+    column_map.apply(&mut df)?;
+    Ok(df)
+}
+
+// Files of a tags.csv by media key, with the non-empty values of `tag_column`
+pub(crate) fn read_tagged_files(
+    csv_path: &Path,
+    tag_column: &str,
+    column_map: &ColumnMap,
+) -> anyhow::Result<BTreeMap<String, TaggedFile>> {
+    let df = read_csv(csv_path, &[PATH_COLUMN, tag_column], column_map)?;
+    let mut files: BTreeMap<String, TaggedFile> = BTreeMap::new();
+    for (path, value) in df
+        .column(PATH_COLUMN)?
+        .str()?
+        .iter()
+        .zip(df.column(tag_column)?.str()?.iter())
+    {
+        let Some(path) = path.filter(|path| !path.is_empty()) else {
+            continue;
+        };
+        let file = files.entry(media_key(path)).or_insert_with(|| TaggedFile {
+            path: path.to_string(),
+            values: BTreeSet::new(),
+        });
+        if let Some(value) = value.map(str::trim).filter(|value| !value.is_empty()) {
+            file.values.insert(value.to_string());
+        }
+    }
+    Ok(files)
+}
+
+// Sidecar an `xmp update` row of a tags.csv file writes to
+pub(crate) fn sidecar_target(path: &str) -> PathBuf {
+    let media = Path::new(path);
+    if media_path_for(media).is_some() {
+        media.to_path_buf()
+    } else {
+        existing_sidecar_for(media).unwrap_or_else(|| sidecar_path_for(media))
+    }
+}
+
+// (old, xmp_update) pairs turning `current` into `new`: changed values pair up as
+// replacements, extra new values are inserted and extra old values deleted
+pub(crate) fn correction_rows(
+    current: &BTreeSet<String>,
+    new: &BTreeSet<String>,
+) -> Vec<(String, String)> {
+    let removed: Vec<&String> = current.difference(new).collect();
+    let added: Vec<&String> = new.difference(current).collect();
+    let mut rows = Vec::new();
+    for (index, new_value) in added.iter().enumerate() {
+        rows.push((
+            removed
+                .get(index)
+                .map(|value| value.to_string())
+                .unwrap_or_default(),
+            new_value.to_string(),
+        ));
+    }
+    for removed_value in removed.iter().skip(added.len()) {
+        rows.push((removed_value.to_string(), DELETE_TAG_VALUE.to_string()));
+    }
+    rows
+}
+
+// Copies of an extraction (manifest.csv) looked up by their path
+struct Manifest {
+    originals: Vec<String>,
+    outputs: Vec<Vec<String>>,
+    by_output: HashMap<String, usize>,
+    by_name: HashMap<String, Vec<usize>>,
+}
+
+impl Manifest {
+    fn read(manifest_path: &Path) -> anyhow::Result<Self> {
+        let df = read_csv(
+            manifest_path,
+            &[PATH_COLUMN, OUTPUT_PATH_COLUMN],
+            &ColumnMap::default(),
+        )?;
+        let mut manifest = Self {
+            originals: Vec::new(),
+            outputs: Vec::new(),
+            by_output: HashMap::new(),
+            by_name: HashMap::new(),
+        };
+        for (original, output) in df
+            .column(PATH_COLUMN)?
+            .str()?
+            .iter()
+            .zip(df.column(OUTPUT_PATH_COLUMN)?.str()?.iter())
+        {
+            let (Some(original), Some(output)) = (original, output) else {
+                continue;
+            };
+            let index = manifest.originals.len();
+            let output = normalize_path_str(output);
+            let components: Vec<String> = output.split('/').map(str::to_string).collect();
+            manifest
+                .by_name
+                .entry(components.last().cloned().unwrap_or_default())
+                .or_default()
+                .push(index);
+            manifest.by_output.insert(output, index);
+            manifest.originals.push(normalize_path_str(original));
+            manifest.outputs.push(components);
+        }
+        Ok(manifest)
+    }
+
+    // Copy at `path`, or when the reviewed copies were moved, the one copy whose trailing
+    // directories match the most (renamed and collision-suffixed names are in the manifest)
+    fn find(&self, path: &str) -> Result<usize, String> {
+        if let Some(&index) = self.by_output.get(path) {
+            return Ok(index);
+        }
+        let components: Vec<&str> = path.split('/').collect();
+        let mut candidates = self
+            .by_name
+            .get(*components.last().unwrap_or(&""))
+            .cloned()
+            .unwrap_or_default();
+        if candidates.is_empty() {
+            return Err("not in the manifest".to_string());
+        }
+        for depth in 2..=components.len() {
+            if candidates.len() == 1 {
+                break;
+            }
+            let narrowed: Vec<usize> = candidates
+                .iter()
+                .copied()
+                .filter(|&index| {
+                    let output = &self.outputs[index];
+                    output.len() >= depth
+                        && output[output.len() - depth..]
+                            .iter()
+                            .zip(&components[components.len() - depth..])
+                            .all(|(a, b)| a == b)
+                })
+                .collect();
+            if narrowed.is_empty() {
+                break;
+            }
+            candidates = narrowed;
+        }
+        match candidates.as_slice() {
+            [index] => Ok(*index),
+            _ => Err(format!(
+                "matches {} copies in the manifest",
+                candidates.len()
+            )),
+        }
+    }
+}
+
+// Map re-tagged extraction copies back to their originals through the extract manifest and
+// write an `xmp update` CSV correcting the original sidecars, removed tags as DELETE_TAG rows.
+// Reviewed files that can't be mapped are listed, not dropped.
+pub fn reconcile_tags(
+    original_csv: PathBuf,
+    reviewed_csv: PathBuf,
+    manifest_path: PathBuf,
+    output_dir: PathBuf,
+    update_type: XmpUpdateType,
+    column_map: ColumnMap,
+) -> anyhow::Result<()> {
+    if update_type.tag_type().is_none() {
+        return Err(anyhow::anyhow!(
+            "reconcile compares tags, {update_type:?} is not a tag type"
+        ));
+    }
+    let tag_column = update_type.col_name();
+    let originals = read_tagged_files(&original_csv, tag_column, &column_map)?;
+    let reviewed = read_tagged_files(&reviewed_csv, tag_column, &column_map)?;
+    let manifest = Manifest::read(&manifest_path)?;
+
+    let mut unmapped: Vec<(String, String)> = Vec::new();
+    let mut mapped: BTreeMap<&str, &TaggedFile> = BTreeMap::new();
+    for (key, reviewed_file) in &reviewed {
+        let index = match manifest.find(key) {
+            Ok(index) => index,
+            Err(reason) => {
+                unmapped.push((reviewed_file.path.clone(), reason));
+                continue;
+            }
+        };
+        let original_key = manifest.originals[index].as_str();
+        if !originals.contains_key(original_key) {
+            unmapped.push((
+                reviewed_file.path.clone(),
+                format!("original {original_key} not in the original tags CSV"),
+            ));
+            continue;
+        }
+        match mapped.get(original_key) {
+            Some(other) if other.values != reviewed_file.values => unmapped.push((
+                reviewed_file.path.clone(),
+                format!(
+                    "disagrees with {}, another copy of {original_key}",
+                    other.path
+                ),
+            )),
+            Some(_) => {}
+            None => {
+                mapped.insert(original_key, reviewed_file);
+            }
+        }
+    }
+
+    let mut target_paths: Vec<String> = Vec::new();
+    let mut old_values: Vec<String> = Vec::new();
+    let mut new_values: Vec<String> = Vec::new();
+    let mut reviewed_paths: Vec<String> = Vec::new();
+    let mut num_changed = 0;
+    for (original_key, reviewed_file) in &mapped {
+        let original = &originals[*original_key];
+        let rows = correction_rows(&original.values, &reviewed_file.values);
+        if rows.is_empty() {
+            continue;
+        }
+        num_changed += 1;
+        let sidecar = sidecar_target(&original.path);
+        for (old_value, new_value) in rows {
+            target_paths.push(sidecar.to_string_lossy().into_owned());
+            old_values.push(old_value);
+            new_values.push(new_value);
+            reviewed_paths.push(reviewed_file.path.clone());
+        }
+    }
+
+    fs::create_dir_all(&output_dir)?;
+    let mut df_updates = df!(
+        PATH_COLUMN => target_paths,
+        tag_column => old_values,
+        XMP_UPDATE_COLUMN => new_values,
+        REVIEWED_PATH_COLUMN => reviewed_paths,
+    )?;
+    let updates_path = output_dir.join("reconcile_updates.csv");
+    let mut file = fs::File::create(&updates_path)?;
+    csv_writer(&mut file).finish(&mut df_updates)?;
+    println!(
+        "{} of {} reviewed file(s) mapped to originals, {num_changed} with changed {tag_column}",
+        mapped.len(),
+        reviewed.len()
+    );
+    println!(
+        "Saved to {}, apply with serval xmp update -t {tag_column}",
+        updates_path.display()
+    );
+
+    if !unmapped.is_empty() {
+        let (paths, reasons): (Vec<String>, Vec<String>) = unmapped.into_iter().unzip();
+        let mut df_unmapped = df!(
+            PATH_COLUMN => &paths,
+            "reason" => reasons,
+        )?;
+        let unmapped_path = output_dir.join("reconcile_unmapped.csv");
+        let mut file = fs::File::create(&unmapped_path)?;
+        csv_writer(&mut file).finish(&mut df_unmapped)?;
+        println!(
+            "Warning: {} reviewed row(s) not reconciled, review {}",
+            paths.len(),
+            unmapped_path.display()
+        );
+    }
+    Ok(())
+}
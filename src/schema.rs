@@ -1,6 +1,9 @@
+use crate::info::environment_json;
+use crate::utils::media_path_for;
 use anyhow::anyhow;
 use polars::prelude::*;
-use std::path::{Path, PathBuf};
+use std::fs;
+use std::path::Path;
 
 pub const PATH_COLUMN: &str = "path";
 pub const FILENAME_COLUMN: &str = "filename";
@@ -17,10 +20,47 @@ pub const RATING_COLUMN: &str = "rating";
 pub const CUSTOM_COLUMN: &str = "custom";
 pub const XMP_UPDATE_COLUMN: &str = "xmp_update";
 pub const XMP_UPDATE_DATETIME_COLUMN: &str = "xmp_update_datetime";
+// Optional xmp update column, the xmp:Rating (0-5) to write alongside the tag update
+pub const RATING_UPDATE_COLUMN: &str = "rating_update";
 pub const SUBJECTS_COLUMN: &str = "subjects";
 pub const TIME_MODIFIED_COLUMN: &str = "time_modified";
 pub const EVENT_ID_COLUMN: &str = "event_id";
 pub const DEPLOYMENT_ID_COLUMN: &str = "deploymentID";
+pub const FILE_KEY_COLUMN: &str = "file_key";
+pub const MEDIA_PATH_COLUMN: &str = "media_path";
+pub const SIDECAR_EXISTS_COLUMN: &str = "sidecar_exists";
+pub const MEDIA_EXISTS_COLUMN: &str = "media_exists";
+pub const MEDIA_TRASHED_COLUMN: &str = "media_trashed";
+pub const TRASHED_COLUMN: &str = "trashed";
+pub const TAGGER_COLUMN: &str = "tagger";
+pub const PICK_LABEL_COLUMN: &str = "pick_label";
+pub const COLOR_LABEL_COLUMN: &str = "color_label";
+pub const DATETIME_UTC_COLUMN: &str = "datetime_utc";
+pub const DATETIME_SOURCE_COLUMN: &str = "datetime_source";
+// Parts of the datetime for pivot tables (--derive-dateparts)
+pub const DATE_COLUMN: &str = "date";
+pub const YEAR_COLUMN: &str = "year";
+pub const MONTH_COLUMN: &str = "month";
+pub const HOUR_COLUMN: &str = "hour";
+pub const CAMERA_MAKE_COLUMN: &str = "camera_make";
+pub const CAMERA_MODEL_COLUMN: &str = "camera_model";
+pub const CAMERA_SERIAL_COLUMN: &str = "camera_serial";
+pub const DEPLOYMENT_COLUMN: &str = "deployment";
+// Columns read from a camtrap-dp observations.csv by capture --camtrap-dp
+pub const CAMTRAP_DP_COLUMNS: &[&str] = &[
+    "observationID",
+    DEPLOYMENT_ID_COLUMN,
+    "eventStart",
+    "scientificName",
+    "individualID",
+];
+// Deploy table column with the camera clock offset from UTC, e.g. +08:00
+pub const UTC_OFFSET_COLUMN: &str = "utcOffset";
+// digiKam stores Pick/Color labels as indices into these names
+pub const PICK_LABELS: &[&str] = &["none", "rejected", "pending", "accepted"];
+pub const COLOR_LABELS: &[&str] = &[
+    "none", "red", "orange", "yellow", "green", "blue", "magenta", "gray", "black", "white",
+];
 pub const CANONICAL_TAGS_HEADER: &[&str] = &[
     PATH_COLUMN,
     FILENAME_COLUMN,
@@ -39,6 +79,611 @@ pub const CANONICAL_TAGS_HEADER: &[&str] = &[
     XMP_UPDATE_DATETIME_COLUMN,
 ];
 
+// Optional observe columns, appended after the canonical header when present
+pub const OPTIONAL_TAGS_COLUMNS: &[&str] = &[
+    FILE_KEY_COLUMN,
+    MEDIA_PATH_COLUMN,
+    SIDECAR_EXISTS_COLUMN,
+    MEDIA_EXISTS_COLUMN,
+    MEDIA_TRASHED_COLUMN,
+    TAGGER_COLUMN,
+    PICK_LABEL_COLUMN,
+    COLOR_LABEL_COLUMN,
+    DATETIME_UTC_COLUMN,
+    DATETIME_SOURCE_COLUMN,
+    DATE_COLUMN,
+    YEAR_COLUMN,
+    MONTH_COLUMN,
+    HOUR_COLUMN,
+    CAMERA_MAKE_COLUMN,
+    CAMERA_MODEL_COLUMN,
+    CAMERA_SERIAL_COLUMN,
+    DEPLOYMENT_COLUMN,
+    EVENT_ID_COLUMN,
+    TRASHED_COLUMN,
+    TIME_MODIFIED_COLUMN,
+];
+
+// Serval columns an external CSV can be mapped onto (--column-map)
+pub fn is_known_column(name: &str) -> bool {
+    CANONICAL_TAGS_HEADER.contains(&name)
+        || OPTIONAL_TAGS_COLUMNS.contains(&name)
+        || [
+            SUBJECTS_COLUMN,
+            TIME_MODIFIED_COLUMN,
+            DEPLOYMENT_ID_COLUMN,
+            RATING_UPDATE_COLUMN,
+        ]
+        .contains(&name)
+}
+
+/// Serval output documented by a `.schema.json` written next to it (`serval schema <kind>`)
+#[derive(clap::ValueEnum, PartialEq, Clone, Copy, Debug)]
+pub enum OutputKind {
+    Tags,
+    SpeciesStats,
+    SpeciesStatsByTagger,
+    TemporalIndependence,
+    Events,
+    CountByDeployment,
+    CountAll,
+    Demographics,
+    DemographicsByDeployment,
+}
+
+/// One column of an output: JSON-ish dtype, meaning, and format or units when relevant
+pub struct ColumnDoc {
+    pub name: &'static str,
+    pub dtype: &'static str,
+    pub description: &'static str,
+    pub format: &'static str,
+}
+
+const fn column_doc(
+    name: &'static str,
+    dtype: &'static str,
+    description: &'static str,
+    format: &'static str,
+) -> ColumnDoc {
+    ColumnDoc {
+        name,
+        dtype,
+        description,
+        format,
+    }
+}
+
+const CAMERA_LOCAL_DATETIME: &str = "%Y-%m-%d %H:%M:%S, camera local time without timezone";
+
+const DATE_DOC: ColumnDoc = column_doc(
+    DATE_COLUMN,
+    "date",
+    "Date of the datetime, empty without one (--derive-dateparts)",
+    "%Y-%m-%d",
+);
+const YEAR_DOC: ColumnDoc = column_doc(
+    YEAR_COLUMN,
+    "integer",
+    "Year of the datetime (--derive-dateparts)",
+    "",
+);
+const MONTH_DOC: ColumnDoc = column_doc(
+    MONTH_COLUMN,
+    "integer",
+    "Month of the datetime (--derive-dateparts)",
+    "1-12",
+);
+const HOUR_DOC: ColumnDoc = column_doc(
+    HOUR_COLUMN,
+    "integer",
+    "Hour of the datetime, camera local time (--derive-dateparts)",
+    "0-23",
+);
+
+const DEPLOYMENT_DOC: ColumnDoc = column_doc(
+    DEPLOYMENT_COLUMN,
+    "string",
+    "Deployment of the record, from the deploy table or the chosen level of the path",
+    "",
+);
+const TIME_DOC: ColumnDoc = column_doc(
+    "time",
+    "datetime",
+    "Datetime of the record",
+    CAMERA_LOCAL_DATETIME,
+);
+const TARGET_SPECIES_DOC: ColumnDoc = column_doc(
+    SPECIES_COLUMN,
+    "string",
+    "Species of the record, when the analysis target is species",
+    "",
+);
+const TARGET_INDIVIDUAL_DOC: ColumnDoc = column_doc(
+    INDIVIDUAL_COLUMN,
+    "string",
+    "Individual of the record, when the analysis target is individual",
+    "",
+);
+const RECORD_PATH_DOC: ColumnDoc = column_doc(
+    PATH_COLUMN,
+    "string",
+    "Path of the media or XMP file of the record, as listed in the input tags CSV",
+    "",
+);
+const OBSERVATION_ID_DOC: ColumnDoc = column_doc(
+    "observationID",
+    "string",
+    "Observation of the record, with --camtrap-dp input instead of path",
+    "",
+);
+const SEX_DOC: ColumnDoc = column_doc(
+    SEX_COLUMN,
+    "string",
+    "Sex of the individuals, \"conflicting\" when tagged with several, \"unknown\" when untagged",
+    "",
+);
+const AGE_DOC: ColumnDoc = column_doc(
+    "age",
+    "string",
+    "Age class of the individuals, \"mixed\" when tagged with several, \"unknown\" when untagged",
+    "",
+);
+const INDIVIDUALS_DOC: ColumnDoc = column_doc(
+    "individuals",
+    "integer",
+    "Distinct individuals seen in independent records",
+    "count of individuals",
+);
+
+const TAGS_DOCS: &[ColumnDoc] = &[
+    column_doc(
+        PATH_COLUMN,
+        "string",
+        "Media or XMP file of the row, archive.zip!/inner/path for files read from a ZIP archive",
+        "",
+    ),
+    column_doc(FILENAME_COLUMN, "string", "File name of the row", ""),
+    column_doc(
+        MEDIA_TYPE_COLUMN,
+        "string",
+        "Media type inferred from the media path (the sidecar path without .xmp)",
+        "IANA media type, e.g. image/jpeg",
+    ),
+    column_doc(
+        DATETIME_COLUMN,
+        "datetime",
+        "Capture datetime, from DateTimeOriginal, CreateDate or the file modified time",
+        CAMERA_LOCAL_DATETIME,
+    ),
+    column_doc(
+        LATITUDE_COLUMN,
+        "number",
+        "GPS latitude of the file, from XMP or with observe --gps the EXIF of images, empty when the file has none",
+        "decimal degrees, WGS84",
+    ),
+    column_doc(
+        LONGITUDE_COLUMN,
+        "number",
+        "GPS longitude of the file, from XMP or with observe --gps the EXIF of images, empty when the file has none",
+        "decimal degrees, WGS84",
+    ),
+    column_doc(
+        SPECIES_COLUMN,
+        "string",
+        "Species tag, one row per species when a file has several",
+        "",
+    ),
+    column_doc(
+        INDIVIDUAL_COLUMN,
+        "string",
+        "Individual tag, one row per individual when a file has several",
+        "",
+    ),
+    column_doc(
+        COUNT_COLUMN,
+        "string",
+        "Count tag of the file as tagged (count_tags in the raw debug CSV), not a number of rows",
+        "",
+    ),
+    column_doc(SEX_COLUMN, "string", "Sex tag of the file", ""),
+    column_doc(BODYPART_COLUMN, "string", "Bodypart tag of the file", ""),
+    column_doc(RATING_COLUMN, "string", "xmp:Rating of the file", "0-5"),
+    column_doc(
+        CUSTOM_COLUMN,
+        "string",
+        "Free-form column kept for users",
+        "",
+    ),
+    column_doc(
+        XMP_UPDATE_COLUMN,
+        "string",
+        "Replacement tag value for serval xmp update, DELETE_TAG removes the tag",
+        "",
+    ),
+    column_doc(
+        XMP_UPDATE_DATETIME_COLUMN,
+        "string",
+        "Replacement datetime for serval xmp update --datetime",
+        "%Y-%m-%d %H:%M:%S",
+    ),
+    column_doc(
+        FILE_KEY_COLUMN,
+        "string",
+        "Short hash of the full path followed by the file name (--unique-name)",
+        "",
+    ),
+    column_doc(
+        MEDIA_PATH_COLUMN,
+        "string",
+        "Media file paired with the row (--pair-media)",
+        "",
+    ),
+    column_doc(
+        SIDECAR_EXISTS_COLUMN,
+        "boolean",
+        "Whether the XMP sidecar of the row exists (--pair-media)",
+        "",
+    ),
+    column_doc(
+        MEDIA_EXISTS_COLUMN,
+        "boolean",
+        "Whether media_path exists (--pair-media)",
+        "",
+    ),
+    column_doc(
+        MEDIA_TRASHED_COLUMN,
+        "boolean",
+        "Whether media_path was moved to the digiKam trash (.dtrash), a sidecar left behind is not an orphan (--pair-media)",
+        "",
+    ),
+    column_doc(
+        TAGGER_COLUMN,
+        "string",
+        "serval:tagger recorded by serval xmp update --tagger",
+        "",
+    ),
+    column_doc(
+        PICK_LABEL_COLUMN,
+        "string",
+        "digiKam Pick label",
+        "none, rejected, pending, accepted",
+    ),
+    column_doc(
+        COLOR_LABEL_COLUMN,
+        "string",
+        "digiKam Color label",
+        "none, red, orange, yellow, green, blue, magenta, gray, black, white",
+    ),
+    column_doc(
+        DATETIME_UTC_COLUMN,
+        "string",
+        "datetime converted to UTC with the deployment utcOffset or --utc-offset",
+        "%Y-%m-%dT%H:%M:%SZ, UTC",
+    ),
+    column_doc(
+        DATETIME_SOURCE_COLUMN,
+        "string",
+        "Where datetime was read from (observe --exif-fallback)",
+        "xmp, exif or none",
+    ),
+    DATE_DOC,
+    YEAR_DOC,
+    MONTH_DOC,
+    HOUR_DOC,
+    column_doc(
+        CAMERA_MAKE_COLUMN,
+        "string",
+        "tiff:Make of the camera that took the file (observe --camera-info)",
+        "",
+    ),
+    column_doc(
+        CAMERA_MODEL_COLUMN,
+        "string",
+        "tiff:Model of the camera that took the file (observe --camera-info)",
+        "",
+    ),
+    column_doc(
+        CAMERA_SERIAL_COLUMN,
+        "string",
+        "Body serial number of the camera, from exif:BodySerialNumber or aux:SerialNumber (observe --camera-info)",
+        "",
+    ),
+    column_doc(
+        DEPLOYMENT_COLUMN,
+        "string",
+        "Deployment of the file (observe --deploy-level or --deploy-table), empty when its path has none",
+        "",
+    ),
+    column_doc(
+        EVENT_ID_COLUMN,
+        "integer",
+        "Burst the file belongs to (observe --event-gap), a new one starts when the gap to the previous file of the deployment exceeds the threshold. Numbered from 1, empty without a datetime",
+        "",
+    ),
+    column_doc(
+        TRASHED_COLUMN,
+        "boolean",
+        "Whether the file is in the digiKam trash (.dtrash) (--include-trash)",
+        "",
+    ),
+    column_doc(
+        TIME_MODIFIED_COLUMN,
+        "datetime",
+        "File modified time when observe read it (observe --debug), checked by observe --resume",
+        "local time, truncated to seconds",
+    ),
+];
+
+// Rows of species_stats and count_all after the species, unless --no-format
+pub const UNTAGGED_ROW: &str = "(untagged)";
+pub const TOTAL_ROW: &str = "TOTAL";
+const PERCENT_DOC: ColumnDoc = column_doc(
+    "percent",
+    "number",
+    "Share of the TOTAL count, not written with --no-format",
+    "0-100, 2 decimals",
+);
+
+const SPECIES_STATS_DOCS: &[ColumnDoc] = &[
+    column_doc(
+        SPECIES_COLUMN,
+        "string",
+        "Species tag, by count then name, followed by the indeterminate labels (e.g. Unidentified) the same way, (untagged) for files without one and TOTAL",
+        "",
+    ),
+    column_doc(
+        "count",
+        "integer",
+        "Rows of the tags CSV with this species, i.e. tagged files, not independent records or animals",
+        "count of rows",
+    ),
+    PERCENT_DOC,
+];
+
+const SPECIES_STATS_BY_TAGGER_DOCS: &[ColumnDoc] = &[
+    column_doc(TAGGER_COLUMN, "string", "serval:tagger of the files", ""),
+    column_doc(SPECIES_COLUMN, "string", "Species tag", ""),
+    column_doc(
+        "count",
+        "integer",
+        "Rows of the tags CSV with this tagger and species",
+        "count of rows",
+    ),
+];
+
+const TEMPORAL_INDEPENDENCE_DOCS: &[ColumnDoc] = &[
+    RECORD_PATH_DOC,
+    OBSERVATION_ID_DOC,
+    DEPLOYMENT_DOC,
+    TIME_DOC,
+    TARGET_SPECIES_DOC,
+    TARGET_INDIVIDUAL_DOC,
+    DATE_DOC,
+    YEAR_DOC,
+    MONTH_DOC,
+    HOUR_DOC,
+];
+
+const EVENTS_DOCS: &[ColumnDoc] = &[
+    RECORD_PATH_DOC,
+    OBSERVATION_ID_DOC,
+    DEPLOYMENT_DOC,
+    TIME_DOC,
+    TARGET_SPECIES_DOC,
+    TARGET_INDIVIDUAL_DOC,
+    column_doc(
+        EVENT_ID_COLUMN,
+        "integer",
+        "Independent record (event) the record belongs to, numbered from 1",
+        "",
+    ),
+    DATE_DOC,
+    YEAR_DOC,
+    MONTH_DOC,
+    HOUR_DOC,
+];
+
+const COUNT_BY_DEPLOYMENT_DOCS: &[ColumnDoc] = &[
+    DEPLOYMENT_DOC,
+    TARGET_SPECIES_DOC,
+    TARGET_INDIVIDUAL_DOC,
+    column_doc(
+        "count",
+        "integer",
+        "Independent records of the target in the deployment",
+        "count of independent records",
+    ),
+];
+
+const COUNT_ALL_DOCS: &[ColumnDoc] = &[
+    column_doc(
+        SPECIES_COLUMN,
+        "string",
+        "Species of the records, by count then name, followed by the indeterminate labels when not excluded, (untagged) and TOTAL",
+        "",
+    ),
+    column_doc(
+        "count",
+        "integer",
+        "Independent records of the species over all deployments",
+        "count of independent records",
+    ),
+    PERCENT_DOC,
+];
+
+const DEMOGRAPHICS_DOCS: &[ColumnDoc] = &[SEX_DOC, AGE_DOC, INDIVIDUALS_DOC];
+
+const DEMOGRAPHICS_BY_DEPLOYMENT_DOCS: &[ColumnDoc] =
+    &[DEPLOYMENT_DOC, SEX_DOC, AGE_DOC, INDIVIDUALS_DOC];
+
+impl OutputKind {
+    /// File name prefix of the output
+    pub fn name(self) -> &'static str {
+        match self {
+            OutputKind::Tags => "tags",
+            OutputKind::SpeciesStats => "species_stats",
+            OutputKind::SpeciesStatsByTagger => "species_stats_by_tagger",
+            OutputKind::TemporalIndependence => "temporal-independence",
+            OutputKind::Events => "events",
+            OutputKind::CountByDeployment => "count_by_deployment",
+            OutputKind::CountAll => "count_all",
+            OutputKind::Demographics => "demographics",
+            OutputKind::DemographicsByDeployment => "demographics_by_deployment",
+        }
+    }
+
+    /// Every column the output can have, those depending on options included
+    pub fn columns(self) -> &'static [ColumnDoc] {
+        match self {
+            OutputKind::Tags => TAGS_DOCS,
+            OutputKind::SpeciesStats => SPECIES_STATS_DOCS,
+            OutputKind::SpeciesStatsByTagger => SPECIES_STATS_BY_TAGGER_DOCS,
+            OutputKind::TemporalIndependence => TEMPORAL_INDEPENDENCE_DOCS,
+            OutputKind::Events => EVENTS_DOCS,
+            OutputKind::CountByDeployment => COUNT_BY_DEPLOYMENT_DOCS,
+            OutputKind::CountAll => COUNT_ALL_DOCS,
+            OutputKind::Demographics => DEMOGRAPHICS_DOCS,
+            OutputKind::DemographicsByDeployment => DEMOGRAPHICS_BY_DEPLOYMENT_DOCS,
+        }
+    }
+}
+
+fn column_json(doc: &ColumnDoc) -> serde_json::Value {
+    let mut column = serde_json::json!({
+        "name": doc.name,
+        "dtype": doc.dtype,
+        "description": doc.description,
+    });
+    if !doc.format.is_empty() {
+        column["format"] = doc.format.into();
+    }
+    column
+}
+
+// Schema dtype of a written column, None for all-null columns that carry no type
+fn schema_dtype(dtype: &DataType) -> Option<&'static str> {
+    match dtype {
+        DataType::Null => None,
+        DataType::Boolean => Some("boolean"),
+        DataType::Float32 | DataType::Float64 => Some("number"),
+        DataType::Datetime(_, _) => Some("datetime"),
+        DataType::Date => Some("date"),
+        dtype if dtype.is_integer() => Some("integer"),
+        _ => Some("string"),
+    }
+}
+
+/// Schema of every column `kind` can have, as printed by `serval schema`
+pub fn output_schema(kind: OutputKind) -> serde_json::Value {
+    serde_json::json!({
+        "output": kind.name(),
+        "columns": kind.columns().iter().map(column_json).collect::<Vec<_>>(),
+    })
+}
+
+/// Write `<csv stem>.schema.json` next to `csv_path` for the columns of `df` as written.
+/// A column missing from the registry, or written with another dtype, is an error so the
+/// registry can't silently drift from the writers.
+pub fn write_output_schema(
+    csv_path: &Path,
+    kind: OutputKind,
+    df: &DataFrame,
+) -> anyhow::Result<()> {
+    write_output_schema_carrying(csv_path, kind, df, &[])
+}
+
+/// [`write_output_schema`] for outputs that copy the `carried` input columns through as they
+/// are (capture --carry-columns), these are documented by name and dtype only
+pub fn write_output_schema_carrying(
+    csv_path: &Path,
+    kind: OutputKind,
+    df: &DataFrame,
+    carried: &[String],
+) -> anyhow::Result<()> {
+    write_output_schema_with(
+        csv_path,
+        kind,
+        df,
+        carried,
+        "Copied from the input CSV, first value of the species",
+    )
+}
+
+/// [`write_output_schema`] for tags with the `extra_columns` of observe --extra-tag
+pub fn write_tags_schema_with_extra(
+    csv_path: &Path,
+    df: &DataFrame,
+    extra_columns: &[String],
+) -> anyhow::Result<()> {
+    write_output_schema_with(
+        csv_path,
+        OutputKind::Tags,
+        df,
+        extra_columns,
+        "Tags under a category declared with --extra-tag, several joined by the multi-value separator",
+    )
+}
+
+// Columns in `undocumented` are described by name, dtype and `description` only
+fn write_output_schema_with(
+    csv_path: &Path,
+    kind: OutputKind,
+    df: &DataFrame,
+    undocumented: &[String],
+    description: &str,
+) -> anyhow::Result<()> {
+    let mut columns = Vec::new();
+    for column in df.columns() {
+        if undocumented
+            .iter()
+            .any(|name| name == column.name().as_str())
+        {
+            columns.push(serde_json::json!({
+                "name": column.name().as_str(),
+                "dtype": schema_dtype(column.dtype()).unwrap_or("string"),
+                "description": description,
+            }));
+            continue;
+        }
+        let doc = kind
+            .columns()
+            .iter()
+            .find(|doc| doc.name == column.name().as_str())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Column {} of {} missing from the {} schema",
+                    column.name(),
+                    csv_path.display(),
+                    kind.name()
+                )
+            })?;
+        if let Some(dtype) = schema_dtype(column.dtype())
+            && dtype != doc.dtype
+        {
+            return Err(anyhow!(
+                "Column {} of {} is written as {dtype}, the {} schema says {}",
+                column.name(),
+                csv_path.display(),
+                kind.name(),
+                doc.dtype
+            ));
+        }
+        columns.push(column_json(doc));
+    }
+    let schema = serde_json::json!({
+        "output": kind.name(),
+        "file": csv_path.file_name().map(|name| name.to_string_lossy()),
+        "serval_version": env!("CARGO_PKG_VERSION"),
+        "environment": environment_json(),
+        "columns": columns,
+    });
+    fs::write(
+        csv_path.with_extension("schema.json"),
+        serde_json::to_string_pretty(&schema)?,
+    )?;
+    Ok(())
+}
+
 pub const LEGACY_DATETIME_COLUMN: &str = "datetime_original";
 pub const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png"];
 pub const VIDEO_EXTENSIONS: &[&str] = &["avi", "mp4", "mov"];
@@ -52,18 +697,11 @@ pub fn resource_extension(path: &Path) -> Option<String> {
         .map(|ext| ext.to_ascii_lowercase())
 }
 
-pub fn underlying_media_path(path: &Path) -> PathBuf {
-    if resource_extension(path).is_some_and(|ext| XMP_EXTENSIONS.contains(&ext.as_str())) {
-        let mut media_path = path.to_path_buf();
-        media_path.set_extension("");
-        media_path
-    } else {
-        path.to_path_buf()
-    }
-}
-
 pub fn media_extension(path: &Path) -> Option<String> {
-    resource_extension(&underlying_media_path(path))
+    match media_path_for(path) {
+        Some(media) => resource_extension(&media),
+        None => resource_extension(path),
+    }
 }
 
 pub fn infer_media_type(path: &Path) -> anyhow::Result<&'static str> {
@@ -87,7 +725,75 @@ pub fn infer_media_type(path: &Path) -> anyhow::Result<&'static str> {
     }
 }
 
-pub fn canonicalize_observe_tags_df(df: DataFrame) -> PolarsResult<DataFrame> {
+// Whether the first datetime of a text column ends in a UTC offset like +00:00
+fn has_utc_offsets(df: &DataFrame, name: &str) -> bool {
+    df.column(name)
+        .ok()
+        .and_then(|column| column.str().ok())
+        .and_then(|values| values.iter().flatten().find(|value| !value.is_empty()))
+        .is_some_and(|value| {
+            value.len() > 6
+                && value.as_bytes()[value.len() - 3] == b':'
+                && matches!(value.as_bytes()[value.len() - 6], b'+' | b'-')
+        })
+}
+
+/// Tags read back as text (CSV, or Parquet through `read_parquet_as_text`) with the dtypes
+/// observe writes them in, empty canonical columns as empty strings like a fresh run
+pub fn parse_observe_tags_text(df: DataFrame) -> PolarsResult<DataFrame> {
+    let columns: Vec<Expr> = df
+        .get_column_names()
+        .into_iter()
+        .map(|name| {
+            let column = col(name.clone());
+            match TAGS_DOCS.iter().find(|doc| doc.name == name.as_str()) {
+                // Written with an offset by observe --timezone keep
+                Some(doc) if doc.dtype == "datetime" && has_utc_offsets(&df, name.as_str()) => {
+                    column.str().strptime(
+                        DataType::Datetime(TimeUnit::Milliseconds, Some(TimeZone::UTC)),
+                        StrptimeOptions {
+                            format: Some("%Y-%m-%d %H:%M:%S%:z".into()),
+                            strict: false,
+                            ..Default::default()
+                        },
+                        lit("raise"),
+                    )
+                }
+                Some(doc) if doc.dtype == "datetime" => column.str().strptime(
+                    DataType::Datetime(TimeUnit::Milliseconds, None),
+                    StrptimeOptions {
+                        format: Some("%Y-%m-%d %H:%M:%S".into()),
+                        strict: false,
+                        ..Default::default()
+                    },
+                    lit("raise"),
+                ),
+                Some(doc) if doc.dtype == "date" => column.str().strptime(
+                    DataType::Date,
+                    StrptimeOptions {
+                        format: Some("%Y-%m-%d".into()),
+                        strict: false,
+                        ..Default::default()
+                    },
+                    lit("raise"),
+                ),
+                Some(doc) if doc.dtype == "boolean" => column.eq(lit("true")),
+                Some(doc) if doc.dtype == "number" => column.cast(DataType::Float64),
+                Some(doc) if doc.dtype == "integer" => column.cast(DataType::UInt32),
+                _ if CANONICAL_TAGS_HEADER.contains(&name.as_str()) => column.fill_null(lit("")),
+                _ => column,
+            }
+        })
+        .collect();
+    df.lazy().select(columns).collect()
+}
+
+/// Canonical header then the optional columns present, the `extra_columns` of observe
+/// --extra-tag last, other columns are dropped
+pub fn canonicalize_observe_tags_df(
+    df: DataFrame,
+    extra_columns: &[String],
+) -> PolarsResult<DataFrame> {
     let missing_columns = CANONICAL_TAGS_HEADER
         .iter()
         .filter(|col| {
@@ -98,6 +804,22 @@ pub fn canonicalize_observe_tags_df(df: DataFrame) -> PolarsResult<DataFrame> {
         .map(|col| lit("").alias(*col))
         .collect::<Vec<_>>();
 
+    let optional_columns = OPTIONAL_TAGS_COLUMNS
+        .iter()
+        .filter(|column| {
+            df.get_column_names()
+                .iter()
+                .any(|name| name.as_str() == **column)
+        })
+        .map(|name| col(*name))
+        .chain(
+            extra_columns
+                .iter()
+                .filter(|column| df.get_column_index(column).is_some())
+                .map(|name| col(name.as_str())),
+        )
+        .collect::<Vec<_>>();
+
     let mut df_lazy = df.lazy();
     if !missing_columns.is_empty() {
         df_lazy = df_lazy.with_columns(missing_columns);
@@ -108,7 +830,27 @@ pub fn canonicalize_observe_tags_df(df: DataFrame) -> PolarsResult<DataFrame> {
             CANONICAL_TAGS_HEADER
                 .iter()
                 .map(|name| col(*name))
+                .chain(optional_columns)
                 .collect::<Vec<_>>(),
         )
         .collect()
 }
+
+// Short stable hash (FNV-1a) of a path, independent of the separator style
+pub fn short_path_hash(path: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in path.replace('\\', "/").bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:08x}", hash >> 32)
+}
+
+// Join key that stays unique when flattened deployments share basenames
+pub fn file_key_for(path: &Path) -> String {
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    format!("{}_{}", short_path_hash(&path.to_string_lossy()), filename)
+}
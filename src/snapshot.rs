@@ -0,0 +1,285 @@
+use crate::progress::ServalProgress;
+use crate::schema::PATH_COLUMN;
+use crate::utils::{
+    ResourceType, csv_projection_columns, hash_file, is_parquet, path_enumerate,
+    read_parquet_as_text, reject_duplicate_csv_columns, with_io_permit,
+};
+use itertools::izip;
+use polars::prelude::*;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+const SIZE_COLUMN: &str = "size";
+const MTIME_COLUMN: &str = "mtime";
+const HASH_COLUMN: &str = "sha256";
+
+#[derive(Clone)]
+struct SnapshotRow {
+    path: String,
+    size: u64,
+    mtime: i64,
+    hash: String,
+}
+
+// Manifest paths are relative to the snapshot root with '/' separators,
+// so a project can still be verified after it has been moved
+fn snapshot_key(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .iter()
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn size_and_mtime(path: &Path) -> anyhow::Result<(u64, i64)> {
+    let metadata = fs::metadata(path)?;
+    let mtime = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default();
+    Ok((metadata.len(), mtime))
+}
+
+// Manifests are CSV or Parquet, chosen by extension
+fn read_manifest(manifest_path: &Path) -> anyhow::Result<HashMap<String, SnapshotRow>> {
+    let columns = [PATH_COLUMN, SIZE_COLUMN, MTIME_COLUMN, HASH_COLUMN];
+    let df = if is_parquet(manifest_path) {
+        read_parquet_as_text(manifest_path)?.select(columns)?
+    } else {
+        CsvReadOptions::default()
+            .with_infer_schema_length(Some(0))
+            .with_columns(csv_projection_columns(&columns))
+            .try_into_reader_with_file_path(Some(manifest_path.to_path_buf()))?
+            .finish()?
+    };
+    reject_duplicate_csv_columns(&df)?;
+    let mut rows = HashMap::new();
+    for (path, size, mtime, hash) in izip!(
+        df.column(PATH_COLUMN)?.str()?.iter(),
+        df.column(SIZE_COLUMN)?.str()?.iter(),
+        df.column(MTIME_COLUMN)?.str()?.iter(),
+        df.column(HASH_COLUMN)?.str()?.iter()
+    ) {
+        let path = path.unwrap_or_default().to_string();
+        rows.insert(
+            path.clone(),
+            SnapshotRow {
+                path,
+                size: size.unwrap_or_default().parse()?,
+                mtime: mtime.unwrap_or_default().parse()?,
+                hash: hash.unwrap_or_default().to_string(),
+            },
+        );
+    }
+    Ok(rows)
+}
+
+// Rows of an interrupted snapshot, one `hash\tsize\tmtime\tpath` line per hashed file.
+// A trailing line without newline was cut off mid-write and is ignored.
+fn read_partial_snapshot(partial_path: &Path) -> HashMap<String, SnapshotRow> {
+    let Ok(content) = fs::read_to_string(partial_path) else {
+        return HashMap::new();
+    };
+    let mut lines: Vec<&str> = content.split('\n').collect();
+    lines.pop();
+    lines
+        .into_iter()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\t');
+            let hash = fields.next()?;
+            let size = fields.next()?.parse().ok()?;
+            let mtime = fields.next()?.parse().ok()?;
+            let path = fields.next()?;
+            Some((
+                path.to_string(),
+                SnapshotRow {
+                    path: path.to_string(),
+                    size,
+                    mtime,
+                    hash: hash.to_string(),
+                },
+            ))
+        })
+        .collect()
+}
+
+fn write_manifest(manifest_path: &Path, mut rows: Vec<SnapshotRow>) -> anyhow::Result<()> {
+    rows.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut df = DataFrame::new(
+        rows.len(),
+        vec![
+            Column::new(
+                PATH_COLUMN.into(),
+                rows.iter().map(|row| row.path.as_str()).collect::<Vec<_>>(),
+            ),
+            Column::new(
+                SIZE_COLUMN.into(),
+                rows.iter().map(|row| row.size).collect::<Vec<_>>(),
+            ),
+            Column::new(
+                MTIME_COLUMN.into(),
+                rows.iter().map(|row| row.mtime).collect::<Vec<_>>(),
+            ),
+            Column::new(
+                HASH_COLUMN.into(),
+                rows.iter().map(|row| row.hash.as_str()).collect::<Vec<_>>(),
+            ),
+        ],
+    )?;
+    let mut file = File::create(manifest_path)?;
+    if is_parquet(manifest_path) {
+        ParquetWriter::new(file).finish(&mut df)?;
+    } else {
+        CsvWriter::new(&mut file)
+            .include_bom(true)
+            .finish(&mut df)?;
+    }
+    Ok(())
+}
+
+// Hash every media and sidecar file under root into a manifest CSV, or Parquet
+// when the manifest path ends in .parquet.
+// Progress is appended to <manifest>.partial so an interrupted run can resume.
+pub fn snapshot(root: PathBuf, manifest_path: PathBuf) -> anyhow::Result<()> {
+    let partial_path = manifest_path.with_added_extension("partial");
+    let resumed = read_partial_snapshot(&partial_path);
+    if !resumed.is_empty() {
+        println!(
+            "Resuming snapshot, {} file(s) already hashed in {}",
+            resumed.len(),
+            partial_path.display()
+        );
+    }
+
+    let resource_paths = path_enumerate(root.clone(), ResourceType::All);
+    println!(
+        "{} file(s) found in {}",
+        resource_paths.len(),
+        root.display()
+    );
+    let mut rows: Vec<SnapshotRow> = Vec::new();
+    let mut pending: Vec<(PathBuf, String, u64, i64)> = Vec::new();
+    for path in resource_paths {
+        let key = snapshot_key(&root, &path);
+        let (size, mtime) = size_and_mtime(&path)?;
+        // Reuse a resumed hash only if the file is unchanged since
+        match resumed.get(&key) {
+            Some(row) if row.size == size && row.mtime == mtime => rows.push(row.clone()),
+            _ => pending.push((path, key, size, mtime)),
+        }
+    }
+    let num_resumed = rows.len();
+
+    let partial_file = Mutex::new(
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&partial_path)?,
+    );
+    let pb = ServalProgress::new(pending.len() as u64, "hashing files");
+    let hashed: Vec<SnapshotRow> = pending
+        .into_par_iter()
+        .map(|(path, key, size, mtime)| {
+            let hash = with_io_permit(|| hash_file(&path))?;
+            {
+                let mut partial_file = partial_file.lock().unwrap();
+                partial_file.write_all(format!("{hash}\t{size}\t{mtime}\t{key}\n").as_bytes())?;
+                partial_file.flush()?;
+            }
+            pb.inc(1);
+            Ok(SnapshotRow {
+                path: key,
+                size,
+                mtime,
+                hash,
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+    pb.finish();
+    drop(partial_file);
+    rows.extend(hashed);
+
+    let num_files = rows.len();
+    write_manifest(&manifest_path, rows)?;
+    fs::remove_file(&partial_path)?;
+    println!(
+        "Snapshot of {num_files} file(s) ({num_resumed} resumed) saved to {}",
+        manifest_path.display()
+    );
+    Ok(())
+}
+
+// Compare root against a snapshot manifest, failing when anything differs
+pub fn verify_snapshot(root: PathBuf, manifest_path: PathBuf) -> anyhow::Result<()> {
+    let expected = read_manifest(&manifest_path)?;
+    let current: HashMap<String, PathBuf> = path_enumerate(root.clone(), ResourceType::All)
+        .into_iter()
+        .map(|path| (snapshot_key(&root, &path), path))
+        .collect();
+
+    let mut missing: Vec<&str> = expected
+        .keys()
+        .filter(|key| !current.contains_key(*key))
+        .map(String::as_str)
+        .collect();
+    let mut added: Vec<&str> = current
+        .keys()
+        .filter(|key| !expected.contains_key(*key))
+        .map(String::as_str)
+        .collect();
+    let common: Vec<(&String, &PathBuf)> = current
+        .iter()
+        .filter(|(key, _)| expected.contains_key(*key))
+        .collect();
+
+    let pb = ServalProgress::new(common.len() as u64, "verifying files");
+    let mut modified: Vec<&str> = common
+        .into_par_iter()
+        .map(|(key, path)| {
+            let row = &expected[key];
+            let (size, _) = size_and_mtime(path)?;
+            let changed = size != row.size || with_io_permit(|| hash_file(path))? != row.hash;
+            pb.inc(1);
+            Ok(changed.then_some(key.as_str()))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    pb.finish();
+
+    missing.sort();
+    added.sort();
+    modified.sort();
+    for path in &missing {
+        println!("Missing: {path}");
+    }
+    for path in &added {
+        println!("Added: {path}");
+    }
+    for path in &modified {
+        println!("Modified: {path}");
+    }
+    let num_differences = missing.len() + added.len() + modified.len();
+    println!(
+        "Missing: {}, added: {}, modified: {}, unchanged: {}",
+        missing.len(),
+        added.len(),
+        modified.len(),
+        expected.len() - missing.len() - modified.len()
+    );
+    if num_differences > 0 {
+        return Err(anyhow::anyhow!(
+            "Snapshot verification failed with {num_differences} difference(s)"
+        ));
+    }
+    println!("{} matches {}", root.display(), manifest_path.display());
+    Ok(())
+}
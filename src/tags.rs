@@ -1,54 +1,154 @@
+use crate::analysis::{
+    write_activity_overlap, write_demographics, write_geojson, write_species_accumulation,
+};
+use crate::anonymize::{Anonymizer, is_path_token, keyfile_name, reject_anonymized};
+use crate::archive::{is_archive_entry_path, is_zip_archive, read_zip_sidecars};
+use crate::audit::{AuditChange, record_changes};
+use crate::config::{CONFIG_FILE, IndeterminateLabels, ReviewFilter, RunParams, ServalConfig};
+use crate::duplicates::DuplicateCheck;
+use crate::events::event_records;
+use crate::progress::ServalProgress;
 use crate::schema::{
-    DATETIME_COLUMN, DEPLOYMENT_ID_COLUMN, FILENAME_COLUMN, LATITUDE_COLUMN,
-    LEGACY_DATETIME_COLUMN, LONGITUDE_COLUMN, MEDIA_TYPE_COLUMN, PATH_COLUMN, RATING_COLUMN,
-    SUBJECTS_COLUMN, TIME_MODIFIED_COLUMN, XMP_UPDATE_COLUMN, XMP_UPDATE_DATETIME_COLUMN,
-    canonicalize_observe_tags_df, infer_media_type,
+    CAMERA_MAKE_COLUMN, CAMERA_MODEL_COLUMN, CAMERA_SERIAL_COLUMN, CAMTRAP_DP_COLUMNS,
+    COLOR_LABEL_COLUMN, COLOR_LABELS, CUSTOM_COLUMN, DATE_COLUMN, DATETIME_COLUMN,
+    DATETIME_SOURCE_COLUMN, DEPLOYMENT_COLUMN, DEPLOYMENT_ID_COLUMN, EVENT_ID_COLUMN,
+    FILE_KEY_COLUMN, FILENAME_COLUMN, HOUR_COLUMN, IMAGE_EXTENSIONS, LATITUDE_COLUMN,
+    LEGACY_DATETIME_COLUMN, LONGITUDE_COLUMN, MEDIA_EXISTS_COLUMN, MEDIA_PATH_COLUMN,
+    MEDIA_TRASHED_COLUMN, MEDIA_TYPE_COLUMN, MONTH_COLUMN, OPTIONAL_TAGS_COLUMNS, OutputKind,
+    PATH_COLUMN, PICK_LABEL_COLUMN, PICK_LABELS, RATING_COLUMN, RATING_UPDATE_COLUMN,
+    SIDECAR_EXISTS_COLUMN, SUBJECTS_COLUMN, TAGGER_COLUMN, TIME_MODIFIED_COLUMN, TOTAL_ROW,
+    TRASHED_COLUMN, UNTAGGED_ROW, VIDEO_EXTENSIONS, XMP_UPDATE_COLUMN, XMP_UPDATE_DATETIME_COLUMN,
+    YEAR_COLUMN, canonicalize_observe_tags_df, file_key_for, infer_media_type, is_known_column,
+    parse_observe_tags_text, resource_extension, write_output_schema, write_output_schema_carrying,
+    write_tags_schema_with_extra,
 };
 use crate::utils::{
-    ExtractFilterType, ResourceType, SubdirType, TagType, XmpUpdateType, absolute_path,
-    configure_progress_bar, csv_projection_columns, deployment_from_path,
-    deployment_from_path_expr, filter_expr_to_polars, get_path_levels,
-    has_same_field_and_conditions, ignore_timezone, is_temporal_independent,
-    iso_datetime_to_csv_format, parse_advanced_filter, path_enumerate,
-    reject_duplicate_csv_columns, sync_modified_time,
+    BalanceBy, ColumnMap, CsvDialect, DatetimeTimezone, DeploymentLookup, DigikamTrash,
+    ExtractFilterType, FileTimeoutError, FileWorkersStuckError, GroupBy, IndependenceMode,
+    MultivalueSeparator, OnConflict, PathResolver, ResourceType, SubdirType, TagType, TagsFormat,
+    UNKNOWN_GROUP, UtcOffsets, XmpDecodeError, XmpUpdateType, check_csv_columns,
+    csv_datetime_format, csv_header, csv_projection_columns, csv_writer, deployment_from_path,
+    deployment_from_path_expr, dir_output_name, existing_sidecar_for, explode_multivalue_cells,
+    filter_expr_to_polars, format_size, get_path_levels, has_same_field_and_conditions,
+    ignore_timezone, is_inside_dir, is_parquet, iso_datetime_to_csv_format, label_index,
+    label_name, media_path_for, modified_after, normalize_path_column, normalize_path_str,
+    pair_resource_media, parse_advanced_filter, parse_time_modified, path_enumerate,
+    plan_collision_suffixes, read_parquet_as_text, read_xmp_sidecar, reject_duplicate_csv_columns,
+    run_with_timeout, seeded_shuffle, sidecar_path_for, sync_modified_time, unpreserved_mtime,
+    versioned_output_dir, with_io_permit,
 };
+use crate::viewer::{ReviewView, review_observe};
 use chrono::{DateTime, Datelike, Local, NaiveDateTime, Timelike};
-use indicatif::ProgressBar;
 use itertools::izip;
 use polars::{lazy::dsl::StrptimeOptions, prelude::*};
 use rayon::prelude::*;
 use rustyline::{
     Cmd, Completer, ConditionalEventHandler, Editor, Event, EventContext, EventHandler, Helper,
     Highlighter, Hinter, KeyCode, KeyEvent, Modifiers, RepeatCount, Result,
+    error::ReadlineError,
+    history::DefaultHistory,
     validate::{ValidationContext, ValidationResult, Validator},
 };
 use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fs,
+    io::{BufRead, IsTerminal, Write},
     path::{Path, PathBuf},
     str::FromStr,
 };
 use xmp_toolkit::{
-    FromStrOptions, OpenFileOptions, ToStringOptions, XmpDate, XmpDateTime, XmpFile, XmpMeta,
-    XmpTime, XmpValue, xmp_gps, xmp_ns,
+    FromStrOptions, IterOptions, OpenFileOptions, ToStringOptions, XmpDate, XmpDateTime, XmpFile,
+    XmpMeta, XmpProperty, XmpTime, XmpValue, xmp_gps, xmp_ns,
 };
 
 // Namesapce for "taglists"
 // Adobe
-const LIGHTROOM_NS: &str = "http://ns.adobe.com/lightroom/1.0/";
-const LR_HIERARCHICAL_SUBJECT: &str = "hierarchicalSubject";
+pub(crate) const LIGHTROOM_NS: &str = "http://ns.adobe.com/lightroom/1.0/";
+pub(crate) const LR_HIERARCHICAL_SUBJECT: &str = "hierarchicalSubject";
 // DigiKam
 const DIGIKAM_NS: &str = "http://www.digikam.org/ns/1.0/";
 const DIGIKAM_TAGSLIST: &str = "TagsList";
-
-// Default species/tags to exclude from temporal independence analysis
-const DEFAULT_EXCLUDE_TAGS: &[&str] = &[
-    "",
-    "Blank",
-    "Useless data",
-    "Unidentified",
-    "Unknown",
-    "Blur",
-];
+const DIGIKAM_PICK_LABEL: &str = "PickLabel";
+const DIGIKAM_COLOR_LABEL: &str = "ColorLabel";
+// Camera body serial number, EXIF 2.3 (CIPA) and the older Adobe auxiliary namespace
+const EXIF_EX_NS: &str = "http://cipa.jp/exif/1.0/";
+const AUX_NS: &str = "http://ns.adobe.com/exif/1.0/aux/";
+const SERVAL_NS: &str = "https://github.com/wsyxbcl/Serval/ns/1.0/";
+const SERVAL_TAGGER: &str = "tagger";
+
+/// xmp_update value removing the tag named in the tag column, e.g. species=Serval
+pub const DELETE_TAG_VALUE: &str = "DELETE_TAG";
+
+// Default species/tags to exclude from temporal independence analysis, along with the
+// indeterminate labels
+pub(crate) const DEFAULT_EXCLUDE_TAGS: &[&str] = &["", "Blank", "Useless data", "Blur"];
+
+// Tags excluded by capture: built-ins and indeterminate labels, then serval.toml
+// capture.exclude, then --exclude
+pub fn capture_exclude_tags(
+    config: &ServalConfig,
+    extra: Vec<String>,
+    no_default_excludes: bool,
+    no_exclude: bool,
+) -> anyhow::Result<Vec<String>> {
+    if no_exclude {
+        println!("Excluded tags: none (--no-exclude)");
+        return Ok(Vec::new());
+    }
+    let built_in: Vec<String> = if no_default_excludes {
+        Vec::new()
+    } else {
+        DEFAULT_EXCLUDE_TAGS
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect()
+    };
+    let indeterminate: Vec<String> = if no_default_excludes {
+        Vec::new()
+    } else {
+        IndeterminateLabels::current().labels().to_vec()
+    };
+    let from_config = config.string_list("capture.exclude")?;
+    let config_source = config
+        .path()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| CONFIG_FILE.to_string());
+    let mut sources = Vec::new();
+    for (tags, source) in [
+        (&built_in, "built-in"),
+        (&indeterminate, "indeterminate"),
+        (&from_config, config_source.as_str()),
+        (&extra, "--exclude"),
+    ] {
+        if !tags.is_empty() {
+            let tags: Vec<&str> = tags
+                .iter()
+                .map(|tag| if tag.is_empty() { "<empty>" } else { tag })
+                .collect();
+            sources.push(format!("{} ({source})", tags.join(", ")));
+        }
+    }
+    println!(
+        "Excluded tags: {}",
+        if sources.is_empty() {
+            "none".to_string()
+        } else {
+            sources.join("; ")
+        }
+    );
+    let mut exclude_tags: Vec<String> = Vec::new();
+    for tag in built_in
+        .into_iter()
+        .chain(indeterminate)
+        .chain(from_config)
+        .chain(extra)
+    {
+        if !exclude_tags.contains(&tag) {
+            exclude_tags.push(tag);
+        }
+    }
+    Ok(exclude_tags)
+}
 
 struct NumericFilteringHandler;
 impl ConditionalEventHandler for NumericFilteringHandler {
@@ -69,23 +169,629 @@ struct NumericSelectValidator {
     min: i32,
     max: i32,
 }
+impl NumericSelectValidator {
+    fn expectation(&self) -> String {
+        if self.max == i32::MAX {
+            format!("number of at least {}", self.min)
+        } else {
+            format!("number between {} and {}", self.min, self.max)
+        }
+    }
+}
 impl Validator for NumericSelectValidator {
     fn validate(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
         use ValidationResult::{Invalid, Valid};
-        let input: i32 = if ctx.input() == "" {
-            return Ok(Invalid(Some(" --< Expect numeric input".to_owned())));
+        // Empty input accepts the default
+        if ctx.input().is_empty() {
+            return Ok(Valid(None));
+        }
+        let result = match ctx.input().parse::<i32>() {
+            Ok(input) if input >= self.min && input <= self.max => Valid(None),
+            _ => Invalid(Some(format!(" --< Expect: {}", self.expectation()))),
+        };
+        Ok(result)
+    }
+}
+
+// Interactive prompts with defaults, falls back to plain stdin lines when stdin is not a
+// terminal so answers can be piped, e.g. printf "30\n1\n1\n4\n" | serval capture ...
+pub(crate) struct Prompt {
+    editor: Option<Editor<NumericSelectValidator, DefaultHistory>>,
+}
+
+impl Prompt {
+    pub(crate) fn new() -> anyhow::Result<Self> {
+        let editor = if std::io::stdin().is_terminal() {
+            let mut rl = Editor::new()?;
+            rl.bind_sequence(
+                Event::Any,
+                EventHandler::Conditional(Box::new(NumericFilteringHandler)), // Force numerical input
+            );
+            Some(rl)
+        } else {
+            None
+        };
+        Ok(Self { editor })
+    }
+
+    // One line of input, None when stdin is closed
+    fn read_line<H: Helper>(
+        editor: Option<&mut Editor<H, DefaultHistory>>,
+        prompt: &str,
+    ) -> anyhow::Result<Option<String>> {
+        let Some(rl) = editor else {
+            print!("{prompt}");
+            std::io::stdout().flush()?;
+            let mut line = String::new();
+            if std::io::stdin().lock().read_line(&mut line)? == 0 {
+                println!();
+                return Ok(None);
+            }
+            // Echo piped answers so logs read like an interactive session
+            println!("{}", line.trim_end());
+            return Ok(Some(line));
+        };
+        match rl.readline(prompt) {
+            Ok(line) => Ok(Some(line)),
+            Err(ReadlineError::Interrupted) => {
+                println!("Aborted.");
+                crate::lock::exit_interrupted();
+            }
+            Err(ReadlineError::Eof) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // A closed stdin leaves the question unanswered, which stops the run rather than
+    // quietly taking a default the piped answers never chose
+    fn answered(line: Option<String>, prompt: &str) -> anyhow::Result<String> {
+        line.ok_or_else(|| {
+            anyhow::anyhow!(
+                "No answer to '{}', the input ended before it",
+                prompt.trim()
+            )
+        })
+    }
+
+    // Number in [min, max], Enter accepts the default
+    fn select(&mut self, prompt: &str, min: i32, max: i32, default: i32) -> anyhow::Result<i32> {
+        let validator = NumericSelectValidator { min, max };
+        let expectation = validator.expectation();
+        if let Some(rl) = self.editor.as_mut() {
+            rl.set_helper(Some(validator));
+        }
+        let line = Self::answered(
+            Self::read_line(self.editor.as_mut(), &format!("{prompt} [{default}]: "))?,
+            prompt,
+        )?;
+        let line = line.trim();
+        if line.is_empty() {
+            return Ok(default);
+        }
+        line.parse::<i32>()
+            .ok()
+            .filter(|value| (min..=max).contains(value))
+            .ok_or_else(|| anyhow::anyhow!("Invalid input '{line}': expected {expectation}"))
+    }
+
+    // Yes/no question, Enter accepts the default
+    fn confirm(&mut self, prompt: &str, default: bool) -> anyhow::Result<bool> {
+        let hint = if default { "Y/n" } else { "y/N" };
+        let question = format!("{prompt} [{hint}]: ");
+        // The numeric editor filters out letters, so answer on a plain one
+        let line = if self.editor.is_some() {
+            Self::read_line(Some(&mut rustyline::DefaultEditor::new()?), &question)?
         } else {
-            ctx.input().parse().unwrap()
+            Self::read_line::<()>(None, &question)?
         };
-        let result = if !(input >= self.min && input <= self.max) {
-            Invalid(Some(format!(
-                " --< Expect: number between {} and {}",
-                self.min, self.max
-            )))
+        let line = Self::answered(line, prompt)?;
+        match line.trim().to_ascii_lowercase().as_str() {
+            "" => Ok(default),
+            "y" | "yes" => Ok(true),
+            "n" | "no" => Ok(false),
+            other => Err(anyhow::anyhow!("Invalid input '{other}': expected y or n")),
+        }
+    }
+
+    // Tag value, Tab completes over the known values and others need confirming
+    fn tag_value(&mut self, prompt: &str, completer: TagCompleter) -> anyhow::Result<String> {
+        let known = completer.candidates.clone();
+        let mut editor: Option<Editor<TagCompleter, DefaultHistory>> = if self.editor.is_some() {
+            let config = rustyline::Config::builder()
+                .completion_type(rustyline::CompletionType::List)
+                .build();
+            let mut rl = Editor::with_config(config)?;
+            rl.set_helper(Some(completer));
+            Some(rl)
         } else {
-            Valid(None)
+            None
         };
-        Ok(result)
+        loop {
+            let line = Self::answered(
+                Self::read_line(editor.as_mut(), &format!("{prompt}: "))?,
+                prompt,
+            )?;
+            let value = line.trim();
+            if value.is_empty() {
+                continue;
+            }
+            // Known values keep their spelling from the list
+            if let Some(known_value) = known
+                .iter()
+                .find(|known_value| known_value.eq_ignore_ascii_case(value))
+            {
+                return Ok(known_value.clone());
+            }
+            if self.confirm(
+                &format!(
+                    "'{value}' is not among the {} known value(s), use it anyway?",
+                    known.len()
+                ),
+                false,
+            )? {
+                return Ok(value.to_string());
+            }
+        }
+    }
+}
+
+/// Capture prompt answers, saved in the capture output directory and read by --replay
+pub const CAPTURE_REPLAY_FILE: &str = ".serval_last_capture.json";
+/// Extract prompt answers, saved in the extract output directory and read by --replay
+pub const EXTRACT_REPLAY_FILE: &str = ".serval_last_extract.json";
+
+// Prompt answers written to `path` as soon as each is given, so a run that fails or is
+// interrupted later can be repeated with --replay without answering again
+struct PromptAnswers {
+    path: PathBuf,
+    values: serde_json::Map<String, serde_json::Value>,
+}
+
+impl PromptAnswers {
+    // The answers of `replay` once confirmed, the ones missing there are still asked
+    fn new(path: PathBuf, replay: Option<&Path>, prompt: &mut Prompt) -> anyhow::Result<Self> {
+        let mut answers = Self {
+            path,
+            values: serde_json::Map::new(),
+        };
+        let Some(replay) = replay else {
+            return Ok(answers);
+        };
+        let serde_json::Value::Object(values) = serde_json::from_str(
+            &fs::read_to_string(replay)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {e}", replay.display()))?,
+        )?
+        else {
+            return Err(anyhow::anyhow!("No saved answers in {}", replay.display()));
+        };
+        println!("Answers saved in {}:", replay.display());
+        for (key, value) in &values {
+            println!("  {key}: {value}");
+        }
+        if prompt.confirm("Replay these answers?", true)? {
+            answers.values = values;
+            answers.save()?;
+        }
+        Ok(answers)
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string_pretty(&self.values)?)?;
+        Ok(())
+    }
+
+    fn get_i64(&self, key: &str) -> Option<i64> {
+        self.values.get(key).and_then(serde_json::Value::as_i64)
+    }
+
+    fn get_str(&self, key: &str) -> Option<&str> {
+        self.values.get(key).and_then(serde_json::Value::as_str)
+    }
+
+    fn record(&mut self, key: &str, value: impl Into<serde_json::Value>) -> anyhow::Result<()> {
+        self.values.insert(key.to_string(), value.into());
+        self.save()
+    }
+}
+
+/// Species or individual value asked interactively, with Tab completion over the `tag_type`
+/// column of `taglist`, or over the values already in the tags CSV when no taglist is given
+pub fn prompt_tag_value(
+    csv_path: &Path,
+    tag_type: TagType,
+    taglist: Option<PathBuf>,
+    column_map: &ColumnMap,
+    multivalue_separator: Option<&MultivalueSeparator>,
+) -> anyhow::Result<String> {
+    let mut df = CsvReadOptions::default()
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(taglist.clone().unwrap_or(csv_path.to_path_buf())))?
+        .finish()?;
+    reject_duplicate_csv_columns(&df)?;
+    if taglist.is_none() {
+        column_map.apply(&mut df)?;
+        if let Some(separator) = multivalue_separator {
+            df = explode_multivalue_cells(df, separator)?;
+        }
+    }
+    let values = df.column(tag_type.col_name())?.unique()?;
+    let completer = TagCompleter::new(values.str()?.iter().flatten().map(str::to_string));
+    println!(
+        "{} known {} value(s), press Tab to complete",
+        completer.candidates.len(),
+        tag_type.col_name()
+    );
+    Prompt::new()?.tag_value(&format!("Input the {}", tag_type.col_name()), completer)
+}
+
+// Tab completion over known tag values: case-insensitive prefix matches first, then substring
+// matches, each in sorted order. The whole line is the value, species names have spaces.
+#[derive(Helper, Highlighter, Hinter)]
+struct TagCompleter {
+    candidates: Vec<String>,
+}
+
+impl TagCompleter {
+    fn new(values: impl IntoIterator<Item = String>) -> Self {
+        let candidates: BTreeSet<String> = values
+            .into_iter()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+            .collect();
+        Self {
+            candidates: candidates.into_iter().collect(),
+        }
+    }
+
+    fn matches(&self, input: &str) -> Vec<String> {
+        let input = input.trim_start().to_lowercase();
+        let (mut prefix_matches, substring_matches): (Vec<String>, Vec<String>) = self
+            .candidates
+            .iter()
+            .filter(|candidate| candidate.to_lowercase().contains(&input))
+            .cloned()
+            .partition(|candidate| candidate.to_lowercase().starts_with(&input));
+        prefix_matches.extend(substring_matches);
+        prefix_matches
+    }
+}
+
+impl rustyline::completion::Completer for TagCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> Result<(usize, Vec<String>)> {
+        Ok((0, self.matches(&line[..pos])))
+    }
+}
+
+impl Validator for TagCompleter {}
+
+/// Capture parameters otherwise asked interactively
+#[derive(Clone, Copy, Debug)]
+pub struct CaptureSettings {
+    /// Minimum time difference between independent records, in minutes
+    pub min_delta_time: i32,
+    /// Compare with the last record instead of the last independent record
+    pub compare_to_last_record: bool,
+    /// Species or individual
+    pub target: TagType,
+    /// Path component holding the deployment, None for camtrap-dp observations and tags
+    /// with a deployment column
+    pub deploy_path_index: Option<i32>,
+}
+
+/// Observe options beyond the directory and resource type, all off by default
+#[derive(Clone, Debug, Default)]
+pub struct ObserveSettings {
+    /// Print the raw metadata and write it to a raw CSV, with file modified times
+    pub debug_mode: bool,
+    /// Only list the files still missing tags
+    pub volunteer_mode: bool, //TODO: make a mode argument
+    /// Skip files whose metadata read takes longer than this
+    pub file_timeout: Option<std::time::Duration>,
+    /// Add a file_key column that stays unique across deployments
+    pub unique_name: bool,
+    /// Add media_path, sidecar_exists and media_exists columns
+    pub pair_media: bool,
+    /// Read files digiKam moved to its trash too, marked in a trashed column
+    pub include_trash: bool,
+    /// Read DateTimeOriginal from EXIF when there is none in XMP
+    pub exif_fallback: bool,
+    /// Read GPS coordinates missing from XMP from the EXIF of images
+    pub gps: bool,
+    /// Add camera make, model and serial columns
+    pub camera_info: bool,
+    /// Tag categories read into columns of their own (`--extra-tag`)
+    pub extra_tags: Vec<String>,
+    /// Seconds between bursts that start a new event_id
+    pub event_gap: Option<u64>,
+    /// Add date, year, month and hour columns
+    pub derive_dateparts: bool,
+    /// Abort on a malformed datetime instead of listing it in the errors CSV
+    pub strict_datetime: bool,
+    /// What to do with the UTC offsets of the datetimes
+    pub timezone: DatetimeTimezone,
+    /// Offsets for a datetime_utc column
+    pub utc_offsets: Option<UtcOffsets>,
+    /// Where a deployment column is read from
+    pub deployment_lookup: Option<DeploymentLookup>,
+    /// Hash the paths of the shared outputs
+    pub anonymize_paths: bool,
+    /// Report deployment directories holding copies of each other
+    pub duplicate_check: Option<DuplicateCheck>,
+    /// Earlier tags CSV whose rows are taken over
+    pub resume: Option<ObserveResume>,
+    /// Browse the results in the terminal afterwards
+    pub review_view: bool,
+    /// Write species_stats as plain value counts
+    pub no_format: bool,
+    /// CSV and/or Parquet tags
+    pub tags_format: TagsFormat,
+    /// When outputs of the same name exist
+    pub on_conflict: OnConflict,
+}
+
+/// Capture options beyond the tags CSV and output directory, all off by default
+#[derive(Default)]
+pub struct CaptureOptions {
+    /// Also write the events of each independent record
+    pub event: bool,
+    /// Keep the tags otherwise excluded (Blank, Useless data, ...)
+    pub no_exclude: bool,
+    /// Read a camtrap-dp observations CSV instead of tags.csv
+    pub camtrap_dp: bool,
+    /// Write species accumulation data, a row every this many trap-days
+    pub accumulation: Option<u32>,
+    /// Also write the accumulation per deployment
+    pub per_deployment: bool,
+    /// Deployment table with the deployment dates and locations
+    pub deploy_table: Option<PathBuf>,
+    /// Species pair (A,B) whose activity overlap is written
+    pub overlap: Option<String>,
+    /// Write the sex and age structure of individually identified species
+    pub demographics: bool,
+    /// Also write the independent records as GeoJSON
+    pub geojson: bool,
+    /// Write the counts as plain value counts
+    pub no_format: bool,
+    /// Add date, year, month and hour columns
+    pub derive_dateparts: bool,
+    /// tags.csv columns carried into the count outputs
+    pub carry_columns: Vec<String>,
+    /// Write the per-species minimum-gap histogram
+    pub gap_histogram: bool,
+    /// Other windows and comparisons run over the same records
+    pub compare: Option<CaptureComparison>,
+    /// Replace paths and deployments with tokens in the outputs
+    pub anonymize_paths: bool,
+    /// Tags left out of the analysis (prefix match)
+    pub exclude_tags: Vec<String>,
+    /// Records that need review
+    pub review: ReviewFilter,
+    /// Count records that need review too
+    pub include_review: bool,
+    /// When outputs of the same name exist
+    pub on_conflict: OnConflict,
+    /// Serval columns mapped onto the CSV's columns
+    pub column_map: ColumnMap,
+    /// Split multi-valued cells of a foreign CSV
+    pub multivalue_separator: Option<MultivalueSeparator>,
+    /// Answers of an earlier run to replay instead of prompting
+    pub replay: Option<PathBuf>,
+    /// Answers given up front, nothing is asked
+    pub settings: Option<CaptureSettings>,
+}
+
+/// Extract options beyond the filter, tags CSV and output directory, all off by default
+#[derive(Default)]
+pub struct ExtractOptions {
+    /// Column read by the custom filter instead of `custom`
+    pub custom_column: Option<String>,
+    /// Prefix the copies with their species and individual
+    pub rename: bool,
+    /// Leave copies already in the output directory
+    pub skip_existing: bool,
+    /// Copy into a subdirectory per `subdir_value`
+    pub use_subdir: bool,
+    /// Value the subdirectories are named after
+    pub subdir_value: SubdirType,
+    /// One directory level per value above the kept structure
+    pub group_by: Option<GroupBy>,
+    /// Serval columns mapped onto the CSV's columns
+    pub column_map: ColumnMap,
+    /// Split multi-valued cells of a foreign CSV
+    pub multivalue_separator: Option<MultivalueSeparator>,
+    /// At most this many files of each species
+    pub per_species_limit: Option<usize>,
+    /// Spread the per-species quota over deployments or events
+    pub balance_by: Option<BalanceBy>,
+    /// Seed of the quota sampling
+    pub seed: u64,
+    /// Fail when a copy's modified time can't be kept
+    pub require_mtime: bool,
+    /// What to do with videos whose tags have no sidecar
+    pub require_sidecar: Option<RequireSidecar>,
+    /// Only the records that need review
+    pub review: Option<ReviewFilter>,
+    /// Copy the whole event of each matched file
+    pub whole_event: bool,
+    /// Independence CSV whose events are copied
+    pub events_from: Option<PathBuf>,
+    /// Stop before copying more than this many bytes
+    pub max_size: Option<u64>,
+    /// Directory the kept structure starts below
+    pub keep_relative_to: Option<KeepRelativeTo>,
+    /// Keep indeterminate labels in ALL_VALUES
+    pub include_indeterminate: bool,
+    /// Answers of an earlier run to replay instead of prompting
+    pub replay: Option<PathBuf>,
+    /// Directory levels kept above each file, nothing is asked
+    pub keep_level: Option<usize>,
+}
+
+/// Directory the kept structure of extracted copies starts below, instead of the asked level
+#[derive(Clone, Debug, PartialEq)]
+pub enum KeepRelativeTo {
+    /// Common directory of every path in the tags CSV, i.e. the observed media root
+    Auto,
+    Dir(PathBuf),
+}
+
+impl FromStr for KeepRelativeTo {
+    type Err = std::convert::Infallible;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        Ok(if value == "auto" {
+            Self::Auto
+        } else {
+            Self::Dir(PathBuf::from(value))
+        })
+    }
+}
+
+/// Windows and comparisons capture --compare runs over the same records
+#[derive(Clone, Debug, Default)]
+pub struct CaptureComparison {
+    /// Minimum time differences in minutes, the first one is the reference
+    pub windows: Vec<i32>,
+    /// LIR and/or LR, the run's own comparison when empty
+    pub modes: Vec<IndependenceMode>,
+}
+
+impl CaptureComparison {
+    // (min_delta_time, compare_to_last_record) of each run, the reference first
+    fn runs(&self, compare_to_last_record: bool) -> Vec<(i32, bool)> {
+        let modes = if self.modes.is_empty() {
+            vec![compare_to_last_record]
+        } else {
+            self.modes
+                .iter()
+                .map(|mode| *mode == IndependenceMode::Lr)
+                .collect()
+        };
+        let mut runs = Vec::new();
+        for compare_to_last_record in modes {
+            for &window in &self.windows {
+                if !runs.contains(&(window, compare_to_last_record)) {
+                    runs.push((window, compare_to_last_record));
+                }
+            }
+        }
+        runs
+    }
+}
+
+impl CaptureSettings {
+    // Replayed answers are used as long as they're valid, anything else is asked. With
+    // --compare the reference setting stands in for the window and comparison questions.
+    fn prompt(
+        df: &DataFrame,
+        camtrap_dp: bool,
+        compare: Option<&CaptureComparison>,
+        answers: &mut PromptAnswers,
+    ) -> anyhow::Result<Self> {
+        let mut prompt = Prompt::new()?;
+        // Read min_delta_time
+        let min_delta_time = match compare
+            .and_then(|compare| compare.windows.first().copied())
+            .or_else(|| {
+                answers
+                    .get_i64("min_delta_time")
+                    .and_then(|value| i32::try_from(value).ok())
+            })
+            .filter(|value| *value >= 1)
+        {
+            Some(value) => value,
+            None => {
+                let value = prompt.select(
+                    "Input the Minimum Time Difference (when considering records as independent) in minutes",
+                    1,
+                    i32::MAX,
+                    30,
+                )?;
+                answers.record("min_delta_time", value)?;
+                value
+            }
+        };
+        // Read delta_time_compared_to
+        let compare_mode = compare.and_then(|compare| compare.modes.first());
+        let compare_to_last_record = match (compare_mode, answers.get_str("compare_to")) {
+            (Some(mode), _) => *mode == IndependenceMode::Lr,
+            (None, Some("last_independent_record")) => false,
+            (None, Some("last_record")) => true,
+            _ => {
+                let value = prompt.select(
+                    "\nThe Minimum Time Difference should be compared with?\n1) Last independent record 2) Last record\nEnter a selection",
+                    1,
+                    2,
+                    1,
+                )? == 2;
+                answers.record(
+                    "compare_to",
+                    if value {
+                        "last_record"
+                    } else {
+                        "last_independent_record"
+                    },
+                )?;
+                value
+            }
+        };
+        // Get target (species/individual)
+        let target = match answers.get_str("target") {
+            Some("species") => TagType::Species,
+            Some("individual") => TagType::Individual,
+            _ => {
+                let target = match prompt.select(
+                    "\nPerform analysis on\n1) species 2) individual\nEnter a selection",
+                    1,
+                    2,
+                    1,
+                )? {
+                    2 => TagType::Individual,
+                    _ => TagType::Species,
+                };
+                answers.record("target", target.col_name())?;
+                target
+            }
+        };
+        // Find deployment, unless observe already wrote it
+        let deploy_path_index = if camtrap_dp || df.column(DEPLOYMENT_COLUMN).is_ok() {
+            None
+        } else {
+            let path_sample = df
+                .column("path")?
+                .str()?
+                .get(0)
+                .ok_or_else(|| anyhow::anyhow!("Missing path value in the first record"))?
+                .to_string();
+            let num_levels = get_path_levels(path_sample.clone()).len() as i64;
+            match answers
+                .get_i64("deploy_path_index")
+                .filter(|value| (1..=num_levels).contains(value))
+            {
+                Some(value) => Some(value as i32),
+                None => {
+                    let value = prompt_deployment_path_index(&mut prompt, path_sample)?;
+                    answers.record("deploy_path_index", value)?;
+                    Some(value)
+                }
+            }
+        };
+        Ok(Self {
+            min_delta_time,
+            compare_to_last_record,
+            target,
+            deploy_path_index,
+        })
     }
 }
 
@@ -166,26 +872,58 @@ fn format_coordinate(coordinate: f64) -> String {
     format!("{coordinate:.6}")
 }
 
-fn extract_xmp_gps_coordinates(xmp: &XmpMeta) -> (Option<String>, Option<String>) {
-    let latitude_raw = xmp
-        .property(xmp_ns::EXIF, "GPSLatitude")
-        .map(|value| value.value);
-    let longitude_raw = xmp
-        .property(xmp_ns::EXIF, "GPSLongitude")
-        .map(|value| value.value);
-    let latitude = latitude_raw
-        .as_deref()
-        .and_then(|raw| parse_xmp_gps_coordinate(raw, "GPSLatitude"))
-        .map(format_coordinate);
-    let longitude = longitude_raw
-        .as_deref()
-        .and_then(|raw| parse_xmp_gps_coordinate(raw, "GPSLongitude"))
-        .map(format_coordinate);
+// Decimal degrees of exif:GPSLatitude/GPSLongitude in the packet, written by the camera
+// or reconciled by the toolkit from JPEG EXIF
+fn extract_xmp_gps_coordinates(xmp: &XmpMeta) -> (Option<f64>, Option<f64>) {
+    let [latitude, longitude] = ["GPSLatitude", "GPSLongitude"].map(|property| {
+        xmp.property(xmp_ns::EXIF, property)
+            .and_then(|value| parse_xmp_gps_coordinate(&value.value, property))
+    });
     (latitude, longitude)
 }
 
-fn prompt_deployment_path_index(
-    rl: &mut Editor<NumericSelectValidator, rustyline::history::DefaultHistory>,
+// Coordinates are kept to the 6 decimals (~0.1 m) written to tags.csv
+fn round_coordinate(coordinate: f64) -> f64 {
+    (coordinate * 1e6).round() / 1e6
+}
+
+// Decimal degrees of the EXIF GPS block of an image, for containers whose EXIF the toolkit
+// does not reconcile into XMP (e.g. PNG eXIf). None unless both coordinates are present
+fn exif_gps_coordinates(file_path: &Path) -> Option<(f64, f64)> {
+    let file = fs::File::open(file_path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut std::io::BufReader::new(file))
+        .ok()?;
+    let coordinate = |tag: exif::Tag, ref_tag: exif::Tag, negative_ref: u8| -> Option<f64> {
+        let field = exif.get_field(tag, exif::In::PRIMARY)?;
+        let exif::Value::Rational(parts) = &field.value else {
+            return None;
+        };
+        // degrees, minutes and seconds, cameras writing decimal minutes leave seconds at 0
+        let degrees = parts
+            .iter()
+            .zip([1.0, 60.0, 3600.0])
+            .map(|(part, divisor)| part.to_f64() / divisor)
+            .sum::<f64>();
+        let negative = match &exif.get_field(ref_tag, exif::In::PRIMARY)?.value {
+            exif::Value::Ascii(values) => values
+                .first()
+                .and_then(|value| value.first())
+                .is_some_and(|value| value.eq_ignore_ascii_case(&negative_ref)),
+            _ => false,
+        };
+        degrees
+            .is_finite()
+            .then_some(if negative { -degrees } else { degrees })
+    };
+    Some((
+        coordinate(exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef, b'S')?,
+        coordinate(exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef, b'W')?,
+    ))
+}
+
+pub(crate) fn prompt_deployment_path_index(
+    prompt: &mut Prompt,
     path_sample: String,
 ) -> anyhow::Result<i32> {
     println!("\nHere is a sample of the file path ({path_sample})");
@@ -198,13 +936,14 @@ fn prompt_deployment_path_index(
     for (i, entry) in path_levels.iter().enumerate() {
         println!("{}): {}", i + 1, entry);
     }
-    let h = NumericSelectValidator {
-        min: 1,
-        max: path_levels.len().try_into()?,
-    };
-    rl.set_helper(Some(h));
-    let readline = rl.readline("Select the number corresponding to the deployment: ");
-    Ok(readline?.trim().parse::<i32>()?)
+    // Deployments are usually the directory holding the files
+    let num_levels: i32 = path_levels.len().try_into()?;
+    prompt.select(
+        "Select the number corresponding to the deployment",
+        1,
+        num_levels,
+        num_levels,
+    )
 }
 
 pub fn write_taglist(
@@ -344,14 +1083,127 @@ fn write_xmp_init_debug_csv(
     )?;
     df = df.sort([PATH_COLUMN], SortMultipleOptions::default())?;
     let mut file = std::fs::File::create(debug_csv_path.clone())?;
-    CsvWriter::new(&mut file)
-        .include_bom(true)
-        .finish(&mut df)?;
+    csv_writer(&mut file).finish(&mut df)?;
     println!("Saved debug CSV to {}", debug_csv_path.to_string_lossy());
     Ok(())
 }
 
-pub fn init_xmp(working_dir: PathBuf, info: bool) -> anyhow::Result<()> {
+// Read the XMP packet embedded in a media file, None if the file cannot be opened
+fn read_embedded_xmp(media: &Path) -> anyhow::Result<Option<XmpMeta>> {
+    let mut media_xmp = XmpFile::new()?;
+    if media_xmp
+        .open_file(media, OpenFileOptions::default())
+        .is_err()
+    {
+        return Ok(None);
+    }
+    let xmp = media_xmp.xmp().unwrap_or_default();
+    finalize_xmp_file(&mut media_xmp, Ok(Some(xmp)))
+}
+
+// Sidecar to initialize for a media file, adopting an existing extension-replaced
+// sidecar (IMG_0001.xmp) when it unambiguously belongs to this media
+fn init_sidecar_path(working_dir: &Path, media: &Path) -> (PathBuf, bool) {
+    let media = working_dir.join(media);
+    let appended = sidecar_path_for(&media);
+    match existing_sidecar_for(&media) {
+        Some(sidecar) => {
+            let adopted = sidecar != appended;
+            (sidecar, adopted)
+        }
+        None => (appended, false),
+    }
+}
+
+// Template text with {datetime}, {deployment} and {filename} filled in, escaped for XML
+fn fill_xmp_template(template: &str, datetime: &str, deployment: &str, filename: &str) -> String {
+    let escape = |value: &str| {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+    template
+        .replace("{datetime}", &escape(datetime))
+        .replace("{deployment}", &escape(deployment))
+        .replace("{filename}", &escape(filename))
+}
+
+// Read an `xmp init --template`, checking it parses as XMP before any sidecar is written
+fn load_xmp_template(path: &Path) -> anyhow::Result<String> {
+    let template = read_xmp_sidecar(path)?;
+    let sample = fill_xmp_template(
+        &template,
+        "2000-01-01T00:00:00",
+        "deployment",
+        "IMG_0001.JPG",
+    );
+    let xmp = XmpMeta::from_str(&sample)
+        .map_err(|err| anyhow::anyhow!("Template {} is not valid XMP: {err}", path.display()))?;
+    if xmp.iter(IterOptions::default()).next().is_none() {
+        return Err(anyhow::anyhow!(
+            "Template {} is not valid XMP: no properties found",
+            path.display()
+        ));
+    }
+    Ok(template)
+}
+
+// Copy the template's top-level properties the sidecar doesn't have yet,
+// simple values and arrays only (structs are left out)
+fn merge_xmp_template(xmp: &mut XmpMeta, template: &XmpMeta) -> anyhow::Result<()> {
+    let schemas: Vec<String> = template
+        .iter(IterOptions::default().immediate_children_only())
+        .map(|schema| schema.schema_ns)
+        .collect();
+    for schema_ns in schemas {
+        let properties: Vec<XmpProperty> = template
+            .iter(
+                IterOptions::default()
+                    .schema_ns(&schema_ns)
+                    .immediate_children_only(),
+            )
+            .collect();
+        for property in properties {
+            let name = property.name.as_str();
+            if xmp.contains_property(&schema_ns, name) || property.value.is_struct() {
+                continue;
+            }
+            if property.value.is_alt_text() {
+                for index in 1..=template.array_len(&schema_ns, name) {
+                    let item = format!("{name}[{index}]");
+                    let lang = template
+                        .property(&schema_ns, &format!("{item}/?xml:lang"))
+                        .map_or_else(|| "x-default".to_string(), |lang| lang.value);
+                    if let Some(value) = template.property(&schema_ns, &item) {
+                        xmp.set_localized_text(&schema_ns, name, None, &lang, &value.value)?;
+                    }
+                }
+            } else if property.value.is_array() {
+                let array = XmpValue::from(name)
+                    .set_is_array(true)
+                    .set_is_ordered(property.value.is_ordered())
+                    .set_is_alternate(property.value.is_alternate());
+                for item in template.property_array(&schema_ns, name) {
+                    xmp.append_array_item(&schema_ns, &array, &item)?;
+                }
+            } else {
+                xmp.set_property(&schema_ns, name, &property.value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn init_xmp(
+    working_dir: PathBuf,
+    info: bool,
+    file_timeout: Option<std::time::Duration>,
+    template: Option<PathBuf>,
+    deploy_level: Option<i32>,
+) -> anyhow::Result<()> {
+    let template = template.as_deref().map(load_xmp_template).transpose()?;
     let media_paths = path_enumerate(working_dir.clone(), ResourceType::Media);
     let media_count = media_paths.len();
 
@@ -360,25 +1212,23 @@ pub fn init_xmp(working_dir: PathBuf, info: bool) -> anyhow::Result<()> {
     } else {
         Vec::new()
     };
-    let debug_row_init = if info {
-        let deploy_path_index = if media_count > 0 {
-            let mut rl = Editor::new()?;
-            rl.bind_sequence(
-                Event::Any,
-                EventHandler::Conditional(Box::new(NumericFilteringHandler)),
-            );
+    // Deployments of the info CSV and the template, asked for when no --deploy-level is given
+    let deploy_path_index = match deploy_level {
+        Some(level) => Some(level),
+        None if (info || template.is_some()) && media_count > 0 => {
             Some(prompt_deployment_path_index(
-                &mut rl,
+                &mut Prompt::new()?,
                 media_paths[0].to_string_lossy().into_owned(),
             )?)
-        } else {
-            None
-        };
+        }
+        None => None,
+    };
+    let debug_row_init = if info {
         Some(
             media_paths
                 .iter()
                 .map(|media| {
-                    let xmp_path = working_dir.join(media.with_added_extension("xmp"));
+                    let (xmp_path, _) = init_sidecar_path(&working_dir, media);
                     let mut row = XmpInitDebugRow::new(&xmp_path);
                     if let Some(deploy_path_index) = deploy_path_index {
                         row.deployment = deployment_from_path(media, deploy_path_index)?;
@@ -391,11 +1241,10 @@ pub fn init_xmp(working_dir: PathBuf, info: bool) -> anyhow::Result<()> {
     } else {
         None
     };
-    let pb = ProgressBar::new(media_count.try_into()?);
-    configure_progress_bar(&pb);
+    let pb = ServalProgress::new(media_count.try_into()?, "initializing XMP files");
 
     for (index, media) in media_paths.into_iter().enumerate() {
-        let xmp_path = working_dir.join(media.with_added_extension("xmp"));
+        let (xmp_path, adopted) = init_sidecar_path(&working_dir, &media);
         let mut debug_row = debug_row_init
             .as_ref()
             .and_then(|rows| rows.get(index).cloned());
@@ -407,38 +1256,54 @@ pub fn init_xmp(working_dir: PathBuf, info: bool) -> anyhow::Result<()> {
             row.file_modified_time =
                 iso_datetime_to_csv_format(&datetime.format("%Y-%m-%dT%H:%M:%S").to_string());
         }
+        if adopted && !info {
+            pb.inc(1);
+            pb.notice(
+                "Adopted extension-replaced XMP file",
+                format!("Adopted existing XMP file: {}", xmp_path.display()),
+            );
+            continue;
+        }
         if xmp_path.exists() && !info {
             pb.inc(1);
-            pb.println(format!("XMP file already exists: {}", xmp_path.display()));
+            pb.notice(
+                "XMP file already exists",
+                format!("XMP file already exists: {}", xmp_path.display()),
+            );
             continue;
         }
-        let mut media_xmp = XmpFile::new()?;
-        if media_xmp
-            .open_file(media.clone(), OpenFileOptions::default())
-            .is_ok()
-        {
-            let xmp_result = (|| -> anyhow::Result<XmpMeta> {
-                let mut xmp = media_xmp.xmp().unwrap_or_default();
-                if let Some(row) = debug_row.as_mut() {
-                    if let Some(value) = xmp.property_date(xmp_ns::EXIF, "DateTimeOriginal") {
-                        row.embedded_datetime_original_raw =
-                            iso_datetime_to_csv_format(&ignore_timezone(value.value.to_string())?);
-                        row.datetime = row.embedded_datetime_original_raw.clone();
-                    }
-                    if let Some(value) = xmp.property_date(xmp_ns::XMP, "CreateDate") {
-                        row.embedded_create_date_raw =
-                            iso_datetime_to_csv_format(&ignore_timezone(value.value.to_string())?);
+        let media_to_read = media.clone();
+        let embedded_xmp =
+            match run_with_timeout(file_timeout, move || read_embedded_xmp(&media_to_read)) {
+                Ok(embedded_xmp) => embedded_xmp,
+                Err(err) if err.is::<FileTimeoutError>() => {
+                    pb.warn("Timed out files", format!("{err}: {}", media.display()));
+                    pb.inc(1);
+                    if let Some(row) = debug_row {
+                        debug_rows.push(row);
                     }
-                    let (latitude, longitude) = extract_xmp_gps_coordinates(&xmp);
-                    row.latitude = latitude.unwrap_or_default();
-                    row.longitude = longitude.unwrap_or_default();
+                    continue;
                 }
-                // Workaround for Exiv2 not recognizing this EXIF field in sidecars.
-                xmp.delete_property(xmp_ns::EXIF, "DeviceSettingDescription")
-                    .map_err(anyhow::Error::from)?;
-                Ok(xmp)
-            })();
-            let mut xmp = finalize_xmp_file(&mut media_xmp, xmp_result)?;
+                Err(err) => return Err(err),
+            };
+        if let Some(mut xmp) = embedded_xmp {
+            if let Some(row) = debug_row.as_mut() {
+                if let Some(value) = xmp.property_date(xmp_ns::EXIF, "DateTimeOriginal") {
+                    row.embedded_datetime_original_raw =
+                        iso_datetime_to_csv_format(&ignore_timezone(value.value.to_string())?);
+                    row.datetime = row.embedded_datetime_original_raw.clone();
+                }
+                if let Some(value) = xmp.property_date(xmp_ns::XMP, "CreateDate") {
+                    row.embedded_create_date_raw =
+                        iso_datetime_to_csv_format(&ignore_timezone(value.value.to_string())?);
+                }
+                let (latitude, longitude) = extract_xmp_gps_coordinates(&xmp);
+                row.latitude = latitude.map(format_coordinate).unwrap_or_default();
+                row.longitude = longitude.map(format_coordinate).unwrap_or_default();
+            }
+            // Workaround for Exiv2 not recognizing this EXIF field in sidecars.
+            xmp.delete_property(xmp_ns::EXIF, "DeviceSettingDescription")
+                .map_err(anyhow::Error::from)?;
 
             let has_datetime_original = xmp.property(xmp_ns::EXIF, "DateTimeOriginal").is_some();
             let has_metadata_date = xmp.property(xmp_ns::XMP, "MetadataDate").is_some();
@@ -473,7 +1338,7 @@ pub fn init_xmp(working_dir: PathBuf, info: bool) -> anyhow::Result<()> {
                     strip_xmp_datetime_timezone(&mut xmp, xmp_ns::XMP, "ModifyDate")?;
                 } else {
                     // Get the modified time of the file
-                    if let Ok(metadata) = fs::metadata(media)
+                    if let Ok(metadata) = fs::metadata(&media)
                         && let Ok(modified_time) = metadata.modified()
                     {
                         let datetime: DateTime<Local> = DateTime::from(modified_time);
@@ -485,12 +1350,32 @@ pub fn init_xmp(working_dir: PathBuf, info: bool) -> anyhow::Result<()> {
                     }
                 }
             }
+            if let Some(template) = template.as_deref() {
+                let datetime = xmp
+                    .property(xmp_ns::EXIF, "DateTimeOriginal")
+                    .map(|value| ignore_timezone(value.value))
+                    .transpose()?
+                    .unwrap_or_default();
+                let deployment = match deploy_path_index {
+                    Some(deploy_path_index) => deployment_from_path(&media, deploy_path_index)?,
+                    None => String::new(),
+                };
+                let file_name = media
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let filled = fill_xmp_template(template, &datetime, &deployment, &file_name);
+                merge_xmp_template(&mut xmp, &XmpMeta::from_str(&filled)?)?;
+            }
             let xmp_string = xmp
                 .to_string_with_options(ToStringOptions::default().set_newline("\n".to_string()))?;
             fs::write(&xmp_path, xmp_string)?;
             pb.inc(1);
         } else {
-            pb.println(format!("Failed to open file: {}", media.display()));
+            pb.warn(
+                "Failed to open files",
+                format!("Failed to open file: {}", media.display()),
+            );
             pb.inc(1);
         }
         if let Some(row) = debug_row {
@@ -504,151 +1389,814 @@ pub fn init_xmp(working_dir: PathBuf, info: bool) -> anyhow::Result<()> {
     Ok(())
 }
 
-type Metadata = (
-    Vec<String>, // species
-    Vec<String>, // individuals
-    Vec<String>, // count
-    Vec<String>, // sex
-    Vec<String>, // bodyparts
-    Vec<String>, // subjects
-    String,      // datetime
-    String,      // latitude
-    String,      // longitude
-    // String,      // datetime_digitized
-    String, // time_modified
-    String, // rating
-);
-
-fn retrieve_metadata(file_path: &Path, debug_mode: bool) -> anyhow::Result<Metadata> {
+// What observe reads from one file, empty for a file that couldn't be read
+#[derive(Default)]
+struct FileMetadata {
+    species: Vec<String>,
+    individuals: Vec<String>,
+    count: Vec<String>,
+    sex: Vec<String>,
+    bodyparts: Vec<String>,
+    subjects: Vec<String>,
+    datetime: String,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    // datetime_digitized: String,
+    time_modified: String,
+    rating: String,
+    tagger: String,
+    pick_label: String,
+    color_label: String,
+    datetime_source: String,
+    camera_make: String,
+    camera_model: String,
+    camera_serial: String,
+    // One list per --extra-tag category
+    extra: Vec<Vec<String>>,
+}
+
+fn retrieve_metadata(
+    file_path: &Path,
+    debug_mode: bool,
+    exif_fallback: bool,
+    gps: bool,
+    camera_info: bool,
+    extra_tags: &[String],
+    timezone: DatetimeTimezone,
+) -> anyhow::Result<FileMetadata> {
     // Retrieve metadata from given file
     // species, individual, bodypart, sex, count in digikam taglist / adobe hierarchicalsubject (species only), subject (for debugging),
     // datetime, datetime_digitized, rating and file modified time
 
-    let mut f = XmpFile::new()?;
-    f.open_file(file_path, OpenFileOptions::default())?;
-
-    let mut species: Vec<String> = Vec::new();
-    let mut individuals: Vec<String> = Vec::new();
-    let mut count: Vec<String> = Vec::new();
-    let mut sex: Vec<String> = Vec::new();
-    let mut bodyparts: Vec<String> = Vec::new();
-    let mut subjects: Vec<String> = Vec::new(); // for old digikam vesrion?
-    let mut datetime = String::new();
-    let mut latitude = String::new();
-    let mut longitude = String::new();
-    // let mut datetime_digitized = String::new();
     let mut time_modified = String::new();
-    let mut rating = String::new();
-
     if debug_mode {
         let file_metadata = fs::metadata(file_path)?;
         let file_modified_time: DateTime<Local> = file_metadata.modified()?.into();
         time_modified = file_modified_time.format("%Y-%m-%dT%H:%M:%S").to_string();
     }
-    let metadata_result = (|| -> anyhow::Result<Metadata> {
-        if let Some(xmp) = f.xmp() {
-            if let Some(value) = xmp.property_date(xmp_ns::EXIF, "DateTimeOriginal") {
-                datetime = ignore_timezone(value.value.to_string())?;
-            } else if let Some(value) = xmp.property_date(xmp_ns::XMP, "CreateDate") {
-                // Workaround for video files, as some manufacturer only write to xmp:CreateDate
-                // And timezone is ignored for they write UTC-8 time but label as UTC
-                // i.e. we follow time shown in the picture without considering timezone in metadata
-                // Ignore 0 timestamp in QuickTime:CreateDate, i.e. not start with 1904 and 1970
-                if !value.value.to_string().starts_with("1904")
-                    && !value.value.to_string().starts_with("1970")
-                {
-                    datetime = ignore_timezone(value.value.to_string())?;
-                }
-            }
-            // if let Some(value) = xmp.property_date(xmp_ns::EXIF, "DateTimeDigitized") {
-            //     datetime_digitized = ignore_timezone(value.value.to_string())?;
-            // }
-            if let Some(value) = xmp.property(xmp_ns::XMP, "Rating") {
-                rating = value.value.to_string();
-            }
-            let (gps_latitude, gps_longitude) = extract_xmp_gps_coordinates(&xmp);
-            latitude = gps_latitude.unwrap_or_default();
-            longitude = gps_longitude.unwrap_or_default();
-            if debug_mode {
-                for property in xmp.property_array(xmp_ns::DC, "subject") {
-                    subjects.push(property.value.to_string());
-                }
-            }
-
-            // use adobe hierarchicalSubject if available (digikam also writes to this field)
-            for property in xmp.property_array(LIGHTROOM_NS, LR_HIERARCHICAL_SUBJECT) {
-                let tag = property.value;
-                if tag.starts_with(TagType::Species.adobe_tag_prefix()) {
-                    species.push(
-                        tag.strip_prefix(TagType::Species.adobe_tag_prefix())
-                            .unwrap()
-                            .to_string(),
-                    );
-                } else if tag.starts_with(TagType::Individual.adobe_tag_prefix()) {
-                    individuals.push(
-                        tag.strip_prefix(TagType::Individual.adobe_tag_prefix())
-                            .unwrap()
-                            .to_string(),
-                    );
-                } else if tag.starts_with(TagType::Count.adobe_tag_prefix()) {
-                    count.push(
-                        tag.strip_prefix(TagType::Count.adobe_tag_prefix())
-                            .unwrap()
-                            .to_string(),
-                    );
-                } else if tag.starts_with(TagType::Sex.adobe_tag_prefix()) {
-                    sex.push(
-                        tag.strip_prefix(TagType::Sex.adobe_tag_prefix())
-                            .unwrap()
-                            .to_string(),
-                    );
-                } else if tag.starts_with(TagType::Bodypart.adobe_tag_prefix()) {
-                    bodyparts.push(
-                        tag.strip_prefix(TagType::Bodypart.adobe_tag_prefix())
-                            .unwrap()
-                            .to_string(),
-                    );
-                }
-            }
-        }
-        Ok((
-            species,
-            individuals,
-            count,
-            sex,
-            bodyparts,
-            subjects,
-            datetime,
-            latitude,
-            longitude,
-            // datetime_digitized,
+    // Sidecars are decoded here so that an undecodable one lands in the errors output,
+    // the toolkit's packet scanner would read it as having no metadata
+    if media_path_for(file_path).is_some() {
+        let xmp = XmpMeta::from_str(&read_xmp_sidecar(file_path)?)?;
+        return metadata_from_xmp(
+            Some(xmp),
+            debug_mode,
+            camera_info,
+            extra_tags,
+            timezone,
             time_modified,
-            rating,
-        ))
-    })();
-    finalize_xmp_file(&mut f, metadata_result)
-}
+        );
+    }
+
+    let mut f = XmpFile::new()?;
+    f.open_file(file_path, OpenFileOptions::default())?;
+    let metadata_result = metadata_from_xmp(
+        f.xmp(),
+        debug_mode,
+        camera_info,
+        extra_tags,
+        timezone,
+        time_modified,
+    );
+    let mut metadata = finalize_xmp_file(&mut f, metadata_result)?;
+    // Cameras writing DateTimeOriginal to EXIF only, before any `xmp init`
+    if exif_fallback
+        && metadata.datetime.is_empty()
+        && resource_extension(file_path).is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+        && let Some(datetime) = exif_datetime_original(file_path)
+    {
+        metadata.datetime = datetime;
+        metadata.datetime_source = "exif".to_string();
+    }
+    if gps
+        && (metadata.latitude.is_none() || metadata.longitude.is_none())
+        && resource_extension(file_path).is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+        && let Some((latitude, longitude)) = exif_gps_coordinates(file_path)
+    {
+        metadata.latitude = Some(round_coordinate(latitude));
+        metadata.longitude = Some(round_coordinate(longitude));
+    }
+    Ok(metadata)
+}
+
+// EXIF DateTimeOriginal of an image in the `YYYY-MM-DDTHH:MM:SS` form read from XMP,
+// None when the file has no EXIF block or a blank (zero) date
+fn exif_datetime_original(file_path: &Path) -> Option<String> {
+    let file = fs::File::open(file_path).ok()?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut std::io::BufReader::new(file))
+        .ok()?;
+    let field = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)?;
+    let exif::Value::Ascii(values) = &field.value else {
+        return None;
+    };
+    let datetime = exif::DateTime::from_ascii(values.first()?).ok()?;
+    (datetime.year > 0).then(|| {
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            datetime.year,
+            datetime.month,
+            datetime.day,
+            datetime.hour,
+            datetime.minute,
+            datetime.second
+        )
+    })
+}
+
+// Tags, datetime, location and rating of an XMP packet, shared by files and archive entries
+fn metadata_from_xmp(
+    xmp: Option<XmpMeta>,
+    debug_mode: bool,
+    camera_info: bool,
+    extra_tags: &[String],
+    timezone: DatetimeTimezone,
+    time_modified: String,
+) -> anyhow::Result<FileMetadata> {
+    // species, individuals, count, sex, bodyparts and extra tags
+    let mut tags = FileMetadata {
+        extra: vec![Vec::new(); extra_tags.len()],
+        ..FileMetadata::default()
+    };
+    let mut subjects: Vec<String> = Vec::new(); // for old digikam vesrion?
+    let mut datetime = String::new();
+    let mut latitude = None;
+    let mut longitude = None;
+    // let mut datetime_digitized = String::new();
+    let mut rating = String::new();
+    let mut tagger = String::new();
+    let mut pick_label = String::new();
+    let mut color_label = String::new();
+    let mut camera_make = String::new();
+    let mut camera_model = String::new();
+    let mut camera_serial = String::new();
+
+    if let Some(xmp) = xmp {
+        if let Some(value) = xmp.property(SERVAL_NS, SERVAL_TAGGER) {
+            tagger = value.value;
+        }
+        if let Some(value) = xmp.property_date(xmp_ns::EXIF, "DateTimeOriginal") {
+            datetime = timezone.apply(&value.value.to_string());
+        } else if let Some(value) = xmp.property_date(xmp_ns::XMP, "CreateDate") {
+            // Workaround for video files, as some manufacturer only write to xmp:CreateDate
+            // And timezone is ignored for they write UTC-8 time but label as UTC
+            // i.e. we follow time shown in the picture without considering timezone in metadata
+            // (unless --timezone asks otherwise, trusting the label)
+            // Ignore 0 timestamp in QuickTime:CreateDate, i.e. not start with 1904 and 1970
+            if !value.value.to_string().starts_with("1904")
+                && !value.value.to_string().starts_with("1970")
+            {
+                datetime = timezone.apply(&value.value.to_string());
+            }
+        }
+        // if let Some(value) = xmp.property_date(xmp_ns::EXIF, "DateTimeDigitized") {
+        //     datetime_digitized = ignore_timezone(value.value.to_string())?;
+        // }
+        if let Some(value) = xmp.property(xmp_ns::XMP, "Rating") {
+            rating = value.value.to_string();
+        }
+        // Stored as indices, unknown values are kept as they are
+        if let Some(value) = xmp.property(DIGIKAM_NS, DIGIKAM_PICK_LABEL) {
+            pick_label = label_name(PICK_LABELS, &value.value)
+                .map(str::to_string)
+                .unwrap_or(value.value);
+        }
+        if let Some(value) = xmp.property(DIGIKAM_NS, DIGIKAM_COLOR_LABEL) {
+            color_label = label_name(COLOR_LABELS, &value.value)
+                .map(str::to_string)
+                .unwrap_or(value.value);
+        }
+        let (gps_latitude, gps_longitude) = extract_xmp_gps_coordinates(&xmp);
+        latitude = gps_latitude.map(round_coordinate);
+        longitude = gps_longitude.map(round_coordinate);
+        if camera_info {
+            let text = |namespace: &str, name: &str| {
+                xmp.property(namespace, name)
+                    .map(|value| value.value.trim().to_string())
+                    .filter(|value| !value.is_empty())
+            };
+            camera_make = text(xmp_ns::TIFF, "Make").unwrap_or_default();
+            camera_model = text(xmp_ns::TIFF, "Model").unwrap_or_default();
+            // Reconciled from EXIF as exifEX:, some writers use exif: or the older aux:
+            camera_serial = text(EXIF_EX_NS, "BodySerialNumber")
+                .or_else(|| text(xmp_ns::EXIF, "BodySerialNumber"))
+                .or_else(|| text(AUX_NS, "SerialNumber"))
+                .unwrap_or_default();
+        }
+        if debug_mode {
+            for property in xmp.property_array(xmp_ns::DC, "subject") {
+                subjects.push(property.value.to_string());
+            }
+        }
+
+        // use adobe hierarchicalSubject if it has Serval tags (digikam also writes to this field),
+        // else the digiKam TagsList of older sidecars, whose levels are separated by /
+        let hierarchical_subject = xmp
+            .property_array(LIGHTROOM_NS, LR_HIERARCHICAL_SUBJECT)
+            .map(|property| property.value);
+        tags = serval_tags(
+            hierarchical_subject,
+            '|',
+            TagType::adobe_tag_prefix,
+            extra_tags,
+        );
+        if !tags.has_tags() {
+            let tags_list = xmp
+                .property_array(DIGIKAM_NS, DIGIKAM_TAGSLIST)
+                .map(|property| property.value);
+            tags = serval_tags(tags_list, '/', TagType::digikam_tag_prefix, extra_tags);
+        }
+    }
+    let datetime_source = if datetime.is_empty() { "none" } else { "xmp" }.to_string();
+    Ok(FileMetadata {
+        subjects,
+        datetime,
+        latitude,
+        longitude,
+        // datetime_digitized,
+        time_modified,
+        rating,
+        tagger,
+        pick_label,
+        color_label,
+        datetime_source,
+        camera_make,
+        camera_model,
+        camera_serial,
+        ..tags
+    })
+}
+
+// Species, individual, count, sex, bodypart and --extra-tag values of a keyword array, the
+// other fields left empty
+fn serval_tags(
+    tags: impl Iterator<Item = String>,
+    level_separator: char,
+    prefix: fn(TagType) -> &'static str,
+    extra_tags: &[String],
+) -> FileMetadata {
+    let mut metadata = FileMetadata {
+        extra: vec![Vec::new(); extra_tags.len()],
+        ..FileMetadata::default()
+    };
+    for tag in tags {
+        if let Some(value) = tag.strip_prefix(prefix(TagType::Species)) {
+            metadata.species.push(value.to_string());
+        } else if let Some(value) = tag.strip_prefix(prefix(TagType::Individual)) {
+            metadata.individuals.push(value.to_string());
+        } else if let Some(value) = tag.strip_prefix(prefix(TagType::Count)) {
+            metadata.count.push(value.to_string());
+        } else if let Some(value) = tag.strip_prefix(prefix(TagType::Sex)) {
+            metadata.sex.push(value.to_string());
+        } else if let Some(value) = tag.strip_prefix(prefix(TagType::Bodypart)) {
+            metadata.bodyparts.push(value.to_string());
+        } else {
+            // Categories not declared with --extra-tag are ignored
+            for (category, values) in extra_tags.iter().zip(metadata.extra.iter_mut()) {
+                if let Some(value) = tag
+                    .strip_prefix(category.as_str())
+                    .and_then(|rest| rest.strip_prefix(level_separator))
+                {
+                    values.push(value.to_string());
+                }
+            }
+        }
+    }
+    metadata
+}
+
+impl FileMetadata {
+    // Whether any keyword was read as a Serval tag, other keywords (Places|...) don't count
+    fn has_tags(&self) -> bool {
+        !(self.species.is_empty()
+            && self.individuals.is_empty()
+            && self.count.is_empty()
+            && self.sex.is_empty()
+            && self.bodyparts.is_empty()
+            && self.extra.iter().all(Vec::is_empty))
+    }
+}
+
+// Layout of species_stats and count_all: species by count, ties by name, then the
+// indeterminate labels the same way, (untagged) for empty species and TOTAL, each with its
+// percent of the total
+fn format_species_counts(df: &DataFrame) -> PolarsResult<DataFrame> {
+    let species_column = TagType::Species.col_name();
+    let species = df.column(species_column)?.str()?;
+    let counts = df.column("count")?.as_materialized_series().idx()?;
+    let indeterminate_labels = IndeterminateLabels::current();
+    let mut rows: Vec<(&str, IdxSize)> = Vec::new();
+    let mut indeterminate: Vec<(&str, IdxSize)> = Vec::new();
+    let mut untagged: IdxSize = 0;
+    for (species, count) in species.iter().zip(counts.iter()) {
+        match species.filter(|species| !species.is_empty()) {
+            Some(species) if indeterminate_labels.contains(species) => {
+                indeterminate.push((species, count.unwrap_or(0)))
+            }
+            Some(species) => rows.push((species, count.unwrap_or(0))),
+            None => untagged += count.unwrap_or(0),
+        }
+    }
+    rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    indeterminate.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    rows.extend(indeterminate);
+    let total = rows.iter().map(|(_, count)| count).sum::<IdxSize>() + untagged;
+    rows.push((UNTAGGED_ROW, untagged));
+    rows.push((TOTAL_ROW, total));
+    let percent: Vec<f64> = rows
+        .iter()
+        .map(|(_, count)| {
+            if total == 0 {
+                0.0
+            } else {
+                (*count as f64 * 10000.0 / total as f64).round() / 100.0
+            }
+        })
+        .collect();
+    let (names, counts): (Vec<&str>, Vec<IdxSize>) = rows.into_iter().unzip();
+    DataFrame::new(
+        names.len(),
+        vec![
+            Column::new(species_column.into(), names),
+            Column::new("count".into(), counts),
+            Column::new("percent".into(), percent),
+        ],
+    )
+}
+
+/// Previous observe output whose rows are taken over for files still on disk (observe --resume)
+#[derive(Clone, Debug)]
+pub struct ObserveResume {
+    pub tags: PathBuf,
+    /// Read again the files modified after their time_modified in `tags`, or after `tags` was
+    /// written where it has none
+    pub check_mtime: bool,
+}
+
+// Rows of the previous output kept for the files listed now, and the paths they cover
+fn load_resumed_tags(
+    resume: &ObserveResume,
+    file_paths: &[PathBuf],
+) -> anyhow::Result<(DataFrame, HashSet<String>)> {
+    check_csv_columns(&resume.tags, &[PATH_COLUMN], &ColumnMap::default())?;
+    let df = if is_parquet(&resume.tags) {
+        read_parquet_as_text(&resume.tags)?
+    } else {
+        CsvReadOptions::default()
+            .with_infer_schema_length(Some(0))
+            .try_into_reader_with_file_path(Some(resume.tags.clone()))?
+            .finish()?
+    };
+    reject_duplicate_csv_columns(&df)?;
+    let paths = df.column(PATH_COLUMN)?.str()?;
+    if paths.iter().flatten().any(is_path_token) {
+        return Err(anyhow::anyhow!(
+            "{} has anonymized paths (--anonymize-paths), it can't be resumed from",
+            resume.tags.display()
+        ));
+    }
+
+    let listed: HashSet<String> = file_paths
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    let previous: HashSet<&str> = paths.iter().flatten().collect();
+    let num_gone = previous
+        .iter()
+        .filter(|path| !listed.contains(**path))
+        .count();
+    if num_gone > 0 {
+        println!(
+            "Dropped {num_gone} file(s) of {} no longer on disk",
+            resume.tags.display()
+        );
+    }
+    let mut kept: HashSet<String> = previous
+        .into_iter()
+        .filter(|path| listed.contains(*path))
+        .map(str::to_string)
+        .collect();
+    // Files that failed to read (a timeout, a flaky share) have a blank row, they're read again
+    if let Some(errors_path) = resumed_errors_path(&resume.tags) {
+        let errors = CsvReadOptions::default()
+            .with_infer_schema_length(Some(0))
+            .try_into_reader_with_file_path(Some(errors_path.clone()))?
+            .finish()?;
+        let failed: Vec<&str> = errors
+            .column(PATH_COLUMN)?
+            .str()?
+            .iter()
+            .flatten()
+            .filter(|path| kept.remove(*path))
+            .collect();
+        if !failed.is_empty() {
+            println!(
+                "{} file(s) listed in {}, read again",
+                failed.len(),
+                errors_path.display()
+            );
+        }
+    }
+    if resume.check_mtime {
+        // Files compare against the time_modified they were read at when it was recorded
+        // (observe --debug), against the time the tags were written otherwise
+        let tags_modified: DateTime<Local> = fs::metadata(&resume.tags)?.modified()?.into();
+        let tags_modified = tags_modified.naive_local();
+        let time_modified_col = df.column(TIME_MODIFIED_COLUMN).ok();
+        let mut recorded: HashMap<&str, NaiveDateTime> = HashMap::new();
+        for (i, path) in paths.iter().enumerate() {
+            let Some(path) = path.filter(|path| kept.contains(*path)) else {
+                continue;
+            };
+            let time_modified = time_modified_col
+                .and_then(|column| column.str().ok()?.get(i))
+                .and_then(parse_time_modified);
+            recorded
+                .entry(path)
+                .or_insert(time_modified.unwrap_or(tags_modified));
+        }
+        let changed: Vec<String> = recorded
+            .into_par_iter()
+            .filter(|(path, time_modified)| {
+                modified_after(Path::new(path), *time_modified) == Some(true)
+            })
+            .map(|(path, _)| path.to_string())
+            .collect();
+        if !changed.is_empty() {
+            println!(
+                "{} file(s) modified since {} was written, read again",
+                changed.len(),
+                resume.tags.display()
+            );
+        }
+        for path in &changed {
+            kept.remove(path);
+        }
+    }
+    let keep = BooleanChunked::from_iter_values(
+        "keep".into(),
+        paths
+            .iter()
+            .map(|path| path.is_some_and(|path| kept.contains(path))),
+    );
+    let df = parse_observe_tags_text(df.filter(&keep)?)?;
+    Ok((df, kept))
+}
+
+// Errors CSV written by the same observe run as `tags` (tags_<suffix> -> errors_<suffix>.csv),
+// if there is one
+fn resumed_errors_path(tags: &Path) -> Option<PathBuf> {
+    let stem = tags.file_stem()?.to_str()?;
+    let suffix = stem.strip_prefix("tags")?;
+    let errors_path = tags.with_file_name(format!("errors{suffix}.csv"));
+    errors_path.is_file().then_some(errors_path)
+}
+
+// Previous rows and the rows read now in one frame, a column missing on either side left empty
+fn merge_resumed_tags(
+    df_new: DataFrame,
+    df_previous: DataFrame,
+    extra_columns: &[String],
+) -> PolarsResult<DataFrame> {
+    let mut schema = df_new.schema().as_ref().clone();
+    for (name, dtype) in df_previous.schema().iter() {
+        if schema.get(name).is_none() {
+            schema.insert(name.clone(), dtype.clone());
+        }
+    }
+    let align = |df: DataFrame| {
+        let columns: Vec<Expr> = schema
+            .iter()
+            .map(|(name, dtype)| match df.column(name) {
+                Ok(column) if column.dtype() == dtype => col(name.clone()),
+                Ok(_) => col(name.clone()).cast(dtype.clone()),
+                Err(_) => lit(NULL).cast(dtype.clone()).alias(name.clone()),
+            })
+            .collect();
+        df.lazy().select(columns)
+    };
+    let merged = concat([align(df_previous), align(df_new)], UnionArgs::default())?
+        .sort(
+            [PATH_COLUMN],
+            SortMultipleOptions::default().with_maintain_order(true),
+        )
+        .collect()?;
+    canonicalize_observe_tags_df(merged, extra_columns)
+}
+
+// Number the bursts of each deployment (else directory) in datetime order, a new event starts
+// when the gap to the previous file exceeds `gap_seconds`, grouped like propagate's sequences.
+// All rows of a file share its event, files without a datetime get none.
+fn assign_event_ids(df: DataFrame, gap_seconds: u64) -> anyhow::Result<DataFrame> {
+    let df = match df.column(EVENT_ID_COLUMN) {
+        Ok(_) => df.drop(EVENT_ID_COLUMN)?,
+        Err(_) => df,
+    };
+    let paths = df.column(PATH_COLUMN)?.str()?.clone();
+    let datetimes = df
+        .column(DATETIME_COLUMN)?
+        .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?
+        .datetime()?
+        .physical()
+        .clone();
+    let deployments = match df.column(DEPLOYMENT_COLUMN) {
+        Ok(column) => Some(column.cast(&DataType::String)?.str()?.clone()),
+        Err(_) => None,
+    };
+    let mut files: BTreeMap<&str, (String, Option<NaiveDateTime>)> = BTreeMap::new();
+    for row in 0..df.height() {
+        let Some(path) = paths.get(row) else {
+            continue;
+        };
+        let group = deployments
+            .as_ref()
+            .and_then(|deployments| deployments.get(row))
+            .filter(|deployment| !deployment.is_empty())
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                let path = path.replace('\\', "/");
+                path.rsplit_once('/')
+                    .map_or(String::new(), |(dir, _)| dir.to_string())
+            });
+        let datetime = datetimes
+            .get(row)
+            .and_then(DateTime::from_timestamp_millis)
+            .map(|datetime| datetime.naive_utc());
+        files.entry(path).or_insert((group, datetime));
+    }
+    let files: Vec<(&str, &String, Option<NaiveDateTime>)> = files
+        .iter()
+        .map(|(path, (group, datetime))| (*path, group, *datetime))
+        .collect();
+    let window = i64::try_from(gap_seconds)
+        .ok()
+        .and_then(chrono::Duration::try_seconds)
+        .unwrap_or(chrono::Duration::MAX);
+    let events = crate::propagate::sequences(
+        files
+            .iter()
+            .map(|(_, group, datetime)| (Some(group.as_str()), *datetime)),
+        window,
+    );
+    let mut event_paths: Vec<&str> = Vec::new();
+    let mut event_ids: Vec<u32> = Vec::new();
+    for (event, indices) in events.iter().enumerate() {
+        for &index in indices {
+            event_paths.push(files[index].0);
+            event_ids.push(event as u32 + 1);
+        }
+    }
+    let num_files = event_paths.len();
+    let num_events = events.len();
+    let df_events = DataFrame::new(
+        num_files,
+        vec![
+            Column::new(PATH_COLUMN.into(), event_paths),
+            Column::new(EVENT_ID_COLUMN.into(), event_ids),
+        ],
+    )?;
+    println!(
+        "Grouped {num_files} file(s) with a datetime into {num_events} event(s), {gap_seconds}s apart at most"
+    );
+    Ok(df
+        .lazy()
+        .join(
+            df_events.lazy(),
+            [col(PATH_COLUMN)],
+            [col(PATH_COLUMN)],
+            JoinArgs {
+                maintain_order: MaintainOrderJoin::Left,
+                ..JoinArgs::new(JoinType::Left)
+            },
+        )
+        .collect()?)
+}
+
+// Date, year, month and hour of `datetime_column` as columns of their own (--derive-dateparts),
+// null where the datetime is
+fn with_dateparts(df: DataFrame, datetime_column: &str) -> PolarsResult<DataFrame> {
+    let datetime = col(datetime_column);
+    df.lazy()
+        .with_columns([
+            datetime.clone().dt().date().alias(DATE_COLUMN),
+            datetime.clone().dt().year().alias(YEAR_COLUMN),
+            datetime.clone().dt().month().alias(MONTH_COLUMN),
+            datetime.dt().hour().alias(HOUR_COLUMN),
+        ])
+        .collect()
+}
+
+// Path and raw value of the datetimes read from the files that `options` doesn't parse
+fn malformed_datetimes(
+    df_raw: &DataFrame,
+    dtype: &DataType,
+    options: &StrptimeOptions,
+) -> PolarsResult<Vec<(String, String)>> {
+    let df = df_raw
+        .clone()
+        .lazy()
+        .select([
+            col(PATH_COLUMN),
+            col(DATETIME_COLUMN),
+            col(DATETIME_COLUMN)
+                .str()
+                .strptime(dtype.clone(), options.clone(), lit("raise"))
+                .alias("parsed"),
+        ])
+        .filter(
+            col(DATETIME_COLUMN)
+                .neq(lit(""))
+                .and(col("parsed").is_null()),
+        )
+        .collect()?;
+    let paths = df.column(PATH_COLUMN)?.str()?;
+    let datetimes = df.column(DATETIME_COLUMN)?.str()?;
+    Ok(paths
+        .iter()
+        .zip(datetimes.iter())
+        .filter_map(|(path, datetime)| Some((path?.to_string(), datetime?.to_string())))
+        .collect())
+}
+
+// The three most common shapes of the malformed datetimes (digits as #), each with the
+// directory most of them come from, usually one camera
+fn describe_malformed_datetimes(malformed: &[(String, String)]) -> Vec<String> {
+    let mut patterns: HashMap<String, HashMap<String, usize>> = HashMap::new();
+    for (path, datetime) in malformed {
+        let pattern: String = datetime
+            .chars()
+            .map(|c| if c.is_ascii_digit() { '#' } else { c })
+            .collect();
+        let dir = Path::new(path)
+            .parent()
+            .map(|dir| dir.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        *patterns.entry(pattern).or_default().entry(dir).or_default() += 1;
+    }
+    let mut patterns: Vec<(String, usize, String, usize)> = patterns
+        .into_iter()
+        .map(|(pattern, dirs)| {
+            let total = dirs.values().sum();
+            let (dir, count) = dirs
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+                .unwrap_or_default();
+            (pattern, total, dir, count)
+        })
+        .collect();
+    patterns.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    patterns
+        .into_iter()
+        .take(3)
+        .map(|(pattern, total, dir, count)| {
+            format!(
+                "\"{pattern}\" in {total} file(s), {count} of them in {dir}, check the camera there"
+            )
+        })
+        .collect()
+}
+
+// Column of each --extra-tag category, its name lowercased, which may not stand for a
+// column observe writes already
+fn extra_tag_columns(categories: &[String]) -> anyhow::Result<Vec<String>> {
+    let mut columns: Vec<String> = Vec::new();
+    for category in categories {
+        if category.trim().is_empty() || category.contains('|') {
+            return Err(anyhow::anyhow!(
+                "--extra-tag takes a top-level tag category, e.g. Behaviour for Behaviour|Grazing, got \"{category}\""
+            ));
+        }
+        let column = category.to_lowercase();
+        if is_known_column(&column) || column.ends_with("_tags") || columns.contains(&column) {
+            return Err(anyhow::anyhow!(
+                "--extra-tag {category} would be written to the {column} column, which is taken"
+            ));
+        }
+        columns.push(column);
+    }
+    Ok(columns)
+}
 
 pub fn get_classifications(
     file_dir: PathBuf,
     output_dir: PathBuf,
     resource_type: ResourceType,
-    debug_mode: bool,
-    volunteer_mode: bool, //TODO: make a mode argument
+    settings: ObserveSettings,
 ) -> anyhow::Result<()> {
+    let ObserveSettings {
+        debug_mode,
+        volunteer_mode,
+        file_timeout,
+        unique_name,
+        pair_media,
+        include_trash,
+        exif_fallback,
+        gps,
+        camera_info,
+        extra_tags,
+        event_gap,
+        derive_dateparts,
+        strict_datetime,
+        timezone,
+        utc_offsets,
+        deployment_lookup,
+        anonymize_paths,
+        duplicate_check,
+        resume,
+        review_view,
+        no_format,
+        tags_format,
+        on_conflict,
+    } = settings;
     // Get tag info from the old digikam workflow in shanshui
     // by enumerating file_dir and read xmp metadata from resources
+    let extra_columns = extra_tag_columns(&extra_tags)?;
 
-    let file_paths = path_enumerate(file_dir.clone(), resource_type);
-    fs::create_dir_all(output_dir.clone())?;
+    if exif_fallback && matches!(resource_type, ResourceType::Xmp) {
+        return Err(anyhow::anyhow!(
+            "--exif-fallback reads the EXIF of images and is not supported with --xmp"
+        ));
+    }
+    // A ZIP archive passed as file_dir is read in place, sidecars only for now
+    let archive_sidecars = if is_zip_archive(&file_dir) {
+        if !matches!(resource_type, ResourceType::Xmp) {
+            return Err(anyhow::anyhow!(
+                "Only XMP sidecars can be read from ZIP archives, use --xmp"
+            ));
+        }
+        if pair_media {
+            return Err(anyhow::anyhow!(
+                "--pair-media is not supported for ZIP archives"
+            ));
+        }
+        if include_trash {
+            return Err(anyhow::anyhow!(
+                "--include-trash is not supported for ZIP archives"
+            ));
+        }
+        if duplicate_check.is_some() {
+            return Err(anyhow::anyhow!(
+                "--detect-duplicate-deployments is not supported for ZIP archives"
+            ));
+        }
+        if resume.is_some() {
+            return Err(anyhow::anyhow!(
+                "--resume is not supported for ZIP archives"
+            ));
+        }
+        Some(read_zip_sidecars(&file_dir)?)
+    } else {
+        None
+    };
+    let mut file_paths = match &archive_sidecars {
+        Some(sidecars) => sidecars
+            .iter()
+            .map(|sidecar| sidecar.path.clone())
+            .collect(),
+        None => path_enumerate(file_dir.clone(), resource_type),
+    };
+    // The digiKam trash is skipped by the walk, it is read to include it or to tell media
+    // deleted in digiKam from missing media
+    let trash = if include_trash || pair_media {
+        DigikamTrash::scan(&file_dir)
+    } else {
+        DigikamTrash::default()
+    };
+    if include_trash {
+        let trashed_files = trash.files(resource_type);
+        println!(
+            "Including {} file(s) from the digiKam trash",
+            trashed_files.len()
+        );
+        file_paths.extend(trashed_files);
+    }
+    // Only new files, and changed ones with --check-mtime, are read
+    let resumed = match &resume {
+        Some(resume) => {
+            let (df_previous, reused) = load_resumed_tags(resume, &file_paths)?;
+            file_paths.retain(|path| !reused.contains(path.to_string_lossy().as_ref()));
+            println!(
+                "Resuming from {}: {} file(s) taken over, {} to read",
+                resume.tags.display(),
+                reused.len(),
+                file_paths.len()
+            );
+            Some(df_previous)
+        }
+        None => None,
+    };
+    let trashed: Vec<bool> = file_paths.iter().map(|path| trash.contains(path)).collect();
     // Determine output filename based on parameters
     let output_suffix = if volunteer_mode {
         String::new()
     } else {
-        let file_name = file_dir
-            .file_name()
-            .map(|s| s.to_string_lossy())
-            .unwrap_or_else(|| std::borrow::Cow::Borrowed("unk")); // For root dir
+        let (file_name, fallback) = dir_output_name(&file_dir);
+        if fallback {
+            println!(
+                "Warning: {} has no directory name (root or ending in ..), outputs are named after \"{file_name}\" instead",
+                file_dir.display()
+            );
+        }
 
         let suffix = format!(
             "_{}_{}_{}.csv",
@@ -658,6 +2206,19 @@ pub fn get_classifications(
         );
         suffix
     };
+    // Same name as an earlier run (volunteer mode, a collapsed directory name)
+    let output_dir = versioned_output_dir(
+        &output_dir,
+        &[
+            format!("tags{output_suffix}"),
+            format!("species_stats{output_suffix}"),
+            format!("data_quality{output_suffix}"),
+        ],
+        on_conflict,
+    )?;
+    fs::create_dir_all(output_dir.clone())?;
+    // Between multiple tags of one image, until species and individuals are exploded below
+    let separator = CsvDialect::current().multi_value_separator();
 
     let image_paths: Vec<String> = file_paths
         .clone()
@@ -675,8 +2236,7 @@ pub fn get_classifications(
         .collect::<anyhow::Result<_>>()?;
     let num_images = file_paths.len();
     println!("Total {resource_type}: {num_images}.");
-    let pb = ProgressBar::new(num_images as u64);
-    configure_progress_bar(&pb);
+    let pb = ServalProgress::new(num_images as u64, "reading metadata");
 
     let mut species_tags: Vec<String> = Vec::new();
     let mut individual_tags: Vec<String> = Vec::new();
@@ -685,79 +2245,115 @@ pub fn get_classifications(
     let mut bodypart_tags: Vec<String> = Vec::new();
     let mut subjects: Vec<String> = Vec::new();
     let mut datetimes: Vec<String> = Vec::new();
-    let mut latitudes: Vec<String> = Vec::new();
-    let mut longitudes: Vec<String> = Vec::new();
+    let mut latitudes: Vec<Option<f64>> = Vec::new();
+    let mut longitudes: Vec<Option<f64>> = Vec::new();
     // let mut datetime_digitizeds: Vec<String> = Vec::new();
     let mut time_modifieds: Vec<String> = Vec::new();
     let mut ratings: Vec<String> = Vec::new();
+    let mut taggers: Vec<Option<String>> = Vec::new();
+    let mut pick_labels: Vec<Option<String>> = Vec::new();
+    let mut color_labels: Vec<Option<String>> = Vec::new();
+    let mut datetime_sources: Vec<String> = Vec::new();
+    let mut camera_makes: Vec<String> = Vec::new();
+    let mut camera_models: Vec<String> = Vec::new();
+    let mut camera_serials: Vec<String> = Vec::new();
+    let mut extra_values: Vec<Vec<String>> = vec![Vec::new(); extra_tags.len()];
 
     let result: Vec<_> = (0..num_images)
         .into_par_iter()
         .map(|i| {
-            match retrieve_metadata(&file_paths[i], debug_mode) {
-                Ok((
-                    species,
-                    individuals,
-                    count,
-                    sex,
-                    bodyparts,
-                    subjects,
-                    datetime,
-                    latitude,
-                    longitude,
-                    // datetime_digitized,
-                    time_modified,
-                    rating,
-                )) => {
+            let file_path = file_paths[i].clone();
+            let metadata =
+                crate::lock::check_interrupted().and_then(|()| match &archive_sidecars {
+                    Some(sidecars) => sidecars[i]
+                        .content
+                        .as_deref()
+                        .map_err(|e| anyhow::anyhow!("{e}"))
+                        .and_then(|content| XmpMeta::from_str(content).map_err(anyhow::Error::from))
+                        .and_then(|xmp| {
+                            let time_modified = if debug_mode {
+                                sidecars[i].modified.clone()
+                            } else {
+                                String::new()
+                            };
+                            metadata_from_xmp(
+                                Some(xmp),
+                                debug_mode,
+                                camera_info,
+                                &extra_tags,
+                                timezone,
+                                time_modified,
+                            )
+                        }),
+                    None => {
+                        let extra_tags = extra_tags.clone();
+                        run_with_timeout(file_timeout, move || {
+                            retrieve_metadata(
+                                &file_path,
+                                debug_mode,
+                                exif_fallback,
+                                gps,
+                                camera_info,
+                                &extra_tags,
+                                timezone,
+                            )
+                        })
+                    }
+                });
+            match metadata {
+                Ok(metadata) => {
                     pb.inc(1);
-                    (
-                        species.join("|"),
-                        individuals.join("|"),
-                        count.join("|"),
-                        sex.join("|"),
-                        bodyparts.join("|"),
-                        subjects.join("|"), // subject just for reviewing
-                        datetime,
-                        latitude,
-                        longitude,
-                        // datetime_digitized,
-                        time_modified,
-                        rating,
-                    )
+                    (None, metadata)
                 }
                 Err(error) => {
-                    pb.println(format!("{} in {}", error, file_paths[i].display()));
+                    if !error.is::<crate::lock::Interrupted>() {
+                        pb.warn(
+                            "Failed to read files",
+                            format!("{} in {}", error, file_paths[i].display()),
+                        );
+                    }
                     pb.inc(1);
-                    (
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                        "".to_string(),
-                    )
+                    let metadata = FileMetadata {
+                        extra: vec![Vec::new(); extra_tags.len()],
+                        ..FileMetadata::default()
+                    };
+                    (Some(error), metadata)
                 }
             }
         })
         .collect();
-    for tag in result {
-        species_tags.push(tag.0);
-        individual_tags.push(tag.1);
-        count_tags.push(tag.2);
-        sex_tags.push(tag.3);
-        bodypart_tags.push(tag.4);
-        subjects.push(tag.5);
-        datetimes.push(tag.6);
-        latitudes.push(tag.7);
-        longitudes.push(tag.8);
-        // datetime_digitizeds.push(tag.7);
-        time_modifieds.push(tag.9);
-        ratings.push(tag.10);
+    let mut error_paths: Vec<String> = Vec::new();
+    let mut error_messages: Vec<String> = Vec::new();
+    for (i, (error, metadata)) in result.into_iter().enumerate() {
+        if let Some(error) = error {
+            if error.is::<FileWorkersStuckError>() || error.is::<crate::lock::Interrupted>() {
+                return Err(error);
+            }
+            error_paths.push(image_paths[i].clone());
+            error_messages.push(error.to_string());
+        }
+        species_tags.push(metadata.species.join(separator));
+        individual_tags.push(metadata.individuals.join(separator));
+        count_tags.push(metadata.count.join(separator));
+        sex_tags.push(metadata.sex.join(separator));
+        bodypart_tags.push(metadata.bodyparts.join(separator));
+        subjects.push(metadata.subjects.join(separator)); // subject just for reviewing
+        datetimes.push(metadata.datetime);
+        latitudes.push(metadata.latitude);
+        longitudes.push(metadata.longitude);
+        // datetime_digitizeds.push(metadata.datetime_digitized);
+        time_modifieds.push(metadata.time_modified);
+        ratings.push(metadata.rating);
+        taggers.push(Some(metadata.tagger).filter(|tagger| !tagger.is_empty()));
+        pick_labels.push(Some(metadata.pick_label).filter(|label| !label.is_empty()));
+        color_labels.push(Some(metadata.color_label).filter(|label| !label.is_empty()));
+        datetime_sources.push(metadata.datetime_source);
+        camera_makes.push(metadata.camera_make);
+        camera_models.push(metadata.camera_model);
+        camera_serials.push(metadata.camera_serial);
+        for (values, value) in extra_values.iter_mut().zip(metadata.extra) {
+            values.push(value.join(separator));
+        }
     }
     pb.finish();
     // Analysis
@@ -775,6 +2371,8 @@ pub fn get_classifications(
     let s_rating = Column::new(RATING_COLUMN.into(), ratings);
 
     let df_raw_height = image_paths.len();
+    let file_keys: Option<Vec<String>> =
+        unique_name.then(|| file_paths.iter().map(|path| file_key_for(path)).collect());
     let mut df_raw = DataFrame::new(
         df_raw_height,
         vec![
@@ -795,6 +2393,97 @@ pub fn get_classifications(
             s_rating,
         ],
     )?;
+    if let Some(file_keys) = file_keys {
+        df_raw.with_column(Column::new(FILE_KEY_COLUMN.into(), file_keys))?;
+    }
+    if exif_fallback {
+        df_raw.with_column(Column::new(DATETIME_SOURCE_COLUMN.into(), datetime_sources))?;
+    }
+    if camera_info {
+        for (column_name, values) in [
+            (CAMERA_MAKE_COLUMN, camera_makes),
+            (CAMERA_MODEL_COLUMN, camera_models),
+            (CAMERA_SERIAL_COLUMN, camera_serials),
+        ] {
+            df_raw.with_column(Column::new(column_name.into(), values))?;
+        }
+    }
+    // Joined like count and sex, a column for each declared category even when never used
+    for (column_name, values) in extra_columns.iter().zip(extra_values) {
+        df_raw.with_column(Column::new(column_name.into(), values))?;
+    }
+    // Only files stamped by `xmp update --tagger` carry attribution
+    if taggers.iter().any(Option::is_some) {
+        df_raw.with_column(Column::new(TAGGER_COLUMN.into(), taggers))?;
+    }
+    // digiKam Pick/Color labels, likewise only when some file has one
+    for (column_name, labels, values) in [
+        (PICK_LABEL_COLUMN, PICK_LABELS, pick_labels),
+        (COLOR_LABEL_COLUMN, COLOR_LABELS, color_labels),
+    ] {
+        if values.iter().all(Option::is_none) {
+            continue;
+        }
+        let num_unknown = values
+            .iter()
+            .flatten()
+            .filter(|value| !labels.contains(&value.as_str()))
+            .count();
+        if num_unknown > 0 {
+            println!(
+                "Warning: {num_unknown} file(s) with unknown {column_name} values, kept as-is (known: {})",
+                labels.join(", ")
+            );
+        }
+        df_raw.with_column(Column::new(column_name.into(), values))?;
+    }
+    if pair_media {
+        let (media_paths, (sidecar_exists, media_exists)): (Vec<String>, (Vec<bool>, Vec<bool>)) =
+            file_paths
+                .iter()
+                .map(|path| {
+                    let (media_path, sidecar_exists, media_exists) = pair_resource_media(path);
+                    (
+                        media_path
+                            .map(|media| media.to_string_lossy().into_owned())
+                            .unwrap_or_default(),
+                        (sidecar_exists, media_exists),
+                    )
+                })
+                .unzip();
+        // Media deleted in digiKam, its sidecar left behind isn't an orphan
+        let media_trashed: Vec<bool> = media_paths
+            .iter()
+            .map(|media| !media.is_empty() && trash.contains(Path::new(media)))
+            .collect();
+        let num_trashed_media = media_exists
+            .iter()
+            .zip(&media_trashed)
+            .filter(|(exists, trashed)| !**exists && **trashed)
+            .count();
+        if num_trashed_media > 0 {
+            println!(
+                "{num_trashed_media} file(s) have their media in the digiKam trash, marked in {MEDIA_TRASHED_COLUMN}"
+            );
+        }
+        df_raw.with_column(Column::new(MEDIA_PATH_COLUMN.into(), media_paths))?;
+        df_raw.with_column(Column::new(SIDECAR_EXISTS_COLUMN.into(), sidecar_exists))?;
+        df_raw.with_column(Column::new(MEDIA_EXISTS_COLUMN.into(), media_exists))?;
+        df_raw.with_column(Column::new(MEDIA_TRASHED_COLUMN.into(), media_trashed))?;
+    }
+    if include_trash {
+        df_raw.with_column(Column::new(TRASHED_COLUMN.into(), trashed.clone()))?;
+    }
+    // Deployment of each file, from where it was before for files in the digiKam trash
+    let file_deployments: Option<Vec<Option<String>>> = deployment_lookup.map(|lookup| {
+        file_paths
+            .iter()
+            .map(|path| lookup.deployment_for(trash.original_path(path).unwrap_or(path)))
+            .collect()
+    });
+    if let Some(deployments) = &file_deployments {
+        df_raw.with_column(Column::new(DEPLOYMENT_COLUMN.into(), deployments.clone()))?;
+    }
     if volunteer_mode {
         // println!("{:?}", df_raw);
         let mut df_empty_species = df_raw
@@ -812,20 +2501,15 @@ pub fn get_classifications(
 
         println!("Species Labeling Progress: {progress:.2}%");
 
-        let pb = ProgressBar::new(num_xmp as u64);
-        pb.set_prefix("Species Labeling Progress:");
+        let pb = ServalProgress::new(num_xmp as u64, "species labeling progress");
         pb.set_position(num_tagged_sp as u64);
+        pb.finish();
 
         println!("Untagged xmp: {}", df_empty_species.height());
 
-        let mut rl = rustyline::DefaultEditor::new()?;
-        let input = rl.readline("Save CSV of files with missing tags for review? (y/n): ")?;
-
-        if input.trim().eq_ignore_ascii_case("y") {
+        if Prompt::new()?.confirm("Save CSV of files with missing tags for review?", false)? {
             let mut file = std::fs::File::create("serval_check_empty.csv")?;
-            CsvWriter::new(&mut file)
-                .include_bom(true)
-                .finish(&mut df_empty_species)?;
+            csv_writer(&mut file).finish(&mut df_empty_species)?;
         } else {
             println!("Skipping save.");
         }
@@ -833,66 +2517,144 @@ pub fn get_classifications(
         return Ok(());
     }
     let datetime_options = StrptimeOptions {
-        // TODO: Serval does not include timezone info now
         format: Some("%Y-%m-%dT%H:%M:%S".into()),
         strict: false,
         ..Default::default()
     };
-    let df_split = df_raw
-        .clone()
-        .lazy()
-        .select([
-            col(PATH_COLUMN),
-            col(FILENAME_COLUMN),
-            col(MEDIA_TYPE_COLUMN),
-            col(DATETIME_COLUMN).str().strptime(
-                DataType::Datetime(TimeUnit::Milliseconds, None),
-                datetime_options.clone(),
+    // --timezone keep: UTC instants, read from the offsets kept in the datetimes
+    let (datetime_dtype, observe_datetime_options, expected_datetime) = if timezone.is_tz_aware() {
+        (
+            DataType::Datetime(TimeUnit::Milliseconds, Some(TimeZone::UTC)),
+            StrptimeOptions {
+                format: Some("%Y-%m-%dT%H:%M:%S%:z".into()),
+                ..datetime_options.clone()
+            },
+            "YYYY-MM-DDTHH:MM:SS+HH:MM",
+        )
+    } else {
+        (
+            DataType::Datetime(TimeUnit::Milliseconds, None),
+            datetime_options.clone(),
+            "YYYY-MM-DDTHH:MM:SS",
+        )
+    };
+    // Datetimes read but not parsed are left empty, the run goes on with them in the errors
+    let malformed = malformed_datetimes(&df_raw, &datetime_dtype, &observe_datetime_options)?;
+    let mut error_datetimes: Vec<String> = vec![String::new(); error_paths.len()];
+    if !malformed.is_empty() {
+        let (path, raw_datetime) = &malformed[0];
+        if strict_datetime {
+            return Err(anyhow::anyhow!(
+                "{} file(s) with a malformed datetime, e.g. \"{raw_datetime}\" in {path}, run without --strict-datetime to leave them empty",
+                malformed.len()
+            ));
+        }
+        println!(
+            "Warning: {} file(s) with a malformed datetime, left empty and listed in the errors CSV (--strict-datetime to abort instead)",
+            malformed.len()
+        );
+        for line in describe_malformed_datetimes(&malformed) {
+            println!("  {line}");
+        }
+        for (path, raw_datetime) in malformed {
+            error_paths.push(path);
+            error_messages.push(format!("Malformed datetime (expected {expected_datetime})"));
+            error_datetimes.push(raw_datetime);
+        }
+    }
+    let mut df_errors = None;
+    if !error_paths.is_empty() {
+        let mut errors = DataFrame::new(
+            error_paths.len(),
+            vec![
+                Column::new(PATH_COLUMN.into(), error_paths),
+                Column::new("error".into(), error_messages),
+            ],
+        )?;
+        if error_datetimes.iter().any(|datetime| !datetime.is_empty()) {
+            errors.with_column(Column::new("raw_datetime".into(), error_datetimes))?;
+        }
+        let errors_csv_path = output_dir.join(format!("errors{output_suffix}"));
+        let mut file = std::fs::File::create(errors_csv_path.clone())?;
+        csv_writer(&mut file).finish(&mut errors)?;
+        println!(
+            "{} file(s) failed, saved to {}",
+            errors.height(),
+            errors_csv_path.to_string_lossy()
+        );
+        df_errors = Some(errors);
+    }
+    let mut split_columns = vec![
+        col(PATH_COLUMN),
+        col(FILENAME_COLUMN),
+        col(MEDIA_TYPE_COLUMN),
+        col(DATETIME_COLUMN)
+            .str()
+            .strptime(datetime_dtype, observe_datetime_options, lit("raise")),
+        col(LATITUDE_COLUMN),
+        col(LONGITUDE_COLUMN),
+        // col("datetime_digitized").str().strptime(
+        //     DataType::Datetime(TimeUnit::Milliseconds, None),
+        //     datetime_options.clone(),
+        //     lit("raise"),
+        // ),
+        col(TIME_MODIFIED_COLUMN)
+            .str()
+            .to_datetime(
+                Some(TimeUnit::Milliseconds),
+                None,
+                datetime_options,
                 lit("raise"),
-            ),
-            col(LATITUDE_COLUMN),
-            col(LONGITUDE_COLUMN),
-            // col("datetime_digitized").str().strptime(
-            //     DataType::Datetime(TimeUnit::Milliseconds, None),
-            //     datetime_options.clone(),
-            //     lit("raise"),
-            // ),
-            col(TIME_MODIFIED_COLUMN)
-                .str()
-                .to_datetime(
-                    Some(TimeUnit::Milliseconds),
-                    None,
-                    datetime_options,
-                    lit("raise"),
-                )
-                .dt()
-                .replace_time_zone(None, lit("raise"), NonExistent::Raise),
-            col("species_tags")
-                .str()
-                .split(lit("|"))
-                .alias(TagType::Species.col_name()),
-            col("individual_tags")
-                .str()
-                .split(lit("|"))
-                .alias(TagType::Individual.col_name()),
-            col("count_tags").alias(TagType::Count.col_name()),
-            col("sex_tags").alias(TagType::Sex.col_name()),
-            col("bodypart_tags").alias(TagType::Bodypart.col_name()),
-            col(SUBJECTS_COLUMN),
-            col(RATING_COLUMN),
-        ])
-        .collect()?;
+            )
+            .dt()
+            .replace_time_zone(None, lit("raise"), NonExistent::Raise),
+        col("species_tags")
+            .str()
+            .split(lit(separator))
+            .alias(TagType::Species.col_name()),
+        col("individual_tags")
+            .str()
+            .split(lit(separator))
+            .alias(TagType::Individual.col_name()),
+        col("count_tags").alias(TagType::Count.col_name()),
+        col("sex_tags").alias(TagType::Sex.col_name()),
+        col("bodypart_tags").alias(TagType::Bodypart.col_name()),
+        col(SUBJECTS_COLUMN),
+        col(RATING_COLUMN),
+    ];
+    // Optional columns are passed through untouched
+    split_columns.extend(
+        OPTIONAL_TAGS_COLUMNS
+            .iter()
+            .filter(|column| {
+                **column != TIME_MODIFIED_COLUMN
+                    && df_raw
+                        .get_column_names()
+                        .iter()
+                        .any(|name| name.as_str() == **column)
+            })
+            .map(|name| col(*name)),
+    );
+    split_columns.extend(extra_columns.iter().map(|name| col(name.as_str())));
+    let mut df_split = df_raw.clone().lazy().select(split_columns).collect()?;
+    // Alongside the camera local datetime, which stays as is
+    if let Some(utc_offsets) = &utc_offsets {
+        let utc_column = utc_offsets.utc_column(&df_split)?;
+        df_split.with_column(utc_column)?;
+    }
     println!("{df_split:?}");
 
     if debug_mode {
         println!("{df_raw}");
         let debug_csv_path = output_dir.join(format!("raw{output_suffix}"));
         let mut file = std::fs::File::create(debug_csv_path.clone())?;
-        CsvWriter::new(&mut file)
-            .include_bom(true)
-            .with_datetime_format(Some("%Y-%m-%d %H:%M:%S".into()))
+        csv_writer(&mut file)
+            .with_datetime_format(csv_datetime_format("%Y-%m-%d %H:%M:%S"))
             .finish(&mut df_raw)?;
         println!("Saved to {}", debug_csv_path.to_string_lossy());
+    } else {
+        // Only recorded in debug mode, kept in the tags then for --resume
+        df_split.drop_in_place(TIME_MODIFIED_COLUMN)?;
     }
     // For multiple tags in a single image (individual only for two species that won't be in the same image)
     let df_flatten = df_split
@@ -915,16 +2677,54 @@ pub fn get_classifications(
         )
         .sort([PATH_COLUMN], SortMultipleOptions::default())
         .collect()?;
-    let mut df_flatten = canonicalize_observe_tags_df(df_flatten)?;
+    let df_flatten = canonicalize_observe_tags_df(df_flatten, &extra_columns)?;
+    // Stats and checks below cover the previous files as well
+    let df_flatten = match resumed {
+        Some(df_previous) => merge_resumed_tags(df_flatten, df_previous, &extra_columns)?,
+        None => df_flatten,
+    };
+    let df_flatten = match event_gap {
+        Some(gap_seconds) => canonicalize_observe_tags_df(
+            assign_event_ids(df_flatten, gap_seconds)?,
+            &extra_columns,
+        )?,
+        None => df_flatten,
+    };
+    let df_flatten = if derive_dateparts {
+        canonicalize_observe_tags_df(with_dateparts(df_flatten, DATETIME_COLUMN)?, &extra_columns)?
+    } else {
+        df_flatten
+    };
     println!("{df_flatten}");
 
+    // Shared copies only, the review below still shows the real paths
+    let mut anonymizer = anonymize_paths.then(Anonymizer::new).transpose()?;
+    let mut df_tags = match anonymizer.as_mut() {
+        Some(anonymizer) => anonymizer.anonymize(&df_flatten)?,
+        None => df_flatten.clone(),
+    };
     let tags_csv_path = output_dir.join(format!("tags{output_suffix}"));
-    let mut file = std::fs::File::create(tags_csv_path.clone())?;
-    CsvWriter::new(&mut file)
-        .with_datetime_format(Some("%Y-%m-%d %H:%M:%S".into()))
-        .include_bom(true)
-        .finish(&mut df_flatten)?;
-    println!("Saved to {}", tags_csv_path.to_string_lossy());
+    if tags_format.csv() {
+        let mut file = std::fs::File::create(tags_csv_path.clone())?;
+        csv_writer(&mut file)
+            .with_datetime_format(csv_datetime_format(if timezone.is_tz_aware() {
+                "%Y-%m-%d %H:%M:%S%:z"
+            } else {
+                "%Y-%m-%d %H:%M:%S"
+            }))
+            .finish(&mut df_tags)?;
+        write_tags_schema_with_extra(&tags_csv_path, &df_tags, &extra_columns)?;
+        println!("Saved to {}", tags_csv_path.to_string_lossy());
+    }
+    // Datetimes stay datetimes, capture reads it in place of the CSV
+    if tags_format.parquet() {
+        let tags_parquet_path = tags_csv_path.with_extension("parquet");
+        ParquetWriter::new(std::fs::File::create(&tags_parquet_path)?).finish(&mut df_tags)?;
+        if !tags_format.csv() {
+            write_tags_schema_with_extra(&tags_parquet_path, &df_tags, &extra_columns)?;
+        }
+        println!("Saved to {}", tags_parquet_path.to_string_lossy());
+    }
 
     let mut df_count_species = df_flatten
         .clone()
@@ -932,27 +2732,543 @@ pub fn get_classifications(
         .select([col(TagType::Species.col_name()).value_counts(true, true, "count", false)])
         .unnest(cols([TagType::Species.col_name()]), None)
         .collect()?;
+    if !no_format {
+        df_count_species = format_species_counts(&df_count_species)?;
+    }
     println!("{df_count_species:?}");
+    let indeterminate = indeterminate_counts(&df_flatten, TagType::Species.col_name())?;
+    if !indeterminate.is_empty() {
+        println!(
+            "Indeterminate species labels, counted apart from the species: {}",
+            describe_indeterminate_counts(&indeterminate)
+        );
+    }
 
     let species_stats_path = output_dir.join(format!("species_stats{output_suffix}"));
     let mut file = std::fs::File::create(species_stats_path.clone())?;
-    CsvWriter::new(&mut file)
-        .include_bom(true)
-        .finish(&mut df_count_species)?;
+    csv_writer(&mut file).finish(&mut df_count_species)?;
+    write_output_schema(
+        &species_stats_path,
+        OutputKind::SpeciesStats,
+        &df_count_species,
+    )?;
     println!("Saved to {}", species_stats_path.to_string_lossy());
+
+    if df_flatten.column(TAGGER_COLUMN).is_ok() {
+        let mut df_count_tagger = df_flatten
+            .clone()
+            .lazy()
+            .group_by([col(TAGGER_COLUMN), col(TagType::Species.col_name())])
+            .agg([len().alias("count")])
+            .sort(
+                [TAGGER_COLUMN, "count"],
+                SortMultipleOptions::default()
+                    .with_order_descending_multi([false, true])
+                    .with_nulls_last(true),
+            )
+            .collect()?;
+        let tagger_stats_path = output_dir.join(format!("species_stats_by_tagger{output_suffix}"));
+        let mut file = std::fs::File::create(tagger_stats_path.clone())?;
+        csv_writer(&mut file).finish(&mut df_count_tagger)?;
+        write_output_schema(
+            &tagger_stats_path,
+            OutputKind::SpeciesStatsByTagger,
+            &df_count_tagger,
+        )?;
+        println!("Saved to {}", tagger_stats_path.to_string_lossy());
+    }
+
+    // Files that temporal analyses can't use, per deployment (the deployment column, else the
+    // parent directory of the file, where it was before for files in the digiKam trash)
+    let num_without_deployment = file_deployments.as_ref().map_or(0, |deployments| {
+        deployments.iter().filter(|d| d.is_none()).count()
+    });
+    let deployments: Vec<Option<String>> = file_deployments.unwrap_or_else(|| {
+        file_paths
+            .iter()
+            .map(|path| {
+                let parent = trash
+                    .original_path(path)
+                    .unwrap_or(path)
+                    .parent()
+                    .and_then(|parent| parent.file_name());
+                Some(
+                    parent
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                )
+            })
+            .collect()
+    });
+    let mut quality_columns = vec![
+        Column::new("deployment".into(), deployments),
+        df_raw.column("species_tags")?.clone(),
+        df_split.column(DATETIME_COLUMN)?.clone(),
+    ];
+    let mut quality_counts = vec![
+        col("tagged")
+            .and(col("dated"))
+            .sum()
+            .alias("tagged_with_datetime"),
+        col("tagged")
+            .and(col("dated").not())
+            .sum()
+            .alias("tagged_without_datetime"),
+        col("tagged")
+            .not()
+            .and(col("dated"))
+            .sum()
+            .alias("datetime_without_tags"),
+    ];
+    if include_trash {
+        quality_columns.push(Column::new(TRASHED_COLUMN.into(), trashed));
+        quality_counts.push(col(TRASHED_COLUMN).sum());
+    }
+    let df_quality = DataFrame::new(df_raw_height, quality_columns)?
+        .lazy()
+        .with_columns([
+            col("species_tags").neq(lit("")).alias("tagged"),
+            col(DATETIME_COLUMN).is_not_null().alias("dated"),
+        ])
+        .group_by([col("deployment")])
+        .agg(quality_counts)
+        .sort(
+            ["deployment"],
+            SortMultipleOptions::default().with_nulls_last(true),
+        )
+        .collect()?;
+    let quality_path = output_dir.join(format!("data_quality{output_suffix}"));
+    let mut file = std::fs::File::create(quality_path.clone())?;
+    csv_writer(&mut file).finish(&mut match anonymizer.as_mut() {
+        Some(anonymizer) => anonymizer.anonymize(&df_quality)?,
+        None => df_quality.clone(),
+    })?;
+    let total = |column: &str| -> anyhow::Result<u64> {
+        Ok(df_quality
+            .column(column)?
+            .as_materialized_series()
+            .sum::<u64>()?)
+    };
+    let (num_dated, num_undated, num_untagged) = (
+        total("tagged_with_datetime")?,
+        total("tagged_without_datetime")?,
+        total("datetime_without_tags")?,
+    );
+    println!(
+        "Data quality: {num_dated} tagged with datetime, {num_undated} tagged without datetime, {num_untagged} with datetime but no tags"
+    );
+    if num_undated > 0 {
+        println!(
+            "Warning: {num_undated} tagged file(s) have no datetime and will be left out of temporal analyses"
+        );
+    }
+    if num_without_deployment > 0 {
+        println!(
+            "Warning: {num_without_deployment} file(s) have no deployment, left empty in {DEPLOYMENT_COLUMN} and counted in the last data quality row"
+        );
+    }
+    if include_trash {
+        println!(
+            "{} file(s) in the digiKam trash, counted per deployment in the {TRASHED_COLUMN} column",
+            total(TRASHED_COLUMN)?
+        );
+    }
+    println!("Saved to {}", quality_path.to_string_lossy());
+    if let Some(anonymizer) = &anonymizer {
+        anonymizer.write_keyfile(&output_dir.join(keyfile_name(&output_suffix)))?;
+    }
+
+    // Copies of a deployment under several directories, files in the digiKam trash aside
+    if let Some(duplicate_check) = duplicate_check {
+        let datetimes: Vec<Option<NaiveDateTime>> = df_split
+            .column(DATETIME_COLUMN)?
+            .cast(&DataType::Int64)?
+            .i64()?
+            .iter()
+            .map(|millis| DateTime::from_timestamp_millis(millis?).map(|d| d.naive_utc()))
+            .collect();
+        let (files, datetimes): (Vec<PathBuf>, Vec<Option<NaiveDateTime>>) = file_paths
+            .iter()
+            .zip(datetimes)
+            .filter(|(path, _)| !trash.contains(path))
+            .map(|(path, datetime)| (path.clone(), datetime))
+            .unzip();
+        duplicate_check.report(
+            &files,
+            &datetimes,
+            &output_dir.join(format!("duplicate_deployments{output_suffix}")),
+        )?;
+    }
+
+    if review_view {
+        let df_untagged = df_flatten
+            .clone()
+            .lazy()
+            .filter(col(TagType::Species.col_name()).eq(lit("")))
+            .select([col(PATH_COLUMN), col(DATETIME_COLUMN)])
+            .collect()?;
+        let mut views = vec![
+            ReviewView {
+                name: "Species",
+                df: df_count_species,
+            },
+            ReviewView {
+                name: "Deployments",
+                df: df_quality,
+            },
+            ReviewView {
+                name: "Untagged",
+                df: df_untagged,
+            },
+        ];
+        if let Some(df_errors) = df_errors {
+            views.push(ReviewView {
+                name: "Errors",
+                df: df_errors,
+            });
+        }
+        review_observe(views, &output_dir, &output_suffix)?;
+    }
     Ok(())
 }
 
+// Quota group of one sampled row: label, rows kept and rows available in the group
+struct QuotaGroup {
+    label: String,
+    selected: u32,
+    available: u32,
+}
+
+// Deployment of every row, from the deployment column when there is one, rows without a
+// value fall back to the parent directory of the file
+pub(crate) fn deployment_values(df: &DataFrame) -> anyhow::Result<Vec<String>> {
+    let column = df
+        .column("deployment")
+        .ok()
+        .map(|column| column.str().cloned())
+        .transpose()?;
+    let mut num_fallback = 0;
+    let deployments = df
+        .column(PATH_COLUMN)?
+        .str()?
+        .iter()
+        .enumerate()
+        .map(
+            |(row, path)| match column.as_ref().and_then(|column| column.get(row)) {
+                Some(deployment) if !deployment.is_empty() => deployment.to_string(),
+                _ => {
+                    num_fallback += 1;
+                    Path::new(path.unwrap_or_default())
+                        .parent()
+                        .and_then(|parent| parent.file_name())
+                        .map(|name| name.to_string_lossy().into_owned())
+                        .unwrap_or_default()
+                }
+            },
+        )
+        .collect();
+    if num_fallback > 0 {
+        println!(
+            "Note: {num_fallback} record(s) without deployment, using the parent directory of the file"
+        );
+    }
+    Ok(deployments)
+}
+
+// Sample at most `limit` rows per species, spread round-robin over deployments when balancing.
+// Returns the kept row indices (in input order) with the quota group of each.
+fn sample_species_quota(
+    df: &DataFrame,
+    limit: usize,
+    balance_by: Option<BalanceBy>,
+    seed: u64,
+) -> anyhow::Result<(Vec<IdxSize>, Vec<QuotaGroup>)> {
+    let species: Vec<&str> = df
+        .column(TagType::Species.col_name())?
+        .str()?
+        .iter()
+        .map(|value| value.unwrap_or_default())
+        .collect();
+    let deployments: Vec<String> = match balance_by {
+        None => vec![String::new(); df.height()],
+        Some(BalanceBy::Deployment) => deployment_values(df)?,
+    };
+
+    // species -> deployment -> row indices
+    let mut groups: BTreeMap<&str, BTreeMap<&str, Vec<IdxSize>>> = BTreeMap::new();
+    for (row, (species, deployment)) in species.iter().zip(&deployments).enumerate() {
+        groups
+            .entry(species)
+            .or_default()
+            .entry(deployment.as_str())
+            .or_default()
+            .push(row as IdxSize);
+    }
+
+    let mut kept: Vec<(IdxSize, usize)> = Vec::new();
+    let mut quota_groups: Vec<QuotaGroup> = Vec::new();
+    for (species, by_deployment) in groups {
+        let mut queues: Vec<(&str, Vec<IdxSize>)> = by_deployment
+            .into_iter()
+            .map(|(deployment, mut rows)| {
+                seeded_shuffle(&mut rows, seed);
+                (deployment, rows)
+            })
+            .collect();
+        let mut taken: Vec<Vec<IdxSize>> = vec![Vec::new(); queues.len()];
+        let mut num_taken = 0;
+        // One row from each deployment in turn until the quota is met or all are exhausted
+        while num_taken < limit && queues.iter().any(|(_, rows)| !rows.is_empty()) {
+            for (queue, taken) in queues.iter_mut().zip(taken.iter_mut()) {
+                if num_taken == limit {
+                    break;
+                }
+                if let Some(row) = queue.1.pop() {
+                    taken.push(row);
+                    num_taken += 1;
+                }
+            }
+        }
+        for ((deployment, remaining), taken) in queues.into_iter().zip(taken) {
+            let label = if balance_by.is_some() {
+                format!("{species}|{deployment}")
+            } else {
+                species.to_string()
+            };
+            let group_index = quota_groups.len();
+            quota_groups.push(QuotaGroup {
+                label,
+                selected: taken.len() as u32,
+                available: (taken.len() + remaining.len()) as u32,
+            });
+            kept.extend(taken.into_iter().map(|row| (row, group_index)));
+        }
+    }
+    kept.sort_unstable();
+
+    println!("Sampled {} of {} records:", kept.len(), df.height());
+    for group in &quota_groups {
+        println!("  {}: {}/{}", group.label, group.selected, group.available);
+    }
+    let rows = kept.iter().map(|(row, _)| *row).collect();
+    let row_groups = kept
+        .into_iter()
+        .map(|(_, group_index)| {
+            let group = &quota_groups[group_index];
+            QuotaGroup {
+                label: group.label.clone(),
+                selected: group.selected,
+                available: group.available,
+            }
+        })
+        .collect();
+    Ok((rows, row_groups))
+}
+
+// Deepest directory holding every file of the path column
+fn common_parent_dir(df: &DataFrame) -> anyhow::Result<Option<PathBuf>> {
+    Ok(df
+        .column(PATH_COLUMN)?
+        .str()?
+        .iter()
+        .flatten()
+        .filter_map(|path| Path::new(path).parent().map(Path::to_path_buf))
+        .reduce(|common, parent| {
+            common
+                .ancestors()
+                .find(|ancestor| parent.starts_with(ancestor))
+                .map(Path::to_path_buf)
+                .unwrap_or_default()
+        }))
+}
+
+/// Missing source files of an extraction, written instead of failing mid-copy
+pub const MISSING_SOURCES_FILE: &str = "missing_sources.csv";
+/// Videos extracted without their XMP sidecar under --require-sidecar
+pub const MISSING_SIDECARS_FILE: &str = "missing_sidecars.csv";
+
+/// What extract --require-sidecar does with videos whose tags have no sidecar to travel in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RequireSidecar {
+    /// Copy them anyway and list them in missing_sidecars.csv
+    Report,
+    /// Copy nothing while any is missing (--strict)
+    Strict,
+}
+
+// (sidecar, media) of every record, media paired by Observe --pair-media used instead of
+// deriving it from the sidecar path
+fn record_paths(df: &DataFrame) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+    let paired_media_paths: Vec<Option<PathBuf>> = if let Ok(column) = df.column(MEDIA_PATH_COLUMN)
+    {
+        column
+            .str()?
+            .iter()
+            .map(|media| media.filter(|media| !media.is_empty()).map(PathBuf::from))
+            .collect()
+    } else {
+        vec![None; df.height()]
+    };
+    Ok(df
+        .column(PATH_COLUMN)?
+        .str()?
+        .iter()
+        .zip(paired_media_paths)
+        .map(|(path, paired_media)| {
+            let input_path = &PathBuf::from(path.unwrap_or_default());
+            match media_path_for(input_path) {
+                Some(media) => (input_path.to_path_buf(), paired_media.unwrap_or(media)),
+                None => (sidecar_path_for(input_path), input_path.to_path_buf()),
+            }
+        })
+        .collect())
+}
+
+// Print the file count and size an extraction implies, per value of breakdown_column, and
+// drop the records whose media is gone. Returns the kept records and their media sizes
+fn estimate_extraction(
+    df: DataFrame,
+    output_dir: &Path,
+    breakdown_column: Option<&str>,
+    max_size: Option<u64>,
+) -> anyhow::Result<(DataFrame, Vec<u64>)> {
+    let sizes: Vec<Option<u64>> = record_paths(&df)?
+        .par_iter()
+        .map(|(_, media)| with_io_permit(|| fs::metadata(media).ok().map(|meta| meta.len())))
+        .collect();
+    let present =
+        BooleanChunked::from_iter_values("present".into(), sizes.iter().map(Option::is_some));
+    let num_missing = sizes.iter().filter(|size| size.is_none()).count();
+    if num_missing > 0 {
+        let mut df_missing = df.filter(&!&present)?.select([PATH_COLUMN])?;
+        df_missing.with_column(Column::new(
+            MEDIA_PATH_COLUMN.into(),
+            record_paths(&df_missing)?
+                .into_iter()
+                .map(|(_, media)| media.to_string_lossy().into_owned())
+                .collect::<Vec<_>>(),
+        ))?;
+        fs::create_dir_all(output_dir)?;
+        let missing_path = output_dir.join(MISSING_SOURCES_FILE);
+        let mut file = std::fs::File::create(&missing_path)?;
+        csv_writer(&mut file).finish(&mut df_missing)?;
+        if num_missing == df.height() {
+            return Err(anyhow::anyhow!(
+                "None of the {num_missing} matched source file(s) exist, see {}",
+                missing_path.display()
+            ));
+        }
+        println!(
+            "Warning: {num_missing} source file(s) missing, not extracted, see {}",
+            missing_path.display()
+        );
+    }
+    let df = df.filter(&present)?;
+    let sizes: Vec<u64> = sizes.into_iter().flatten().collect();
+    let total: u64 = sizes.iter().sum();
+    println!("{} file(s) to extract, {}", sizes.len(), format_size(total));
+    if let Some(column) = breakdown_column {
+        let values = df.column(column)?.cast(&DataType::String)?;
+        let mut breakdown: BTreeMap<&str, (usize, u64)> = BTreeMap::new();
+        for (value, size) in values.str()?.iter().zip(&sizes) {
+            let entry = breakdown
+                .entry(
+                    value
+                        .filter(|value| !value.is_empty())
+                        .unwrap_or(UNKNOWN_GROUP),
+                )
+                .or_default();
+            entry.0 += 1;
+            entry.1 += size;
+        }
+        for (value, (count, size)) in breakdown {
+            println!("  {value}: {count} file(s), {}", format_size(size));
+        }
+    }
+    if let Some(max_size) = max_size
+        && total > max_size
+    {
+        return Err(anyhow::anyhow!(
+            "Extraction needs {}, above --max-size {}, nothing copied",
+            format_size(total),
+            format_size(max_size)
+        ));
+    }
+    Ok((df, sizes))
+}
+
+// Videos only have their tags in the sidecar, the ones without are listed before copying
+fn check_video_sidecars(
+    df: &DataFrame,
+    output_dir: &Path,
+    require_sidecar: RequireSidecar,
+) -> anyhow::Result<()> {
+    let missing: Vec<String> = record_paths(df)?
+        .into_iter()
+        .filter(|(sidecar, media)| {
+            resource_extension(media).is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.as_str()))
+                && !sidecar.exists()
+        })
+        .map(|(_, media)| media.to_string_lossy().into_owned())
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let num_missing = missing.len();
+    fs::create_dir_all(output_dir)?;
+    let missing_path = output_dir.join(MISSING_SIDECARS_FILE);
+    let mut df_missing = DataFrame::new(
+        num_missing,
+        vec![Column::new(MEDIA_PATH_COLUMN.into(), missing)],
+    )?;
+    let mut file = std::fs::File::create(&missing_path)?;
+    csv_writer(&mut file).finish(&mut df_missing)?;
+    match require_sidecar {
+        RequireSidecar::Strict => Err(anyhow::anyhow!(
+            "{num_missing} video(s) without an XMP sidecar (--require-sidecar --strict), nothing copied, see {}",
+            missing_path.display()
+        )),
+        RequireSidecar::Report => {
+            println!(
+                "Error: {num_missing} video(s) without an XMP sidecar, copied without their tags, see {}",
+                missing_path.display()
+            );
+            Ok(())
+        }
+    }
+}
+
 pub fn extract_resources(
     filter_value: String,
     filter_type: ExtractFilterType,
-    rename: bool,
-    skip_existing: bool,
     csv_path: PathBuf,
     output_dir: PathBuf,
-    use_subdir: bool,
-    subdir_value: SubdirType,
+    options: ExtractOptions,
 ) -> anyhow::Result<()> {
+    let ExtractOptions {
+        custom_column,
+        rename,
+        skip_existing,
+        use_subdir,
+        subdir_value,
+        group_by,
+        column_map,
+        multivalue_separator,
+        per_species_limit,
+        balance_by,
+        seed,
+        require_mtime,
+        require_sidecar,
+        review,
+        whole_event,
+        events_from,
+        max_size,
+        keep_relative_to,
+        include_indeterminate,
+        replay,
+        keep_level,
+    } = options;
+    let answers_path = output_dir.join(EXTRACT_REPLAY_FILE);
     // Use subdir for default output_dir in case of overwrite
     let output_dir = if output_dir.ends_with("serval_extract") {
         let current_time = Local::now().format("%Y%m%d%H%M%S").to_string();
@@ -962,18 +3278,67 @@ pub fn extract_resources(
     } else {
         output_dir
     };
-
-    let df = CsvReadOptions::default()
-        .with_infer_schema_length(Some(0)) // parse all columns as string
-        .with_ignore_errors(true)
-        .with_parse_options(
-            CsvParseOptions::default()
-                .with_try_parse_dates(true)
-                .with_missing_is_null(true),
-        )
-        .try_into_reader_with_file_path(Some(csv_path))?
-        .finish()?;
-    reject_duplicate_csv_columns(&df)?;
+
+    check_csv_columns(&csv_path, &[PATH_COLUMN], &column_map)?;
+    let mut df = if is_parquet(&csv_path) {
+        read_parquet_as_text(&csv_path)?
+    } else {
+        CsvReadOptions::default()
+            .with_infer_schema_length(Some(0)) // parse all columns as string
+            .with_ignore_errors(true)
+            .with_parse_options(
+                CsvParseOptions::default()
+                    .with_try_parse_dates(true)
+                    .with_missing_is_null(true),
+            )
+            .try_into_reader_with_file_path(Some(csv_path.clone()))?
+            .finish()?
+    };
+    reject_duplicate_csv_columns(&df)?;
+    column_map.apply(&mut df)?;
+    reject_anonymized(&df, &csv_path)?;
+    // The custom filter reads another column instead, e.g. an observe --extra-tag one
+    if let Some(custom_column) = &custom_column {
+        if !matches!(
+            filter_type,
+            ExtractFilterType::Custom | ExtractFilterType::Advanced
+        ) {
+            return Err(anyhow::anyhow!(
+                "--custom-column is only read by the custom filter (and custom: in advanced filters)"
+            ));
+        }
+        let column = df.column(custom_column).map_err(|_| {
+            anyhow::anyhow!(
+                "No {custom_column} column in {} for --custom-column",
+                csv_path.display()
+            )
+        })?;
+        let values = column.clone().with_name(CUSTOM_COLUMN.into());
+        df.with_column(values)?;
+    }
+    if let Some(separator) = &multivalue_separator {
+        df = explode_multivalue_cells(df, separator)?;
+    }
+    // Relative paths of the CSV resolve against the working directory of this run
+    let resolver = PathResolver::new()?;
+    // Resolved over the whole CSV, the filter may leave a single deployment
+    let keep_base = match keep_relative_to {
+        Some(KeepRelativeTo::Auto) => {
+            let common = common_parent_dir(&normalize_path_column(df.clone(), PATH_COLUMN)?)?
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "No paths in {} for --keep-relative-to auto",
+                        csv_path.display()
+                    )
+                })?;
+            Some(resolver.absolute(common))
+        }
+        // Compared with the directories of the paths, whose separators are normalized
+        Some(KeepRelativeTo::Dir(dir)) => {
+            Some(resolver.absolute(PathBuf::from(normalize_path_str(&dir.to_string_lossy()))))
+        }
+        None => None,
+    };
     // Create default values for missing columns
     // TODO: https://github.com/pola-rs/polars/issues/18372, wait for polars ergonomic improve
     let required_columns = [
@@ -981,6 +3346,8 @@ pub fn extract_resources(
         TagType::Individual.col_name(),
         "rating",
         "custom",
+        PICK_LABEL_COLUMN,
+        COLOR_LABEL_COLUMN,
     ];
 
     let missing_columns = required_columns
@@ -1006,14 +3373,50 @@ pub fn extract_resources(
     }
     let df = df_lazy.collect()?;
 
-    let filter_expr = if filter_value == "ALL_VALUES" {
+    let filter_expr = if let Some(independence_csv) = &events_from {
+        println!(
+            "Selecting the events of the independent records in {}",
+            independence_csv.display()
+        );
+        event_records(&df, independence_csv)?.into_series().lit()
+    } else if let Some(review) = &review {
+        if review.is_empty() {
+            return Err(anyhow::anyhow!(
+                "No review filter set, add review.max_rating, review.custom or review.species with serval config set"
+            ));
+        }
+        println!("Selecting records needing review ({})", review.describe());
+        review.flag_expr(&df)
+    } else if filter_value == "ALL_VALUES" {
         match filter_type {
+            ExtractFilterType::Species if !include_indeterminate => {
+                let species_col = TagType::Species.col_name();
+                let indeterminate = indeterminate_counts(&df, species_col)?;
+                if !indeterminate.is_empty() {
+                    println!(
+                        "Skipping indeterminate records: {}, use --include-indeterminate to extract them",
+                        describe_indeterminate_counts(&indeterminate)
+                    );
+                }
+                col(species_col)
+                    .is_not_null()
+                    .and(IndeterminateLabels::current().expr(species_col).not())
+            }
             ExtractFilterType::Species => col(TagType::Species.col_name()).is_not_null(),
             ExtractFilterType::Path => col("path").is_not_null(),
             ExtractFilterType::Individual => col(TagType::Individual.col_name()).is_not_null(),
             ExtractFilterType::Rating => col("rating").is_not_null(),
             ExtractFilterType::Event => col("event_id").is_not_null(),
+            ExtractFilterType::Custom if custom_column.is_some() => col(CUSTOM_COLUMN)
+                .is_not_null()
+                .and(col(CUSTOM_COLUMN).neq(lit(""))),
             ExtractFilterType::Custom => col("custom").is_not_null(),
+            ExtractFilterType::PickLabel => col(PICK_LABEL_COLUMN)
+                .is_not_null()
+                .and(col(PICK_LABEL_COLUMN).neq(lit(""))),
+            ExtractFilterType::ColorLabel => col(COLOR_LABEL_COLUMN)
+                .is_not_null()
+                .and(col(COLOR_LABEL_COLUMN).neq(lit(""))),
             ExtractFilterType::Advanced => {
                 return Err(anyhow::anyhow!(
                     "Advanced filter requires a specific filter expression, not 'ALL_VALUES'"
@@ -1054,7 +3457,24 @@ pub fn extract_resources(
                 }
             }
             ExtractFilterType::Event => col("event_id").eq(lit(filter_value.clone())),
+            // Several tags of an --extra-tag category are joined in one cell
+            ExtractFilterType::Custom if custom_column.is_some() => col(CUSTOM_COLUMN)
+                .str()
+                .split(lit(CsvDialect::current().multi_value_separator()))
+                .list()
+                .contains(lit(filter_value.clone()), false),
             ExtractFilterType::Custom => col("custom").eq(lit(filter_value.clone())),
+            // Labels accept names in any case or digiKam indices
+            ExtractFilterType::PickLabel => {
+                col(PICK_LABEL_COLUMN).eq(lit(
+                    label_name(PICK_LABELS, &filter_value).unwrap_or(&filter_value)
+                ))
+            }
+            ExtractFilterType::ColorLabel => {
+                col(COLOR_LABEL_COLUMN).eq(lit(
+                    label_name(COLOR_LABELS, &filter_value).unwrap_or(&filter_value)
+                ))
+            }
             ExtractFilterType::Advanced => {
                 // Parse the advanced filter expression
                 let advanced_expr = parse_advanced_filter(&filter_value)?;
@@ -1073,6 +3493,8 @@ pub fn extract_resources(
                             col(TagType::Individual.col_name()).drop_nulls().unique(),
                             col("rating").first(), // Rating is scalar per path
                             col("custom").first(), // Custom is scalar per path
+                            col(PICK_LABEL_COLUMN).first(),
+                            col(COLOR_LABEL_COLUMN).first(),
                         ])
                         .collect()?;
 
@@ -1097,15 +3519,86 @@ pub fn extract_resources(
         }
     };
 
-    let df_filtered = df.lazy().filter(filter_expr).collect()?;
+    let mut df_filtered = df.clone().lazy().filter(filter_expr).collect()?;
+    // Every record sharing an event with a matched one, as in the events CSV of capture --event
+    if whole_event {
+        let event_ids = df_filtered
+            .column(EVENT_ID_COLUMN)
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "--whole-event needs an {EVENT_ID_COLUMN} column, extract from the events CSV of serval capture --event or a tags.csv of observe --event-gap"
+                )
+            })?
+            .as_materialized_series()
+            .drop_nulls()
+            .unique()?;
+        let num_matched = df_filtered.height();
+        df_filtered = df
+            .lazy()
+            .filter(col(EVENT_ID_COLUMN).is_in(lit(event_ids), false))
+            .collect()?;
+        println!(
+            "Expanded {num_matched} matching record(s) to {} record(s) of their events",
+            df_filtered.height()
+        );
+    }
 
     // Check if any records match the filter
     if df_filtered.height() == 0 {
         return Err(anyhow::anyhow!("No records found matching the filter."));
     }
+    if let Some(archive_path) = df_filtered
+        .column(PATH_COLUMN)?
+        .str()?
+        .iter()
+        .flatten()
+        .find(|path| is_archive_entry_path(path))
+    {
+        return Err(anyhow::anyhow!(
+            "Cannot extract files inside a ZIP archive ({archive_path}), unpack the archive and run observe on the unpacked directory first"
+        ));
+    }
 
     println!("Found {} matching records", df_filtered.height());
 
+    // Sample before the keep-level prompt so the path sample comes from the final set
+    let (df_filtered, quota_groups) = match per_species_limit {
+        Some(limit) => {
+            let (rows, quota_groups) = sample_species_quota(&df_filtered, limit, balance_by, seed)?;
+            let rows = IdxCa::from_vec("rows".into(), rows);
+            (df_filtered.take(&rows)?, Some(quota_groups))
+        }
+        None => (df_filtered, None),
+    };
+    // Backslash paths from Windows, so ancestors/strip_prefix see the directories
+    let mut df_filtered = normalize_path_column(df_filtered, PATH_COLUMN)?;
+    if df_filtered.get_column_index(MEDIA_PATH_COLUMN).is_some() {
+        df_filtered = normalize_path_column(df_filtered, MEDIA_PATH_COLUMN)?;
+    }
+
+    // Extracted copies inside the source tree would be read again by the next observe
+    let source_root = common_parent_dir(&df_filtered)?;
+    if let Some(source_root) = source_root
+        && source_root.parent().is_some()
+        && is_inside_dir(&source_root, &output_dir)
+    {
+        println!(
+            "Warning: output directory {} is inside the source tree {}, later observe/align runs on that tree will pick up the extracted copies",
+            output_dir.display(),
+            source_root.display()
+        );
+    }
+
+    // Read-only estimate before the prompt and the copies, missing sources are set aside
+    let breakdown_column = group_by
+        .map(GroupBy::col_name)
+        .or(use_subdir.then(|| subdir_value.col_name()));
+    let (df_filtered, media_sizes) =
+        estimate_extraction(df_filtered, &output_dir, breakdown_column, max_size)?;
+    if let Some(require_sidecar) = require_sidecar {
+        check_video_sidecars(&df_filtered, &output_dir, require_sidecar)?;
+    }
+
     // Get the top level directory (to keep)
     let path_sample = df_filtered
         .column("path")?
@@ -1113,29 +3606,69 @@ pub fn extract_resources(
         .get(0)
         .ok_or_else(|| anyhow::anyhow!("Missing path value in the first filtered record"))?
         .to_string();
-    println!("Here is a sample of the file path ({path_sample}): ");
-    let mut num_option = 0;
-    println!("0): File Only (no directory)");
-    for (i, entry) in absolute_path(Path::new(&path_sample).to_path_buf())?
+    let ancestors: Vec<PathBuf> = resolver
+        .absolute(PathBuf::from(&path_sample))
         .parent()
         .unwrap()
         .ancestors()
-        .enumerate()
-    {
-        println!("{}): {}", i + 1, entry.to_string_lossy());
-        num_option += 1;
-    }
-
-    let mut rl = Editor::new()?;
-    let h = NumericSelectValidator {
-        min: 0,
-        max: num_option,
+        .filter(|entry| !entry.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .collect();
+    let num_option = ancestors.len() as i32;
+    let keep_level = match &keep_base {
+        Some(keep_base) => Some(
+            ancestors
+                .iter()
+                .position(|ancestor| ancestor == keep_base)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "--keep-relative-to {} is not a directory above {path_sample}",
+                        keep_base.display()
+                    )
+                })?,
+        ),
+        None => keep_level,
+    };
+    let mut answers = None;
+    let keep_level = match keep_level {
+        Some(keep_level) => Some(keep_level),
+        None => {
+            let replayed =
+                PromptAnswers::new(answers_path, replay.as_deref(), &mut Prompt::new()?)?;
+            let keep_level = replayed
+                .get_i64("keep_level")
+                .filter(|keep_level| (0..=num_option as i64).contains(keep_level))
+                .map(|keep_level| keep_level as usize);
+            answers = Some(replayed);
+            keep_level
+        }
+    };
+    let deploy_path_index = match keep_level {
+        Some(keep_level) if keep_level > ancestors.len() => {
+            return Err(anyhow::anyhow!(
+                "Keep level {keep_level} is above the {num_option} directories of {path_sample}"
+            ));
+        }
+        Some(keep_level) => keep_level,
+        None => {
+            println!("Here is a sample of the file path ({path_sample}): ");
+            println!("0): File Only (no directory)");
+            for (i, entry) in ancestors.iter().enumerate() {
+                println!("{}): {}", i + 1, entry.to_string_lossy());
+            }
+            // Keeping the file's own directory avoids name clashes across deployments
+            let keep_level = Prompt::new()?.select(
+                "Select the top level directory to keep",
+                0,
+                num_option,
+                1.min(num_option),
+            )?;
+            if let Some(answers) = answers.as_mut() {
+                answers.record("keep_level", keep_level)?;
+            }
+            keep_level as usize
+        }
     };
-    rl.set_helper(Some(h));
-    let readline = rl.readline("Select the top level directory to keep: ");
-    let deploy_path_index = readline?.trim().parse::<usize>()?;
-    let pb = ProgressBar::new(df_filtered["path"].len().try_into()?);
-    configure_progress_bar(&pb);
 
     let paths = df_filtered.column("path")?.str()?;
     // Remove dot from tags, as it causes issues when cross-platform
@@ -1156,12 +3689,52 @@ pub fn extract_resources(
         .str()?
         .replace_all(r"\.", "")?;
 
-    for (path, species_tag, individual_tag, rating_tag, custom_tag) in izip!(
-        paths.iter(),
+    // Stable join key for the manifest, prefer file_key over filename whenever present
+    let file_keys: Vec<String> = if let Ok(column) = df_filtered.column(FILE_KEY_COLUMN) {
+        column
+            .str()?
+            .iter()
+            .map(|key| key.unwrap_or_default().to_string())
+            .collect()
+    } else if let Ok(column) = df_filtered.column(FILENAME_COLUMN) {
+        column
+            .str()?
+            .iter()
+            .map(|filename| filename.unwrap_or_default().to_string())
+            .collect()
+    } else {
+        paths
+            .iter()
+            .map(|path| {
+                Path::new(path.unwrap_or_default())
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            })
+            .collect()
+    };
+    let input_paths = record_paths(&df_filtered)?;
+    let pb =
+        ServalProgress::new_bytes(media_sizes.iter().sum(), input_paths.len() as u64, "copied");
+
+    let mut manifest_paths: Vec<String> = Vec::new();
+    let mut manifest_keys: Vec<String> = Vec::new();
+    let mut manifest_outputs: Vec<String> = Vec::new();
+    let mut manifest_renamed: Vec<bool> = Vec::new();
+    let mut manifest_groups: Vec<&QuotaGroup> = Vec::new();
+    let mut manifest_group_dirs: Vec<&str> = Vec::new();
+    let mut manifest_sidecar_copied: Vec<bool> = Vec::new();
+    let mut num_videos_without_sidecar = 0;
+
+    // (sidecar, media) copy of every record before collisions are resolved
+    let mut output_paths: Vec<(PathBuf, PathBuf)> = Vec::with_capacity(input_paths.len());
+    let mut group_dirs: Vec<&str> = Vec::with_capacity(input_paths.len());
+    for (species_tag, individual_tag, rating_tag, custom_tag, (input_path_xmp, input_path_media)) in izip!(
         species_tags.iter(),
         individual_tags.iter(),
         rating_tags.iter(),
-        custom_tags.iter()
+        custom_tags.iter(),
+        input_paths.iter()
     ) {
         let subdir = if use_subdir {
             match subdir_value {
@@ -1173,19 +3746,24 @@ pub fn extract_resources(
         } else {
             ""
         };
-        let input_path_xmp: String;
-        let input_path_media: String;
-        if path.unwrap().ends_with(".xmp") {
-            input_path_xmp = path.unwrap().to_string();
-            input_path_media = path.unwrap().strip_suffix(".xmp").unwrap().to_string();
-        } else {
-            input_path_xmp = path.unwrap().to_string() + ".xmp";
-            input_path_media = path.unwrap().to_string();
-        }
-
-        let (mut output_path_xmp, mut output_path_media) = if deploy_path_index == 0 {
-            let relative_path_output_xmp = Path::new(&input_path_xmp).file_name().unwrap();
-            let relative_path_output_media = Path::new(&input_path_media).file_name().unwrap();
+        // One directory level above the kept structure, whatever the subdir and rename options
+        let group_dir = group_by.map(|group_by| {
+            match group_by {
+                GroupBy::Species => species_tag,
+                GroupBy::Individual => individual_tag,
+                GroupBy::Rating => rating_tag,
+            }
+            .filter(|value| !value.is_empty())
+            .unwrap_or(UNKNOWN_GROUP)
+        });
+        group_dirs.push(group_dir.unwrap_or_default());
+        let output_root = match group_dir {
+            Some(group_dir) => output_dir.join(group_dir),
+            None => output_dir.clone(),
+        };
+        let output_path = if deploy_path_index == 0 {
+            let relative_path_output_xmp = input_path_xmp.file_name().unwrap();
+            let relative_path_output_media = input_path_media.file_name().unwrap();
             if rename {
                 let filename_prefix = format!(
                     "{}-{}-",
@@ -1193,12 +3771,12 @@ pub fn extract_resources(
                     individual_tag.unwrap_or("untagged_individual")
                 );
                 (
-                    output_dir.join(subdir).join(format!(
+                    output_root.join(subdir).join(format!(
                         "{}{}",
                         filename_prefix,
                         relative_path_output_xmp.to_string_lossy()
                     )),
-                    output_dir.join(subdir).join(format!(
+                    output_root.join(subdir).join(format!(
                         "{}{}",
                         filename_prefix,
                         relative_path_output_media.to_string_lossy()
@@ -1206,23 +3784,27 @@ pub fn extract_resources(
                 )
             } else {
                 (
-                    output_dir.join(subdir).join(relative_path_output_xmp),
-                    output_dir.join(subdir).join(relative_path_output_media),
+                    output_root.join(subdir).join(relative_path_output_xmp),
+                    output_root.join(subdir).join(relative_path_output_media),
                 )
             }
         } else {
-            let path_strip = Path::new(&input_path_media)
+            // Kept levels count from the absolute path, as in the prompt, relative paths too
+            let absolute_media = resolver.absolute(input_path_media.clone());
+            let path_strip = absolute_media
                 .ancestors()
                 .nth(deploy_path_index + 1)
                 .ok_or_else(|| {
                     anyhow::anyhow!(
                         "Failed to determine the preserved directory prefix for {}",
-                        input_path_media
+                        input_path_media.display()
                     )
                 })?;
-            let relative_path_output_xmp = Path::new(&input_path_xmp).strip_prefix(path_strip)?;
-            let relative_path_output_media =
-                Path::new(&input_path_media).strip_prefix(path_strip)?;
+            let relative_path_output_xmp = resolver
+                .absolute(input_path_xmp.clone())
+                .strip_prefix(path_strip)?
+                .to_path_buf();
+            let relative_path_output_media = absolute_media.strip_prefix(path_strip)?;
             if rename {
                 let filename_prefix = format!(
                     "{}-{}-",
@@ -1230,7 +3812,7 @@ pub fn extract_resources(
                     individual_tag.unwrap_or("unknown_individual")
                 );
                 (
-                    output_dir
+                    output_root
                         .join(relative_path_output_xmp.parent().unwrap())
                         .join(subdir)
                         .join(format!(
@@ -1241,7 +3823,7 @@ pub fn extract_resources(
                                 .unwrap()
                                 .to_string_lossy()
                         )),
-                    output_dir
+                    output_root
                         .join(relative_path_output_media.parent().unwrap())
                         .join(subdir)
                         .join(format!(
@@ -1255,101 +3837,566 @@ pub fn extract_resources(
                 )
             } else {
                 (
-                    output_dir
+                    output_root
                         .join(relative_path_output_xmp.parent().unwrap())
                         .join(subdir)
                         .join(relative_path_output_xmp.file_name().unwrap()),
-                    output_dir
+                    output_root
                         .join(relative_path_output_media.parent().unwrap())
                         .join(subdir)
                         .join(relative_path_output_media.file_name().unwrap()),
                 )
             }
         };
+        output_paths.push(output_path);
+    }
 
-        pb.println(format!(
-            "Copying to {}",
-            output_path_media.to_string_lossy()
-        ));
+    // Copies already there are left alone with --skip-existing, otherwise their names are
+    // taken and colliding copies get suffixed, numbered in the order of the source paths
+    let skipped: Vec<bool> = output_paths
+        .iter()
+        .map(|(_, output_path_media)| skip_existing && output_path_media.exists())
+        .collect();
+    let planned: Vec<(String, PathBuf)> = izip!(&input_paths, &output_paths, &skipped)
+        .filter(|(_, _, skipped)| !**skipped)
+        .map(|((_, input_path_media), (_, output_path_media), _)| {
+            (
+                input_path_media.to_string_lossy().into_owned(),
+                output_path_media.clone(),
+            )
+        })
+        .collect();
+    let mut planned_media = plan_collision_suffixes(&planned, Path::exists).into_iter();
+
+    for (
+        file_key,
+        (input_path_xmp, input_path_media),
+        (mut output_path_xmp, mut output_path_media),
+        skipped,
+        media_size,
+        row,
+    ) in izip!(
+        file_keys.iter(),
+        input_paths,
+        output_paths,
+        skipped,
+        media_sizes,
+        0..
+    ) {
+        // Stopped on Ctrl-C, the manifest still lists what was copied
+        if crate::lock::is_interrupted() {
+            break;
+        }
         fs::create_dir_all(output_path_media.parent().unwrap())?;
-        if skip_existing && output_path_media.exists() {
-            pb.println(format!(
-                "Skipping existing {}",
-                output_path_media.to_string_lossy()
-            ));
-            pb.inc(1);
+        if skipped {
+            pb.notice(
+                "Skipped existing",
+                format!("Skipping existing {}", output_path_media.to_string_lossy()),
+            );
+            pb.inc_file(media_size);
             continue;
         }
-        // check if the file exists, if so, rename it
-        if output_path_media.exists() {
-            let mut i = 1;
-            let mut output_path_media_renamed = output_path_media.clone();
-            while output_path_media_renamed.exists() {
-                output_path_media_renamed = output_path_media.with_file_name(format!(
-                    "{}_{}.{}",
-                    output_path_media.file_stem().unwrap().to_string_lossy(),
-                    i,
-                    output_path_media.extension().unwrap().to_string_lossy()
-                ));
-                i += 1;
-            }
-            // get the xmp file from output_path_media_renamed
-            let output_path_xmp_renamed =
-                output_path_media_renamed.to_string_lossy().into_owned() + ".xmp";
-            pb.println(format!(
-                "Renamed to {}",
-                output_path_media_renamed.to_string_lossy()
-            ));
-            output_path_media = output_path_media_renamed.clone();
-            output_path_xmp = output_path_xmp_renamed.into();
+        let final_path_media = planned_media.next().ok_or_else(|| {
+            anyhow::anyhow!("No output planned for {}", input_path_media.display())
+        })?;
+        let renamed = final_path_media != output_path_media;
+        if renamed {
+            pb.notice(
+                "Renamed on collision",
+                format!("Renamed to {}", final_path_media.to_string_lossy()),
+            );
+            output_path_xmp = sidecar_path_for(&final_path_media);
+            output_path_media = final_path_media;
         }
 
+        pb.notice(
+            "Copied",
+            format!("Copying to {}", output_path_media.to_string_lossy()),
+        );
         fs::copy(input_path_media.clone(), output_path_media.clone())?;
         if let Err(err) = fs::copy(&input_path_xmp, &output_path_xmp) {
             if err.kind() == std::io::ErrorKind::NotFound {
-                pb.println("Missing XMP file, tag info for certain video files may be lost.");
+                pb.notice(
+                    "Missing XMP (tag info for certain video files may be lost)",
+                    format!("Missing XMP file: {}", input_path_xmp.display()),
+                );
             } else {
                 return Err(anyhow::anyhow!("Failed to copy XMP file: {err}"));
             }
         }
-        sync_modified_time(input_path_media.into(), output_path_media)?;
+        if !sync_modified_time(input_path_media.clone(), output_path_media.clone())? {
+            pb.notice(
+                "Warning: could not preserve mtime on files",
+                unpreserved_mtime(&output_path_media, require_mtime)?,
+            );
+        }
+        manifest_paths.push(input_path_media.to_string_lossy().into_owned());
+        manifest_keys.push(file_key.clone());
+        manifest_outputs.push(output_path_media.to_string_lossy().into_owned());
+        manifest_renamed.push(renamed);
+        // Checked on the output, a video copied without it has lost its tags
+        let sidecar_copied = output_path_xmp.exists();
+        if !sidecar_copied
+            && resource_extension(&output_path_media)
+                .is_some_and(|ext| VIDEO_EXTENSIONS.contains(&ext.as_str()))
+        {
+            num_videos_without_sidecar += 1;
+        }
+        manifest_sidecar_copied.push(sidecar_copied);
+        if let Some(quota_groups) = &quota_groups {
+            manifest_groups.push(&quota_groups[row]);
+        }
+        manifest_group_dirs.push(group_dirs[row]);
 
-        pb.inc(1);
+        pb.inc_file(media_size);
     }
     pb.finish_with_message("done");
+    if require_sidecar.is_some() && num_videos_without_sidecar > 0 {
+        println!(
+            "Warning: {num_videos_without_sidecar} copied video(s) have no sidecar next to them in {}",
+            output_dir.display()
+        );
+    }
+
+    let mut manifest_columns = vec![
+        Column::new(PATH_COLUMN.into(), manifest_paths),
+        Column::new(FILE_KEY_COLUMN.into(), manifest_keys),
+        Column::new("output_path".into(), manifest_outputs),
+        Column::new("renamed_on_collision".into(), manifest_renamed),
+        Column::new("sidecar_copied".into(), manifest_sidecar_copied),
+    ];
+    if quota_groups.is_some() {
+        manifest_columns.extend([
+            Column::new(
+                "quota_group".into(),
+                manifest_groups
+                    .iter()
+                    .map(|group| group.label.as_str())
+                    .collect::<Vec<_>>(),
+            ),
+            Column::new(
+                "group_selected".into(),
+                manifest_groups
+                    .iter()
+                    .map(|group| group.selected)
+                    .collect::<Vec<_>>(),
+            ),
+            Column::new(
+                "group_available".into(),
+                manifest_groups
+                    .iter()
+                    .map(|group| group.available)
+                    .collect::<Vec<_>>(),
+            ),
+        ]);
+    }
+    if let Some(group_by) = group_by {
+        manifest_columns.push(Column::new(
+            format!("group_{}", group_by.col_name()).into(),
+            manifest_group_dirs,
+        ));
+    }
+    let mut df_manifest = DataFrame::new(manifest_columns[0].len(), manifest_columns)?;
+    let manifest_path = output_dir.join("manifest.csv");
+    let mut file = std::fs::File::create(&manifest_path)?;
+    csv_writer(&mut file).finish(&mut df_manifest)?;
+    println!("Saved manifest to {}", manifest_path.to_string_lossy());
+    crate::lock::check_interrupted()
+}
+
+// Candidate independence windows in minutes, also the upper edges of the gap bins
+const GAP_WINDOWS: [i64; 7] = [1, 5, 15, 30, 60, 120, 360];
+
+// Rows of each indeterminate label in `column`, most frequent first
+fn indeterminate_counts(df: &DataFrame, column: &str) -> PolarsResult<Vec<(String, IdxSize)>> {
+    let counts = df
+        .clone()
+        .lazy()
+        .filter(IndeterminateLabels::current().expr(column))
+        .group_by([col(column).cast(DataType::String)])
+        .agg([len().alias("count")])
+        .sort(
+            ["count", column],
+            SortMultipleOptions::default().with_order_descending_multi([true, false]),
+        )
+        .collect()?;
+    let labels = counts.column(column)?.str()?;
+    let num_rows = counts.column("count")?.as_materialized_series().idx()?;
+    Ok(labels
+        .iter()
+        .zip(num_rows.iter())
+        .filter_map(|(label, count)| Some((label?.to_string(), count?)))
+        .collect())
+}
+
+// e.g. "15 (Unidentified 12, Unknown 3)"
+fn describe_indeterminate_counts(counts: &[(String, IdxSize)]) -> String {
+    format!(
+        "{} ({})",
+        counts.iter().map(|(_, count)| count).sum::<IdxSize>(),
+        counts
+            .iter()
+            .map(|(label, count)| format!("{label} {count}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+// Indeterminate records are excluded like the other tags, but how many there were tells how
+// much of the data the counts leave out
+fn report_excluded_indeterminate(
+    df_deployment: &DataFrame,
+    target: TagType,
+    exclude_expr: &Expr,
+) -> PolarsResult<()> {
+    if target != TagType::Species {
+        return Ok(());
+    }
+    let df_excluded = df_deployment
+        .clone()
+        .lazy()
+        .drop_nulls(None)
+        .filter(exclude_expr.clone())
+        .collect()?;
+    let counts = indeterminate_counts(&df_excluded, target.col_name())?;
+    if !counts.is_empty() {
+        println!(
+            "Excluded indeterminate records: {}",
+            describe_indeterminate_counts(&counts)
+        );
+    }
     Ok(())
 }
 
+// LIR: Last Independent Record, LR: Last Record
+fn independence_mode_name(compare_to_last_record: bool) -> &'static str {
+    if compare_to_last_record { "LR" } else { "LIR" }
+}
+
+// Suffix of the files of one capture run, e.g. _species_30m_LIR.csv
+fn capture_output_suffix(
+    target: TagType,
+    min_delta_time: i32,
+    compare_to_last_record: bool,
+) -> String {
+    format!(
+        "_{}_{}m_{}.csv",
+        target.to_string().to_lowercase(),
+        min_delta_time,
+        independence_mode_name(compare_to_last_record)
+    )
+}
+
+fn comparison_label(min_delta_time: i32, compare_to_last_record: bool) -> String {
+    format!(
+        "{min_delta_time}m_{}",
+        independence_mode_name(compare_to_last_record)
+    )
+}
+
+// Independent counts of each target value per run, with the change from the first run
+fn capture_comparison(
+    runs: &[(i32, bool)],
+    run_counts: &BTreeMap<String, Vec<u32>>,
+    target: TagType,
+) -> anyhow::Result<DataFrame> {
+    let mut rows: Vec<(&str, Vec<u32>)> = run_counts
+        .iter()
+        .map(|(value, counts)| (value.as_str(), counts.clone()))
+        .collect();
+    let totals = (0..runs.len())
+        .map(|run| run_counts.values().map(|counts| counts[run]).sum())
+        .collect();
+    rows.push(("TOTAL", totals));
+
+    let mut columns = vec![Column::new(
+        target.col_name().into(),
+        rows.iter().map(|(value, _)| *value).collect::<Vec<_>>(),
+    )];
+    for (run, &(min_delta_time, compare_to_last_record)) in runs.iter().enumerate() {
+        columns.push(Column::new(
+            comparison_label(min_delta_time, compare_to_last_record).into(),
+            rows.iter()
+                .map(|(_, counts)| counts[run])
+                .collect::<Vec<_>>(),
+        ));
+    }
+    for (run, &(min_delta_time, compare_to_last_record)) in runs.iter().enumerate().skip(1) {
+        let change: Vec<Option<f64>> = rows
+            .iter()
+            .map(|(_, counts)| {
+                let reference = f64::from(counts[0]);
+                (counts[0] > 0).then(|| {
+                    ((f64::from(counts[run]) - reference) * 10000.0 / reference).round() / 100.0
+                })
+            })
+            .collect();
+        columns.push(Column::new(
+            format!(
+                "{}_change_percent",
+                comparison_label(min_delta_time, compare_to_last_record)
+            )
+            .into(),
+            change,
+        ));
+    }
+    Ok(DataFrame::new(rows.len(), columns)?)
+}
+
+fn units_per_minute(time_unit: TimeUnit) -> i64 {
+    match time_unit {
+        TimeUnit::Milliseconds => 60_000,
+        TimeUnit::Microseconds => 60_000_000,
+        TimeUnit::Nanoseconds => 60_000_000_000,
+    }
+}
+
+/// Flag the records at least `min_delta_time` minutes after the last independent record of
+/// the same target at the same deployment, as capture does by default (LIR).
+///
+/// `df_sorted` is sorted by deployment, target and time, as in get_temporal_independence.
+/// Columns are read chunk by chunk with typed accessors, so memory stays at one flag per row.
+pub fn last_independent_records(
+    df_sorted: &DataFrame,
+    target: TagType,
+    min_delta_time: i32,
+) -> anyhow::Result<BooleanChunked> {
+    let time = df_sorted.column("time")?.datetime().map_err(|_| {
+        anyhow::anyhow!("Capture needs datetimes, check the datetime column parses")
+    })?;
+    let window = i64::from(min_delta_time) * units_per_minute(time.time_unit());
+    let deployments = df_sorted.column("deployment")?.str()?;
+    let targets = df_sorted.column(target.col_name())?.str()?;
+
+    let mut independent = Vec::with_capacity(df_sorted.height());
+    let mut last_independent: Option<(Option<&str>, Option<&str>, i64)> = None;
+    for (deployment, target, time) in
+        izip!(deployments.iter(), targets.iter(), time.physical().iter())
+    {
+        let time = time.ok_or_else(|| anyhow::anyhow!("Record without time in {deployment:?}"))?;
+        let is_independent = match last_independent {
+            Some((last_deployment, last_target, last_time)) => {
+                deployment != last_deployment || target != last_target || time - last_time >= window
+            }
+            None => true,
+        };
+        if is_independent {
+            last_independent = Some((deployment, target, time));
+        }
+        independent.push(is_independent);
+    }
+    Ok(BooleanChunked::from_slice(
+        "independent".into(),
+        &independent,
+    ))
+}
+
+// Gaps between consecutive records of each target at a deployment (df_sorted of
+// get_temporal_independence), binned by GAP_WINDOWS. Each bin also gives the share of the
+// records kept with its upper edge as window, comparing as the run does.
+fn gap_histogram(
+    df_sorted: &DataFrame,
+    target: TagType,
+    compare_to_last_record: bool,
+) -> anyhow::Result<DataFrame> {
+    let time = df_sorted.column("time")?.datetime().map_err(|_| {
+        anyhow::anyhow!("--gap-histogram needs datetimes, check the datetime column parses")
+    })?;
+    let ms_per_minute = units_per_minute(time.time_unit());
+    let times = time.physical();
+    let deployments = df_sorted.column("deployment")?.str()?;
+    let targets = df_sorted.column(target.col_name())?.str()?;
+
+    // Times of each (deployment, target) run of rows, df_sorted keeping them contiguous
+    let mut groups: Vec<(&str, Vec<i64>)> = Vec::new();
+    let mut current: Option<(&str, &str)> = None;
+    for ((deployment, target), time) in deployments.iter().zip(targets.iter()).zip(times.iter()) {
+        let (Some(deployment), Some(target), Some(time)) = (deployment, target, time) else {
+            continue;
+        };
+        if current != Some((deployment, target)) {
+            groups.push((target, Vec::new()));
+            current = Some((deployment, target));
+        }
+        if let Some((_, group)) = groups.last_mut() {
+            group.push(time);
+        }
+    }
+
+    // target -> (gaps per bin, records, records kept per window)
+    let mut stats: BTreeMap<&str, ([u32; GAP_WINDOWS.len() + 1], u32, [u32; GAP_WINDOWS.len()])> =
+        BTreeMap::new();
+    for (key, group) in &groups {
+        let (bins, records, kept) = stats.entry(key).or_default();
+        *records += group.len() as u32;
+        for pair in group.windows(2) {
+            let gap = pair[1] - pair[0];
+            let bin = GAP_WINDOWS
+                .iter()
+                .position(|window| gap < window * ms_per_minute)
+                .unwrap_or(GAP_WINDOWS.len());
+            bins[bin] += 1;
+        }
+        for (window, kept) in GAP_WINDOWS.iter().zip(kept.iter_mut()) {
+            let window = window * ms_per_minute;
+            let mut last = group[0];
+            *kept += 1;
+            for (previous, time) in group.iter().zip(&group[1..]) {
+                let reference = if compare_to_last_record {
+                    *previous
+                } else {
+                    last
+                };
+                if time - reference >= window {
+                    *kept += 1;
+                    last = *time;
+                }
+            }
+        }
+    }
+
+    let mut names: Vec<&str> = Vec::new();
+    let mut bin_labels: Vec<String> = Vec::new();
+    let mut counts: Vec<u32> = Vec::new();
+    let mut windows: Vec<Option<i64>> = Vec::new();
+    let mut retained: Vec<Option<f64>> = Vec::new();
+    for (name, (bins, records, kept)) in &stats {
+        for (bin, count) in bins.iter().enumerate() {
+            names.push(name);
+            bin_labels.push(match bin {
+                0 => format!("0-{}", GAP_WINDOWS[0]),
+                _ if bin == GAP_WINDOWS.len() => format!("{}+", GAP_WINDOWS[bin - 1]),
+                _ => format!("{}-{}", GAP_WINDOWS[bin - 1], GAP_WINDOWS[bin]),
+            });
+            counts.push(*count);
+            windows.push(GAP_WINDOWS.get(bin).copied());
+            retained.push(
+                kept.get(bin)
+                    .map(|kept| (*kept as f64 * 10000.0 / *records as f64).round() / 100.0),
+            );
+        }
+    }
+    Ok(DataFrame::new(
+        names.len(),
+        vec![
+            Column::new(target.col_name().into(), names),
+            Column::new("gap_minutes".into(), bin_labels),
+            Column::new("count".into(), counts),
+            Column::new("window_minutes".into(), windows),
+            Column::new("retained_percent".into(), retained),
+        ],
+    )?)
+}
+
 pub fn get_temporal_independence(
     csv_path: PathBuf,
     output_dir: PathBuf,
-    event: bool,
-    no_exclude: bool,
-    camtrap_dp: bool,
+    options: CaptureOptions,
 ) -> anyhow::Result<()> {
+    let CaptureOptions {
+        event,
+        no_exclude,
+        camtrap_dp,
+        accumulation,
+        per_deployment,
+        deploy_table,
+        overlap,
+        demographics,
+        geojson,
+        no_format,
+        derive_dateparts,
+        carry_columns,
+        gap_histogram,
+        compare,
+        anonymize_paths,
+        exclude_tags,
+        review,
+        include_review,
+        on_conflict,
+        column_map,
+        multivalue_separator,
+        replay,
+        settings,
+    } = options;
     // Temporal independence analysis
+    if demographics && camtrap_dp {
+        return Err(anyhow::anyhow!(
+            "--demographics reads sex from tags.csv and is not supported with --camtrap-dp"
+        ));
+    }
+    if !carry_columns.is_empty() && camtrap_dp {
+        return Err(anyhow::anyhow!(
+            "--carry-columns reads tags.csv columns and is not supported with --camtrap-dp"
+        ));
+    }
+    if let Some(column) = carry_columns.iter().find(|column| {
+        [
+            TagType::Species.col_name(),
+            DEPLOYMENT_COLUMN,
+            "count",
+            "percent",
+        ]
+        .contains(&column.as_str())
+    }) {
+        return Err(anyhow::anyhow!(
+            "--carry-columns {column} is already a column of the count outputs"
+        ));
+    }
+    // These join the outputs back to the real paths and deployments
+    if anonymize_paths && (deploy_table.is_some() || geojson || demographics) {
+        return Err(anyhow::anyhow!(
+            "--anonymize-paths is not supported with --deploy-table, --geojson or --demographics"
+        ));
+    }
+
+    let mut params = RunParams::new("capture");
+    // Shared params don't tell where the input lives either
+    let input = if anonymize_paths {
+        csv_path.file_name().unwrap_or_default().to_string_lossy()
+    } else {
+        csv_path.to_string_lossy()
+    };
+    params.set("input", input.as_ref());
+    params.set("anonymize_paths", anonymize_paths);
+    params.set("camtrap_dp", camtrap_dp);
+    params.set("csv_dialect", CsvDialect::current().name());
+    if !carry_columns.is_empty() {
+        params.set("carry_columns", carry_columns.clone());
+    }
+    if let Some(separator) = &multivalue_separator {
+        params.set("multivalue_separator", separator.name());
+    }
 
     let mut read_opts = CsvReadOptions::default().with_ignore_errors(false);
     if camtrap_dp {
         read_opts = read_opts
-            .with_columns(csv_projection_columns(&[
-                "observationID",
-                DEPLOYMENT_ID_COLUMN,
-                "eventStart",
-                "scientificName",
-                "individualID",
-            ]))
+            .with_columns(csv_projection_columns(CAMTRAP_DP_COLUMNS))
             .with_parse_options(CsvParseOptions::default());
     } else {
         read_opts =
             read_opts.with_parse_options(CsvParseOptions::default().with_try_parse_dates(true));
     }
-    let mut df = match read_opts
-        .try_into_reader_with_file_path(Some(csv_path))
-        .and_then(|reader| reader.finish())
-    {
-        Ok(df) => {
+    if !camtrap_dp {
+        let required: Vec<&str> = [PATH_COLUMN, DATETIME_COLUMN]
+            .into_iter()
+            .chain(carry_columns.iter().map(String::as_str))
+            .collect();
+        check_csv_columns(&csv_path, &required, &column_map)?;
+    }
+    // A Parquet tags table from observe --format keeps its datetime dtype
+    let read = if is_parquet(&csv_path) && !camtrap_dp {
+        std::fs::File::open(&csv_path)
+            .map_err(PolarsError::from)
+            .and_then(|file| ParquetReader::new(file).finish())
+    } else {
+        read_opts
+            .try_into_reader_with_file_path(Some(csv_path))
+            .and_then(|reader| reader.finish())
+    };
+    let mut df = match read {
+        Ok(mut df) => {
             reject_duplicate_csv_columns(&df)?;
+            column_map.apply(&mut df)?;
             if camtrap_dp {
                 let event_col = df.column("eventStart")?;
                 if event_col.null_count() > 0 {
@@ -1372,6 +4419,9 @@ pub fn get_temporal_independence(
                         Hint: Ensure the datetime format in your file matches the pattern 'yyyy-MM-dd HH:mm:ss'."
                     ));
                 }
+                if let Some(separator) = &multivalue_separator {
+                    df = explode_multivalue_cells(df, separator)?;
+                }
             }
             df
         }
@@ -1390,67 +4440,79 @@ pub fn get_temporal_independence(
         }
     };
 
-    // Readlines for parameter setup
-    let mut rl = Editor::new()?;
-    rl.bind_sequence(
-        Event::Any,
-        EventHandler::Conditional(Box::new(NumericFilteringHandler)), // Force numerical input
-    );
-    // Read min_delta_time
-    let readline = rl.readline(
-        "Input the Minimum Time Difference (when considering records as independent) in minutes (e.g. 30): ");
-    let min_delta_time: i32 = readline?
-        .trim()
-        .parse()
-        .map_err(|_| anyhow::anyhow!("Invalid input: please enter a valid number"))?;
-    if min_delta_time <= 0 {
-        return Err(anyhow::anyhow!(
-            "Invalid time difference: must be greater than 0"
-        ));
-    }
-    if min_delta_time > 10080 {
-        // 1 week
-        println!("Note: {min_delta_time} minutes is unusually large (> 1 week)",);
-    }
-    // Read delta_time_compared_to
-    let h = NumericSelectValidator { min: 1, max: 2 };
-    rl.set_helper(Some(h));
-    let readline = rl.readline(
-        "\nThe Minimum Time Difference should be compared with?\n1) Last independent record 2) Last record\nEnter a selection (e.g. 1): ");
-    let delta_time_compared_to = match readline?.trim().parse()? {
-        1 => "LastIndependentRecord",
-        2 => "LastRecord",
-        _ => "LastIndependentRecord",
-    };
-    // Get target (species/individual)
-    let h = NumericSelectValidator { min: 1, max: 2 };
-    rl.set_helper(Some(h));
-    let readline =
-        rl.readline("\nPerform analysis on\n1) species 2) individual\nEnter a selection: ");
-    let target = match readline?.trim().parse()? {
-        1 => TagType::Species,
-        2 => TagType::Individual,
-        _ => TagType::Species,
-    };
-    // Find deployment
-    let deploy_path_index = if camtrap_dp {
+    // One row per species, the columns are read off its first record
+    let carried = if carry_columns.is_empty() {
         None
     } else {
-        let path_sample = df
-            .column("path")?
-            .str()?
-            .get(0)
-            .ok_or_else(|| anyhow::anyhow!("Missing path value in the first record"))?
-            .to_string();
-        Some(prompt_deployment_path_index(&mut rl, path_sample)?)
+        Some(
+            df.clone()
+                .lazy()
+                .select(
+                    std::iter::once(TagType::Species.col_name())
+                        .chain(carry_columns.iter().map(String::as_str))
+                        .map(col)
+                        .collect::<Vec<_>>(),
+                )
+                .filter(col(TagType::Species.col_name()).is_not_null())
+                .unique_stable(
+                    Some(cols([TagType::Species.col_name()])),
+                    UniqueKeepStrategy::First,
+                )
+                .collect()?,
+        )
     };
 
+    // Uncertain identifications stay out of the analysis, see serval extract --review-only
+    if !review.is_empty() && !include_review {
+        if camtrap_dp {
+            println!(
+                "Note: review filter not applied, camtrap-dp observations have no rating or custom tags"
+            );
+        } else {
+            let num_records = df.height();
+            *df = df
+                .clone()
+                .lazy()
+                .filter(review.flag_expr(df).not())
+                .collect()?;
+            println!(
+                "Excluded {} record(s) needing review ({}), use --include-review to keep them",
+                num_records - df.height(),
+                review.describe()
+            );
+        }
+    }
+
+    let CaptureSettings {
+        min_delta_time,
+        compare_to_last_record,
+        target,
+        deploy_path_index,
+    } = match settings {
+        Some(settings) => settings,
+        None => {
+            let mut answers = PromptAnswers::new(
+                output_dir.join(CAPTURE_REPLAY_FILE),
+                replay.as_deref(),
+                &mut Prompt::new()?,
+            )?;
+            CaptureSettings::prompt(df, camtrap_dp, compare.as_ref(), &mut answers)?
+        }
+    };
+    if carried.is_some() && target != TagType::Species {
+        return Err(anyhow::anyhow!(
+            "--carry-columns follows the species, not supported for {} captures",
+            target.col_name()
+        ));
+    }
+    // The deployment column written by observe is preferred over a path level
+    let deploy_path_index = deploy_path_index.filter(|_| df.column(DEPLOYMENT_COLUMN).is_err());
     let mut exclude_expr = lit(false);
-    for tag in DEFAULT_EXCLUDE_TAGS {
+    for tag in &exclude_tags {
         let tag_expr = if tag.is_empty() {
             col(target.col_name()).eq(lit(""))
         } else {
-            col(target.col_name()).str().starts_with(lit(*tag))
+            col(target.col_name()).str().starts_with(lit(tag.as_str()))
         };
         exclude_expr = exclude_expr.or(tag_expr);
     }
@@ -1503,6 +4565,29 @@ pub fn get_temporal_independence(
             ));
         }
         df_deployment
+    } else if df.column(DEPLOYMENT_COLUMN).is_ok() {
+        // Written by observe --deploy-level/--deploy-table, empty where the path had none
+        let df_deployment = df
+            .clone()
+            .lazy()
+            .select([
+                col(PATH_COLUMN).alias(id_col_name),
+                when(col(DEPLOYMENT_COLUMN).cast(DataType::String).eq(lit("")))
+                    .then(lit(NULL).cast(DataType::String))
+                    .otherwise(col(DEPLOYMENT_COLUMN).cast(DataType::String))
+                    .alias("deployment"),
+                col(DATETIME_COLUMN).alias("time"),
+                col(target.col_name()),
+            ])
+            .collect()?;
+        let num_without_deployment = df_deployment.column("deployment")?.null_count();
+        if num_without_deployment > 0 {
+            println!(
+                "Warning: {num_without_deployment} of {} input record(s) have no {DEPLOYMENT_COLUMN} and are left out",
+                df_deployment.height()
+            );
+        }
+        df_deployment
     } else {
         let deploy_path_index = deploy_path_index
             .ok_or_else(|| anyhow::anyhow!("Missing deployment path index selection"))?;
@@ -1517,6 +4602,28 @@ pub fn get_temporal_independence(
             .collect()?
     };
 
+    // Every output below is written from df_deployment, paths and deployments included
+    let mut anonymizer = anonymize_paths.then(Anonymizer::new).transpose()?;
+    let df_deployment = match anonymizer.as_mut() {
+        Some(anonymizer) => anonymizer.anonymize(&df_deployment)?,
+        None => df_deployment,
+    };
+
+    // Times read with their offsets (observe --timezone keep) are compared and written as instants
+    let time_format = match df_deployment.column("time")?.dtype() {
+        DataType::Datetime(_, Some(_)) => "%Y-%m-%d %H:%M:%S%:z",
+        _ => "%Y-%m-%d %H:%M:%S",
+    };
+
+    // Rows dropped below for lack of a time, so the independent counts can be read honestly
+    let num_undated = df_deployment.column("time")?.null_count();
+    if num_undated > 0 {
+        println!(
+            "Warning: {num_undated} of {} input record(s) have no usable datetime and are left out",
+            df_deployment.height()
+        );
+    }
+
     let df_cleaned = if no_exclude {
         df_deployment
             .clone()
@@ -1532,6 +4639,7 @@ pub fn get_temporal_independence(
             )
             .collect()?
     } else {
+        report_excluded_indeterminate(&df_deployment, target, &exclude_expr)?;
         df_deployment
             .clone()
             .lazy()
@@ -1550,205 +4658,436 @@ pub fn get_temporal_independence(
 
     // The temporal pass relies on contiguous [deployment, target] groups and ascending time.
     // Keep the sort stable so exact duplicate keys preserve input order deterministically.
-    let mut df_sorted = df_cleaned.sort(
+    let df_sorted = df_cleaned.sort(
         ["deployment", target.col_name(), "time"],
         SortMultipleOptions::default().with_maintain_order(true),
     )?;
+    // (min_delta_time, compare_to_last_record) of each run over df_sorted, the first one is
+    // the reference of --compare and writes the count files
+    let runs = match &compare {
+        Some(compare) => compare.runs(compare_to_last_record),
+        None => vec![(min_delta_time, compare_to_last_record)],
+    };
+    // The histogram covers every window, so only the target and comparison name it
+    let gap_histogram_filename = |compare_to_last_record: bool| {
+        format!(
+            "gap_histogram_{}_{}.csv",
+            target.to_string().to_lowercase(),
+            independence_mode_name(compare_to_last_record)
+        )
+    };
+    let comparison_filename = format!(
+        "capture_comparison_{}.csv",
+        target.to_string().to_lowercase()
+    );
+    let mut output_files = vec![
+        "count_by_deployment.csv".to_string(),
+        "count_all.csv".to_string(),
+    ];
+    for &(min_delta_time, compare_to_last_record) in &runs {
+        let output_suffix = capture_output_suffix(target, min_delta_time, compare_to_last_record);
+        output_files.push(format!("params{}", output_suffix.replace(".csv", ".toml")));
+        output_files.push(format!("temporal-independence{output_suffix}"));
+        if gap_histogram {
+            output_files.push(gap_histogram_filename(compare_to_last_record));
+        }
+    }
+    if compare.is_some() {
+        output_files.push(comparison_filename.clone());
+    }
+    if anonymize_paths {
+        output_files.push(keyfile_name(".csv"));
+    }
+    let output_dir = versioned_output_dir(&output_dir, &output_files, on_conflict)?;
+    fs::create_dir_all(output_dir.clone())?;
 
-    let mut df_capture_independent;
-    if delta_time_compared_to == "LastRecord" {
-        df_capture_independent = df_sorted
-            .clone()
-            .lazy()
-            .rolling(
-                col("time"),
-                [col("deployment"), col(target.col_name())],
-                RollingGroupOptions {
-                    period: Duration::parse(format!("{min_delta_time}m").as_str()),
-                    offset: Duration::parse(format!("-{min_delta_time}m").as_str()),
-                    closed_window: ClosedWindow::Right,
-                    ..Default::default()
-                },
-            )
-            .agg([
-                col(target.col_name()).count().alias("count"),
-                col(id_col_name).last(),
-            ])
-            .filter(col("count").eq(lit(1)))
-            .select([
-                col("deployment"),
-                col(id_col_name),
-                col("time"),
-                col(target.col_name()),
-            ])
-            .collect()?;
-        println!("{df_capture_independent}");
-    } else {
-        if df_sorted.height() == 0 {
-            return Err(anyhow::anyhow!(
-                "No records remain after filtering empty/default tags."
-            ));
+    // target value -> independent count of each run
+    let mut run_counts: BTreeMap<String, Vec<u32>> = BTreeMap::new();
+    let mut gap_histogram_modes: Vec<bool> = Vec::new();
+    for (run, &(min_delta_time, compare_to_last_record)) in runs.iter().enumerate() {
+        let delta_time_compared_to = if compare_to_last_record {
+            "LastRecord"
+        } else {
+            "LastIndependentRecord"
+        };
+        if compare.is_some() {
+            println!(
+                "\nRun {} of {}: {min_delta_time} minutes, compared to {delta_time_compared_to}",
+                run + 1,
+                runs.len()
+            );
         }
-        df_sorted.align_chunks_par();
-        let columns = [
-            df_sorted.column("time")?,
-            df_sorted.column(target.col_name())?,
-            df_sorted.column("deployment")?,
-        ];
-        let mut iters = columns
-            .iter()
-            .map(|s| s.as_materialized_series().iter())
-            .collect::<Vec<_>>();
-
-        let mut capture = Vec::new();
-        for _row in 0..df_sorted.height() {
-            for iter in &mut iters {
-                let value = iter.next().expect("should have as many iterations as rows");
-                capture.push(value);
-            }
+        if min_delta_time > 10080 {
+            // 1 week
+            println!("Note: {min_delta_time} minutes is unusually large (> 1 week)",);
         }
-        let capture_time: Vec<&AnyValue<'_>> = capture.iter().step_by(3).collect();
-        let capture_species: Vec<&AnyValue<'_>> = capture.iter().skip(1).step_by(3).collect();
-        let capture_deployment: Vec<&AnyValue<'_>> = capture.iter().skip(2).step_by(3).collect();
-
-        // Get temporal independent records
-        let mut capture_independent = Vec::new();
-        let mut last_indep_time = capture_time[0].to_string();
-        let mut last_indep_species = capture_species[0].to_string();
-        let mut last_indep_deployment = capture_deployment[0].to_string();
-        for i in 0..capture_time.len() {
-            let time = capture_time[i].to_string();
-            let species = capture_species[i].to_string();
-            let deployment = capture_deployment[i].to_string();
-
-            if i == 0
-                || species != last_indep_species
-                || deployment != last_indep_deployment
-                || is_temporal_independent(last_indep_time.clone(), time, min_delta_time)?
-            {
-                capture_independent.push(true);
-                last_indep_time = capture_time[i].to_string();
-                last_indep_species = capture_species[i].to_string();
-                last_indep_deployment = capture_deployment[i].to_string();
+        // Taken before the independence filter, to see what each window would keep
+        let df_gap_histogram =
+            if gap_histogram && !gap_histogram_modes.contains(&compare_to_last_record) {
+                gap_histogram_modes.push(compare_to_last_record);
+                Some(self::gap_histogram(
+                    &df_sorted,
+                    target,
+                    compare_to_last_record,
+                )?)
             } else {
-                capture_independent.push(false);
+                None
+            };
+
+        let df_capture_independent;
+        if compare_to_last_record {
+            df_capture_independent = df_sorted
+                .clone()
+                .lazy()
+                .rolling(
+                    col("time"),
+                    [col("deployment"), col(target.col_name())],
+                    RollingGroupOptions {
+                        period: Duration::parse(format!("{min_delta_time}m").as_str()),
+                        offset: Duration::parse(format!("-{min_delta_time}m").as_str()),
+                        closed_window: ClosedWindow::Right,
+                        ..Default::default()
+                    },
+                )
+                .agg([
+                    col(target.col_name()).count().alias("count"),
+                    col(id_col_name).last(),
+                ])
+                .filter(col("count").eq(lit(1)))
+                .select([
+                    col("deployment"),
+                    col(id_col_name),
+                    col("time"),
+                    col(target.col_name()),
+                ])
+                .collect()?;
+            println!("{df_capture_independent}");
+        } else {
+            if df_sorted.height() == 0 {
+                return Err(anyhow::anyhow!(
+                    "No records remain after filtering empty/default tags."
+                ));
             }
+            let independent = last_independent_records(&df_sorted, target, min_delta_time)?;
+            df_capture_independent = df_sorted.filter(&independent)?;
+            println!("{df_capture_independent}");
+        }
+        for value in df_capture_independent
+            .column(target.col_name())?
+            .str()?
+            .iter()
+            .flatten()
+        {
+            let counts = run_counts
+                .entry(value.to_string())
+                .or_insert_with(|| vec![0; runs.len()]);
+            counts[run] += 1;
         }
 
-        df_capture_independent = df_sorted
-            .lazy()
-            .filter(Series::new("independent".into(), capture_independent).lit())
-            .collect()?;
-        println!("{df_capture_independent}");
-    }
-
-    // Include parameters in the output filename, LIR: Last Independent Record, LR: Last Record
-    let output_suffix = format!(
-        "_{}_{}m_{}.csv",
-        target.to_string().to_lowercase(),
-        min_delta_time,
-        if delta_time_compared_to == "LastIndependentRecord" {
-            "LIR"
+        // Include parameters in the output filename, LIR: Last Independent Record, LR: Last Record
+        let output_suffix = capture_output_suffix(target, min_delta_time, compare_to_last_record);
+        let params_filename = format!("params{}", output_suffix.replace(".csv", ".toml"));
+        params.set("min_delta_time_minutes", min_delta_time as i64);
+        params.set("delta_time_compared_to", delta_time_compared_to);
+        params.set("target", target.col_name());
+        if let Some(deploy_path_index) = deploy_path_index {
+            params.set("deployment_path_index", deploy_path_index as i64);
+        } else if !camtrap_dp {
+            params.set("deployment_column", DEPLOYMENT_COLUMN);
+        }
+        params.set("no_exclude", no_exclude);
+        params.set("exclude", exclude_tags.clone());
+        params.set(
+            "indeterminate",
+            IndeterminateLabels::current().labels().to_vec(),
+        );
+        params.set("event", event);
+        params.set("accumulation", accumulation.is_some());
+        if let Some(accumulation_step) = accumulation {
+            params.set("accumulation_step", accumulation_step as i64);
+            params.set("per_deployment", per_deployment);
+        }
+        if let Some(deploy_table) = &deploy_table {
+            params.set("deploy_table", deploy_table.to_string_lossy().as_ref());
+        }
+        if let Some(species_pair) = &overlap {
+            params.set("overlap", species_pair.as_str());
+        }
+        review.set_params(&mut params);
+        params.set("include_review", include_review);
+        params.set("demographics", demographics);
+        params.set("geojson", geojson);
+        params.set("no_format", no_format);
+        params.set("derive_dateparts", derive_dateparts);
+        params.set("gap_histogram", gap_histogram);
+        if compare.is_some() {
+            params.set(
+                "compare",
+                runs.iter()
+                    .map(|&(min_delta_time, compare_to_last_record)| {
+                        comparison_label(min_delta_time, compare_to_last_record)
+                    })
+                    .collect::<Vec<_>>(),
+            );
+        }
+        params.write(&output_dir, &params_filename)?;
+        let filename = format!("temporal-independence{output_suffix}");
+        // The analyses below read the records without the date parts
+        let mut df_independent_written = if derive_dateparts {
+            with_dateparts(df_capture_independent.clone(), "time")?
         } else {
-            "LR"
-        },
-    );
-    fs::create_dir_all(output_dir.clone())?;
-    let filename = format!("temporal-independence{output_suffix}");
-    let mut file = std::fs::File::create(output_dir.join(filename.clone()))?;
-    CsvWriter::new(&mut file)
-        .include_bom(true)
-        .with_datetime_format(Some("%Y-%m-%d %H:%M:%S".into()))
-        .finish(&mut df_capture_independent)?;
-    println!("Saved to {}", output_dir.join(filename).to_string_lossy());
-
-    if event {
-        let df_events = df_capture_independent.with_row_index("event_id".into(), Some(1))?;
-        let by_columns = &[target.col_name(), "deployment"];
-        let df_raw_sorted = df_deployment.sort(
-            ["deployment", target.col_name(), "time"],
-            SortMultipleOptions::default().with_maintain_order(true),
-        )?;
-        let mut df_with_events = df_raw_sorted.join_asof_by(
-            &df_events,
-            "time",
-            "time",
-            by_columns,
-            by_columns,
-            AsofStrategy::Backward,
-            None,
-            true,
-            false, // Sortedness of columns cannot be checked when 'by' groups provided
-        )?;
-        df_with_events = df_with_events
-            .lazy()
-            .select([
-                col(id_col_name),
-                col("deployment"),
-                col("time"),
-                col(target.col_name()),
-                col("event_id"),
-            ])
-            .collect()?;
-        let filename = format!("events{output_suffix}");
+            df_capture_independent.clone()
+        };
         let mut file = std::fs::File::create(output_dir.join(filename.clone()))?;
-        CsvWriter::new(&mut file)
-            .include_bom(true)
-            .with_datetime_format(Some("%Y-%m-%d %H:%M:%S".into()))
-            .finish(&mut df_with_events.clone())?;
+        csv_writer(&mut file)
+            .with_datetime_format(csv_datetime_format(time_format))
+            .finish(&mut df_independent_written)?;
+        write_output_schema(
+            &output_dir.join(&filename),
+            OutputKind::TemporalIndependence,
+            &df_independent_written,
+        )?;
         println!("Saved to {}", output_dir.join(filename).to_string_lossy());
-    }
 
-    let mut df_count_independent = df_capture_independent
-        .clone()
-        .lazy()
-        .group_by_stable([col("deployment"), col(target.col_name())])
-        .agg([col(target.col_name()).count().alias("count")])
-        .collect()?;
-    println!("{df_count_independent}");
-
-    let filename = "count_by_deployment.csv";
-    let mut file = std::fs::File::create(output_dir.join(filename))?;
-    CsvWriter::new(&mut file)
-        .include_bom(true)
-        .with_datetime_format(Some("%Y-%m-%d %H:%M:%S".into()))
-        .finish(&mut df_count_independent)?;
-    println!("Saved to {}", output_dir.join(filename).to_string_lossy());
-
-    if target == TagType::Species {
-        let mut df_count_independent_species = df_capture_independent
+        if let Some(mut df_gap_histogram) = df_gap_histogram {
+            let path = output_dir.join(gap_histogram_filename(compare_to_last_record));
+            let mut file = std::fs::File::create(&path)?;
+            csv_writer(&mut file).finish(&mut df_gap_histogram)?;
+            println!("Saved gap histogram to {}", path.display());
+        }
+
+        if let Some(accumulation_step) = accumulation {
+            write_species_accumulation(
+                &df_deployment,
+                &df_capture_independent,
+                target,
+                deploy_table.clone(),
+                accumulation_step,
+                per_deployment,
+                &output_dir,
+                &output_suffix,
+            )?;
+        }
+        if let Some(species_pair) = &overlap {
+            write_activity_overlap(
+                &df_capture_independent,
+                target,
+                species_pair,
+                &output_dir,
+                &output_suffix,
+            )?;
+        }
+        if geojson {
+            write_geojson(
+                df,
+                &df_deployment,
+                &df_capture_independent,
+                id_col_name,
+                target,
+                deploy_table.as_deref(),
+                &output_dir,
+                &output_suffix,
+            )?;
+        }
+        if demographics {
+            write_demographics(
+                df,
+                &df_capture_independent,
+                target,
+                &output_dir,
+                &output_suffix,
+            )?;
+        }
+
+        if event {
+            let df_events = df_capture_independent.with_row_index("event_id".into(), Some(1))?;
+            let by_columns = &[target.col_name(), "deployment"];
+            let df_raw_sorted = df_deployment.sort(
+                ["deployment", target.col_name(), "time"],
+                SortMultipleOptions::default().with_maintain_order(true),
+            )?;
+            let mut df_with_events = df_raw_sorted.join_asof_by(
+                &df_events,
+                "time",
+                "time",
+                by_columns,
+                by_columns,
+                AsofStrategy::Backward,
+                None,
+                true,
+                false, // Sortedness of columns cannot be checked when 'by' groups provided
+            )?;
+            df_with_events = df_with_events
+                .lazy()
+                .select([
+                    col(id_col_name),
+                    col("deployment"),
+                    col("time"),
+                    col(target.col_name()),
+                    col("event_id"),
+                ])
+                .collect()?;
+            if derive_dateparts {
+                df_with_events = with_dateparts(df_with_events, "time")?;
+            }
+            let filename = format!("events{output_suffix}");
+            let mut file = std::fs::File::create(output_dir.join(filename.clone()))?;
+            csv_writer(&mut file)
+                .with_datetime_format(csv_datetime_format(time_format))
+                .finish(&mut df_with_events.clone())?;
+            write_output_schema(
+                &output_dir.join(&filename),
+                OutputKind::Events,
+                &df_with_events,
+            )?;
+            println!("Saved to {}", output_dir.join(filename).to_string_lossy());
+        }
+
+        // The count files have fixed names, the comparison covers the other runs
+        if run > 0 {
+            continue;
+        }
+        let mut df_count_independent = df_capture_independent
             .clone()
             .lazy()
-            .group_by_stable([col(TagType::Species.col_name())])
-            .agg([col(TagType::Species.col_name()).count().alias("count")])
+            .group_by_stable([col("deployment"), col(target.col_name())])
+            .agg([col(target.col_name()).count().alias("count")])
             .collect()?;
-        println!("{df_count_independent_species}");
+        if let Some(carried) = &carried {
+            df_count_independent = join_carried_columns(df_count_independent, carried)?;
+        }
+        println!("{df_count_independent}");
 
-        let filename = "count_all.csv";
+        let filename = "count_by_deployment.csv";
         let mut file = std::fs::File::create(output_dir.join(filename))?;
-        CsvWriter::new(&mut file)
-            .include_bom(true)
-            .with_datetime_format(Some("%Y-%m-%d %H:%M:%S".into()))
-            .finish(&mut df_count_independent_species)?;
+        csv_writer(&mut file)
+            .with_datetime_format(csv_datetime_format("%Y-%m-%d %H:%M:%S"))
+            .finish(&mut df_count_independent)?;
+        write_output_schema_carrying(
+            &output_dir.join(filename),
+            OutputKind::CountByDeployment,
+            &df_count_independent,
+            &carry_columns,
+        )?;
         println!("Saved to {}", output_dir.join(filename).to_string_lossy());
+
+        if target == TagType::Species {
+            let mut df_count_independent_species = df_capture_independent
+                .clone()
+                .lazy()
+                .group_by_stable([col(TagType::Species.col_name())])
+                .agg([col(TagType::Species.col_name()).count().alias("count")])
+                .collect()?;
+            if !no_format {
+                df_count_independent_species =
+                    format_species_counts(&df_count_independent_species)?;
+            }
+            if let Some(carried) = &carried {
+                df_count_independent_species =
+                    join_carried_columns(df_count_independent_species, carried)?;
+            }
+            println!("{df_count_independent_species}");
+
+            let filename = "count_all.csv";
+            let mut file = std::fs::File::create(output_dir.join(filename))?;
+            csv_writer(&mut file)
+                .with_datetime_format(csv_datetime_format("%Y-%m-%d %H:%M:%S"))
+                .finish(&mut df_count_independent_species)?;
+            write_output_schema_carrying(
+                &output_dir.join(filename),
+                OutputKind::CountAll,
+                &df_count_independent_species,
+                &carry_columns,
+            )?;
+            println!("Saved to {}", output_dir.join(filename).to_string_lossy());
+        }
+    }
+
+    if compare.is_some() {
+        let mut df_comparison = capture_comparison(&runs, &run_counts, target)?;
+        println!("{df_comparison}");
+        let path = output_dir.join(&comparison_filename);
+        let mut file = std::fs::File::create(&path)?;
+        csv_writer(&mut file).finish(&mut df_comparison)?;
+        println!("Saved comparison to {}", path.display());
+    }
+    if let Some(anonymizer) = &anonymizer {
+        anonymizer.write_keyfile(&output_dir.join(keyfile_name(".csv")))?;
     }
     Ok(())
 }
 
-fn update_xmp(
+// Columns of the species lookup after the counts, the (untagged) and TOTAL rows stay empty
+fn join_carried_columns(df: DataFrame, carried: &DataFrame) -> PolarsResult<DataFrame> {
+    let species = TagType::Species.col_name();
+    df.lazy()
+        .join(
+            carried.clone().lazy(),
+            [col(species)],
+            [col(species)],
+            JoinArgs {
+                maintain_order: MaintainOrderJoin::Left,
+                ..JoinArgs::new(JoinType::Left)
+            },
+        )
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_xmp(
     file_path: PathBuf,
     old_value: String,
     new_value: String,
     update_type: XmpUpdateType,
-    pb: &ProgressBar,
+    rating_update: Option<&str>,
+    tagger: Option<&str>,
+    verify_roundtrip: bool,
+    pb: &ServalProgress,
 ) -> anyhow::Result<()> {
-    let xmp_content = fs::read_to_string(&file_path)?;
+    let xmp_content = read_xmp_sidecar(&file_path)?;
     let mut xmp = XmpMeta::from_str_with_options(&xmp_content, FromStrOptions::default())
         .map_err(|e| anyhow::anyhow!("Failed to parse XMP: {e:?}"))?;
+    let original = verify_roundtrip.then(|| xmp.clone());
+
+    // The rating goes into the same write as the tag change
+    let mut changes = Vec::new();
+    if let Some(rating) = rating_update {
+        let old_rating = xmp
+            .property(xmp_ns::XMP, "Rating")
+            .map(|current| current.value)
+            .unwrap_or_default();
+        changes.push(AuditChange::new(
+            XmpUpdateType::Rating.col_name(),
+            old_rating,
+            rating,
+        ));
+        set_xmp_rating(&mut xmp, rating, pb)?;
+    }
+    if new_value.is_empty() {
+        stamp_tagger(&mut xmp, tagger)?;
+        return finalize_xmp_update(file_path, original.as_ref(), xmp, changes, pb);
+    }
+    changes.push(AuditChange::new(
+        update_type.col_name(),
+        old_value.as_str(),
+        new_value.as_str(),
+    ));
 
     if update_type == XmpUpdateType::Rating {
         update_xmp_rating(&file_path, &mut xmp, &old_value, &new_value, pb)?;
-        return finalize_xmp_update(file_path, xmp);
+        stamp_tagger(&mut xmp, tagger)?;
+        return finalize_xmp_update(file_path, original.as_ref(), xmp, changes, pb);
+    }
+    if let Some((property, labels)) = match update_type {
+        XmpUpdateType::PickLabel => Some((DIGIKAM_PICK_LABEL, PICK_LABELS)),
+        XmpUpdateType::ColorLabel => Some((DIGIKAM_COLOR_LABEL, COLOR_LABELS)),
+        _ => None,
+    } {
+        update_xmp_label(
+            &file_path, &mut xmp, property, labels, &old_value, &new_value, pb,
+        )?;
+        stamp_tagger(&mut xmp, tagger)?;
+        return finalize_xmp_update(file_path, original.as_ref(), xmp, changes, pb);
     }
 
     let tag_type = update_type
@@ -1800,8 +5139,61 @@ fn update_xmp(
         Ok(match_count)
     }
 
-    if old_value.is_empty() {
-        pb.println(format!("Inserting new {tag_type} tag: {new_value}"));
+    fn remove_tag_array(
+        xmp: &mut XmpMeta,
+        ns: &str,
+        array_name: &str,
+        tag: &str,
+    ) -> anyhow::Result<usize> {
+        let mut match_count = 0;
+        // Backwards, so deleting doesn't shift the items still to check
+        for i in (1..=xmp.array_len(ns, array_name)).rev() {
+            if xmp
+                .property(ns, &format!("{array_name}[{i}]"))
+                .is_some_and(|prop| prop.value == tag)
+            {
+                xmp.delete_array_item(ns, array_name, i as i32)
+                    .map_err(|e| {
+                        anyhow::anyhow!("Failed to delete tag {i} in {array_name}: {e:?}")
+                    })?;
+                match_count += 1;
+            }
+        }
+        Ok(match_count)
+    }
+
+    if new_value == DELETE_TAG_VALUE {
+        if old_value.is_empty() {
+            return Err(anyhow::anyhow!(
+                "{DELETE_TAG_VALUE} in {} needs the {tag_type} tag to delete",
+                file_path.display()
+            ));
+        }
+        pb.notice(
+            "Deleted tags",
+            format!("Deleting {tag_type} tag: {old_value}"),
+        );
+        let adobe_tag = format!("{}{}", tag_type.adobe_tag_prefix(), old_value);
+        if remove_tag_array(&mut xmp, LIGHTROOM_NS, LR_HIERARCHICAL_SUBJECT, &adobe_tag)? == 0 {
+            return Err(anyhow::anyhow!(
+                "Tag mismatch in {}: expected '{}' in {}",
+                file_path.display(),
+                adobe_tag,
+                LR_HIERARCHICAL_SUBJECT,
+            ));
+        }
+        remove_tag_array(
+            &mut xmp,
+            DIGIKAM_NS,
+            DIGIKAM_TAGSLIST,
+            &format!("{}{}", tag_type.digikam_tag_prefix(), old_value),
+        )?;
+        remove_tag_array(&mut xmp, xmp_ns::DC, "subject", &old_value)?;
+    } else if old_value.is_empty() {
+        pb.notice(
+            "Inserted tags",
+            format!("Inserting new {tag_type} tag: {new_value}"),
+        );
 
         let new_tag_adobe = format!("{}{}", tag_type.adobe_tag_prefix(), new_value);
         let new_tag_digikam = format!("{}{}", tag_type.digikam_tag_prefix(), new_value);
@@ -1815,9 +5207,10 @@ fn update_xmp(
         insert_tag(&mut xmp, DIGIKAM_NS, DIGIKAM_TAGSLIST, new_tag_digikam)?;
         insert_tag(&mut xmp, xmp_ns::DC, "subject", new_value.to_string())?;
     } else {
-        pb.println(format!(
-            "Updating {tag_type} tag from '{old_value}' to '{new_value}'"
-        ));
+        pb.notice(
+            "Updated tags",
+            format!("Updating {tag_type} tag from '{old_value}' to '{new_value}'"),
+        );
         // adobe hierarchical subject
         let adobe_matches = update_tag_array(
             &mut xmp,
@@ -1849,7 +5242,8 @@ fn update_xmp(
         update_tag_array(&mut xmp, xmp_ns::DC, "subject", &old_value, &new_value)?;
     }
 
-    finalize_xmp_update(file_path, xmp)
+    stamp_tagger(&mut xmp, tagger)?;
+    finalize_xmp_update(file_path, original.as_ref(), xmp, changes, pb)
 }
 
 fn update_xmp_rating(
@@ -1857,7 +5251,7 @@ fn update_xmp_rating(
     xmp: &mut XmpMeta,
     old_value: &str,
     new_value: &str,
-    pb: &ProgressBar,
+    pb: &ServalProgress,
 ) -> anyhow::Result<()> {
     let current_rating = xmp
         .property(xmp_ns::XMP, "Rating")
@@ -1874,18 +5268,228 @@ fn update_xmp_rating(
     }
 
     if old_value.is_empty() {
-        pb.println(format!("Setting Rating to '{new_value}'"));
+        pb.notice(
+            "Inserted ratings",
+            format!("Setting Rating to '{new_value}'"),
+        );
     } else {
-        pb.println(format!(
-            "Updating Rating from '{old_value}' to '{new_value}'"
-        ));
+        pb.notice(
+            "Updated ratings",
+            format!("Updating Rating from '{old_value}' to '{new_value}'"),
+        );
     }
 
     xmp.set_property(xmp_ns::XMP, "Rating", &XmpValue::new(new_value.to_string()))?;
     Ok(())
 }
 
-fn finalize_xmp_update(file_path: PathBuf, xmp: XmpMeta) -> anyhow::Result<()> {
+// xmp:Rating from the rating_update column, created or replaced whatever it was
+fn set_xmp_rating(xmp: &mut XmpMeta, rating: &str, pb: &ServalProgress) -> anyhow::Result<()> {
+    match xmp.property(xmp_ns::XMP, "Rating") {
+        Some(current) if current.value == rating => {}
+        Some(current) => pb.notice(
+            "Updated ratings",
+            format!("Updating Rating from '{}' to '{rating}'", current.value),
+        ),
+        None => pb.notice("Inserted ratings", format!("Setting Rating to '{rating}'")),
+    }
+    xmp.set_property(xmp_ns::XMP, "Rating", &XmpValue::new(rating.to_string()))?;
+    Ok(())
+}
+
+// rating_update values as xmp:Rating integers (e.g. "4.0" -> "4"), anything else than
+// 0-5 rejected with its CSV line, as are files given two different ratings
+fn normalize_rating_updates(
+    csv_path: &Path,
+    paths: &StringChunked,
+    ratings: &StringChunked,
+) -> anyhow::Result<Vec<Option<String>>> {
+    let mut normalized = Vec::with_capacity(ratings.len());
+    let mut invalid = Vec::new();
+    let mut by_path: HashMap<&str, (String, usize)> = HashMap::new();
+    for (index, (path, rating)) in paths.iter().zip(ratings.iter()).enumerate() {
+        let line = index + 2;
+        let Some(rating) = rating.map(str::trim).filter(|rating| !rating.is_empty()) else {
+            normalized.push(None);
+            continue;
+        };
+        let rating = match rating.parse::<f64>() {
+            Ok(value) if value.fract() == 0.0 && (0.0..=5.0).contains(&value) => {
+                (value as i64).to_string()
+            }
+            _ => {
+                invalid.push(format!("line {line}: '{rating}'"));
+                normalized.push(None);
+                continue;
+            }
+        };
+        if let Some(path) = path {
+            match by_path.get(path) {
+                Some((other, other_line)) if *other != rating => invalid.push(format!(
+                    "line {line}: '{rating}' for {path}, line {other_line} gives '{other}'"
+                )),
+                Some(_) => {}
+                None => {
+                    by_path.insert(path, (rating.clone(), line));
+                }
+            }
+        }
+        normalized.push(Some(rating));
+    }
+    if !invalid.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Invalid {RATING_UPDATE_COLUMN} in {}, expected one integer 0-5 per file:\n  {}",
+            csv_path.display(),
+            invalid.join("\n  ")
+        ));
+    }
+    Ok(normalized)
+}
+
+// digiKam Pick/Color label, written as the index digiKam expects
+fn update_xmp_label(
+    file_path: &Path,
+    xmp: &mut XmpMeta,
+    property: &str,
+    labels: &[&'static str],
+    old_value: &str,
+    new_value: &str,
+    pb: &ServalProgress,
+) -> anyhow::Result<()> {
+    let current_label = xmp
+        .property(DIGIKAM_NS, property)
+        .map(|value| {
+            label_name(labels, &value.value)
+                .map(str::to_string)
+                .unwrap_or(value.value)
+        })
+        .unwrap_or_default();
+    let old_label = label_name(labels, old_value).unwrap_or(old_value);
+    if !old_value.is_empty() && current_label != old_label {
+        return Err(anyhow::anyhow!(
+            "{property} mismatch in {}: expected '{}', found '{}'",
+            file_path.display(),
+            old_value,
+            current_label
+        ));
+    }
+
+    let new_xmp_value = match label_index(labels, new_value) {
+        Some(index) => index.to_string(),
+        None => {
+            pb.warn(
+                "Unknown labels written as-is",
+                format!(
+                    "Warning: unknown {property} '{new_value}' for {}, written as-is (known: {})",
+                    file_path.display(),
+                    labels.join(", ")
+                ),
+            );
+            new_value.to_string()
+        }
+    };
+    if old_value.is_empty() {
+        pb.notice(
+            "Inserted labels",
+            format!("Setting {property} to '{new_value}'"),
+        );
+    } else {
+        pb.notice(
+            "Updated labels",
+            format!("Updating {property} from '{old_value}' to '{new_value}'"),
+        );
+    }
+
+    XmpMeta::register_namespace(DIGIKAM_NS, "digiKam")?;
+    xmp.set_property(DIGIKAM_NS, property, &XmpValue::new(new_xmp_value))?;
+    Ok(())
+}
+
+// Attribute a sidecar modification to a tagger
+fn stamp_tagger(xmp: &mut XmpMeta, tagger: Option<&str>) -> anyhow::Result<()> {
+    let Some(tagger) = tagger else {
+        return Ok(());
+    };
+    XmpMeta::register_namespace(SERVAL_NS, "serval")?;
+    xmp.set_property(SERVAL_NS, SERVAL_TAGGER, &XmpValue::new(tagger.to_string()))?;
+    let now = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+    xmp.set_property_date(
+        xmp_ns::XMP,
+        "MetadataDate",
+        &XmpValue::new(naive_datetime_to_xmp(&now)?),
+    )?;
+    Ok(())
+}
+
+// Leaf properties (qualifiers included) of an XMP packet as (namespace, path) -> value
+fn xmp_leaves(xmp: &XmpMeta) -> BTreeMap<(String, String), String> {
+    xmp.iter(IterOptions::default().leaf_nodes_only())
+        .map(|prop| ((prop.schema_ns, prop.name), prop.value.value))
+        .collect()
+}
+
+// Properties of `original` that were meant to survive the update but did not make it into the
+// written file, compared by re-parsing what was written
+fn roundtrip_differences(
+    original: &XmpMeta,
+    intended: &XmpMeta,
+    written: &str,
+) -> anyhow::Result<Vec<String>> {
+    let reparsed = XmpMeta::from_str_with_options(written, FromStrOptions::default())
+        .map_err(|e| anyhow::anyhow!("Failed to re-parse written XMP: {e:?}"))?;
+    let before = xmp_leaves(original);
+    let intended = xmp_leaves(intended);
+    let after = xmp_leaves(&reparsed);
+    Ok(before
+        .keys()
+        .chain(intended.keys())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .filter_map(|key| {
+            // Properties changed by the update are checked against the intended value, the
+            // ones it deleted (DELETE_TAG) are expected to be gone
+            let expected = intended.get(key).or_else(|| before.get(key))?;
+            if !intended.contains_key(key) && !after.contains_key(key) {
+                return None;
+            }
+            match after.get(key) {
+                Some(value) if value == expected => None,
+                Some(value) => Some(format!(
+                    "{} {}: '{expected}' became '{value}'",
+                    key.0, key.1
+                )),
+                None => Some(format!("{} {}: '{expected}' dropped", key.0, key.1)),
+            }
+        })
+        .collect())
+}
+
+/// Compare the sidecar just written from `intended` with the `original` it was updated from
+/// (xmp update --verify-roundtrip), restoring `backup_path` over it when a property that
+/// should have survived went missing or changed. The differences, none when the file is kept.
+pub fn verify_xmp_roundtrip(
+    file_path: &Path,
+    backup_path: &Path,
+    original: &XmpMeta,
+    intended: &XmpMeta,
+) -> anyhow::Result<Vec<String>> {
+    let written = fs::read_to_string(file_path)?;
+    let differences = roundtrip_differences(original, intended, &written)?;
+    if !differences.is_empty() {
+        fs::copy(backup_path, file_path)?;
+    }
+    Ok(differences)
+}
+
+// Write through a temporary file after a timestamped backup, then log the changes unless the
+// round-trip check restored the backup
+fn finalize_xmp_update(
+    file_path: PathBuf,
+    original: Option<&XmpMeta>,
+    xmp: XmpMeta,
+    changes: Vec<AuditChange>,
+    pb: &ServalProgress,
+) -> anyhow::Result<()> {
     let modified_xmp =
         xmp.to_string_with_options(ToStringOptions::default().set_newline("\n".to_string()))?;
 
@@ -1897,32 +5501,86 @@ fn finalize_xmp_update(file_path: PathBuf, xmp: XmpMeta) -> anyhow::Result<()> {
     fs::write(&temp_path, &modified_xmp)?;
     fs::rename(&temp_path, &file_path)?;
 
-    Ok(())
+    if let Some(original) = original {
+        let differences =
+            verify_xmp_roundtrip(&file_path, Path::new(&backup_path), original, &xmp)?;
+        if !differences.is_empty() {
+            pb.warn(
+                "Reverted after round-trip check",
+                format!(
+                    "Warning: round-trip check failed for {}, restored from backup:\n  {}",
+                    file_path.display(),
+                    differences.join("\n  ")
+                ),
+            );
+            return Ok(());
+        }
+    }
+    record_changes(&file_path, &changes, Some(Path::new(&backup_path)))
 }
 
-pub fn update_tags(csv_path: PathBuf, update_type: XmpUpdateType) -> anyhow::Result<()> {
+pub fn update_tags(
+    csv_path: PathBuf,
+    update_type: XmpUpdateType,
+    tagger: Option<String>,
+    verify_roundtrip: bool,
+    column_map: ColumnMap,
+) -> anyhow::Result<()> {
     let tag_column_name = update_type.col_name();
-    let df = CsvReadOptions::default()
+    let required_columns = [PATH_COLUMN, XMP_UPDATE_COLUMN, tag_column_name];
+    check_csv_columns(&csv_path, &required_columns, &column_map)?;
+    let mut columns = required_columns
+        .map(|name| column_map.source(name))
+        .to_vec();
+    let has_rating_update = csv_header(&csv_path)?
+        .iter()
+        .any(|name| name == column_map.source(RATING_UPDATE_COLUMN));
+    if has_rating_update {
+        if update_type == XmpUpdateType::Rating {
+            return Err(anyhow::anyhow!(
+                "{RATING_UPDATE_COLUMN} can't be combined with -t rating, use xmp_update instead"
+            ));
+        }
+        columns.push(column_map.source(RATING_UPDATE_COLUMN));
+    }
+    let mut df = CsvReadOptions::default()
         .with_infer_schema_length(Some(0))
-        .with_columns(csv_projection_columns(&[
-            PATH_COLUMN,
-            XMP_UPDATE_COLUMN,
-            tag_column_name,
-        ]))
+        .with_columns(csv_projection_columns(&columns))
         .with_ignore_errors(false)
-        .try_into_reader_with_file_path(Some(csv_path))?
+        .try_into_reader_with_file_path(Some(csv_path.clone()))?
         .finish()?;
     reject_duplicate_csv_columns(&df)?;
+    column_map.apply(&mut df)?;
+    if has_rating_update {
+        let ratings = normalize_rating_updates(
+            &csv_path,
+            df.column(PATH_COLUMN)?.str()?,
+            df.column(RATING_UPDATE_COLUMN)?.str()?,
+        )?;
+        df.with_column(Column::new(RATING_UPDATE_COLUMN.into(), ratings))?;
+    } else {
+        df.with_column(Column::full_null(
+            RATING_UPDATE_COLUMN.into(),
+            df.height(),
+            &DataType::String,
+        ))?;
+    }
 
     let mut df_filtered_lazy = df
         .lazy()
-        .filter(col(XMP_UPDATE_COLUMN).is_not_null())
+        .filter(
+            col(XMP_UPDATE_COLUMN)
+                .is_not_null()
+                .or(col(RATING_UPDATE_COLUMN).is_not_null()),
+        )
         .select([
             col(PATH_COLUMN),
             col(XMP_UPDATE_COLUMN),
             col(tag_column_name),
+            col(RATING_UPDATE_COLUMN),
         ]);
-    if update_type == XmpUpdateType::Rating {
+    // Ratings and labels are per file, not per tag row
+    if update_type.tag_type().is_none() {
         df_filtered_lazy = df_filtered_lazy.unique(
             Some(cols(vec![
                 PATH_COLUMN.to_string(),
@@ -1936,69 +5594,92 @@ pub fn update_tags(csv_path: PathBuf, update_type: XmpUpdateType) -> anyhow::Res
     let num_updates = df_filtered.height();
     println!("Found {num_updates} rows with updates");
 
-    let pb = ProgressBar::new(num_updates as u64);
-    configure_progress_bar(&pb);
-    pb.set_message("Processing XMP updates...");
+    let pb = ServalProgress::new(num_updates as u64, "processing XMP updates");
 
     let path_col = df_filtered.column(PATH_COLUMN)?.str()?;
     let xmp_update_col = df_filtered.column(XMP_UPDATE_COLUMN)?.str()?;
     let tag_original_col = df_filtered.column(tag_column_name)?.str()?;
+    let rating_update_col = df_filtered.column(RATING_UPDATE_COLUMN)?.str()?;
+    // Files whose rating was already written by an earlier row
+    let mut rated: HashSet<&str> = HashSet::new();
+    let mut undecodable = Vec::new();
+
+    let iter = izip!(
+        path_col.iter(),
+        xmp_update_col.iter(),
+        tag_original_col.iter(),
+        rating_update_col.iter()
+    );
 
-    let iter = path_col
-        .iter()
-        .zip(xmp_update_col.iter())
-        .zip(tag_original_col.iter())
-        .map(|((path, xmp_up), tag_orig)| (path, xmp_up, tag_orig));
-
-    for (path, xmp_update, tag_original) in iter {
+    for (path, xmp_update, tag_original, rating_update) in iter {
         if let Some(path_str) = path {
             let current_path = PathBuf::from(path_str);
             let xmp_update = xmp_update.unwrap_or("");
+            let rating_update = rating_update.filter(|_| !rated.contains(path_str));
 
-            if !xmp_update.is_empty() {
+            if !xmp_update.is_empty() || rating_update.is_some() {
                 // Check if the file has .xmp extension
                 if let Some(ext) = current_path.extension() {
                     if ext != "xmp" {
-                        pb.println(format!("Skipping non-XMP file: {path_str}"));
+                        pb.notice(
+                            "Skipped non-XMP files",
+                            format!("Skipping non-XMP file: {path_str}"),
+                        );
                         pb.inc(1);
                         continue;
                     }
                 } else {
-                    pb.println(format!("Skipping file without extension: {path_str}"));
+                    pb.notice(
+                        "Skipped files without extension",
+                        format!("Skipping file without extension: {path_str}"),
+                    );
                     pb.inc(1);
                     continue;
                 }
 
                 let tag_original = tag_original.unwrap_or("");
-                pb.println(format!("Processing: {path_str}"));
-                update_xmp(
+                pb.notice("Processed", format!("Processing: {path_str}"));
+                let result = update_xmp(
                     current_path.clone(),
                     tag_original.to_string(),
                     xmp_update.to_string(),
                     update_type,
+                    rating_update,
+                    tagger.as_deref(),
+                    verify_roundtrip,
                     &pb,
-                )?;
+                );
+                if skip_undecodable(result, &mut undecodable, &pb)? && rating_update.is_some() {
+                    rated.insert(path_str);
+                }
             }
         } else {
-            pb.println("Missing xmp path, skipping.");
+            pb.notice("Missing XMP path", "Missing xmp path, skipping.");
         }
         pb.inc(1);
     }
 
     pb.finish_with_message("Finished processing all XMP updates");
-    Ok(())
+    write_undecodable(&csv_path, undecodable)
 }
 
-pub fn update_datetime(csv_path: PathBuf) -> anyhow::Result<()> {
-    let df = CsvReadOptions::default()
-        .with_columns(csv_projection_columns(&[
-            PATH_COLUMN,
-            XMP_UPDATE_DATETIME_COLUMN,
-        ]))
+pub fn update_datetime(
+    csv_path: PathBuf,
+    tagger: Option<String>,
+    verify_roundtrip: bool,
+    column_map: ColumnMap,
+) -> anyhow::Result<()> {
+    let required_columns = [PATH_COLUMN, XMP_UPDATE_DATETIME_COLUMN];
+    check_csv_columns(&csv_path, &required_columns, &column_map)?;
+    let mut df = CsvReadOptions::default()
+        .with_columns(csv_projection_columns(
+            &required_columns.map(|name| column_map.source(name)),
+        ))
         .with_ignore_errors(false)
-        .try_into_reader_with_file_path(Some(csv_path))?
+        .try_into_reader_with_file_path(Some(csv_path.clone()))?
         .finish()?;
     reject_duplicate_csv_columns(&df)?;
+    column_map.apply(&mut df)?;
 
     let df_filtered = df
         .lazy()
@@ -2029,15 +5710,14 @@ pub fn update_datetime(csv_path: PathBuf) -> anyhow::Result<()> {
     let num_updates = df_filtered.height();
     println!("Found {num_updates} rows with valid datetime updates");
 
-    let pb = ProgressBar::new(num_updates as u64);
-    configure_progress_bar(&pb);
-    pb.set_message("Processing XMP datetime updates...");
+    let pb = ServalProgress::new(num_updates as u64, "processing XMP datetime updates");
 
     let path_col = df_filtered.column(PATH_COLUMN)?.str()?;
     let datetime_col = df_filtered.column(XMP_UPDATE_DATETIME_COLUMN)?.datetime()?;
     let datetime_strings = datetime_col.to_string("%Y-%m-%dT%H:%M:%S")?;
 
     let iter = path_col.iter().zip(datetime_strings.iter());
+    let mut undecodable = Vec::new();
 
     for (path, datetime) in iter {
         if let Some(path_str) = path {
@@ -2047,48 +5727,114 @@ pub fn update_datetime(csv_path: PathBuf) -> anyhow::Result<()> {
                 // Check if the file has .xmp extension
                 if let Some(ext) = current_path.extension() {
                     if ext != "xmp" {
-                        pb.println(format!("Skipping non-XMP file: {path_str}"));
+                        pb.notice(
+                            "Skipped non-XMP files",
+                            format!("Skipping non-XMP file: {path_str}"),
+                        );
                         pb.inc(1);
                         continue;
                     }
                 } else {
-                    pb.println(format!("Skipping file without extension: {path_str}"));
+                    pb.notice(
+                        "Skipped files without extension",
+                        format!("Skipping file without extension: {path_str}"),
+                    );
                     pb.inc(1);
                     continue;
                 }
 
-                pb.println(format!(
-                    "Processing datetime update: {path_str} -> {datetime_str}"
-                ));
-                update_xmp_datetime(current_path.clone(), datetime_str.to_string())?;
+                pb.notice(
+                    "Processed",
+                    format!("Processing datetime update: {path_str} -> {datetime_str}"),
+                );
+                let result = update_xmp_datetime(
+                    current_path.clone(),
+                    datetime_str.to_string(),
+                    tagger.as_deref(),
+                    verify_roundtrip,
+                    &pb,
+                );
+                skip_undecodable(result, &mut undecodable, &pb)?;
             }
         } else {
-            pb.println("Missing xmp path, skipping.");
+            pb.notice("Missing XMP path", "Missing xmp path, skipping.");
         }
         pb.inc(1);
     }
 
     pb.finish_with_message("Finished processing all XMP datetime updates");
+    write_undecodable(&csv_path, undecodable)
+}
+
+// Undecodable sidecars are collected and skipped so one file doesn't abort the batch,
+// returns whether the update was written
+fn skip_undecodable(
+    result: anyhow::Result<()>,
+    undecodable: &mut Vec<XmpDecodeError>,
+    pb: &ServalProgress,
+) -> anyhow::Result<bool> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(e) => {
+            let error = e.downcast::<XmpDecodeError>()?;
+            pb.warn("Undecodable XMP files skipped", error.to_string());
+            undecodable.push(error);
+            Ok(false)
+        }
+    }
+}
+
+// Errors output of an xmp update, errors_<csv name> next to the update CSV
+fn write_undecodable(csv_path: &Path, undecodable: Vec<XmpDecodeError>) -> anyhow::Result<()> {
+    if undecodable.is_empty() {
+        return Ok(());
+    }
+    let (paths, reasons): (Vec<String>, Vec<String>) = undecodable
+        .into_iter()
+        .map(|error| (error.path.to_string_lossy().into_owned(), error.reason))
+        .unzip();
+    let mut errors = DataFrame::new(
+        paths.len(),
+        vec![
+            Column::new(PATH_COLUMN.into(), paths),
+            Column::new("error".into(), reasons),
+        ],
+    )?;
+    let file_name = csv_path.file_name().unwrap_or_default().to_string_lossy();
+    let errors_csv_path = csv_path.with_file_name(format!("errors_{file_name}"));
+    let mut file = std::fs::File::create(&errors_csv_path)?;
+    csv_writer(&mut file).finish(&mut errors)?;
+    println!(
+        "{} file(s) failed, saved to {}",
+        errors.height(),
+        errors_csv_path.to_string_lossy()
+    );
     Ok(())
 }
 
-fn update_xmp_datetime(file_path: PathBuf, iso8601_datetime: String) -> anyhow::Result<()> {
-    let xmp_content = fs::read_to_string(&file_path)?;
+fn update_xmp_datetime(
+    file_path: PathBuf,
+    iso8601_datetime: String,
+    tagger: Option<&str>,
+    verify_roundtrip: bool,
+    pb: &ServalProgress,
+) -> anyhow::Result<()> {
+    let xmp_content = read_xmp_sidecar(&file_path)?;
     let mut xmp = XmpMeta::from_str_with_options(&xmp_content, FromStrOptions::default())
         .map_err(|e| anyhow::anyhow!("Failed to parse XMP: {e:?}"))?;
+    let original = verify_roundtrip.then(|| xmp.clone());
+    let old_datetime = xmp
+        .property(xmp_ns::EXIF, "DateTimeOriginal")
+        .map(|current| current.value)
+        .unwrap_or_default();
 
     set_xmp_datetime_fields(&mut xmp, &iso8601_datetime)?;
-
-    let modified_xmp =
-        xmp.to_string_with_options(ToStringOptions::default().set_newline("\n".to_string()))?;
-
-    let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S").to_string();
-    let backup_path = format!("{}.{}.backup", file_path.display(), timestamp);
-    let temp_path = format!("{}.{}.tmp", file_path.display(), timestamp);
-
-    fs::copy(&file_path, &backup_path)?;
-    fs::write(&temp_path, &modified_xmp)?;
-    fs::rename(&temp_path, &file_path)?;
-
-    Ok(())
+    stamp_tagger(&mut xmp, tagger)?;
+
+    let changes = vec![AuditChange::new(
+        DATETIME_COLUMN,
+        old_datetime,
+        iso8601_datetime,
+    )];
+    finalize_xmp_update(file_path, original.as_ref(), xmp, changes, pb)
 }
@@ -1,26 +1,37 @@
+use crate::archive::is_archive_entry_path;
+use crate::audit::{AuditChange, record_changes};
+use crate::config::{CONFIG_FILE, IndeterminateLabels};
+use crate::progress::{ServalMultiProgress, ServalProgress, is_verbose};
 use crate::schema::{
-    ALL_RESOURCE_EXTENSIONS, CUSTOM_COLUMN, DEPLOYMENT_ID_COLUMN, EVENT_ID_COLUMN,
-    IMAGE_EXTENSIONS, PATH_COLUMN, RATING_COLUMN, VIDEO_EXTENSIONS, XMP_EXTENSIONS,
-    resource_extension,
+    ALL_RESOURCE_EXTENSIONS, CANONICAL_TAGS_HEADER, COLOR_LABEL_COLUMN, COLOR_LABELS,
+    CUSTOM_COLUMN, DATETIME_COLUMN, DATETIME_UTC_COLUMN, DEPLOYMENT_ID_COLUMN, EVENT_ID_COLUMN,
+    IMAGE_EXTENSIONS, MEDIA_EXTENSIONS, PATH_COLUMN, PICK_LABEL_COLUMN, PICK_LABELS, RATING_COLUMN,
+    TIME_MODIFIED_COLUMN, UTC_OFFSET_COLUMN, VIDEO_EXTENSIONS, XMP_EXTENSIONS, is_known_column,
+    resource_extension, short_path_hash,
 };
-use chrono::NaiveDateTime;
+use crate::tags::{LIGHTROOM_NS, LR_HIERARCHICAL_SUBJECT};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Timelike};
+use chrono_tz::Tz;
 use core::fmt;
-use indicatif::{ProgressBar, ProgressStyle};
 use pest_derive::Parser;
 use polars::prelude::*;
 use rayon::prelude::*;
-use std::collections::HashSet;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::ffi::OsString;
 use std::fs::{File, FileTimes};
 use std::io;
 use std::str::FromStr;
 use std::{
     env, fs,
-    path::{Path, PathBuf},
-    sync::Arc,
+    path::{Component, Path, PathBuf},
+    sync::{
+        Arc, Condvar, Mutex, OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
 };
 use walkdir::{DirEntry, WalkDir};
-use xmp_toolkit::{OpenFileOptions, XmpFile, XmpMeta};
+use xmp_toolkit::{OpenFileOptions, XmpFile, XmpMeta, xmp_ns};
 
 pub fn csv_projection_columns(names: &[&str]) -> Option<Arc<[PlSmallStr]>> {
     Some(Arc::from(
@@ -32,6 +43,266 @@ pub fn csv_projection_columns(names: &[&str]) -> Option<Arc<[PlSmallStr]>> {
     ))
 }
 
+/// Serval column names mapped onto the columns of a user's CSV, e.g. path -> filepath
+#[derive(Clone, Debug, Default)]
+pub struct ColumnMap(Vec<(String, String)>);
+
+impl ColumnMap {
+    /// Map for `--path-column`, empty when not given
+    pub fn from_path_column(path_column: Option<String>) -> Self {
+        Self(
+            path_column
+                .map(|column| vec![(PATH_COLUMN.to_string(), column)])
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Combine `--column-map` with `--path-column`, which is shorthand for `path=COLUMN`
+    pub fn with_path_column(mut self, path_column: Option<String>) -> anyhow::Result<Self> {
+        let Some(path_column) = path_column else {
+            return Ok(self);
+        };
+        match self.0.iter().find(|(target, _)| target == PATH_COLUMN) {
+            Some((_, source)) if *source != path_column => Err(anyhow::anyhow!(
+                "--path-column {path_column} conflicts with path={source} in --column-map"
+            )),
+            Some(_) => Ok(self),
+            None => {
+                self.0.push((PATH_COLUMN.to_string(), path_column));
+                Ok(self)
+            }
+        }
+    }
+
+    /// Column to read from the CSV for a Serval column
+    pub fn source<'a>(&'a self, name: &'a str) -> &'a str {
+        self.0
+            .iter()
+            .find(|(target, _)| target == name)
+            .map_or(name, |(_, source)| source.as_str())
+    }
+
+    /// Rename mapped columns to their Serval names, right after reading
+    pub fn apply(&self, df: &mut DataFrame) -> anyhow::Result<()> {
+        for (target, source) in &self.0 {
+            if target == source || df.get_column_index(source).is_none() {
+                continue;
+            }
+            if df.get_column_index(target).is_some() {
+                return Err(anyhow::anyhow!(
+                    "Cannot map {source} onto {target}, the CSV already has a {target} column"
+                ));
+            }
+            df.rename(source, target.into())?;
+        }
+        Ok(())
+    }
+
+    /// Rename Serval columns back to the CSV's own names, for writing a copy of the input
+    pub fn restore(&self, df: &mut DataFrame) -> anyhow::Result<()> {
+        for (target, source) in &self.0 {
+            if target != source && df.get_column_index(target).is_some() {
+                df.rename(target, source.into())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Parse "path=RelativePath,datetime=DateTime", or @file with one mapping per line
+pub fn parse_column_map_arg(value: &str) -> anyhow::Result<ColumnMap> {
+    let content = match value.strip_prefix('@') {
+        Some(map_path) => fs::read_to_string(map_path)
+            .map_err(|e| anyhow::anyhow!("Failed to read column map {map_path}: {e}"))?,
+        None => value.to_string(),
+    };
+    let mut mappings: Vec<(String, String)> = Vec::new();
+    for entry in content
+        .lines()
+        .flat_map(|line| line.split(','))
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty() && !entry.starts_with('#'))
+    {
+        let (target, source) = entry
+            .split_once('=')
+            .map(|(target, source)| (target.trim(), source.trim()))
+            .filter(|(target, source)| !target.is_empty() && !source.is_empty())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Invalid column mapping '{entry}', expected serval_column=CsvColumn"
+                )
+            })?;
+        if !is_known_column(target) {
+            return Err(anyhow::anyhow!(
+                "Unknown Serval column '{target}' in column map, expected one of: {}",
+                CANONICAL_TAGS_HEADER.join(", ")
+            ));
+        }
+        if mappings.iter().any(|(mapped, _)| mapped == target) {
+            return Err(anyhow::anyhow!("Column '{target}' is mapped twice"));
+        }
+        if mappings.iter().any(|(_, mapped)| mapped == source) {
+            return Err(anyhow::anyhow!("CSV column '{source}' is mapped twice"));
+        }
+        mappings.push((target.to_string(), source.to_string()));
+    }
+    Ok(ColumnMap(mappings))
+}
+
+// Columns that look like they hold file paths, suggested for --path-column
+fn path_column_candidates(header: &[String]) -> Vec<&str> {
+    header
+        .iter()
+        .filter(|name| {
+            let name = name.to_lowercase().replace(['_', ' ', '-'], "");
+            name.contains("path") || name == "file" || name == "sourcefile"
+        })
+        .map(String::as_str)
+        .collect()
+}
+
+// Check the header before reading, so a missing column is reported plainly instead of
+// failing deep inside polars
+/// Column names of a CSV, read without loading any row
+/// Tags table written as Parquet by observe --format, read in place of a CSV
+pub fn is_parquet(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("parquet"))
+}
+
+// Every column as text, as the CSV readers without schema inference see a tags CSV
+pub fn read_parquet_as_text(path: &Path) -> anyhow::Result<DataFrame> {
+    let df = ParquetReader::new(File::open(path)?).finish()?;
+    let columns: Vec<Expr> = df
+        .schema()
+        .iter()
+        .map(|(name, dtype)| match dtype {
+            DataType::Datetime(_, _) => col(name.clone()).dt().to_string("%Y-%m-%d %H:%M:%S"),
+            _ => col(name.clone()).cast(DataType::String),
+        })
+        .collect();
+    Ok(df.lazy().select(columns).collect()?)
+}
+
+pub fn csv_header(csv_path: &Path) -> anyhow::Result<Vec<String>> {
+    if is_parquet(csv_path) {
+        return Ok(ParquetReader::new(File::open(csv_path)?)
+            .schema()?
+            .iter_names()
+            .map(|name| name.to_string())
+            .collect());
+    }
+    Ok(CsvReadOptions::default()
+        .with_n_rows(Some(0))
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(csv_path.to_path_buf()))?
+        .finish()?
+        .get_column_names()
+        .iter()
+        .map(|name| name.to_string())
+        .collect())
+}
+
+pub fn check_csv_columns(
+    csv_path: &Path,
+    required: &[&str],
+    column_map: &ColumnMap,
+) -> anyhow::Result<()> {
+    let header = csv_header(csv_path)?;
+    let missing: Vec<&str> = required
+        .iter()
+        .map(|name| column_map.source(name))
+        .filter(|name| !header.iter().any(|column| column == name))
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let mut message = format!(
+        "Missing required column(s) in {}: {}\nFound columns: {}",
+        csv_path.display(),
+        missing.join(", "),
+        header.join(", ")
+    );
+    if missing.contains(&column_map.source(PATH_COLUMN)) {
+        let candidates = path_column_candidates(&header);
+        if let Some(candidate) = candidates.first() {
+            message.push_str(&format!(
+                "\nHint: use --path-column \"{candidate}\" to read file paths from '{candidate}'"
+            ));
+        }
+    }
+    Err(anyhow::anyhow!(message))
+}
+
+/// Problems found before a long run starts, reported together so they're fixed in one pass
+#[derive(Default)]
+pub struct Preflight {
+    problems: Vec<String>,
+}
+
+impl Preflight {
+    /// Output directory can be created and written to, checked with a probe file. Directories
+    /// created for the probe are removed again.
+    pub fn output_dir(&mut self, dir: &Path) {
+        let mut created = None;
+        for ancestor in dir.ancestors() {
+            if ancestor.as_os_str().is_empty() || ancestor.exists() {
+                break;
+            }
+            created = Some(ancestor);
+        }
+        let probe = dir.join(format!(".serval_preflight_{}", std::process::id()));
+        let result = fs::create_dir_all(dir)
+            .and_then(|_| fs::write(&probe, b"serval"))
+            .and_then(|_| fs::remove_file(&probe));
+        if let Some(created) = created {
+            let _ = fs::remove_dir_all(created);
+        }
+        if let Err(e) = result {
+            self.problems.push(format!(
+                "Output directory {} is not writable: {e}",
+                dir.display()
+            ));
+        }
+    }
+
+    pub fn input_dir(&mut self, dir: &Path) {
+        if !dir.is_dir() {
+            self.problems
+                .push(format!("Input directory {} not found", dir.display()));
+        }
+    }
+
+    pub fn input_file(&mut self, path: &Path) -> bool {
+        if path.is_file() {
+            return true;
+        }
+        self.problems
+            .push(format!("Input file {} not found", path.display()));
+        false
+    }
+
+    pub fn csv_columns(&mut self, csv_path: &Path, required: &[&str], column_map: &ColumnMap) {
+        if !self.input_file(csv_path) {
+            return;
+        }
+        if let Err(e) = check_csv_columns(csv_path, required, column_map) {
+            self.problems.push(e.to_string());
+        }
+    }
+
+    pub fn finish(self) -> anyhow::Result<()> {
+        if self.problems.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow::anyhow!(
+            "{} problem(s) found before starting, nothing was done:\n- {}",
+            self.problems.len(),
+            self.problems.join("\n- ")
+        ))
+    }
+}
+
 pub fn reject_duplicate_csv_columns(df: &DataFrame) -> anyhow::Result<()> {
     if df
         .get_column_names()
@@ -130,7 +401,11 @@ impl TagType {
 pub enum XmpUpdateType {
     Species,
     Individual,
+    Count,
+    Sex,
     Rating,
+    PickLabel,
+    ColorLabel,
 }
 
 impl fmt::Display for XmpUpdateType {
@@ -144,7 +419,11 @@ impl XmpUpdateType {
         match self {
             Self::Species => TagType::Species.col_name(),
             Self::Individual => TagType::Individual.col_name(),
+            Self::Count => TagType::Count.col_name(),
+            Self::Sex => TagType::Sex.col_name(),
             Self::Rating => RATING_COLUMN,
+            Self::PickLabel => PICK_LABEL_COLUMN,
+            Self::ColorLabel => COLOR_LABEL_COLUMN,
         }
     }
 
@@ -152,7 +431,9 @@ impl XmpUpdateType {
         match self {
             Self::Species => Some(TagType::Species),
             Self::Individual => Some(TagType::Individual),
-            Self::Rating => None,
+            Self::Count => Some(TagType::Count),
+            Self::Sex => Some(TagType::Sex),
+            Self::Rating | Self::PickLabel | Self::ColorLabel => None,
         }
     }
 }
@@ -165,17 +446,119 @@ pub enum ExtractFilterType {
     Rating,
     Event,
     Custom,
+    PickLabel,
+    ColorLabel,
     Advanced,
 }
 
+/// Sidecar naming convention, appended (IMG_0001.JPG.xmp) or extension-replaced (IMG_0001.xmp)
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum SidecarConvention {
+    Appended,
+    Replaced,
+}
+
+/// What the minimum time difference of capture is compared with
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum IndependenceMode {
+    /// Last independent record
+    Lir,
+    /// Last record
+    Lr,
+}
+
 #[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum BalanceBy {
+    Deployment,
+}
+
+// Deterministic Fisher-Yates shuffle (splitmix64), stable across platforms for a given seed
+pub fn seeded_shuffle<T>(items: &mut [T], seed: u64) {
+    let mut state = seed;
+    let mut next = || {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    };
+    for i in (1..items.len()).rev() {
+        let j = (next() % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
 pub enum SubdirType {
+    #[default]
     Species,
     Individual,
     Rating,
     Custom,
 }
 
+impl SubdirType {
+    pub fn col_name(self) -> &'static str {
+        match self {
+            Self::Species => TagType::Species.col_name(),
+            Self::Individual => TagType::Individual.col_name(),
+            Self::Rating => RATING_COLUMN,
+            Self::Custom => CUSTOM_COLUMN,
+        }
+    }
+}
+
+/// File format of the tags table written by observe
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum TagsFormat {
+    #[default]
+    Csv,
+    /// Keeps the column dtypes (datetimes) and reloads faster for large surveys
+    Parquet,
+    Both,
+}
+
+impl TagsFormat {
+    pub fn csv(self) -> bool {
+        self != Self::Parquet
+    }
+
+    pub fn parquet(self) -> bool {
+        self != Self::Csv
+    }
+}
+
+/// Column whose values name the top directory of each extracted copy
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum GroupBy {
+    Species,
+    Individual,
+    Rating,
+}
+
+impl GroupBy {
+    pub fn col_name(self) -> &'static str {
+        match self {
+            Self::Species => TagType::Species.col_name(),
+            Self::Individual => TagType::Individual.col_name(),
+            Self::Rating => RATING_COLUMN,
+        }
+    }
+}
+
+/// Group directory of records missing the --group-by value
+pub const UNKNOWN_GROUP: &str = "_unknown";
+
+/// What a run does when its fixed-name outputs already exist in the output directory
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum OnConflict {
+    /// Write into the next free run_NNN/ subdirectory
+    #[default]
+    Version,
+    Overwrite,
+    Fail,
+}
+
 /// Represents a parsed filter condition
 #[derive(Debug, Clone)]
 pub struct FilterCondition {
@@ -226,11 +609,39 @@ impl ExtractFilterType {
             "path" | "p" => Some(Self::Path),
             "event" | "e" => Some(Self::Event),
             "custom" | "c" => Some(Self::Custom),
+            "pick_label" | "pick" | "pl" => Some(Self::PickLabel),
+            "color_label" | "color" | "colour" | "cl" => Some(Self::ColorLabel),
+            _ => None,
+        }
+    }
+
+    /// Label names for the Pick/Color label filters
+    pub fn labels(self) -> Option<&'static [&'static str]> {
+        match self {
+            Self::PickLabel => Some(PICK_LABELS),
+            Self::ColorLabel => Some(COLOR_LABELS),
             _ => None,
         }
     }
 }
 
+/// Name of a digiKam label given as name (any case) or index, None when out of range
+pub fn label_name(labels: &[&'static str], value: &str) -> Option<&'static str> {
+    let value = value.trim();
+    match value.parse::<usize>() {
+        Ok(index) => labels.get(index).copied(),
+        Err(_) => labels
+            .iter()
+            .find(|label| label.eq_ignore_ascii_case(value))
+            .copied(),
+    }
+}
+
+/// Index digiKam stores for a label given as name or index, None when out of range
+pub fn label_index(labels: &[&'static str], value: &str) -> Option<usize> {
+    label_name(labels, value).and_then(|name| labels.iter().position(|label| *label == name))
+}
+
 /// Parse advanced filter string into FilterExpr using pest
 pub fn parse_advanced_filter(input: &str) -> anyhow::Result<FilterExpr> {
     use pest::Parser;
@@ -411,6 +822,8 @@ pub fn filter_expr_to_polars(expr: &FilterExpr, use_aggregated: bool) -> anyhow:
                 ExtractFilterType::Path => PATH_COLUMN,
                 ExtractFilterType::Event => EVENT_ID_COLUMN,
                 ExtractFilterType::Custom => CUSTOM_COLUMN,
+                ExtractFilterType::PickLabel => PICK_LABEL_COLUMN,
+                ExtractFilterType::ColorLabel => COLOR_LABEL_COLUMN,
                 ExtractFilterType::Advanced => {
                     return Err(anyhow::anyhow!(
                         "Advanced filter should not appear in conditions"
@@ -435,6 +848,12 @@ pub fn filter_expr_to_polars(expr: &FilterExpr, use_aggregated: bool) -> anyhow:
                         Ok(base_col
                             .list()
                             .contains(lit(condition.value.clone()), false))
+                    } else if let Some(labels) = condition.filter_type.labels() {
+                        // Labels match by name or digiKam index, unknown values as written
+                        let value = label_name(labels, &condition.value)
+                            .map(str::to_string)
+                            .unwrap_or_else(|| condition.value.clone());
+                        Ok(base_col.eq(lit(value)))
                     } else {
                         Ok(base_col.eq(lit(condition.value.clone())))
                     }
@@ -512,205 +931,1888 @@ pub fn filter_expr_to_polars(expr: &FilterExpr, use_aggregated: bool) -> anyhow:
     }
 }
 
-// Serval ignores
-fn is_ignored(entry: &DirEntry) -> bool {
-    entry
-        .file_name()
-        .to_str()
-        .map(|s| s.starts_with('.') || s.contains("精选")) // ignore 精选 and .dtrash
-        .unwrap_or(false)
+// Counting semaphore limiting concurrent file opens/copies in parallel stages
+struct IoPermits {
+    limit: usize,
+    available: Mutex<usize>,
+    released: Condvar,
 }
 
-// Serval bar style
-pub fn serval_pb_style() -> ProgressStyle {
-    ProgressStyle::default_bar()
-        .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})")
-        .unwrap()
-        .progress_chars("=> ")
+static IO_PERMITS: OnceLock<IoPermits> = OnceLock::new();
+
+pub fn configure_parallelism(
+    threads: Option<usize>,
+    io_concurrency: Option<usize>,
+) -> anyhow::Result<()> {
+    if let Some(threads) = threads {
+        if threads == 0 {
+            return Err(anyhow::anyhow!("Thread count must be greater than 0"));
+        }
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+    if let Some(limit) = io_concurrency {
+        if limit == 0 {
+            return Err(anyhow::anyhow!("IO concurrency must be greater than 0"));
+        }
+        let _ = IO_PERMITS.set(IoPermits {
+            limit,
+            available: Mutex::new(limit),
+            released: Condvar::new(),
+        });
+    }
+    if is_verbose() {
+        println!(
+            "Threads: {}, IO concurrency: {}",
+            rayon::current_num_threads(),
+            io_concurrency.map_or("unlimited".to_string(), |limit| limit.to_string())
+        );
+    }
+    Ok(())
 }
 
-pub fn configure_progress_bar(pb: &ProgressBar) {
-    pb.set_style(serval_pb_style());
-    pb.enable_steady_tick(std::time::Duration::from_secs(1));
+// Permit taken from IO_PERMITS, given back when dropped
+struct IoPermit(&'static IoPermits);
+
+impl IoPermit {
+    // Waits for a permit when --io-concurrency is set
+    fn acquire() -> Option<Self> {
+        let permits = IO_PERMITS.get()?;
+        let mut available = permits.available.lock().unwrap();
+        while *available == 0 {
+            available = permits.released.wait(available).unwrap();
+        }
+        *available -= 1;
+        Some(IoPermit(permits))
+    }
 }
 
-// workaround for https://github.com/rust-lang/rust/issues/42869
-// ref. https://github.com/sharkdp/fd/pull/72/files
-fn path_to_absolute(path: PathBuf) -> io::Result<PathBuf> {
-    if path.is_absolute() {
-        return Ok(path);
+impl Drop for IoPermit {
+    fn drop(&mut self) {
+        *self.0.available.lock().unwrap() += 1;
+        self.0.released.notify_one();
     }
-    let path = path.strip_prefix(".").unwrap_or(&path);
-    env::current_dir().map(|current_dir| current_dir.join(path))
 }
 
-pub fn absolute_path(path: PathBuf) -> io::Result<PathBuf> {
-    let path_buf = path_to_absolute(path)?;
-    #[cfg(windows)]
-    let path_buf = Path::new(
-        path_buf
-            .as_path()
-            .to_string_lossy()
-            .trim_start_matches(r"\\?\"),
-    )
-    .to_path_buf();
-    Ok(path_buf)
+// Run an IO-bound closure, waiting for a permit when --io-concurrency is set
+pub fn with_io_permit<T>(f: impl FnOnce() -> T) -> T {
+    let _permit = IoPermit::acquire();
+    f()
 }
 
-pub fn path_enumerate(root_dir: PathBuf, resource_type: ResourceType) -> Vec<PathBuf> {
-    WalkDir::new(root_dir)
-        .into_iter()
-        .filter_entry(|e| !is_ignored(e))
-        .par_bridge()
-        .filter_map(Result::ok)
-        .filter(|e| resource_type.is_resource(e.path()))
-        .map(|e| e.into_path())
-        .collect()
+/// Layout of the CSVs serval writes
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum CsvDialect {
+    /// Comma separated, ISO datetimes, `|` between multiple values
+    #[default]
+    Default,
+    /// Semicolon separated with decimal commas, day.month.year datetimes and `;` between
+    /// multiple values, as Excel opens them in European locales
+    ExcelEu,
 }
 
-pub fn resources_flatten(
-    deploy_dir: PathBuf,
-    working_dir: PathBuf,
-    resource_type: ResourceType,
-    dry_run: bool,
-    move_mode: bool,
-    prefix_deploy_id_in_name: bool,
-    keep_first_subdir: bool,
-) -> anyhow::Result<()> {
-    let deploy_id = deploy_dir
-        .file_name()
-        .ok_or_else(|| anyhow::anyhow!("Invalid deploy directory path: no filename"))?;
+static CSV_DIALECT: OnceLock<CsvDialect> = OnceLock::new();
 
-    let base_output_dir = working_dir.join(deploy_id);
-    fs::create_dir_all(base_output_dir.clone())?;
+const EXCEL_EU_DATETIME_FORMAT: &str = "%d.%m.%Y %H:%M:%S";
+const EXCEL_EU_DATE_FORMAT: &str = "%d.%m.%Y";
 
-    let resource_paths = path_enumerate(deploy_dir.clone(), resource_type);
-    let num_resource = resource_paths.len();
-    println!(
-        "{} {}(s) found in {}",
-        num_resource,
-        resource_type,
-        deploy_dir.to_string_lossy()
-    );
+pub fn configure_csv_dialect(dialect: CsvDialect) {
+    let _ = CSV_DIALECT.set(dialect);
+}
 
-    let mut visited_path: HashSet<String> = HashSet::new();
-    let pb = if !dry_run {
-        Some(indicatif::ProgressBar::new(num_resource as u64))
-    } else {
-        None
-    };
-    if let Some(pb_ref) = &pb {
-        configure_progress_bar(pb_ref);
+impl CsvDialect {
+    /// Dialect of this invocation, set once by --csv-dialect
+    pub fn current() -> Self {
+        CSV_DIALECT.get().copied().unwrap_or_default()
     }
-    for resource in resource_paths {
-        let mut output_path = PathBuf::new();
-        let resource_parent = resource.parent().unwrap();
-        let relative_path = resource.strip_prefix(&deploy_dir).unwrap_or(&resource);
-        let mut relative_parts: Vec<OsString> = relative_path
-            .iter()
-            .map(|part| part.to_os_string())
-            .collect();
-        if relative_parts.is_empty() {
-            relative_parts.push("unnamed_file".into());
-        }
 
-        let mut output_dir = base_output_dir.clone();
-        if keep_first_subdir && relative_parts.len() > 1 {
-            output_dir = output_dir.join(&relative_parts[0]);
-            if !dry_run {
-                fs::create_dir_all(output_dir.clone())?;
-            }
+    pub fn name(self) -> &'static str {
+        match self {
+            CsvDialect::Default => "default",
+            CsvDialect::ExcelEu => "excel-eu",
         }
+    }
 
-        let mut name_parts: Vec<OsString> = Vec::new();
-        if prefix_deploy_id_in_name {
-            name_parts.push(deploy_id.to_os_string());
+    /// Joins multiple species, individuals, ... in one cell
+    pub fn multi_value_separator(self) -> &'static str {
+        match self {
+            CsvDialect::Default => "|",
+            CsvDialect::ExcelEu => ";",
         }
-        name_parts.extend(relative_parts.into_iter());
-        let resource_name = name_parts.join(std::ffi::OsStr::new("-"));
-
-        output_path.push(output_dir.join(resource_name));
+    }
+}
 
-        if !dry_run {
-            if move_mode {
-                fs::rename(resource, output_path)?;
-            } else {
-                fs::copy(resource, output_path)?;
-            }
-            if let Some(pb_ref) = &pb {
-                pb_ref.inc(1);
-            }
-        } else if !visited_path.contains(resource_parent.to_string_lossy().as_ref()) {
-            visited_path.insert(resource_parent.to_string_lossy().to_string());
-            println!(
-                "DRYRUN sample: From {} to {}",
-                resource.display(),
-                output_path.display()
-            );
-        }
+/// CsvWriter for an output CSV, with a BOM and the separator and formats of --csv-dialect
+pub fn csv_writer<W: io::Write>(writer: W) -> CsvWriter<W> {
+    let writer = CsvWriter::new(writer).include_bom(true);
+    match CsvDialect::current() {
+        CsvDialect::Default => writer,
+        CsvDialect::ExcelEu => writer
+            .with_separator(b';')
+            .with_decimal_comma(true)
+            .with_datetime_format(Some(EXCEL_EU_DATETIME_FORMAT.into()))
+            .with_date_format(Some(EXCEL_EU_DATE_FORMAT.into())),
     }
-    if let Some(pb_ref) = pb {
-        pb_ref.finish();
+}
+
+/// `format` for the datetime columns of an output CSV, unless --csv-dialect has its own
+pub fn csv_datetime_format(format: &str) -> Option<PlSmallStr> {
+    match CsvDialect::current() {
+        CsvDialect::Default => Some(format.into()),
+        CsvDialect::ExcelEu => Some(EXCEL_EU_DATETIME_FORMAT.into()),
     }
-    Ok(())
 }
 
-pub fn deployments_align(
-    project_dir: PathBuf,
-    output_dir: PathBuf,
-    deploy_table: PathBuf,
-    resource_type: ResourceType,
-    dry_run: bool,
-    move_mode: bool,
-    keep_first_subdir: bool,
-) -> anyhow::Result<()> {
-    let deploy_df = CsvReadOptions::default()
-        .with_columns(csv_projection_columns(&[DEPLOYMENT_ID_COLUMN]))
-        .try_into_reader_with_file_path(Some(deploy_table))?
-        .finish()?;
-    reject_duplicate_csv_columns(&deploy_df)?;
-    let deploy_df = deploy_df
-        .lazy()
-        .select([col(DEPLOYMENT_ID_COLUMN)])
-        .collect()?;
-    let deploy_array = deploy_df[DEPLOYMENT_ID_COLUMN].str()?;
+/// How cells listing several species or individuals are split in a CSV from another tool,
+/// e.g. `fox; badger` from Timelapse
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MultivalueSeparator {
+    /// The most common of `|`, `;` and `,` in the cells
+    Auto,
+    Literal(String),
+}
 
-    let deploy_iter = deploy_array.iter();
-    let num_iter = deploy_iter.len();
-    let pb = indicatif::ProgressBar::new(num_iter as u64);
-    configure_progress_bar(&pb);
-    for deploy_id in deploy_iter {
-        let (_, collection_name) = deploy_id.unwrap().rsplit_once('_').unwrap();
-        let deploy_dir = project_dir.join(collection_name).join(deploy_id.unwrap());
-        let collection_output_dir = output_dir.join(collection_name);
-        resources_flatten(
-            deploy_dir,
-            collection_output_dir.clone(),
-            resource_type,
-            dry_run,
-            move_mode,
-            true,
-            keep_first_subdir,
-        )?;
-        pb.inc(1);
+const MULTIVALUE_SEPARATOR_CANDIDATES: &[&str] = &["|", ";", ","];
+
+impl MultivalueSeparator {
+    /// As given on the command line
+    pub fn name(&self) -> &str {
+        match self {
+            MultivalueSeparator::Auto => "auto",
+            MultivalueSeparator::Literal(separator) => separator,
+        }
     }
-    pb.finish();
-    Ok(())
 }
 
-pub fn deployments_rename(project_dir: PathBuf, dry_run: bool) -> anyhow::Result<()> {
-    // rename deployment path name to <deployment_name>_<collection_name>
-    let mut count = 0;
-    for entry in project_dir.read_dir()? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_dir() {
-            let mut collection_dir = path;
-            let original_collection_name = collection_dir
-                .file_name()
-                .and_then(|name| name.to_str())
-                .ok_or_else(|| anyhow::anyhow!("Invalid collection directory name"))?;
-            let collection_name_lower = original_collection_name.to_lowercase();
+// Parse --multivalue-separator, `auto` detects it from the cells
+pub fn parse_multivalue_separator_arg(value: &str) -> anyhow::Result<MultivalueSeparator> {
+    if value.trim().eq_ignore_ascii_case("auto") {
+        return Ok(MultivalueSeparator::Auto);
+    }
+    if value.trim().is_empty() {
+        return Err(anyhow::anyhow!(
+            "Multi-value separator can't be empty, use e.g. ';' or auto"
+        ));
+    }
+    Ok(MultivalueSeparator::Literal(value.trim().to_string()))
+}
+
+/// Split the species and individual cells listing several values on `separator` into one row
+/// per value, the other columns repeated as they are. Like observe, a row with several species
+/// and several individuals gives every combination.
+pub fn explode_multivalue_cells(
+    df: DataFrame,
+    separator: &MultivalueSeparator,
+) -> anyhow::Result<DataFrame> {
+    let columns: Vec<&str> = [TagType::Species.col_name(), TagType::Individual.col_name()]
+        .into_iter()
+        .filter(|name| {
+            df.column(name)
+                .is_ok_and(|column| column.dtype() == &DataType::String)
+        })
+        .collect();
+    let cells: Vec<Vec<Option<&str>>> = columns
+        .iter()
+        .map(|name| Ok(df.column(name)?.str()?.iter().collect()))
+        .collect::<PolarsResult<_>>()?;
+    let separator = match separator {
+        MultivalueSeparator::Literal(separator) => separator.clone(),
+        MultivalueSeparator::Auto => {
+            let num_cells_with = |candidate: &str| {
+                cells
+                    .iter()
+                    .flatten()
+                    .flatten()
+                    .filter(|cell| cell.contains(candidate))
+                    .count()
+            };
+            // Ties go to the first candidate
+            let detected = MULTIVALUE_SEPARATOR_CANDIDATES
+                .iter()
+                .map(|candidate| (*candidate, num_cells_with(candidate)))
+                .filter(|(_, count)| *count > 0)
+                .min_by_key(|(_, count)| std::cmp::Reverse(*count));
+            let Some((separator, count)) = detected else {
+                return Ok(df);
+            };
+            println!("Detected multi-value separator '{separator}' in {count} cell(s)");
+            separator.to_string()
+        }
+    };
+    let split = |cell: Option<&str>| -> Vec<Option<String>> {
+        let Some(cell) = cell else {
+            return vec![None];
+        };
+        let values: Vec<Option<String>> = cell
+            .split(separator.as_str())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| Some(value.to_string()))
+            .collect();
+        if values.is_empty() {
+            vec![Some(cell.to_string())]
+        } else {
+            values
+        }
+    };
+
+    let mut indices: Vec<IdxSize> = Vec::new();
+    let mut exploded: Vec<Vec<Option<String>>> = vec![Vec::new(); columns.len()];
+    let mut num_split_rows = 0;
+    for row in 0..df.height() {
+        let mut combinations: Vec<Vec<Option<String>>> = vec![Vec::new()];
+        for column_cells in &cells {
+            let values = split(column_cells[row]);
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    values.iter().map(move |value| {
+                        let mut combination = combination.clone();
+                        combination.push(value.clone());
+                        combination
+                    })
+                })
+                .collect();
+        }
+        if combinations.len() > 1 {
+            num_split_rows += 1;
+        }
+        for combination in combinations {
+            indices.push(row as IdxSize);
+            for (values, value) in exploded.iter_mut().zip(combination) {
+                values.push(value);
+            }
+        }
+    }
+    if num_split_rows == 0 {
+        return Ok(df);
+    }
+    let num_rows = df.height();
+    let mut df = df.take(&IdxCa::from_vec("".into(), indices))?;
+    for (name, values) in columns.iter().zip(exploded) {
+        df.with_column(Column::new((*name).into(), values))?;
+    }
+    println!(
+        "Split {num_split_rows} row(s) with several values on '{separator}', {num_rows} rows became {}",
+        df.height()
+    );
+    Ok(df)
+}
+
+#[derive(Debug)]
+pub struct FileTimeoutError(pub std::time::Duration);
+
+impl fmt::Display for FileTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Timed out after {}s", self.0.as_secs_f64())
+    }
+}
+
+impl std::error::Error for FileTimeoutError {}
+
+/// Every file worker is still blocked on a read that timed out
+#[derive(Debug)]
+pub struct FileWorkersStuckError(pub usize);
+
+impl fmt::Display for FileWorkersStuckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "All {} file workers are stuck on reads that timed out, stopping (is the storage still reachable?)",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for FileWorkersStuckError {}
+
+/// Sidecar that is neither UTF-8 nor UTF-16 with a byte order mark
+#[derive(Debug)]
+pub struct XmpDecodeError {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl fmt::Display for XmpDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Failed to decode {}: {}",
+            self.path.display(),
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for XmpDecodeError {}
+
+// Text of an XMP packet: UTF-8 with or without BOM, or UTF-16 with a BOM as some Windows
+// tools write it. The BOM is dropped, written back sidecars are plain UTF-8.
+pub fn decode_xmp_bytes(bytes: &[u8]) -> Result<String, String> {
+    let utf16 = |rest: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        if !rest.len().is_multiple_of(2) {
+            return Err("odd number of bytes for UTF-16".to_string());
+        }
+        char::decode_utf16(
+            rest.chunks_exact(2)
+                .map(|pair| from_bytes([pair[0], pair[1]])),
+        )
+        .collect::<Result<String, _>>()
+        .map_err(|e| format!("invalid UTF-16: {e}"))
+    };
+    match bytes {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => {
+            String::from_utf8(rest.to_vec()).map_err(|e| format!("invalid UTF-8: {e}"))
+        }
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        _ => String::from_utf8(bytes.to_vec()).map_err(|e| format!("invalid UTF-8: {e}")),
+    }
+}
+
+// Sidecar text for XmpMeta::from_str, undecodable files fail with an XmpDecodeError
+pub fn read_xmp_sidecar(path: &Path) -> anyhow::Result<String> {
+    let bytes = fs::read(path)?;
+    decode_xmp_bytes(&bytes).map_err(|reason| {
+        XmpDecodeError {
+            path: path.to_path_buf(),
+            reason,
+        }
+        .into()
+    })
+}
+
+// Parse durations like "30s", "2m", "500ms" (plain numbers are seconds)
+pub fn parse_duration_arg(value: &str) -> anyhow::Result<std::time::Duration> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(index) => value.split_at(index),
+        None => (value, "s"),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid duration: {value}"))?;
+    let seconds = match unit {
+        "ms" => number / 1000.0,
+        "s" => number,
+        "m" => number * 60.0,
+        "h" => number * 3600.0,
+        "d" => number * 86400.0,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Invalid duration unit in '{value}', use ms/s/m/h/d"
+            ));
+        }
+    };
+    Ok(std::time::Duration::from_secs_f64(seconds))
+}
+
+// Parse sizes like "200G", "500M", "1.5T" (binary units, plain numbers are bytes)
+pub fn parse_size_arg(value: &str) -> anyhow::Result<u64> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(index) => value.split_at(index),
+        None => (value, "B"),
+    };
+    let number: f64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid size: {value}"))?;
+    let unit = unit.trim().to_ascii_uppercase();
+    let prefix = unit
+        .strip_suffix("IB")
+        .or_else(|| unit.strip_suffix('B'))
+        .unwrap_or(&unit);
+    let exponent = match prefix {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Invalid size unit in '{value}', use K/M/G/T"
+            ));
+        }
+    };
+    Ok((number * 1024f64.powi(exponent)) as u64)
+}
+
+// Sizes as printed in estimates, e.g. 12.3 GiB
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// UTC offset like +08:00, +0530, -03 or Z
+pub fn parse_utc_offset_arg(value: &str) -> anyhow::Result<FixedOffset> {
+    let invalid =
+        || anyhow::anyhow!("Invalid UTC offset '{value}', expected e.g. +08:00 or -03:30");
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("z") {
+        return Ok(FixedOffset::east_opt(0).unwrap());
+    }
+    let (sign, digits) = match value.split_at_checked(1) {
+        Some(("+", digits)) => (1, digits),
+        Some(("-", digits)) => (-1, digits),
+        _ => return Err(invalid()),
+    };
+    let digits = digits.replace(':', "");
+    if !digits.bytes().all(|b| b.is_ascii_digit()) || ![2, 4].contains(&digits.len()) {
+        return Err(invalid());
+    }
+    let hours: i32 = digits[..2].parse()?;
+    let minutes: i32 = digits
+        .get(2..)
+        .filter(|m| !m.is_empty())
+        .map_or(Ok(0), str::parse)?;
+    if minutes >= 60 {
+        return Err(invalid());
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60)).ok_or_else(invalid)
+}
+
+/// UTC offsets of the deployments (utcOffset in the deploy table), with a fallback for the rest
+#[derive(Clone, Debug, Default)]
+pub struct UtcOffsets {
+    default: Option<FixedOffset>,
+    by_deployment: HashMap<String, FixedOffset>,
+}
+
+impl UtcOffsets {
+    pub fn new(default: Option<FixedOffset>, deploy_table: Option<&Path>) -> anyhow::Result<Self> {
+        let mut by_deployment = HashMap::new();
+        if let Some(deploy_table) = deploy_table {
+            let deploy_df = CsvReadOptions::default()
+                .with_infer_schema_length(Some(0))
+                .with_columns(csv_projection_columns(&[
+                    DEPLOYMENT_ID_COLUMN,
+                    UTC_OFFSET_COLUMN,
+                ]))
+                .try_into_reader_with_file_path(Some(deploy_table.to_path_buf()))
+                .and_then(|reader| reader.finish())
+                .map_err(|e| {
+                    anyhow::anyhow!(
+                        "Failed to read {DEPLOYMENT_ID_COLUMN} and {UTC_OFFSET_COLUMN} from {}: {e}",
+                        deploy_table.display()
+                    )
+                })?;
+            reject_duplicate_csv_columns(&deploy_df)?;
+            for (deployment, offset) in deploy_df
+                .column(DEPLOYMENT_ID_COLUMN)?
+                .str()?
+                .iter()
+                .zip(deploy_df.column(UTC_OFFSET_COLUMN)?.str()?.iter())
+            {
+                let (Some(deployment), Some(offset)) = (deployment, offset) else {
+                    continue;
+                };
+                if offset.trim().is_empty() {
+                    continue;
+                }
+                let offset = parse_utc_offset_arg(offset)
+                    .map_err(|e| anyhow::anyhow!("{deployment}: {e}"))?;
+                by_deployment.insert(deployment.to_string(), offset);
+            }
+        }
+        Ok(Self {
+            default,
+            by_deployment,
+        })
+    }
+
+    // Offset of the deployment named by a directory of the path (the deepest one), else the default
+    fn offset_for(&self, path: &str) -> Option<FixedOffset> {
+        normalize_path_str(path)
+            .split('/')
+            .rev()
+            .find_map(|component| self.by_deployment.get(component).copied())
+            .or(self.default)
+    }
+
+    /// datetime_utc of each row, ISO 8601 with Z; null without a datetime or a known offset
+    pub fn utc_column(&self, df: &DataFrame) -> anyhow::Result<Column> {
+        let paths = df.column(PATH_COLUMN)?.str()?.clone();
+        // Datetimes kept with their offsets (--timezone keep) are UTC instants already
+        let instants = matches!(
+            df.column(DATETIME_COLUMN)?.dtype(),
+            DataType::Datetime(_, Some(_))
+        );
+        let datetimes = df
+            .column(DATETIME_COLUMN)?
+            .cast(&DataType::Datetime(TimeUnit::Milliseconds, None))?
+            .cast(&DataType::Int64)?;
+        let mut num_missing_offset = 0;
+        let mut missing_deployments: BTreeSet<String> = BTreeSet::new();
+        let values: Vec<Option<String>> = paths
+            .iter()
+            .zip(datetimes.i64()?.iter())
+            .map(|(path, millis)| {
+                let (path, millis) = (path.unwrap_or_default(), millis?);
+                if instants {
+                    let utc = DateTime::from_timestamp_millis(millis)?;
+                    return Some(utc.format("%Y-%m-%dT%H:%M:%SZ").to_string());
+                }
+                let Some(offset) = self.offset_for(path) else {
+                    num_missing_offset += 1;
+                    if let Some(parent) = Path::new(path).parent().and_then(Path::file_name) {
+                        missing_deployments.insert(parent.to_string_lossy().into_owned());
+                    }
+                    return None;
+                };
+                let local = DateTime::from_timestamp_millis(millis)?;
+                let utc = local - chrono::Duration::seconds(offset.local_minus_utc().into());
+                Some(utc.format("%Y-%m-%dT%H:%M:%SZ").to_string())
+            })
+            .collect();
+        if num_missing_offset > 0 {
+            println!(
+                "Warning: {num_missing_offset} record(s) without a UTC offset, {DATETIME_UTC_COLUMN} left empty (in {})",
+                missing_deployments
+                    .into_iter()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        Ok(Column::new(DATETIME_UTC_COLUMN.into(), values))
+    }
+}
+
+type FileJob = Box<dyn FnOnce() + Send>;
+
+// Idle workers, and workers still busy with a file whose caller timed out
+#[derive(Default)]
+struct FileWorkerState {
+    idle: usize,
+    stuck: usize,
+}
+
+// Fixed pool of threads running per-file work for run_with_timeout. A read past its timeout
+// can't be cancelled, so its worker stays stuck until the blocking call returns.
+struct FileWorkers {
+    size: usize,
+    jobs: Mutex<std::sync::mpsc::Sender<FileJob>>,
+    state: Mutex<FileWorkerState>,
+    changed: Condvar,
+}
+
+static FILE_WORKERS: OnceLock<FileWorkers> = OnceLock::new();
+
+impl FileWorkers {
+    // One worker per thread of the global pool, as many as can wait on a file at once. No
+    // more than the IO permits, which stuck workers keep, so an idle worker can get one.
+    fn get() -> &'static Self {
+        FILE_WORKERS.get_or_init(|| {
+            let threads = rayon::current_num_threads();
+            let size = IO_PERMITS
+                .get()
+                .map_or(threads, |permits| threads.min(permits.limit));
+            let (sender, receiver) = std::sync::mpsc::channel::<FileJob>();
+            let receiver = Arc::new(Mutex::new(receiver));
+            for _ in 0..size {
+                let receiver = receiver.clone();
+                std::thread::Builder::new()
+                    .name("serval-file-worker".to_string())
+                    .spawn(move || {
+                        loop {
+                            let job = receiver.lock().unwrap().recv();
+                            let Ok(job) = job else { break };
+                            // A panicking job drops its sender, which its caller reports
+                            let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+                        }
+                    })
+                    .expect("Failed to spawn file worker");
+            }
+            FileWorkers {
+                size,
+                jobs: Mutex::new(sender),
+                state: Mutex::new(FileWorkerState {
+                    idle: size,
+                    stuck: 0,
+                }),
+                changed: Condvar::new(),
+            }
+        })
+    }
+
+    // Waits for an idle worker, failing once every worker is stuck
+    fn reserve(&self) -> anyhow::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.stuck >= self.size {
+                return Err(FileWorkersStuckError(self.size).into());
+            }
+            if state.idle > 0 {
+                state.idle -= 1;
+                return Ok(());
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+}
+
+// Gives a worker back to the pool once its job is done, however it ends. The job's flag
+// is set, under the state lock, when its caller timed out and counted the worker as stuck.
+struct ReleaseWorker(&'static FileWorkers, Arc<AtomicBool>);
+
+impl Drop for ReleaseWorker {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        if self.1.load(Ordering::SeqCst) {
+            state.stuck -= 1;
+        }
+        state.idle += 1;
+        self.0.changed.notify_all();
+    }
+}
+
+/// Run per-file work with an optional timeout, holding an IO permit until the work returns.
+///
+/// With a timeout the work runs on a fixed pool of worker threads. A timed-out read cannot be
+/// cancelled: its worker stays stuck, along with the IO permit, until the blocking call
+/// returns. Once every worker is stuck a [`FileWorkersStuckError`] is returned instead of
+/// waiting on a storage that no longer answers.
+pub fn run_with_timeout<T, F>(timeout: Option<std::time::Duration>, f: F) -> anyhow::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> anyhow::Result<T> + Send + 'static,
+{
+    let Some(timeout) = timeout else {
+        return with_io_permit(f);
+    };
+    let workers = FileWorkers::get();
+    workers.reserve()?;
+    let abandoned = Arc::new(AtomicBool::new(false));
+    let release = ReleaseWorker(workers, abandoned.clone());
+    let permit = IoPermit::acquire();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let job: FileJob = Box::new(move || {
+        let _permit = permit;
+        let _release = release;
+        let _ = sender.send(f());
+    });
+    workers
+        .jobs
+        .lock()
+        .unwrap()
+        .send(job)
+        .map_err(|_| anyhow::anyhow!("File workers stopped"))?;
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+            let mut state = workers.state.lock().unwrap();
+            // The result may have come in since, its worker is then being released
+            match receiver.try_recv() {
+                Ok(result) => return result,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    return Err(anyhow::anyhow!("Worker thread panicked"));
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => {}
+            }
+            abandoned.store(true, Ordering::SeqCst);
+            state.stuck += 1;
+            workers.changed.notify_all();
+            Err(FileTimeoutError(timeout).into())
+        }
+        Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+            Err(anyhow::anyhow!("Worker thread panicked"))
+        }
+    }
+}
+
+/// File of ignore patterns, one glob per line, read from the walked directory or its parents
+pub const IGNORE_FILE: &str = ".servalignore";
+
+// Built-in ignores and --ignore patterns of this invocation, see configure_ignores
+struct IgnoreConfig {
+    defaults: bool,
+    patterns: Vec<glob::Pattern>,
+}
+
+static IGNORE_CONFIG: OnceLock<IgnoreConfig> = OnceLock::new();
+
+// Patterns of the .servalignore files read so far, by the directory holding them
+static IGNORE_FILES: Mutex<BTreeMap<PathBuf, Arc<Vec<glob::Pattern>>>> =
+    Mutex::new(BTreeMap::new());
+
+pub fn configure_ignores(patterns: &[String], no_default_ignores: bool) -> anyhow::Result<()> {
+    let patterns = patterns
+        .iter()
+        .map(|pattern| compile_ignore_pattern(pattern))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let _ = IGNORE_CONFIG.set(IgnoreConfig {
+        defaults: !no_default_ignores,
+        patterns,
+    });
+    Ok(())
+}
+
+// A trailing slash ("test_shots/") names the same directory
+fn compile_ignore_pattern(pattern: &str) -> anyhow::Result<glob::Pattern> {
+    glob::Pattern::new(pattern.trim_end_matches('/'))
+        .map_err(|e| anyhow::anyhow!("Invalid ignore pattern {pattern:?}: {e}"))
+}
+
+// Built-in ignores: hidden entries (.dtrash among them) and 精选 selections
+fn is_default_ignored(name: &str) -> bool {
+    IGNORE_CONFIG.get().is_none_or(|config| config.defaults)
+        && (name.starts_with('.') || name.contains("精选"))
+}
+
+// Patterns match either the entry's name or its whole relative path
+fn matches_ignore_pattern(patterns: &[glob::Pattern], name: &str, relative: &str) -> bool {
+    patterns
+        .iter()
+        .any(|pattern| pattern.matches(name) || pattern.matches(relative))
+}
+
+/// Whether a path relative to a walked root is skipped by the built-in and --ignore rules,
+/// for listings that are not walked on disk (e.g. ZIP entries)
+pub fn is_ignored_relative(relative: &str) -> bool {
+    let patterns = IGNORE_CONFIG
+        .get()
+        .map_or(&[][..], |config| config.patterns.as_slice());
+    let mut prefix = String::new();
+    relative.split('/').any(|part| {
+        if !prefix.is_empty() {
+            prefix.push('/');
+        }
+        prefix.push_str(part);
+        is_default_ignored(part) || matches_ignore_pattern(patterns, part, &prefix)
+    })
+}
+
+// Nearest .servalignore at or above dir, parsed once per run. Invalid lines are skipped.
+fn ignore_file_patterns(dir: &Path) -> Option<(PathBuf, Arc<Vec<glob::Pattern>>)> {
+    let ignore_dir = dir
+        .ancestors()
+        .find(|ancestor| ancestor.join(IGNORE_FILE).is_file())?;
+    let mut ignore_files = IGNORE_FILES.lock().unwrap();
+    if let Some(patterns) = ignore_files.get(ignore_dir) {
+        return Some((ignore_dir.to_path_buf(), patterns.clone()));
+    }
+    let ignore_file = ignore_dir.join(IGNORE_FILE);
+    let patterns: Vec<glob::Pattern> = fs::read_to_string(&ignore_file)
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| match compile_ignore_pattern(line) {
+            Ok(pattern) => Some(pattern),
+            Err(e) => {
+                println!("Warning: {}: {e}, skipped", ignore_file.display());
+                None
+            }
+        })
+        .collect();
+    println!(
+        "Note: {} ignore pattern(s) read from {}",
+        patterns.len(),
+        ignore_file.display()
+    );
+    let patterns = Arc::new(patterns);
+    ignore_files.insert(ignore_dir.to_path_buf(), patterns.clone());
+    Some((ignore_dir.to_path_buf(), patterns))
+}
+
+// Ignore rules of one walk: --ignore patterns match below the walked root, those of a
+// .servalignore below the directory holding it
+struct IgnoreRules {
+    root: PathBuf,
+    // Walked root relative to the .servalignore directory, and its patterns
+    ignore_file: Option<(PathBuf, Arc<Vec<glob::Pattern>>)>,
+    // Canonical output directory inside the walked tree, see exclude_output_dir
+    excluded_dir: Option<PathBuf>,
+}
+
+impl IgnoreRules {
+    fn for_root(root: &Path) -> Self {
+        let ignore_file = absolute_path(root.to_path_buf())
+            .ok()
+            .and_then(|absolute_root| {
+                let (ignore_dir, patterns) = ignore_file_patterns(&absolute_root)?;
+                let offset = absolute_root.strip_prefix(&ignore_dir).ok()?.to_path_buf();
+                Some((offset, patterns))
+            });
+        IgnoreRules {
+            root: root.to_path_buf(),
+            ignore_file,
+            excluded_dir: None,
+        }
+    }
+
+    fn excluding(self, excluded_dir: Option<&Path>) -> Self {
+        IgnoreRules {
+            excluded_dir: excluded_dir.map(Path::to_path_buf),
+            ..self
+        }
+    }
+
+    fn is_ignored(&self, entry: &DirEntry) -> bool {
+        let name = entry.file_name().to_string_lossy();
+        is_default_ignored(&name)
+            || (entry.depth() > 0 && self.matches_patterns(&name, entry.path()))
+            || (entry.file_type().is_dir() && self.is_excluded_dir(entry.path()))
+    }
+
+    fn is_excluded_dir(&self, path: &Path) -> bool {
+        self.excluded_dir.as_ref().is_some_and(|excluded_dir| {
+            fs::canonicalize(path).is_ok_and(|path| &path == excluded_dir)
+        })
+    }
+
+    fn matches_patterns(&self, name: &str, path: &Path) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        IGNORE_CONFIG.get().is_some_and(|config| {
+            matches_ignore_pattern(
+                &config.patterns,
+                name,
+                &normalize_path_str(&relative.to_string_lossy()),
+            )
+        }) || self.ignore_file.as_ref().is_some_and(|(offset, patterns)| {
+            matches_ignore_pattern(
+                patterns,
+                name,
+                &normalize_path_str(&offset.join(relative).to_string_lossy()),
+            )
+        })
+    }
+}
+
+// Canonical form of a path that may not exist yet, resolving its deepest existing ancestor.
+// A dangling link is followed to its target, as the output written through it lands there.
+fn canonicalize_lenient(path: &Path) -> io::Result<PathBuf> {
+    canonicalize_lenient_at(path, 0)
+}
+
+// Links followed before giving up, as the kernel does
+const MAX_LINK_DEPTH: usize = 40;
+
+fn canonicalize_lenient_at(path: &Path, depth: usize) -> io::Result<PathBuf> {
+    let path = absolute_path(path.to_path_buf())?;
+    let mut missing: Vec<&std::ffi::OsStr> = Vec::new();
+    let mut existing = path.as_path();
+    loop {
+        let resolved = match fs::canonicalize(existing) {
+            Ok(canonical) => Some(canonical),
+            Err(_) if depth < MAX_LINK_DEPTH && existing.is_symlink() => {
+                let target = fs::read_link(existing)?;
+                let target = existing
+                    .parent()
+                    .map_or(target.clone(), |dir| dir.join(target));
+                Some(canonicalize_lenient_at(&target, depth + 1)?)
+            }
+            Err(_) => None,
+        };
+        if let Some(canonical) = resolved {
+            return Ok(missing
+                .iter()
+                .rev()
+                .fold(canonical, |path, part| path.join(part)));
+        }
+        match (existing.file_name(), existing.parent()) {
+            (Some(name), Some(parent)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return Ok(path),
+        }
+    }
+}
+
+// Output written inside the input tree would be picked up again by the walker (e.g. an
+// align rerun doubling every file), so it is returned for the walk to skip, with a note.
+// Symlinks are resolved first.
+pub fn exclude_output_dir(input_dir: &Path, output_dir: &Path) -> anyhow::Result<Option<PathBuf>> {
+    let input = canonicalize_lenient(input_dir)?;
+    let output = canonicalize_lenient(output_dir)?;
+    if output == input {
+        return Err(anyhow::anyhow!(
+            "Output directory {} is the input directory, choose a separate output directory",
+            output_dir.display()
+        ));
+    }
+    if output.starts_with(&input) {
+        println!(
+            "Note: output directory {} is inside {}, excluding it from the scan",
+            output.display(),
+            input_dir.display()
+        );
+        return Ok(Some(output));
+    }
+    Ok(None)
+}
+
+// Whether output_dir resolves to a directory inside input_dir
+pub fn is_inside_dir(input_dir: &Path, output_dir: &Path) -> bool {
+    match (
+        canonicalize_lenient(input_dir),
+        canonicalize_lenient(output_dir),
+    ) {
+        (Ok(input), Ok(output)) => output.starts_with(input),
+        _ => false,
+    }
+}
+
+// Absolute on this platform, or a Windows drive path read elsewhere
+fn is_absolute_path(path: &Path) -> bool {
+    path.is_absolute() || has_drive_prefix(&path.to_string_lossy())
+}
+
+// workaround for https://github.com/rust-lang/rust/issues/42869
+// ref. https://github.com/sharkdp/fd/pull/72/files
+fn path_to_absolute(path: PathBuf, current_dir: &Path) -> PathBuf {
+    let path = path.strip_prefix(".").unwrap_or(&path);
+    current_dir.join(path)
+}
+
+// Verbatim prefix left by canonicalize on Windows (\\?\D:\...), which most tools don't accept
+const VERBATIM_PREFIX: &str = r"\\?\";
+
+/// `path` without the Windows verbatim prefix, `\\?\UNC\server\share` back to
+/// `\\server\share`. None when there is no prefix.
+pub fn trim_verbatim_prefix(path: &str) -> Option<String> {
+    let trimmed = path.strip_prefix(VERBATIM_PREFIX)?;
+    Some(match trimmed.strip_prefix(r"UNC\") {
+        Some(share) => format!(r"\\{share}"),
+        None => trimmed.to_string(),
+    })
+}
+
+// Only paths carrying the prefix are rebuilt
+fn without_verbatim_prefix(path_buf: PathBuf) -> PathBuf {
+    match path_buf.to_str().and_then(trim_verbatim_prefix) {
+        Some(trimmed) if cfg!(windows) => PathBuf::from(trimmed),
+        _ => path_buf,
+    }
+}
+
+pub fn absolute_path(path: PathBuf) -> io::Result<PathBuf> {
+    if is_absolute_path(&path) {
+        return Ok(without_verbatim_prefix(path));
+    }
+    // Read each time, a library caller may change directory between calls
+    Ok(PathResolver::new()?.absolute(path))
+}
+
+/// [`absolute_path`] for the paths of one command, against the working directory read once
+/// when it is created instead of once per relative path
+pub struct PathResolver {
+    current_dir: PathBuf,
+}
+
+impl PathResolver {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            current_dir: env::current_dir()?,
+        })
+    }
+
+    pub fn absolute(&self, path: PathBuf) -> PathBuf {
+        if is_absolute_path(&path) {
+            return without_verbatim_prefix(path);
+        }
+        without_verbatim_prefix(path_to_absolute(path, &self.current_dir))
+    }
+}
+
+/// Separators of a column of paths unified to `/` in one pass, see [`normalize_path_str`]
+pub fn normalize_path_column(df: DataFrame, column: &str) -> PolarsResult<DataFrame> {
+    df.lazy()
+        .with_column(
+            col(column)
+                .str()
+                .replace_all(lit("\\"), lit("/"), true)
+                .alias(column),
+        )
+        .collect()
+}
+
+pub fn path_enumerate(root_dir: PathBuf, resource_type: ResourceType) -> Vec<PathBuf> {
+    path_enumerate_excluding(root_dir, resource_type, None)
+}
+
+/// [`path_enumerate`] skipping `excluded_dir`, as returned by [`exclude_output_dir`]
+pub fn path_enumerate_excluding(
+    root_dir: PathBuf,
+    resource_type: ResourceType,
+    excluded_dir: Option<&Path>,
+) -> Vec<PathBuf> {
+    let ignores = IgnoreRules::for_root(&root_dir).excluding(excluded_dir);
+    WalkDir::new(root_dir)
+        .into_iter()
+        .filter_entry(|e| !ignores.is_ignored(e))
+        .par_bridge()
+        .filter_map(Result::ok)
+        .filter(|e| resource_type.is_resource(e.path()))
+        .map(|e| e.into_path())
+        .collect()
+}
+
+// Files under a directory listed by subdirectory: images, videos, sidecars, ignored, bytes
+#[derive(Default)]
+struct ScanCounts {
+    images: u64,
+    videos: u64,
+    sidecars: u64,
+    ignored: u64,
+    size: u64,
+}
+
+// Top-level subdirectory of a path under root_dir, "." for files directly in it
+fn top_level_subdir(root_dir: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root_dir).unwrap_or(path);
+    match relative.components().next() {
+        Some(Component::Normal(name)) if relative.components().count() > 1 => {
+            name.to_string_lossy().into_owned()
+        }
+        _ => ".".to_string(),
+    }
+}
+
+/// What observe would read under `root_dir`, per top-level subdirectory, without opening any file.
+///
+/// Resources skipped by the ignore rules (hidden and 精选 entries, --ignore and .servalignore
+/// patterns) are counted separately, so the rules can be checked before a long run.
+pub fn scan_resources(root_dir: PathBuf) -> anyhow::Result<()> {
+    if !root_dir.is_dir() {
+        return Err(anyhow::anyhow!("{} is not a directory", root_dir.display()));
+    }
+    let mut counts: BTreeMap<String, ScanCounts> = BTreeMap::new();
+    for resource_type in [ResourceType::Image, ResourceType::Video, ResourceType::Xmp] {
+        for path in path_enumerate(root_dir.clone(), resource_type) {
+            let entry = counts
+                .entry(top_level_subdir(&root_dir, &path))
+                .or_default();
+            match resource_type {
+                ResourceType::Image => entry.images += 1,
+                ResourceType::Video => entry.videos += 1,
+                _ => entry.sidecars += 1,
+            }
+            entry.size += fs::metadata(&path).map_or(0, |metadata| metadata.len());
+        }
+    }
+    // Whole ignored subtrees are walked again only to count what they hold
+    let ignores = IgnoreRules::for_root(&root_dir);
+    let mut ignored_entries: Vec<PathBuf> = Vec::new();
+    WalkDir::new(&root_dir)
+        .into_iter()
+        .filter_entry(|entry| {
+            let ignored = entry.depth() > 0 && ignores.is_ignored(entry);
+            if ignored {
+                ignored_entries.push(entry.path().to_path_buf());
+            }
+            !ignored
+        })
+        .for_each(drop);
+    for ignored_entry in ignored_entries {
+        let num_ignored = WalkDir::new(&ignored_entry)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| ResourceType::All.is_resource(entry.path()))
+            .count();
+        if num_ignored > 0 {
+            counts
+                .entry(top_level_subdir(&root_dir, &ignored_entry))
+                .or_default()
+                .ignored += num_ignored as u64;
+        }
+    }
+
+    // Printed in full, a DataFrame display would elide rows of large projects
+    let width = counts
+        .keys()
+        .map(|subdir| subdir.chars().count())
+        .chain(["subdirectory".len()])
+        .max()
+        .unwrap_or_default();
+    println!(
+        "{:<width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>10}",
+        "subdirectory", "images", "videos", "sidecars", "ignored", "size (MB)"
+    );
+    let mut total = ScanCounts::default();
+    for (subdir, count) in &counts {
+        println!(
+            "{subdir:<width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>10.1}",
+            count.images,
+            count.videos,
+            count.sidecars,
+            count.ignored,
+            count.size as f64 / 1e6
+        );
+        total.images += count.images;
+        total.videos += count.videos;
+        total.sidecars += count.sidecars;
+        total.ignored += count.ignored;
+        total.size += count.size;
+    }
+    println!(
+        "Total: {} image(s), {} video(s), {} sidecar(s), {} ignored, {:.1} MB",
+        total.images,
+        total.videos,
+        total.sidecars,
+        total.ignored,
+        total.size as f64 / 1e6
+    );
+    Ok(())
+}
+
+/// Final path of each planned copy `(sort_key, target)`, numbering the names that collide.
+///
+/// Targets equal ignoring case (so also on case-insensitive file systems) or `occupied` are
+/// suffixed `<stem>_1.<ext>`, `<stem>_2.<ext>`, ... in the order of their sort key, the
+/// source's path relative to the input, and the first one not occupied keeps its name. Names
+/// are all decided before copying, so the same inputs give the same layout whatever the order
+/// the copies run in. Paths are returned in the order of `planned`.
+pub fn plan_collision_suffixes(
+    planned: &[(String, PathBuf)],
+    occupied: impl Fn(&Path) -> bool,
+) -> Vec<PathBuf> {
+    let target_key = |path: &Path| path.to_string_lossy().to_lowercase();
+    let mut order: Vec<usize> = (0..planned.len()).collect();
+    order.sort_by(|a, b| planned[*a].0.cmp(&planned[*b].0));
+    let mut taken: HashSet<String> = HashSet::new();
+    let mut finals: Vec<Option<PathBuf>> = vec![None; planned.len()];
+    // Unsuffixed names first, so a suffixed name never takes another file's own name
+    for &i in &order {
+        let target = &planned[i].1;
+        if !occupied(target) && taken.insert(target_key(target)) {
+            finals[i] = Some(target.clone());
+        }
+    }
+    for &i in &order {
+        if finals[i].is_some() {
+            continue;
+        }
+        let target = &planned[i].1;
+        let stem = target
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let extension = target
+            .extension()
+            .map(|extension| format!(".{}", extension.to_string_lossy()))
+            .unwrap_or_default();
+        let mut suffix = 1;
+        let renamed = loop {
+            let candidate = target.with_file_name(format!("{stem}_{suffix}{extension}"));
+            if !occupied(&candidate) && taken.insert(target_key(&candidate)) {
+                break candidate;
+            }
+            suffix += 1;
+        };
+        finals[i] = Some(renamed);
+    }
+    finals.into_iter().flatten().collect()
+}
+
+/// Outcome of flattening one directory, files that couldn't be copied or moved are listed
+/// with the error instead of stopping the run
+#[derive(Default)]
+pub struct FlattenSummary {
+    pub copied: usize,
+    pub skipped: usize,
+    pub failed: Vec<(PathBuf, String)>,
+    /// `(source, output)` of files suffixed because their flattened names collided
+    pub renamed: Vec<(PathBuf, PathBuf)>,
+}
+
+impl FlattenSummary {
+    pub fn num_found(&self) -> usize {
+        self.copied + self.skipped + self.failed.len()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn resources_flatten(
+    deploy_dir: PathBuf,
+    working_dir: PathBuf,
+    resource_type: ResourceType,
+    dry_run: bool,
+    move_mode: bool,
+    prefix_deploy_id_in_name: bool,
+    keep_first_subdir: bool,
+    fail_fast: bool,
+    excluded_dir: Option<&Path>,
+) -> anyhow::Result<FlattenSummary> {
+    let deploy_id = deploy_dir
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Invalid deploy directory path: no filename"))?
+        .to_string_lossy()
+        .into_owned();
+    let base_output_dir = working_dir.join(&deploy_id);
+    let summary = resources_flatten_into(
+        deploy_dir,
+        base_output_dir,
+        resource_type,
+        dry_run,
+        move_mode,
+        prefix_deploy_id_in_name,
+        keep_first_subdir,
+        fail_fast,
+        excluded_dir,
+        None,
+    )?;
+    if !dry_run {
+        println!(
+            "{} {}(s) {}, {} failed",
+            summary.copied,
+            resource_type,
+            if move_mode { "moved" } else { "copied" },
+            summary.failed.len()
+        );
+    }
+    for (path, e) in &summary.failed {
+        println!("  {}: {e}", path.display());
+    }
+    let renamed: Vec<(String, PathBuf, PathBuf)> = summary
+        .renamed
+        .iter()
+        .map(|(path, output)| (deploy_id.clone(), path.clone(), output.clone()))
+        .collect();
+    report_collisions(&working_dir, &renamed, dry_run)?;
+    Ok(summary)
+}
+
+pub const COLLISION_REPORT_FILE: &str = "renamed_on_collision.csv";
+
+// Print how many flattened names collided and, unless dry_run, write which source got which
+// suffixed name to renamed_on_collision.csv in output_dir
+fn report_collisions(
+    output_dir: &Path,
+    renamed: &[(String, PathBuf, PathBuf)],
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    if renamed.is_empty() {
+        return Ok(());
+    }
+    if dry_run {
+        println!(
+            "DRYRUN {} file(s) would be renamed on collision",
+            renamed.len()
+        );
+        return Ok(());
+    }
+    let mut df = DataFrame::new(
+        renamed.len(),
+        vec![
+            Column::new(
+                DEPLOYMENT_ID_COLUMN.into(),
+                renamed
+                    .iter()
+                    .map(|(deploy_id, _, _)| deploy_id.as_str())
+                    .collect::<Vec<_>>(),
+            ),
+            Column::new(
+                PATH_COLUMN.into(),
+                renamed
+                    .iter()
+                    .map(|(_, path, _)| path.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>(),
+            ),
+            Column::new(
+                "output_path".into(),
+                renamed
+                    .iter()
+                    .map(|(_, _, output)| output.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>(),
+            ),
+        ],
+    )?;
+    let report_path = output_dir.join(COLLISION_REPORT_FILE);
+    let mut file = File::create(&report_path)?;
+    csv_writer(&mut file).finish(&mut df)?;
+    println!(
+        "{} file(s) renamed on collision, see {}",
+        renamed.len(),
+        report_path.display()
+    );
+    Ok(())
+}
+
+// Flatten deploy_dir directly into base_output_dir. Files that fail are collected in the
+// summary and the others still copied, unless fail_fast returns the first error.
+#[allow(clippy::too_many_arguments)]
+fn resources_flatten_into(
+    deploy_dir: PathBuf,
+    base_output_dir: PathBuf,
+    resource_type: ResourceType,
+    dry_run: bool,
+    move_mode: bool,
+    prefix_deploy_id_in_name: bool,
+    keep_first_subdir: bool,
+    fail_fast: bool,
+    excluded_dir: Option<&Path>,
+    multi: Option<&ServalMultiProgress>,
+) -> anyhow::Result<FlattenSummary> {
+    // Messages go above the other lines when running alongside other deployments
+    let print = |msg: String| match multi {
+        Some(multi) => multi.println(msg),
+        None => println!("{msg}"),
+    };
+    let deploy_id = deploy_dir
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("Invalid deploy directory path: no filename"))?;
+
+    fs::create_dir_all(base_output_dir.clone())?;
+
+    let resource_paths = path_enumerate_excluding(deploy_dir.clone(), resource_type, excluded_dir);
+    let num_resource = resource_paths.len();
+    print(format!(
+        "{} {}(s) found in {}",
+        num_resource,
+        resource_type,
+        deploy_dir.to_string_lossy()
+    ));
+
+    let mut summary = FlattenSummary::default();
+    let mut visited_path: HashSet<String> = HashSet::new();
+    // Sizes are only needed for the progress bar, so dry runs skip the stat entirely
+    let resource_sizes: Vec<u64> = if !dry_run {
+        resource_paths
+            .par_iter()
+            .map(|resource| fs::metadata(resource).map_or(0, |meta| meta.len()))
+            .collect()
+    } else {
+        vec![0; num_resource]
+    };
+    let pb = if !dry_run {
+        let total_bytes = resource_sizes.iter().sum();
+        Some(match multi {
+            Some(multi) => multi.add_bytes(
+                total_bytes,
+                num_resource as u64,
+                &deploy_id.to_string_lossy(),
+            ),
+            None => ServalProgress::new_bytes(total_bytes, num_resource as u64, "copied"),
+        })
+    } else {
+        None
+    };
+    // Joining directories into names can collide (a-b/c.JPG, a/b-c.JPG), those get suffixed.
+    // Files left by earlier runs are overwritten, so a rerun gives the same layout.
+    let planned: Vec<(String, PathBuf)> = resource_paths
+        .iter()
+        .map(|resource| {
+            let relative_path = resource.strip_prefix(&deploy_dir).unwrap_or(resource);
+            let mut relative_parts: Vec<OsString> = relative_path
+                .iter()
+                .map(|part| part.to_os_string())
+                .collect();
+            if relative_parts.is_empty() {
+                relative_parts.push("unnamed_file".into());
+            }
+
+            let mut output_dir = base_output_dir.clone();
+            if keep_first_subdir && relative_parts.len() > 1 {
+                output_dir = output_dir.join(&relative_parts[0]);
+            }
+
+            let mut name_parts: Vec<OsString> = Vec::new();
+            if prefix_deploy_id_in_name {
+                name_parts.push(deploy_id.to_os_string());
+            }
+            name_parts.extend(relative_parts);
+            let resource_name = name_parts.join(std::ffi::OsStr::new("-"));
+            let sort_key = relative_path
+                .iter()
+                .map(|part| part.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            (sort_key, output_dir.join(resource_name))
+        })
+        .collect();
+    let output_paths = plan_collision_suffixes(&planned, |_| false);
+    for ((resource, (_, target)), output_path) in
+        resource_paths.iter().zip(&planned).zip(&output_paths)
+    {
+        if target != output_path {
+            summary
+                .renamed
+                .push((resource.clone(), output_path.clone()));
+        }
+    }
+
+    for ((resource, output_path), resource_size) in resource_paths
+        .into_iter()
+        .zip(output_paths)
+        .zip(resource_sizes)
+    {
+        crate::lock::check_interrupted()?;
+        let resource_parent = resource.parent().unwrap();
+
+        if !dry_run {
+            let result = fs::create_dir_all(output_path.parent().unwrap()).and_then(|_| {
+                if move_mode {
+                    fs::rename(&resource, &output_path)
+                } else {
+                    fs::copy(&resource, &output_path).map(|_| ())
+                }
+            });
+            if let Some(pb_ref) = &pb {
+                pb_ref.inc_file(resource_size);
+            }
+            match result {
+                Ok(()) => summary.copied += 1,
+                Err(e) if fail_fast => {
+                    return Err(anyhow::anyhow!("{}: {e}", resource.display()));
+                }
+                Err(e) => {
+                    print(format!("Error: {}: {e}", resource.display()));
+                    summary.failed.push((resource, e.to_string()));
+                }
+            }
+        } else {
+            summary.skipped += 1;
+            if visited_path.insert(resource_parent.to_string_lossy().to_string()) {
+                print(format!(
+                    "DRYRUN sample: From {} to {}",
+                    resource.display(),
+                    output_path.display()
+                ));
+            }
+        }
+    }
+    if let Some(pb_ref) = pb {
+        pb_ref.finish();
+    }
+    Ok(summary)
+}
+
+// Placeholder resolved from the deploymentID suffix rather than a deploy table column
+const COLLECTION_PLACEHOLDER: &str = "collection";
+pub const DEFAULT_ALIGN_STRUCTURE: &str = "{collection}/{deploymentID}";
+
+// Placeholder names of an align structure template, e.g. {year}/{collection}/{deploymentID}
+fn structure_placeholders(template: &str) -> anyhow::Result<Vec<String>> {
+    let placeholder_re = regex::Regex::new(r"\{([^{}]*)\}")?;
+    let placeholders: Vec<String> = placeholder_re
+        .captures_iter(template)
+        .map(|caps| caps[1].to_string())
+        .collect();
+    if placeholders.iter().any(|name| name.is_empty())
+        || placeholder_re
+            .replace_all(template, "")
+            .contains(['{', '}'])
+    {
+        return Err(anyhow::anyhow!("Invalid structure template: {template}"));
+    }
+    Ok(placeholders)
+}
+
+// Make a deploy table value safe to use as a single path component
+fn sanitize_path_component(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_control() || matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect::<String>()
+        .trim_matches(|c: char| c.is_whitespace() || c == '.')
+        .to_string()
+}
+
+// Destination root of a deployment, relative to the output directory
+fn resolve_structure(
+    template: &str,
+    deploy_id: &str,
+    values: &[(String, String)],
+) -> anyhow::Result<PathBuf> {
+    let placeholder_re = regex::Regex::new(r"\{([^{}]*)\}")?;
+    let mut missing: Option<String> = None;
+    let resolved = placeholder_re.replace_all(template, |caps: &regex::Captures| {
+        let value = values
+            .iter()
+            .find(|(name, _)| name == &caps[1])
+            .map(|(_, value)| sanitize_path_component(value))
+            .unwrap_or_default();
+        if value.is_empty() && missing.is_none() {
+            missing = Some(caps[1].to_string());
+        }
+        value
+    });
+    if let Some(name) = missing {
+        return Err(anyhow::anyhow!(
+            "Empty value for {{{name}}} in deployment {deploy_id}"
+        ));
+    }
+    let mut root = PathBuf::new();
+    for part in resolved.split(['/', '\\']) {
+        match part.trim() {
+            "" | "." => {}
+            ".." => {
+                return Err(anyhow::anyhow!(
+                    "Structure template must not contain '..': {template}"
+                ));
+            }
+            part => root.push(part),
+        }
+    }
+    Ok(root)
+}
+
+// Child directory of parent whose name equals name, ignoring case
+fn find_dir_case_insensitive(parent: &Path, name: &str) -> Option<PathBuf> {
+    let name = name.to_lowercase();
+    parent
+        .read_dir()
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.is_dir()
+                && path
+                    .file_name()
+                    .is_some_and(|dir_name| dir_name.to_string_lossy().to_lowercase() == name)
+        })
+}
+
+// Locate <project>/<collection>/<deploymentID>, falling back to case-insensitive
+// names and one extra directory level. Every fallback used is described in the Vec.
+pub(crate) fn resolve_deploy_dir(
+    project_dir: &Path,
+    collection_name: &str,
+    deploy_id: &str,
+) -> (Option<PathBuf>, Vec<String>) {
+    let mut fallbacks = Vec::new();
+    let mut collection_dir = project_dir.join(collection_name);
+    if !collection_dir.is_dir() {
+        match find_dir_case_insensitive(project_dir, collection_name) {
+            Some(dir) => {
+                fallbacks.push(format!(
+                    "collection {collection_name} found as {}",
+                    dir.display()
+                ));
+                collection_dir = dir;
+            }
+            None => return (None, fallbacks),
+        }
+    }
+    let deploy_dir = collection_dir.join(deploy_id);
+    if deploy_dir.is_dir() {
+        return (Some(deploy_dir), fallbacks);
+    }
+    if let Some(dir) = find_dir_case_insensitive(&collection_dir, deploy_id) {
+        fallbacks.push(format!("deployment found as {}", dir.display()));
+        return (Some(dir), fallbacks);
+    }
+    let nested = collection_dir
+        .read_dir()
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .find_map(|subdir| find_dir_case_insensitive(&subdir, deploy_id));
+    if let Some(dir) = nested {
+        fallbacks.push(format!(
+            "deployment found one level deeper as {}",
+            dir.display()
+        ));
+        return (Some(dir), fallbacks);
+    }
+    (None, fallbacks)
+}
+
+// Expand a comma separated name list where `@file` entries read one name per line
+pub fn expand_name_list(values: Vec<String>) -> anyhow::Result<Vec<String>> {
+    let mut names = Vec::new();
+    for value in values {
+        if let Some(list_path) = value.strip_prefix('@') {
+            let content = fs::read_to_string(list_path)
+                .map_err(|e| anyhow::anyhow!("Failed to read name list {list_path}: {e}"))?;
+            names.extend(
+                content
+                    .lines()
+                    .flat_map(|line| line.split(','))
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty() && !name.starts_with('#'))
+                    .map(str::to_string),
+            );
+        } else {
+            names.extend(
+                value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|name| !name.is_empty())
+                    .map(str::to_string),
+            );
+        }
+    }
+    Ok(names)
+}
+
+// Exit code when some deployments or files failed to align while the others completed
+pub const ALIGN_FAILED_EXIT_CODE: i32 = 3;
+
+/// Deployments that failed as a whole and files that failed within the others
+#[derive(Default)]
+pub struct AlignReport {
+    pub failed_deployments: Vec<String>,
+    pub failed_files: Vec<(String, PathBuf, String)>,
+    /// `(deploymentID, source, output)` of files suffixed on collision
+    pub renamed_files: Vec<(String, PathBuf, PathBuf)>,
+}
+
+impl AlignReport {
+    pub fn is_complete(&self) -> bool {
+        self.failed_deployments.is_empty() && self.failed_files.is_empty()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn deployments_align(
+    project_dir: PathBuf,
+    output_dir: PathBuf,
+    deploy_table: PathBuf,
+    resource_type: ResourceType,
+    dry_run: bool,
+    move_mode: bool,
+    keep_first_subdir: bool,
+    structure: Option<String>,
+    only: Vec<String>,
+    skip: Vec<String>,
+    jobs: usize,
+    fail_fast: bool,
+    excluded_dir: Option<&Path>,
+) -> anyhow::Result<AlignReport> {
+    if jobs == 0 {
+        return Err(anyhow::anyhow!("Job count must be greater than 0"));
+    }
+    let structure = structure.unwrap_or_else(|| DEFAULT_ALIGN_STRUCTURE.to_string());
+    let placeholders = structure_placeholders(&structure)?;
+    let mut table_columns: Vec<&str> = vec![DEPLOYMENT_ID_COLUMN];
+    for name in &placeholders {
+        if name != COLLECTION_PLACEHOLDER && !table_columns.contains(&name.as_str()) {
+            table_columns.push(name);
+        }
+    }
+
+    let deploy_df = CsvReadOptions::default()
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(deploy_table))?
+        .finish()?;
+    reject_duplicate_csv_columns(&deploy_df)?;
+    // Validate the template against the deploy table before touching any file
+    let missing_columns: Vec<&str> = table_columns
+        .iter()
+        .filter(|name| {
+            !deploy_df
+                .get_column_names()
+                .iter()
+                .any(|column| column.as_str() == **name)
+        })
+        .copied()
+        .collect();
+    if !missing_columns.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Columns referenced by --structure not found in deploy table: {}",
+            missing_columns.join(", ")
+        ));
+    }
+    let deploy_df = deploy_df
+        .lazy()
+        .select(
+            table_columns
+                .iter()
+                .map(|name| col(*name))
+                .collect::<Vec<_>>(),
+        )
+        .collect()?;
+    let deploy_array = deploy_df[DEPLOYMENT_ID_COLUMN].str()?;
+
+    let unknown_names: Vec<&str> = only
+        .iter()
+        .chain(skip.iter())
+        .filter(|name| !deploy_array.iter().any(|id| id == Some(name.as_str())))
+        .map(String::as_str)
+        .collect();
+    if !unknown_names.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Deployments not found in deploy table: {}",
+            unknown_names.join(", ")
+        ));
+    }
+
+    let mut deployments: Vec<(String, PathBuf, PathBuf)> = Vec::new();
+    let mut missing_deployments: Vec<String> = Vec::new();
+    let mut num_filtered = 0;
+    for (row, deploy_id) in deploy_array.iter().enumerate() {
+        let deploy_id =
+            deploy_id.ok_or_else(|| anyhow::anyhow!("Missing deploymentID in row {}", row + 1))?;
+        if (!only.is_empty() && !only.iter().any(|name| name == deploy_id))
+            || skip.iter().any(|name| name == deploy_id)
+        {
+            num_filtered += 1;
+            continue;
+        }
+        let (_, collection_name) = deploy_id.rsplit_once('_').ok_or_else(|| {
+            anyhow::anyhow!(
+                "Invalid deploymentID (expected <deployment>_<collection>): {deploy_id}"
+            )
+        })?;
+        let mut values = vec![(
+            COLLECTION_PLACEHOLDER.to_string(),
+            collection_name.to_string(),
+        )];
+        for name in &table_columns {
+            let value = deploy_df[*name].str()?.get(row).unwrap_or_default();
+            values.push((name.to_string(), value.to_string()));
+        }
+        let destination = output_dir.join(resolve_structure(&structure, deploy_id, &values)?);
+        let (deploy_dir, fallbacks) = resolve_deploy_dir(&project_dir, collection_name, deploy_id);
+        for fallback in fallbacks {
+            println!("Warning: {deploy_id}: {fallback}");
+        }
+        match deploy_dir {
+            Some(deploy_dir) => deployments.push((deploy_id.to_string(), deploy_dir, destination)),
+            None => {
+                println!(
+                    "Warning: {deploy_id}: directory not found under {}",
+                    project_dir.display()
+                );
+                missing_deployments.push(deploy_id.to_string());
+            }
+        }
+    }
+
+    let num_processed = deployments.len();
+    // Separate pool so --jobs bounds concurrent deployments (disk queues), not --threads
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(jobs).build()?;
+    let multi = ServalMultiProgress::new();
+    let pb = multi.add(num_processed as u64, "aligning deployments");
+    let results: Vec<(String, anyhow::Result<FlattenSummary>)> = pool.install(|| {
+        deployments
+            .into_par_iter()
+            .map(|(deploy_id, deploy_dir, destination)| {
+                if dry_run {
+                    multi.println(format!("DRYRUN {} -> {}", deploy_id, destination.display()));
+                }
+                let result = resources_flatten_into(
+                    deploy_dir,
+                    destination,
+                    resource_type,
+                    dry_run,
+                    move_mode,
+                    true,
+                    keep_first_subdir,
+                    fail_fast,
+                    excluded_dir,
+                    Some(&multi),
+                );
+                if let Err(e) = &result
+                    && !e.is::<crate::lock::Interrupted>()
+                {
+                    multi.println(format!("Error: {deploy_id}: {e}"));
+                }
+                pb.inc(1);
+                (deploy_id, result)
+            })
+            .collect()
+    });
+    pb.finish();
+    crate::lock::check_interrupted()?;
+
+    let mut empty_deployments: Vec<String> = Vec::new();
+    let mut failed_deployments: Vec<(String, anyhow::Error)> = Vec::new();
+    let mut report = AlignReport::default();
+    let mut num_copied = 0;
+    for (deploy_id, result) in results {
+        match result {
+            Ok(summary) if summary.num_found() == 0 => {
+                println!("Warning: {deploy_id}: no {resource_type} found");
+                empty_deployments.push(deploy_id);
+            }
+            Ok(summary) => {
+                num_copied += summary.copied;
+                report.renamed_files.extend(
+                    summary
+                        .renamed
+                        .into_iter()
+                        .map(|(path, output)| (deploy_id.clone(), path, output)),
+                );
+                report.failed_files.extend(
+                    summary
+                        .failed
+                        .into_iter()
+                        .map(|(path, e)| (deploy_id.clone(), path, e)),
+                );
+            }
+            Err(e) => failed_deployments.push((deploy_id, e)),
+        }
+    }
+    println!("Processed {num_processed} deployments, filtered out {num_filtered}");
+    if !dry_run {
+        println!(
+            "{num_copied} {resource_type}(s) {}, {} failed",
+            if move_mode { "moved" } else { "copied" },
+            report.failed_files.len()
+        );
+    }
+    // Deployments finish in any order, the report doesn't
+    report.renamed_files.sort();
+    report_collisions(&output_dir, &report.renamed_files, dry_run)?;
+    if !missing_deployments.is_empty() {
+        println!(
+            "Warning: {} deployment(s) not found: {}",
+            missing_deployments.len(),
+            missing_deployments.join(", ")
+        );
+    }
+    if !empty_deployments.is_empty() {
+        println!(
+            "Warning: {} deployment(s) without {}: {}",
+            empty_deployments.len(),
+            resource_type,
+            empty_deployments.join(", ")
+        );
+    }
+    if !failed_deployments.is_empty() {
+        println!("{} deployment(s) failed:", failed_deployments.len());
+        for (deploy_id, e) in &failed_deployments {
+            println!("  {deploy_id}: {e}");
+        }
+    }
+    if !report.failed_files.is_empty() {
+        println!("{} file(s) failed:", report.failed_files.len());
+        for (deploy_id, path, e) in &report.failed_files {
+            println!("  {deploy_id}: {}: {e}", path.display());
+        }
+    }
+    report.failed_deployments = failed_deployments
+        .into_iter()
+        .map(|(deploy_id, _)| deploy_id)
+        .collect();
+    Ok(report)
+}
+
+pub fn deployments_rename(project_dir: PathBuf, dry_run: bool) -> anyhow::Result<()> {
+    // rename deployment path name to <deployment_name>_<collection_name>
+    let mut count = 0;
+    for entry in project_dir.read_dir()? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_dir() {
+            let mut collection_dir = path;
+            let original_collection_name = collection_dir
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| anyhow::anyhow!("Invalid collection directory name"))?;
+            let collection_name_lower = original_collection_name.to_lowercase();
             if original_collection_name != collection_name_lower {
                 let mut new_collection_dir = collection_dir.clone();
                 new_collection_dir.set_file_name(&collection_name_lower);
@@ -773,12 +2875,15 @@ pub fn deployments_rename(project_dir: PathBuf, dry_run: bool) -> anyhow::Result
 }
 
 // copy xmp files to output_dir and keep the directory structure
-pub fn copy_xmp(source_dir: PathBuf, output_dir: PathBuf) -> anyhow::Result<()> {
-    let xmp_paths = path_enumerate(source_dir.clone(), ResourceType::Xmp);
+pub fn copy_xmp(
+    source_dir: PathBuf,
+    output_dir: PathBuf,
+    excluded_dir: Option<&Path>,
+) -> anyhow::Result<()> {
+    let xmp_paths = path_enumerate_excluding(source_dir.clone(), ResourceType::Xmp, excluded_dir);
     let num_xmp = xmp_paths.len();
     println!("{num_xmp} xmp files found");
-    let pb = indicatif::ProgressBar::new(num_xmp as u64);
-    configure_progress_bar(&pb);
+    let pb = ServalProgress::new(num_xmp as u64, "copying XMP files");
 
     for xmp in xmp_paths {
         let mut output_path = output_dir.clone();
@@ -792,41 +2897,404 @@ pub fn copy_xmp(source_dir: PathBuf, output_dir: PathBuf) -> anyhow::Result<()>
     Ok(())
 }
 
-// Sync XMP metadata to corresponding media files
-pub fn sync_xmp_to_media(xmp_path: &Path) -> anyhow::Result<()> {
-    let media_path_str = match xmp_path.to_str() {
-        Some(path_str) => path_str.trim_end_matches(".xmp"),
-        None => {
+/// Media file a sidecar belongs to, see `resolve_sidecar_media`
+pub enum SidecarMedia {
+    Found(PathBuf),
+    Missing(PathBuf),
+    Ambiguous(Vec<PathBuf>),
+}
+
+/// Sidecar of a media file in the appended convention, `IMG_0001.JPG` -> `IMG_0001.JPG.xmp`
+pub fn sidecar_path_for(media: &Path) -> PathBuf {
+    media.with_added_extension("xmp")
+}
+
+/// Path left after stripping exactly one (case-insensitive) `.xmp` extension,
+/// `None` when `sidecar` is not an XMP file
+pub fn media_path_for(sidecar: &Path) -> Option<PathBuf> {
+    resource_extension(sidecar)
+        .is_some_and(|ext| XMP_EXTENSIONS.contains(&ext.as_str()))
+        .then(|| sidecar.with_extension(""))
+}
+
+// Resolve the media of a sidecar, either appended (IMG_0001.JPG.xmp) or
+// extension-replaced (IMG_0001.xmp). The appended name wins when its media exists,
+// an extension-replaced sidecar matching several media files is never guessed.
+pub fn resolve_sidecar_media(xmp_path: &Path) -> SidecarMedia {
+    let Some(appended) = media_path_for(xmp_path) else {
+        return SidecarMedia::Missing(xmp_path.to_path_buf());
+    };
+    if resource_extension(&appended).is_some_and(|ext| MEDIA_EXTENSIONS.contains(&ext.as_str())) {
+        return if appended.exists() {
+            SidecarMedia::Found(appended)
+        } else {
+            SidecarMedia::Missing(appended)
+        };
+    }
+
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    let mut seen: HashSet<PathBuf> = HashSet::new();
+    for ext in MEDIA_EXTENSIONS {
+        for ext in [ext.to_string(), ext.to_ascii_uppercase()] {
+            let candidate = xmp_path.with_extension(ext);
+            // Case-insensitive filesystems report both casings for the same file
+            if candidate.is_file()
+                && seen.insert(fs::canonicalize(&candidate).unwrap_or(candidate.clone()))
+            {
+                candidates.push(candidate);
+            }
+        }
+    }
+    match candidates.len() {
+        0 => SidecarMedia::Missing(appended),
+        1 => SidecarMedia::Found(candidates.pop().unwrap()),
+        _ => SidecarMedia::Ambiguous(candidates),
+    }
+}
+
+// Existing sidecar of a media file, either appended or an extension-replaced one
+// that unambiguously belongs to this media
+pub fn existing_sidecar_for(media: &Path) -> Option<PathBuf> {
+    let appended = sidecar_path_for(media);
+    if appended.exists() {
+        return Some(appended);
+    }
+    let replaced = media.with_extension("xmp");
+    if replaced.is_file()
+        && let SidecarMedia::Found(owner) = resolve_sidecar_media(&replaced)
+        && owner == media
+    {
+        return Some(replaced);
+    }
+    None
+}
+
+// Pair a resource with its media: (media path, sidecar exists, media exists).
+// The media path is None for ambiguous sidecars.
+pub fn pair_resource_media(path: &Path) -> (Option<PathBuf>, bool, bool) {
+    if media_path_for(path).is_some() {
+        match resolve_sidecar_media(path) {
+            SidecarMedia::Found(media) => (Some(media), true, true),
+            SidecarMedia::Missing(media) => (Some(media), true, false),
+            SidecarMedia::Ambiguous(_) => (None, true, false),
+        }
+    } else {
+        (
+            Some(path.to_path_buf()),
+            existing_sidecar_for(path).is_some(),
+            path.exists(),
+        )
+    }
+}
+
+// digiKam moves deleted files to .dtrash/files under the album root, each with a JSON record
+// .dtrash/info/<name>.dtrashinfo holding the original path. Sidecars moved along keep the
+// media name, e.g. files/<name>.JPG.xmp
+pub const DIGIKAM_TRASH_DIR: &str = ".dtrash";
+
+/// Files in the digiKam trash under a directory, with their original path when recorded
+#[derive(Default)]
+pub struct DigikamTrash {
+    originals: HashMap<PathBuf, Option<PathBuf>>,
+    trashed_originals: HashSet<PathBuf>,
+}
+
+impl DigikamTrash {
+    pub fn scan(root_dir: &Path) -> Self {
+        let is_trash_dir =
+            |entry: &DirEntry| entry.file_type().is_dir() && entry.file_name() == DIGIKAM_TRASH_DIR;
+        let ignores = IgnoreRules::for_root(root_dir);
+        let mut trash = DigikamTrash::default();
+        let trash_dirs: Vec<PathBuf> = WalkDir::new(root_dir)
+            .into_iter()
+            .filter_entry(|entry| !ignores.is_ignored(entry) || is_trash_dir(entry))
+            .filter_map(Result::ok)
+            .filter(is_trash_dir)
+            .map(DirEntry::into_path)
+            .collect();
+        for trash_dir in trash_dirs {
+            let Ok(entries) = fs::read_dir(trash_dir.join("files")) else {
+                continue;
+            };
+            for path in entries.filter_map(Result::ok).map(|entry| entry.path()) {
+                if !path.is_file() {
+                    continue;
+                }
+                let original = Self::recorded_original(&trash_dir, &path);
+                if let Some(original) = &original {
+                    trash.trashed_originals.insert(original.clone());
+                }
+                trash.originals.insert(path, original);
+            }
+        }
+        trash
+    }
+
+    fn recorded_original(trash_dir: &Path, trashed: &Path) -> Option<PathBuf> {
+        // A sidecar trashed with its media shares the media's record
+        let media = media_path_for(trashed);
+        let base = media.as_deref().unwrap_or(trashed).file_stem()?.to_str()?;
+        let info =
+            fs::read_to_string(trash_dir.join("info").join(format!("{base}.dtrashinfo"))).ok()?;
+        let record: serde_json::Value = serde_json::from_str(&info).ok()?;
+        let original = PathBuf::from(record["path"].as_str()?);
+        Some(if media.is_some() {
+            sidecar_path_for(&original)
+        } else {
+            original
+        })
+    }
+
+    /// Trashed files of a resource type
+    pub fn files(&self, resource_type: ResourceType) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self
+            .originals
+            .keys()
+            .filter(|path| resource_type.is_resource(path))
+            .cloned()
+            .collect();
+        files.sort();
+        files
+    }
+
+    /// Where a trashed file was before it was deleted in digiKam
+    pub fn original_path(&self, trashed: &Path) -> Option<&Path> {
+        self.originals.get(trashed)?.as_deref()
+    }
+
+    /// Whether the file is in the trash, given either its trashed or its original path
+    pub fn contains(&self, path: &Path) -> bool {
+        self.originals.contains_key(path) || self.trashed_originals.contains(path)
+    }
+}
+
+// Sync XMP metadata to corresponding media files, returns false when the sidecar was skipped
+pub fn sync_xmp_to_media(xmp_path: &Path) -> anyhow::Result<bool> {
+    let media_path = match resolve_sidecar_media(xmp_path) {
+        SidecarMedia::Found(media_path) => media_path,
+        SidecarMedia::Missing(media_path) => {
             eprintln!(
-                "Warning: Skipping XMP file with non-UTF-8 path: {}",
-                xmp_path.display()
+                "Warning: Skipping,'{}' does not exist.",
+                media_path.display()
             );
-            return Ok(());
+            return Ok(false);
+        }
+        SidecarMedia::Ambiguous(candidates) => {
+            eprintln!(
+                "Warning: Skipping ambiguous sidecar '{}', matches {}",
+                xmp_path.display(),
+                candidates
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            return Ok(false);
         }
     };
-    let media_path = Path::new(media_path_str);
 
-    if !media_path.exists() {
-        eprintln!(
-            "Warning: Skipping,'{}' does not exist.",
-            media_path.display()
-        );
-        return Ok(());
-    }
-
-    let xmp_content = fs::read_to_string(xmp_path)?;
+    let xmp_content = read_xmp_sidecar(xmp_path)?;
     let xmp_meta = XmpMeta::from_str(&xmp_content)?;
 
     let mut xmp_file = XmpFile::new()?;
     let open_options = OpenFileOptions::default().for_update();
-    xmp_file.open_file(media_path, open_options)?;
+    xmp_file.open_file(&media_path, open_options)?;
     xmp_file.put_xmp(&xmp_meta)?;
     xmp_file.try_close()?;
 
+    Ok(true)
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum SyncCheckStatus {
+    Identical,
+    SidecarAddsData,
+    Conflict,
+}
+
+type SyncCheckResult = anyhow::Result<(SyncCheckStatus, Vec<SyncCheckDiff>)>;
+
+struct SyncCheckDiff {
+    field: &'static str,
+    embedded: String,
+    sidecar: String,
+}
+
+// Values compared by the sync check, multi-value fields are sorted so order does not matter
+fn sync_check_fields(xmp: &XmpMeta) -> [(&'static str, String); 2] {
+    let mut subjects: Vec<String> = xmp
+        .property_array(LIGHTROOM_NS, LR_HIERARCHICAL_SUBJECT)
+        .map(|property| property.value)
+        .collect();
+    subjects.sort();
+    subjects.dedup();
+    let datetime = xmp
+        .property(xmp_ns::EXIF, "DateTimeOriginal")
+        .map(|property| property.value)
+        .unwrap_or_default();
+    [
+        (LR_HIERARCHICAL_SUBJECT, subjects.join("; ")),
+        ("DateTimeOriginal", datetime),
+    ]
+}
+
+// Camera local time and offset of an XMP datetime, seconds and fractions optional
+fn parse_xmp_datetime(value: &str) -> Option<(NaiveDateTime, Option<FixedOffset>)> {
+    let (local, offset) = split_utc_offset(value);
+    NaiveDateTime::parse_from_str(local, "%Y-%m-%dT%H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(local, "%Y-%m-%dT%H:%M"))
+        .ok()
+        .map(|local| (local, offset))
+}
+
+// Whether two values of a sync check field say the same. Datetimes are compared parsed, as
+// instants when both carry an offset and as camera local times otherwise.
+fn same_sync_value(field: &str, left: &str, right: &str) -> bool {
+    if left == right {
+        return true;
+    }
+    if field != "DateTimeOriginal" {
+        return false;
+    }
+    match (parse_xmp_datetime(left), parse_xmp_datetime(right)) {
+        (Some((left, Some(left_offset))), Some((right, Some(right_offset)))) => {
+            left_offset.from_local_datetime(&left).single()
+                == right_offset.from_local_datetime(&right).single()
+        }
+        (Some((left, _)), Some((right, _))) => left == right,
+        _ => false,
+    }
+}
+
+// Compare the sidecar against the XMP embedded in its media file without writing anything
+fn check_xmp_against_media(xmp_path: &Path) -> SyncCheckResult {
+    let media_path = match resolve_sidecar_media(xmp_path) {
+        SidecarMedia::Found(media_path) => media_path,
+        SidecarMedia::Missing(media_path) => {
+            return Err(anyhow::anyhow!(
+                "'{}' does not exist.",
+                media_path.display()
+            ));
+        }
+        SidecarMedia::Ambiguous(candidates) => {
+            return Err(anyhow::anyhow!(
+                "ambiguous sidecar, matches {} media files",
+                candidates.len()
+            ));
+        }
+    };
+    let sidecar = XmpMeta::from_str(&read_xmp_sidecar(xmp_path)?)?;
+
+    let mut media_file = XmpFile::new()?;
+    media_file.open_file(&media_path, OpenFileOptions::default().only_xmp())?;
+    let embedded = media_file.xmp().unwrap_or_default();
+    media_file.try_close()?;
+
+    let mut status = SyncCheckStatus::Identical;
+    let mut diffs = Vec::new();
+    for ((field, embedded_value), (_, sidecar_value)) in sync_check_fields(&embedded)
+        .into_iter()
+        .zip(sync_check_fields(&sidecar))
+    {
+        if same_sync_value(field, &embedded_value, &sidecar_value) {
+            continue;
+        }
+        let field_status = if embedded_value.is_empty() {
+            SyncCheckStatus::SidecarAddsData
+        } else {
+            SyncCheckStatus::Conflict
+        };
+        status = status.max(field_status);
+        diffs.push(SyncCheckDiff {
+            field,
+            embedded: embedded_value,
+            sidecar: sidecar_value,
+        });
+    }
+    Ok((status, diffs))
+}
+
+// Read-only counterpart of the sync, writes a diff CSV into report_dir
+fn check_xmp_paths(xmp_paths: Vec<PathBuf>, report_dir: &Path) -> anyhow::Result<()> {
+    let pb = ServalProgress::new(xmp_paths.len() as u64, "comparing sidecars with media");
+    let results: Vec<(PathBuf, SyncCheckResult)> = xmp_paths
+        .into_par_iter()
+        .map(|xmp_path| {
+            let result = with_io_permit(|| check_xmp_against_media(&xmp_path));
+            pb.inc(1);
+            (xmp_path, result)
+        })
+        .collect();
+    pb.finish();
+
+    let mut num_identical = 0;
+    let mut num_adds_data = 0;
+    let mut num_conflict = 0;
+    let mut num_failed = 0;
+    let mut diff_paths: Vec<String> = Vec::new();
+    let mut diff_status: Vec<&str> = Vec::new();
+    let mut diff_fields: Vec<&str> = Vec::new();
+    let mut diff_embedded: Vec<String> = Vec::new();
+    let mut diff_sidecar: Vec<String> = Vec::new();
+    for (xmp_path, result) in results {
+        match result {
+            Ok((status, diffs)) => {
+                let status_name = match status {
+                    SyncCheckStatus::Identical => {
+                        num_identical += 1;
+                        "identical"
+                    }
+                    SyncCheckStatus::SidecarAddsData => {
+                        num_adds_data += 1;
+                        "sidecar_adds_data"
+                    }
+                    SyncCheckStatus::Conflict => {
+                        num_conflict += 1;
+                        "conflict"
+                    }
+                };
+                for diff in diffs {
+                    diff_paths.push(xmp_path.to_string_lossy().into_owned());
+                    diff_status.push(status_name);
+                    diff_fields.push(diff.field);
+                    diff_embedded.push(diff.embedded);
+                    diff_sidecar.push(diff.sidecar);
+                }
+            }
+            Err(e) => {
+                num_failed += 1;
+                eprintln!("Failed to check {}: {e}", xmp_path.display());
+            }
+        }
+    }
+
+    let mut df_diff = DataFrame::new(
+        diff_paths.len(),
+        vec![
+            Column::new(PATH_COLUMN.into(), diff_paths),
+            Column::new("status".into(), diff_status),
+            Column::new("field".into(), diff_fields),
+            Column::new("embedded_value".into(), diff_embedded),
+            Column::new("sidecar_value".into(), diff_sidecar),
+        ],
+    )?;
+    let timestamp = chrono::Local::now().format("%Y%m%d%H%M%S");
+    fs::create_dir_all(report_dir)?;
+    let diff_csv_path = report_dir.join(format!("xmp_sync_check_{timestamp}.csv"));
+    let mut file = File::create(&diff_csv_path)?;
+    csv_writer(&mut file).finish(&mut df_diff)?;
+
+    println!(
+        "Identical: {num_identical}, sidecar adds data: {num_adds_data}, conflict: {num_conflict}, failed: {num_failed}"
+    );
+    println!("Saved diff to {}", diff_csv_path.display());
     Ok(())
 }
 
-pub fn sync_xmp_directory(source_dir: PathBuf) -> anyhow::Result<()> {
+/// Write the XMP sidecars under `source_dir` into their media files, or with `check` only
+/// compare them and write the differences to an `xmp_sync_check_<timestamp>.csv` in the
+/// `check` directory
+pub fn sync_xmp_directory(source_dir: PathBuf, check: Option<&Path>) -> anyhow::Result<()> {
     let xmp_paths = path_enumerate(source_dir.clone(), ResourceType::Xmp);
     let num_xmp = xmp_paths.len();
 
@@ -835,20 +3303,27 @@ pub fn sync_xmp_directory(source_dir: PathBuf) -> anyhow::Result<()> {
         return Ok(());
     }
 
+    if let Some(report_dir) = check {
+        println!(
+            "Checking {} XMP files against media in {}",
+            num_xmp,
+            source_dir.display()
+        );
+        return check_xmp_paths(xmp_paths, report_dir);
+    }
+
     println!(
         "Found {} XMP files to sync in {}",
         num_xmp,
         source_dir.display()
     );
 
-    let pb = indicatif::ProgressBar::new(num_xmp as u64);
-    configure_progress_bar(&pb);
-    pb.set_message("Syncing XMP metadata to media files...");
+    let pb = ServalProgress::new(num_xmp as u64, "syncing XMP metadata to media files");
 
-    let results: Vec<anyhow::Result<()>> = xmp_paths
+    let results: Vec<anyhow::Result<bool>> = xmp_paths
         .par_iter()
         .map(|xmp_path| {
-            let result = sync_xmp_to_media(xmp_path);
+            let result = with_io_permit(|| sync_xmp_to_media(xmp_path));
             pb.inc(1);
             result
         })
@@ -858,8 +3333,11 @@ pub fn sync_xmp_directory(source_dir: PathBuf) -> anyhow::Result<()> {
 
     let (successes, failures): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
 
-    let num_synced = successes.len();
-    let num_skipped = failures.len();
+    let num_synced = successes
+        .iter()
+        .filter(|result| matches!(result, Ok(true)))
+        .count();
+    let num_skipped = failures.len() + successes.len() - num_synced;
 
     for result in failures {
         if let Err(e) = result {
@@ -872,18 +3350,31 @@ pub fn sync_xmp_directory(source_dir: PathBuf) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn sync_xmp_from_csv(csv_path: PathBuf) -> anyhow::Result<()> {
-    let df = CsvReadOptions::default()
-        .with_columns(csv_projection_columns(&[PATH_COLUMN]))
+/// `sync_xmp_directory` for the XMP paths listed in a CSV
+pub fn sync_xmp_from_csv(
+    csv_path: PathBuf,
+    check: Option<&Path>,
+    column_map: ColumnMap,
+) -> anyhow::Result<()> {
+    check_csv_columns(&csv_path, &[PATH_COLUMN], &column_map)?;
+    let mut df = CsvReadOptions::default()
+        .with_columns(csv_projection_columns(&[column_map.source(PATH_COLUMN)]))
         .with_ignore_errors(false)
-        .try_into_reader_with_file_path(Some(csv_path))?
+        .try_into_reader_with_file_path(Some(csv_path.clone()))?
         .finish()?;
     reject_duplicate_csv_columns(&df)?;
+    column_map.apply(&mut df)?;
 
     let df_filtered = df
         .lazy()
         .filter(col("path").is_not_null())
-        .filter(col("path").str().ends_with(lit(".xmp")))
+        .filter(
+            col("path")
+                .str()
+                .to_lowercase()
+                .str()
+                .ends_with(lit(".xmp")),
+        )
         .select([col("path")])
         .unique(
             Some(cols(vec!["path".to_string()])),
@@ -897,39 +3388,372 @@ pub fn sync_xmp_from_csv(csv_path: PathBuf) -> anyhow::Result<()> {
         return Ok(());
     }
 
-    println!("Found {num_files} XMP files in CSV to sync");
-
-    let pb = indicatif::ProgressBar::new(num_files as u64);
-    configure_progress_bar(&pb);
-    pb.set_message("Syncing XMP files in CSV...");
-
-    let path_col = df_filtered.column("path")?.str()?;
+    let path_col = df_filtered.column("path")?.str()?;
+
+    if let Some(report_dir) = check {
+        println!("Checking {num_files} XMP files in CSV against media");
+        let xmp_paths = path_col.iter().flatten().map(PathBuf::from).collect();
+        return check_xmp_paths(xmp_paths, report_dir);
+    }
+
+    println!("Found {num_files} XMP files in CSV to sync");
+
+    let pb = ServalProgress::new(num_files as u64, "syncing XMP files in CSV");
+
+    let results: Vec<anyhow::Result<bool>> = path_col
+        .par_iter()
+        .filter_map(|path| path.map(PathBuf::from))
+        .map(|xmp_path| {
+            let result = with_io_permit(|| sync_xmp_to_media(&xmp_path));
+            pb.inc(1);
+            result
+        })
+        .collect();
+
+    pb.finish();
+
+    let (successes, failures): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
+
+    let num_synced = successes
+        .iter()
+        .filter(|result| matches!(result, Ok(true)))
+        .count();
+    let num_skipped = failures.len() + successes.len() - num_synced;
+
+    for result in failures {
+        if let Err(e) = result {
+            eprintln!("Failed to sync: {e}");
+        }
+    }
+
+    println!("Successfully synced {num_synced} XMP files, skipped {num_skipped} files");
+
+    Ok(())
+}
+
+// Status of one sidecar in the embed report
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum EmbedStatus {
+    Embedded,
+    Verified,
+    // Embedded, but the media did not read back as the sidecar
+    VerifyFailed,
+    Failed,
+    Skipped,
+}
+
+impl EmbedStatus {
+    fn name(self) -> &'static str {
+        match self {
+            EmbedStatus::Embedded => "embedded",
+            EmbedStatus::Verified => "verified",
+            EmbedStatus::VerifyFailed => "verify_failed",
+            EmbedStatus::Failed => "failed",
+            EmbedStatus::Skipped => "skipped",
+        }
+    }
+}
+
+// Embed one sidecar into its media. When verifying, the media is re-read and the fields of
+// the sync check the sidecar sets must come back unchanged; the message says why a sidecar
+// was skipped or failed
+fn embed_sidecar(xmp_path: &Path, only_images: bool, verify: bool) -> (EmbedStatus, String) {
+    let media_path = match resolve_sidecar_media(xmp_path) {
+        SidecarMedia::Found(media_path) => media_path,
+        SidecarMedia::Missing(media_path) => {
+            return (
+                EmbedStatus::Skipped,
+                format!("'{}' does not exist", media_path.display()),
+            );
+        }
+        SidecarMedia::Ambiguous(candidates) => {
+            return (
+                EmbedStatus::Skipped,
+                format!(
+                    "ambiguous sidecar, matches {} media files",
+                    candidates.len()
+                ),
+            );
+        }
+    };
+    if only_images
+        && !resource_extension(&media_path)
+            .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+    {
+        return (
+            EmbedStatus::Skipped,
+            "not an image (--only-images=false embeds videos too)".to_string(),
+        );
+    }
+    let embed = || -> anyhow::Result<[(&'static str, String); 2]> {
+        let sidecar = XmpMeta::from_str(&read_xmp_sidecar(xmp_path)?)?;
+        let mut media_file = XmpFile::new()?;
+        media_file.open_file(&media_path, OpenFileOptions::default().for_update())?;
+        media_file.put_xmp(&sidecar)?;
+        media_file.try_close()?;
+        Ok(sync_check_fields(&sidecar))
+    };
+    let expected = match embed() {
+        Ok(expected) => expected,
+        Err(e) => return (EmbedStatus::Failed, e.to_string()),
+    };
+    if !verify {
+        return (EmbedStatus::Embedded, String::new());
+    }
+    let reread = || -> anyhow::Result<XmpMeta> {
+        let mut media_file = XmpFile::new()?;
+        media_file.open_file(&media_path, OpenFileOptions::default().only_xmp())?;
+        let embedded = media_file.xmp().unwrap_or_default();
+        media_file.try_close()?;
+        Ok(embedded)
+    };
+    let embedded = match reread() {
+        Ok(embedded) => embedded,
+        Err(e) => {
+            return (
+                EmbedStatus::VerifyFailed,
+                format!("re-reading the media: {e}"),
+            );
+        }
+    };
+    // Fields the sidecar leaves empty are not written by the embed
+    let mismatches: Vec<String> = expected
+        .into_iter()
+        .zip(sync_check_fields(&embedded))
+        .filter(|((field, sidecar_value), (_, embedded_value))| {
+            !sidecar_value.is_empty() && !same_sync_value(field, sidecar_value, embedded_value)
+        })
+        .map(|((field, sidecar_value), (_, embedded_value))| {
+            format!("{field} is '{embedded_value}', expected '{sidecar_value}'")
+        })
+        .collect();
+    if mismatches.is_empty() {
+        (EmbedStatus::Verified, String::new())
+    } else {
+        (EmbedStatus::VerifyFailed, mismatches.join("; "))
+    }
+}
+
+/// Embed every sidecar under `source_dir` into its media for archival, videos are left out
+/// with `only_images`. Writes an `xmp_embed_<timestamp>.csv` report into `source_dir`,
+/// `remove_sidecars_after` deletes the sidecars whose embed passed verification.
+pub fn embed_xmp_directory(
+    source_dir: PathBuf,
+    only_images: bool,
+    verify: bool,
+    remove_sidecars_after: bool,
+) -> anyhow::Result<()> {
+    if remove_sidecars_after && !verify {
+        return Err(anyhow::anyhow!(
+            "Sidecars are only removed after a verified embed, pass --verify"
+        ));
+    }
+    let xmp_paths = path_enumerate(source_dir.clone(), ResourceType::Xmp);
+    if xmp_paths.is_empty() {
+        println!("No XMP files found in {}", source_dir.display());
+        return Ok(());
+    }
+    println!(
+        "Found {} XMP files to embed in {}",
+        xmp_paths.len(),
+        source_dir.display()
+    );
 
-    let results: Vec<anyhow::Result<()>> = path_col
-        .par_iter()
-        .filter_map(|path| path.map(PathBuf::from))
+    let pb = ServalProgress::new(xmp_paths.len() as u64, "embedding XMP metadata into media");
+    let results: Vec<(PathBuf, EmbedStatus, String)> = xmp_paths
+        .into_par_iter()
         .map(|xmp_path| {
-            let result = sync_xmp_to_media(&xmp_path);
+            let (status, message) =
+                with_io_permit(|| embed_sidecar(&xmp_path, only_images, verify));
+            if matches!(status, EmbedStatus::Failed | EmbedStatus::VerifyFailed) {
+                pb.warn(
+                    "Failed to embed",
+                    format!("{message} in {}", xmp_path.display()),
+                );
+            }
             pb.inc(1);
-            result
+            (xmp_path, status, message)
         })
         .collect();
-
     pb.finish();
 
-    let (successes, failures): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
+    let mut report_paths: Vec<String> = Vec::new();
+    let mut report_status: Vec<&str> = Vec::new();
+    let mut report_messages: Vec<String> = Vec::new();
+    let mut report_removed: Vec<bool> = Vec::new();
+    let mut num_removed = 0;
+    for (xmp_path, status, message) in &results {
+        let mut removed = false;
+        if remove_sidecars_after && *status == EmbedStatus::Verified {
+            match fs::remove_file(xmp_path) {
+                Ok(()) => {
+                    record_changes(
+                        xmp_path,
+                        &[AuditChange::new("file", "present", "removed")],
+                        None,
+                    )?;
+                    removed = true;
+                    num_removed += 1;
+                }
+                Err(e) => eprintln!("Failed to remove {}: {e}", xmp_path.display()),
+            }
+        }
+        report_paths.push(xmp_path.to_string_lossy().into_owned());
+        report_status.push(status.name());
+        report_messages.push(message.clone());
+        report_removed.push(removed);
+    }
+    let mut df_report = DataFrame::new(
+        report_paths.len(),
+        vec![
+            Column::new(PATH_COLUMN.into(), report_paths),
+            Column::new("status".into(), report_status),
+            Column::new("message".into(), report_messages),
+            Column::new("sidecar_removed".into(), report_removed),
+        ],
+    )?;
+    let timestamp = chrono::Local::now().format("%Y%m%d%H%M%S");
+    let report_path = source_dir.join(format!("xmp_embed_{timestamp}.csv"));
+    let mut file = File::create(&report_path)?;
+    csv_writer(&mut file).finish(&mut df_report)?;
+
+    let count = |wanted: &[EmbedStatus]| {
+        results
+            .iter()
+            .filter(|(_, status, _)| wanted.contains(status))
+            .count()
+    };
+    println!(
+        "Embedded: {}, verified: {}, failed: {}, skipped: {}",
+        count(&[
+            EmbedStatus::Embedded,
+            EmbedStatus::Verified,
+            EmbedStatus::VerifyFailed
+        ]),
+        count(&[EmbedStatus::Verified]),
+        count(&[EmbedStatus::Failed, EmbedStatus::VerifyFailed]),
+        count(&[EmbedStatus::Skipped]),
+    );
+    if remove_sidecars_after {
+        println!("Removed {num_removed} verified sidecars");
+    }
+    println!("Saved report to {}", report_path.display());
+    Ok(())
+}
 
-    let num_synced = successes.len();
-    let num_skipped = failures.len();
+// Rename sidecars under root to the given naming convention
+pub fn xmp_rename_convention(
+    root: PathBuf,
+    to: SidecarConvention,
+    dry_run: bool,
+) -> anyhow::Result<()> {
+    let xmp_paths = path_enumerate(root.clone(), ResourceType::Xmp);
+    if xmp_paths.is_empty() {
+        println!("No XMP files found in {}", root.display());
+        return Ok(());
+    }
 
-    for result in failures {
-        if let Err(e) = result {
-            eprintln!("Failed to sync: {e}");
+    let pb = ServalProgress::new(xmp_paths.len() as u64, "renaming XMP files");
+    let mut planned_targets: HashSet<PathBuf> = HashSet::new();
+    let mut num_renamed = 0;
+    let mut num_unchanged = 0;
+    let mut num_refused = 0;
+    for xmp_path in xmp_paths {
+        pb.inc(1);
+        let media_path = match resolve_sidecar_media(&xmp_path) {
+            SidecarMedia::Found(media_path) => media_path,
+            SidecarMedia::Missing(_) => {
+                num_refused += 1;
+                pb.warn(
+                    "Skipped, no matching media file",
+                    format!("Skipping {}, no matching media file", xmp_path.display()),
+                );
+                continue;
+            }
+            SidecarMedia::Ambiguous(candidates) => {
+                num_refused += 1;
+                pb.warn(
+                    "Skipped, ambiguous sidecar",
+                    format!(
+                        "Skipping ambiguous sidecar {}, matches {} media files",
+                        xmp_path.display(),
+                        candidates.len()
+                    ),
+                );
+                continue;
+            }
+        };
+        // Keep the sidecar's own extension casing
+        let xmp_ext = xmp_path.extension().unwrap_or_default();
+        let target = match to {
+            SidecarConvention::Appended => media_path.with_added_extension(xmp_ext),
+            SidecarConvention::Replaced => media_path.with_extension(xmp_ext),
+        };
+        if target == xmp_path {
+            num_unchanged += 1;
+            continue;
+        }
+        if to == SidecarConvention::Replaced
+            && let SidecarMedia::Ambiguous(candidates) = resolve_sidecar_media(&target)
+        {
+            num_refused += 1;
+            pb.warn(
+                "Refused, media files share the stem",
+                format!(
+                    "Refusing to rename {}, {} media files share the stem of {}",
+                    xmp_path.display(),
+                    candidates.len(),
+                    target.display()
+                ),
+            );
+            continue;
+        }
+        if target.exists() || !planned_targets.insert(target.clone()) {
+            num_refused += 1;
+            pb.warn(
+                "Skipped, target already exists",
+                format!(
+                    "Collision: {} already exists, skipping {}",
+                    target.display(),
+                    xmp_path.display()
+                ),
+            );
+            continue;
+        }
+        if dry_run {
+            pb.notice(
+                "Will rename",
+                format!("Will rename {} to {}", xmp_path.display(), target.display()),
+            );
+        } else {
+            with_io_permit(|| fs::rename(&xmp_path, &target))?;
+            record_changes(
+                &target,
+                &[AuditChange::new(
+                    "path",
+                    normalize_path_str(&xmp_path.to_string_lossy()),
+                    normalize_path_str(&target.to_string_lossy()),
+                )],
+                None,
+            )?;
+            pb.notice(
+                "Renamed",
+                format!("Renamed {} to {}", xmp_path.display(), target.display()),
+            );
         }
+        num_renamed += 1;
     }
+    pb.finish();
 
-    println!("Successfully synced {num_synced} XMP files, skipped {num_skipped} files");
-
+    if dry_run {
+        println!(
+            "Will rename {num_renamed} XMP files, {num_unchanged} already follow the convention, {num_refused} skipped"
+        );
+    } else {
+        println!(
+            "Renamed {num_renamed} XMP files, {num_unchanged} already follow the convention, {num_refused} skipped"
+        );
+    }
     Ok(())
 }
 
@@ -945,16 +3769,20 @@ pub fn remove_xmp_files(source_dir: PathBuf) -> anyhow::Result<()> {
 
     println!("Found {} XMP files in {}", num_xmp, source_dir.display());
 
-    let pb = indicatif::ProgressBar::new(num_xmp as u64);
-    configure_progress_bar(&pb);
-    pb.set_message("Removing XMP files...");
+    let pb = ServalProgress::new(num_xmp as u64, "removing XMP files");
 
     let results: Vec<anyhow::Result<()>> = xmp_paths
         .par_iter()
         .map(|xmp_path| {
-            let result = fs::remove_file(xmp_path);
+            let result = with_io_permit(|| fs::remove_file(xmp_path));
             pb.inc(1);
-            result.map_err(|e| anyhow::anyhow!("Failed to remove {}: {}", xmp_path.display(), e))
+            result
+                .map_err(|e| anyhow::anyhow!("Failed to remove {}: {}", xmp_path.display(), e))?;
+            record_changes(
+                xmp_path,
+                &[AuditChange::new("file", "present", "removed")],
+                None,
+            )
         })
         .collect();
 
@@ -975,23 +3803,6 @@ pub fn remove_xmp_files(source_dir: PathBuf) -> anyhow::Result<()> {
     Ok(())
 }
 
-pub fn is_temporal_independent(
-    time_ref: String,
-    time: String,
-    min_delta_time: i32,
-) -> anyhow::Result<bool> {
-    // TODO Timezone
-    let dt_ref = NaiveDateTime::parse_from_str(time_ref.as_str(), "%Y-%m-%d %H:%M:%S")
-        .map_err(|e| anyhow::anyhow!("Failed to parse reference datetime '{time_ref}': {e}"))?;
-    let dt = NaiveDateTime::parse_from_str(time.as_str(), "%Y-%m-%d %H:%M:%S")
-        .map_err(|e| anyhow::anyhow!("Failed to parse datetime '{time}': {e}"))?;
-    let diff = dt - dt_ref;
-
-    Ok(diff
-        >= chrono::Duration::try_minutes(min_delta_time.into())
-            .ok_or_else(|| anyhow::anyhow!("Invalid minute value: {min_delta_time}"))?)
-}
-
 pub fn get_path_levels(path: String) -> Vec<String> {
     // Abandoned for performance
     // let normalized_path = PathBuf::from(path.replace('\\', "/"));
@@ -1004,7 +3815,7 @@ pub fn get_path_levels(path: String) -> Vec<String> {
     //     })
     //     .collect();
 
-    let normalized_path = normalize_path_separators(&path);
+    let normalized_path = normalize_path_str(&path);
     let levels: Vec<String> = normalized_path
         .split('/')
         .map(|comp| comp.to_string())
@@ -1012,12 +3823,23 @@ pub fn get_path_levels(path: String) -> Vec<String> {
     levels[1..levels.len() - 1].to_vec()
 }
 
-fn normalize_path_separators(path: &str) -> String {
+/// Path from tags.csv with `/` separators, so files listed on Windows (D:\proj\dep\IMG.jpg)
+/// split into the same directories on every platform
+pub fn normalize_path_str(path: &str) -> String {
     path.replace('\\', "/")
 }
 
+// Windows drive (D:/ or D:\), absolute even where the platform doesn't know drives
+fn has_drive_prefix(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && (bytes[2] == b'/' || bytes[2] == b'\\')
+}
+
 pub fn deployment_from_path(path: &Path, deploy_path_index: i32) -> anyhow::Result<String> {
-    let normalized_path = normalize_path_separators(&path.to_string_lossy());
+    let normalized_path = normalize_path_str(&path.to_string_lossy());
     normalized_path
         .split('/')
         .nth(deploy_path_index.try_into()?)
@@ -1031,6 +3853,61 @@ pub fn deployment_from_path(path: &Path, deploy_path_index: i32) -> anyhow::Resu
         })
 }
 
+/// Where observe finds the deployment of each file for the deployment column of tags.csv
+#[derive(Clone, Debug)]
+pub enum DeploymentLookup {
+    /// Directory at this level of the path, numbered as in the deployment prompt of capture
+    Level(i32),
+    /// Deepest directory of the path named like a deploymentID of the deploy table
+    Table(HashSet<String>),
+}
+
+impl DeploymentLookup {
+    pub fn from_deploy_table(deploy_table: &Path) -> anyhow::Result<Self> {
+        let deploy_df = CsvReadOptions::default()
+            .with_infer_schema_length(Some(0))
+            .with_columns(csv_projection_columns(&[DEPLOYMENT_ID_COLUMN]))
+            .try_into_reader_with_file_path(Some(deploy_table.to_path_buf()))
+            .and_then(|reader| reader.finish())
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Failed to read {DEPLOYMENT_ID_COLUMN} from {}: {e}",
+                    deploy_table.display()
+                )
+            })?;
+        reject_duplicate_csv_columns(&deploy_df)?;
+        let deployments = deploy_df
+            .column(DEPLOYMENT_ID_COLUMN)?
+            .str()?
+            .iter()
+            .flatten()
+            .map(|deployment| deployment.trim().to_string())
+            .filter(|deployment| !deployment.is_empty())
+            .collect();
+        Ok(Self::Table(deployments))
+    }
+
+    /// None when the path has no directory at the level, or none named in the table
+    pub fn deployment_for(&self, path: &Path) -> Option<String> {
+        let normalized_path = normalize_path_str(&path.to_string_lossy());
+        let components: Vec<&str> = normalized_path.split('/').collect();
+        // The file name is not a directory level
+        let directories = &components[..components.len().saturating_sub(1)];
+        match self {
+            Self::Level(level) => usize::try_from(*level)
+                .ok()
+                .filter(|level| *level >= 1)
+                .and_then(|level| directories.get(level))
+                .map(|directory| directory.to_string()),
+            Self::Table(deployments) => directories
+                .iter()
+                .rev()
+                .find(|directory| deployments.contains(**directory))
+                .map(|directory| directory.to_string()),
+        }
+    }
+}
+
 pub fn deployment_from_path_expr(path_expr: Expr, deploy_path_index: i32) -> Expr {
     path_expr
         .str()
@@ -1042,84 +3919,634 @@ pub fn deployment_from_path_expr(path_expr: Expr, deploy_path_index: i32) -> Exp
 }
 
 pub fn ignore_timezone(time: String) -> anyhow::Result<String> {
-    let time_remove_designator = time.replace('Z', "");
-    let time_ignore_zone = time_remove_designator.split('+').collect::<Vec<&str>>()[0];
-    Ok(time_ignore_zone.to_string())
+    Ok(split_utc_offset(&time).0.to_string())
+}
+
+// Datetime and its trailing UTC offset (Z, +08:00, -0800), only looked for after the
+// time so the dashes of the date are left alone
+fn split_utc_offset(time: &str) -> (&str, Option<FixedOffset>) {
+    if let Some(local) = time.strip_suffix(['Z', 'z']) {
+        return (local, FixedOffset::east_opt(0));
+    }
+    let Some(time_start) = time.find('T') else {
+        return (time, None);
+    };
+    match time[time_start..].rfind(['+', '-']) {
+        Some(sign) => {
+            let (local, offset) = time.split_at(time_start + sign);
+            match parse_utc_offset_arg(offset) {
+                Ok(offset) => (local, Some(offset)),
+                Err(_) => (time, None),
+            }
+        }
+        None => (time, None),
+    }
+}
+
+/// How observe treats the UTC offset of XMP datetimes (`--timezone`)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DatetimeTimezone {
+    /// Drop the offset, keeping the time shown by the camera
+    #[default]
+    Strip,
+    /// Keep the offset, datetimes become UTC instants
+    Keep,
+    /// Convert datetimes with an offset into the local time of this zone
+    Zone(Tz),
+}
+
+impl DatetimeTimezone {
+    /// Whether observe writes datetimes with an offset
+    pub fn is_tz_aware(&self) -> bool {
+        matches!(self, Self::Keep)
+    }
+
+    /// XMP datetime (ISO 8601, maybe with an offset) as observe reads it in this mode.
+    /// Datetimes without an offset can't be placed in time: they are left as they are,
+    /// and in keep mode end up among the malformed datetimes.
+    pub fn apply(&self, time: &str) -> String {
+        let (local, offset) = split_utc_offset(time);
+        let (Some(offset), Self::Keep | Self::Zone(_)) = (offset, self) else {
+            return local.to_string();
+        };
+        let Some(datetime) = NaiveDateTime::parse_from_str(local, "%Y-%m-%dT%H:%M:%S%.f")
+            .ok()
+            .and_then(|naive| offset.from_local_datetime(&naive).single())
+        else {
+            return local.to_string();
+        };
+        match self {
+            Self::Zone(tz) => datetime
+                .with_timezone(tz)
+                .format("%Y-%m-%dT%H:%M:%S")
+                .to_string(),
+            _ => datetime.format("%Y-%m-%dT%H:%M:%S%:z").to_string(),
+        }
+    }
+}
+
+/// `strip`, `keep` or an IANA time zone like Africa/Nairobi
+pub fn parse_timezone_arg(value: &str) -> anyhow::Result<DatetimeTimezone> {
+    match value {
+        "strip" => Ok(DatetimeTimezone::Strip),
+        "keep" => Ok(DatetimeTimezone::Keep),
+        zone => zone.parse::<Tz>().map(DatetimeTimezone::Zone).map_err(|_| {
+            anyhow::anyhow!(
+                "Invalid timezone '{zone}', expected strip, keep or an IANA name like Asia/Shanghai"
+            )
+        }),
+    }
 }
 
 pub fn iso_datetime_to_csv_format(time: &str) -> String {
     time.replace('T', " ")
 }
 
-pub fn sync_modified_time(source: PathBuf, target: PathBuf) -> anyhow::Result<()> {
+// Errors of filesystems that don't let us set file times (SMB/NFS mounts, FAT SD cards,
+// read-only targets). FAT also rejects times it can't store with InvalidInput.
+fn is_set_times_unsupported(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::PermissionDenied
+            | io::ErrorKind::Unsupported
+            | io::ErrorKind::ReadOnlyFilesystem
+            | io::ErrorKind::InvalidInput
+    )
+}
+
+/// Whether setting the times of `target` worked, false when its filesystem doesn't allow it
+/// and an error for any other failure
+pub fn set_times_outcome(result: io::Result<()>, target: &Path) -> anyhow::Result<bool> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(err) if is_set_times_unsupported(&err) => Ok(false),
+        Err(err) => Err(anyhow::anyhow!(
+            "Failed to set file times on {}: {err}",
+            target.display()
+        )),
+    }
+}
+
+/// Copy access and modified times of `source` onto `target`.
+///
+/// Returns false, without failing, when the target is a directory or its filesystem
+/// doesn't allow setting times; the copy itself is still fine then.
+pub fn sync_modified_time(source: PathBuf, target: PathBuf) -> anyhow::Result<bool> {
     let src = fs::metadata(source)?;
-    let dest = File::options().write(true).open(target)?;
+    if target.is_dir() {
+        return Ok(false);
+    }
     let times = FileTimes::new()
         .set_accessed(src.accessed()?)
         .set_modified(src.modified()?);
-    dest.set_times(times)?;
+    let result = File::options()
+        .write(true)
+        .open(&target)
+        .and_then(|dest| dest.set_times(times));
+    set_times_outcome(result, &target)
+}
+
+/// Warning for a copy whose modified time could not be kept, an error instead with
+/// `require_mtime` (extract --require-mtime)
+pub fn unpreserved_mtime(target: &Path, require_mtime: bool) -> anyhow::Result<String> {
+    if require_mtime {
+        return Err(anyhow::anyhow!(
+            "Could not preserve mtime on {} (--require-mtime)",
+            target.display()
+        ));
+    }
+    Ok(format!(
+        "Warning: could not preserve mtime on {}",
+        target.display()
+    ))
+}
+
+/// Directory a run writes `filenames` to: `output_dir` when none of them exist there yet,
+/// otherwise decided by `on_conflict`. The first run counts as run 1, so versions start at run_002.
+pub fn versioned_output_dir(
+    output_dir: &Path,
+    filenames: &[String],
+    on_conflict: OnConflict,
+) -> anyhow::Result<PathBuf> {
+    let Some(existing) = filenames
+        .iter()
+        .map(|filename| output_dir.join(filename))
+        .find(|path| path.exists())
+    else {
+        return Ok(output_dir.to_path_buf());
+    };
+    match on_conflict {
+        OnConflict::Overwrite => {
+            println!("Note: overwriting {}", existing.display());
+            Ok(output_dir.to_path_buf())
+        }
+        OnConflict::Fail => Err(anyhow::anyhow!(
+            "{} already exists, use --on-conflict version or overwrite",
+            existing.display()
+        )),
+        OnConflict::Version => {
+            let run_dir = (2..)
+                .map(|run| output_dir.join(format!("run_{run:03}")))
+                .find(|run_dir| !run_dir.exists())
+                .unwrap();
+            println!(
+                "Note: {} already exists, writing this run to {}",
+                existing.display(),
+                run_dir.display()
+            );
+            Ok(run_dir)
+        }
+    }
+}
+
+/// Name of a directory for output file names, with whether a fallback had to be used.
+///
+/// Roots and paths ending in `..` have no file name: the last two directories of the
+/// lexically resolved path are used instead, or `root_<hash>` for a filesystem or drive
+/// root (D:\), so different roots don't collapse into the same name.
+pub fn dir_output_name(dir: &Path) -> (String, bool) {
+    // Windows paths are split the same on every platform, where D: is a drive and no name
+    let dir = PathBuf::from(normalize_path_str(&dir.to_string_lossy()));
+    let is_drive = |name: &std::ffi::OsStr| {
+        let name = name.as_encoded_bytes();
+        name.len() == 2 && name[0].is_ascii_alphabetic() && name[1] == b':'
+    };
+    if let Some(name) = dir.file_name().filter(|name| !is_drive(name)) {
+        return (name.to_string_lossy().into_owned(), false);
+    }
+    let mut names: Vec<String> = Vec::new();
+    for component in dir.components() {
+        match component {
+            Component::Normal(name) if !is_drive(name) => {
+                names.push(name.to_string_lossy().into_owned())
+            }
+            Component::ParentDir => {
+                names.pop();
+            }
+            _ => {}
+        }
+    }
+    let name = if names.is_empty() {
+        format!("root_{}", short_path_hash(&dir.to_string_lossy()))
+    } else {
+        names[names.len().saturating_sub(2)..].join("_")
+    };
+    (name, true)
+}
+
+// Parse percentages like "5%" or "5"
+pub fn parse_percent_arg(value: &str) -> anyhow::Result<f64> {
+    let number = value.trim().trim_end_matches('%').trim();
+    let percent: f64 = number
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid percentage: {value}"))?;
+    if !(0.0..=100.0).contains(&percent) {
+        return Err(anyhow::anyhow!(
+            "Percentage must be between 0 and 100: {value}"
+        ));
+    }
+    Ok(percent)
+}
+
+// Parse independence windows like "30m", "2h" or "30" (minutes)
+pub fn parse_window_minutes(value: &str) -> anyhow::Result<i32> {
+    let value = value.trim();
+    let (number, minutes_per_unit) = match value.strip_suffix(['h', 'H']) {
+        Some(hours) => (hours, 60),
+        None => (value.trim_end_matches(['m', 'M']), 1),
+    };
+    number
+        .trim()
+        .parse::<i32>()
+        .ok()
+        .and_then(|number| number.checked_mul(minutes_per_unit))
+        .filter(|minutes| *minutes >= 1)
+        .ok_or_else(|| anyhow::anyhow!("Invalid window: {value}, expected e.g. 30m or 2h"))
+}
+
+// A time_modified value of the tags CSV, written by observe --debug
+pub fn parse_time_modified(time: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S"))
+        .ok()
+}
+
+// Whether a file was modified after the recorded time, None when it can't be read
+pub fn modified_after(path: &Path, recorded: NaiveDateTime) -> Option<bool> {
+    let modified = with_io_permit(|| fs::metadata(path).and_then(|meta| meta.modified())).ok()?;
+    let modified: DateTime<Local> = modified.into();
+    // Recorded times are truncated to seconds
+    Some(
+        modified
+            .naive_local()
+            .with_nanosecond(0)
+            .unwrap_or_default()
+            > recorded,
+    )
+}
+
+// Report files modified after the tags.csv that was generated from them, using the per-row
+// time_modified (observe --debug) when present and the CSV's own mtime otherwise
+pub fn check_tags_staleness(
+    csv_path: &Path,
+    check_stale: bool,
+    fail_if_stale: Option<f64>,
+    column_map: &ColumnMap,
+) -> anyhow::Result<()> {
+    let mut df = if is_parquet(csv_path) {
+        read_parquet_as_text(csv_path)?
+    } else {
+        CsvReadOptions::default()
+            .with_infer_schema_length(Some(0))
+            .try_into_reader_with_file_path(Some(csv_path.to_path_buf()))?
+            .finish()?
+    };
+    column_map.apply(&mut df)?;
+    let time_modified_col = df.column(TIME_MODIFIED_COLUMN).ok();
+    // A missing path column is reported by the command itself
+    if df.column(PATH_COLUMN).is_err()
+        || (time_modified_col.is_none() && !check_stale && fail_if_stale.is_none())
+    {
+        return Ok(());
+    }
+    let csv_modified: DateTime<Local> = fs::metadata(csv_path)?.modified()?.into();
+    let csv_modified = csv_modified.naive_local();
+
+    // One check per file, a file with several tags has several rows
+    let mut recorded: HashMap<&str, Option<NaiveDateTime>> = HashMap::new();
+    let paths = df.column(PATH_COLUMN)?.str()?;
+    for (i, path) in paths.iter().enumerate() {
+        let Some(path) = path.filter(|path| !path.is_empty() && !is_archive_entry_path(path))
+        else {
+            continue;
+        };
+        let time_modified = time_modified_col
+            .and_then(|column| column.str().ok()?.get(i))
+            .and_then(parse_time_modified);
+        recorded.entry(path).or_insert(time_modified);
+    }
+
+    let num_files = recorded.len();
+    let pb = ServalProgress::new(num_files as u64, "checking for stale files");
+    let results: Vec<Option<bool>> = recorded
+        .into_par_iter()
+        .map(|(path, time_modified)| {
+            let modified = modified_after(Path::new(path), time_modified.unwrap_or(csv_modified));
+            pb.inc(1);
+            modified
+        })
+        .collect();
+    pb.finish();
+
+    let num_missing = results.iter().filter(|result| result.is_none()).count();
+    let num_stale = results
+        .iter()
+        .filter(|result| **result == Some(true))
+        .count();
+    let percent_stale = if num_files > 0 {
+        num_stale as f64 / num_files as f64 * 100.0
+    } else {
+        0.0
+    };
+    println!(
+        "Staleness check: {num_stale} of {num_files} file(s) ({percent_stale:.1}%) modified after {} was generated, {num_missing} missing",
+        csv_path.display()
+    );
+    if num_stale > 0 {
+        println!("Hint: re-run serval observe to pick up the latest tag corrections.");
+    }
+    if let Some(threshold) = fail_if_stale
+        && percent_stale > threshold
+    {
+        return Err(anyhow::anyhow!(
+            "{percent_stale:.1}% of files are stale, above the --fail-if-stale threshold of {threshold}%"
+        ));
+    }
     Ok(())
 }
 
+// SHA-256 of a file, read in fixed-size chunks so large videos are never loaded whole
+pub fn hash_file(path: &Path) -> anyhow::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1 << 20];
+    loop {
+        let num_read = io::Read::read(&mut file, &mut buffer)?;
+        if num_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..num_read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+const TAGLIST_KEY_COLUMN: &str = "taglist_key";
+
+// Join key of a species value, surrounding whitespace ignored and case too when asked
+fn taglist_key(value: &str, ignore_case: bool) -> String {
+    let key = value.trim();
+    if ignore_case {
+        key.to_lowercase()
+    } else {
+        key.to_string()
+    }
+}
+
+fn with_taglist_key(
+    mut df: DataFrame,
+    column: &str,
+    ignore_case: bool,
+) -> anyhow::Result<DataFrame> {
+    let keys: StringChunked = df
+        .column(column)?
+        .str()?
+        .iter()
+        .map(|value| value.map(|value| taglist_key(value, ignore_case)))
+        .collect();
+    df.with_column(keys.with_name(TAGLIST_KEY_COLUMN.into()).into_column())?;
+    Ok(df)
+}
+
+// Left join of a taglist onto the species column, matching its `from` column
+fn join_taglist(
+    source_df: DataFrame,
+    taglist_df: DataFrame,
+    from: &str,
+    ignore_case: bool,
+) -> anyhow::Result<LazyFrame> {
+    let source_df = with_taglist_key(source_df, TagType::Species.col_name(), ignore_case)?;
+    let taglist_df = with_taglist_key(taglist_df, from, ignore_case)?.drop(from)?;
+    Ok(source_df
+        .lazy()
+        .join(
+            taglist_df.lazy(),
+            [col(TAGLIST_KEY_COLUMN)],
+            [col(TAGLIST_KEY_COLUMN)],
+            JoinArgs::new(JoinType::Left),
+        )
+        .drop(cols([TAGLIST_KEY_COLUMN])))
+}
+
+// Replace the species of a tags CSV with the taglist `to` column, or keep it and append the
+// `add_columns` of the taglist next to it
+#[allow(clippy::too_many_arguments)]
 pub fn tags_csv_translate(
     source_csv: PathBuf,
     taglist_csv: PathBuf,
     output_dir: PathBuf,
     from: &str,
-    to: &str,
+    to: Option<&str>,
+    add_columns: &[String],
+    on_conflict: OnConflict,
+    column_map: ColumnMap,
 ) -> anyhow::Result<()> {
-    let source_df = CsvReadOptions::default()
+    let species_col = TagType::Species.col_name();
+    check_csv_columns(&source_csv, &[species_col], &column_map)?;
+    let mut source_df = CsvReadOptions::default()
         .with_infer_schema_length(Some(0))
         .try_into_reader_with_file_path(Some(source_csv.clone()))?
         .finish()?;
     reject_duplicate_csv_columns(&source_df)?;
+    column_map.apply(&mut source_df)?;
+    for column in add_columns {
+        if source_df.get_column_index(column).is_some() {
+            return Err(anyhow::anyhow!(
+                "{} already has a {column} column, remove it before translating",
+                source_csv.display()
+            ));
+        }
+    }
+    let taglist_columns: Vec<&str> = std::iter::once(from)
+        .chain(to)
+        .chain(add_columns.iter().map(String::as_str))
+        .collect();
+    check_csv_columns(&taglist_csv, &taglist_columns, &ColumnMap::default())?;
     let taglist_df = CsvReadOptions::default()
-        .with_columns(csv_projection_columns(&[from, to]))
-        .try_into_reader_with_file_path(Some(taglist_csv))?
+        .with_columns(csv_projection_columns(&taglist_columns))
+        .try_into_reader_with_file_path(Some(taglist_csv.clone()))?
         .finish()?;
     reject_duplicate_csv_columns(&taglist_df)?;
 
-    let joined = source_df.clone().lazy().join(
-        taglist_df.clone().lazy(),
-        [col(TagType::Species.col_name())],
-        [col(from)],
-        JoinArgs::new(JoinType::Left),
-    );
+    let joined = join_taglist(source_df, taglist_df, from, false)?;
+    let unmatched = |column: &str| -> anyhow::Result<Vec<String>> {
+        let unknown = joined
+            .clone()
+            .filter(
+                col(column)
+                    .is_null()
+                    .and(col(species_col).is_not_null())
+                    .and(col(species_col).neq(lit(""))),
+            )
+            .select([col(species_col)])
+            .unique_stable(None, UniqueKeepStrategy::Any)
+            .collect()?;
+        Ok(unknown
+            .column(species_col)?
+            .str()?
+            .iter()
+            .flatten()
+            .map(str::to_string)
+            .collect())
+    };
 
-    let unknown = joined
-        .clone()
-        .filter(
-            col(to)
-                .is_null()
-                .and(col(TagType::Species.col_name()).is_not_null())
-                .and(col(TagType::Species.col_name()).neq(lit(""))),
-        )
-        .select([col(TagType::Species.col_name())])
-        .unique(None, UniqueKeepStrategy::Any)
-        .collect()?;
-    if unknown.height() > 0 {
-        let mut sample = Vec::new();
-        if let Ok(col) = unknown.column(TagType::Species.col_name())
-            && let Ok(ca) = col.str()
-        {
-            for v in ca.iter().flatten().take(20) {
-                sample.push(v.to_string());
+    let mut result = match to {
+        Some(to) => {
+            let unknown = unmatched(to)?;
+            if !unknown.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "Unknown tag(s) not found in taglist: {}",
+                    unknown[..unknown.len().min(20)].join(", ")
+                ));
+            }
+            joined
+                .drop(cols([species_col]))
+                .rename(vec![to], vec![species_col], true)
+                .collect()?
+        }
+        // The species stays, a value missing from one column doesn't hold back the others
+        None => {
+            for column in add_columns {
+                let unknown = unmatched(column)?;
+                if !unknown.is_empty() {
+                    println!(
+                        "Warning: {} species without {column} in {}: {}",
+                        unknown.len(),
+                        taglist_csv.display(),
+                        unknown[..unknown.len().min(20)].join(", ")
+                    );
+                }
             }
+            joined.collect()?
+        }
+    };
+
+    let output_filename = format!(
+        "{}_translated.csv",
+        source_csv
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("tags")
+    );
+    let output_dir = versioned_output_dir(
+        &output_dir,
+        std::slice::from_ref(&output_filename),
+        on_conflict,
+    )?;
+    let output_csv = output_dir.join(output_filename);
+    fs::create_dir_all(output_dir.clone())?;
+    let mut file = std::fs::File::create(&output_csv)?;
+    csv_writer(&mut file).finish(&mut result)?;
+
+    println!("Saved to {}", output_csv.display());
+    Ok(())
+}
+
+// Map the species of a tags CSV onto a regional checklist (case-insensitive on `match_column`),
+// appending the checklist `columns` and reporting tagged species absent from it
+pub fn tags_csv_checklist(
+    source_csv: PathBuf,
+    checklist_csv: PathBuf,
+    output_dir: PathBuf,
+    match_column: &str,
+    columns: Vec<String>,
+    column_map: ColumnMap,
+) -> anyhow::Result<()> {
+    let species_col = TagType::Species.col_name();
+    check_csv_columns(&source_csv, &[species_col], &column_map)?;
+    let mut source_df = CsvReadOptions::default()
+        .with_infer_schema_length(Some(0))
+        .try_into_reader_with_file_path(Some(source_csv.clone()))?
+        .finish()?;
+    reject_duplicate_csv_columns(&source_df)?;
+    column_map.apply(&mut source_df)?;
+
+    let checklist_columns: Vec<&str> = std::iter::once(match_column)
+        .chain(columns.iter().map(String::as_str))
+        .collect();
+    check_csv_columns(&checklist_csv, &checklist_columns, &ColumnMap::default())?;
+    for column in &columns {
+        if source_df.get_column_index(column).is_some() {
+            return Err(anyhow::anyhow!(
+                "{} already has a {column} column, remove it before checking",
+                source_csv.display()
+            ));
         }
+    }
+    let mut checklist_df = CsvReadOptions::default()
+        .with_infer_schema_length(Some(0))
+        .with_columns(csv_projection_columns(&checklist_columns))
+        .try_into_reader_with_file_path(Some(checklist_csv.clone()))?
+        .finish()?;
+    reject_duplicate_csv_columns(&checklist_df)?;
+
+    // A repeated name would multiply the tag rows it matches
+    let mut keys = HashSet::new();
+    let mut duplicates: Vec<String> = checklist_df
+        .column(match_column)?
+        .str()?
+        .iter()
+        .flatten()
+        .map(|value| taglist_key(value, true))
+        .filter(|key| !keys.insert(key.clone()))
+        .collect();
+    if !duplicates.is_empty() {
+        duplicates.sort_unstable();
+        duplicates.dedup();
         return Err(anyhow::anyhow!(
-            "Unknown tag(s) not found in taglist: {}",
-            sample.join(", ")
+            "{} has repeated {match_column} value(s): {}",
+            checklist_csv.display(),
+            duplicates.join(", ")
         ));
     }
 
-    let mut result = joined
-        .drop(cols([TagType::Species.col_name()]))
-        .rename(vec![to], vec![TagType::Species.col_name()], true)
-        // .with_column(col(to).alias("species"))
+    // Marks matched rows, checklist columns may be empty themselves
+    let num_checklist = checklist_df.height();
+    checklist_df.with_column(Column::new_scalar(
+        "in_checklist".into(),
+        true.into(),
+        num_checklist,
+    ))?;
+    let joined = join_taglist(source_df, checklist_df, match_column, true)?;
+    let mut discrepancies = joined
+        .clone()
+        .filter(
+            col("in_checklist")
+                .is_null()
+                .and(col(species_col).is_not_null())
+                .and(col(species_col).neq(lit(""))),
+        )
+        .group_by_stable([col(species_col)])
+        .agg([len().alias("records")])
+        .sort(
+            ["records"],
+            SortMultipleOptions::default().with_order_descending(true),
+        )
         .collect()?;
+    let mut result = joined.drop(cols(["in_checklist"])).collect()?;
+    // Indeterminate labels are not species to look up, but new spellings of them would be
+    // counted as species until added to the set
+    let indeterminate = IndeterminateLabels::current();
+    discrepancies = discrepancies
+        .lazy()
+        .filter(indeterminate.expr(species_col).not())
+        .collect()?;
+    let other_spellings: Vec<&str> = discrepancies
+        .column(species_col)?
+        .str()?
+        .iter()
+        .flatten()
+        .filter(|species| indeterminate.is_other_spelling(species))
+        .collect();
+    if !other_spellings.is_empty() {
+        println!(
+            "Warning: {} look like indeterminate labels ({}), add them to species.indeterminate in {CONFIG_FILE} or --indeterminate",
+            other_spellings.join(", "),
+            indeterminate.labels().join(", ")
+        );
+    }
 
     let output_csv = output_dir.join(format!(
-        "{}_translated.csv",
+        "{}_checklist.csv",
         source_csv
             .file_stem()
             .and_then(|stem| stem.to_str())
@@ -1127,10 +4554,22 @@ pub fn tags_csv_translate(
     ));
     fs::create_dir_all(output_dir.clone())?;
     let mut file = std::fs::File::create(&output_csv)?;
-    CsvWriter::new(&mut file)
-        .include_bom(true)
-        .finish(&mut result)?;
-
+    csv_writer(&mut file).finish(&mut result)?;
     println!("Saved to {}", output_csv.display());
+
+    if discrepancies.height() > 0 {
+        println!("{discrepancies}");
+        println!(
+            "Warning: {} tagged species not in {}",
+            discrepancies.height(),
+            checklist_csv.display()
+        );
+    } else {
+        println!("All tagged species are in {}", checklist_csv.display());
+    }
+    let report_csv = output_dir.join("checklist_discrepancies.csv");
+    let mut file = std::fs::File::create(&report_csv)?;
+    csv_writer(&mut file).finish(&mut discrepancies)?;
+    println!("Saved to {}", report_csv.display());
     Ok(())
 }
@@ -0,0 +1,322 @@
+use crate::reconcile::read_csv;
+use crate::schema::{FILE_KEY_COLUMN, PATH_COLUMN, RATING_COLUMN, SPECIES_COLUMN};
+use crate::tags::deployment_values;
+use crate::utils::{
+    ColumnMap, csv_writer, media_path_for, plan_collision_suffixes, seeded_shuffle,
+    sidecar_path_for, sync_modified_time,
+};
+use itertools::izip;
+use polars::prelude::*;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml::{Table, Value};
+
+const STRATA_COLUMNS: &[&str] = &[SPECIES_COLUMN, "deployment", RATING_COLUMN];
+
+// Verification sample spec, e.g.
+//   strata = ["species", "deployment"]
+//   sample_size = 50
+//   seed = 1
+//   rating_bins = ["0-2", "3-4", "5"]
+//   [sizes]
+//   "Leopard cat" = 200
+// Strata whose values include a key of [sizes] get that size (the largest when several match)
+struct VerifySpec {
+    strata: Vec<String>,
+    sample_size: usize,
+    seed: u64,
+    rating_bins: Vec<(String, i64, i64)>,
+    sizes: BTreeMap<String, usize>,
+}
+
+impl VerifySpec {
+    fn read(spec_path: &Path) -> anyhow::Result<Self> {
+        let table: Table = toml::from_str(&fs::read_to_string(spec_path)?)
+            .map_err(|e| anyhow::anyhow!("Failed to parse {}: {e}", spec_path.display()))?;
+        let invalid = |key: &str, expected: &str| {
+            anyhow::anyhow!(
+                "Invalid {key} in {}, expected {expected}",
+                spec_path.display()
+            )
+        };
+        let count = |value: &Value, key: &str| {
+            value
+                .as_integer()
+                .and_then(|value| usize::try_from(value).ok())
+                .ok_or_else(|| invalid(key, "a positive integer"))
+        };
+
+        let strata = match table.get("strata") {
+            None => vec![SPECIES_COLUMN.to_string()],
+            Some(value) => value
+                .as_array()
+                .ok_or_else(|| invalid("strata", "a list of column names"))?
+                .iter()
+                .map(|column| match column.as_str() {
+                    Some(column) if STRATA_COLUMNS.contains(&column) => Ok(column.to_string()),
+                    _ => Err(invalid(
+                        "strata",
+                        &format!("some of {}", STRATA_COLUMNS.join(", ")),
+                    )),
+                })
+                .collect::<anyhow::Result<_>>()?,
+        };
+        let sample_size = table
+            .get("sample_size")
+            .ok_or_else(|| invalid("sample_size", "the records drawn per stratum"))
+            .and_then(|value| count(value, "sample_size"))?;
+        let seed = match table.get("seed") {
+            Some(value) => value
+                .as_integer()
+                .and_then(|seed| u64::try_from(seed).ok())
+                .ok_or_else(|| invalid("seed", "a non-negative integer"))?,
+            None => 0,
+        };
+        let mut rating_bins = Vec::new();
+        for bin in table
+            .get("rating_bins")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let label = bin
+                .as_str()
+                .ok_or_else(|| invalid("rating_bins", "ranges such as \"0-2\""))?;
+            let (low, high) = label.split_once('-').unwrap_or((label, label));
+            let (Ok(low), Ok(high)) = (low.trim().parse(), high.trim().parse()) else {
+                return Err(invalid("rating_bins", "ranges such as \"0-2\""));
+            };
+            rating_bins.push((label.to_string(), low, high));
+        }
+        if strata.iter().any(|column| column == RATING_COLUMN) && rating_bins.is_empty() {
+            return Err(invalid("rating_bins", "bins when stratifying by rating"));
+        }
+        let mut sizes = BTreeMap::new();
+        if let Some(value) = table.get("sizes") {
+            let sizes_table = value
+                .as_table()
+                .ok_or_else(|| invalid("sizes", "a table of stratum values and sizes"))?;
+            for (key, value) in sizes_table {
+                sizes.insert(key.clone(), count(value, &format!("sizes.{key}"))?);
+            }
+        }
+        Ok(Self {
+            strata,
+            sample_size,
+            seed,
+            rating_bins,
+            sizes,
+        })
+    }
+
+    fn rating_bin(&self, rating: &str) -> String {
+        rating
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .and_then(|rating| {
+                self.rating_bins
+                    .iter()
+                    .find(|(_, low, high)| (*low as f64..=*high as f64).contains(&rating))
+            })
+            .map_or_else(|| "unrated".to_string(), |(label, _, _)| label.clone())
+    }
+
+    fn size_for(&self, values: &[String]) -> usize {
+        values
+            .iter()
+            .filter_map(|value| self.sizes.get(value))
+            .max()
+            .copied()
+            .unwrap_or(self.sample_size)
+    }
+}
+
+/// Draw a stratified sample of a tags CSV for verifying imported labels, copy it into
+/// `species/deployment/` and write a review sheet plus the manifest used by reconcile
+pub fn extract_verify_sample(
+    csv_path: PathBuf,
+    spec_path: PathBuf,
+    output_dir: PathBuf,
+    column_map: ColumnMap,
+) -> anyhow::Result<()> {
+    let spec = VerifySpec::read(&spec_path)?;
+    let mut required = vec![PATH_COLUMN, SPECIES_COLUMN];
+    if spec.strata.iter().any(|column| column == RATING_COLUMN) {
+        required.push(RATING_COLUMN);
+    }
+    let df = read_csv(&csv_path, &required, &column_map)?;
+    // One row per file, sidecar rows standing for their media
+    let df = df
+        .lazy()
+        .unique_stable(
+            Some(cols([PATH_COLUMN, SPECIES_COLUMN])),
+            UniqueKeepStrategy::First,
+        )
+        .collect()?;
+
+    let paths: Vec<&str> = df
+        .column(PATH_COLUMN)?
+        .str()?
+        .iter()
+        .map(Option::unwrap_or_default)
+        .collect();
+    let species: Vec<&str> = df
+        .column(SPECIES_COLUMN)?
+        .str()?
+        .iter()
+        .map(Option::unwrap_or_default)
+        .collect();
+    let deployments = deployment_values(&df)?;
+    let ratings: Vec<&str> = match df.column(RATING_COLUMN) {
+        Ok(column) => column
+            .str()?
+            .iter()
+            .map(Option::unwrap_or_default)
+            .collect(),
+        Err(_) => vec![""; df.height()],
+    };
+    let file_keys: Vec<String> = match df.column(FILE_KEY_COLUMN) {
+        Ok(column) => column
+            .str()?
+            .iter()
+            .map(|key| key.unwrap_or_default().to_string())
+            .collect(),
+        Err(_) => paths
+            .iter()
+            .map(|path| {
+                let media = media_path_for(Path::new(path)).unwrap_or_else(|| PathBuf::from(path));
+                media
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            })
+            .collect(),
+    };
+
+    // stratum values -> row indices
+    let mut strata: BTreeMap<Vec<String>, Vec<usize>> = BTreeMap::new();
+    for row in 0..df.height() {
+        if paths[row].is_empty() {
+            continue;
+        }
+        let values = spec
+            .strata
+            .iter()
+            .map(|column| match column.as_str() {
+                SPECIES_COLUMN => species[row].to_string(),
+                RATING_COLUMN => spec.rating_bin(ratings[row]),
+                _ => deployments[row].clone(),
+            })
+            .collect();
+        strata.entry(values).or_default().push(row);
+    }
+
+    let mut sample: Vec<(usize, String)> = Vec::new();
+    let mut num_short = 0;
+    println!("Sampling by {}:", spec.strata.join(", "));
+    for (values, mut rows) in strata {
+        let label = values.join("|");
+        let size = spec.size_for(&values);
+        if rows.len() < size {
+            num_short += 1;
+            println!("  {label}: {} (all, {size} requested)", rows.len());
+        } else {
+            println!("  {label}: {size}/{}", rows.len());
+        }
+        seeded_shuffle(&mut rows, spec.seed);
+        rows.truncate(size);
+        sample.extend(rows.into_iter().map(|row| (row, label.clone())));
+    }
+    sample.sort_unstable();
+    if num_short > 0 {
+        println!(
+            "Note: {num_short} stratum/strata had fewer records than requested, taken in full"
+        );
+    }
+
+    let mut sheet_paths: Vec<String> = Vec::new();
+    let mut original_paths: Vec<String> = Vec::new();
+    let mut media_paths: Vec<String> = Vec::new();
+    let mut sheet_keys: Vec<String> = Vec::new();
+    let mut sheet_strata: Vec<String> = Vec::new();
+    let mut predicted: Vec<&str> = Vec::new();
+    // (sidecar, media) of each sampled record and where it goes, colliding names suffixed
+    let mut inputs: Vec<(PathBuf, PathBuf)> = Vec::with_capacity(sample.len());
+    let mut planned: Vec<(String, PathBuf)> = Vec::with_capacity(sample.len());
+    for (row, _) in &sample {
+        let input_path = Path::new(paths[*row]);
+        let (sidecar, media) = match media_path_for(input_path) {
+            Some(media) => (input_path.to_path_buf(), media),
+            None => (sidecar_path_for(input_path), input_path.to_path_buf()),
+        };
+        let species_dir = match species[*row] {
+            "" => "untagged_species",
+            species => species,
+        };
+        let target = output_dir
+            .join(species_dir)
+            .join(&deployments[*row])
+            .join(media.file_name().unwrap_or_default());
+        planned.push((media.to_string_lossy().into_owned(), target));
+        inputs.push((sidecar, media));
+    }
+    let output_paths = plan_collision_suffixes(&planned, Path::exists);
+    for ((row, stratum), (sidecar, media), output_media) in izip!(sample, inputs, output_paths) {
+        if let Some(dir) = output_media.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::copy(&media, &output_media)
+            .map_err(|e| anyhow::anyhow!("Failed to copy {}: {e}", media.display()))?;
+        if sidecar.is_file() {
+            fs::copy(&sidecar, sidecar_path_for(&output_media))?;
+        }
+        media_paths.push(media.to_string_lossy().into_owned());
+        sync_modified_time(media, output_media.clone())?;
+        sheet_paths.push(output_media.to_string_lossy().into_owned());
+        original_paths.push(paths[row].to_string());
+        sheet_keys.push(file_keys[row].clone());
+        sheet_strata.push(stratum);
+        predicted.push(species[row]);
+    }
+
+    // Reviewers correct `species` where the prediction is wrong and mark the rows they checked,
+    // `path` points at the copies so reconcile maps the sheet back through the manifest
+    let num_sampled = sheet_paths.len();
+    let blank = vec![""; num_sampled];
+    let mut df_sheet = df!(
+        PATH_COLUMN => &sheet_paths,
+        "original_path" => &original_paths,
+        FILE_KEY_COLUMN => &sheet_keys,
+        "stratum" => &sheet_strata,
+        "predicted_species" => &predicted,
+        SPECIES_COLUMN => &predicted,
+        "verified" => &blank,
+        "reviewer" => &blank,
+        "notes" => &blank,
+    )?;
+    let mut df_manifest = df!(
+        PATH_COLUMN => &media_paths,
+        FILE_KEY_COLUMN => &sheet_keys,
+        "output_path" => &sheet_paths,
+        "stratum" => &sheet_strata,
+    )?;
+    for (name, df) in [
+        ("verify_sheet.csv", &mut df_sheet),
+        ("manifest.csv", &mut df_manifest),
+    ] {
+        let mut file = fs::File::create(output_dir.join(name))?;
+        csv_writer(&mut file).finish(df)?;
+    }
+    println!(
+        "Extracted {num_sampled} record(s) to {}, review sheet: {}",
+        output_dir.display(),
+        output_dir.join("verify_sheet.csv").display()
+    );
+    println!(
+        "Apply corrections with serval reconcile --original {} --reviewed verify_sheet.csv --manifest manifest.csv",
+        csv_path.display()
+    );
+    Ok(())
+}
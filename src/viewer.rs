@@ -0,0 +1,178 @@
+use crate::utils::{csv_datetime_format, csv_writer};
+use console::{Key, Term, measure_text_width, pad_str, truncate_str};
+use polars::prelude::*;
+use std::fs;
+use std::path::Path;
+
+/// One table of the observe review, shown as computed and savable to CSV
+pub struct ReviewView {
+    pub name: &'static str,
+    pub df: DataFrame,
+}
+
+const HELP: &str = "←/→ view  ↑/↓ PgUp/PgDn scroll  s save CSV  q quit";
+
+fn cell(value: AnyValue) -> String {
+    match value {
+        AnyValue::Null => String::new(),
+        AnyValue::String(value) => value.to_string(),
+        AnyValue::StringOwned(value) => value.to_string(),
+        value => value.to_string(),
+    }
+}
+
+// Header and the rows from `offset` that fit in `height` lines, columns as wide as their
+// widest visible cell and the line cut at `width`
+fn render_table(df: &DataFrame, offset: usize, height: usize, width: usize) -> Vec<String> {
+    let end = (offset + height.saturating_sub(2)).min(df.height());
+    let mut rows: Vec<Vec<String>> = vec![
+        df.get_column_names()
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+    ];
+    for row in offset..end {
+        rows.push(
+            df.columns()
+                .iter()
+                .map(|column| column.get(row).map(cell).unwrap_or_default())
+                .collect(),
+        );
+    }
+    let widths: Vec<usize> = (0..df.width())
+        .map(|index| {
+            rows.iter()
+                .map(|row| measure_text_width(&row[index]))
+                .max()
+                .unwrap_or_default()
+                .min(60)
+        })
+        .collect();
+    let format_row = |row: &Vec<String>| {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(value, &column_width)| {
+                pad_str(value, column_width, console::Alignment::Left, Some("…")).into_owned()
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        truncate_str(&line, width, "…").into_owned()
+    };
+    let mut lines = vec![format_row(&rows[0])];
+    lines.push(
+        "─"
+            .repeat(widths.iter().sum::<usize>() + 2 * widths.len().saturating_sub(1))
+            .chars()
+            .take(width)
+            .collect(),
+    );
+    lines.extend(rows[1..].iter().map(format_row));
+    lines
+}
+
+fn draw(
+    term: &Term,
+    views: &[ReviewView],
+    current: usize,
+    offset: usize,
+    status: &str,
+) -> anyhow::Result<()> {
+    let (height, width) = term.size();
+    let (height, width) = (height as usize, width as usize);
+    let tabs = views
+        .iter()
+        .enumerate()
+        .map(|(index, view)| {
+            if index == current {
+                format!("[{}]", view.name)
+            } else {
+                format!(" {} ", view.name)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+    let view = &views[current];
+    term.clear_screen()?;
+    term.write_line(&truncate_str(&tabs, width, "…"))?;
+    // Tabs on the first line, the table below and the footer on the last line
+    for line in render_table(&view.df, offset, height.saturating_sub(4), width) {
+        term.write_line(&line)?;
+    }
+    term.move_cursor_to(0, height.saturating_sub(1))?;
+    let position = format!(
+        "{}-{} of {}",
+        (offset + 1).min(view.df.height()),
+        (offset + height.saturating_sub(6)).min(view.df.height()),
+        view.df.height()
+    );
+    let footer = if status.is_empty() {
+        format!("{position}  {HELP}")
+    } else {
+        format!("{position}  {status}")
+    };
+    term.write_str(&truncate_str(&footer, width, "…"))?;
+    term.flush()?;
+    Ok(())
+}
+
+/// Browse the observe summaries in the terminal, `s` saves the current one as
+/// `review_<view><output_suffix>` in `output_dir`. Outputs already written are left alone.
+pub fn review_observe(
+    views: Vec<ReviewView>,
+    output_dir: &Path,
+    output_suffix: &str,
+) -> anyhow::Result<()> {
+    let term = Term::stdout();
+    if !term.is_term() {
+        println!("Note: --review needs a terminal, skipped");
+        return Ok(());
+    }
+    let (mut current, mut offset) = (0, 0);
+    let mut status = String::new();
+    term.hide_cursor()?;
+    let result = loop {
+        if let Err(e) = draw(&term, &views, current, offset, &status) {
+            break Err(e);
+        }
+        status.clear();
+        let page = (term.size().0 as usize).saturating_sub(6).max(1);
+        let last = views[current].df.height().saturating_sub(1);
+        match term.read_key() {
+            Ok(Key::ArrowRight | Key::Tab) => (current, offset) = ((current + 1) % views.len(), 0),
+            Ok(Key::ArrowLeft | Key::BackTab) => {
+                (current, offset) = ((current + views.len() - 1) % views.len(), 0)
+            }
+            Ok(Key::ArrowDown) => offset = (offset + 1).min(last),
+            Ok(Key::ArrowUp) => offset = offset.saturating_sub(1),
+            Ok(Key::PageDown | Key::Char(' ')) => offset = (offset + page).min(last),
+            Ok(Key::PageUp) => offset = offset.saturating_sub(page),
+            Ok(Key::Home) => offset = 0,
+            Ok(Key::End) => offset = last,
+            Ok(Key::Char('s')) => {
+                let view = &views[current];
+                let path = output_dir.join(format!(
+                    "review_{}{output_suffix}",
+                    view.name.to_lowercase().replace(' ', "_")
+                ));
+                let saved = fs::File::create(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|mut file| {
+                        csv_writer(&mut file)
+                            .with_datetime_format(csv_datetime_format("%Y-%m-%d %H:%M:%S"))
+                            .finish(&mut view.df.clone())
+                            .map_err(anyhow::Error::from)
+                    });
+                status = match saved {
+                    Ok(()) => format!("Saved to {}", path.display()),
+                    Err(e) => format!("Failed to save {}: {e}", path.display()),
+                };
+            }
+            Ok(Key::Char('q') | Key::Escape) | Err(_) => break Ok(()),
+            Ok(_) => {}
+        }
+    };
+    term.show_cursor()?;
+    term.clear_screen()?;
+    result
+}
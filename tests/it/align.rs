@@ -0,0 +1,158 @@
+// align copies what it can and reports the files it can't, unless --fail-fast, and leaves
+// out an output directory reached through a link into the project
+#![cfg(unix)]
+
+use crate::common::{TempDir, list_files};
+use serval::utils::{AlignReport, ResourceType, deployments_align, exclude_output_dir};
+use std::fs;
+use std::path::Path;
+
+// Project with DEP01_coll and DEP02_coll, DEP01 holding a file that can't be read
+fn unreadable_project(dir: &Path) {
+    for (deployment, file_name) in [
+        ("DEP01_coll", "IMG_0001.JPG"),
+        ("DEP01_coll/100MEDIA", "IMG_0003.JPG"),
+        ("DEP02_coll", "IMG_0001.JPG"),
+    ] {
+        let deploy_dir = dir.join("project/coll").join(deployment);
+        fs::create_dir_all(&deploy_dir).unwrap();
+        fs::write(deploy_dir.join(file_name), b"jpeg").unwrap();
+    }
+    // A dangling link fails to read even as root, where permission bits are ignored
+    std::os::unix::fs::symlink(
+        dir.join("missing.JPG"),
+        dir.join("project/coll/DEP01_coll/IMG_0002.JPG"),
+    )
+    .unwrap();
+    fs::write(
+        dir.join("deployments.csv"),
+        "deploymentID\nDEP01_coll\nDEP02_coll\n",
+    )
+    .unwrap();
+}
+
+// DEP01_coll and DEP02_coll holding one image each
+fn plain_project(dir: &Path) {
+    for deployment in ["DEP01_coll", "DEP02_coll"] {
+        let deploy_dir = dir.join("project/coll").join(deployment);
+        fs::create_dir_all(&deploy_dir).unwrap();
+        fs::write(deploy_dir.join("IMG_0001.JPG"), b"jpeg").unwrap();
+    }
+    fs::write(
+        dir.join("deployments.csv"),
+        "deploymentID\nDEP01_coll\nDEP02_coll\n",
+    )
+    .unwrap();
+}
+
+fn align(
+    dir: &Path,
+    output: &Path,
+    fail_fast: bool,
+    excluded_dir: Option<&Path>,
+) -> anyhow::Result<AlignReport> {
+    deployments_align(
+        dir.join("project"),
+        output.to_path_buf(),
+        dir.join("deployments.csv"),
+        ResourceType::Image,
+        false,
+        false,
+        false,
+        None,
+        Vec::new(),
+        Vec::new(),
+        1,
+        fail_fast,
+        excluded_dir,
+    )
+}
+
+#[test]
+fn unreadable_file_is_reported_and_the_run_completes() {
+    let dir = TempDir::new("align");
+    unreadable_project(dir.path());
+
+    let report = align(dir.path(), &dir.path().join("aligned"), false, None).unwrap();
+    assert!(!report.is_complete());
+    assert!(report.failed_deployments.is_empty());
+    let failed: Vec<(&str, &str)> = report
+        .failed_files
+        .iter()
+        .map(|(deploy_id, path, _)| {
+            (
+                deploy_id.as_str(),
+                path.file_name().unwrap().to_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(failed, [("DEP01_coll", "IMG_0002.JPG")]);
+    assert_eq!(
+        list_files(&dir.path().join("aligned")),
+        [
+            "coll/DEP01_coll/DEP01_coll-100MEDIA-IMG_0003.JPG",
+            "coll/DEP01_coll/DEP01_coll-IMG_0001.JPG",
+            "coll/DEP02_coll/DEP02_coll-IMG_0001.JPG",
+        ]
+    );
+
+    // With --fail-fast the deployment stops at the file, the other deployments still run
+    let report = align(
+        dir.path(),
+        &dir.path().join("aligned_fail_fast"),
+        true,
+        None,
+    )
+    .unwrap();
+    assert_eq!(report.failed_deployments, ["DEP01_coll"]);
+    assert!(report.failed_files.is_empty());
+}
+
+#[test]
+fn output_linked_into_a_deployment_is_not_aligned_again() {
+    let dir = TempDir::new("align_link");
+    plain_project(dir.path());
+    let target = dir.path().join("project/coll/DEP01_coll/aligned");
+    fs::create_dir_all(&target).unwrap();
+    let output = dir.path().join("out");
+    std::os::unix::fs::symlink(&target, &output).unwrap();
+
+    let excluded_dir = exclude_output_dir(&dir.path().join("project"), &output).unwrap();
+    assert_eq!(excluded_dir, Some(fs::canonicalize(&target).unwrap()));
+    // A rerun finds the first run's copies under DEP01_coll unless they are left out
+    for _ in 0..2 {
+        let report = align(dir.path(), &output, false, excluded_dir.as_deref()).unwrap();
+        assert!(report.is_complete());
+    }
+    assert_eq!(
+        list_files(&target),
+        [
+            "coll/DEP01_coll/DEP01_coll-IMG_0001.JPG",
+            "coll/DEP02_coll/DEP02_coll-IMG_0001.JPG",
+        ]
+    );
+}
+
+#[test]
+fn dangling_output_link_resolves_to_its_target() {
+    let dir = TempDir::new("align_dangling");
+    plain_project(dir.path());
+    let output = dir.path().join("out");
+    std::os::unix::fs::symlink(dir.path().join("project/coll/DEP01_coll/aligned"), &output)
+        .unwrap();
+
+    let excluded_dir = exclude_output_dir(&dir.path().join("project"), &output).unwrap();
+    let project = fs::canonicalize(dir.path().join("project")).unwrap();
+    assert_eq!(excluded_dir, Some(project.join("coll/DEP01_coll/aligned")));
+}
+
+#[test]
+fn output_linked_to_the_project_is_refused() {
+    let dir = TempDir::new("align_same");
+    plain_project(dir.path());
+    let output = dir.path().join("out");
+    std::os::unix::fs::symlink(dir.path().join("project"), &output).unwrap();
+
+    let err = exclude_output_dir(&dir.path().join("project"), &output).unwrap_err();
+    assert!(err.to_string().contains("is the input directory"), "{err}");
+}
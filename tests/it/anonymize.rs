@@ -0,0 +1,193 @@
+// --anonymize-paths hashes paths and pseudonymizes deployments, the keyfile maps them back
+use crate::common::{Project, RECORDS, csv_column, find_output, observe};
+use serval::tags::{
+    CaptureOptions, CaptureSettings, ExtractOptions, ObserveSettings, extract_resources,
+    get_temporal_independence, init_xmp, update_datetime, update_tags,
+};
+use serval::utils::{
+    ColumnMap, DeploymentLookup, ExtractFilterType, OnConflict, TagType, XmpUpdateType,
+};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::Path;
+
+// token -> original of one kind of keyfile row
+fn keyfile_map(keyfile: &Path, kind: &str) -> HashMap<String, String> {
+    let kinds = csv_column(keyfile, "kind");
+    let tokens = csv_column(keyfile, "token");
+    let originals = csv_column(keyfile, "original");
+    kinds
+        .into_iter()
+        .zip(tokens.into_iter().zip(originals))
+        .filter(|(row_kind, _)| row_kind == kind)
+        .map(|(_, mapping)| mapping)
+        .collect()
+}
+
+// No directory or file name of the project, nor a media file name or stem, anywhere in `output`
+fn assert_no_path_components(project: &Project, output: &Path) {
+    let text = fs::read_to_string(output).unwrap();
+    for record in RECORDS {
+        let sidecar = project.sidecar_path(record);
+        let stem = Path::new(record.file_name).file_stem().unwrap();
+        for component in sidecar
+            .components()
+            .map(|component| component.as_os_str())
+            .chain([OsStr::new(record.file_name), stem])
+        {
+            let component = component.to_string_lossy();
+            if component.len() > 1 {
+                assert!(
+                    !text.contains(component.as_ref()),
+                    "{component} in {}",
+                    output.display()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn anonymized_outputs_map_back_through_the_keyfile() {
+    let project = Project::create();
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let species_csv =
+        project.write_update_csv("species_update.csv", "species,xmp_update", |record| {
+            format!(",{}", record.species)
+        });
+    update_tags(
+        species_csv,
+        XmpUpdateType::Species,
+        None,
+        true,
+        ColumnMap::default(),
+    )
+    .unwrap();
+    let datetime_csv =
+        project.write_update_csv("datetime_update.csv", "xmp_update_datetime", |record| {
+            record.datetime.to_string()
+        });
+    update_datetime(datetime_csv, None, true, ColumnMap::default()).unwrap();
+
+    let observe_dir = project.output_dir("observe");
+    observe(
+        &project.root(),
+        &observe_dir,
+        ObserveSettings {
+            deployment_lookup: Some(DeploymentLookup::Level(project.deploy_path_index())),
+            unique_name: true,
+            anonymize_paths: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let tags_csv = find_output(&observe_dir, "tags_");
+    let keyfile = find_output(&observe_dir, "anonymize_key_");
+    let root = project.root().to_string_lossy().into_owned();
+    for output in [&tags_csv, &find_output(&observe_dir, "data_quality_")] {
+        assert_no_path_components(&project, output);
+    }
+    let filenames = keyfile_map(&keyfile, "filename");
+    assert!(
+        csv_column(&tags_csv, "filename")
+            .iter()
+            .chain(&csv_column(&tags_csv, "file_key"))
+            .all(|token| token.starts_with("anon_")),
+    );
+    assert!(filenames.values().all(|filename| {
+        RECORDS
+            .iter()
+            .any(|record| filename.starts_with(record.file_name))
+    }));
+
+    let paths = keyfile_map(&keyfile, "path");
+    let mut restored: Vec<String> = csv_column(&tags_csv, "path")
+        .iter()
+        .map(|token| {
+            assert!(token.starts_with("anon_"), "{token}");
+            paths[token].clone()
+        })
+        .collect();
+    restored.sort();
+    let mut expected: Vec<String> = RECORDS
+        .iter()
+        .map(|record| project.sidecar_path(record).to_string_lossy().into_owned())
+        .collect();
+    expected.sort();
+    assert_eq!(restored, expected);
+
+    // Pseudonyms are the same in every output of the run
+    let deployments = keyfile_map(&keyfile, "deployment");
+    let mut pseudonyms: Vec<String> = deployments.keys().cloned().collect();
+    pseudonyms.sort();
+    assert_eq!(pseudonyms, ["site_001", "site_002"]);
+    let dep01 = deployments
+        .iter()
+        .find(|(_, deployment)| deployment.as_str() == "DEP01")
+        .map(|(pseudonym, _)| pseudonym.clone())
+        .unwrap();
+    let tag_deployments = csv_column(&tags_csv, "deployment");
+    assert_eq!(tag_deployments.iter().filter(|d| **d == dep01).count(), 4);
+    let quality_deployments = csv_column(&find_output(&observe_dir, "data_quality_"), "deployment");
+    let mut quality_deployments_sorted = quality_deployments.clone();
+    quality_deployments_sorted.sort();
+    assert_eq!(quality_deployments_sorted, pseudonyms);
+
+    // There are no files behind the tokens to extract
+    let error = extract_resources(
+        "Serval".to_string(),
+        ExtractFilterType::Species,
+        tags_csv,
+        project.output_dir("extract"),
+        ExtractOptions {
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert!(error.to_string().contains("anonymized paths"), "{error}");
+
+    // Capture anonymizes the tags CSV it reads, its params don't give the input location
+    let plain_dir = project.output_dir("observe_plain");
+    observe(&project.root(), &plain_dir, ObserveSettings::default()).unwrap();
+    let capture_dir = project.output_dir("capture");
+    get_temporal_independence(
+        find_output(&plain_dir, "tags_"),
+        capture_dir.clone(),
+        CaptureOptions {
+            event: true,
+            anonymize_paths: true,
+            exclude_tags: vec!["Blank".to_string()],
+            on_conflict: OnConflict::Fail,
+            settings: Some(CaptureSettings {
+                min_delta_time: 30,
+                compare_to_last_record: false,
+                target: TagType::Species,
+                deploy_path_index: Some(project.deploy_path_index()),
+            }),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let keyfile = capture_dir.join("anonymize_key.csv");
+    let paths = keyfile_map(&keyfile, "path");
+    for output in [
+        "temporal-independence_species_30m_LIR.csv",
+        "events_species_30m_LIR.csv",
+    ] {
+        let output = capture_dir.join(output);
+        assert_no_path_components(&project, &output);
+        assert!(
+            csv_column(&output, "path")
+                .iter()
+                .all(|token| paths.contains_key(token))
+        );
+    }
+    let mut counted: Vec<String> =
+        csv_column(&capture_dir.join("count_by_deployment.csv"), "deployment");
+    counted.sort();
+    counted.dedup();
+    assert_eq!(counted, ["site_001", "site_002"]);
+    let params = fs::read_to_string(capture_dir.join("params_species_30m_LIR.toml")).unwrap();
+    assert!(!params.contains(&root), "{params}");
+}
@@ -0,0 +1,45 @@
+// Observe reads the sidecars of a ZIP archive in place, entries it can't read go to the errors CSV
+use crate::common::{TempDir, csv_column, find_output, observe};
+use serval::tags::ObserveSettings;
+use std::fs;
+
+// IMG_0002 no longer matches its checksum, IMG_0003 is neither UTF-8 nor UTF-16
+const ARCHIVE: &[u8] = include_bytes!("fixtures/archive_broken_entries.zip");
+
+#[test]
+fn broken_entries_are_listed_and_the_others_read() {
+    let dir = TempDir::new("archive");
+    let archive = dir.path().join("DEP01.zip");
+    fs::write(&archive, ARCHIVE).unwrap();
+
+    let output_dir = dir.path().join("observe");
+    observe(&archive, &output_dir, ObserveSettings::default()).unwrap();
+    let entry_name = |path: String| path.rsplit_once("!/").unwrap().1.to_string();
+
+    let tags = find_output(&output_dir, "tags_");
+    let read: Vec<(String, String)> = csv_column(&tags, "path")
+        .into_iter()
+        .map(entry_name)
+        .zip(csv_column(&tags, "species"))
+        .filter(|(_, species)| !species.is_empty())
+        .collect();
+    assert_eq!(
+        read,
+        [
+            ("DEP01/IMG_0001.JPG.xmp".to_string(), "Serval".to_string()),
+            ("DEP01/IMG_0004.JPG.xmp".to_string(), "Leopard".to_string()),
+        ]
+    );
+
+    let errors = find_output(&output_dir, "errors_");
+    let failed: Vec<(String, String)> = csv_column(&errors, "path")
+        .into_iter()
+        .map(entry_name)
+        .zip(csv_column(&errors, "error"))
+        .collect();
+    assert_eq!(failed.len(), 2, "{failed:?}");
+    assert_eq!(failed[0].0, "DEP01/IMG_0002.JPG.xmp");
+    assert!(failed[0].1.starts_with("Failed to read"), "{failed:?}");
+    assert_eq!(failed[1].0, "DEP01/IMG_0003.JPG.xmp");
+    assert!(failed[1].1.starts_with("Failed to decode"), "{failed:?}");
+}
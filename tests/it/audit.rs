@@ -0,0 +1,76 @@
+// Sidecar updates are appended to the audit log with their backups, which verify checks
+use crate::common::{Project, RECORDS, in_own_process};
+use serde_json::Value;
+use serval::audit::{audit_verify, start_audit};
+use serval::tags::{init_xmp, update_tags};
+use serval::utils::{ColumnMap, XmpUpdateType};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+#[test]
+fn updates_are_logged_with_their_backups() {
+    if !in_own_process("audit::updates_are_logged_with_their_backups") {
+        return;
+    }
+    let project = Project::create();
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let log = project.dir.path().join("logs/serval_audit.jsonl");
+    start_audit(&log).unwrap();
+    let csv = project.write_update_csv(
+        "species_update.csv",
+        "species,xmp_update,rating_update",
+        |record| match record.file_name {
+            "IMG_0003.JPG" => format!(",{},4", record.species),
+            _ => format!(",{},", record.species),
+        },
+    );
+    update_tags(
+        csv,
+        XmpUpdateType::Species,
+        None,
+        false,
+        ColumnMap::default(),
+    )
+    .unwrap();
+
+    let entries: Vec<Value> = fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), RECORDS.len() + 1);
+    let sidecar = project
+        .sidecar_path(&RECORDS[2])
+        .to_string_lossy()
+        .replace('\\', "/");
+    let changes: Vec<(&str, &str, &str)> = entries
+        .iter()
+        .filter(|entry| entry["path"] == sidecar.as_str())
+        .map(|entry| {
+            (
+                entry["field"].as_str().unwrap(),
+                entry["old_value"].as_str().unwrap(),
+                entry["new_value"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(changes, [("rating", "", "4"), ("species", "", "Leopard")]);
+    assert!(entries.iter().all(|entry| {
+        Path::new(entry["backup"].as_str().unwrap()).is_file()
+            && entry["command"].as_str().is_some()
+    }));
+    audit_verify(&log).unwrap();
+
+    // A line cut short by a crash is skipped, a deleted backup is reported
+    fs::OpenOptions::new()
+        .append(true)
+        .open(&log)
+        .unwrap()
+        .write_all(b"{\"timestamp\":")
+        .unwrap();
+    audit_verify(&log).unwrap();
+    fs::remove_file(entries[0]["backup"].as_str().unwrap()).unwrap();
+    let error = audit_verify(&log).unwrap_err().to_string();
+    assert!(error.starts_with("1 backup(s) missing"), "{error}");
+}
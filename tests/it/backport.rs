@@ -0,0 +1,112 @@
+// backport carries fixes made in a reviewed temporal-independence CSV back to the tags
+use crate::common::{TempDir, read_csv};
+use serval::backport::backport_corrections;
+use serval::utils::ColumnMap;
+use std::fs;
+
+#[test]
+fn reviewed_species_fixes_are_backported() {
+    let dir = TempDir::new("backport");
+    let root = dir.path();
+    let tags = root.join("tags.csv");
+    fs::write(
+        &tags,
+        "path,species,rating\n\
+         /project/DEP01/IMG_0001.JPG.xmp,Serval,3\n\
+         /project/DEP01/IMG_0002.JPG.xmp,Serval,\n\
+         /project/DEP01/IMG_0003.JPG.xmp,Leopard cat,\n\
+         /project/DEP01/IMG_0003.JPG.xmp,Serval,\n\
+         /project/DEP01/IMG_0004.JPG.xmp,Leopard cat,\n\
+         /project/DEP01/IMG_0004.JPG.xmp,Serval,\n\
+         /project/DEP02/IMG_0001.JPG.xmp,Serval,\n\
+         /project/DEP02/IMG_0002.JPG.xmp,Serval,\n",
+    )
+    .unwrap();
+    let original = root.join("temporal-independence_species_30m_LIR.csv");
+    fs::write(
+        &original,
+        "deployment,path,time,species\n\
+         DEP01,/project/DEP01/IMG_0001.JPG.xmp,2024-03-01 10:00:00,Serval\n\
+         DEP01,/project/DEP01/IMG_0003.JPG.xmp,2024-03-01 11:00:00,Leopard cat\n\
+         DEP01,/project/DEP01/IMG_0003.JPG.xmp,2024-03-01 11:00:00,Serval\n\
+         DEP01,/project/DEP01/IMG_0004.JPG.xmp,2024-03-01 12:00:00,Leopard cat\n\
+         DEP01,/project/DEP01/IMG_0004.JPG.xmp,2024-03-01 12:00:00,Serval\n\
+         DEP02,/project/DEP02/IMG_0001.JPG.xmp,2024-03-02 09:00:00,Serval\n\
+         DEP02,/project/DEP02/IMG_0002.JPG.xmp,2024-03-02 12:00:00,Serval\n",
+    )
+    .unwrap();
+    // Saved from a spreadsheet: times rewritten, DEP02 IMG_0001 deleted, IMG_0002 re-timed
+    let reviewed = root.join("temporal-independence_edited.csv");
+    fs::write(
+        &reviewed,
+        "deployment,path,time,species\n\
+         DEP01,/project/DEP01/IMG_0001.JPG.xmp,2024/03/01 10:00:00,Civet\n\
+         DEP01,/project/DEP01/IMG_0003.JPG.xmp,2024/03/01 11:00:00,Leopard cat\n\
+         DEP01,/project/DEP01/IMG_0003.JPG.xmp,2024/03/01 11:00:00,Leopard cat\n\
+         DEP01,/project/DEP01/IMG_0004.JPG.xmp,2024/03/01 12:00:00,Civet\n\
+         DEP01,/project/DEP01/IMG_0004.JPG.xmp,2024/03/01 12:00:00,Badger\n\
+         DEP02,/project/DEP02/IMG_0002.JPG.xmp,2024/03/02 12:30:00,Serval\n",
+    )
+    .unwrap();
+
+    let output_dir = root.join("output");
+    backport_corrections(
+        reviewed,
+        original,
+        tags,
+        output_dir.clone(),
+        ColumnMap::default(),
+    )
+    .unwrap();
+
+    // IMG_0003 merged into the Leopard cat it already had
+    assert_eq!(
+        read_csv(&output_dir.join("backport_updates.csv")),
+        [
+            vec!["path", "species", "xmp_update"],
+            vec!["/project/DEP01/IMG_0001.JPG.xmp", "Serval", "Civet"],
+            vec!["/project/DEP01/IMG_0003.JPG.xmp", "Serval", "DELETE_TAG"],
+        ]
+    );
+    assert_eq!(
+        read_csv(&output_dir.join("tags_backported.csv")),
+        [
+            vec!["path", "species", "rating"],
+            vec!["/project/DEP01/IMG_0001.JPG.xmp", "Civet", "3"],
+            vec!["/project/DEP01/IMG_0002.JPG.xmp", "Serval", ""],
+            vec!["/project/DEP01/IMG_0003.JPG.xmp", "Leopard cat", ""],
+            vec!["/project/DEP01/IMG_0004.JPG.xmp", "Leopard cat", ""],
+            vec!["/project/DEP01/IMG_0004.JPG.xmp", "Serval", ""],
+            vec!["/project/DEP02/IMG_0001.JPG.xmp", "Serval", ""],
+            vec!["/project/DEP02/IMG_0002.JPG.xmp", "Serval", ""],
+        ]
+    );
+    // Two of IMG_0004's species changed, which became which can't be told
+    assert_eq!(
+        read_csv(&output_dir.join("backport_unmatched.csv")),
+        [
+            vec!["deployment", "path", "time", "species", "reason"],
+            vec![
+                "DEP01",
+                "/project/DEP01/IMG_0004.JPG.xmp",
+                "2024-03-01 12:00:00",
+                "Civet|Badger",
+                "ambiguous: 2 changed among 2 rows of this record"
+            ],
+            vec![
+                "DEP02",
+                "/project/DEP02/IMG_0001.JPG.xmp",
+                "2024-03-02 09:00:00",
+                "Serval",
+                "deleted in review"
+            ],
+            vec![
+                "DEP02",
+                "/project/DEP02/IMG_0002.JPG.xmp",
+                "2024-03-02 12:30:00",
+                "Serval",
+                "re-timed in review from 2024-03-02 12:00:00"
+            ],
+        ]
+    );
+}
@@ -0,0 +1,85 @@
+// observe --camera-info adds the make, model and serial of the camera behind each file
+use crate::common::{TempDir, csv_column, find_output, observe};
+use serval::tags::ObserveSettings;
+use std::fs;
+
+fn camera_sidecar(camera: &str, species: &[&str]) -> String {
+    let subjects: String = species
+        .iter()
+        .map(|species| format!("<rdf:li>Species|{species}</rdf:li>"))
+        .collect();
+    format!(
+        r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:tiff="http://ns.adobe.com/tiff/1.0/"
+    xmlns:exifEX="http://cipa.jp/exif/1.0/"
+    xmlns:aux="http://ns.adobe.com/exif/1.0/aux/"
+    xmlns:lr="http://ns.adobe.com/lightroom/1.0/"
+    {camera}>
+   <lr:hierarchicalSubject><rdf:Bag>{subjects}</rdf:Bag></lr:hierarchicalSubject>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#
+    )
+}
+
+#[test]
+fn camera_columns_follow_every_tag_row() {
+    let dir = TempDir::new("camera_info");
+    let media_dir = dir.path().join("DEP01");
+    fs::create_dir_all(&media_dir).unwrap();
+    for (file_name, camera, species) in [
+        (
+            "IMG_0001.JPG.xmp",
+            r#"tiff:Make="Browning" tiff:Model="BTC-8E" exifEX:BodySerialNumber="B1234""#,
+            &["Serval", "Leopard"][..],
+        ),
+        (
+            "IMG_0002.JPG.xmp",
+            r#"tiff:Make="Reconyx" tiff:Model="HP2X" aux:SerialNumber=" H5678 ""#,
+            &["Serval"][..],
+        ),
+        ("IMG_0003.JPG.xmp", "", &["Blank"][..]),
+    ] {
+        fs::write(media_dir.join(file_name), camera_sidecar(camera, species)).unwrap();
+    }
+
+    let with_camera = dir.path().join("with_camera");
+    observe(
+        &media_dir,
+        &with_camera,
+        ObserveSettings {
+            camera_info: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let tags = find_output(&with_camera, "tags_");
+    // One row per species, the camera repeated on each
+    assert_eq!(
+        csv_column(&tags, "species"),
+        ["Serval", "Leopard", "Serval", "Blank"]
+    );
+    assert_eq!(
+        csv_column(&tags, "camera_make"),
+        ["Browning", "Browning", "Reconyx", ""]
+    );
+    assert_eq!(
+        csv_column(&tags, "camera_model"),
+        ["BTC-8E", "BTC-8E", "HP2X", ""]
+    );
+    assert_eq!(
+        csv_column(&tags, "camera_serial"),
+        ["B1234", "B1234", "H5678", ""]
+    );
+
+    let plain = dir.path().join("plain");
+    observe(&media_dir, &plain, ObserveSettings::default()).unwrap();
+    let header = fs::read_to_string(find_output(&plain, "tags_")).unwrap();
+    assert!(!header.lines().next().unwrap().contains("camera_"));
+    assert_eq!(
+        fs::read_to_string(find_output(&with_camera, "species_stats_")).unwrap(),
+        fs::read_to_string(find_output(&plain, "species_stats_")).unwrap()
+    );
+}
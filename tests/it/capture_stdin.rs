@@ -0,0 +1,78 @@
+// capture reads its answers from piped stdin, and stops when the input runs out before them
+use crate::common::{Project, find_output, observe};
+use serval::tags::{ObserveSettings, init_xmp, update_datetime, update_tags};
+use serval::utils::{ColumnMap, XmpUpdateType};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+fn tagged_project() -> Project {
+    let project = Project::create();
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let species_csv =
+        project.write_update_csv("species_update.csv", "species,xmp_update", |record| {
+            format!(",{}", record.species)
+        });
+    update_tags(
+        species_csv,
+        XmpUpdateType::Species,
+        None,
+        false,
+        ColumnMap::default(),
+    )
+    .unwrap();
+    let datetime_csv =
+        project.write_update_csv("datetime_update.csv", "xmp_update_datetime", |record| {
+            record.datetime.to_string()
+        });
+    update_datetime(datetime_csv, None, false, ColumnMap::default()).unwrap();
+    project
+}
+
+fn capture_piped(project: &Project, tags: &Path, output_dir: &Path, answers: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_serval"))
+        .arg("capture")
+        .arg(tags)
+        .arg("--output")
+        .arg(output_dir)
+        .current_dir(project.dir.path())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(answers.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn piped_answers_drive_capture_until_they_run_out() {
+    let project = tagged_project();
+    let observed = project.output_dir("observe");
+    observe(&project.root(), &observed, ObserveSettings::default()).unwrap();
+    let tags = find_output(&observed, "tags_");
+
+    // Window, comparison, target and deployment level
+    let answers = format!("30\n1\n1\n{}\n", project.deploy_path_index());
+    let output_dir = project.output_dir("capture");
+    let output = capture_piped(&project, &tags, &output_dir, &answers);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    find_output(&output_dir, "temporal-independence_");
+
+    // Without the deployment level the run fails instead of taking the default
+    let output_dir = project.output_dir("capture_cut_short");
+    let output = capture_piped(&project, &tags, &output_dir, "30\n1\n1\n");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("No answer to"), "{stderr}");
+    assert!(stderr.contains("deployment"), "{stderr}");
+}
@@ -0,0 +1,124 @@
+// Colliding output names are suffixed in the order of the source paths, whatever the run order
+use crate::common::{TempDir, csv_column, list_files};
+use serval::tags::{ExtractOptions, extract_resources};
+use serval::utils::{COLLISION_REPORT_FILE, ExtractFilterType, ResourceType, resources_flatten};
+use std::fs;
+use std::path::Path;
+
+// Every output file with its content
+fn layout(dir: &Path) -> Vec<(String, Vec<u8>)> {
+    list_files(dir)
+        .into_iter()
+        .filter(|file| file != "manifest.csv")
+        .map(|file| {
+            let content = fs::read(dir.join(&file)).unwrap();
+            (file, content)
+        })
+        .collect()
+}
+
+fn extract(tags_csv: &Path, output_dir: &Path) {
+    extract_resources(
+        "Serval".to_string(),
+        ExtractFilterType::Species,
+        tags_csv.to_path_buf(),
+        output_dir.to_path_buf(),
+        ExtractOptions {
+            keep_level: Some(0),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn extraction_layout_is_the_same_across_runs() {
+    let dir = TempDir::new("collisions_extract");
+    let mut paths = Vec::new();
+    for deployment in ["DEP01", "DEP02", "DEP03"] {
+        let deploy_dir = dir.path().join("project").join(deployment);
+        fs::create_dir_all(&deploy_dir).unwrap();
+        let media = deploy_dir.join("IMG_0001.JPG");
+        fs::write(&media, deployment).unwrap();
+        paths.push(media.to_string_lossy().into_owned());
+    }
+    // Rows out of path order, keeping only the file name makes all three collide
+    let rows = |order: [usize; 3]| {
+        let mut csv = "path,species\n".to_string();
+        for i in order {
+            csv.push_str(&format!("{},Serval\n", paths[i]));
+        }
+        csv
+    };
+    let tags_csv = dir.path().join("tags.csv");
+    fs::write(&tags_csv, rows([2, 0, 1])).unwrap();
+    extract(&tags_csv, &dir.path().join("first"));
+    extract(&tags_csv, &dir.path().join("second"));
+    fs::write(&tags_csv, rows([1, 2, 0])).unwrap();
+    extract(&tags_csv, &dir.path().join("reordered"));
+
+    let first = layout(&dir.path().join("first"));
+    assert_eq!(
+        first,
+        [
+            ("IMG_0001.JPG".to_string(), b"DEP01".to_vec()),
+            ("IMG_0001_1.JPG".to_string(), b"DEP02".to_vec()),
+            ("IMG_0001_2.JPG".to_string(), b"DEP03".to_vec()),
+        ]
+    );
+    assert_eq!(layout(&dir.path().join("second")), first);
+    assert_eq!(layout(&dir.path().join("reordered")), first);
+
+    let manifest = dir.path().join("first/manifest.csv");
+    assert_eq!(
+        csv_column(&manifest, "renamed_on_collision"),
+        ["true", "false", "true"]
+    );
+    let outputs = csv_column(&manifest, "output_path");
+    assert!(outputs[0].ends_with("IMG_0001_2.JPG"), "{outputs:?}");
+}
+
+#[test]
+fn flattened_names_that_collide_are_suffixed_and_reported() {
+    let dir = TempDir::new("collisions_flatten");
+    let deploy_dir = dir.path().join("DEP01_coll");
+    // Both flatten to a-b-c.JPG
+    for (subdir, name) in [("a", "b-c.JPG"), ("a-b", "c.JPG")] {
+        fs::create_dir_all(deploy_dir.join(subdir)).unwrap();
+        fs::write(deploy_dir.join(subdir).join(name), subdir).unwrap();
+    }
+    let output = dir.path().join("flat");
+    let summary = resources_flatten(
+        deploy_dir.clone(),
+        output.clone(),
+        ResourceType::Image,
+        false,
+        false,
+        false,
+        false,
+        false,
+        None,
+    )
+    .unwrap();
+    assert_eq!(summary.copied, 2);
+    assert_eq!(
+        layout(&output),
+        [
+            ("DEP01_coll/a-b-c.JPG".to_string(), b"a-b".to_vec()),
+            ("DEP01_coll/a-b-c_1.JPG".to_string(), b"a".to_vec()),
+            (
+                COLLISION_REPORT_FILE.to_string(),
+                fs::read(output.join(COLLISION_REPORT_FILE)).unwrap()
+            ),
+        ]
+    );
+    let report = output.join(COLLISION_REPORT_FILE);
+    assert_eq!(
+        csv_column(&report, "path"),
+        [deploy_dir.join("a/b-c.JPG").to_string_lossy()]
+    );
+    assert_eq!(
+        csv_column(&report, "output_path"),
+        [output.join("DEP01_coll/a-b-c_1.JPG").to_string_lossy()]
+    );
+}
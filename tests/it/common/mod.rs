@@ -0,0 +1,364 @@
+// Synthetic camera trap project shared by the integration tests
+#![allow(dead_code)]
+
+use image::{ImageFormat, RgbImage};
+use serval::tags::{
+    CaptureOptions, CaptureSettings, ObserveSettings, get_classifications,
+    get_temporal_independence,
+};
+use serval::utils::{OnConflict, ResourceType};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Smallest MP4 the XMP toolkit opens: ftyp and a moov with only the movie header
+fn mp4_fixture() -> Vec<u8> {
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(payload);
+        data
+    }
+    let mut mvhd = vec![0u8; 100];
+    mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes()); // timescale
+    mvhd[20..24].copy_from_slice(&0x0001_0000u32.to_be_bytes()); // rate 1.0
+    mvhd[24..26].copy_from_slice(&0x0100u16.to_be_bytes()); // volume 1.0
+    // Identity matrix
+    for (offset, value) in [(36, 0x0001_0000u32), (52, 0x0001_0000), (68, 0x4000_0000)] {
+        mvhd[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+    }
+    mvhd[96..100].copy_from_slice(&1u32.to_be_bytes()); // next track ID
+    let mut data = mp4_box(b"ftyp", b"isom\0\0\x02\0isommp41");
+    data.extend(mp4_box(b"moov", &mp4_box(b"mvhd", &mvhd)));
+    data.extend(mp4_box(b"mdat", &[]));
+    data
+}
+
+/// One media file of the project, with the tag and datetime written to its sidecar
+pub struct Record {
+    pub deployment: &'static str,
+    pub file_name: &'static str,
+    pub species: &'static str,
+    pub datetime: &'static str,
+}
+
+/// Two deployments of one collection, times picked so that with a 30 minute window
+/// DEP01 has 2 independent Serval and 1 Leopard record, DEP02 1 Serval and 1 Blank
+pub const RECORDS: &[Record] = &[
+    Record {
+        deployment: "DEP01",
+        file_name: "IMG_0001.JPG",
+        species: "Serval",
+        datetime: "2024-03-01 10:00:00",
+    },
+    Record {
+        deployment: "DEP01",
+        file_name: "IMG_0002.JPG",
+        species: "Serval",
+        datetime: "2024-03-01 10:05:00",
+    },
+    Record {
+        deployment: "DEP01",
+        file_name: "IMG_0003.JPG",
+        species: "Leopard",
+        datetime: "2024-03-01 10:10:00",
+    },
+    Record {
+        deployment: "DEP01",
+        file_name: "VID_0004.MP4",
+        species: "Serval",
+        datetime: "2024-03-01 11:00:00",
+    },
+    Record {
+        deployment: "DEP02",
+        file_name: "IMG_0001.JPG",
+        species: "Serval",
+        datetime: "2024-03-02 09:00:00",
+    },
+    Record {
+        deployment: "DEP02",
+        file_name: "IMG_0002.JPG",
+        species: "Blank",
+        datetime: "2024-03-02 09:30:00",
+    },
+];
+
+pub const COLLECTION: &str = "Collection";
+
+/// Temporary directory removed when dropped
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "serval-test-{}-{}-{name}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+pub struct Project {
+    pub dir: TempDir,
+}
+
+impl Project {
+    /// Media of RECORDS under <tmp>/project/Collection/<deployment>/, plus a deployments.csv
+    pub fn create() -> Self {
+        let dir = TempDir::new("project");
+        let project = Self { dir };
+        for record in RECORDS {
+            let media = project.media_path(record);
+            fs::create_dir_all(media.parent().unwrap()).unwrap();
+            if record.file_name.ends_with(".MP4") {
+                fs::write(&media, mp4_fixture()).unwrap();
+            } else {
+                RgbImage::from_pixel(16, 16, image::Rgb([90, 120, 60]))
+                    .save_with_format(&media, ImageFormat::Jpeg)
+                    .unwrap();
+            }
+        }
+        fs::write(
+            project.deploy_table(),
+            "deploymentID,latitude,longitude\nDEP01,29.5,91.1\nDEP02,29.6,91.2\n",
+        )
+        .unwrap();
+        project
+    }
+
+    pub fn root(&self) -> PathBuf {
+        self.dir.path().join("project")
+    }
+
+    pub fn media_path(&self, record: &Record) -> PathBuf {
+        self.root()
+            .join(COLLECTION)
+            .join(record.deployment)
+            .join(record.file_name)
+    }
+
+    pub fn sidecar_path(&self, record: &Record) -> PathBuf {
+        let mut sidecar = self.media_path(record).into_os_string();
+        sidecar.push(".xmp");
+        PathBuf::from(sidecar)
+    }
+
+    pub fn deploy_table(&self) -> PathBuf {
+        self.dir.path().join("deployments.csv")
+    }
+
+    pub fn output_dir(&self, name: &str) -> PathBuf {
+        self.dir.path().join("output").join(name)
+    }
+
+    /// Index of the deployment directory in the `/`-split path of a media file
+    pub fn deploy_path_index(&self) -> i32 {
+        deploy_path_index_of(&self.media_path(&RECORDS[0]))
+    }
+
+    /// CSV for the xmp update commands, one row per record
+    pub fn write_update_csv(
+        &self,
+        name: &str,
+        header: &str,
+        value: impl Fn(&Record) -> String,
+    ) -> PathBuf {
+        let mut csv = format!("path,{header}\n");
+        for record in RECORDS {
+            csv.push_str(&format!(
+                "{},{}\n",
+                self.sidecar_path(record).display(),
+                value(record)
+            ));
+        }
+        let path = self.dir.path().join(name);
+        fs::write(&path, csv).unwrap();
+        path
+    }
+}
+
+/// Index of the parent directory of `media` in its `/`-split path, the deployment of
+/// media lying directly in it
+pub fn deploy_path_index_of(media: &Path) -> i32 {
+    let normalized = media.to_string_lossy().replace('\\', "/");
+    normalized.split('/').count() as i32 - 2
+}
+
+/// Sidecar with a DateTimeOriginal (none when empty) and these hierarchical tags,
+/// e.g. `Species|Serval`
+pub fn sidecar(datetime: &str, tags: &[&str]) -> String {
+    let datetime = if datetime.is_empty() {
+        String::new()
+    } else {
+        format!(r#" exif:DateTimeOriginal="{datetime}""#)
+    };
+    let subjects: String = tags
+        .iter()
+        .map(|tag| format!("<rdf:li>{tag}</rdf:li>"))
+        .collect();
+    format!(
+        r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:exif="http://ns.adobe.com/exif/1.0/"
+    xmlns:lr="http://ns.adobe.com/lightroom/1.0/"{datetime}>
+   <lr:hierarchicalSubject><rdf:Bag>{subjects}</rdf:Bag></lr:hierarchicalSubject>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#
+    )
+}
+
+/// Observe the sidecars under `media_dir`, failing rather than versioning on existing outputs
+pub fn observe(
+    media_dir: &Path,
+    output_dir: &Path,
+    settings: ObserveSettings,
+) -> anyhow::Result<()> {
+    observe_resources(media_dir, output_dir, ResourceType::Xmp, settings)
+}
+
+/// Observe of another resource type, e.g. images read without sidecars
+pub fn observe_resources(
+    media_dir: &Path,
+    output_dir: &Path,
+    resource_type: ResourceType,
+    settings: ObserveSettings,
+) -> anyhow::Result<()> {
+    get_classifications(
+        media_dir.to_path_buf(),
+        output_dir.to_path_buf(),
+        resource_type,
+        ObserveSettings {
+            on_conflict: OnConflict::Fail,
+            ..settings
+        },
+    )
+}
+
+/// Capture with events written and these tags excluded, the other options off
+pub fn capture(
+    tags: &Path,
+    output_dir: &Path,
+    exclude_tags: Vec<String>,
+    settings: CaptureSettings,
+) -> anyhow::Result<()> {
+    get_temporal_independence(
+        tags.to_path_buf(),
+        output_dir.to_path_buf(),
+        CaptureOptions {
+            event: true,
+            exclude_tags,
+            on_conflict: OnConflict::Fail,
+            settings: Some(settings),
+            ..Default::default()
+        },
+    )
+}
+
+/// The single file in `dir` whose name starts with `prefix`, not counting `.schema.json` files
+pub fn find_output(dir: &Path, prefix: &str) -> PathBuf {
+    let matches: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.file_name().is_some_and(|name| {
+                let name = name.to_string_lossy();
+                name.starts_with(prefix) && !name.ends_with(".schema.json")
+            })
+        })
+        .collect();
+    assert_eq!(
+        matches.len(),
+        1,
+        "{prefix}* in {}: {matches:?}",
+        dir.display()
+    );
+    matches.into_iter().next().unwrap()
+}
+
+/// Rows of a CSV written by serval (BOM, comma separated, no quoted commas)
+pub fn read_csv(path: &Path) -> Vec<Vec<String>> {
+    fs::read_to_string(path)
+        .unwrap()
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(|line| {
+            line.split(',')
+                .map(|value| value.trim_matches('"').to_string())
+                .collect()
+        })
+        .collect()
+}
+
+/// Values of `column` in a CSV with a header row
+pub fn csv_column(path: &Path, column: &str) -> Vec<String> {
+    let rows = read_csv(path);
+    let index = rows[0]
+        .iter()
+        .position(|name| name == column)
+        .unwrap_or_else(|| panic!("no {column} column in {}", path.display()));
+    rows[1..].iter().map(|row| row[index].clone()).collect()
+}
+
+/// Files below `dir`, relative and with `/` separators, sorted
+pub fn list_files(dir: &Path) -> Vec<String> {
+    fn walk(dir: &Path, root: &Path, files: &mut Vec<String>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                walk(&path, root, files);
+            } else {
+                files.push(
+                    path.strip_prefix(root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/"),
+                );
+            }
+        }
+    }
+    let mut files = Vec::new();
+    walk(dir, dir, &mut files);
+    files.sort();
+    files
+}
+
+/// Run the calling test again in a process of its own, for tests that set process-wide
+/// configuration (CSV dialect, ignores, indeterminate labels, audit log, thread pools) which
+/// the other tests of this binary must not see. True in that process, where the test body
+/// goes on.
+pub fn in_own_process(test: &str) -> bool {
+    const OWN_PROCESS_ENV: &str = "SERVAL_TEST_OWN_PROCESS";
+    if std::env::var_os(OWN_PROCESS_ENV).is_some() {
+        return true;
+    }
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args([test, "--exact", "--test-threads=1"])
+        .env(OWN_PROCESS_ENV, "1")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success() && stdout.contains("1 passed"),
+        "{test} failed in its own process:\n{stdout}{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    false
+}
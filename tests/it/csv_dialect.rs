@@ -0,0 +1,31 @@
+// The excel-eu dialect writes semicolons, decimal commas and day.month.year datetimes
+use crate::common::in_own_process;
+use chrono::NaiveDateTime;
+use polars::prelude::*;
+use serval::utils::{CsvDialect, configure_csv_dialect, csv_datetime_format, csv_writer};
+
+#[test]
+fn excel_eu_dialect_layout() {
+    if !in_own_process("csv_dialect::excel_eu_dialect_layout") {
+        return;
+    }
+    configure_csv_dialect(CsvDialect::ExcelEu);
+    let time = NaiveDateTime::parse_from_str("2024-03-01 06:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let mut df = df!(
+        "species" => ["Serval;Civet"],
+        "count" => [1.5],
+        "time" => [time],
+    )
+    .unwrap();
+    let mut buffer = Vec::new();
+    csv_writer(&mut buffer)
+        .with_datetime_format(csv_datetime_format("%Y-%m-%d %H:%M:%S"))
+        .finish(&mut df)
+        .unwrap();
+    let csv = String::from_utf8(buffer).unwrap();
+    assert_eq!(
+        csv.trim_start_matches('\u{feff}'),
+        "species;count;time\n\"Serval;Civet\";1,5;01.03.2024 06:30:00\n"
+    );
+    assert_eq!(CsvDialect::current().multi_value_separator(), ";");
+}
@@ -0,0 +1,78 @@
+// --derive-dateparts adds date, year, month and hour columns to observe and capture outputs
+use crate::common::{TempDir, csv_column, deploy_path_index_of, find_output, observe, sidecar};
+use serval::tags::{CaptureOptions, CaptureSettings, ObserveSettings, get_temporal_independence};
+use serval::utils::{OnConflict, TagType};
+use std::fs;
+
+#[test]
+fn dateparts_follow_the_datetime() {
+    let dir = TempDir::new("dateparts");
+    let media_dir = dir.path().join("project/DEP01");
+    fs::create_dir_all(&media_dir).unwrap();
+    for (file_name, datetime, species) in [
+        ("IMG_0001.JPG.xmp", "2024-03-01T00:15:00", "Species|Serval"),
+        ("IMG_0002.JPG.xmp", "2024-12-31T23:59:59", "Species|Civet"),
+        ("IMG_0003.JPG.xmp", "", "Species|Serval"),
+    ] {
+        fs::write(media_dir.join(file_name), sidecar(datetime, &[species])).unwrap();
+    }
+
+    let observe_dir = dir.path().join("observe");
+    observe(
+        &media_dir,
+        &observe_dir,
+        ObserveSettings {
+            derive_dateparts: true,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let tags = find_output(&observe_dir, "tags_");
+    assert_eq!(csv_column(&tags, "date"), ["2024-03-01", "2024-12-31", ""]);
+    assert_eq!(csv_column(&tags, "year"), ["2024", "2024", ""]);
+    assert_eq!(csv_column(&tags, "month"), ["3", "12", ""]);
+    assert_eq!(csv_column(&tags, "hour"), ["0", "23", ""]);
+
+    // Capture takes records with a datetime only
+    let dated = dir.path().join("dated.csv");
+    let content = fs::read_to_string(&tags).unwrap();
+    let lines: Vec<&str> = content
+        .lines()
+        .filter(|line| !line.contains("IMG_0003"))
+        .collect();
+    fs::write(&dated, lines.join("\n")).unwrap();
+    let capture_dir = dir.path().join("capture");
+    let deploy_path_index = deploy_path_index_of(&media_dir.join("IMG_0001.JPG.xmp"));
+    get_temporal_independence(
+        dated,
+        capture_dir.clone(),
+        CaptureOptions {
+            event: true,
+            derive_dateparts: true,
+            on_conflict: OnConflict::Fail,
+            settings: Some(CaptureSettings {
+                min_delta_time: 30,
+                compare_to_last_record: false,
+                target: TagType::Species,
+                deploy_path_index: Some(deploy_path_index),
+            }),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    for output in [
+        "temporal-independence_species_30m_LIR.csv",
+        "events_species_30m_LIR.csv",
+    ] {
+        let path = capture_dir.join(output);
+        let mut parts: Vec<String> = csv_column(&path, "date")
+            .into_iter()
+            .zip(csv_column(&path, "hour"))
+            .map(|(date, hour)| format!("{date} {hour}"))
+            .collect();
+        parts.sort();
+        assert_eq!(parts, ["2024-03-01 0", "2024-12-31 23"], "{output}");
+        let schema = fs::read_to_string(path.with_extension("schema.json")).unwrap();
+        assert!(schema.contains("\"month\""), "{output}");
+    }
+}
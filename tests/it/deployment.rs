@@ -0,0 +1,117 @@
+// observe --deploy-level/--deploy-table write the deployment column that capture then reads
+use crate::common::{Project, RECORDS, csv_column, find_output, observe};
+use serval::tags::{
+    CaptureOptions, CaptureSettings, ObserveSettings, get_temporal_independence, init_xmp,
+    update_datetime, update_tags,
+};
+use serval::utils::{ColumnMap, DeploymentLookup, OnConflict, TagType, XmpUpdateType};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn observe_deployments(project: &Project, lookup: DeploymentLookup, name: &str) -> PathBuf {
+    let output_dir = project.output_dir(name);
+    observe(
+        &project.root(),
+        &output_dir,
+        ObserveSettings {
+            deployment_lookup: Some(lookup),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    output_dir
+}
+
+fn sorted_column(csv: &Path, column: &str) -> Vec<String> {
+    let mut values = csv_column(csv, column);
+    values.sort();
+    values
+}
+
+#[test]
+fn observe_writes_deployment_column_for_capture() {
+    let project = Project::create();
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let species_csv =
+        project.write_update_csv("species_update.csv", "species,xmp_update", |record| {
+            format!(",{}", record.species)
+        });
+    update_tags(
+        species_csv,
+        XmpUpdateType::Species,
+        None,
+        true,
+        ColumnMap::default(),
+    )
+    .unwrap();
+    let datetime_csv =
+        project.write_update_csv("datetime_update.csv", "xmp_update_datetime", |record| {
+            record.datetime.to_string()
+        });
+    update_datetime(datetime_csv, None, true, ColumnMap::default()).unwrap();
+    // A file above the deployment directories has no deployment
+    let stray = project
+        .sidecar_path(&RECORDS[0])
+        .parent()
+        .unwrap()
+        .with_file_name("IMG_9999.JPG.xmp");
+    fs::copy(project.sidecar_path(&RECORDS[0]), &stray).unwrap();
+
+    let observe_dir = observe_deployments(
+        &project,
+        DeploymentLookup::Level(project.deploy_path_index()),
+        "observe_level",
+    );
+    let tags_csv = find_output(&observe_dir, "tags_");
+    assert_eq!(
+        sorted_column(&tags_csv, "deployment"),
+        ["", "DEP01", "DEP01", "DEP01", "DEP01", "DEP02", "DEP02"]
+    );
+    let data_quality = crate::common::read_csv(&find_output(&observe_dir, "data_quality_"));
+    assert_eq!(
+        data_quality[1..],
+        [
+            vec!["DEP01", "4", "0", "0"],
+            vec!["DEP02", "2", "0", "0"],
+            vec!["", "1", "0", "0"],
+        ]
+    );
+
+    // The deploymentIDs of the deploy table name the same directories
+    let table_dir = observe_deployments(
+        &project,
+        DeploymentLookup::from_deploy_table(&project.deploy_table()).unwrap(),
+        "observe_table",
+    );
+    assert_eq!(
+        sorted_column(&find_output(&table_dir, "tags_"), "deployment"),
+        sorted_column(&tags_csv, "deployment")
+    );
+
+    // Capture reads the column, without a path level, and leaves the stray file out
+    let capture_dir = project.output_dir("capture");
+    get_temporal_independence(
+        tags_csv,
+        capture_dir.clone(),
+        CaptureOptions {
+            exclude_tags: vec!["Blank".to_string()],
+            on_conflict: OnConflict::Fail,
+            settings: Some(CaptureSettings {
+                min_delta_time: 30,
+                compare_to_last_record: false,
+                target: TagType::Species,
+                deploy_path_index: None,
+            }),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let independent = capture_dir.join("temporal-independence_species_30m_LIR.csv");
+    assert_eq!(
+        sorted_column(&independent, "deployment"),
+        ["DEP01", "DEP01", "DEP01", "DEP02"]
+    );
+    let params = fs::read_to_string(capture_dir.join("params_species_30m_LIR.toml")).unwrap();
+    assert!(params.contains("deployment_column"), "{params}");
+    assert!(!params.contains("deployment_path_index"), "{params}");
+}
@@ -0,0 +1,190 @@
+// digiKam tag edits read from digikam4.db and applied back with xmp update, deletions included
+use crate::common::{Project, RECORDS, csv_column, find_output, observe_resources, read_csv};
+use rusqlite::Connection;
+use serval::digikam::import_digikam;
+use serval::tags::{ObserveSettings, init_xmp, update_tags};
+use serval::utils::{ColumnMap, ResourceType, XmpUpdateType};
+use std::fs;
+
+#[test]
+fn database_edits_become_updates() {
+    let project = Project::create();
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let species_csv =
+        project.write_update_csv("species_update.csv", "species,xmp_update", |record| {
+            format!(",{}", record.species)
+        });
+    update_tags(
+        species_csv,
+        XmpUpdateType::Species,
+        None,
+        true,
+        ColumnMap::default(),
+    )
+    .unwrap();
+    let tags_csv =
+        project.write_update_csv("tags.csv", "species", |record| record.species.to_string());
+
+    // DEP01/IMG_0001 retagged, the Leopard tag of DEP01/IMG_0003 removed, the rest unchanged
+    let db = project.dir.path().join("digikam4.db");
+    let connection = Connection::open(&db).unwrap();
+    connection
+        .execute_batch(
+            "CREATE TABLE Albums (id INTEGER PRIMARY KEY, albumRoot INTEGER, relativePath TEXT);
+             CREATE TABLE Images (id INTEGER PRIMARY KEY, album INTEGER, name TEXT, status INTEGER);
+             CREATE TABLE Tags (id INTEGER PRIMARY KEY, pid INTEGER, name TEXT);
+             CREATE TABLE ImageTags (imageid INTEGER, tagid INTEGER);
+             INSERT INTO Albums VALUES (1, 1, '/Collection/DEP01'), (2, 1, '/Collection/DEP02');
+             INSERT INTO Tags VALUES (1, 0, 'Species'), (2, 1, 'Serval'), (3, 1, 'Leopard cat'),
+                 (4, 1, 'Blank'), (5, 0, 'Favourites');
+             INSERT INTO Images VALUES (1, 1, 'IMG_0001.JPG', 1), (2, 1, 'IMG_0002.JPG', 1),
+                 (3, 1, 'IMG_0003.JPG', 1), (4, 1, 'VID_0004.MP4', 1),
+                 (5, 2, 'IMG_0001.JPG', 1), (6, 2, 'IMG_0002.JPG', 1),
+                 (7, 2, 'IMG_0005.JPG', 1), (8, 2, 'IMG_0006.JPG', 3);
+             INSERT INTO ImageTags VALUES (1, 3), (1, 5), (2, 2), (3, 5), (4, 2), (5, 2), (6, 4),
+                 (7, 2), (8, 2);",
+        )
+        .unwrap();
+    drop(connection);
+
+    let output_dir = project.output_dir("digikam");
+    import_digikam(
+        db,
+        project.root(),
+        tags_csv,
+        output_dir.clone(),
+        XmpUpdateType::Species,
+        "path".to_string(),
+        "tags".to_string(),
+        ColumnMap::default(),
+    )
+    .unwrap();
+
+    let sidecar = |index: usize| project.sidecar_path(&RECORDS[index]).display().to_string();
+    let updates = output_dir.join("digikam_updates.csv");
+    assert_eq!(
+        read_csv(&updates),
+        [
+            vec!["path".to_string(), "species".into(), "xmp_update".into()],
+            vec![sidecar(0), "Serval".into(), "Leopard cat".into()],
+            vec![sidecar(2), "Leopard".into(), "DELETE_TAG".into()],
+        ]
+    );
+    // The deleted IMG_0006 (status 3) isn't listed
+    let unmatched = project
+        .root()
+        .join("Collection")
+        .join("DEP02")
+        .join("IMG_0005.JPG");
+    assert_eq!(
+        read_csv(&output_dir.join("digikam_unmatched.csv")),
+        [
+            vec!["path".to_string(), "reason".into()],
+            vec![
+                unmatched.display().to_string(),
+                "not in the tags CSV".into()
+            ],
+        ]
+    );
+
+    update_tags(
+        updates,
+        XmpUpdateType::Species,
+        None,
+        true,
+        ColumnMap::default(),
+    )
+    .unwrap();
+    let retagged = fs::read_to_string(sidecar(0)).unwrap();
+    assert!(retagged.contains("Species|Leopard cat"), "{retagged}");
+    assert!(!retagged.contains("Species|Serval"), "{retagged}");
+    let deleted = fs::read_to_string(sidecar(2)).unwrap();
+    assert!(!deleted.contains("Leopard"), "{deleted}");
+}
+
+// A file deleted in digiKam sits in .dtrash at the album root, its sidecar left behind
+#[test]
+fn trashed_media_is_not_an_orphan() {
+    let project = Project::create();
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let media = project.media_path(&RECORDS[2]);
+    let trash = project.root().join(".dtrash");
+    fs::create_dir_all(trash.join("files")).unwrap();
+    fs::create_dir_all(trash.join("info")).unwrap();
+    fs::rename(&media, trash.join("files").join("IMG_0003.JPG")).unwrap();
+    fs::write(
+        trash.join("info").join("IMG_0003.dtrashinfo"),
+        serde_json::json!({
+            "path": media.display().to_string(),
+            "deletiontimestamp": "2024-03-05T10:00:00",
+            "imageid": "3",
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let observe = |resource_type, pair_media, include_trash, name| {
+        let output_dir = project.output_dir(name);
+        observe_resources(
+            &project.root(),
+            &output_dir,
+            resource_type,
+            ObserveSettings {
+                pair_media,
+                include_trash,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        output_dir
+    };
+
+    let output_dir = observe(ResourceType::Xmp, true, false, "pair_media");
+    let tags_csv = find_output(&output_dir, "tags_");
+    let status: Vec<(String, String, String)> = csv_column(&tags_csv, "media_path")
+        .into_iter()
+        .zip(csv_column(&tags_csv, "media_exists"))
+        .zip(csv_column(&tags_csv, "media_trashed"))
+        .filter(|((_, exists), _)| exists == "false")
+        .map(|((path, exists), trashed)| (path, exists, trashed))
+        .collect();
+    assert_eq!(
+        status,
+        [(
+            media.display().to_string(),
+            "false".to_string(),
+            "true".to_string()
+        )]
+    );
+
+    // Trashed media counted with the deployment it was deleted from
+    let output_dir = observe(ResourceType::Image, false, true, "include_trash");
+    let tags_csv = find_output(&output_dir, "tags_");
+    let trashed: Vec<String> = csv_column(&tags_csv, "path")
+        .into_iter()
+        .zip(csv_column(&tags_csv, "trashed"))
+        .filter(|(_, trashed)| trashed == "true")
+        .map(|(path, _)| path)
+        .collect();
+    assert_eq!(
+        trashed,
+        [trash
+            .join("files")
+            .join("IMG_0003.JPG")
+            .display()
+            .to_string()]
+    );
+    assert_eq!(
+        read_csv(&find_output(&output_dir, "data_quality_")),
+        [
+            vec![
+                "deployment",
+                "tagged_with_datetime",
+                "tagged_without_datetime",
+                "datetime_without_tags",
+                "trashed"
+            ],
+            vec!["DEP01", "0", "0", "0", "1"],
+            vec!["DEP02", "0", "0", "0", "0"],
+        ]
+    );
+}
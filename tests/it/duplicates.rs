@@ -0,0 +1,83 @@
+// observe --detect-duplicate-deployments reports directories holding copies of the same files
+use crate::common::{Project, RECORDS, find_output, observe};
+use serval::duplicates::{DuplicateCheck, DuplicateKey};
+use serval::tags::{ObserveSettings, init_xmp, update_datetime};
+use serval::utils::ColumnMap;
+use std::fs;
+
+#[test]
+fn copied_deployment_is_reported_with_its_overlap() {
+    let project = Project::create();
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let datetime_csv =
+        project.write_update_csv("datetime_update.csv", "xmp_update_datetime", |record| {
+            record.datetime.to_string()
+        });
+    update_datetime(datetime_csv, None, true, ColumnMap::default()).unwrap();
+
+    // A backup copy of DEP01, and one of its files left out of it
+    let dep01 = project
+        .media_path(&RECORDS[0])
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let backup = project.root().join("backup_2023").join("DEP01");
+    fs::create_dir_all(&backup).unwrap();
+    for entry in fs::read_dir(&dep01).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        if !name.starts_with("VID_0004") {
+            fs::copy(&path, backup.join(name)).unwrap();
+        }
+    }
+
+    for (key, name) in [
+        (DuplicateKey::Name, "by_name"),
+        (DuplicateKey::Hash, "by_hash"),
+    ] {
+        let observe_dir = project.output_dir(name);
+        observe(
+            &project.root(),
+            &observe_dir,
+            ObserveSettings {
+                duplicate_check: Some(DuplicateCheck {
+                    key,
+                    threshold: 50.0,
+                }),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let report = crate::common::read_csv(&find_output(&observe_dir, "duplicate_deployments_"));
+        assert_eq!(
+            report,
+            [
+                vec![
+                    "cluster".to_string(),
+                    "directory_a".to_string(),
+                    "directory_b".to_string(),
+                    "files_a".to_string(),
+                    "files_b".to_string(),
+                    "shared_files".to_string(),
+                    "percent_of_a".to_string(),
+                    "percent_of_b".to_string(),
+                    "overlap_start".to_string(),
+                    "overlap_end".to_string(),
+                ],
+                vec![
+                    "1".to_string(),
+                    dep01.to_string_lossy().into_owned(),
+                    backup.to_string_lossy().into_owned(),
+                    "4".to_string(),
+                    "3".to_string(),
+                    "3".to_string(),
+                    "75.0".to_string(),
+                    "100.0".to_string(),
+                    "2024-03-01 10:00:00".to_string(),
+                    "2024-03-01 10:10:00".to_string(),
+                ],
+            ],
+            "{key:?}"
+        );
+    }
+}
@@ -0,0 +1,178 @@
+// observe --event-gap numbers the bursts of each deployment into an event_id column
+use crate::common::{
+    TempDir, csv_column, deploy_path_index_of, find_output, list_files, observe, sidecar,
+};
+use serval::propagate::propagate_tags;
+use serval::tags::{ExtractOptions, ObserveSettings, extract_resources};
+use serval::utils::{ColumnMap, DeploymentLookup, ExtractFilterType};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+// DEP01 bursts at 10:00-10:04 and 10:30, DEP02 starts at 10:05 right after the first one
+const FILES: &[(&str, &str, &str, &str)] = &[
+    ("DEP01", "IMG_0001.JPG", "2024-03-01T10:00:00", "Serval"),
+    ("DEP01", "IMG_0002.JPG", "2024-03-01T10:02:00", "Serval"),
+    ("DEP01", "IMG_0003.JPG", "2024-03-01T10:04:00", "Civet"),
+    ("DEP01", "IMG_0004.JPG", "2024-03-01T10:30:00", "Serval"),
+    ("DEP02", "IMG_0001.JPG", "2024-03-01T10:05:00", "Serval"),
+];
+
+// DEP01 has an untagged frame after a Serval and a burst tagged both Civet and Serval
+const PROPAGATED_FILES: &[(&str, &str, &str, &str)] = &[
+    ("DEP01", "IMG_0001.JPG", "2024-03-01T10:00:00", "Serval"),
+    ("DEP01", "IMG_0002.JPG", "2024-03-01T10:02:00", ""),
+    ("DEP01", "IMG_0003.JPG", "2024-03-01T10:30:00", "Civet"),
+    ("DEP01", "IMG_0004.JPG", "2024-03-01T10:33:00", "Serval"),
+    ("DEP01", "IMG_0005.JPG", "2024-03-01T10:34:00", ""),
+    ("DEP02", "IMG_0001.JPG", "2024-03-01T10:05:00", ""),
+];
+
+// Media and sidecars of `files` under `media_dir`, untagged where the species is empty
+fn write_files(media_dir: &Path, files: &[(&str, &str, &str, &str)]) {
+    for (deployment, file_name, datetime, species) in files {
+        let media = media_dir.join(deployment).join(file_name);
+        fs::create_dir_all(media.parent().unwrap()).unwrap();
+        fs::write(&media, format!("{deployment}/{file_name}")).unwrap();
+        let tag = format!("Species|{species}");
+        let tags: &[&str] = if species.is_empty() { &[] } else { &[&tag] };
+        fs::write(
+            media.with_file_name(format!("{file_name}.xmp")),
+            sidecar(datetime, tags),
+        )
+        .unwrap();
+    }
+}
+
+#[test]
+fn bursts_share_an_event_id() {
+    let dir = TempDir::new("event_gap");
+    let media_dir = dir.path().join("project");
+    write_files(&media_dir, FILES);
+
+    let grouped = dir.path().join("grouped");
+    observe(
+        &media_dir,
+        &grouped,
+        ObserveSettings {
+            event_gap: Some(300),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let tags = find_output(&grouped, "tags_");
+    let mut events: Vec<(String, String)> = csv_column(&tags, "path")
+        .into_iter()
+        .map(|path| {
+            Path::new(&path)
+                .strip_prefix(&media_dir)
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .zip(csv_column(&tags, "event_id"))
+        .collect();
+    events.sort();
+    assert_eq!(
+        events,
+        [
+            ("DEP01/IMG_0001.JPG.xmp".to_string(), "1".to_string()),
+            ("DEP01/IMG_0002.JPG.xmp".to_string(), "1".to_string()),
+            ("DEP01/IMG_0003.JPG.xmp".to_string(), "1".to_string()),
+            ("DEP01/IMG_0004.JPG.xmp".to_string(), "2".to_string()),
+            ("DEP02/IMG_0001.JPG.xmp".to_string(), "3".to_string()),
+        ]
+    );
+
+    // The event ids carry over to extract --whole-event, media copied along with the sidecars
+    let extract_dir = dir.path().join("extract");
+    extract_resources(
+        "DEP01/IMG_0002".to_string(),
+        ExtractFilterType::Path,
+        tags,
+        extract_dir.clone(),
+        ExtractOptions {
+            whole_event: true,
+            keep_level: Some(1),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let files: Vec<String> = list_files(&extract_dir)
+        .into_iter()
+        .filter(|file| file != "manifest.csv")
+        .collect();
+    assert_eq!(
+        files,
+        [
+            "DEP01/IMG_0001.JPG",
+            "DEP01/IMG_0001.JPG.xmp",
+            "DEP01/IMG_0002.JPG",
+            "DEP01/IMG_0002.JPG.xmp",
+            "DEP01/IMG_0003.JPG",
+            "DEP01/IMG_0003.JPG.xmp"
+        ]
+    );
+
+    let plain = dir.path().join("plain");
+    observe(&media_dir, &plain, ObserveSettings::default()).unwrap();
+    let header = fs::read_to_string(find_output(&plain, "tags_")).unwrap();
+    assert!(!header.lines().next().unwrap().contains("event_id"));
+}
+
+#[test]
+fn events_match_propagation_sequences() {
+    let dir = TempDir::new("event_gap_propagate");
+    let media_dir = dir.path().join("project");
+    write_files(&media_dir, PROPAGATED_FILES);
+    let deploy_path_index = deploy_path_index_of(&media_dir.join("DEP01").join("IMG_0001.JPG"));
+
+    let grouped = dir.path().join("grouped");
+    observe(
+        &media_dir,
+        &grouped,
+        ObserveSettings {
+            event_gap: Some(300),
+            deployment_lookup: Some(DeploymentLookup::Level(deploy_path_index)),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let tags = find_output(&grouped, "tags_");
+    let event_ids: HashMap<String, String> = csv_column(&tags, "path")
+        .into_iter()
+        .zip(csv_column(&tags, "event_id"))
+        .collect();
+
+    let propagated_dir = dir.path().join("propagated");
+    propagate_tags(
+        tags,
+        Duration::from_secs(300),
+        propagated_dir.clone(),
+        false,
+        ColumnMap::default(),
+    )
+    .unwrap();
+    let propagated = propagated_dir.join("tags_propagated.csv");
+    let propagated: Vec<String> = csv_column(&propagated, "path")
+        .into_iter()
+        .zip(csv_column(&propagated, "propagated"))
+        .filter(|(_, propagated)| propagated == "true")
+        .map(|(path, _)| path)
+        .collect();
+    assert_eq!(propagated.len(), 1, "{propagated:?}");
+    assert!(propagated[0].ends_with("IMG_0002.JPG.xmp"));
+    assert_eq!(event_ids[&propagated[0]], "1");
+
+    // Numbered the same way, the conflicting sequence is the second event
+    let conflicts = propagated_dir.join("propagation_conflicts.csv");
+    let sequences: Vec<(String, String)> = csv_column(&conflicts, "path")
+        .into_iter()
+        .zip(csv_column(&conflicts, "sequence"))
+        .collect();
+    assert_eq!(sequences.len(), 3, "{sequences:?}");
+    for (path, sequence) in sequences {
+        assert_eq!(event_ids[&path], sequence, "{path}");
+    }
+}
@@ -0,0 +1,166 @@
+// Extract whole events, from event ids or from the independent records standing for them
+use crate::common::{self, TempDir, list_files};
+use serval::tags::{CaptureSettings, ExtractOptions, extract_resources};
+use serval::utils::{ExtractFilterType, TagType};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// DEP01 Serval bursts at 10:00-10:40 and 11:30, a Civet between them and a DEP02 Serval
+const RECORDS: &[(&str, &str, &str, &str)] = &[
+    ("DEP01", "IMG_0001.JPG", "2024-03-01 10:00:00", "Serval"),
+    ("DEP01", "IMG_0002.JPG", "2024-03-01 10:05:00", "Serval"),
+    ("DEP01", "IMG_0003.JPG", "2024-03-01 10:10:00", "Civet"),
+    ("DEP01", "IMG_0004.JPG", "2024-03-01 10:20:00", "Serval"),
+    ("DEP01", "IMG_0005.JPG", "2024-03-01 10:40:00", "Serval"),
+    ("DEP01", "IMG_0006.JPG", "2024-03-01 11:30:00", "Serval"),
+    ("DEP02", "IMG_0001.JPG", "2024-03-01 10:00:00", "Serval"),
+];
+
+fn write_tags(root: &Path) -> (PathBuf, i32) {
+    let mut csv = "path,datetime,species\n".to_string();
+    for (deployment, file_name, datetime, species) in RECORDS {
+        let media = root.join("project").join(deployment).join(file_name);
+        fs::create_dir_all(media.parent().unwrap()).unwrap();
+        fs::write(&media, format!("{deployment}/{file_name}")).unwrap();
+        csv.push_str(&format!("{},{datetime},{species}\n", media.display()));
+    }
+    let tags_csv = root.join("tags.csv");
+    fs::write(&tags_csv, csv).unwrap();
+    let sample = root.join("project/DEP01/IMG_0001.JPG");
+    let deploy_path_index = sample.to_string_lossy().split('/').count() as i32 - 2;
+    (tags_csv, deploy_path_index)
+}
+
+fn capture(
+    tags_csv: &Path,
+    output_dir: &Path,
+    compare_to_last_record: bool,
+    deploy_path_index: i32,
+) {
+    common::capture(
+        tags_csv,
+        output_dir,
+        Vec::new(),
+        CaptureSettings {
+            min_delta_time: 30,
+            compare_to_last_record,
+            target: TagType::Species,
+            deploy_path_index: Some(deploy_path_index),
+        },
+    )
+    .unwrap();
+}
+
+fn extract(
+    csv_path: &Path,
+    filter: Option<(ExtractFilterType, &str)>,
+    whole_event: bool,
+    events_from: Option<PathBuf>,
+    output_dir: &Path,
+) -> Vec<String> {
+    let (filter_type, value) = filter.unwrap_or((ExtractFilterType::Custom, "events"));
+    extract_resources(
+        value.to_string(),
+        filter_type,
+        csv_path.to_path_buf(),
+        output_dir.to_path_buf(),
+        ExtractOptions {
+            whole_event,
+            events_from,
+            keep_level: Some(1),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    list_files(output_dir)
+        .into_iter()
+        .filter(|file| file != "manifest.csv")
+        .collect()
+}
+
+// Keep only the first DEP01 Serval record, as when picking one detection to review
+fn keep_first_record(independence_csv: &Path) {
+    let content = fs::read_to_string(independence_csv).unwrap();
+    let mut lines = content.lines();
+    let header = lines.next().unwrap();
+    let first = lines
+        .find(|line| line.contains("DEP01/IMG_0001.JPG"))
+        .unwrap();
+    fs::write(independence_csv, format!("{header}\n{first}\n")).unwrap();
+}
+
+#[test]
+fn independent_records_expand_to_their_events() {
+    let dir = TempDir::new("events_from");
+    let (tags_csv, deploy_path_index) = write_tags(dir.path());
+
+    // LIR: within 30 minutes of 10:00, the 10:40 record starts the next event
+    let lir_dir = dir.path().join("capture_lir");
+    capture(&tags_csv, &lir_dir, false, deploy_path_index);
+    let independence_csv = lir_dir.join("temporal-independence_species_30m_LIR.csv");
+    keep_first_record(&independence_csv);
+    assert_eq!(
+        extract(
+            &tags_csv,
+            None,
+            false,
+            Some(independence_csv),
+            &dir.path().join("extract_lir")
+        ),
+        [
+            "DEP01/IMG_0001.JPG",
+            "DEP01/IMG_0002.JPG",
+            "DEP01/IMG_0004.JPG"
+        ]
+    );
+
+    // LR: gaps under 30 minutes chain 10:40 into the event, 11:30 comes 50 minutes later
+    let lr_dir = dir.path().join("capture_lr");
+    capture(&tags_csv, &lr_dir, true, deploy_path_index);
+    let independence_csv = lr_dir.join("temporal-independence_species_30m_LR.csv");
+    keep_first_record(&independence_csv);
+    assert_eq!(
+        extract(
+            &tags_csv,
+            None,
+            false,
+            Some(independence_csv),
+            &dir.path().join("extract_lr")
+        ),
+        [
+            "DEP01/IMG_0001.JPG",
+            "DEP01/IMG_0002.JPG",
+            "DEP01/IMG_0004.JPG",
+            "DEP01/IMG_0005.JPG"
+        ]
+    );
+}
+
+#[test]
+fn whole_event_takes_every_record_sharing_the_event_id() {
+    let dir = TempDir::new("whole_event");
+    let (tags_csv, deploy_path_index) = write_tags(dir.path());
+    let capture_dir = dir.path().join("capture");
+    capture(&tags_csv, &capture_dir, false, deploy_path_index);
+    let events_csv = capture_dir.join("events_species_30m_LIR.csv");
+
+    let matched = Some((ExtractFilterType::Path, "DEP01/IMG_0002"));
+    assert_eq!(
+        extract(
+            &events_csv,
+            matched,
+            false,
+            None,
+            &dir.path().join("single")
+        ),
+        ["DEP01/IMG_0002.JPG"]
+    );
+    assert_eq!(
+        extract(&events_csv, matched, true, None, &dir.path().join("whole")),
+        [
+            "DEP01/IMG_0001.JPG",
+            "DEP01/IMG_0002.JPG",
+            "DEP01/IMG_0004.JPG"
+        ]
+    );
+}
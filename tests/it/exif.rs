@@ -0,0 +1,199 @@
+// observe --exif-fallback dates images that carry DateTimeOriginal in EXIF only, coordinates
+// are read from XMP and, with --gps, from the EXIF the toolkit leaves out of it
+use crate::common::{TempDir, csv_column, find_output, observe, observe_resources};
+use image::{ImageFormat, RgbImage};
+use serval::tags::ObserveSettings;
+use serval::utils::ResourceType;
+use std::fs;
+use std::io::Cursor;
+use std::path::Path;
+
+// One little-endian IFD: entry count, the 12-byte entries and a zero next-IFD offset
+fn ifd(entries: &[(u16, u16, u32, [u8; 4])]) -> Vec<u8> {
+    let mut bytes = (entries.len() as u16).to_le_bytes().to_vec();
+    for (tag, kind, count, value) in entries {
+        bytes.extend(tag.to_le_bytes());
+        bytes.extend(kind.to_le_bytes());
+        bytes.extend(count.to_le_bytes());
+        bytes.extend(value);
+    }
+    bytes.extend(0u32.to_le_bytes());
+    bytes
+}
+
+fn rationals(values: [u32; 3]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| [value.to_le_bytes(), 1u32.to_le_bytes()].concat())
+        .collect()
+}
+
+// TIFF block holding DateTimeOriginal and the GPS position 29°30'S 91°6'36"E
+// (-29.5, 91.11): IFD0 at 8 points to the EXIF IFD at 38 and the GPS IFD at 76,
+// the values they reference follow each of them
+fn exif_tiff(datetime: &str) -> Vec<u8> {
+    let mut tiff = b"II*\0".to_vec();
+    tiff.extend(8u32.to_le_bytes());
+    tiff.extend(ifd(&[
+        (0x8769, 4, 1, 38u32.to_le_bytes()),
+        (0x8825, 4, 1, 76u32.to_le_bytes()),
+    ]));
+    tiff.extend(ifd(&[(0x9003, 2, 20, 56u32.to_le_bytes())]));
+    tiff.extend(datetime.as_bytes());
+    tiff.push(0);
+    tiff.extend(ifd(&[
+        (0x1, 2, 2, *b"S\0\0\0"),
+        (0x2, 5, 3, 130u32.to_le_bytes()),
+        (0x3, 2, 2, *b"E\0\0\0"),
+        (0x4, 5, 3, 154u32.to_le_bytes()),
+    ]));
+    tiff.extend(rationals([29, 30, 0]));
+    tiff.extend(rationals([91, 6, 36]));
+    tiff
+}
+
+fn encoded_image(format: ImageFormat) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    RgbImage::from_pixel(16, 16, image::Rgb([90, 120, 60]))
+        .write_to(&mut Cursor::new(&mut bytes), format)
+        .unwrap();
+    bytes
+}
+
+// A PNG with an eXIf chunk, which the XMP toolkit doesn't read
+fn png_with_exif(datetime: &str) -> Vec<u8> {
+    let mut png = encoded_image(ImageFormat::Png);
+    let mut chunk = b"eXIf".to_vec();
+    chunk.extend(exif_tiff(datetime));
+    let mut segment = ((chunk.len() - 4) as u32).to_be_bytes().to_vec();
+    segment.extend(&chunk);
+    segment.extend(crc32(&chunk).to_be_bytes());
+    // Before the IEND chunk
+    let iend = png.len() - 12;
+    png.splice(iend..iend, segment);
+    png
+}
+
+// A JPEG with an APP1 Exif segment right after SOI, reconciled into XMP by the toolkit
+fn jpeg_with_exif(datetime: &str) -> Vec<u8> {
+    let mut jpeg = encoded_image(ImageFormat::Jpeg);
+    let mut payload = b"Exif\0\0".to_vec();
+    payload.extend(exif_tiff(datetime));
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend(((payload.len() + 2) as u16).to_be_bytes());
+    segment.extend(payload);
+    jpeg.splice(2..2, segment);
+    jpeg
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn observe_images(media_dir: &Path, output_dir: &Path, exif_fallback: bool, gps: bool) {
+    observe_resources(
+        media_dir,
+        output_dir,
+        ResourceType::Image,
+        ObserveSettings {
+            exif_fallback,
+            gps,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn exif_datetime_fills_in_and_is_audited() {
+    let dir = TempDir::new("exif");
+    let media_dir = dir.path().join("DEP01");
+    fs::create_dir_all(&media_dir).unwrap();
+    fs::write(
+        media_dir.join("IMG_0001.PNG"),
+        png_with_exif("2024:03:01 06:30:00"),
+    )
+    .unwrap();
+    RgbImage::from_pixel(16, 16, image::Rgb([90, 120, 60]))
+        .save_with_format(media_dir.join("IMG_0002.JPG"), ImageFormat::Jpeg)
+        .unwrap();
+
+    let fallback = dir.path().join("fallback");
+    observe_images(&media_dir, &fallback, true, false);
+    let tags = find_output(&fallback, "tags_");
+    assert_eq!(csv_column(&tags, "datetime"), ["2024-03-01 06:30:00", ""]);
+    assert_eq!(csv_column(&tags, "datetime_source"), ["exif", "none"]);
+
+    let plain = dir.path().join("plain");
+    observe_images(&media_dir, &plain, false, false);
+    let header = fs::read_to_string(find_output(&plain, "tags_")).unwrap();
+    assert!(!header.lines().next().unwrap().contains("datetime_source"));
+}
+
+#[test]
+fn gps_coordinates_are_decimal_degrees() {
+    let dir = TempDir::new("exif_gps");
+    let media_dir = dir.path().join("DEP01");
+    fs::create_dir_all(&media_dir).unwrap();
+    fs::write(
+        media_dir.join("IMG_0001.PNG"),
+        png_with_exif("2024:03:01 06:30:00"),
+    )
+    .unwrap();
+    fs::write(
+        media_dir.join("IMG_0002.JPG"),
+        jpeg_with_exif("2024:03:01 06:31:00"),
+    )
+    .unwrap();
+    RgbImage::from_pixel(16, 16, image::Rgb([90, 120, 60]))
+        .save_with_format(media_dir.join("IMG_0003.JPG"), ImageFormat::Jpeg)
+        .unwrap();
+
+    let gps = dir.path().join("gps");
+    observe_images(&media_dir, &gps, false, true);
+    let tags = find_output(&gps, "tags_");
+    assert_eq!(csv_column(&tags, "latitude"), ["-29.5", "-29.5", ""]);
+    assert_eq!(csv_column(&tags, "longitude"), ["91.11", "91.11", ""]);
+
+    let plain = dir.path().join("plain");
+    observe_images(&media_dir, &plain, false, false);
+    let tags = find_output(&plain, "tags_");
+    assert_eq!(csv_column(&tags, "latitude"), ["", "", ""]);
+}
+
+#[test]
+fn sidecar_gps_is_read_without_flag() {
+    let dir = TempDir::new("exif_gps_sidecar");
+    let media_dir = dir.path().join("DEP01");
+    fs::create_dir_all(&media_dir).unwrap();
+    fs::write(
+        media_dir.join("IMG_0001.JPG.xmp"),
+        r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:exif="http://ns.adobe.com/exif/1.0/"
+    exif:DateTimeOriginal="2024-03-01T06:30:00"
+    exif:GPSLatitude="29,30.0000S"
+    exif:GPSLongitude="91,6.6000E"/>
+ </rdf:RDF>
+</x:xmpmeta>"#,
+    )
+    .unwrap();
+
+    let output_dir = dir.path().join("output");
+    observe(&media_dir, &output_dir, ObserveSettings::default()).unwrap();
+    let tags = find_output(&output_dir, "tags_");
+    assert_eq!(csv_column(&tags, "latitude"), ["-29.5"]);
+    assert_eq!(csv_column(&tags, "longitude"), ["91.11"]);
+}
@@ -0,0 +1,82 @@
+// observe --extra-tag reads tag categories beyond the built-in ones, extract filters on them
+use crate::common::{TempDir, csv_column, find_output, list_files, observe, sidecar};
+use serval::tags::{ExtractOptions, ObserveSettings, extract_resources};
+use serval::utils::ExtractFilterType;
+use std::fs;
+use std::path::Path;
+
+fn observe_extra(media_dir: &Path, output_dir: &Path, extra_tags: &[&str]) -> anyhow::Result<()> {
+    observe(
+        media_dir,
+        output_dir,
+        ObserveSettings {
+            extra_tags: extra_tags.iter().map(|tag| tag.to_string()).collect(),
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+fn declared_categories_become_columns() {
+    let dir = TempDir::new("extra_tags");
+    let media_dir = dir.path().join("DEP01");
+    fs::create_dir_all(&media_dir).unwrap();
+    for (file_name, tags) in [
+        (
+            "IMG_0001.JPG",
+            &[
+                "Species|Zebra",
+                "Behaviour|Grazing",
+                "Behaviour|Resting",
+                "Age|Juvenile",
+                "Weather|Rain",
+            ][..],
+        ),
+        ("IMG_0002.JPG", &["Species|Zebra", "Behaviour|Grazing"][..]),
+        ("IMG_0003.JPG", &["Species|Blank"][..]),
+    ] {
+        fs::write(media_dir.join(file_name), file_name).unwrap();
+        fs::write(
+            media_dir.join(format!("{file_name}.xmp")),
+            sidecar("", tags),
+        )
+        .unwrap();
+    }
+
+    let output_dir = dir.path().join("observe");
+    observe_extra(&media_dir, &output_dir, &["Behaviour", "Age"]).unwrap();
+    let tags = find_output(&output_dir, "tags_");
+    assert_eq!(
+        csv_column(&tags, "behaviour"),
+        ["Grazing|Resting", "Grazing", ""]
+    );
+    assert_eq!(csv_column(&tags, "age"), ["Juvenile", "", ""]);
+    // Undeclared categories are still ignored
+    let header = fs::read_to_string(&tags).unwrap();
+    assert!(!header.lines().next().unwrap().contains("weather"));
+    let schema = fs::read_to_string(tags.with_extension("schema.json")).unwrap();
+    assert!(schema.contains("\"behaviour\""));
+
+    // A category may not stand for a column observe writes already
+    let err = observe_extra(&media_dir, &dir.path().join("taken"), &["Species"]).unwrap_err();
+    assert!(err.to_string().contains("species column"), "{err}");
+
+    let extract_dir = dir.path().join("extract");
+    extract_resources(
+        "Resting".to_string(),
+        ExtractFilterType::Custom,
+        tags,
+        extract_dir.clone(),
+        ExtractOptions {
+            custom_column: Some("behaviour".to_string()),
+            keep_level: Some(1),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let files: Vec<String> = list_files(&extract_dir)
+        .into_iter()
+        .filter(|file| file != "manifest.csv")
+        .collect();
+    assert_eq!(files, ["DEP01/IMG_0001.JPG", "DEP01/IMG_0001.JPG.xmp"]);
+}
@@ -0,0 +1,55 @@
+// Missing sources are set aside before copying, --max-size aborts on the estimate
+use crate::common::{TempDir, csv_column, list_files};
+use serval::tags::{ExtractOptions, MISSING_SOURCES_FILE, extract_resources};
+use serval::utils::ExtractFilterType;
+use std::fs;
+use std::path::Path;
+
+fn extract(tags_csv: &Path, output_dir: &Path, max_size: Option<u64>) -> anyhow::Result<()> {
+    extract_resources(
+        "Serval".to_string(),
+        ExtractFilterType::Species,
+        tags_csv.to_path_buf(),
+        output_dir.to_path_buf(),
+        ExtractOptions {
+            max_size,
+            keep_level: Some(0),
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+fn missing_sources_are_reported_and_budget_enforced() {
+    let dir = TempDir::new("extract_estimate");
+    let deploy_dir = dir.path().join("DEP01");
+    fs::create_dir_all(&deploy_dir).unwrap();
+    fs::write(deploy_dir.join("IMG_0001.JPG"), "0123456789").unwrap();
+    let missing = deploy_dir.join("IMG_0002.JPG");
+    let tags_csv = dir.path().join("tags.csv");
+    fs::write(
+        &tags_csv,
+        format!(
+            "path,species\n{},Serval\n{},Serval\n",
+            deploy_dir.join("IMG_0001.JPG").display(),
+            missing.display()
+        ),
+    )
+    .unwrap();
+
+    let output = dir.path().join("extract");
+    extract(&tags_csv, &output, None).unwrap();
+    assert_eq!(
+        list_files(&output),
+        ["IMG_0001.JPG", "manifest.csv", MISSING_SOURCES_FILE]
+    );
+    assert_eq!(
+        csv_column(&output.join(MISSING_SOURCES_FILE), "path"),
+        [missing.to_string_lossy()]
+    );
+
+    let over_budget = dir.path().join("over_budget");
+    let error = extract(&tags_csv, &over_budget, Some(9)).unwrap_err();
+    assert!(error.to_string().contains("--max-size"), "{error}");
+    assert!(!over_budget.join("IMG_0001.JPG").exists());
+}
@@ -0,0 +1,47 @@
+// --group-by adds one directory level above the kept structure, renamed files included
+use crate::common::{TempDir, csv_column, list_files};
+use serval::tags::{ExtractOptions, extract_resources};
+use serval::utils::{ExtractFilterType, GroupBy};
+use std::fs;
+
+#[test]
+fn renamed_copies_are_grouped_by_individual() {
+    let dir = TempDir::new("extract_group");
+    let deploy_dir = dir.path().join("project").join("DEP01");
+    fs::create_dir_all(&deploy_dir).unwrap();
+    let mut csv = "path,species,individual\n".to_string();
+    for (name, individual) in [("IMG_0001.JPG", "S1"), ("IMG_0002.JPG", "")] {
+        let media = deploy_dir.join(name);
+        fs::write(&media, name).unwrap();
+        csv.push_str(&format!("{},Serval,{individual}\n", media.display()));
+    }
+    let tags_csv = dir.path().join("tags.csv");
+    fs::write(&tags_csv, csv).unwrap();
+
+    let output = dir.path().join("extract");
+    extract_resources(
+        "Serval".to_string(),
+        ExtractFilterType::Species,
+        tags_csv,
+        output.clone(),
+        ExtractOptions {
+            rename: true,
+            group_by: Some(GroupBy::Individual),
+            keep_level: Some(1),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        list_files(&output),
+        [
+            "S1/DEP01/Serval-S1-IMG_0001.JPG",
+            "_unknown/DEP01/Serval--IMG_0002.JPG",
+            "manifest.csv",
+        ]
+    );
+    assert_eq!(
+        csv_column(&output.join("manifest.csv"), "group_individual"),
+        ["S1", "_unknown"]
+    );
+}
@@ -0,0 +1,135 @@
+// --keep-relative-to resolves the keep level from a directory, no prompt involved
+use crate::common::{TempDir, in_own_process, list_files};
+use serval::tags::{ExtractOptions, KeepRelativeTo, extract_resources};
+use serval::utils::ExtractFilterType;
+use std::fs;
+use std::path::Path;
+
+fn extract(tags_csv: &Path, output_dir: &Path, keep_relative_to: KeepRelativeTo) {
+    extract_resources(
+        "Serval".to_string(),
+        ExtractFilterType::Species,
+        tags_csv.to_path_buf(),
+        output_dir.to_path_buf(),
+        ExtractOptions {
+            keep_relative_to: Some(keep_relative_to),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn keep_level_follows_the_base_directory() {
+    let dir = TempDir::new("extract_keep");
+    let project = dir.path().join("project");
+    let mut csv = "path,species\n".to_string();
+    for deployment in ["A/DEP01", "B/DEP02"] {
+        let deploy_dir = project.join(deployment);
+        fs::create_dir_all(&deploy_dir).unwrap();
+        let media = deploy_dir.join("IMG_0001.JPG");
+        fs::write(&media, deployment).unwrap();
+        csv.push_str(&format!("{},Serval\n", media.display()));
+    }
+    let tags_csv = dir.path().join("tags.csv");
+    fs::write(&tags_csv, csv).unwrap();
+
+    let auto = dir.path().join("auto");
+    extract(&tags_csv, &auto, KeepRelativeTo::Auto);
+    assert_eq!(
+        list_files(&auto),
+        [
+            "A/DEP01/IMG_0001.JPG",
+            "B/DEP02/IMG_0001.JPG",
+            "manifest.csv"
+        ]
+    );
+
+    let above = dir.path().join("above");
+    extract(
+        &tags_csv,
+        &above,
+        KeepRelativeTo::Dir(dir.path().to_path_buf()),
+    );
+    assert_eq!(
+        list_files(&above),
+        [
+            "manifest.csv",
+            "project/A/DEP01/IMG_0001.JPG",
+            "project/B/DEP02/IMG_0001.JPG",
+        ]
+    );
+}
+
+#[test]
+fn windows_paths_keep_their_structure() {
+    if !in_own_process("extract_keep::windows_paths_keep_their_structure") {
+        return;
+    }
+    // D:/proj is a relative directory here, the CSV lists its files the way Windows does
+    let dir = TempDir::new("extract_keep_windows");
+    std::env::set_current_dir(dir.path()).unwrap();
+    let mut csv = "path,species\n".to_string();
+    for deployment in ["dep", "dep2"] {
+        let deploy_dir = Path::new("D:/proj/coll").join(deployment);
+        fs::create_dir_all(&deploy_dir).unwrap();
+        fs::write(deploy_dir.join("IMG.jpg"), deployment).unwrap();
+        csv.push_str(&format!(r"D:\proj\coll\{deployment}\IMG.jpg,Serval"));
+        csv.push('\n');
+    }
+    let tags_csv = dir.path().join("tags.csv");
+    fs::write(&tags_csv, csv).unwrap();
+
+    let auto = dir.path().join("auto");
+    extract(&tags_csv, &auto, KeepRelativeTo::Auto);
+    assert_eq!(
+        list_files(&auto),
+        ["dep/IMG.jpg", "dep2/IMG.jpg", "manifest.csv"]
+    );
+    assert_eq!(
+        fs::read_to_string(auto.join("dep2/IMG.jpg")).unwrap(),
+        "dep2"
+    );
+
+    let above = dir.path().join("above");
+    extract(&tags_csv, &above, KeepRelativeTo::Dir(r"D:\proj".into()));
+    assert_eq!(
+        list_files(&above),
+        ["coll/dep/IMG.jpg", "coll/dep2/IMG.jpg", "manifest.csv"]
+    );
+}
+
+#[test]
+fn relative_paths_keep_levels_above_the_working_directory() {
+    if !in_own_process("extract_keep::relative_paths_keep_levels_above_the_working_directory") {
+        return;
+    }
+    // Listed relative to the working directory, the kept levels reach above it
+    let dir = TempDir::new("extract_keep_relative");
+    let working_dir = dir.path().join("work");
+    let mut csv = "path,species\n".to_string();
+    for deployment in ["A/DEP01", "B/DEP02"] {
+        let deploy_dir = working_dir.join(deployment);
+        fs::create_dir_all(&deploy_dir).unwrap();
+        fs::write(deploy_dir.join("IMG_0001.JPG"), deployment).unwrap();
+        csv.push_str(&format!("{deployment}/IMG_0001.JPG,Serval\n"));
+    }
+    std::env::set_current_dir(&working_dir).unwrap();
+    let tags_csv = dir.path().join("tags.csv");
+    fs::write(&tags_csv, csv).unwrap();
+
+    let above = dir.path().join("above");
+    extract(
+        &tags_csv,
+        &above,
+        KeepRelativeTo::Dir(dir.path().to_path_buf()),
+    );
+    assert_eq!(
+        list_files(&above),
+        [
+            "manifest.csv",
+            "work/A/DEP01/IMG_0001.JPG",
+            "work/B/DEP02/IMG_0001.JPG",
+        ]
+    );
+}
@@ -0,0 +1,66 @@
+// --require-sidecar lists videos without their XMP, --strict refuses to copy them
+use crate::common::{TempDir, csv_column, list_files};
+use serval::tags::{ExtractOptions, MISSING_SIDECARS_FILE, RequireSidecar, extract_resources};
+use serval::utils::ExtractFilterType;
+use std::fs;
+use std::path::Path;
+
+fn extract(
+    tags_csv: &Path,
+    output_dir: &Path,
+    require_sidecar: RequireSidecar,
+) -> anyhow::Result<()> {
+    extract_resources(
+        "Serval".to_string(),
+        ExtractFilterType::Species,
+        tags_csv.to_path_buf(),
+        output_dir.to_path_buf(),
+        ExtractOptions {
+            require_sidecar: Some(require_sidecar),
+            keep_level: Some(0),
+            ..Default::default()
+        },
+    )
+}
+
+#[test]
+fn videos_without_sidecar_are_reported() {
+    let dir = TempDir::new("extract_sidecar");
+    let deploy_dir = dir.path().join("DEP01");
+    fs::create_dir_all(&deploy_dir).unwrap();
+    let mut csv = "path,species\n".to_string();
+    for name in ["VID_0001.AVI", "VID_0002.AVI"] {
+        let media = deploy_dir.join(name);
+        fs::write(&media, name).unwrap();
+        csv.push_str(&format!("{},Serval\n", media.display()));
+    }
+    fs::write(deploy_dir.join("VID_0001.AVI.xmp"), "<x:xmpmeta/>").unwrap();
+    let tags_csv = dir.path().join("tags.csv");
+    fs::write(&tags_csv, csv).unwrap();
+
+    let strict = dir.path().join("strict");
+    let error = extract(&tags_csv, &strict, RequireSidecar::Strict).unwrap_err();
+    assert!(error.to_string().contains("--strict"), "{error}");
+    assert_eq!(list_files(&strict), [MISSING_SIDECARS_FILE]);
+
+    let report = dir.path().join("report");
+    extract(&tags_csv, &report, RequireSidecar::Report).unwrap();
+    assert_eq!(
+        list_files(&report),
+        [
+            "VID_0001.AVI",
+            "VID_0001.AVI.xmp",
+            "VID_0002.AVI",
+            "manifest.csv",
+            MISSING_SIDECARS_FILE,
+        ]
+    );
+    assert_eq!(
+        csv_column(&report.join(MISSING_SIDECARS_FILE), "media_path"),
+        [deploy_dir.join("VID_0002.AVI").to_string_lossy()]
+    );
+    assert_eq!(
+        csv_column(&report.join("manifest.csv"), "sidecar_copied"),
+        ["true", "false"]
+    );
+}
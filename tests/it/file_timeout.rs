@@ -0,0 +1,56 @@
+// --file-timeout runs reads on a fixed pool of workers and stops once all of them are stuck
+use crate::common::in_own_process;
+use serval::utils::{
+    FileTimeoutError, FileWorkersStuckError, configure_parallelism, run_with_timeout,
+    with_io_permit,
+};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Option<Duration> = Some(Duration::from_millis(50));
+
+#[test]
+fn stuck_workers_stop_the_run_and_keep_their_io_permits() {
+    if !in_own_process("file_timeout::stuck_workers_stop_the_run_and_keep_their_io_permits") {
+        return;
+    }
+    configure_parallelism(Some(2), Some(2)).unwrap();
+    assert_eq!(run_with_timeout(TIMEOUT, || Ok(1)).unwrap(), 1);
+
+    // Reads that never answer until their gate is dropped
+    let mut gates = Vec::new();
+    for _ in 0..2 {
+        let (gate, blocked) = mpsc::channel::<()>();
+        gates.push(gate);
+        let err = run_with_timeout(TIMEOUT, move || {
+            let _ = blocked.recv();
+            Ok(())
+        })
+        .unwrap_err();
+        assert!(err.is::<FileTimeoutError>(), "{err}");
+    }
+    let err = run_with_timeout(TIMEOUT, || Ok(())).unwrap_err();
+    assert!(err.is::<FileWorkersStuckError>(), "{err}");
+    assert!(
+        err.to_string().contains("All 2 file workers are stuck"),
+        "{err}"
+    );
+
+    // The stuck reads still hold both IO permits
+    let (done, finished) = mpsc::channel();
+    std::thread::spawn(move || with_io_permit(|| done.send(()).unwrap()));
+    assert!(finished.recv_timeout(Duration::from_millis(200)).is_err());
+
+    drop(gates);
+    finished.recv_timeout(Duration::from_secs(5)).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        match run_with_timeout(TIMEOUT, || Ok(2)) {
+            Ok(value) => break assert_eq!(value, 2),
+            Err(err) if err.is::<FileWorkersStuckError>() && Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(10))
+            }
+            Err(err) => panic!("{err}"),
+        }
+    }
+}
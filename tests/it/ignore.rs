@@ -0,0 +1,64 @@
+// --ignore and .servalignore patterns skip entries by name or relative path
+use crate::common::{TempDir, in_own_process};
+use serval::utils::{
+    IGNORE_FILE, ResourceType, configure_ignores, is_ignored_relative, path_enumerate,
+};
+use std::fs;
+
+#[test]
+fn patterns_and_ignore_file_replace_the_defaults() {
+    if !in_own_process("ignore::patterns_and_ignore_file_replace_the_defaults") {
+        return;
+    }
+    let temp = TempDir::new("ignore");
+    let root = temp.path().join("project");
+    for file in [
+        "DEP01/IMG_0001.JPG",
+        "DEP01/_recycle/IMG_0002.JPG",
+        "DEP01/test_shots/IMG_0003.JPG",
+        "DEP02/raw/IMG_0004.JPG",
+        "DEP03/raw/IMG_0005.JPG",
+        "DEP03/.hidden/IMG_0006.JPG",
+        "DEP03/精选/IMG_0007.JPG",
+    ] {
+        let path = root.join(file);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"jpeg").unwrap();
+    }
+    fs::write(
+        temp.path().join(IGNORE_FILE),
+        "# project-wide\n_recycle\nproject/DEP02/raw\n",
+    )
+    .unwrap();
+    configure_ignores(&["test_shots/".to_string()], true).unwrap();
+
+    let relative = |root: &std::path::Path| {
+        let mut paths: Vec<String> = path_enumerate(root.to_path_buf(), ResourceType::Image)
+            .iter()
+            .map(|path| {
+                path.strip_prefix(root)
+                    .unwrap()
+                    .to_string_lossy()
+                    .replace('\\', "/")
+            })
+            .collect();
+        paths.sort();
+        paths
+    };
+    // Names match anywhere, paths from the directory of the .servalignore, which applies
+    // to walks of its subdirectories too
+    assert_eq!(
+        relative(&root),
+        [
+            "DEP01/IMG_0001.JPG",
+            "DEP03/.hidden/IMG_0006.JPG",
+            "DEP03/raw/IMG_0005.JPG",
+            "DEP03/精选/IMG_0007.JPG",
+        ]
+    );
+    assert_eq!(relative(&root.join("DEP02")), Vec::<String>::new());
+
+    assert!(is_ignored_relative("DEP01/test_shots/IMG_0003.JPG"));
+    assert!(!is_ignored_relative("DEP03/.hidden/IMG_0006.JPG"));
+    assert!(configure_ignores(&["[".to_string()], false).is_err());
+}
@@ -0,0 +1,100 @@
+// The typed LIR pass flags the same records as the row-by-row string comparison it replaced
+use chrono::NaiveDateTime;
+use polars::prelude::*;
+use serval::tags::last_independent_records;
+use serval::utils::TagType;
+
+// The replaced implementation: every value formatted and times parsed back, row by row
+fn reference_flags(df_sorted: &DataFrame, min_delta_time: i64) -> Vec<bool> {
+    let value = |name: &str, row: usize| {
+        df_sorted
+            .column(name)
+            .unwrap()
+            .get(row)
+            .unwrap()
+            .to_string()
+    };
+    let parse = |time: &str| NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M:%S").unwrap();
+    let mut flags = Vec::new();
+    let mut last = (String::new(), String::new(), String::new());
+    for row in 0..df_sorted.height() {
+        let current = (
+            value("time", row),
+            value("species", row),
+            value("deployment", row),
+        );
+        let independent = row == 0
+            || current.1 != last.1
+            || current.2 != last.2
+            || parse(&current.0) - parse(&last.0) >= chrono::Duration::minutes(min_delta_time);
+        if independent {
+            last = current;
+        }
+        flags.push(independent);
+    }
+    flags
+}
+
+#[test]
+fn typed_pass_matches_string_comparison() {
+    // Bursts and lone records, with gaps landing exactly on the windows tested
+    let mut state: u64 = 42;
+    let mut next = |modulo: u64| {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 33) % modulo
+    };
+    let start = NaiveDateTime::parse_from_str("2024-03-01 00:00:00", "%Y-%m-%d %H:%M:%S")
+        .unwrap()
+        .and_utc()
+        .timestamp_micros();
+    let (mut deployments, mut species, mut times) = (Vec::new(), Vec::new(), Vec::new());
+    for row in 0..3000 {
+        deployments.push(format!("DEP0{}", next(4)));
+        species.push(["Serval", "Leopard cat", "Civet"][next(3) as usize]);
+        let seconds = match next(4) {
+            0 => next(120),
+            1 => next(40) * 60,
+            2 => [1, 5, 30, 60][next(4) as usize] * 60 * (row % 7),
+            _ => next(86_400),
+        };
+        times.push(start + row as i64 * 600_000_000 + seconds as i64 * 1_000_000);
+    }
+    let df = DataFrame::new(
+        times.len(),
+        vec![
+            Column::new("deployment".into(), deployments),
+            Column::new("species".into(), species),
+            Column::new("time".into(), times)
+                .cast(&DataType::Datetime(TimeUnit::Microseconds, None))
+                .unwrap(),
+        ],
+    )
+    .unwrap()
+    .sort(
+        ["deployment", "species", "time"],
+        SortMultipleOptions::default().with_maintain_order(true),
+    )
+    .unwrap();
+    // Chunk boundaries inside groups, as left by reading and filtering large inputs
+    let mut df_sorted = df.slice(0, 1000);
+    for offset in [1000, 1777, 2554] {
+        df_sorted
+            .vstack_mut(&df.slice(offset, 777.min(3000 - offset as usize)))
+            .unwrap();
+    }
+    assert_eq!(df_sorted.height(), 3000);
+    assert!(df_sorted.first_col_n_chunks() > 1);
+
+    for min_delta_time in [1, 5, 30, 60] {
+        let flags: Option<Vec<bool>> =
+            last_independent_records(&df_sorted, TagType::Species, min_delta_time as i32)
+                .unwrap()
+                .iter()
+                .collect();
+        let expected = reference_flags(&df_sorted, min_delta_time);
+        assert_eq!(flags.unwrap(), expected, "window {min_delta_time}m");
+        assert!(expected.iter().any(|independent| !independent));
+    }
+}
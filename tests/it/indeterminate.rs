@@ -0,0 +1,78 @@
+// Indeterminate labels are skipped by extract ALL_VALUES and left out of checklist discrepancies
+use crate::common::{TempDir, csv_column, in_own_process, list_files};
+use serval::config::configure_indeterminate_labels;
+use serval::tags::{ExtractOptions, KeepRelativeTo, extract_resources};
+use serval::utils::{ColumnMap, ExtractFilterType, tags_csv_checklist};
+use std::fs;
+use std::path::Path;
+
+fn extract_all_species(tags_csv: &Path, output_dir: &Path, include_indeterminate: bool) {
+    extract_resources(
+        "ALL_VALUES".to_string(),
+        ExtractFilterType::Species,
+        tags_csv.to_path_buf(),
+        output_dir.to_path_buf(),
+        ExtractOptions {
+            keep_relative_to: Some(KeepRelativeTo::Auto),
+            include_indeterminate,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn configured_labels_are_set_apart() {
+    if !in_own_process("indeterminate::configured_labels_are_set_apart") {
+        return;
+    }
+    configure_indeterminate_labels(Some(vec!["Unidentified".to_string(), " UNK ".to_string()]))
+        .unwrap();
+    let dir = TempDir::new("indeterminate");
+    let deploy_dir = dir.path().join("project/DEP01");
+    fs::create_dir_all(&deploy_dir).unwrap();
+    let mut csv = "path,species\n".to_string();
+    for (file_name, species) in [
+        ("IMG_0001.JPG", "Serval"),
+        ("IMG_0002.JPG", "UNK"),
+        ("IMG_0003.JPG", "Unidentified"),
+        ("IMG_0004.JPG", "unidentified bird"),
+    ] {
+        let media = deploy_dir.join(file_name);
+        fs::write(&media, file_name).unwrap();
+        csv.push_str(&format!("{},{species}\n", media.display()));
+    }
+    let tags_csv = dir.path().join("tags.csv");
+    fs::write(&tags_csv, csv).unwrap();
+
+    let species_only = dir.path().join("species_only");
+    extract_all_species(&tags_csv, &species_only, false);
+    assert_eq!(
+        list_files(&species_only),
+        ["IMG_0001.JPG", "IMG_0004.JPG", "manifest.csv"]
+    );
+    let everything = dir.path().join("everything");
+    extract_all_species(&tags_csv, &everything, true);
+    assert_eq!(list_files(&everything).len(), 5);
+
+    // Only the other spelling is reported, the configured labels are not species to look up
+    let checklist = dir.path().join("checklist.csv");
+    fs::write(&checklist, "name\nServal\n").unwrap();
+    let checklist_dir = dir.path().join("checklist");
+    tags_csv_checklist(
+        tags_csv,
+        checklist,
+        checklist_dir.clone(),
+        "name",
+        Vec::new(),
+        ColumnMap::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        csv_column(
+            &checklist_dir.join("checklist_discrepancies.csv"),
+            "species"
+        ),
+        ["unidentified bird"]
+    );
+}
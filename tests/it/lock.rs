@@ -0,0 +1,90 @@
+// Output directories are locked while a run writes to them, stale locks are taken over
+use crate::common::TempDir;
+use serval::lock::{LOCK_FILE, OutputLock};
+use std::fs;
+
+#[test]
+fn fresh_lock_refuses_and_stale_lock_is_taken_over() {
+    let dir = TempDir::new("lock");
+    let output = dir.path().join("output");
+    let lock_path = output.join(LOCK_FILE);
+
+    let lock = OutputLock::acquire(&output, false).unwrap();
+    let held = fs::read_to_string(&lock_path).unwrap();
+    assert!(
+        held.contains(&format!("pid = {}", std::process::id())),
+        "{held}"
+    );
+    let err = OutputLock::acquire(&output, false).unwrap_err();
+    assert!(
+        err.to_string().contains("in use by another serval run"),
+        "{err}"
+    );
+    drop(lock);
+    assert!(!lock_path.exists());
+
+    // However old, a lock whose process still runs on this host is kept
+    let started = held
+        .lines()
+        .find(|line| line.starts_with("started"))
+        .unwrap()
+        .to_string();
+    fs::write(
+        &lock_path,
+        held.replace(&started, "started = \"2020-01-01T00:00:00+00:00\""),
+    )
+    .unwrap();
+    let err = OutputLock::acquire(&output, false).unwrap_err();
+    assert!(
+        err.to_string().contains("in use by another serval run"),
+        "{err}"
+    );
+    fs::remove_file(&lock_path).unwrap();
+
+    // A lock left on this host by a process that has exited
+    let mut child = std::process::Command::new(std::env::current_exe().unwrap())
+        .arg("--list")
+        .stdout(std::process::Stdio::null())
+        .spawn()
+        .unwrap();
+    let dead_pid = child.id();
+    child.wait().unwrap();
+    fs::write(
+        &lock_path,
+        held.replace(
+            &format!("pid = {}", std::process::id()),
+            &format!("pid = {dead_pid}"),
+        ),
+    )
+    .unwrap();
+    let lock = OutputLock::acquire(&output, false).unwrap();
+    assert!(
+        fs::read_to_string(&lock_path)
+            .unwrap()
+            .contains(&format!("pid = {}", std::process::id()))
+    );
+    drop(lock);
+
+    // A lock too old to trust, from a host whose processes can't be checked
+    fs::write(
+        &lock_path,
+        "pid = 1\nhost = \"other-host\"\ncommand = \"serval observe\"\n\
+         started = \"2020-01-01T00:00:00+00:00\"\n",
+    )
+    .unwrap();
+    drop(OutputLock::acquire(&output, false).unwrap());
+    assert!(!lock_path.exists());
+
+    // A fresh lock from another host is only taken over with --force-lock
+    let started = chrono::Local::now().to_rfc3339();
+    fs::write(
+        &lock_path,
+        format!("pid = 1\nhost = \"other-host\"\ncommand = \"serval observe\"\nstarted = \"{started}\"\n"),
+    )
+    .unwrap();
+    let err = OutputLock::acquire(&output, false).unwrap_err();
+    assert!(err.to_string().contains("pid 1 on other-host"), "{err}");
+    assert!(err.to_string().contains("serval observe"), "{err}");
+    drop(OutputLock::acquire(&output, true).unwrap());
+    assert!(!lock_path.exists());
+}
@@ -0,0 +1,48 @@
+// Integration tests, one module per feature in a single test binary
+mod common;
+
+mod align;
+mod anonymize;
+mod archive;
+mod audit;
+mod backport;
+mod camera_info;
+mod capture_stdin;
+mod collisions;
+mod csv_dialect;
+mod dateparts;
+mod deployment;
+mod digikam;
+mod duplicates;
+mod event_gap;
+mod events;
+mod exif;
+mod extra_tags;
+mod extract_estimate;
+mod extract_group;
+mod extract_keep;
+mod extract_sidecar;
+mod file_timeout;
+mod ignore;
+mod independence;
+mod indeterminate;
+mod lock;
+mod malformed_datetime;
+mod multivalue;
+mod parquet;
+mod paths;
+mod pipeline;
+mod qa;
+mod reconcile;
+mod rename_convention;
+mod resume;
+mod snapshot;
+mod tagslist;
+mod timezone;
+mod translate;
+mod verify_sample;
+mod xmp_embed;
+mod xmp_roundtrip;
+mod xmp_sync;
+mod xmp_template;
+mod xmp_update;
@@ -0,0 +1,78 @@
+// Datetimes observe can't parse are left empty and listed in the errors CSV, unless --strict-datetime
+use crate::common::{TempDir, csv_column, find_output, observe, sidecar};
+use serval::tags::ObserveSettings;
+use std::fs;
+use std::path::Path;
+
+// Date-only values from one camera, a fractional second from another
+const FILES: &[(&str, &str, &str)] = &[
+    ("DEP01", "IMG_0001.JPG.xmp", "2024-03-01"),
+    ("DEP01", "IMG_0002.JPG.xmp", "2024-03-02"),
+    ("DEP01", "IMG_0003.JPG.xmp", "2024-03-02T10:00:00"),
+    ("DEP02", "IMG_0001.JPG.xmp", "2024-03-01T10:00:00.5"),
+];
+
+#[test]
+fn malformed_datetimes_go_to_the_errors() {
+    let dir = TempDir::new("malformed_datetime");
+    let media_dir = dir.path().join("project");
+    for (deployment, file_name, datetime) in FILES {
+        let path = media_dir.join(deployment).join(file_name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, sidecar(datetime, &[])).unwrap();
+    }
+
+    let strict = dir.path().join("strict");
+    let err = observe(
+        &media_dir,
+        &strict,
+        ObserveSettings {
+            strict_datetime: true,
+            ..Default::default()
+        },
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("3 file(s) with a malformed datetime"),
+        "{err}"
+    );
+
+    let output_dir = dir.path().join("observe");
+    observe(&media_dir, &output_dir, ObserveSettings::default()).unwrap();
+    let tags = find_output(&output_dir, "tags_");
+    assert_eq!(
+        csv_column(&tags, "datetime"),
+        ["", "", "2024-03-02 10:00:00", ""]
+    );
+    let errors = find_output(&output_dir, "errors_");
+    let mut rows: Vec<(String, String)> = csv_column(&errors, "path")
+        .into_iter()
+        .map(|path| {
+            Path::new(&path)
+                .strip_prefix(&media_dir)
+                .unwrap()
+                .to_string_lossy()
+                .into_owned()
+        })
+        .zip(csv_column(&errors, "raw_datetime"))
+        .collect();
+    rows.sort();
+    assert_eq!(
+        rows,
+        [
+            (
+                "DEP01/IMG_0001.JPG.xmp".to_string(),
+                "2024-03-01".to_string()
+            ),
+            (
+                "DEP01/IMG_0002.JPG.xmp".to_string(),
+                "2024-03-02".to_string()
+            ),
+            (
+                "DEP02/IMG_0001.JPG.xmp".to_string(),
+                "2024-03-01T10:00:00.5".to_string()
+            ),
+        ]
+    );
+}
@@ -0,0 +1,155 @@
+// --multivalue-separator splits the "fox; badger" cells of a Timelapse export into one row each
+use crate::common::{TempDir, csv_column, list_files};
+use polars::prelude::*;
+use serval::tags::{
+    CaptureOptions, CaptureSettings, ExtractOptions, KeepRelativeTo, extract_resources,
+    get_temporal_independence,
+};
+use serval::utils::{
+    ColumnMap, ExtractFilterType, MultivalueSeparator, OnConflict, TagType,
+    explode_multivalue_cells, parse_column_map_arg,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TIMELAPSE_EXPORT: &str = include_str!("fixtures/timelapse_export.csv");
+
+// The export with its images under `root`, the full path of each prepended as FilePath
+fn timelapse_project(root: &Path) -> PathBuf {
+    let mut lines = TIMELAPSE_EXPORT.lines();
+    let mut csv = format!("FilePath,{}\n", lines.next().unwrap());
+    for line in lines {
+        let fields: Vec<&str> = line.split(',').collect();
+        let media = root.join(fields[2]).join(fields[1]);
+        fs::create_dir_all(media.parent().unwrap()).unwrap();
+        fs::write(&media, fields[1]).unwrap();
+        csv.push_str(&format!("{},{line}\n", media.display()));
+    }
+    let csv_path = root.join("timelapse.csv");
+    fs::write(&csv_path, csv).unwrap();
+    csv_path
+}
+
+fn column_map() -> ColumnMap {
+    parse_column_map_arg(
+        "path=FilePath,datetime=DateTime,species=Species,individual=Individual,deployment=RelativePath",
+    )
+    .unwrap()
+}
+
+fn capture_species(csv_path: &Path, output_dir: &Path, separator: Option<MultivalueSeparator>) {
+    get_temporal_independence(
+        csv_path.to_path_buf(),
+        output_dir.to_path_buf(),
+        CaptureOptions {
+            no_exclude: true,
+            on_conflict: OnConflict::Fail,
+            column_map: column_map(),
+            multivalue_separator: separator,
+            settings: Some(CaptureSettings {
+                min_delta_time: 30,
+                compare_to_last_record: false,
+                target: TagType::Species,
+                deploy_path_index: None,
+            }),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+}
+
+#[test]
+fn listed_species_are_split_before_capture_and_extract() {
+    let dir = TempDir::new("multivalue");
+    let csv_path = timelapse_project(dir.path());
+
+    let joined = dir.path().join("joined");
+    capture_species(&csv_path, &joined, None);
+    assert!(
+        csv_column(&joined.join("count_all.csv"), "species").contains(&"fox; badger".to_string())
+    );
+
+    // `;` is picked over the comma inside the quoted Notes
+    let split = dir.path().join("split");
+    capture_species(&csv_path, &split, Some(MultivalueSeparator::Auto));
+    let count_all = split.join("count_all.csv");
+    let counts: Vec<(String, String)> = csv_column(&count_all, "species")
+        .into_iter()
+        .zip(csv_column(&count_all, "count"))
+        .collect();
+    for (species, count) in [("fox", "3"), ("badger", "2")] {
+        assert!(
+            counts.contains(&(species.to_string(), count.to_string())),
+            "{counts:?}"
+        );
+    }
+
+    let extract_dir = dir.path().join("extract");
+    extract_resources(
+        "ALL_VALUES".to_string(),
+        ExtractFilterType::Species,
+        csv_path,
+        extract_dir.clone(),
+        ExtractOptions {
+            use_subdir: true,
+            column_map: column_map(),
+            multivalue_separator: Some(MultivalueSeparator::Literal(";".to_string())),
+            keep_relative_to: Some(KeepRelativeTo::Auto),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let files = list_files(&extract_dir);
+    for expected in [
+        "DEP01/badger/IMG_0001.JPG",
+        "DEP01/badger/IMG_0003.JPG",
+        "DEP01/fox/IMG_0001.JPG",
+        "DEP01/fox/IMG_0002.JPG",
+        "DEP02/fox/IMG_0004.JPG",
+    ] {
+        assert!(files.contains(&expected.to_string()), "{files:?}");
+    }
+    assert!(!files.iter().any(|file| file.contains("fox; badger")));
+
+    // Each split row keeps the rest of its record, every pair of a listed species and
+    // individual becomes a row
+    let df = CsvReadOptions::default()
+        .with_infer_schema_length(Some(0))
+        .into_reader_with_file_handle(std::io::Cursor::new(TIMELAPSE_EXPORT))
+        .finish()
+        .unwrap()
+        .lazy()
+        .rename(["Species", "Individual"], ["species", "individual"], true)
+        .collect()
+        .unwrap();
+    let exploded =
+        explode_multivalue_cells(df, &MultivalueSeparator::Literal(";".to_string())).unwrap();
+    let rows: Vec<String> = (0..exploded.height())
+        .map(|row| {
+            ["File", "species", "individual", "Count", "Notes"]
+                .map(|column| {
+                    exploded
+                        .column(column)
+                        .unwrap()
+                        .str()
+                        .unwrap()
+                        .get(row)
+                        .unwrap_or_default()
+                        .to_string()
+                })
+                .join("/")
+        })
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            "IMG_0001.JPG/fox//2/dusk, rain",
+            "IMG_0001.JPG/badger//2/dusk, rain",
+            "IMG_0002.JPG/fox/F01/1/",
+            "IMG_0003.JPG/badger//1/",
+            "IMG_0004.JPG/fox/F02/2/pair",
+            "IMG_0004.JPG/fox/F03/2/pair",
+            "IMG_0005.JPG///0/empty",
+        ]
+    );
+}
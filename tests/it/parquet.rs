@@ -0,0 +1,107 @@
+// Observe --format both: capture and extract read the Parquet tags table like the CSV
+use crate::common::{self, Project, csv_column, list_files, observe};
+use serval::config::ServalConfig;
+use serval::tags::{
+    CaptureSettings, ExtractOptions, ObserveSettings, capture_exclude_tags, extract_resources,
+    init_xmp, update_datetime, update_tags,
+};
+use serval::utils::{
+    ColumnMap, ExtractFilterType, TagType, TagsFormat, XmpUpdateType, check_tags_staleness,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn capture(tags: PathBuf, output_dir: &Path, deploy_path_index: i32) -> PathBuf {
+    common::capture(
+        &tags,
+        output_dir,
+        capture_exclude_tags(&ServalConfig::default(), Vec::new(), false, false).unwrap(),
+        CaptureSettings {
+            min_delta_time: 30,
+            compare_to_last_record: false,
+            target: TagType::Species,
+            deploy_path_index: Some(deploy_path_index),
+        },
+    )
+    .unwrap();
+    output_dir.join("temporal-independence_species_30m_LIR.csv")
+}
+
+#[test]
+fn parquet_tags_table_reads_like_the_csv() {
+    let project = Project::create();
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let species_csv =
+        project.write_update_csv("species_update.csv", "species,xmp_update", |record| {
+            format!(",{}", record.species)
+        });
+    update_tags(
+        species_csv,
+        XmpUpdateType::Species,
+        None,
+        true,
+        ColumnMap::default(),
+    )
+    .unwrap();
+    let datetime_csv =
+        project.write_update_csv("datetime_update.csv", "xmp_update_datetime", |record| {
+            record.datetime.to_string()
+        });
+    update_datetime(datetime_csv, None, true, ColumnMap::default()).unwrap();
+
+    let observe_dir = project.output_dir("observe");
+    observe(
+        &project.root(),
+        &observe_dir,
+        ObserveSettings {
+            tags_format: TagsFormat::Both,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let tags: Vec<PathBuf> = list_files(&observe_dir)
+        .into_iter()
+        .filter(|name| name.starts_with("tags_") && !name.ends_with(".schema.json"))
+        .map(|name| observe_dir.join(name))
+        .collect();
+    let [tags_csv, tags_parquet] = [".csv", ".parquet"].map(|extension| {
+        tags.iter()
+            .find(|path| path.to_string_lossy().ends_with(extension))
+            .unwrap()
+            .clone()
+    });
+    check_tags_staleness(&tags_parquet, true, None, &ColumnMap::default()).unwrap();
+
+    let from_csv = capture(
+        tags_csv,
+        &project.output_dir("capture_csv"),
+        project.deploy_path_index(),
+    );
+    let from_parquet = capture(
+        tags_parquet.clone(),
+        &project.output_dir("capture_parquet"),
+        project.deploy_path_index(),
+    );
+    assert_eq!(
+        fs::read(&from_parquet).unwrap(),
+        fs::read(&from_csv).unwrap()
+    );
+    assert_eq!(csv_column(&from_parquet, "species").len(), 4);
+
+    let extract_dir = project.output_dir("extract");
+    extract_resources(
+        "Serval".to_string(),
+        ExtractFilterType::Species,
+        tags_parquet,
+        extract_dir.clone(),
+        ExtractOptions {
+            keep_level: Some(1),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        csv_column(&extract_dir.join("manifest.csv"), "path").len(),
+        4
+    );
+}
@@ -0,0 +1,247 @@
+// Path handling shared by the commands, on paths as tags.csv lists them on every platform
+use crate::common::{TempDir, in_own_process};
+use serval::utils::{
+    PathResolver, absolute_path, deployment_from_path, dir_output_name, media_path_for,
+    normalize_path_str, set_times_outcome, sidecar_path_for, sync_modified_time,
+    trim_verbatim_prefix, unpreserved_mtime,
+};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[test]
+fn windows_paths_split_like_unix_paths() {
+    assert_eq!(
+        normalize_path_str(r"D:\project\Collection\DEP01\IMG_0001.JPG"),
+        "D:/project/Collection/DEP01/IMG_0001.JPG"
+    );
+    for path in [
+        r"D:\project\Collection\DEP01\IMG_0001.JPG",
+        "D:/project/Collection/DEP01/IMG_0001.JPG",
+        r"D:\project/Collection\DEP01/IMG_0001.JPG",
+    ] {
+        assert_eq!(deployment_from_path(Path::new(path), 3).unwrap(), "DEP01");
+    }
+    assert!(deployment_from_path(Path::new(r"D:\project\IMG_0001.JPG"), 5).is_err());
+}
+
+#[test]
+fn absolute_path_keeps_absolute_paths() {
+    let cwd = std::env::current_dir().unwrap();
+    assert_eq!(
+        absolute_path(PathBuf::from("./project/DEP01")).unwrap(),
+        cwd.join("project/DEP01")
+    );
+    assert_eq!(
+        absolute_path(PathBuf::from("project")).unwrap(),
+        cwd.join("project")
+    );
+    assert_eq!(
+        absolute_path(PathBuf::from("/data/project")).unwrap(),
+        PathBuf::from("/data/project")
+    );
+    assert_eq!(
+        absolute_path(PathBuf::from("D:/project")).unwrap(),
+        PathBuf::from("D:/project")
+    );
+}
+
+#[test]
+fn absolute_path_follows_the_working_directory() {
+    if !in_own_process("paths::absolute_path_follows_the_working_directory") {
+        return;
+    }
+    let dir = TempDir::new("working_dir");
+    let mut resolver = None;
+    for name in ["first", "second"] {
+        let working_dir = dir.path().join(name);
+        fs::create_dir_all(&working_dir).unwrap();
+        std::env::set_current_dir(&working_dir).unwrap();
+        assert_eq!(
+            absolute_path(PathBuf::from("DEP01")).unwrap(),
+            std::env::current_dir().unwrap().join("DEP01")
+        );
+        resolver.get_or_insert_with(|| {
+            (
+                PathResolver::new().unwrap(),
+                std::env::current_dir().unwrap(),
+            )
+        });
+    }
+    // Created in the first directory, a resolver stays there
+    let (resolver, first_dir) = resolver.unwrap();
+    assert_eq!(
+        resolver.absolute(PathBuf::from("./DEP01")),
+        first_dir.join("DEP01")
+    );
+    assert_eq!(
+        resolver.absolute(PathBuf::from("/data/project")),
+        PathBuf::from("/data/project")
+    );
+}
+
+#[test]
+fn verbatim_prefix_is_trimmed() {
+    assert_eq!(
+        trim_verbatim_prefix(r"\\?\D:\project\DEP01").as_deref(),
+        Some(r"D:\project\DEP01")
+    );
+    assert_eq!(
+        trim_verbatim_prefix(r"\\?\UNC\server\share\DEP01").as_deref(),
+        Some(r"\\server\share\DEP01")
+    );
+    for path in [
+        r"D:\project\DEP01",
+        r"\\server\share\DEP01",
+        "/data/project",
+    ] {
+        assert_eq!(trim_verbatim_prefix(path), None, "{path}");
+    }
+    // Only Windows paths carry it, elsewhere the backslashes are part of a file name
+    let verbatim = PathBuf::from(r"\\?\D:\project\DEP01");
+    let expected = if cfg!(windows) {
+        PathBuf::from(r"D:\project\DEP01")
+    } else {
+        std::env::current_dir().unwrap().join(&verbatim)
+    };
+    assert_eq!(absolute_path(verbatim).unwrap(), expected);
+}
+
+#[test]
+fn output_name_falls_back_for_roots() {
+    assert_eq!(
+        dir_output_name(Path::new("/data/project")),
+        ("project".to_string(), false)
+    );
+    assert_eq!(
+        dir_output_name(Path::new("/data/project/DEP01/..")),
+        ("data_project".to_string(), true)
+    );
+    let (root_name, fallback) = dir_output_name(Path::new("/"));
+    assert!(fallback && root_name.starts_with("root_"), "{root_name}");
+
+    // Windows drives, whichever platform reads them
+    let (drive_name, fallback) = dir_output_name(Path::new(r"D:\"));
+    assert!(fallback && drive_name.starts_with("root_"), "{drive_name}");
+    assert_eq!(
+        dir_output_name(Path::new("D:/")),
+        (drive_name.clone(), true)
+    );
+    assert_ne!(dir_output_name(Path::new(r"E:\")).0, drive_name);
+    assert_eq!(
+        dir_output_name(Path::new(r"D:\data\project")),
+        ("project".to_string(), false)
+    );
+    assert_eq!(
+        dir_output_name(Path::new(r"D:\project\DEP01\..")),
+        ("project".to_string(), true)
+    );
+}
+
+#[test]
+fn modified_time_is_copied() {
+    let dir = TempDir::new("mtime");
+    let source = dir.path().join("source.JPG");
+    let target = dir.path().join("target.JPG");
+    fs::write(&source, b"source").unwrap();
+    fs::write(&target, b"target").unwrap();
+    let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_709_287_200);
+    fs::File::options()
+        .write(true)
+        .open(&source)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+
+    assert!(sync_modified_time(source.clone(), target.clone()).unwrap());
+    assert_eq!(fs::metadata(&target).unwrap().modified().unwrap(), modified);
+    // Directories are left alone
+    assert!(!sync_modified_time(source, dir.path().to_path_buf()).unwrap());
+}
+
+#[test]
+fn unsupported_file_times_are_not_errors() {
+    let target = Path::new("/mnt/sdcard/DEP01/IMG_0001.JPG");
+    assert!(set_times_outcome(Ok(()), target).unwrap());
+    // SMB/NFS mounts, FAT cards and read-only targets
+    for kind in [
+        io::ErrorKind::PermissionDenied,
+        io::ErrorKind::Unsupported,
+        io::ErrorKind::ReadOnlyFilesystem,
+        io::ErrorKind::InvalidInput,
+    ] {
+        assert!(
+            !set_times_outcome(Err(io::Error::from(kind)), target).unwrap(),
+            "{kind:?}"
+        );
+    }
+    for kind in [io::ErrorKind::NotFound, io::ErrorKind::StorageFull] {
+        let err = set_times_outcome(Err(io::Error::from(kind)), target).unwrap_err();
+        assert!(
+            err.to_string().contains("Failed to set file times"),
+            "{err}"
+        );
+    }
+
+    // --require-mtime turns the warning into an error
+    let warning = unpreserved_mtime(target, false).unwrap();
+    assert!(
+        warning.starts_with("Warning: could not preserve mtime"),
+        "{warning}"
+    );
+    let err = unpreserved_mtime(target, true).unwrap_err();
+    assert!(err.to_string().contains("--require-mtime"), "{err}");
+}
+
+#[test]
+fn sidecar_names_pair_with_their_media() {
+    assert_eq!(
+        sidecar_path_for(Path::new("DEP01/IMG_0001.JPG")),
+        Path::new("DEP01/IMG_0001.JPG.xmp")
+    );
+    // Any casing of the extension is a sidecar
+    for sidecar in ["IMG_0001.JPG.xmp", "IMG_0001.JPG.XMP", "IMG_0001.JPG.Xmp"] {
+        assert_eq!(
+            media_path_for(Path::new(sidecar)),
+            Some(PathBuf::from("IMG_0001.JPG")),
+            "{sidecar}"
+        );
+    }
+    // Only the last extension is stripped
+    assert_eq!(
+        media_path_for(Path::new("IMG_0001.JPG.xmp.xmp")),
+        Some(PathBuf::from("IMG_0001.JPG.xmp"))
+    );
+    assert_eq!(
+        media_path_for(Path::new("clip.tar.MP4.xmp")),
+        Some(PathBuf::from("clip.tar.MP4"))
+    );
+    assert_eq!(
+        sidecar_path_for(Path::new("clip.tar.MP4")),
+        Path::new("clip.tar.MP4.xmp")
+    );
+    assert_eq!(media_path_for(Path::new("IMG_0001.JPG")), None);
+    assert_eq!(media_path_for(Path::new("IMG_0001.xmp.JPG")), None);
+    assert_eq!(media_path_for(Path::new("xmp")), None);
+}
+
+#[cfg(unix)]
+#[test]
+fn sidecar_names_keep_non_utf8_bytes() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let media = Path::new(OsStr::from_bytes(b"DEP01/IMG_\xff0001.JPG"));
+    let sidecar = sidecar_path_for(media);
+    assert_eq!(
+        sidecar.as_os_str().as_bytes(),
+        b"DEP01/IMG_\xff0001.JPG.xmp"
+    );
+    assert_eq!(media_path_for(&sidecar).as_deref(), Some(media));
+    let upper = Path::new(OsStr::from_bytes(b"IMG_\xff0001.JPG.XMP"));
+    assert_eq!(
+        media_path_for(upper).unwrap().as_os_str().as_bytes(),
+        b"IMG_\xff0001.JPG"
+    );
+}
@@ -0,0 +1,311 @@
+// init_xmp -> tag via xmp update -> observe -> capture -> extract on a synthetic project
+use crate::common::{Project, RECORDS, csv_column, find_output, list_files, observe};
+use serval::config::ServalConfig;
+use serval::tags::{
+    CaptureComparison, CaptureOptions, CaptureSettings, ExtractOptions, ObserveSettings,
+    capture_exclude_tags, extract_resources, get_temporal_independence, init_xmp, update_datetime,
+    update_tags,
+};
+use serval::utils::{
+    ColumnMap, ExtractFilterType, IndependenceMode, OnConflict, TagType, UtcOffsets, XmpUpdateType,
+    parse_utc_offset_arg,
+};
+use std::fs;
+
+#[test]
+fn pipeline() {
+    let project = Project::create();
+
+    // Sidecars next to every media file
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    for record in RECORDS {
+        assert!(
+            project.sidecar_path(record).is_file(),
+            "missing sidecar for {}",
+            project.media_path(record).display()
+        );
+    }
+
+    // Tag species, ratings and datetimes through the xmp update commands
+    let species_csv = project.write_update_csv(
+        "species_update.csv",
+        "species,xmp_update,rating_update",
+        |record| {
+            let rating = if record.species == "Serval" {
+                "4.0"
+            } else {
+                ""
+            };
+            format!(",{},{rating}", record.species)
+        },
+    );
+    update_tags(
+        species_csv,
+        XmpUpdateType::Species,
+        Some("tester".to_string()),
+        true,
+        ColumnMap::default(),
+    )
+    .unwrap();
+    let datetime_csv =
+        project.write_update_csv("datetime_update.csv", "xmp_update_datetime", |record| {
+            record.datetime.to_string()
+        });
+    update_datetime(datetime_csv, None, true, ColumnMap::default()).unwrap();
+
+    // Observe the sidecars
+    let observe_dir = project.output_dir("observe");
+    observe(
+        &project.root(),
+        &observe_dir,
+        ObserveSettings {
+            utc_offsets: Some(
+                UtcOffsets::new(Some(parse_utc_offset_arg("+05:30").unwrap()), None).unwrap(),
+            ),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let tags_csv = find_output(&observe_dir, "tags_");
+    let mut species = csv_column(&tags_csv, "species");
+    species.sort();
+    assert_eq!(
+        species,
+        ["Blank", "Leopard", "Serval", "Serval", "Serval", "Serval"]
+    );
+    let mut datetimes = csv_column(&tags_csv, "datetime");
+    datetimes.sort();
+    let mut expected: Vec<&str> = RECORDS.iter().map(|record| record.datetime).collect();
+    expected.sort();
+    assert_eq!(datetimes, expected);
+    let mut datetimes_utc = csv_column(&tags_csv, "datetime_utc");
+    datetimes_utc.sort();
+    assert_eq!(datetimes_utc[0], "2024-03-01T04:30:00Z");
+    let mut ratings = csv_column(&tags_csv, "rating");
+    ratings.sort();
+    assert_eq!(ratings, ["", "", "4", "4", "4", "4"]);
+    assert!(
+        csv_column(&tags_csv, "tagger")
+            .iter()
+            .all(|tagger| tagger == "tester")
+    );
+
+    // By count then name, closed by the untagged and total rows
+    let species_stats = find_output(&observe_dir, "species_stats_project");
+    assert_eq!(
+        crate::common::read_csv(&species_stats),
+        [
+            vec!["species", "count", "percent"],
+            vec!["Serval", "4", "66.67"],
+            vec!["Blank", "1", "16.67"],
+            vec!["Leopard", "1", "16.67"],
+            vec!["(untagged)", "0", "0.0"],
+            vec!["TOTAL", "6", "100.0"],
+        ]
+    );
+
+    let data_quality = find_output(&observe_dir, "data_quality_");
+    assert_eq!(
+        crate::common::read_csv(&data_quality),
+        [
+            vec![
+                "deployment",
+                "tagged_with_datetime",
+                "tagged_without_datetime",
+                "datetime_without_tags"
+            ],
+            vec!["DEP01", "4", "0", "0"],
+            vec!["DEP02", "2", "0", "0"],
+        ]
+    );
+
+    // Capture with a 30 minute window, Blank is excluded by default
+    let capture_dir = project.output_dir("capture");
+    let exclude_tags =
+        capture_exclude_tags(&ServalConfig::default(), Vec::new(), false, false).unwrap();
+    get_temporal_independence(
+        tags_csv.clone(),
+        capture_dir.clone(),
+        CaptureOptions {
+            deploy_table: Some(project.deploy_table()),
+            geojson: true,
+            gap_histogram: true,
+            exclude_tags,
+            on_conflict: OnConflict::Fail,
+            settings: Some(CaptureSettings {
+                min_delta_time: 30,
+                compare_to_last_record: false,
+                target: TagType::Species,
+                deploy_path_index: Some(project.deploy_path_index()),
+            }),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let independent = capture_dir.join("temporal-independence_species_30m_LIR.csv");
+    let mut independent_records: Vec<(String, String)> = csv_column(&independent, "deployment")
+        .into_iter()
+        .zip(csv_column(&independent, "species"))
+        .collect();
+    independent_records.sort();
+    assert_eq!(
+        independent_records,
+        [
+            ("DEP01".to_string(), "Leopard".to_string()),
+            ("DEP01".to_string(), "Serval".to_string()),
+            ("DEP01".to_string(), "Serval".to_string()),
+            ("DEP02".to_string(), "Serval".to_string()),
+        ]
+    );
+    assert_eq!(
+        crate::common::read_csv(&capture_dir.join("count_all.csv")),
+        [
+            vec!["species", "count", "percent"],
+            vec!["Serval", "3", "75.0"],
+            vec!["Leopard", "1", "25.0"],
+            vec!["(untagged)", "0", "0.0"],
+            vec!["TOTAL", "4", "100.0"],
+        ]
+    );
+    // Serval gaps of 5 and 55 minutes at DEP01, DEP02 has a single Serval
+    let gap_histogram = crate::common::read_csv(&capture_dir.join("gap_histogram_species_LIR.csv"));
+    assert_eq!(
+        gap_histogram[0],
+        [
+            "species",
+            "gap_minutes",
+            "count",
+            "window_minutes",
+            "retained_percent"
+        ]
+    );
+    let serval_rows: Vec<Vec<&str>> = gap_histogram
+        .iter()
+        .filter(|row| row[0] == "Serval")
+        .map(|row| row[1..].iter().map(String::as_str).collect())
+        .collect();
+    assert_eq!(
+        serval_rows,
+        [
+            vec!["0-1", "0", "1", "100.0"],
+            vec!["1-5", "0", "5", "100.0"],
+            vec!["5-15", "1", "15", "75.0"],
+            vec!["15-30", "0", "30", "75.0"],
+            vec!["30-60", "1", "60", "75.0"],
+            vec!["60-120", "0", "120", "50.0"],
+            vec!["120-360", "0", "360", "50.0"],
+            vec!["360+", "0", "", ""],
+        ]
+    );
+
+    // Both comparisons at 5 and 60 minutes over the same records, against 5m LIR
+    let compare_dir = project.output_dir("capture_compare");
+    get_temporal_independence(
+        tags_csv.clone(),
+        compare_dir.clone(),
+        CaptureOptions {
+            deploy_table: Some(project.deploy_table()),
+            compare: Some(CaptureComparison {
+                windows: vec![5, 60],
+                modes: vec![IndependenceMode::Lir, IndependenceMode::Lr],
+            }),
+            exclude_tags: capture_exclude_tags(&ServalConfig::default(), Vec::new(), false, false)
+                .unwrap(),
+            on_conflict: OnConflict::Fail,
+            settings: Some(CaptureSettings {
+                min_delta_time: 5,
+                compare_to_last_record: false,
+                target: TagType::Species,
+                deploy_path_index: Some(project.deploy_path_index()),
+            }),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        crate::common::read_csv(&compare_dir.join("capture_comparison_species.csv")),
+        [
+            vec![
+                "species",
+                "5m_LIR",
+                "60m_LIR",
+                "5m_LR",
+                "60m_LR",
+                "60m_LIR_change_percent",
+                "5m_LR_change_percent",
+                "60m_LR_change_percent"
+            ],
+            vec!["Leopard", "1", "1", "1", "1", "0.0", "0.0", "0.0"],
+            vec!["Serval", "4", "3", "4", "2", "-25.0", "0.0", "-50.0"],
+            vec!["TOTAL", "5", "4", "5", "3", "-20.0", "0.0", "-40.0"],
+        ]
+    );
+    for suffix in ["5m_LIR", "60m_LIR", "5m_LR", "60m_LR"] {
+        assert!(
+            compare_dir
+                .join(format!("temporal-independence_species_{suffix}.csv"))
+                .is_file()
+        );
+        assert!(
+            compare_dir
+                .join(format!("params_species_{suffix}.toml"))
+                .is_file()
+        );
+    }
+    // The fixed-name counts are of the reference run
+    assert_eq!(
+        csv_column(&compare_dir.join("count_all.csv"), "count"),
+        ["4", "1", "0", "5"]
+    );
+    // Each output is described next to it, columns in the order written
+    let schema: serde_json::Value = serde_json::from_str(
+        &fs::read_to_string(capture_dir.join("count_all.schema.json")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(schema["output"], "count_all");
+    assert_eq!(schema["environment"]["serval"], env!("CARGO_PKG_VERSION"));
+    let columns: Vec<&str> = schema["columns"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|column| column["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(columns, ["species", "count", "percent"]);
+    let tags_schema = fs::read_to_string(tags_csv.with_extension("schema.json")).unwrap();
+    assert!(tags_schema.contains("camera local time"), "{tags_schema}");
+    let params = fs::read_to_string(capture_dir.join("params_species_30m_LIR.toml")).unwrap();
+    assert!(params.contains("[environment]"), "{params}");
+    assert!(
+        capture_dir
+            .join("deployments_species_30m_LIR.geojson")
+            .is_file()
+    );
+
+    // Extract the Serval records, keeping the deployment directory
+    let extract_dir = project.output_dir("extract");
+    extract_resources(
+        "Serval".to_string(),
+        ExtractFilterType::Species,
+        tags_csv,
+        extract_dir.clone(),
+        ExtractOptions {
+            keep_level: Some(1),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        list_files(&extract_dir),
+        [
+            "DEP01/IMG_0001.JPG",
+            "DEP01/IMG_0001.JPG.xmp",
+            "DEP01/IMG_0002.JPG",
+            "DEP01/IMG_0002.JPG.xmp",
+            "DEP01/VID_0004.MP4",
+            "DEP01/VID_0004.MP4.xmp",
+            "DEP02/IMG_0001.JPG",
+            "DEP02/IMG_0001.JPG.xmp",
+            "manifest.csv",
+        ]
+    );
+}
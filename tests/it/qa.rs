@@ -0,0 +1,86 @@
+// qa --counts compares the media of each deployment with the deploy table's expected count
+use crate::common::{TempDir, csv_column};
+use serval::qa::{QaStatus, parse_count_tolerance, run_qa};
+use std::fs;
+use std::path::Path;
+
+// DEP01 has all its media, DEP02 is 1 of 4 short, DEP03 has no directory and DEP04 no count
+fn counted_project(dir: &Path) {
+    for (deployment, num_media) in [("DEP01_coll", 2), ("DEP02_coll", 3), ("DEP04_coll", 1)] {
+        let deploy_dir = dir.join("project/coll").join(deployment);
+        fs::create_dir_all(&deploy_dir).unwrap();
+        for i in 0..num_media {
+            fs::write(deploy_dir.join(format!("IMG_{i:04}.JPG")), b"jpeg").unwrap();
+        }
+        fs::write(deploy_dir.join("notes.txt"), b"not media").unwrap();
+    }
+    fs::write(
+        dir.join("deployments.csv"),
+        "deploymentID,expectedMediaCount\nDEP01_coll,2\nDEP02_coll,4\nDEP03_coll,5\nDEP04_coll,\n",
+    )
+    .unwrap();
+}
+
+fn statuses(dir: &Path, tolerance: &str) -> Vec<(String, QaStatus)> {
+    let report = run_qa(
+        &dir.join("project"),
+        Some((&dir.join("deployments.csv"), "expectedMediaCount")),
+        parse_count_tolerance(tolerance).unwrap(),
+        &dir.join("qa"),
+    )
+    .unwrap();
+    report
+        .counts
+        .into_iter()
+        .map(|check| (check.deployment, check.status))
+        .collect()
+}
+
+#[test]
+fn short_and_missing_deployments_fail() {
+    let dir = TempDir::new("qa");
+    counted_project(dir.path());
+
+    let expected = |dep02| {
+        vec![
+            ("DEP01_coll".to_string(), QaStatus::Ok),
+            ("DEP02_coll".to_string(), dep02),
+            ("DEP03_coll".to_string(), QaStatus::Missing),
+            ("DEP04_coll".to_string(), QaStatus::NotChecked),
+        ]
+    };
+    assert_eq!(statuses(dir.path(), "0"), expected(QaStatus::Short));
+    let report = dir.path().join("qa/qa_report.csv");
+    assert_eq!(csv_column(&report, "actual"), ["2", "3", "", "1"]);
+    assert_eq!(
+        csv_column(&report, "status"),
+        ["ok", "short", "missing", "not checked"]
+    );
+
+    // 1 file short is within 1 file or 25%, not within 20%
+    assert_eq!(statuses(dir.path(), "1"), expected(QaStatus::Ok));
+    assert_eq!(statuses(dir.path(), "25%"), expected(QaStatus::Ok));
+    assert_eq!(statuses(dir.path(), "20%"), expected(QaStatus::Short));
+}
+
+#[test]
+fn invalid_expected_count_is_an_error() {
+    let dir = TempDir::new("qa_invalid");
+    counted_project(dir.path());
+    fs::write(
+        dir.path().join("deployments.csv"),
+        "deploymentID,expectedMediaCount\nDEP01_coll,two\n",
+    )
+    .unwrap();
+
+    let err = run_qa(
+        &dir.path().join("project"),
+        Some((&dir.path().join("deployments.csv"), "expectedMediaCount")),
+        parse_count_tolerance("0").unwrap(),
+        &dir.path().join("qa"),
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("line 2: 'two'"), "{err}");
+    assert!(parse_count_tolerance("120%").is_err());
+}
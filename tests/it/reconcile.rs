@@ -0,0 +1,82 @@
+// reconcile on an extraction whose copies collided (IMG_0001_1.JPG) and were moved for review
+use crate::common::{TempDir, read_csv};
+use serval::reconcile::reconcile_tags;
+use serval::utils::{ColumnMap, XmpUpdateType};
+use std::fs;
+
+#[test]
+fn collision_renamed_copies_map_back() {
+    let dir = TempDir::new("reconcile");
+    let root = dir.path();
+    let original = root.join("tags.csv");
+    fs::write(
+        &original,
+        "path,species\n\
+         /project/DEP01/IMG_0001.JPG.xmp,Serval\n\
+         /project/DEP02/IMG_0001.JPG.xmp,Serval\n\
+         /project/DEP02/IMG_0002.JPG.xmp,Serval\n",
+    )
+    .unwrap();
+    // Both IMG_0001.JPG were extracted into Serval/, the second renamed on collision
+    let manifest = root.join("manifest.csv");
+    fs::write(
+        &manifest,
+        "path,file_key,output_path\n\
+         /project/DEP01/IMG_0001.JPG,IMG_0001.JPG,/extract/Serval/IMG_0001.JPG\n\
+         /project/DEP02/IMG_0001.JPG,IMG_0001.JPG,/extract/Serval/IMG_0001_1.JPG\n\
+         /project/DEP02/IMG_0002.JPG,IMG_0002.JPG,/extract/Serval/IMG_0002.JPG\n",
+    )
+    .unwrap();
+    // Observed after the volunteers moved the copies to another drive
+    let reviewed = root.join("reviewed_tags.csv");
+    fs::write(
+        &reviewed,
+        "path,species\n\
+         D:\\review\\Serval\\IMG_0001.JPG.xmp,Serval\n\
+         D:\\review\\Serval\\IMG_0001_1.JPG.xmp,Leopard cat\n\
+         D:\\review\\Serval\\IMG_0002.JPG.xmp,Serval\n\
+         D:\\review\\Serval\\IMG_0002.JPG.xmp,Leopard cat\n\
+         D:\\review\\Serval\\IMG_0009.JPG.xmp,Serval\n",
+    )
+    .unwrap();
+
+    let output_dir = root.join("output");
+    reconcile_tags(
+        original,
+        reviewed,
+        manifest,
+        output_dir.clone(),
+        XmpUpdateType::Species,
+        ColumnMap::default(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        read_csv(&output_dir.join("reconcile_updates.csv")),
+        [
+            vec!["path", "species", "xmp_update", "reviewed_path"],
+            vec![
+                "/project/DEP02/IMG_0001.JPG.xmp",
+                "Serval",
+                "Leopard cat",
+                "D:\\review\\Serval\\IMG_0001_1.JPG.xmp"
+            ],
+            vec![
+                "/project/DEP02/IMG_0002.JPG.xmp",
+                "",
+                "Leopard cat",
+                "D:\\review\\Serval\\IMG_0002.JPG.xmp"
+            ],
+        ]
+    );
+    assert_eq!(
+        read_csv(&output_dir.join("reconcile_unmapped.csv")),
+        [
+            vec!["path", "reason"],
+            vec![
+                "D:\\review\\Serval\\IMG_0009.JPG.xmp",
+                "not in the manifest"
+            ],
+        ]
+    );
+}
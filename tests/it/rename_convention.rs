@@ -0,0 +1,111 @@
+// xmp rename-convention switches sidecars between IMG_0001.JPG.xmp and IMG_0001.xmp, never
+// guessing when several media files share a stem, and logs each rename
+use crate::common::{TempDir, in_own_process, list_files};
+use serde_json::Value;
+use serval::audit::start_audit;
+use serval::utils::{SidecarConvention, xmp_rename_convention};
+use std::fs;
+use std::path::Path;
+
+fn write_files(dir: &Path, names: &[&str]) {
+    for name in names {
+        fs::write(dir.join(name), name).unwrap();
+    }
+}
+
+#[test]
+fn sidecars_switch_between_conventions() {
+    let dir = TempDir::new("rename_convention");
+    let root = dir.path().join("DEP01");
+    fs::create_dir_all(&root).unwrap();
+    write_files(
+        &root,
+        &[
+            "IMG_0001.JPG",
+            "IMG_0001.JPG.xmp",
+            "VID_0002.MP4",
+            "VID_0002.MP4.XMP",
+        ],
+    );
+
+    xmp_rename_convention(root.clone(), SidecarConvention::Replaced, false).unwrap();
+    assert_eq!(
+        list_files(&root),
+        [
+            "IMG_0001.JPG",
+            "IMG_0001.xmp",
+            "VID_0002.MP4",
+            "VID_0002.XMP"
+        ]
+    );
+    // Contents move along, the extension keeps its casing
+    assert_eq!(
+        fs::read_to_string(root.join("IMG_0001.xmp")).unwrap(),
+        "IMG_0001.JPG.xmp"
+    );
+
+    xmp_rename_convention(root.clone(), SidecarConvention::Appended, false).unwrap();
+    assert_eq!(
+        list_files(&root),
+        [
+            "IMG_0001.JPG",
+            "IMG_0001.JPG.xmp",
+            "VID_0002.MP4",
+            "VID_0002.MP4.XMP",
+        ]
+    );
+
+    // A dry run leaves everything in place
+    xmp_rename_convention(root.clone(), SidecarConvention::Replaced, true).unwrap();
+    assert!(root.join("IMG_0001.JPG.xmp").is_file());
+}
+
+#[test]
+fn shared_stems_are_refused() {
+    let dir = TempDir::new("rename_convention_ambiguous");
+    let root = dir.path().join("DEP01");
+    fs::create_dir_all(&root).unwrap();
+    let files = [
+        // IMG_0001.xmp would pair with both media files
+        "IMG_0001.JPG",
+        "IMG_0001.JPG.xmp",
+        "IMG_0001.MP4",
+        // Which of the two IMG_0002.xmp belongs to can't be told
+        "IMG_0002.JPG",
+        "IMG_0002.MP4",
+        "IMG_0002.xmp",
+    ];
+    write_files(&root, &files);
+
+    xmp_rename_convention(root.clone(), SidecarConvention::Replaced, false).unwrap();
+    xmp_rename_convention(root.clone(), SidecarConvention::Appended, false).unwrap();
+    let mut expected = files.to_vec();
+    expected.sort();
+    assert_eq!(list_files(&root), expected);
+}
+
+#[test]
+fn renames_are_logged() {
+    if !in_own_process("rename_convention::renames_are_logged") {
+        return;
+    }
+    let dir = TempDir::new("rename_convention_audit");
+    let root = dir.path().join("DEP01");
+    fs::create_dir_all(&root).unwrap();
+    write_files(&root, &["IMG_0001.JPG", "IMG_0001.JPG.xmp"]);
+    let log = dir.path().join("serval_audit.jsonl");
+    start_audit(&log).unwrap();
+
+    xmp_rename_convention(root.clone(), SidecarConvention::Replaced, false).unwrap();
+    let entries: Vec<Value> = fs::read_to_string(&log)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let path = |name: &str| root.join(name).to_string_lossy().replace('\\', "/");
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["path"], path("IMG_0001.xmp"));
+    assert_eq!(entries[0]["field"], "path");
+    assert_eq!(entries[0]["old_value"], path("IMG_0001.JPG.xmp"));
+    assert_eq!(entries[0]["new_value"], path("IMG_0001.xmp"));
+}
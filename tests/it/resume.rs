@@ -0,0 +1,165 @@
+// observe --resume takes earlier rows over and gives the same tables as a fresh run
+use crate::common::{Project, RECORDS, csv_column, find_output, observe, read_csv};
+use serval::tags::{ObserveResume, ObserveSettings, init_xmp, update_datetime, update_tags};
+use serval::utils::{ColumnMap, XmpUpdateType};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn observe_resumed(project: &Project, output_dir: &Path, resume: Option<PathBuf>) {
+    observe_resumed_with(project, output_dir, resume, ObserveSettings::default());
+}
+
+fn observe_resumed_with(
+    project: &Project,
+    output_dir: &Path,
+    resume: Option<PathBuf>,
+    settings: ObserveSettings,
+) {
+    observe(
+        &project.root(),
+        output_dir,
+        ObserveSettings {
+            resume: resume.map(|tags| ObserveResume {
+                tags,
+                check_mtime: true,
+            }),
+            ..settings
+        },
+    )
+    .unwrap();
+}
+
+fn tag_species(project: &Project) {
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let species_csv =
+        project.write_update_csv("species_update.csv", "species,xmp_update", |record| {
+            format!(",{}", record.species)
+        });
+    update_tags(
+        species_csv,
+        XmpUpdateType::Species,
+        None,
+        true,
+        ColumnMap::default(),
+    )
+    .unwrap();
+}
+
+#[test]
+fn resumed_observe_matches_a_fresh_one() {
+    let project = Project::create();
+    tag_species(&project);
+    let datetime_csv =
+        project.write_update_csv("datetime_update.csv", "xmp_update_datetime", |record| {
+            record.datetime.to_string()
+        });
+    update_datetime(datetime_csv, None, true, ColumnMap::default()).unwrap();
+
+    let first = project.output_dir("first");
+    observe_resumed(&project, &first, None);
+    // One file gone, one new
+    fs::remove_file(project.sidecar_path(&RECORDS[0])).unwrap();
+    let new_sidecar = project
+        .sidecar_path(&RECORDS[5])
+        .with_file_name("IMG_0003.JPG.xmp");
+    fs::copy(project.sidecar_path(&RECORDS[4]), &new_sidecar).unwrap();
+
+    let fresh = project.output_dir("fresh");
+    observe_resumed(&project, &fresh, None);
+    let resumed = project.output_dir("resumed");
+    observe_resumed(&project, &resumed, Some(find_output(&first, "tags_")));
+    for prefix in ["tags_", "species_stats_"] {
+        assert_eq!(
+            fs::read_to_string(find_output(&resumed, prefix)).unwrap(),
+            fs::read_to_string(find_output(&fresh, prefix)).unwrap(),
+            "{prefix}"
+        );
+    }
+}
+
+#[test]
+fn files_modified_after_their_recorded_time_are_read_again() {
+    let project = Project::create();
+    tag_species(&project);
+    let first = project.output_dir("first");
+    observe_resumed_with(
+        &project,
+        &first,
+        None,
+        ObserveSettings {
+            debug_mode: true,
+            ..Default::default()
+        },
+    );
+
+    // Retagged after observe read it, the tags CSV written again later still says so
+    let sidecar = project.sidecar_path(&RECORDS[1]);
+    let retagged = fs::read_to_string(&sidecar)
+        .unwrap()
+        .replace("Serval", "Leopard");
+    fs::write(&sidecar, retagged).unwrap();
+    let tags = find_output(&first, "tags_");
+    let mut rows = read_csv(&tags);
+    let time_modified = rows[0]
+        .iter()
+        .position(|name| name == "time_modified")
+        .unwrap();
+    let sidecar = sidecar.to_string_lossy();
+    for row in rows.iter_mut().filter(|row| row[0] == sidecar) {
+        row[time_modified] = "2000-01-01T00:00:00".to_string();
+    }
+    let csv: Vec<String> = rows.iter().map(|row| row.join(",")).collect();
+    fs::write(&tags, csv.join("\n")).unwrap();
+
+    let resumed = project.output_dir("resumed");
+    observe_resumed(&project, &resumed, Some(tags));
+    let resumed_tags = find_output(&resumed, "tags_");
+    let species: Vec<(String, String)> = csv_column(&resumed_tags, "path")
+        .into_iter()
+        .zip(csv_column(&resumed_tags, "species"))
+        .filter(|(path, _)| *path == sidecar)
+        .collect();
+    assert_eq!(species.len(), 1, "{species:?}");
+    assert_eq!(species[0].1, "Leopard");
+}
+
+#[test]
+fn files_that_failed_to_read_are_read_again() {
+    let project = Project::create();
+    tag_species(&project);
+    // Undecodable for the first run, as a read cut short would leave it
+    let sidecar = project.sidecar_path(&RECORDS[1]);
+    let content = fs::read(&sidecar).unwrap();
+    fs::write(&sidecar, [0xC3, 0x28]).unwrap();
+    let first = project.output_dir("first");
+    observe_resumed(&project, &first, None);
+    let sidecar_path = sidecar.to_string_lossy();
+    assert_eq!(
+        csv_column(&find_output(&first, "errors_"), "path"),
+        [sidecar_path.as_ref()]
+    );
+
+    // Readable again, its mtime isn't looked at
+    fs::write(&sidecar, content).unwrap();
+    let resumed = project.output_dir("resumed");
+    observe(
+        &project.root(),
+        &resumed,
+        ObserveSettings {
+            resume: Some(ObserveResume {
+                tags: find_output(&first, "tags_"),
+                check_mtime: false,
+            }),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let resumed_tags = find_output(&resumed, "tags_");
+    let species: Vec<String> = csv_column(&resumed_tags, "path")
+        .into_iter()
+        .zip(csv_column(&resumed_tags, "species"))
+        .filter(|(path, _)| *path == sidecar_path)
+        .map(|(_, species)| species)
+        .collect();
+    assert_eq!(species, [RECORDS[1].species]);
+}
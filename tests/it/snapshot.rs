@@ -0,0 +1,30 @@
+// Snapshot manifests are written and read as CSV or Parquet by extension
+use crate::common::{Project, RECORDS};
+use polars::prelude::*;
+use serval::snapshot::{snapshot, verify_snapshot};
+use std::fs::{self, File};
+
+#[test]
+fn parquet_manifest_round_trips() {
+    let project = Project::create();
+    let output = project.output_dir("snapshot");
+    fs::create_dir_all(&output).unwrap();
+    let manifest = output.join("manifest.parquet");
+    snapshot(project.root(), manifest.clone()).unwrap();
+
+    let df = ParquetReader::new(File::open(&manifest).unwrap())
+        .finish()
+        .unwrap();
+    assert_eq!(
+        df.get_column_names()
+            .iter()
+            .map(|name| name.as_str())
+            .collect::<Vec<_>>(),
+        ["path", "size", "mtime", "sha256"]
+    );
+    assert_eq!(df.column("size").unwrap().dtype(), &DataType::UInt64);
+    verify_snapshot(project.root(), manifest.clone()).unwrap();
+
+    fs::write(project.media_path(&RECORDS[0]), b"changed").unwrap();
+    assert!(verify_snapshot(project.root(), manifest).is_err());
+}
@@ -0,0 +1,46 @@
+// Older digiKam sidecars with a TagsList and no hierarchicalSubject are read all the same
+use crate::common::{TempDir, csv_column, find_output, observe};
+use serval::tags::ObserveSettings;
+use std::fs;
+
+const TAGSLIST_SIDECAR: &str = include_str!("fixtures/digikam_tagslist.xmp");
+const TAGSLIST_PLACES_SIDECAR: &str = include_str!("fixtures/digikam_tagslist_places.xmp");
+
+#[test]
+fn tags_list_stands_in_for_hierarchical_subject() {
+    let dir = TempDir::new("tagslist");
+    let media_dir = dir.path().join("DEP01");
+    fs::create_dir_all(&media_dir).unwrap();
+    fs::write(media_dir.join("IMG_0001.JPG.xmp"), TAGSLIST_SIDECAR).unwrap();
+
+    let output_dir = dir.path().join("observe");
+    observe(
+        &media_dir,
+        &output_dir,
+        ObserveSettings {
+            extra_tags: vec!["Behaviour".to_string()],
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let tags = find_output(&output_dir, "tags_");
+    // One row per species like hierarchicalSubject, the other tags repeated on each
+    assert_eq!(csv_column(&tags, "species"), ["Serval", "Civet"]);
+    assert_eq!(csv_column(&tags, "individual"), ["SV03", "SV03"]);
+    assert_eq!(csv_column(&tags, "count"), ["2", "2"]);
+    assert_eq!(csv_column(&tags, "behaviour"), ["Grazing", "Grazing"]);
+}
+
+// A hierarchicalSubject with only keywords of other tools doesn't hide the TagsList
+#[test]
+fn tags_list_read_when_hierarchical_subject_has_no_serval_tags() {
+    let dir = TempDir::new("tagslist_places");
+    let media_dir = dir.path().join("DEP01");
+    fs::create_dir_all(&media_dir).unwrap();
+    fs::write(media_dir.join("IMG_0001.JPG.xmp"), TAGSLIST_PLACES_SIDECAR).unwrap();
+
+    let output_dir = dir.path().join("observe");
+    observe(&media_dir, &output_dir, ObserveSettings::default()).unwrap();
+    let tags = find_output(&output_dir, "tags_");
+    assert_eq!(csv_column(&tags, "species"), ["Serval"]);
+}
@@ -0,0 +1,122 @@
+// observe --timezone strips, keeps or converts the UTC offsets of XMP datetimes
+use crate::common::{
+    TempDir, capture, csv_column, deploy_path_index_of, find_output, observe, sidecar,
+};
+use serval::tags::{CaptureSettings, ObserveSettings};
+use serval::utils::{DatetimeTimezone, TagType, parse_timezone_arg};
+use std::fs;
+use std::path::Path;
+
+// Three shots 10 minutes apart, written by cameras set to different zones
+const FILES: &[(&str, &str)] = &[
+    ("IMG_0001.JPG.xmp", "2023-07-01T14:00:00-08:00"),
+    ("IMG_0002.JPG.xmp", "2023-07-01T22:10:00Z"),
+    ("IMG_0003.JPG.xmp", "2023-07-02T06:20:00+08:00"),
+];
+
+fn observe_datetimes(
+    media_dir: &Path,
+    output_dir: &Path,
+    timezone: DatetimeTimezone,
+) -> Vec<String> {
+    observe(
+        media_dir,
+        output_dir,
+        ObserveSettings {
+            timezone,
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    csv_column(&find_output(output_dir, "tags_"), "datetime")
+}
+
+// Independent Serval records of a tags CSV, with a 30 minute window
+fn independent_records(tags: &Path, output_dir: &Path, deploy_path_index: i32) -> usize {
+    capture(
+        tags,
+        output_dir,
+        Vec::new(),
+        CaptureSettings {
+            min_delta_time: 30,
+            compare_to_last_record: false,
+            target: TagType::Species,
+            deploy_path_index: Some(deploy_path_index),
+        },
+    )
+    .unwrap();
+    csv_column(
+        &output_dir.join("temporal-independence_species_30m_LIR.csv"),
+        "time",
+    )
+    .len()
+}
+
+#[test]
+fn offsets_are_stripped_kept_or_converted() {
+    let dir = TempDir::new("timezone");
+    let media_dir = dir.path().join("project/DEP01");
+    fs::create_dir_all(&media_dir).unwrap();
+    for (file_name, datetime) in FILES {
+        fs::write(
+            media_dir.join(file_name),
+            sidecar(datetime, &["Species|Serval"]),
+        )
+        .unwrap();
+    }
+    let deploy_path_index = deploy_path_index_of(&media_dir.join(FILES[0].0));
+
+    // The time shown by each camera, negative offsets included
+    let strip = dir.path().join("strip");
+    assert_eq!(
+        observe_datetimes(&media_dir, &strip, DatetimeTimezone::Strip),
+        [
+            "2023-07-01 14:00:00",
+            "2023-07-01 22:10:00",
+            "2023-07-02 06:20:00"
+        ]
+    );
+    assert_eq!(
+        independent_records(
+            &find_output(&strip, "tags_"),
+            &dir.path().join("strip_capture"),
+            deploy_path_index
+        ),
+        3
+    );
+
+    let shanghai = dir.path().join("shanghai");
+    assert_eq!(
+        observe_datetimes(
+            &media_dir,
+            &shanghai,
+            parse_timezone_arg("Asia/Shanghai").unwrap()
+        ),
+        [
+            "2023-07-02 06:00:00",
+            "2023-07-02 06:10:00",
+            "2023-07-02 06:20:00"
+        ]
+    );
+
+    // Kept offsets make instants, which capture compares as such
+    let keep = dir.path().join("keep");
+    assert_eq!(
+        observe_datetimes(&media_dir, &keep, DatetimeTimezone::Keep),
+        [
+            "2023-07-01 22:00:00+00:00",
+            "2023-07-01 22:10:00+00:00",
+            "2023-07-01 22:20:00+00:00"
+        ]
+    );
+    assert_eq!(
+        independent_records(
+            &find_output(&keep, "tags_"),
+            &dir.path().join("keep_capture"),
+            deploy_path_index
+        ),
+        1
+    );
+
+    assert!(parse_timezone_arg("Mars/Olympus").is_err());
+}
@@ -0,0 +1,84 @@
+// translate --add-columns keeps the species, capture --carry-columns takes the columns along
+use crate::common::{TempDir, csv_column};
+use serval::tags::{CaptureOptions, CaptureSettings, get_temporal_independence};
+use serval::utils::{ColumnMap, OnConflict, TagType, tags_csv_translate};
+use std::fs;
+
+#[test]
+fn added_columns_reach_the_capture_counts() {
+    let dir = TempDir::new("translate");
+    let tags_csv = dir.path().join("tags.csv");
+    fs::write(
+        &tags_csv,
+        "path,datetime,species,deployment\n\
+         /p/DEP01/1.JPG,2024-01-01 00:00:00,Serval,DEP01\n\
+         /p/DEP01/2.JPG,2024-01-01 06:00:00,Civet,DEP01\n\
+         /p/DEP02/3.JPG,2024-01-02 00:00:00,Serval,DEP02\n",
+    )
+    .unwrap();
+    let taglist = dir.path().join("taglist.csv");
+    fs::write(
+        &taglist,
+        "common_en,scientific,common_zh\nServal,Leptailurus serval,薮猫\nCivet,Viverridae,\n",
+    )
+    .unwrap();
+
+    let translate_dir = dir.path().join("translate");
+    tags_csv_translate(
+        tags_csv,
+        taglist,
+        translate_dir.clone(),
+        "common_en",
+        None,
+        &["scientific".to_string(), "common_zh".to_string()],
+        OnConflict::Fail,
+        ColumnMap::default(),
+    )
+    .unwrap();
+    let translated = translate_dir.join("tags_translated.csv");
+    assert_eq!(
+        csv_column(&translated, "species"),
+        ["Serval", "Civet", "Serval"]
+    );
+    assert_eq!(csv_column(&translated, "common_zh"), ["薮猫", "", "薮猫"]);
+
+    let capture_dir = dir.path().join("capture");
+    get_temporal_independence(
+        translated,
+        capture_dir.clone(),
+        CaptureOptions {
+            no_exclude: true,
+            carry_columns: vec!["scientific".to_string()],
+            on_conflict: OnConflict::Fail,
+            settings: Some(CaptureSettings {
+                min_delta_time: 30,
+                compare_to_last_record: false,
+                target: TagType::Species,
+                deploy_path_index: None,
+            }),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    let count_all = capture_dir.join("count_all.csv");
+    assert_eq!(
+        csv_column(&count_all, "species"),
+        ["Serval", "Civet", "(untagged)", "TOTAL"]
+    );
+    assert_eq!(
+        csv_column(&count_all, "scientific"),
+        ["Leptailurus serval", "Viverridae", "", ""]
+    );
+    let count_by_deployment = capture_dir.join("count_by_deployment.csv");
+    for (species, scientific) in csv_column(&count_by_deployment, "species")
+        .iter()
+        .zip(csv_column(&count_by_deployment, "scientific"))
+    {
+        let expected = if species == "Serval" {
+            "Leptailurus serval"
+        } else {
+            "Viverridae"
+        };
+        assert_eq!(scientific, expected);
+    }
+}
@@ -0,0 +1,78 @@
+// extract --verify-sample draws a reproducible stratified sample that reconcile can apply
+use crate::common::{Project, RECORDS, csv_column, list_files};
+use serval::reconcile::reconcile_tags;
+use serval::utils::{ColumnMap, XmpUpdateType};
+use serval::verify::extract_verify_sample;
+use std::fs;
+
+#[test]
+fn stratified_sample_round_trips_through_reconcile() {
+    let project = Project::create();
+    let tags_csv =
+        project.write_update_csv("tags.csv", "species", |record| record.species.to_string());
+    let spec = project.dir.path().join("verify.toml");
+    fs::write(
+        &spec,
+        "strata = [\"species\"]\nsample_size = 1\nseed = 7\n[sizes]\nServal = 2\nLeopard = 3\n",
+    )
+    .unwrap();
+
+    let sample_dir = project.output_dir("verify");
+    extract_verify_sample(
+        tags_csv.clone(),
+        spec.clone(),
+        sample_dir.clone(),
+        ColumnMap::default(),
+    )
+    .unwrap();
+    let sheet = sample_dir.join("verify_sheet.csv");
+    let mut strata = csv_column(&sheet, "stratum");
+    strata.sort();
+    // Leopard has a single record, fewer than the 3 requested, and is taken in full
+    assert_eq!(strata, ["Blank", "Leopard", "Serval", "Serval"]);
+    let files = list_files(&sample_dir);
+    assert!(
+        files.contains(&"Leopard/DEP01/IMG_0003.JPG".to_string()),
+        "{files:?}"
+    );
+    assert!(
+        files.contains(&"Blank/DEP02/IMG_0002.JPG".to_string()),
+        "{files:?}"
+    );
+
+    // The same seed draws the same records
+    let again_dir = project.output_dir("verify_again");
+    extract_verify_sample(
+        tags_csv.clone(),
+        spec,
+        again_dir.clone(),
+        ColumnMap::default(),
+    )
+    .unwrap();
+    assert_eq!(
+        csv_column(&again_dir.join("verify_sheet.csv"), "original_path"),
+        csv_column(&sheet, "original_path")
+    );
+
+    // The reviewer corrects the Leopard, reconcile maps the sheet back to the original sidecar
+    let reviewed = fs::read_to_string(&sheet)
+        .unwrap()
+        .replace(",Leopard,Leopard,Leopard,", ",Leopard,Leopard,Leopard cat,");
+    fs::write(&sheet, reviewed).unwrap();
+    let reconcile_dir = project.output_dir("reconcile");
+    reconcile_tags(
+        tags_csv,
+        sheet,
+        sample_dir.join("manifest.csv"),
+        reconcile_dir.clone(),
+        XmpUpdateType::Species,
+        ColumnMap::default(),
+    )
+    .unwrap();
+    let updates = reconcile_dir.join("reconcile_updates.csv");
+    assert_eq!(
+        csv_column(&updates, "path"),
+        [project.sidecar_path(&RECORDS[2]).display().to_string()]
+    );
+    assert_eq!(csv_column(&updates, "xmp_update"), ["Leopard cat"]);
+}
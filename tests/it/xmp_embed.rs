@@ -0,0 +1,49 @@
+// xmp embed writes sidecars into the images, removing only the sidecars that verified
+use crate::common::{Project, RECORDS, csv_column, find_output};
+use serval::tags::{init_xmp, update_tags};
+use serval::utils::{ColumnMap, XmpUpdateType, embed_xmp_directory};
+
+#[test]
+fn verified_images_lose_their_sidecars() {
+    let project = Project::create();
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let csv = project.write_update_csv("species_update.csv", "species,xmp_update", |record| {
+        format!(",{}", record.species)
+    });
+    update_tags(
+        csv,
+        XmpUpdateType::Species,
+        None,
+        false,
+        ColumnMap::default(),
+    )
+    .unwrap();
+
+    assert!(embed_xmp_directory(project.root(), true, false, true).is_err());
+    embed_xmp_directory(project.root(), true, true, true).unwrap();
+
+    let report = find_output(&project.root(), "xmp_embed_");
+    let mut statuses: Vec<(String, String, String)> = csv_column(&report, "path")
+        .into_iter()
+        .zip(csv_column(&report, "status"))
+        .zip(csv_column(&report, "sidecar_removed"))
+        .map(|((path, status), removed)| (path, status, removed))
+        .collect();
+    statuses.sort();
+    assert_eq!(statuses.len(), RECORDS.len());
+    for record in RECORDS {
+        let sidecar = project.sidecar_path(record);
+        let (_, status, removed) = statuses
+            .iter()
+            .find(|(path, _, _)| *path == sidecar.to_string_lossy())
+            .unwrap();
+        // The video is left alone, sidecar included
+        if record.file_name.ends_with(".MP4") {
+            assert_eq!((status.as_str(), removed.as_str()), ("skipped", "false"));
+            assert!(sidecar.exists());
+        } else {
+            assert_eq!((status.as_str(), removed.as_str()), ("verified", "true"));
+            assert!(!sidecar.exists());
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// xmp update --verify-roundtrip keeps properties of namespaces serval doesn't know, and puts
+// the backup back when one of them goes missing in the written sidecar
+use crate::common::{Project, RECORDS};
+use serval::tags::{init_xmp, update_tags, verify_xmp_roundtrip};
+use serval::utils::{ColumnMap, XmpUpdateType};
+use std::fs;
+use std::str::FromStr;
+use xmp_toolkit::{ToStringOptions, XmpMeta};
+
+const UNKNOWN_NAMESPACES: &str = include_str!("fixtures/unknown_namespaces.xmp");
+const SURVEY_NS: &str = "http://example.org/ns/survey/1.0/";
+const STATION_NS: &str = "http://example.org/ns/station/2.1/";
+
+fn survey_properties(xmp: &XmpMeta) -> (String, Vec<String>, String, String) {
+    (
+        xmp.property(SURVEY_NS, "BatchId").unwrap().value,
+        xmp.property_array(SURVEY_NS, "Observers")
+            .map(|item| item.value)
+            .collect(),
+        xmp.localized_text(SURVEY_NS, "Remarks", None, "x-default")
+            .unwrap()
+            .0
+            .value,
+        xmp.struct_field(STATION_NS, "Mount", STATION_NS, "Bearing")
+            .unwrap()
+            .value,
+    )
+}
+
+#[test]
+fn unknown_namespaces_survive_an_update() {
+    let project = Project::create();
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let sidecar = project.sidecar_path(&RECORDS[0]);
+    fs::write(&sidecar, UNKNOWN_NAMESPACES).unwrap();
+    let csv = project.write_update_csv("species_update.csv", "species,xmp_update", |record| {
+        format!(",{}", record.species)
+    });
+    update_tags(
+        csv,
+        XmpUpdateType::Species,
+        None,
+        true,
+        ColumnMap::default(),
+    )
+    .unwrap();
+
+    let written = fs::read_to_string(&sidecar).unwrap();
+    assert!(written.contains(RECORDS[0].species), "{written}");
+    assert_eq!(
+        survey_properties(&XmpMeta::from_str(&written).unwrap()),
+        survey_properties(&XmpMeta::from_str(UNKNOWN_NAMESPACES).unwrap())
+    );
+}
+
+#[test]
+fn dropped_property_restores_the_backup() {
+    let project = Project::create();
+    let sidecar = project.sidecar_path(&RECORDS[0]);
+    let backup = sidecar.with_extension("xmp.backup");
+    fs::write(&backup, UNKNOWN_NAMESPACES).unwrap();
+    let original = XmpMeta::from_str(UNKNOWN_NAMESPACES).unwrap();
+
+    // Written without the batch id the update never meant to touch
+    let mut written = original.clone();
+    written.delete_property(SURVEY_NS, "BatchId").unwrap();
+    fs::write(
+        &sidecar,
+        written
+            .to_string_with_options(ToStringOptions::default())
+            .unwrap(),
+    )
+    .unwrap();
+    let differences = verify_xmp_roundtrip(&sidecar, &backup, &original, &original).unwrap();
+    assert_eq!(differences.len(), 1, "{differences:?}");
+    assert!(
+        differences[0].contains("BatchId") && differences[0].ends_with("dropped"),
+        "{differences:?}"
+    );
+    assert_eq!(fs::read_to_string(&sidecar).unwrap(), UNKNOWN_NAMESPACES);
+
+    // Nothing missing once restored, so the file is kept
+    fs::write(&backup, "").unwrap();
+    let differences = verify_xmp_roundtrip(&sidecar, &backup, &original, &original).unwrap();
+    assert!(differences.is_empty(), "{differences:?}");
+    assert_eq!(fs::read_to_string(&sidecar).unwrap(), UNKNOWN_NAMESPACES);
+}
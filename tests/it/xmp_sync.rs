@@ -0,0 +1,53 @@
+// xmp sync --check compares sidecars with the XMP embedded in their media, writing the diff
+// to the output directory and datetimes compared parsed
+use crate::common::{Project, RECORDS, csv_column, find_output};
+use serval::tags::{init_xmp, update_datetime};
+use serval::utils::{ColumnMap, embed_xmp_directory, sync_xmp_directory};
+use std::fs;
+
+#[test]
+fn check_reports_only_real_differences() {
+    let project = Project::create();
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let csv = project.write_update_csv("datetime_update.csv", "xmp_update_datetime", |record| {
+        record.datetime.to_string()
+    });
+    update_datetime(csv, None, false, ColumnMap::default()).unwrap();
+    embed_xmp_directory(project.root(), true, false, false).unwrap();
+
+    // The same instant written with fractions, and a time that really moved
+    let rewrite = |index: usize, from: &str, to: &str| {
+        let sidecar = project.sidecar_path(&RECORDS[index]);
+        let xmp = fs::read_to_string(&sidecar).unwrap();
+        assert!(xmp.contains(from), "{xmp}");
+        fs::write(&sidecar, xmp.replace(from, to)).unwrap();
+    };
+    rewrite(0, "2024-03-01T10:00:00", "2024-03-01T10:00:00.000");
+    rewrite(1, "2024-03-01T10:05:00", "2024-03-01T10:06:00");
+
+    let output = project.output_dir("sync_check");
+    sync_xmp_directory(project.root(), Some(&output)).unwrap();
+    let report = find_output(&output, "xmp_sync_check_");
+    assert!(fs::read_dir(project.root()).unwrap().all(|entry| {
+        !entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with("xmp_sync_check_")
+    }));
+    let diffs: Vec<(String, String)> = csv_column(&report, "path")
+        .into_iter()
+        .zip(csv_column(&report, "field"))
+        .filter(|(path, _)| path.ends_with(".JPG.xmp"))
+        .collect();
+    assert_eq!(
+        diffs,
+        [(
+            project
+                .sidecar_path(&RECORDS[1])
+                .to_string_lossy()
+                .into_owned(),
+            "DateTimeOriginal".to_string()
+        )]
+    );
+}
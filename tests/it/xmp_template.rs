@@ -0,0 +1,90 @@
+// xmp init --template adds project boilerplate to every new sidecar, placeholders filled per file
+use crate::common::{Project, RECORDS, deploy_path_index_of};
+use serval::tags::init_xmp;
+use std::fs;
+use std::str::FromStr;
+use xmp_toolkit::{XmpMeta, xmp_ns};
+
+const TEMPLATE: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:photoshop="http://ns.adobe.com/photoshop/1.0/"
+    photoshop:Credit="Survey team"
+    photoshop:Source="{deployment}/{filename}"
+    photoshop:Instructions="Taken {datetime}">
+   <dc:rights><rdf:Alt><rdf:li xml:lang="x-default">Wildlife Institute</rdf:li></rdf:Alt></dc:rights>
+   <dc:creator><rdf:Seq><rdf:li>Serval project</rdf:li></rdf:Seq></dc:creator>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+#[test]
+fn template_properties_reach_each_sidecar() {
+    let project = Project::create();
+    let template = project.dir.path().join("template.xmp");
+    let deploy_level = Some(deploy_path_index_of(&project.media_path(&RECORDS[0])));
+    // Below its deployment, the deployment is still the one of the level asked for
+    let nested = project
+        .media_path(&RECORDS[0])
+        .with_file_name("100MEDIA")
+        .join("IMG_0100.JPG");
+    fs::create_dir_all(nested.parent().unwrap()).unwrap();
+    fs::copy(project.media_path(&RECORDS[0]), &nested).unwrap();
+
+    // A template that doesn't parse stops the run before any sidecar is written
+    for invalid in ["<x:xmpmeta><rdf:RDF>", "Copyright {deployment}"] {
+        fs::write(&template, invalid).unwrap();
+        let err = init_xmp(
+            project.root(),
+            false,
+            None,
+            Some(template.clone()),
+            deploy_level,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("not valid XMP"), "{err}");
+    }
+    assert!(
+        RECORDS
+            .iter()
+            .all(|record| !project.sidecar_path(record).exists())
+    );
+
+    fs::write(&template, TEMPLATE).unwrap();
+    init_xmp(project.root(), false, None, Some(template), deploy_level).unwrap();
+    for record in RECORDS {
+        let sidecar = fs::read_to_string(project.sidecar_path(record)).unwrap();
+        let xmp = XmpMeta::from_str(&sidecar).unwrap();
+        let property = |name: &str| xmp.property(xmp_ns::PHOTOSHOP, name).unwrap().value;
+        assert_eq!(property("Credit"), "Survey team");
+        assert_eq!(
+            property("Source"),
+            format!("{}/{}", record.deployment, record.file_name)
+        );
+        let datetime = xmp
+            .property(xmp_ns::EXIF, "DateTimeOriginal")
+            .unwrap()
+            .value;
+        assert_eq!(property("Instructions"), format!("Taken {datetime}"));
+        assert_eq!(
+            xmp.localized_text(xmp_ns::DC, "rights", None, "x-default")
+                .unwrap()
+                .0
+                .value,
+            "Wildlife Institute"
+        );
+        let creators: Vec<String> = xmp
+            .property_array(xmp_ns::DC, "creator")
+            .map(|item| item.value)
+            .collect();
+        assert_eq!(creators, ["Serval project"]);
+    }
+
+    let nested_sidecar = fs::read_to_string(nested.with_file_name("IMG_0100.JPG.xmp")).unwrap();
+    let xmp = XmpMeta::from_str(&nested_sidecar).unwrap();
+    assert_eq!(
+        xmp.property(xmp_ns::PHOTOSHOP, "Source").unwrap().value,
+        format!("{}/IMG_0100.JPG", RECORDS[0].deployment)
+    );
+}
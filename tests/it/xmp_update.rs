@@ -0,0 +1,111 @@
+// xmp update input checks that must reject the whole CSV before any sidecar is written
+use crate::common::{Project, RECORDS, csv_column, find_output, observe};
+use serval::tags::{ObserveSettings, init_xmp, update_tags};
+use serval::utils::{ColumnMap, XmpUpdateType};
+use std::fs;
+
+// Sidecars written by a Windows photo tool, as UTF-8 with a BOM, as UTF-16LE with a BOM and
+// in the ANSI code page (Windows-1252), which isn't UTF-8
+const SIDECAR_UTF8_BOM: &[u8] = include_bytes!("fixtures/sidecar_utf8_bom.xmp");
+const SIDECAR_UTF16LE: &[u8] = include_bytes!("fixtures/sidecar_utf16le.xmp");
+const SIDECAR_CP1252: &[u8] = include_bytes!("fixtures/sidecar_cp1252.xmp");
+
+#[test]
+fn invalid_rating_updates_are_rejected() {
+    let project = Project::create();
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let before = fs::read_to_string(project.sidecar_path(&RECORDS[0])).unwrap();
+    let csv = project.write_update_csv(
+        "rating_update.csv",
+        "species,xmp_update,rating_update",
+        |record| match record.file_name {
+            "IMG_0002.JPG" => ",,6".to_string(),
+            "IMG_0003.JPG" => ",,2.5".to_string(),
+            _ => format!(",{},3", record.species),
+        },
+    );
+    let error = update_tags(
+        csv,
+        XmpUpdateType::Species,
+        None,
+        true,
+        ColumnMap::default(),
+    )
+    .unwrap_err()
+    .to_string();
+    // Both IMG_0002 (lines 3 and 7) and IMG_0003, the valid line 2 isn't reported
+    assert!(error.contains("line 3: '6'"), "{error}");
+    assert!(error.contains("line 4: '2.5'"), "{error}");
+    assert!(error.contains("line 7: '6'"), "{error}");
+    assert!(!error.contains("line 2"), "{error}");
+    assert_eq!(
+        fs::read_to_string(project.sidecar_path(&RECORDS[0])).unwrap(),
+        before
+    );
+}
+
+// Sidecars written with a BOM or as UTF-16 by Windows tools are read and written back as
+// UTF-8 without BOM, an ANSI one that doesn't decode is reported and the rest still updated
+#[test]
+fn bom_and_utf16_sidecars_are_updated() {
+    let project = Project::create();
+    init_xmp(project.root(), false, None, None, None).unwrap();
+    let fixtures = [
+        (&RECORDS[0], SIDECAR_UTF8_BOM),
+        (&RECORDS[1], SIDECAR_UTF16LE),
+        (&RECORDS[4], SIDECAR_CP1252),
+    ];
+    for (record, bytes) in fixtures {
+        fs::write(project.sidecar_path(record), bytes).unwrap();
+    }
+
+    let observe_dir = project.output_dir("observe");
+    observe(&project.root(), &observe_dir, ObserveSettings::default()).unwrap();
+    let tags = find_output(&observe_dir, "tags_");
+    let paths = csv_column(&tags, "path");
+    let datetimes = csv_column(&tags, "datetime");
+    for record in &RECORDS[..2] {
+        let sidecar = project.sidecar_path(record).to_string_lossy().into_owned();
+        let row = paths.iter().position(|path| *path == sidecar).unwrap();
+        assert_eq!(datetimes[row], "2024-03-01 08:15:00");
+    }
+    let errors = fs::read_to_string(find_output(&observe_dir, "errors")).unwrap();
+    assert!(errors.contains("DEP02/IMG_0001.JPG.xmp"), "{errors}");
+    assert!(errors.contains("invalid UTF-8"), "{errors}");
+
+    let csv = project.write_update_csv("species_update.csv", "species,xmp_update", |record| {
+        format!(",{}", record.species)
+    });
+    update_tags(
+        csv,
+        XmpUpdateType::Species,
+        None,
+        true,
+        ColumnMap::default(),
+    )
+    .unwrap();
+
+    for record in &RECORDS[..2] {
+        let bytes = fs::read(project.sidecar_path(record)).unwrap();
+        let content = String::from_utf8(bytes).unwrap();
+        assert!(!content.starts_with('\u{feff}'), "{content}");
+        assert!(content.contains(record.species), "{content}");
+        assert!(content.contains("Grünfläche am Wasserloch"), "{content}");
+    }
+    assert_eq!(
+        fs::read(project.sidecar_path(&RECORDS[4])).unwrap(),
+        SIDECAR_CP1252
+    );
+    let after = fs::read_to_string(project.sidecar_path(&RECORDS[5])).unwrap();
+    assert!(after.contains(RECORDS[5].species), "{after}");
+    let errors = project
+        .root()
+        .parent()
+        .unwrap()
+        .join("errors_species_update.csv");
+    assert_eq!(
+        csv_column(&errors, "path"),
+        [project.sidecar_path(&RECORDS[4]).to_string_lossy()]
+    );
+    assert!(csv_column(&errors, "error")[0].contains("invalid UTF-8"));
+}